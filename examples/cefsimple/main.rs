@@ -107,7 +107,7 @@ fn main() {
         &browser_settings,
         None,
         None,
-    );
+    ).expect("failed to create browser");
 
     info!("Running message loop");
 
@@ -410,7 +410,7 @@ fn main() {
                     &browser_settings,
                     None,
                     None,
-                );
+                ).expect("failed to create browser");
                 browser_sender.send(browser).unwrap();
             });
 
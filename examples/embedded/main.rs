@@ -425,7 +425,7 @@ fn main() {
                 &browser_settings,
                 None,
                 None,
-            );
+            ).expect("failed to create browser");
 
             println!("initialize done");
 
@@ -0,0 +1,218 @@
+//! One-shot snapshots of the accessibility tree, see
+//! [Browser::accessibility_snapshot].
+//!
+//! [AccessibilityHandler](crate::accessibility_handler::AccessibilityHandler)
+//! streams tree updates of windowless browsers as they happen. A snapshot
+//! instead asks the renderer for the complete tree once through the DevTools
+//! method `Accessibility.getFullAXTree`, which suits audits and tests:
+//!
+//! ```ignore
+//! let tree = block_on(browser.accessibility_snapshot())?;
+//! assert!(tree.find_by_role("button").iter().any(|button| button.name == "Submit"));
+//! ```
+
+use crate::{
+    browser::Browser,
+    devtools::{DevToolsError, DevToolsSession},
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    fmt::Write,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+/// A node of an [AccessibilityTree].
+#[derive(Debug, Clone)]
+pub struct AXNode {
+    /// The DevTools identifier of the node, unique within the snapshot.
+    pub node_id: String,
+    pub parent_id: Option<String>,
+    pub child_ids: Vec<String>,
+    /// The ARIA role, e.g. `button` or `heading`.
+    pub role: String,
+    /// The accessible name.
+    pub name: String,
+    pub value: Option<String>,
+    pub description: Option<String>,
+    /// Set for nodes that aren't exposed to assistive technology.
+    pub ignored: bool,
+    /// Other properties such as `focusable`, `checked` or `level`.
+    pub properties: Vec<(String, StoredValue)>,
+    /// The DOM node the accessibility node belongs to, if any.
+    pub backend_dom_node_id: Option<i32>,
+}
+
+impl AXNode {
+    fn from_dictionary(node: &DictionaryValue) -> Option<AXNode> {
+        let node_id = match node.get("nodeId") {
+            StoredValue::String(id) => id,
+            _ => return None,
+        };
+        let child_ids = node.try_get_list("childIds")
+            .map(|ids| (0..ids.len()).filter_map(|i| ids.get_string(i)).collect())
+            .unwrap_or_default();
+        let properties = node.try_get_list("properties")
+            .map(|properties| {
+                (0..properties.len())
+                    .filter_map(|i| properties.get_dictionary(i))
+                    .map(|property| {
+                        let value = property.try_get_dictionary("value").map(|value| value.get("value")).unwrap_or(StoredValue::Null);
+                        (property.get_string("name"), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Some(AXNode {
+            node_id,
+            parent_id: match node.get("parentId") {
+                StoredValue::String(id) => Some(id),
+                _ => None,
+            },
+            child_ids,
+            role: ax_value(node, "role").unwrap_or_default(),
+            name: ax_value(node, "name").unwrap_or_default(),
+            value: ax_value(node, "value"),
+            description: ax_value(node, "description"),
+            ignored: node.get_bool("ignored"),
+            properties,
+            backend_dom_node_id: match node.get("backendDOMNodeId") {
+                StoredValue::Int(id) => Some(id),
+                _ => None,
+            },
+        })
+    }
+    /// Returns the property `name`, e.g. `checked`.
+    pub fn property(&self, name: &str) -> Option<&StoredValue> {
+        self.properties.iter().find(|(property, _)| property == name).map(|(_, value)| value)
+    }
+}
+
+/// Returns the `value` of the AXValue `key` of `node` as a string.
+fn ax_value(node: &DictionaryValue, key: &str) -> Option<String> {
+    match node.try_get_dictionary(key)?.get("value") {
+        StoredValue::String(value) => Some(value),
+        StoredValue::Int(value) => Some(value.to_string()),
+        StoredValue::Double(value) => Some(value.to_string()),
+        StoredValue::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// The accessibility tree of a browser's main frame.
+#[derive(Debug, Clone, Default)]
+pub struct AccessibilityTree {
+    /// All nodes, in document order.
+    pub nodes: Vec<AXNode>,
+}
+
+impl AccessibilityTree {
+    /// Returns the root node, normally with the role `RootWebArea`.
+    pub fn root(&self) -> Option<&AXNode> {
+        self.nodes.iter().find(|node| node.parent_id.is_none())
+    }
+    pub fn get(&self, node_id: &str) -> Option<&AXNode> {
+        self.nodes.iter().find(|node| node.node_id == node_id)
+    }
+    pub fn children<'a>(&'a self, node: &'a AXNode) -> impl 'a + Iterator<Item = &'a AXNode> {
+        node.child_ids.iter().filter_map(move |id| self.get(id))
+    }
+    /// Returns the nodes that aren't ignored and have the role `role`.
+    pub fn find_by_role(&self, role: &str) -> Vec<&AXNode> {
+        self.nodes.iter().filter(|node| !node.ignored && node.role == role).collect()
+    }
+    /// Returns the nodes that aren't ignored and have the accessible name
+    /// `name`.
+    pub fn find_by_name(&self, name: &str) -> Vec<&AXNode> {
+        self.nodes.iter().filter(|node| !node.ignored && node.name == name).collect()
+    }
+    /// Renders the tree without ignored nodes as an indented outline of roles
+    /// and names, e.g. `button "Submit"`, for comparing in tests.
+    pub fn to_outline(&self) -> String {
+        let mut outline = String::new();
+        if let Some(root) = self.root() {
+            self.write_outline(root, 0, &mut outline);
+        }
+        outline
+    }
+
+    fn write_outline(&self, node: &AXNode, depth: usize, outline: &mut String) {
+        let depth = if node.ignored {
+            depth
+        } else {
+            let _ = write!(outline, "{:indent$}{}", "", node.role, indent = depth * 2);
+            if !node.name.is_empty() {
+                let _ = write!(outline, " {:?}", node.name);
+            }
+            outline.push('\n');
+            depth + 1
+        };
+        for child in self.children(node) {
+            self.write_outline(child, depth, outline);
+        }
+    }
+}
+
+pub type AccessibilitySnapshotResult = Result<AccessibilityTree, DevToolsError>;
+
+#[derive(Default)]
+struct SnapshotState {
+    result: Option<AccessibilitySnapshotResult>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the accessibility tree of a browser. The snapshot is taken
+/// whether or not the future is polled.
+pub struct AccessibilitySnapshot(Arc<Mutex<SnapshotState>>);
+
+impl Future for AccessibilitySnapshot {
+    type Output = AccessibilitySnapshotResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<AccessibilitySnapshotResult> {
+        let mut state = self.0.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+pub(crate) fn accessibility_snapshot(browser: &Browser) -> AccessibilitySnapshot {
+    let state = Arc::new(Mutex::new(SnapshotState::default()));
+    let session = DevToolsSession::new(&browser.get_host());
+    let result_state = state.clone();
+    // The callback holds the session, which keeps its observer registered
+    // until the result arrived.
+    let keep_alive = session.clone();
+    session.execute("Accessibility.getFullAXTree", None, move |result| {
+        drop(keep_alive);
+        let result = result.and_then(|result| {
+            let nodes = match &result {
+                StoredValue::Dictionary(result) => result.try_get_list("nodes"),
+                _ => None,
+            };
+            let nodes = nodes.ok_or_else(|| DevToolsError { code: -1, message: "unexpected result".to_owned() })?;
+            Ok(AccessibilityTree {
+                nodes: (0..nodes.len())
+                    .filter_map(|i| nodes.get_dictionary(i))
+                    .filter_map(|node| AXNode::from_dictionary(&node))
+                    .collect(),
+            })
+        });
+        let waker = {
+            let mut state = result_state.lock();
+            state.result = Some(result);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+    AccessibilitySnapshot(state)
+}
@@ -0,0 +1,282 @@
+//! The model behind an address bar.
+//!
+//! An [AddressBar] holds what the address field shows: the URL of the main
+//! frame while the user isn't typing, the typed text while they are, and
+//! whether the page is loading. Enter turns the text into a URL, or a search
+//! if it doesn't look like one, and navigates:
+//!
+//! ```ignore
+//! let address_bar = AddressBar::with_search_url("https://duckduckgo.com/?q=%s");
+//! address_bar.on_change(|state| reload_button.set_enabled(!state.loading));
+//! // in DisplayHandlerCallbacks::on_address_change:
+//! address_bar.on_address_change(&frame, url);
+//! // in LoadHandlerCallbacks::on_loading_state_change:
+//! address_bar.on_loading_state_change(is_loading, can_go_back, can_go_forward);
+//! ```
+//!
+//! [AddressBar::create_textfield] returns a views [Textfield] that is kept in
+//! sync with the state and commits on Enter. With a text field of the
+//! application's own toolkit, feed its changes and keys to the address bar
+//! and show the state from the listener instead:
+//!
+//! ```ignore
+//! address_bar.on_change(|state| ui.set_address(&state.text, state.loading));
+//! // when the text field changes or receives a key:
+//! address_bar.set_text(&text);
+//! address_bar.on_key_event(&browser, &event);
+//! ```
+
+use crate::{
+    browser::Browser,
+    events::{KeyEvent, WindowsKeyCode},
+    frame::Frame,
+    views::{BrowserView, Textfield, TextfieldDelegate, TextfieldDelegateCallbacks},
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The search used for input that isn't a URL, with `%s` for the query.
+const DEFAULT_SEARCH_URL: &str = "https://www.google.com/search?q=%s";
+
+/// Schemes that are loaded as typed. `javascript:` is left out on purpose, so
+/// pasted text can't run script in the page.
+const TYPED_SCHEMES: &[&str] = &["http:", "https:", "file:", "about:", "data:", "view-source:", "chrome:", "devtools:"];
+
+/// What an address bar shows.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AddressBarState {
+    /// The text of the field: the typed text while editing, the URL otherwise.
+    pub text: String,
+    /// The URL of the main frame.
+    pub url: String,
+    /// Set while the user is typing. The URL doesn't replace the text then.
+    pub editing: bool,
+    pub loading: bool,
+    pub can_go_back: bool,
+    pub can_go_forward: bool,
+}
+
+/// Turns the text typed into an address bar into a URL. Text that looks like
+/// a URL or host name is completed with a scheme, anything else becomes a
+/// query for `search_url`, where `%s` is replaced with the query. Returns
+/// `None` for empty input.
+pub fn resolve_input(input: &str, search_url: &str) -> Option<String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    let lowercase = input.to_ascii_lowercase();
+    if TYPED_SCHEMES.iter().any(|scheme| lowercase.starts_with(scheme)) {
+        return Some(input.to_owned());
+    }
+    if !input.contains(char::is_whitespace) {
+        let host_port = input.split(|c| c == '/' || c == '?' || c == '#').next().unwrap_or("");
+        let host = host_port.rsplitn(2, ':').last().unwrap_or("").to_ascii_lowercase();
+        if host == "localhost" || host == "127.0.0.1" || host_port.starts_with("[::1]") {
+            return Some(format!("http://{}", input));
+        }
+        let looks_like_host = host.contains('.')
+            && !host.starts_with('.')
+            && !host.ends_with('.')
+            && host.chars().all(|c| c.is_alphanumeric() || c == '.' || c == '-');
+        if looks_like_host {
+            return Some(format!("https://{}", input));
+        }
+    }
+    Some(search_url.replace("%s", &encode_query(input)))
+}
+
+fn encode_query(query: &str) -> String {
+    let mut encoded = String::with_capacity(query.len());
+    for byte in query.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b' ' => encoded.push('+'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+struct AddressBarInner {
+    state: Mutex<AddressBarState>,
+    search_url: String,
+    listener: Mutex<Option<Arc<dyn 'static + Send + Sync + Fn(&AddressBarState)>>>,
+    /// The views text field showing the text, if one was created.
+    textfield: Mutex<Option<Textfield>>,
+}
+
+/// The address bar of one browser. See the [module documentation](self).
+///
+/// Cloning is cheap; clones share the state.
+#[derive(Clone)]
+pub struct AddressBar(Arc<AddressBarInner>);
+
+impl AddressBar {
+    pub fn new() -> AddressBar {
+        AddressBar::with_search_url(DEFAULT_SEARCH_URL)
+    }
+    /// Creates an address bar that searches with `search_url` for input that
+    /// isn't a URL, with `%s` for the query.
+    pub fn with_search_url(search_url: &str) -> AddressBar {
+        AddressBar(Arc::new(AddressBarInner {
+            state: Mutex::new(AddressBarState::default()),
+            search_url: search_url.to_owned(),
+            listener: Mutex::new(None),
+            textfield: Mutex::new(None),
+        }))
+    }
+    /// Calls `listener` whenever the state changes.
+    pub fn on_change(&self, listener: impl 'static + Send + Sync + Fn(&AddressBarState)) {
+        *self.0.listener.lock() = Some(Arc::new(listener));
+    }
+    /// Creates a views text field for the address bar of the browser of
+    /// `browser_view`. It shows the text of the state, passes what the user
+    /// types to [AddressBar::set_text] and handles keys with
+    /// [AddressBar::on_key_event]. Must be called on the UI thread; the
+    /// address bar keeps the field until another one is created.
+    pub fn create_textfield(&self, browser_view: &BrowserView) -> Textfield {
+        let delegate = TextfieldDelegate::new(AddressBarField {
+            address_bar: self.clone(),
+            browser_view: browser_view.clone(),
+        });
+        let textfield = Textfield::new(Some(delegate));
+        textfield.set_text(&self.0.state.lock().text);
+        *self.0.textfield.lock() = Some(textfield.clone());
+        textfield
+    }
+    pub fn state(&self) -> AddressBarState {
+        self.0.state.lock().clone()
+    }
+
+    /// Call this from
+    /// [DisplayHandlerCallbacks::on_address_change](crate::client::display_handler::DisplayHandlerCallbacks::on_address_change).
+    pub fn on_address_change(&self, frame: &Frame, url: &str) {
+        if !frame.is_main() {
+            return;
+        }
+        self.update(|state| {
+            state.url = url.to_owned();
+            if !state.editing {
+                state.text = url.to_owned();
+            }
+        });
+    }
+    /// Call this from
+    /// [LoadHandlerCallbacks::on_loading_state_change](crate::load_handler::LoadHandlerCallbacks::on_loading_state_change).
+    pub fn on_loading_state_change(&self, is_loading: bool, can_go_back: bool, can_go_forward: bool) {
+        self.update(|state| {
+            state.loading = is_loading;
+            state.can_go_back = can_go_back;
+            state.can_go_forward = can_go_forward;
+        });
+    }
+    /// Sets the text the user typed.
+    pub fn set_text(&self, text: &str) {
+        self.update(|state| {
+            state.editing = true;
+            state.text = text.to_owned();
+        });
+    }
+    /// Navigates the main frame of `browser` to the typed text, if it isn't
+    /// empty, and returns the URL.
+    pub fn commit(&self, browser: &Browser) -> Option<String> {
+        let url = resolve_input(&self.0.state.lock().text, &self.0.search_url);
+        match &url {
+            Some(url) => {
+                browser.get_main_frame().load_url(url);
+                self.update(|state| {
+                    state.editing = false;
+                    state.text = url.clone();
+                });
+            }
+            None => self.revert(),
+        }
+        url
+    }
+    /// Discards the typed text and shows the URL again.
+    pub fn revert(&self) {
+        self.update(|state| {
+            state.editing = false;
+            state.text = state.url.clone();
+        });
+    }
+    /// Handles the keys of the text field: Enter commits, Escape reverts or,
+    /// if nothing was typed, stops loading. Returns true if the key was
+    /// handled.
+    pub fn on_key_event(&self, browser: &Browser, event: &KeyEvent) -> bool {
+        let windows_key_code = match *event {
+            KeyEvent::KeyDown { windows_key_code, .. } => windows_key_code,
+            _ => return false,
+        };
+        if windows_key_code == WindowsKeyCode::Return {
+            self.commit(browser);
+            true
+        } else if windows_key_code == WindowsKeyCode::Escape {
+            let (editing, loading) = {
+                let state = self.0.state.lock();
+                (state.editing, state.loading)
+            };
+            if editing {
+                self.revert();
+            } else if loading {
+                browser.stop_load();
+            }
+            editing || loading
+        } else {
+            false
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut AddressBarState)) {
+        let state = {
+            let mut state = self.0.state.lock();
+            let before = state.clone();
+            f(&mut state);
+            if *state == before {
+                return;
+            }
+            state.clone()
+        };
+        let textfield = self.0.textfield.lock().clone();
+        if let Some(textfield) = textfield {
+            // Typed text is already shown; setting it would move the cursor.
+            if textfield.get_text() != state.text {
+                textfield.set_text(&state.text);
+            }
+        }
+        // The listener may call back into the address bar.
+        let listener = self.0.listener.lock().clone();
+        if let Some(listener) = listener {
+            listener(&state);
+        }
+    }
+}
+
+impl Default for AddressBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The delegate of the text field of [AddressBar::create_textfield].
+struct AddressBarField {
+    address_bar: AddressBar,
+    browser_view: BrowserView,
+}
+
+impl TextfieldDelegateCallbacks for AddressBarField {
+    fn on_key_event(&self, textfield: Textfield, event: KeyEvent) -> bool {
+        match self.browser_view.get_browser() {
+            Some(browser) => self.address_bar.on_key_event(&browser, &event),
+            None => false,
+        }
+    }
+    fn on_after_user_action(&self, textfield: Textfield) {
+        // Also called when only the selection changed.
+        let text = textfield.get_text();
+        if text != self.address_bar.0.state.lock().text {
+            self.address_bar.set_text(&text);
+        }
+    }
+}
@@ -11,6 +11,7 @@ use cef_sys::{
     cef_app_t, cef_render_process_handler_t,
     cef_resource_bundle_handler_t,
 };
+use parking_lot::Mutex;
 use std::{ptr::null_mut};
 
 /// Implement this structure to provide handler implementations. Methods will be
@@ -60,6 +61,22 @@ ref_counted_ptr! {
     pub struct App(*mut cef_app_t);
 }
 
+lazy_static::lazy_static!{
+    /// Switches from [Settings::command_line_switches] that are appended to the
+    /// browser process command line.
+    static ref BROWSER_SWITCHES: Mutex<Vec<(String, Option<String>)>> = Mutex::new(Vec::new());
+}
+
+pub(crate) fn set_browser_switches(switches: Vec<(String, Option<String>)>) {
+    *BROWSER_SWITCHES.lock() = switches;
+}
+
+/// App used by [Context::initialize] when the caller doesn't supply one but
+/// the browser command line still needs to be adjusted.
+pub(crate) struct DefaultAppCallbacks;
+
+impl AppCallbacks for DefaultAppCallbacks {}
+
 struct AppWrapper(Box<dyn AppCallbacks>);
 
 impl Wrapper for AppWrapper {
@@ -100,6 +117,14 @@ cef_callback_impl! {
             process_type: Option<&CefString>: *const cef_sys::cef_string_t,
             command_line: CommandLine: *mut cef_sys::cef_command_line_t,
         ) {
+            if process_type.is_none() {
+                for (name, value) in BROWSER_SWITCHES.lock().iter() {
+                    match value {
+                        Some(value) => command_line.append_switch_with_value(name, value),
+                        None => command_line.append_switch(name),
+                    }
+                }
+            }
             self.0.on_before_command_line_processing(
                 process_type
                     .map(String::from)
@@ -0,0 +1,231 @@
+//! Taskbar badges and progress for app-like pages.
+//!
+//! Installed web apps set a badge on their icon with
+//! `navigator.setAppBadge()`, which CEF 85 doesn't implement, and many pages
+//! that predate the API put counts or progress into their title instead,
+//! like `(3) Inbox` or `[42%] Uploading`. An [AppBadgeBridge] provides the
+//! Badging API through a shim and reads both title patterns, and reports the
+//! result as [AppBadgeEvent]s the application maps to its taskbar or dock:
+//!
+//! ```ignore
+//! let badges = AppBadgeBridge::attach(&browser, |_, event| match event {
+//!     AppBadgeEvent::Badge(Badge::Count(count)) => taskbar.set_overlay_count(*count),
+//!     AppBadgeEvent::Badge(_) => taskbar.clear_overlay(),
+//!     AppBadgeEvent::Progress(progress) => taskbar.set_progress(*progress),
+//! });
+//! // in DisplayHandlerCallbacks::on_title_change:
+//! badges.on_title_change(&browser, title);
+//! ```
+//!
+//! A badge set through the API takes precedence over the title.
+
+use crate::{
+    browser::Browser,
+    devtools::DevToolsSession,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The name of the DevTools binding badge changes are reported through.
+const BINDING_NAME: &str = "__cefAppBadge";
+
+const SHIM_SOURCE: &str = "\
+var notify = window.__cefAppBadge;\n\
+if (typeof notify !== 'function' || navigator.__cefAppBadge) return;\n\
+try { delete window.__cefAppBadge; } catch (e) {}\n\
+Object.defineProperty(navigator, '__cefAppBadge', { value: true });\n\
+navigator.setAppBadge = function(contents) {\n\
+  if (contents === undefined) { notify(JSON.stringify({ flag: true })); return Promise.resolve(); }\n\
+  var count = Number(contents);\n\
+  if (!isFinite(count) || count < 0) return Promise.reject(new TypeError('Badge contents must be a non-negative number'));\n\
+  notify(JSON.stringify({ count: Math.floor(count) }));\n\
+  return Promise.resolve();\n\
+};\n\
+navigator.clearAppBadge = function() { notify(JSON.stringify({ count: 0 })); return Promise.resolve(); };\n\
+notify(JSON.stringify({ reset: true }));";
+
+/// The badge of an app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Badge {
+    None,
+    /// A dot without number, set with `navigator.setAppBadge()`.
+    Flag,
+    Count(u64),
+}
+
+/// A change reported by an [AppBadgeBridge].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AppBadgeEvent {
+    Badge(Badge),
+    /// The progress from 0.0 to 1.0, or `None` when there is none anymore.
+    Progress(Option<f64>),
+}
+
+/// What a title says about the state of the page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TitleHints {
+    /// The count of a leading `(3)` or `[3]`.
+    pub count: Option<u64>,
+    /// The progress of a leading `42%`, `(42%)` or `[42%]`.
+    pub progress: Option<f64>,
+}
+
+/// Reads a leading count or percentage from `title`.
+pub fn parse_title(title: &str) -> TitleHints {
+    let mut hints = TitleHints { count: None, progress: None };
+    let title = title.trim_start();
+    let (inner, bracketed) = match title.chars().next() {
+        Some(open @ '(') | Some(open @ '[') => {
+            let close = if open == '(' { ')' } else { ']' };
+            match title.find(close) {
+                Some(end) => (&title[1..end], true),
+                None => return hints,
+            }
+        }
+        _ => (title.split_whitespace().next().unwrap_or(""), false),
+    };
+    let inner = inner.trim();
+    if inner.ends_with('%') {
+        if let Ok(percent) = inner[..inner.len() - 1].trim().parse::<f64>() {
+            if (0.0..=100.0).contains(&percent) {
+                hints.progress = Some(percent / 100.0);
+            }
+        }
+    } else if bracketed {
+        // Counts like "(99+)" are capped by the page.
+        if let Ok(count) = inner.trim_end_matches('+').parse::<u64>() {
+            hints.count = Some(count);
+        }
+    }
+    hints
+}
+
+struct BadgeState {
+    /// The badge set through the Badging API, if any.
+    api_badge: Option<Badge>,
+    title_count: Option<u64>,
+    badge: Badge,
+    progress: Option<f64>,
+}
+
+type BadgeCallback = dyn 'static + Send + Sync + Fn(&Browser, &AppBadgeEvent);
+
+/// Reports the badge and progress of one browser's main frame. See the
+/// [module documentation](self).
+pub struct AppBadgeBridge {
+    state: Arc<Mutex<BadgeState>>,
+    on_event: Arc<BadgeCallback>,
+    session: DevToolsSession,
+    scripts: UserScripts,
+    script_id: UserScriptId,
+}
+
+impl AppBadgeBridge {
+    /// Installs the Badging API shim into the documents of `browser` and calls
+    /// `on_event` on the UI thread whenever the badge or progress changes.
+    pub fn attach(browser: &Browser, on_event: impl 'static + Send + Sync + Fn(&Browser, &AppBadgeEvent)) -> AppBadgeBridge {
+        let state = Arc::new(Mutex::new(BadgeState {
+            api_badge: None,
+            title_count: None,
+            badge: Badge::None,
+            progress: None,
+        }));
+        let on_event: Arc<BadgeCallback> = Arc::new(on_event);
+        let session = DevToolsSession::new(&browser.get_host());
+        let listener_state = Arc::downgrade(&state);
+        let listener_on_event = on_event.clone();
+        session.add_event_listener("Runtime.bindingCalled", move |browser, params| {
+            let params = match params {
+                StoredValue::Dictionary(params) if params.get_string("name") == BINDING_NAME => params,
+                _ => return,
+            };
+            let payload = match StoredValue::from_json(&params.get_string("payload")) {
+                Some(StoredValue::Dictionary(payload)) => payload,
+                _ => return,
+            };
+            let state = match listener_state.upgrade() {
+                Some(state) => state,
+                None => return,
+            };
+            let api_badge = if payload.get_bool("reset") {
+                // A new document starts without a badge.
+                None
+            } else if payload.get_bool("flag") {
+                Some(Badge::Flag)
+            } else {
+                match payload.get("count") {
+                    StoredValue::Int(0) => None,
+                    StoredValue::Int(count) => Some(Badge::Count(count as u64)),
+                    StoredValue::Double(count) => Some(Badge::Count(count as u64)),
+                    _ => return,
+                }
+            };
+            state.lock().api_badge = api_badge;
+            update(&state, browser, &*listener_on_event);
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        let script = UserScript::new(SHIM_SOURCE);
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        AppBadgeBridge {
+            state,
+            on_event,
+            session,
+            scripts,
+            script_id,
+        }
+    }
+    /// Reads the badge and progress patterns of `title`. Call this from
+    /// [DisplayHandlerCallbacks::on_title_change](crate::client::display_handler::DisplayHandlerCallbacks::on_title_change).
+    pub fn on_title_change(&self, browser: &Browser, title: &str) {
+        let hints = parse_title(title);
+        {
+            let mut state = self.state.lock();
+            state.title_count = hints.count;
+            if hints.progress != state.progress {
+                state.progress = hints.progress;
+                drop(state);
+                (self.on_event)(browser, &AppBadgeEvent::Progress(hints.progress));
+            }
+        }
+        update(&self.state, browser, &*self.on_event);
+    }
+    /// Returns the current badge.
+    pub fn badge(&self) -> Badge {
+        self.state.lock().badge
+    }
+    /// Returns the current progress.
+    pub fn progress(&self) -> Option<f64> {
+        self.state.lock().progress
+    }
+}
+
+/// Reports the badge if it changed.
+fn update(state: &Mutex<BadgeState>, browser: &Browser, on_event: &BadgeCallback) {
+    let badge = {
+        let mut state = state.lock();
+        let badge = state.api_badge.unwrap_or_else(|| state.title_count.filter(|count| *count > 0).map_or(Badge::None, Badge::Count));
+        if badge == state.badge {
+            return;
+        }
+        state.badge = badge;
+        badge
+    };
+    on_event(browser, &AppBadgeEvent::Badge(badge));
+}
+
+impl Drop for AppBadgeBridge {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script_id);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
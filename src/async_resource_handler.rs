@@ -0,0 +1,401 @@
+//! [ResourceHandler]s backed by futures.
+//!
+//! [AsyncResourceHandler] drives a user supplied future producing the response
+//! head and an [AsyncRead] body, taking care of the asynchronous continuation
+//! callbacks of [ResourceHandlerCallbacks]. No async runtime is required: the
+//! futures are polled on the CEF IO thread, and waking them posts a task to
+//! that thread. Futures must therefore never block. Futures tied to a runtime
+//! (e.g. tokio sockets) work as long as that runtime keeps running on its own
+//! threads.
+
+use crate::{
+    callback::Callback,
+    load_handler::ErrorCode,
+    request::Request,
+    response::Response,
+    task::{TaskRunner, ThreadId},
+    url_request::{ResourceHandler, ResourceHandlerCallbacks, ResourceReadHandler, ResourceSkipCallback},
+};
+pub use futures_io::AsyncRead;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Weak},
+    task::{Context, Poll, Wake, Waker},
+};
+
+/// Status line and headers of a response produced by an [AsyncResourceHandler].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponseParts {
+    pub status: i32,
+    /// The status text, or `None` to use the default text for `status`.
+    pub status_text: Option<String>,
+    pub mime_type: String,
+    pub charset: Option<String>,
+    /// Header name/value pairs. A name may occur multiple times.
+    pub headers: Vec<(String, String)>,
+    /// The length of the body, or `None` to read it until the end.
+    pub content_length: Option<u64>,
+    /// Redirects the request to this URL instead of reading the body.
+    pub redirect_url: Option<String>,
+}
+
+impl HttpResponseParts {
+    pub fn new(status: i32, mime_type: &str) -> HttpResponseParts {
+        HttpResponseParts {
+            status,
+            status_text: None,
+            mime_type: mime_type.to_owned(),
+            charset: None,
+            headers: Vec::new(),
+            content_length: None,
+            redirect_url: None,
+        }
+    }
+    pub fn status_text(mut self, status_text: &str) -> Self {
+        self.status_text = Some(status_text.to_owned());
+        self
+    }
+    pub fn charset(mut self, charset: &str) -> Self {
+        self.charset = Some(charset.to_owned());
+        self
+    }
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+    pub fn content_length(mut self, content_length: u64) -> Self {
+        self.content_length = Some(content_length);
+        self
+    }
+    pub fn redirect(mut self, url: &str) -> Self {
+        self.redirect_url = Some(url.to_owned());
+        self
+    }
+
+    fn write_to(&self, response: &Response) {
+        response.set_status(self.status);
+        if let Some(status_text) = &self.status_text {
+            response.set_status_text(status_text);
+        }
+        response.set_mime_type(&self.mime_type);
+        if let Some(charset) = &self.charset {
+            response.set_charset(charset);
+        }
+        let mut header_map: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, value) in &self.headers {
+            header_map.entry(name.clone()).or_insert_with(Vec::new).push(value.clone());
+        }
+        response.set_header_map(&header_map);
+    }
+}
+
+/// An in-memory response body.
+#[derive(Debug, Clone)]
+pub struct BytesBody(io::Cursor<Vec<u8>>);
+
+impl BytesBody {
+    pub fn new<T: Into<Vec<u8>>>(bytes: T) -> BytesBody {
+        BytesBody(io::Cursor::new(bytes.into()))
+    }
+}
+
+impl AsyncRead for BytesBody {
+    fn poll_read(mut self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Poll::Ready(io::Read::read(&mut self.0, buf))
+    }
+}
+
+type BoxBody = Pin<Box<dyn AsyncRead + Send>>;
+type BoxResponseFuture = Pin<Box<dyn Future<Output = Result<(HttpResponseParts, BoxBody), ErrorCode>> + Send>>;
+
+/// Creates [ResourceHandler]s from async functions. See the
+/// [module documentation](self).
+pub struct AsyncResourceHandler;
+
+impl AsyncResourceHandler {
+    /// Returns a handler that answers the request with the result of
+    /// `respond`. If the future fails, the request fails with the returned
+    /// error code.
+    ///
+    /// ```ignore
+    /// SchemeHandlerFactory::new(|_browser, _frame, _scheme, _request| {
+    ///     Some(AsyncResourceHandler::new(|request: Request| async move {
+    ///         let body = format!("You requested {}", request.get_url());
+    ///         Ok((HttpResponseParts::new(200, "text/plain"), BytesBody::new(body)))
+    ///     }))
+    /// })
+    /// ```
+    pub fn new<F, Fut, B>(respond: F) -> ResourceHandler
+    where
+        F: 'static + Send + FnOnce(Request) -> Fut,
+        Fut: 'static + Send + Future<Output = Result<(HttpResponseParts, B), ErrorCode>>,
+        B: 'static + Send + AsyncRead,
+    {
+        let respond = move |request| -> BoxResponseFuture {
+            let future = respond(request);
+            Box::pin(async move {
+                let (parts, body) = future.await?;
+                Ok((parts, Box::pin(body) as BoxBody))
+            })
+        };
+        ResourceHandler::new(AsyncHandler {
+            respond: Mutex::new(Some(Box::new(respond))),
+            shared: Arc::new(Shared { stage: Mutex::new(Stage::Idle) }),
+        })
+    }
+}
+
+enum Stage {
+    Idle,
+    Responding {
+        future: BoxResponseFuture,
+        callback: Option<Callback>,
+    },
+    Streaming {
+        parts: HttpResponseParts,
+        body: BoxBody,
+        pending: Option<PendingOp>,
+    },
+    Failed(ErrorCode),
+    Cancelled,
+}
+
+enum PendingOp {
+    Read(ResourceReadHandler),
+    Skip {
+        bytes_to_skip: u64,
+        callback: ResourceSkipCallback,
+    },
+}
+
+struct Shared {
+    stage: Mutex<Stage>,
+}
+
+impl Shared {
+    fn waker(self: &Arc<Self>) -> Waker {
+        Waker::from(Arc::new(IoWaker(Arc::downgrade(self))))
+    }
+    /// Polls whatever the handler is currently waiting for. Called on the IO
+    /// thread whenever the waker fires.
+    fn poll(self: &Arc<Self>) {
+        let waker = self.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut stage = self.stage.lock();
+        match &mut *stage {
+            Stage::Responding { future, callback } => {
+                let result = match future.as_mut().poll(&mut cx) {
+                    Poll::Ready(result) => result,
+                    Poll::Pending => return,
+                };
+                let callback = callback.take();
+                *stage = match result {
+                    Ok((parts, body)) => Stage::Streaming { parts, body, pending: None },
+                    Err(error) => Stage::Failed(error),
+                };
+                drop(stage);
+                if let Some(callback) = callback {
+                    callback.cont();
+                }
+            }
+            Stage::Streaming { body, pending, .. } => {
+                let completed = match pending.take() {
+                    Some(PendingOp::Read(handler)) => match poll_read(body.as_mut(), handler, &mut cx) {
+                        Ok(handler) => Some(PendingOp::Read(handler)),
+                        Err(handler) => {
+                            *pending = Some(PendingOp::Read(handler));
+                            None
+                        }
+                    },
+                    Some(PendingOp::Skip { bytes_to_skip, callback }) => {
+                        match poll_skip(body.as_mut(), bytes_to_skip, &mut cx) {
+                            Poll::Ready(result) => {
+                                drop(stage);
+                                callback.cont(result.map(|skipped| skipped as i64).unwrap_or(-(ErrorCode::Failed as i64)));
+                                return;
+                            }
+                            Poll::Pending => {
+                                *pending = Some(PendingOp::Skip { bytes_to_skip, callback });
+                                None
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                drop(stage);
+                if let Some(PendingOp::Read(handler)) = completed {
+                    handler.cont();
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+struct IoWaker(Weak<Shared>);
+
+impl Wake for IoWaker {
+    fn wake(self: Arc<Self>) {
+        let shared = self.0.clone();
+        TaskRunner::post_task_on(ThreadId::IO, move || {
+            if let Some(shared) = shared.upgrade() {
+                shared.poll();
+            }
+        });
+    }
+}
+
+/// Reads into the buffer of `handler`. Returns `Ok` with the completed handler
+/// or `Err` with the handler to retry once woken.
+fn poll_read(
+    body: Pin<&mut (dyn AsyncRead + Send)>,
+    mut handler: ResourceReadHandler,
+    cx: &mut Context,
+) -> Result<ResourceReadHandler, ResourceReadHandler> {
+    match body.poll_read(cx, handler.as_buffer_ref()) {
+        Poll::Ready(Ok(read)) => handler.set_bytes_read(read as i32),
+        Poll::Ready(Err(error)) => {
+            log::error!("error reading async response body: {}", error);
+            handler.set_error(ErrorCode::Failed);
+        }
+        Poll::Pending => return Err(handler),
+    }
+    Ok(handler)
+}
+
+/// Discards up to `remaining` bytes. Resolves to the number of bytes skipped
+/// as soon as some data was available, and only returns `Pending` if nothing
+/// could be skipped yet.
+fn poll_skip(
+    mut body: Pin<&mut (dyn AsyncRead + Send)>,
+    mut remaining: u64,
+    cx: &mut Context,
+) -> Poll<Result<u64, ErrorCode>> {
+    let mut skipped = 0;
+    let mut scratch = [0u8; 4096];
+    while remaining > 0 {
+        let len = remaining.min(scratch.len() as u64) as usize;
+        match body.as_mut().poll_read(cx, &mut scratch[..len]) {
+            Poll::Ready(Ok(0)) => break,
+            Poll::Ready(Ok(read)) => {
+                remaining -= read as u64;
+                skipped += read as u64;
+            }
+            Poll::Ready(Err(_)) => return Poll::Ready(Err(ErrorCode::Failed)),
+            Poll::Pending if skipped > 0 => break,
+            Poll::Pending => return Poll::Pending,
+        }
+    }
+    Poll::Ready(Ok(skipped))
+}
+
+type Respond = Box<dyn 'static + Send + FnOnce(Request) -> BoxResponseFuture>;
+
+struct AsyncHandler {
+    respond: Mutex<Option<Respond>>,
+    shared: Arc<Shared>,
+}
+
+impl ResourceHandlerCallbacks for AsyncHandler {
+    fn open(&mut self, request: Request, handle_request: &mut bool, callback: Callback) -> bool {
+        let respond = match self.respond.lock().take() {
+            Some(respond) => respond,
+            None => {
+                *handle_request = true;
+                return false;
+            }
+        };
+        let mut future = respond(request);
+        let waker = self.shared.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut stage = self.shared.stage.lock();
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(Ok((parts, body))) => {
+                *stage = Stage::Streaming { parts, body, pending: None };
+                *handle_request = true;
+            }
+            Poll::Ready(Err(error)) => {
+                *stage = Stage::Failed(error);
+                *handle_request = true;
+            }
+            Poll::Pending => {
+                *stage = Stage::Responding { future, callback: Some(callback) };
+                *handle_request = false;
+            }
+        }
+        true
+    }
+    fn get_response_headers(
+        &self,
+        response: Response,
+        response_length: &mut Option<u64>,
+        redirect_url: &mut String,
+    ) {
+        match &*self.shared.stage.lock() {
+            Stage::Streaming { parts, .. } => {
+                parts.write_to(&response);
+                *response_length = parts.content_length;
+                if let Some(url) = &parts.redirect_url {
+                    *redirect_url = url.clone();
+                }
+            }
+            Stage::Failed(error) => {
+                response.set_error(*error);
+                *response_length = Some(0);
+            }
+            _ => {
+                response.set_error(ErrorCode::Failed);
+                *response_length = Some(0);
+            }
+        }
+    }
+    fn skip(&mut self, bytes_to_skip: u64, bytes_skipped: &mut u64, callback: ResourceSkipCallback) -> Result<(), ErrorCode> {
+        let waker = self.shared.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut stage = self.shared.stage.lock();
+        match &mut *stage {
+            Stage::Streaming { body, pending, .. } => match poll_skip(body.as_mut(), bytes_to_skip, &mut cx) {
+                Poll::Ready(result) => {
+                    *bytes_skipped = result?;
+                    Ok(())
+                }
+                Poll::Pending => {
+                    *pending = Some(PendingOp::Skip { bytes_to_skip, callback });
+                    *bytes_skipped = 0;
+                    Ok(())
+                }
+            },
+            Stage::Failed(error) => Err(*error),
+            _ => Err(ErrorCode::Failed),
+        }
+    }
+    fn read(&mut self, mut handler: ResourceReadHandler) -> Option<ResourceReadHandler> {
+        let waker = self.shared.waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut stage = self.shared.stage.lock();
+        match &mut *stage {
+            Stage::Streaming { body, pending, .. } => match poll_read(body.as_mut(), handler, &mut cx) {
+                Ok(handler) => Some(handler),
+                Err(handler) => {
+                    *pending = Some(PendingOp::Read(handler));
+                    None
+                }
+            },
+            Stage::Failed(error) => {
+                handler.set_error(*error);
+                Some(handler)
+            }
+            _ => {
+                handler.set_error(ErrorCode::Failed);
+                Some(handler)
+            }
+        }
+    }
+    fn cancel(&mut self) {
+        *self.shared.stage.lock() = Stage::Cancelled;
+    }
+}
@@ -0,0 +1,43 @@
+//! Copies a CEF binary distribution next to an executable.
+//!
+//! Usage: `cef-deploy [--debug] [--verify] <target dir> [<CEF root>]`. The CEF
+//! root defaults to the `CEF_ROOT` environment variable. On macOS the target
+//! directory is the `.app` bundle.
+
+use cef::deploy::{self, Distribution, Platform};
+use std::{env, path::PathBuf, process};
+
+fn main() {
+    let mut debug = false;
+    let mut verify_only = false;
+    let mut paths = Vec::new();
+    for arg in env::args().skip(1) {
+        match &arg[..] {
+            "--debug" => debug = true,
+            "--verify" => verify_only = true,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let target_dir = match paths.get(0) {
+        Some(target_dir) => target_dir.clone(),
+        None => {
+            eprintln!("usage: cef-deploy [--debug] [--verify] <target dir> [<CEF root>]");
+            process::exit(2);
+        }
+    };
+    let result = if verify_only {
+        deploy::verify(&target_dir).map(|()| println!("{} is up to date", target_dir.display()))
+    } else {
+        let distribution = match paths.get(1) {
+            Some(root) => Ok(Distribution::new(root, Platform::current())),
+            None => Distribution::from_env(),
+        };
+        distribution
+            .and_then(|distribution| distribution.debug(debug).deploy(&target_dir))
+            .map(|manifest| println!("deployed {} files to {}", manifest.len(), target_dir.display()))
+    };
+    if let Err(error) = result {
+        eprintln!("cef-deploy: {}", error);
+        process::exit(1);
+    }
+}
@@ -1,10 +1,20 @@
 use cef_sys::{cef_browser_settings_t, cef_browser_t, cef_state_t};
 
 use crate::{
+    accessibility_snapshot::{self, AccessibilitySnapshot},
     browser_host::BrowserHost,
-    color::Color,
+    color::{Color, Transparency},
+    debug_snapshot::DebugSnapshot,
     frame::Frame,
+    frame_selector::FrameSelector,
+    frame_tree::FrameTree,
+    hit_test::{self, HitTest},
+    load_html,
+    screenshot::{self, Screenshot},
     string::{CefString, CefStringList},
+    throttling,
+    user_scripts::CssHandle,
+    values::Point,
 };
 
 ref_counted_ptr! {
@@ -127,6 +137,69 @@ impl Browser {
         }
         Vec::from(list)
     }
+    /// Returns the current frames of this browser with their parent/child
+    /// relations. Must be called in the browser process.
+    pub fn frame_tree(&self) -> FrameTree {
+        FrameTree::new(self)
+    }
+    /// Returns the frames whose URL matches `pattern`, where `*` matches any
+    /// sequence of characters. The frames are looked up whenever the selector
+    /// is used, e.g. `browser.frames_matching("https://ads.example/*").execute_js(code)`.
+    /// See [crate::frame_selector].
+    pub fn frames_matching(&self, pattern: &str) -> FrameSelector {
+        FrameSelector::new(self, pattern)
+    }
+    /// Injects `css` as a user stylesheet into all current documents of this
+    /// browser and re-injects it into every document loaded afterwards. Must
+    /// be called in the browser process.
+    pub fn inject_css(&self, css: &str) -> CssHandle {
+        CssHandle::inject(self, css)
+    }
+    /// Captures the first element matching the CSS `selector` as PNG. The
+    /// element is scrolled into view and clipped to its border box using the
+    /// DevTools protocol. Must be called in the browser process.
+    pub fn screenshot_element(&self, selector: &str) -> Screenshot {
+        screenshot::screenshot_element(self, selector)
+    }
+    /// Takes a snapshot of the accessibility tree of the main frame with the
+    /// DevTools method `Accessibility.getFullAXTree`. Must be called in the
+    /// browser process.
+    pub fn accessibility_snapshot(&self) -> AccessibilitySnapshot {
+        accessibility_snapshot::accessibility_snapshot(self)
+    }
+    /// Finds out what is under `point`, in view coordinates: a link, an
+    /// editable element, a scrollbar or a draggable region. Uses the DevTools
+    /// protocol and must be called in the browser process.
+    pub fn hit_test(&self, point: Point) -> HitTest {
+        hit_test::hit_test(self, point)
+    }
+    /// Collects the URL, navigation history, load state, zoom level, audio
+    /// state and recent console errors of this browser for attaching to bug
+    /// reports. Must be called on the browser process UI thread.
+    pub fn debug_snapshot(&self) -> DebugSnapshot {
+        DebugSnapshot::capture(self)
+    }
+    /// Loads `html` into the main frame. Without `fake_url` the document is
+    /// loaded from a `data:` URL if it fits, so it has an opaque origin. With
+    /// `fake_url` it is served once under that URL, which then determines its
+    /// origin and how relative URLs resolve; other scheme handler factories
+    /// registered for the scheme and host of `fake_url` are replaced. See
+    /// [crate::load_html].
+    pub fn load_html(&self, html: &str, fake_url: Option<&str>) {
+        load_html::load_html(self, html, fake_url)
+    }
+    /// Marks this browser as hidden and reduces its resource usage: the
+    /// windowless frame rate drops to [THROTTLED_FRAME_RATE](crate::throttling::THROTTLED_FRAME_RATE)
+    /// and the page is frozen with `Page.setWebLifecycleState`. Passing false
+    /// restores the previous state. See [crate::throttling].
+    pub fn set_hidden_and_throttled(&self, throttled: bool) {
+        throttling::set_hidden_and_throttled(self, throttled)
+    }
+    /// Returns true if this browser was throttled with
+    /// [Browser::set_hidden_and_throttled].
+    pub fn is_throttled(&self) -> bool {
+        throttling::is_throttled(self)
+    }
 }
 
 /// Represents the state of a setting.
@@ -187,8 +260,15 @@ pub struct BrowserSettings {
     pub javascript_access_clipboard: State,
     pub javascript_dom_paste: State,
     pub plugins: State,
+    /// Whether pages loaded from `file://` URLs may access any other URL,
+    /// regardless of origin. See [crate::local_content].
     pub universal_access_from_file_urls: State,
+    /// Whether pages loaded from `file://` URLs may access other `file://`
+    /// URLs. Equivalent to the `--allow-file-access-from-files` switch. See
+    /// [crate::local_content].
     pub file_access_from_file_urls: State,
+    /// Whether the same-origin policy is enforced. Equivalent to the
+    /// `--disable-web-security` switch when disabled.
     pub web_security: State,
     pub image_loading: State,
     pub image_shrink_standalone_to_fit: State,
@@ -206,6 +286,16 @@ impl BrowserSettings {
     pub fn new() -> BrowserSettings {
         BrowserSettings::default()
     }
+    /// Returns the background as a [Transparency], or `None` if
+    /// `background_color` is partially transparent and will be rejected by CEF.
+    pub fn transparency(&self) -> Option<Transparency> {
+        Transparency::from_color(self.background_color)
+    }
+    /// Sets `background_color` from a [Transparency]. Transparent backgrounds
+    /// require a windowless browser, see [WindowInfo::windowless](crate::window::WindowInfo::windowless).
+    pub fn set_transparency(&mut self, transparency: Transparency) {
+        self.background_color = transparency.to_color();
+    }
 
     pub unsafe fn from_raw(raw: &cef_browser_settings_t) -> BrowserSettings {
         BrowserSettings {
@@ -1,21 +1,25 @@
 use cef_sys::cef_drag_operations_mask_t;
 use crate::{
     browser::{Browser, BrowserSettings, State},
+    browser_startup::CreateError,
     client::Client,
     devtools_message_observer::DevToolsMessageObserver,
     registration::Registration,
     drag::{DragData, DragOperation},
+    error::{unsupported, CefError},
     events::{KeyEvent, MouseButtonType, MouseEvent, TouchEvent},
     extension::Extension,
     file_dialog::{FileDialogMode, RunFileDialogCallbackWrapper},
     image::Image,
     ime::CompositionUnderline,
-    navigation::NavigationEntry,
-    printing::PDFPrintSettings,
+    load_handler::TransitionType,
+    navigation::{NavigationEntry, NavigationReason},
+    printing::{PDFPrintSettings, PdfError, PdfJob, PdfResult},
     refcounted::{RefCountedPtr, Wrapper},
     request_context::RequestContext,
     send_protector::SendProtectorMut,
     string::{CefString, CefStringList},
+    user_activity::{self, InputKind},
     values::{DictionaryValue, Point, Range, Size, StoredValue},
     window::{RawWindow, WindowInfo},
 };
@@ -28,8 +32,11 @@ use cef_sys::{
 use parking_lot::Mutex;
 use std::{
     collections::HashMap,
+    convert::TryInto,
     iter::FromIterator,
+    path::Path,
     ptr::{null, null_mut},
+    time::Duration,
 };
 
 /// Paint element types.
@@ -96,6 +103,10 @@ impl BrowserHost {
     /// optional `extra_info` parameter provides an opportunity to specify extra
     /// information specific to the created browser that will be passed to
     /// [RenderProcessHandlerCallbacks::on_browser_created] in the render process.
+    ///
+    /// Fails if a missing file or a crashing GPU process would keep the browser
+    /// from starting; see [browser_startup](crate::browser_startup) for
+    /// catching failures that show up after creation.
     pub fn create_browser_sync(
         window_info: &WindowInfo,
         client: Client,
@@ -103,11 +114,15 @@ impl BrowserHost {
         settings: &BrowserSettings,
         extra_info: Option<&HashMap<String, StoredValue>>,
         request_context: Option<RequestContext>,
-    ) -> Browser {
+    ) -> Result<Browser, CreateError> {
+        crate::browser_startup::check_before_create()?;
+        if let Err(error) = window_info.validate(settings) {
+            log::warn!("creating browser with an invalid configuration: {}", error);
+        }
         let extra_info = extra_info.map(DictionaryValue::from);
 
         unsafe {
-            Browser::from_ptr_unchecked(cef_browser_host_create_browser_sync(
+            Browser::from_ptr(cef_browser_host_create_browser_sync(
                 &window_info.into_raw(),
                 client.into_raw(),
                 CefString::new(url).as_ptr(),
@@ -117,6 +132,7 @@ impl BrowserHost {
                     .map(|rc| rc.into_raw())
                     .unwrap_or_else(null_mut),
             ))
+            .ok_or(CreateError::Failed)
         }
     }
     /// Returns the hosted browser object.
@@ -136,6 +152,8 @@ impl BrowserHost {
             unsafe {
                 close_browser(self.0.as_ptr(), force_close as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::close_browser");
         }
     }
     /// Helper for closing a browser. Call this function from the top-level window
@@ -156,6 +174,8 @@ impl BrowserHost {
             unsafe {
                 set_focus(self.0.as_ptr(), focus as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::set_focus");
         }
     }
     /// Retrieve the window handle for this browser. If this browser is wrapped in
@@ -212,6 +232,8 @@ impl BrowserHost {
             unsafe {
                 set_zoom_level(self.0.as_ptr(), zoom_level);
             }
+        } else {
+            unsupported("cef_browser_host_t::set_zoom_level");
         }
     }
     /// Call to run a file chooser dialog. Only a single file chooser dialog may be
@@ -258,6 +280,8 @@ impl BrowserHost {
                         .into_raw(),
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::run_file_dialog");
         }
     }
     /// Download the file at `url` using [DownloadHandler].
@@ -266,6 +290,8 @@ impl BrowserHost {
             unsafe {
                 start_download(self.0.as_ptr(), CefString::new(url).as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::start_download");
         }
     }
     /// Download `image_url` and execute `callback` on completion with the images
@@ -304,6 +330,8 @@ impl BrowserHost {
                         .into_raw(),
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::download_image");
         }
     }
     /// Print the current browser contents.
@@ -312,6 +340,8 @@ impl BrowserHost {
             unsafe {
                 print(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::print");
         }
     }
     /// Print the current browser contents to the PDF file specified by `path` and
@@ -337,7 +367,32 @@ impl BrowserHost {
                     PDFPrintCallbackWrapper::new(callback).wrap().into_raw(),
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::print_to_pdf");
+        }
+    }
+    /// Like [BrowserHost::print_to_pdf], but returns a [PdfJob] that reports
+    /// progress and can be canceled. If the renderer hasn't written the PDF
+    /// after `timeout`, `callback` receives [PdfError::TimedOut] instead of
+    /// never being called. `callback` is called exactly once, on the browser
+    /// process UI thread.
+    pub fn print_to_pdf_job(
+        &self,
+        path: &Path,
+        settings: &PDFPrintSettings,
+        timeout: Duration,
+        callback: impl Send + FnOnce(PdfResult) + 'static,
+    ) -> PdfJob {
+        let browser_id = self.get_browser().get_identifier();
+        let job = PdfJob::start(browser_id, path.to_owned(), timeout, callback);
+        if self.0.print_to_pdf.is_some() {
+            let finished = job.clone();
+            self.print_to_pdf(&path.to_string_lossy(), settings, move |_, ok| finished.finished(ok));
+        } else {
+            unsupported("cef_browser_host_t::print_to_pdf");
+            job.complete(Err(PdfError::Unsupported));
         }
+        job
     }
     /// Search for `searchText`. `identifier` must be a unique ID and these IDs
     /// must strictly increase so that newer requests always have greater IDs than
@@ -367,6 +422,8 @@ impl BrowserHost {
                     find_next as i32,
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::find");
         }
     }
     /// Cancel all searches that are currently going on.
@@ -375,6 +432,8 @@ impl BrowserHost {
             unsafe {
                 stop_finding(self.0.as_ptr(), clear_selection as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::stop_finding");
         }
     }
     /// Open developer tools (DevTools) in its own browser. The DevTools browser
@@ -405,6 +464,8 @@ impl BrowserHost {
                     &inspect_element_at.into(),
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::show_dev_tools");
         }
     }
     /// Explicitly close the associated DevTools browser, if any.
@@ -413,6 +474,8 @@ impl BrowserHost {
             unsafe {
                 close_dev_tools(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::close_dev_tools");
         }
     }
     /// Returns true if this browser currently has an associated DevTools
@@ -527,6 +590,8 @@ impl BrowserHost {
                     current_only as i32,
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::get_navigation_entries");
         }
     }
     /// Set whether mouse cursor change is disabled.
@@ -535,6 +600,8 @@ impl BrowserHost {
             unsafe {
                 set_mouse_cursor_change_disabled(self.0.as_ptr(), disabled as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::set_mouse_cursor_change_disabled");
         }
     }
     /// Returns true if mouse cursor change is disabled.
@@ -553,6 +620,8 @@ impl BrowserHost {
             unsafe {
                 replace_misspelling(self.0.as_ptr(), CefString::new(word).as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::replace_misspelling");
         }
     }
     /// Add the specified `word` to the spelling dictionary.
@@ -561,6 +630,8 @@ impl BrowserHost {
             unsafe {
                 add_word_to_dictionary(self.0.as_ptr(), CefString::new(word).as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::add_word_to_dictionary");
         }
     }
     /// Returns true if window rendering is disabled.
@@ -581,6 +652,26 @@ impl BrowserHost {
             unsafe {
                 was_resized(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::was_resized");
+        }
+    }
+    /// Makes web content leave HTML5 fullscreen mode, e.g. when the user
+    /// presses Escape. [DisplayHandler::on_fullscreen_mode_change] is called
+    /// once it has. Pass `will_cause_resize` if the view size depends on the
+    /// fullscreen state, as with [WindowlessFullscreen](crate::client::fullscreen::WindowlessFullscreen),
+    /// to have the browser query the view rectangle again.
+    ///
+    /// CEF 85 has no native function for this, so the page is asked through
+    /// `document.exitFullscreen()`, which leaves fullscreen for iframes too.
+    pub fn exit_fullscreen(&self, will_cause_resize: bool) {
+        self.get_browser().get_main_frame().execute_java_script(
+            "if (document.fullscreenElement) { document.exitFullscreen(); }",
+            "",
+            0,
+        );
+        if will_cause_resize {
+            self.was_resized();
         }
     }
     /// Notify the browser that it has been hidden or shown. Layouting and
@@ -591,6 +682,8 @@ impl BrowserHost {
             unsafe {
                 was_hidden(self.0.as_ptr(), hidden as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::was_hidden");
         }
     }
     /// Send a notification to the browser that the screen info has changed. The
@@ -604,6 +697,8 @@ impl BrowserHost {
             unsafe {
                 notify_screen_info_changed(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::notify_screen_info_changed");
         }
     }
     /// Invalidate the view. The browser will call [RenderHandler::on_paint]
@@ -614,6 +709,8 @@ impl BrowserHost {
             unsafe {
                 invalidate(self.0.as_ptr(), element_type as crate::CEnumType);
             }
+        } else {
+            unsupported("cef_browser_host_t::invalidate");
         }
     }
     /// Issue a BeginFrame request to Chromium.  Only valid when
@@ -623,6 +720,8 @@ impl BrowserHost {
             unsafe {
                 send_external_begin_frame(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::send_external_begin_frame");
         }
     }
     /// Send a key event to the browser.
@@ -631,7 +730,10 @@ impl BrowserHost {
             unsafe {
                 send_key_event(self.0.as_ptr(), &event.as_cef());
             }
+        } else {
+            unsupported("cef_browser_host_t::send_key_event");
         }
+        self.record_input(InputKind::Key);
     }
     /// Send a mouse click event to the browser. The `x` and `y` coordinates are
     /// relative to the upper-left corner of the view.
@@ -652,7 +754,10 @@ impl BrowserHost {
                     click_count,
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::send_mouse_click_event");
         }
+        self.record_input(InputKind::MouseClick);
     }
     /// Send a mouse move event to the browser. The `x` and `y` coordinates are
     /// relative to the upper-left corner of the view.
@@ -661,7 +766,10 @@ impl BrowserHost {
             unsafe {
                 send_mouse_move_event(self.0.as_ptr(), event.as_cef(), mouse_leave as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::send_mouse_move_event");
         }
+        self.record_input(InputKind::MouseMove);
     }
     /// Send a mouse wheel event to the browser. The `x` and `y` coordinates are
     /// relative to the upper-left corner of the view. The `deltaX` and `deltaY`
@@ -673,7 +781,10 @@ impl BrowserHost {
             unsafe {
                 send_mouse_wheel_event(self.0.as_ptr(), event.as_cef(), delta_x, delta_y);
             }
+        } else {
+            unsupported("cef_browser_host_t::send_mouse_wheel_event");
         }
+        self.record_input(InputKind::MouseWheel);
     }
     /// Send a touch event to the browser for a windowless browser.
     pub fn send_touch_event(&self, event: &TouchEvent) {
@@ -681,7 +792,97 @@ impl BrowserHost {
             unsafe {
                 send_touch_event(self.0.as_ptr(), event.as_cef());
             }
+        } else {
+            unsupported("cef_browser_host_t::send_touch_event");
+        }
+        self.record_input(InputKind::Touch);
+    }
+    /// Reports which functions of this host the loaded CEF binary provides.
+    /// Calls to missing functions are ignored, see [crate::error].
+    pub fn capabilities(&self) -> HostCapabilities {
+        macro_rules! missing {
+            ($($function:ident,)*) => {
+                vec![$(
+                    (stringify!($function), self.0.$function.is_none()),
+                )*].into_iter().filter(|(_, missing)| *missing).map(|(function, _)| function).collect()
+            };
         }
+        HostCapabilities {
+            missing: missing!(
+                get_browser,
+                close_browser,
+                try_close_browser,
+                set_focus,
+                get_window_handle,
+                get_opener_window_handle,
+                has_view,
+                get_client,
+                get_request_context,
+                get_zoom_level,
+                set_zoom_level,
+                run_file_dialog,
+                start_download,
+                download_image,
+                print,
+                print_to_pdf,
+                find,
+                stop_finding,
+                show_dev_tools,
+                close_dev_tools,
+                has_dev_tools,
+                send_dev_tools_message,
+                execute_dev_tools_method,
+                add_dev_tools_message_observer,
+                get_navigation_entries,
+                set_mouse_cursor_change_disabled,
+                is_mouse_cursor_change_disabled,
+                replace_misspelling,
+                add_word_to_dictionary,
+                is_window_rendering_disabled,
+                was_resized,
+                was_hidden,
+                notify_screen_info_changed,
+                invalidate,
+                send_external_begin_frame,
+                send_key_event,
+                send_mouse_click_event,
+                send_mouse_move_event,
+                send_mouse_wheel_event,
+                send_touch_event,
+                send_focus_event,
+                send_capture_lost_event,
+                notify_move_or_resize_started,
+                get_windowless_frame_rate,
+                set_windowless_frame_rate,
+                ime_set_composition,
+                ime_commit_text,
+                ime_finish_composing_text,
+                ime_cancel_composition,
+                drag_target_drag_enter,
+                drag_target_drag_over,
+                drag_target_drag_leave,
+                drag_target_drop,
+                drag_source_ended_at,
+                drag_source_system_drag_ended,
+                get_visible_navigation_entry,
+                set_accessibility_state,
+                set_auto_resize_enabled,
+                get_extension,
+                is_background_host,
+                set_audio_muted,
+                is_audio_muted,
+            ),
+        }
+    }
+    /// Returns the number of seconds since input was last sent to the browser,
+    /// or `None` if it hasn't received any. See [user_activity] for which input
+    /// is seen.
+    pub fn seconds_since_last_input(&self) -> Option<f64> {
+        user_activity::last_input(self.get_browser().get_identifier())
+            .map(|time| time.elapsed().as_secs_f64())
+    }
+    fn record_input(&self, kind: InputKind) {
+        user_activity::record(self.get_browser().get_identifier(), kind);
     }
     /// Send a focus event to the browser.
     pub fn send_focus_event(&self, set_focus: bool) {
@@ -689,6 +890,8 @@ impl BrowserHost {
             unsafe {
                 send_focus_event(self.0.as_ptr(), set_focus as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::send_focus_event");
         }
     }
     /// Send a capture lost event to the browser.
@@ -697,6 +900,8 @@ impl BrowserHost {
             unsafe {
                 send_capture_lost_event(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::send_capture_lost_event");
         }
     }
     /// Notify the browser that the window hosting it is about to be moved or
@@ -706,6 +911,8 @@ impl BrowserHost {
             unsafe {
                 notify_move_or_resize_started(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::notify_move_or_resize_started");
         }
     }
     /// Returns the maximum rate in frames per second (fps) that
@@ -729,6 +936,8 @@ impl BrowserHost {
             unsafe {
                 set_windowless_frame_rate(self.0.as_ptr(), frame_rate);
             }
+        } else {
+            unsupported("cef_browser_host_t::set_windowless_frame_rate");
         }
     }
     /// Begins a new composition or updates the existing composition. Blink has a
@@ -771,6 +980,8 @@ impl BrowserHost {
                     selection_range.as_ptr(),
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::ime_set_composition");
         }
     }
 
@@ -797,6 +1008,8 @@ impl BrowserHost {
                     relative_cursor_pos,
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::ime_commit_text");
         }
     }
     /// Completes the existing composition by applying the current composition node
@@ -808,6 +1021,8 @@ impl BrowserHost {
             unsafe {
                 ime_finish_composing_text(self.0.as_ptr(), keep_selection as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::ime_finish_composing_text");
         }
     }
     /// Cancels the existing composition and discards the composition node contents
@@ -818,6 +1033,8 @@ impl BrowserHost {
             unsafe {
                 ime_cancel_composition(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::ime_cancel_composition");
         }
     }
     /// Call this function when the user drags the mouse into the web view (before
@@ -842,6 +1059,8 @@ impl BrowserHost {
                     cef_drag_operations_mask_t(allowed_ops.bits()),
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::drag_target_drag_enter");
         }
     }
     /// Call this function each time the mouse is moved across the web view during
@@ -857,6 +1076,8 @@ impl BrowserHost {
                     cef_drag_operations_mask_t(allowed_ops.bits()),
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::drag_target_drag_over");
         }
     }
     /// Call this function when the user drags the mouse out of the web view (after
@@ -867,6 +1088,8 @@ impl BrowserHost {
             unsafe {
                 drag_target_drag_leave(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::drag_target_drag_leave");
         }
     }
     /// Call this function when the user completes the drag operation by dropping
@@ -879,6 +1102,8 @@ impl BrowserHost {
             unsafe {
                 drag_target_drop(self.0.as_ptr(), event.as_cef());
             }
+        } else {
+            unsupported("cef_browser_host_t::drag_target_drop");
         }
     }
     /// Call this function when the drag operation started by a
@@ -893,6 +1118,8 @@ impl BrowserHost {
             unsafe {
                 drag_source_ended_at(self.0.as_ptr(), x, y, cef_drag_operations_mask_t(op.bits()));
             }
+        } else {
+            unsupported("cef_browser_host_t::drag_source_ended_at");
         }
     }
     /// Call this function when the drag operation started by a
@@ -906,6 +1133,8 @@ impl BrowserHost {
             unsafe {
                 drag_source_system_drag_ended(self.0.as_ptr());
             }
+        } else {
+            unsupported("cef_browser_host_t::drag_source_system_drag_ended");
         }
     }
     /// Returns the current visible navigation entry for this browser. This
@@ -943,6 +1172,8 @@ impl BrowserHost {
             unsafe {
                 set_accessibility_state(self.0.as_ptr(), accessibility_state as crate::CEnumType);
             }
+        } else {
+            unsupported("cef_browser_host_t::set_accessibility_state");
         }
     }
     /// Enable notifications of auto resize via
@@ -958,6 +1189,8 @@ impl BrowserHost {
                     max_size.as_ptr(),
                 );
             }
+        } else {
+            unsupported("cef_browser_host_t::set_auto_resize_enabled");
         }
     }
     /// Returns the extension hosted in this browser or None if no extension is
@@ -982,6 +1215,8 @@ impl BrowserHost {
             unsafe {
                 set_audio_muted(self.0.as_ptr(), mute as i32);
             }
+        } else {
+            unsupported("cef_browser_host_t::set_audio_muted");
         }
     }
     /// Returns true if the browser's audio is muted. This function can only
@@ -1079,6 +1314,31 @@ cef_callback_impl! {
     }
 }
 
+/// The result of [BrowserHost::capabilities].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostCapabilities {
+    /// The names of the `cef_browser_host_t` functions that are missing.
+    pub missing: Vec<&'static str>,
+}
+
+impl HostCapabilities {
+    /// Returns true if no function is missing.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+    /// Returns true if the function `name`, e.g. `"invalidate"`, is available.
+    pub fn has(&self, name: &str) -> bool {
+        !self.missing.iter().any(|function| *function == name)
+    }
+    /// Returns [CefError::Unsupported] if the function `name` is missing.
+    pub fn require(&self, name: &str) -> Result<(), CefError> {
+        match self.missing.iter().find(|function| **function == name) {
+            Some(function) => Err(CefError::Unsupported(function)),
+            None => Ok(()),
+        }
+    }
+}
+
 pub struct NavigationEntryVisit {
     /// Current navigation entry. Do not keep a reference to this field outside of the
     /// visitor callback.
@@ -1089,6 +1349,13 @@ pub struct NavigationEntryVisit {
     pub index: usize,
     /// The total number of navigation entries.
     pub total: usize,
+    /// How the entry was navigated to, or `None` if CEF reported an unknown
+    /// transition type.
+    pub transition_type: Option<TransitionType>,
+    /// The reason for the navigation, derived from `transition_type`.
+    pub reason: Option<NavigationReason>,
+    /// Whether the entry was loaded with POST data, e.g. by submitting a form.
+    pub is_post: bool,
 }
 
 /// Callback type for `NavigationEntryVisitor`.
@@ -1142,11 +1409,18 @@ cef_callback_impl! {
             index: std::os::raw::c_int: std::os::raw::c_int,
             total: std::os::raw::c_int: std::os::raw::c_int
         ) -> std::os::raw::c_int {
+            let transition_type = entry.0.get_transition_type
+                .and_then(|get_transition_type| unsafe { get_transition_type(entry.as_ptr()) }.0.try_into().ok());
+            let reason = transition_type.as_ref().map(NavigationReason::from);
+            let is_post = entry.has_post_data();
             (unsafe{ &mut *self.callback.get_mut() })(NavigationEntryVisit {
                 entry,
                 current,
                 index: index as usize,
                 total: total as usize,
+                transition_type,
+                reason,
+                is_post,
             } ) as _
         }
     }
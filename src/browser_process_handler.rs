@@ -94,6 +94,7 @@ cef_callback_impl! {
             &self,
             command_line: CommandLine: *mut cef_command_line_t,
         ) {
+            crate::process_monitor::child_process_launching(&command_line);
             self.delegate.on_before_child_process_launch(command_line);
         }
         fn render_process_thread_created(
@@ -118,10 +119,4 @@ cef_callback_impl! {
 }
 
 #[cfg(target_os = "linux")]
-pub use self::print_handler::PrintHandler;
-#[cfg(target_os = "linux")]
-mod print_handler {
-    ref_counted_ptr!{
-        pub struct PrintHandler(*mut cef_sys::cef_print_handler_t);
-    }
-}
+pub use crate::print_handler::PrintHandler;
@@ -0,0 +1,211 @@
+//! Diagnosing browsers that fail to start.
+//!
+//! When CEF can't create a browser it usually doesn't say why: a missing
+//! subprocess executable or locale file and a GPU process that keeps crashing
+//! all end in a blank window or an abort in native code.
+//! [BrowserHost::create_browser_sync](crate::browser_host::BrowserHost::create_browser_sync)
+//! checks for the causes it can see up front and returns a [CreateError].
+//! Problems that only show up later are caught by [watch_startup]:
+//!
+//! ```ignore
+//! let browser = BrowserHost::create_browser_sync(&window_info, client, url, &settings, None, None)?;
+//! browser_startup::watch_startup(&browser, Duration::from_secs(10), |result| {
+//!     if let Err(error) = result {
+//!         log::error!("browser didn't start: {}", error);
+//!     }
+//! });
+//! ```
+//!
+//! Child process launches are only seen with an [App](crate::app::App) that
+//! has a browser process handler, so without one [CreateError::GpuCrashLoop]
+//! and [CreateError::RendererNotStarted] aren't detected.
+
+use crate::{
+    browser::Browser,
+    i18n::{self, DEFAULT_LOCALE},
+    misc_fns::ProcessType,
+    settings::Settings,
+    task::{TaskRunner, ThreadId},
+};
+use parking_lot::Mutex;
+use std::{
+    convert::TryInto,
+    fmt,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Chromium stops restarting the GPU process after this many crashes.
+const GPU_CRASH_LOOP_LAUNCHES: usize = 3;
+
+/// GPU process launches older than this don't count towards a crash loop.
+const GPU_CRASH_LOOP_WINDOW: Duration = Duration::from_secs(60);
+
+/// The settings of [Settings] needed to find missing files.
+struct StartupPaths {
+    browser_subprocess_path: Option<PathBuf>,
+    locales_dir_path: Option<PathBuf>,
+    pack_loading_disabled: bool,
+}
+
+lazy_static::lazy_static! {
+    static ref STARTUP_PATHS: Mutex<Option<StartupPaths>> = Mutex::new(None);
+    /// Recent GPU process launches, oldest first.
+    static ref GPU_LAUNCHES: Mutex<Vec<Instant>> = Mutex::new(Vec::new());
+}
+
+static RENDERER_LAUNCHES: AtomicUsize = AtomicUsize::new(0);
+static CHILD_LAUNCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Why a browser couldn't be created or didn't start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CreateError {
+    /// CEF wasn't initialized with [Context::initialize](crate::Context::initialize).
+    ContextNotInitialized,
+    /// Browsers can only be created on the browser process UI thread.
+    NotOnUIThread,
+    /// [Settings::browser_subprocess_path](crate::settings::Settings::browser_subprocess_path)
+    /// doesn't exist, so no renderer can be launched.
+    SubprocessMissing(PathBuf),
+    /// Neither the locale file of `locale`, nor the one of its language, nor
+    /// the one of [DEFAULT_LOCALE] is in `locales_dir`.
+    LocaleNotFound { locale: String, locales_dir: PathBuf },
+    /// The GPU process was launched `launches` times within a minute, which
+    /// means it crashes on startup. Try the "disable-gpu" switch or update the
+    /// graphics driver. Only reported by [watch_startup]: Chromium falls back
+    /// to software rendering, so browsers can still be created.
+    GpuCrashLoop { launches: usize },
+    /// Other child processes were launched, but never a renderer. This happens
+    /// when the subprocess executable doesn't call
+    /// [execute_process](crate::execute_process) or exits early.
+    RendererNotStarted,
+    /// The browser had no document after the given time, for no known reason.
+    Timeout(Duration),
+    /// CEF returned no browser.
+    Failed,
+}
+
+impl fmt::Display for CreateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CreateError::ContextNotInitialized => write!(f, "CEF is not initialized"),
+            CreateError::NotOnUIThread => write!(f, "browsers must be created on the browser process UI thread"),
+            CreateError::SubprocessMissing(path) => write!(f, "browser subprocess {} does not exist", path.display()),
+            CreateError::LocaleNotFound { locale, locales_dir } => write!(
+                f,
+                "neither {}.pak nor {}.pak was found in {}",
+                locale,
+                DEFAULT_LOCALE,
+                locales_dir.display(),
+            ),
+            CreateError::GpuCrashLoop { launches } => {
+                write!(f, "the GPU process was launched {} times in a row; try the disable-gpu switch", launches)
+            }
+            CreateError::RendererNotStarted => write!(f, "no render process was started"),
+            CreateError::Timeout(timeout) => write!(f, "the browser had no document after {:?}", timeout),
+            CreateError::Failed => write!(f, "CEF failed to create the browser"),
+        }
+    }
+}
+
+impl std::error::Error for CreateError {}
+
+/// Remembers the paths of `settings`. Called by `Context::initialize`.
+pub(crate) fn record_settings(settings: &Settings) {
+    *STARTUP_PATHS.lock() = Some(StartupPaths {
+        browser_subprocess_path: settings.browser_subprocess_path.clone(),
+        locales_dir_path: settings.locales_dir_path.clone(),
+        pack_loading_disabled: settings.pack_loading_disabled,
+    });
+}
+
+/// Counts a child process launch. Called from the browser process handler.
+pub(crate) fn child_process_launched(process_type: ProcessType) {
+    CHILD_LAUNCHES.fetch_add(1, Ordering::SeqCst);
+    match process_type {
+        ProcessType::Renderer => {
+            RENDERER_LAUNCHES.fetch_add(1, Ordering::SeqCst);
+        }
+        ProcessType::Gpu => {
+            let mut launches = GPU_LAUNCHES.lock();
+            launches.retain(|launch| launch.elapsed() < GPU_CRASH_LOOP_WINDOW);
+            launches.push(Instant::now());
+        }
+        _ => (),
+    }
+}
+
+/// Checks for problems that would keep a new browser from starting.
+pub(crate) fn check_before_create() -> Result<(), CreateError> {
+    if !crate::misc_fns::context_initialized() {
+        return Err(CreateError::ContextNotInitialized);
+    }
+    if !TaskRunner::currently_on(ThreadId::UI) {
+        return Err(CreateError::NotOnUIThread);
+    }
+    if let Err(error) = check_gpu() {
+        log::warn!("creating browser after GPU process failures: {}", error);
+    }
+    let paths = STARTUP_PATHS.lock();
+    let paths = match &*paths {
+        Some(paths) => paths,
+        None => return Ok(()),
+    };
+    if let Some(path) = &paths.browser_subprocess_path {
+        if !crate::misc_fns::is_single_process() && !path.exists() {
+            return Err(CreateError::SubprocessMissing(path.clone()));
+        }
+    }
+    // The locales are part of the framework bundle on macOS.
+    if !paths.pack_loading_disabled && !cfg!(target_os = "macos") {
+        let locales_dir = paths.locales_dir_path.clone().or_else(i18n::default_locales_dir);
+        if let Some(locales_dir) = locales_dir {
+            let locale = i18n::current_locale();
+            // Chromium falls back to the language, e.g. from de-AT to de.
+            let language = locale.split(|c| c == '-' || c == '_').next().unwrap_or("");
+            let found = [&locale[..], language, DEFAULT_LOCALE].iter()
+                .filter(|locale| !locale.is_empty())
+                .any(|locale| locales_dir.join(format!("{}.pak", locale)).exists());
+            if !found {
+                return Err(CreateError::LocaleNotFound { locale, locales_dir });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_gpu() -> Result<(), CreateError> {
+    let mut launches = GPU_LAUNCHES.lock();
+    launches.retain(|launch| launch.elapsed() < GPU_CRASH_LOOP_WINDOW);
+    if launches.len() >= GPU_CRASH_LOOP_LAUNCHES {
+        return Err(CreateError::GpuCrashLoop { launches: launches.len() });
+    }
+    Ok(())
+}
+
+/// Calls `callback` on the UI thread once `browser` has a document, or with
+/// the most likely reason if it has none after `timeout`.
+pub fn watch_startup(
+    browser: &Browser,
+    timeout: Duration,
+    callback: impl 'static + Send + FnOnce(Result<(), CreateError>),
+) {
+    let browser = browser.clone();
+    let delay_ms = timeout.as_millis().try_into().unwrap_or(i64::max_value());
+    TaskRunner::post_delayed_task_on(ThreadId::UI, move || {
+        if browser.has_document() {
+            callback(Ok(()));
+            return;
+        }
+        let launches_seen = CHILD_LAUNCHES.load(Ordering::SeqCst) > 0;
+        let result = check_gpu().and_then(|()| {
+            if launches_seen && !crate::misc_fns::is_single_process() && RENDERER_LAUNCHES.load(Ordering::SeqCst) == 0 {
+                Err(CreateError::RendererNotStarted)
+            } else {
+                Err(CreateError::Timeout(timeout))
+            }
+        });
+        callback(result);
+    }, delay_ms);
+}
@@ -0,0 +1,213 @@
+//! Small key/value state mirrored between the browser and render processes.
+//!
+//! Each process creates one [BrowserStore] and forwards the relevant process
+//! callbacks to it:
+//!
+//! * In the browser process, [BrowserStore::on_process_message_received] from
+//!   [ClientCallbacks::on_process_message_received] and
+//!   [BrowserStore::on_browser_destroyed] from
+//!   [LifeSpanHandlerCallbacks::on_before_close].
+//! * In the render process, [BrowserStore::on_browser_created],
+//!   [BrowserStore::on_browser_destroyed] and
+//!   [BrowserStore::on_process_message_received] from the
+//!   [RenderProcessHandlerCallbacks] methods of the same names.
+//!
+//! The browser process holds the authoritative copy. A render process asks for
+//! the current state whenever it creates a browser, since cross-origin
+//! navigations may move a browser to a fresh render process.
+//!
+//! [ClientCallbacks::on_process_message_received]: crate::client::ClientCallbacks::on_process_message_received
+//! [LifeSpanHandlerCallbacks::on_before_close]: crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_before_close
+//! [RenderProcessHandlerCallbacks]: crate::render_process_handler::RenderProcessHandlerCallbacks
+
+use crate::{
+    browser::Browser,
+    helper_traits::DeepClone,
+    process::ProcessMessage,
+    values::{DictionaryValue, StoredValue, Value},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+};
+
+const MESSAGE_NAME: &str = "cef-rs.browser-store";
+
+const OP_SET: &str = "set";
+const OP_REMOVE: &str = "remove";
+const OP_SYNC_REQUEST: &str = "sync-request";
+const OP_SYNC: &str = "sync";
+
+static NEXT_LISTENER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a listener added with [BrowserStore::add_listener].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StoreListenerId(u64);
+
+type ChangeListener = Arc<dyn 'static + Send + Sync + Fn(&Browser, &str, Option<&StoredValue>)>;
+
+struct StoreState {
+    values: Mutex<HashMap<i32, HashMap<String, StoredValue>>>,
+    listeners: Mutex<Vec<(StoreListenerId, ChangeListener)>>,
+}
+
+/// Key/value state per browser, replicated to the other process with process
+/// messages. See the [module documentation](self). Cloning the store is cheap
+/// and all clones share the same state.
+#[derive(Clone)]
+pub struct BrowserStore(Arc<StoreState>);
+
+impl BrowserStore {
+    pub fn new() -> BrowserStore {
+        BrowserStore(Arc::new(StoreState {
+            values: Mutex::new(HashMap::new()),
+            listeners: Mutex::new(Vec::new()),
+        }))
+    }
+    /// Returns the value of `key` for the browser with the identifier
+    /// `browser_id`.
+    pub fn get(&self, browser_id: i32, key: &str) -> Option<StoredValue> {
+        self.0.values.lock().get(&browser_id).and_then(|values| values.get(key)).cloned()
+    }
+    /// Returns all values of the browser with the identifier `browser_id`.
+    pub fn get_all(&self, browser_id: i32) -> HashMap<String, StoredValue> {
+        self.0.values.lock().get(&browser_id).cloned().unwrap_or_default()
+    }
+    /// Sets `key` to `value` in this process and sends the change to the other
+    /// process. Listeners in both processes are notified.
+    pub fn set(&self, browser: &Browser, key: &str, value: StoredValue) {
+        let value = detach(&value);
+        self.apply(browser, key, Some(value.clone()));
+        let message = Self::message(OP_SET);
+        let args = message.get_argument_list();
+        args.set_string(1, key);
+        if let Ok(value) = Value::try_from(value) {
+            args.set_value_inner(2, value);
+        }
+        browser.get_main_frame().send_process_message(message);
+    }
+    /// Removes `key` in both processes.
+    pub fn remove(&self, browser: &Browser, key: &str) {
+        self.apply(browser, key, None);
+        let message = Self::message(OP_REMOVE);
+        message.get_argument_list().set_string(1, key);
+        browser.get_main_frame().send_process_message(message);
+    }
+    /// Calls `listener` whenever a value changes in this process, either
+    /// locally or because of a message from the other process. The value is
+    /// `None` if the key was removed.
+    pub fn add_listener(
+        &self,
+        listener: impl 'static + Send + Sync + Fn(&Browser, &str, Option<&StoredValue>),
+    ) -> StoreListenerId {
+        let id = StoreListenerId(NEXT_LISTENER_ID.fetch_add(1, Ordering::SeqCst));
+        self.0.listeners.lock().push((id, Arc::new(listener)));
+        id
+    }
+    pub fn remove_listener(&self, id: StoreListenerId) {
+        self.0.listeners.lock().retain(|(listener_id, _)| *listener_id != id);
+    }
+
+    /// Requests the current state of `browser` from the browser process. Call
+    /// this from [RenderProcessHandlerCallbacks::on_browser_created](crate::render_process_handler::RenderProcessHandlerCallbacks::on_browser_created).
+    pub fn on_browser_created(&self, browser: &Browser) {
+        browser.get_main_frame().send_process_message(Self::message(OP_SYNC_REQUEST));
+    }
+    /// Drops the state of `browser` in this process.
+    pub fn on_browser_destroyed(&self, browser: &Browser) {
+        self.0.values.lock().remove(&browser.get_identifier());
+    }
+    /// Handles a message sent by the store of the other process. Returns true
+    /// if `message` belonged to the store.
+    pub fn on_process_message_received(&self, browser: &Browser, message: &ProcessMessage) -> bool {
+        if message.get_name().as_ref().map(|s| &**s) != Some(MESSAGE_NAME) {
+            return false;
+        }
+        let args = message.get_argument_list();
+        match args.get_string(0).as_ref().map(|s| &**s) {
+            Some(OP_SET) => {
+                if let (Some(key), Some(value)) = (args.get_string(1), args.get(2)) {
+                    self.apply(browser, &key, Some(detach(&value)));
+                }
+            }
+            Some(OP_REMOVE) => {
+                if let Some(key) = args.get_string(1) {
+                    self.apply(browser, &key, None);
+                }
+            }
+            Some(OP_SYNC_REQUEST) => {
+                let values = DictionaryValue::from(&self.get_all(browser.get_identifier()));
+                let reply = Self::message(OP_SYNC);
+                reply.get_argument_list().set_dictionary(1, values);
+                browser.get_main_frame().send_process_message(reply);
+            }
+            Some(OP_SYNC) => {
+                let values: HashMap<String, StoredValue> = args.get_dictionary(1)
+                    .map(|values| (&values).into())
+                    .unwrap_or_default();
+                let old = self.get_all(browser.get_identifier());
+                for key in old.keys().filter(|key| !values.contains_key(*key)) {
+                    self.apply(browser, key, None);
+                }
+                for (key, value) in values {
+                    self.apply(browser, &key, Some(detach(&value)));
+                }
+            }
+            _ => (),
+        }
+        true
+    }
+
+    fn message(op: &str) -> ProcessMessage {
+        let message = ProcessMessage::new(MESSAGE_NAME);
+        message.get_argument_list().set_string(0, op);
+        message
+    }
+    fn apply(&self, browser: &Browser, key: &str, value: Option<StoredValue>) {
+        let changed = {
+            let mut values = self.0.values.lock();
+            let values = values.entry(browser.get_identifier()).or_insert_with(HashMap::new);
+            let old = match &value {
+                Some(value) => values.insert(key.to_owned(), value.clone()),
+                None => values.remove(key),
+            };
+            match (&old, &value) {
+                (Some(old), Some(value)) => !old.deep_eq(value),
+                (None, None) => false,
+                _ => true,
+            }
+        };
+        // Syncs repeat values that didn't change, which listeners don't need
+        // to hear about.
+        if changed {
+            self.notify(browser, key, value.as_ref());
+        }
+    }
+    fn notify(&self, browser: &Browser, key: &str, value: Option<&StoredValue>) {
+        let listeners = self.0.listeners.lock().iter()
+            .map(|(_, listener)| listener.clone())
+            .collect::<Vec<_>>();
+        for listener in listeners {
+            listener(browser, key, value);
+        }
+    }
+}
+
+impl Default for BrowserStore {
+    fn default() -> Self {
+        BrowserStore::new()
+    }
+}
+
+/// Copies complex values so the store doesn't share data with message
+/// arguments or values still owned by the caller.
+fn detach(value: &StoredValue) -> StoredValue {
+    match value {
+        StoredValue::Binary(binary) => StoredValue::Binary(binary.deep_clone()),
+        StoredValue::Dictionary(dictionary) => StoredValue::Dictionary(dictionary.deep_clone()),
+        StoredValue::List(list) => StoredValue::List(list.deep_clone()),
+        value => value.clone(),
+    }
+}
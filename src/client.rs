@@ -14,19 +14,34 @@ use cef_sys::{
 use downcast_rs::{impl_downcast, Downcast};
 use std::ptr::null_mut;
 
+pub mod accelerators;
+pub mod audio_bus;
 pub mod audio_handler;
 pub mod context_menu_handler;
+pub mod default_client;
 pub mod dialog_handler;
 pub mod display_handler;
+pub mod display_provider;
+pub mod display_watcher;
 pub mod download_handler;
+pub mod download_manager;
+pub mod download_policy;
 pub mod drag_handler;
+pub mod find_controller;
 pub mod find_handler;
 pub mod focus_handler;
+pub mod frame_mailbox;
+pub mod frame_pool;
+pub mod fullscreen;
 pub mod js_dialog_handler;
 pub mod keyboard_handler;
 pub mod life_span_handler;
+pub mod menu_bar;
+pub mod popup_layer;
 pub mod render_handler;
 pub mod request_handler;
+pub mod staging;
+pub mod virtual_keyboard;
 
 use self::{
     audio_handler::AudioHandler,
@@ -0,0 +1,211 @@
+//! Application shortcuts that take precedence over the page.
+//!
+//! Windows of the views framework handle accelerators themselves: register
+//! them with [Window::set_accelerator](crate::views::Window::set_accelerator)
+//! and run the commands in
+//! [WindowDelegateCallbacks::on_accelerator](crate::views::WindowDelegateCallbacks::on_accelerator).
+//! They work while a [BrowserView](crate::views::BrowserView) has focus if
+//! it prefers accelerators, and pages never see the keys:
+//!
+//! ```ignore
+//! const NEW_TAB: i32 = 1;
+//! const SELECT_TAB_1: i32 = 100;
+//! window.set_accelerator(NEW_TAB, Accelerator::new(WindowsKeyCode::T, EventFlags::CONTROL_DOWN));
+//! window.set_accelerator_range(AcceleratorRange::new(SELECT_TAB_1, WindowsKeyCode::Key1, 8, EventFlags::CONTROL_DOWN));
+//! browser_view.set_prefer_accelerators(true);
+//! ```
+//!
+//! Windows the application creates itself have no accelerators, so
+//! [Accelerators] checks key events in
+//! [KeyboardHandlerCallbacks::on_pre_key_event], before the page sees them.
+//! Shortcuts like Ctrl+T or Ctrl+W then work while a browser has focus, and
+//! pages can neither observe nor cancel them:
+//!
+//! ```ignore
+//! const NEW_TAB: i32 = 1;
+//! let accelerators = Accelerators::new();
+//! accelerators.set_accelerator(NEW_TAB, Accelerator::new(WindowsKeyCode::T, EventFlags::CONTROL_DOWN));
+//! accelerators.on_accelerator(|browser, command_id| match command_id {
+//!     NEW_TAB => { open_tab(); true }
+//!     _ => false,
+//! });
+//! // in ClientCallbacks
+//! fn get_keyboard_handler(&self) -> Option<KeyboardHandler> { Some(self.accelerators.keyboard_handler()) }
+//! ```
+//!
+//! Applications with a keyboard handler of their own call
+//! [Accelerators::handle_key_event] from its `on_pre_key_event` instead.
+
+use crate::{
+    browser::Browser,
+    client::keyboard_handler::{KeyboardHandler, KeyboardHandlerCallbacks},
+    events::{EventFlags, KeyEvent, WindowsKeyCode},
+    views::Window,
+};
+use parking_lot::Mutex;
+use std::{collections::HashMap, sync::Arc};
+
+/// A key with the modifiers that have to be held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Accelerator {
+    pub key_code: WindowsKeyCode,
+    /// Any of [EventFlags::SHIFT_DOWN], [EventFlags::CONTROL_DOWN],
+    /// [EventFlags::ALT_DOWN] and [EventFlags::COMMAND_DOWN]. Other flags are
+    /// ignored.
+    pub modifiers: EventFlags,
+}
+
+impl Accelerator {
+    pub fn new(key_code: WindowsKeyCode, modifiers: EventFlags) -> Accelerator {
+        Accelerator {
+            key_code,
+            modifiers: modifiers & Accelerator::MODIFIERS,
+        }
+    }
+
+    /// Returns the accelerator of the key `offset` codes after this one,
+    /// with the same modifiers.
+    fn offset(self, offset: i32) -> Accelerator {
+        Accelerator {
+            key_code: WindowsKeyCode::new(self.key_code.get() + offset),
+            modifiers: self.modifiers,
+        }
+    }
+
+    /// The flags an accelerator distinguishes.
+    const MODIFIERS: EventFlags = EventFlags::from_bits_truncate(
+        EventFlags::SHIFT_DOWN.bits() | EventFlags::CONTROL_DOWN.bits() | EventFlags::ALT_DOWN.bits() | EventFlags::COMMAND_DOWN.bits(),
+    );
+}
+
+/// Consecutive keys that run consecutive commands, like Ctrl+1 to Ctrl+8 for
+/// selecting a tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AcceleratorRange {
+    pub first_command_id: i32,
+    /// The accelerator of the first command.
+    pub first: Accelerator,
+    pub count: i32,
+}
+
+impl AcceleratorRange {
+    /// Runs `first_command_id + n` when the key `first_key_code + n` is
+    /// pressed with `modifiers`, for every `n` below `count`.
+    pub fn new(first_command_id: i32, first_key_code: WindowsKeyCode, count: i32, modifiers: EventFlags) -> AcceleratorRange {
+        AcceleratorRange {
+            first_command_id,
+            first: Accelerator::new(first_key_code, modifiers),
+            count: count.max(0),
+        }
+    }
+    /// Returns the command ids with their accelerators.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, Accelerator)> {
+        let range = *self;
+        (0..range.count).map(move |n| (range.first_command_id + n, range.first.offset(n)))
+    }
+}
+
+type AcceleratorCallback = dyn 'static + Send + Fn(&Browser, i32) -> bool;
+
+struct AcceleratorState {
+    commands: HashMap<Accelerator, i32>,
+    callback: Option<Arc<AcceleratorCallback>>,
+    /// The browser whose next character event belongs to a handled
+    /// accelerator.
+    suppress_char: Option<i32>,
+}
+
+/// Maps key combinations to command ids. See the
+/// [module documentation](self).
+///
+/// Cloning is cheap; clones share the accelerators.
+#[derive(Clone)]
+pub struct Accelerators(Arc<Mutex<AcceleratorState>>);
+
+impl Accelerators {
+    pub fn new() -> Accelerators {
+        Accelerators(Arc::new(Mutex::new(AcceleratorState {
+            commands: HashMap::new(),
+            callback: None,
+            suppress_char: None,
+        })))
+    }
+    /// Runs `command_id` when `accelerator` is pressed, replacing the command
+    /// it ran before.
+    pub fn set_accelerator(&self, command_id: i32, accelerator: Accelerator) {
+        self.0.lock().commands.insert(accelerator, command_id);
+    }
+    /// Sets the accelerators of all commands of `range`.
+    pub fn set_accelerator_range(&self, range: AcceleratorRange) {
+        self.0.lock().commands.extend(range.iter().map(|(command_id, accelerator)| (accelerator, command_id)));
+    }
+    /// Removes the accelerators of `command_id`.
+    pub fn remove_accelerator(&self, command_id: i32) {
+        self.0.lock().commands.retain(|_, id| *id != command_id);
+    }
+    pub fn remove_all_accelerators(&self) {
+        self.0.lock().commands.clear();
+    }
+    /// Returns the command run by `accelerator`.
+    pub fn command_for(&self, accelerator: Accelerator) -> Option<i32> {
+        self.0.lock().commands.get(&accelerator).copied()
+    }
+    /// Calls `callback` on the UI thread when an accelerator is pressed in a
+    /// browser. If it returns false, the key event goes on to the page.
+    pub fn on_accelerator(&self, callback: impl 'static + Send + Fn(&Browser, i32) -> bool) {
+        self.0.lock().callback = Some(Arc::new(callback));
+    }
+    /// Registers all accelerators with `window`, e.g. to use the same
+    /// shortcuts in windows of the views framework. Later changes aren't
+    /// registered.
+    pub fn register_with(&self, window: &Window) {
+        let commands: Vec<_> = self.0.lock().commands.iter().map(|(accelerator, command_id)| (*command_id, *accelerator)).collect();
+        for (command_id, accelerator) in commands {
+            window.set_accelerator(command_id, accelerator);
+        }
+    }
+    pub fn keyboard_handler(&self) -> KeyboardHandler {
+        KeyboardHandler::new(self.clone())
+    }
+    /// Runs the command of `event`, if it is an accelerator. Returns true if
+    /// the event was handled and must not reach the page.
+    pub fn handle_key_event(&self, browser: &Browser, event: &KeyEvent) -> bool {
+        let browser_id = browser.get_identifier();
+        let (command_id, callback) = {
+            let mut state = self.0.lock();
+            let accelerator = match *event {
+                KeyEvent::KeyDown { modifiers, windows_key_code, .. } => Accelerator::new(windows_key_code, modifiers),
+                KeyEvent::KeyUp { .. } => return false,
+                KeyEvent::Char { .. } => return state.suppress_char.take() == Some(browser_id),
+            };
+            state.suppress_char = None;
+            match (state.commands.get(&accelerator), &state.callback) {
+                (Some(command_id), Some(callback)) => (*command_id, callback.clone()),
+                _ => return false,
+            }
+        };
+        if callback(browser, command_id) {
+            self.0.lock().suppress_char = Some(browser_id);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for Accelerators {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeyboardHandlerCallbacks for Accelerators {
+    fn on_pre_key_event(
+        &mut self,
+        browser: Browser,
+        event: KeyEvent,
+        is_keyboard_shortcut: &mut bool,
+    ) -> bool {
+        self.handle_key_event(&browser, &event)
+    }
+}
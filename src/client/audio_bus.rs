@@ -0,0 +1,219 @@
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    slice,
+    sync::Arc,
+    time::Instant,
+};
+
+use crate::{
+    browser::Browser,
+    client::audio_handler::{AudioHandler, AudioHandlerCallbacks, AudioParameters, ChannelLayout},
+};
+
+/// Receives the mixed-down audio of an [AudioBus]. Called on the same threads
+/// as the corresponding [AudioHandlerCallbacks] functions.
+pub trait AudioBusSink: 'static + Send + Sync {
+    /// Configures the streams of all browsers. Return `false` to cancel the
+    /// stream. The default keeps CEF's parameters.
+    fn get_audio_parameters(&self, browser: &Browser, params: &mut AudioParameters) -> bool {
+        true
+    }
+    fn on_stream_started(&self, browser: &Browser, info: &AudioStreamInfo);
+    /// Receives a packet of planar PCM data with the browser's gain applied:
+    /// one slice of `frames` samples per channel.
+    fn on_stream_packet(&self, browser: &Browser, channels: &[&[f32]], frames: usize, pts: i64);
+    fn on_stream_stopped(&self, browser: &Browser);
+    fn on_stream_error(&self, browser: &Browser, message: &str) {}
+}
+
+/// An audio stream tracked by an [AudioBus].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioStreamInfo {
+    pub browser_id: i32,
+    pub channel_layout: ChannelLayout,
+    pub channels: usize,
+    pub sample_rate: i32,
+    pub frames_per_buffer: i32,
+    pub started: Instant,
+    /// The gain applied to the stream's latest packet.
+    pub gain: f32,
+}
+
+struct BusState {
+    streams: HashMap<i32, AudioStreamInfo>,
+    volumes: HashMap<i32, f32>,
+    muted: HashMap<i32, bool>,
+    solo: Option<i32>,
+    /// The browser that keeps full volume and the gain of all others.
+    ducking: Option<(i32, f32)>,
+}
+
+impl BusState {
+    fn gain(&self, browser_id: i32) -> f32 {
+        if self.muted.get(&browser_id).copied().unwrap_or(false) {
+            return 0.0;
+        }
+        if matches!(self.solo, Some(solo) if solo != browser_id) {
+            return 0.0;
+        }
+        let volume = self.volumes.get(&browser_id).copied().unwrap_or(1.0);
+        match self.ducking {
+            Some((focus, level)) if focus != browser_id => volume * level,
+            _ => volume,
+        }
+    }
+}
+
+/// Mixing controls for the audio of many browsers, for video walls and other
+/// multi-view applications.
+///
+/// The bus tracks every audio stream started by browsers whose client returns
+/// its [AudioBus::handler], scales their PCM data by a per-browser gain and
+/// hands the result to an [AudioBusSink], i.e. the application's mixer. The
+/// gain combines the browser's volume, mute state, solo and ducking. Gain
+/// changes are ramped over one packet so they don't click.
+///
+/// Unlike [BrowserHost::set_audio_muted](crate::browser_host::BrowserHost::set_audio_muted),
+/// a browser muted on the bus keeps delivering (silent) packets, so the mixer's
+/// timing stays intact.
+#[derive(Clone)]
+pub struct AudioBus {
+    state: Arc<Mutex<BusState>>,
+    sink: Arc<dyn AudioBusSink>,
+}
+
+impl AudioBus {
+    pub fn new(sink: impl AudioBusSink) -> AudioBus {
+        AudioBus {
+            state: Arc::new(Mutex::new(BusState {
+                streams: HashMap::new(),
+                volumes: HashMap::new(),
+                muted: HashMap::new(),
+                solo: None,
+                ducking: None,
+            })),
+            sink: Arc::new(sink),
+        }
+    }
+    /// Returns an audio handler feeding this bus. Return it from the clients
+    /// of all browsers that should be mixed.
+    pub fn handler(&self) -> AudioHandler {
+        AudioHandler::new(self.clone())
+    }
+    /// Returns the streams that are currently playing.
+    pub fn streams(&self) -> Vec<AudioStreamInfo> {
+        self.state.lock().streams.values().copied().collect()
+    }
+    pub fn is_playing(&self, browser_id: i32) -> bool {
+        self.state.lock().streams.contains_key(&browser_id)
+    }
+    /// Sets the volume of a browser, 1.0 being unchanged. Values above 1.0
+    /// amplify and may clip.
+    pub fn set_volume(&self, browser_id: i32, volume: f32) {
+        self.state.lock().volumes.insert(browser_id, volume.max(0.0));
+    }
+    pub fn volume(&self, browser_id: i32) -> f32 {
+        self.state.lock().volumes.get(&browser_id).copied().unwrap_or(1.0)
+    }
+    pub fn set_muted(&self, browser_id: i32, muted: bool) {
+        self.state.lock().muted.insert(browser_id, muted);
+    }
+    pub fn is_muted(&self, browser_id: i32) -> bool {
+        self.state.lock().muted.get(&browser_id).copied().unwrap_or(false)
+    }
+    /// Silences every browser except `browser_id`, or ends soloing if `None`.
+    pub fn solo(&self, browser_id: Option<i32>) {
+        self.state.lock().solo = browser_id;
+    }
+    pub fn soloed(&self) -> Option<i32> {
+        self.state.lock().solo
+    }
+    /// Scales every browser except `browser_id` by `level`, e.g. 0.2 while
+    /// one tile plays an announcement.
+    pub fn duck_others(&self, browser_id: i32, level: f32) {
+        self.state.lock().ducking = Some((browser_id, level.max(0.0).min(1.0)));
+    }
+    pub fn clear_ducking(&self) {
+        self.state.lock().ducking = None;
+    }
+    /// Returns the gain currently applied to the audio of `browser_id`.
+    pub fn gain(&self, browser_id: i32) -> f32 {
+        self.state.lock().gain(browser_id)
+    }
+    /// Forgets the volume and mute state of a browser, e.g. after it closed.
+    pub fn remove_browser(&self, browser_id: i32) {
+        let mut state = self.state.lock();
+        state.streams.remove(&browser_id);
+        state.volumes.remove(&browser_id);
+        state.muted.remove(&browser_id);
+        if state.solo == Some(browser_id) {
+            state.solo = None;
+        }
+        if matches!(state.ducking, Some((focus, _)) if focus == browser_id) {
+            state.ducking = None;
+        }
+    }
+}
+
+impl AudioHandlerCallbacks for AudioBus {
+    fn get_audio_parameters(&self, browser: Browser, params: &mut AudioParameters) -> bool {
+        self.sink.get_audio_parameters(&browser, params)
+    }
+    fn on_audio_stream_started(&self, browser: Browser, params: &AudioParameters, channels: usize) {
+        let browser_id = browser.get_identifier();
+        let info = {
+            let mut state = self.state.lock();
+            let info = AudioStreamInfo {
+                browser_id,
+                channel_layout: params.channel_layout,
+                channels,
+                sample_rate: params.sample_rate,
+                frames_per_buffer: params.frames_per_buffer,
+                started: Instant::now(),
+                gain: state.gain(browser_id),
+            };
+            state.streams.insert(browser_id, info);
+            info
+        };
+        self.sink.on_stream_started(&browser, &info);
+    }
+    fn on_audio_stream_packet(&self, browser: Browser, data: &[&f32], frames: usize, pts: i64) {
+        let browser_id = browser.get_identifier();
+        let (from, to) = {
+            let mut state = self.state.lock();
+            let to = state.gain(browser_id);
+            match state.streams.get_mut(&browser_id) {
+                Some(stream) => (std::mem::replace(&mut stream.gain, to), to),
+                None => (to, to),
+            }
+        };
+        // Each element points to the first sample of a channel.
+        let input = data.iter()
+            .map(|channel| unsafe { slice::from_raw_parts(*channel as *const f32, frames) })
+            .collect::<Vec<_>>();
+        if from == 1.0 && to == 1.0 {
+            self.sink.on_stream_packet(&browser, &input, frames, pts);
+            return;
+        }
+        let step = (to - from) / frames.max(1) as f32;
+        let scaled = input.iter()
+            .map(|channel| {
+                channel.iter()
+                    .enumerate()
+                    .map(|(i, sample)| sample * (from + step * (i + 1) as f32))
+                    .collect::<Vec<f32>>()
+            })
+            .collect::<Vec<_>>();
+        let scaled = scaled.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        self.sink.on_stream_packet(&browser, &scaled, frames, pts);
+    }
+    fn on_audio_stream_stopped(&self, browser: Browser) {
+        self.state.lock().streams.remove(&browser.get_identifier());
+        self.sink.on_stream_stopped(&browser);
+    }
+    fn on_audio_stream_error(&self, browser: Browser, message: &str) {
+        self.state.lock().streams.remove(&browser.get_identifier());
+        self.sink.on_stream_error(&browser, message);
+    }
+}
@@ -61,7 +61,7 @@ impl MenuModel {
     /// Returns the number of items in this menu.
     pub fn get_count(&self) -> usize {
         unsafe {
-            c(self.0.clear.unwrap()(self.as_ptr()))
+            c(self.0.get_count.unwrap()(self.as_ptr()))
         }
     }
     /// Add a separator to the menu. Returns `true` on success.
@@ -0,0 +1,247 @@
+//! A [ClientCallbacks] implementation with reasonable behavior out of the box.
+//!
+//! Without handlers CEF silently drops popups when windowless rendering is
+//! used and cancels every download. [DefaultClient] instead:
+//!
+//! - shows file and JavaScript dialogs natively, by not providing handlers,
+//! - opens popups in the browser that requested them,
+//! - saves downloads to the user's Downloads directory and logs their progress,
+//!   see [DownloadPolicy],
+//! - forwards console messages to the `log` crate.
+//!
+//! Any handler can be replaced with the builder functions, so applications
+//! only implement what they need:
+//!
+//! ```ignore
+//! let client = Client::new(DefaultClient::new().render_handler(RenderHandler::new(MyRenderer)));
+//! ```
+
+use crate::{
+    browser::{Browser, BrowserSettings},
+    client::{
+        audio_handler::AudioHandler,
+        context_menu_handler::ContextMenuHandler,
+        dialog_handler::DialogHandler,
+        display_handler::{DisplayHandler, DisplayHandlerCallbacks},
+        download_handler::DownloadHandler,
+        download_policy::DownloadPolicy,
+        drag_handler::DragHandler,
+        find_handler::FindHandler,
+        focus_handler::FocusHandler,
+        js_dialog_handler::JsDialogHandler,
+        keyboard_handler::KeyboardHandler,
+        life_span_handler::{LifeSpanHandler, LifeSpanHandlerCallbacks, PopupFeatures},
+        render_handler::RenderHandler,
+        request_handler::{RequestHandler, WindowOpenDisposition},
+        Client, ClientCallbacks,
+    },
+    frame::Frame,
+    load_handler::LoadHandler,
+    settings::LogSeverity,
+    values::DictionaryValue,
+    window::WindowInfo,
+};
+
+/// See the [module documentation](self).
+pub struct DefaultClient {
+    audio_handler: Option<AudioHandler>,
+    context_menu_handler: Option<ContextMenuHandler>,
+    dialog_handler: Option<DialogHandler>,
+    display_handler: Option<DisplayHandler>,
+    download_handler: Option<DownloadHandler>,
+    drag_handler: Option<DragHandler>,
+    find_handler: Option<FindHandler>,
+    focus_handler: Option<FocusHandler>,
+    jsdialog_handler: Option<JsDialogHandler>,
+    keyboard_handler: Option<KeyboardHandler>,
+    life_span_handler: Option<LifeSpanHandler>,
+    load_handler: Option<LoadHandler>,
+    render_handler: Option<RenderHandler>,
+    request_handler: Option<RequestHandler>,
+}
+
+impl DefaultClient {
+    /// Creates a client saving downloads according to [DownloadPolicy::new].
+    pub fn new() -> DefaultClient {
+        DefaultClient {
+            audio_handler: None,
+            context_menu_handler: None,
+            dialog_handler: None,
+            display_handler: Some(DisplayHandler::new(ConsoleLogger)),
+            download_handler: Some(DownloadHandler::with_policy(DownloadPolicy::new())),
+            drag_handler: None,
+            find_handler: None,
+            focus_handler: None,
+            jsdialog_handler: None,
+            keyboard_handler: None,
+            life_span_handler: Some(LifeSpanHandler::new(SameBrowserPopups)),
+            load_handler: None,
+            render_handler: None,
+            request_handler: None,
+        }
+    }
+    /// Saves downloads according to `policy` instead of the default policy.
+    pub fn download_policy(mut self, policy: DownloadPolicy) -> Self {
+        self.download_handler = Some(DownloadHandler::with_policy(policy));
+        self
+    }
+    pub fn audio_handler(mut self, audio_handler: AudioHandler) -> Self {
+        self.audio_handler = Some(audio_handler);
+        self
+    }
+    pub fn context_menu_handler(mut self, context_menu_handler: ContextMenuHandler) -> Self {
+        self.context_menu_handler = Some(context_menu_handler);
+        self
+    }
+    pub fn dialog_handler(mut self, dialog_handler: DialogHandler) -> Self {
+        self.dialog_handler = Some(dialog_handler);
+        self
+    }
+    /// Replaces the handler logging console messages.
+    pub fn display_handler(mut self, display_handler: DisplayHandler) -> Self {
+        self.display_handler = Some(display_handler);
+        self
+    }
+    /// Replaces the handler saving downloads.
+    pub fn download_handler(mut self, download_handler: DownloadHandler) -> Self {
+        self.download_handler = Some(download_handler);
+        self
+    }
+    pub fn drag_handler(mut self, drag_handler: DragHandler) -> Self {
+        self.drag_handler = Some(drag_handler);
+        self
+    }
+    pub fn find_handler(mut self, find_handler: FindHandler) -> Self {
+        self.find_handler = Some(find_handler);
+        self
+    }
+    pub fn focus_handler(mut self, focus_handler: FocusHandler) -> Self {
+        self.focus_handler = Some(focus_handler);
+        self
+    }
+    pub fn jsdialog_handler(mut self, jsdialog_handler: JsDialogHandler) -> Self {
+        self.jsdialog_handler = Some(jsdialog_handler);
+        self
+    }
+    pub fn keyboard_handler(mut self, keyboard_handler: KeyboardHandler) -> Self {
+        self.keyboard_handler = Some(keyboard_handler);
+        self
+    }
+    /// Replaces the handler opening popups in the same browser.
+    pub fn life_span_handler(mut self, life_span_handler: LifeSpanHandler) -> Self {
+        self.life_span_handler = Some(life_span_handler);
+        self
+    }
+    pub fn load_handler(mut self, load_handler: LoadHandler) -> Self {
+        self.load_handler = Some(load_handler);
+        self
+    }
+    pub fn render_handler(mut self, render_handler: RenderHandler) -> Self {
+        self.render_handler = Some(render_handler);
+        self
+    }
+    pub fn request_handler(mut self, request_handler: RequestHandler) -> Self {
+        self.request_handler = Some(request_handler);
+        self
+    }
+}
+
+impl Default for DefaultClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientCallbacks for DefaultClient {
+    fn get_audio_handler(&self) -> Option<AudioHandler> {
+        self.audio_handler.clone()
+    }
+    fn get_context_menu_handler(&self) -> Option<ContextMenuHandler> {
+        self.context_menu_handler.clone()
+    }
+    fn get_dialog_handler(&self) -> Option<DialogHandler> {
+        self.dialog_handler.clone()
+    }
+    fn get_display_handler(&self) -> Option<DisplayHandler> {
+        self.display_handler.clone()
+    }
+    fn get_download_handler(&self) -> Option<DownloadHandler> {
+        self.download_handler.clone()
+    }
+    fn get_drag_handler(&self) -> Option<DragHandler> {
+        self.drag_handler.clone()
+    }
+    fn get_find_handler(&self) -> Option<FindHandler> {
+        self.find_handler.clone()
+    }
+    fn get_focus_handler(&self) -> Option<FocusHandler> {
+        self.focus_handler.clone()
+    }
+    fn get_jsdialog_handler(&self) -> Option<JsDialogHandler> {
+        self.jsdialog_handler.clone()
+    }
+    fn get_keyboard_handler(&self) -> Option<KeyboardHandler> {
+        self.keyboard_handler.clone()
+    }
+    fn get_life_span_handler(&self) -> Option<LifeSpanHandler> {
+        self.life_span_handler.clone()
+    }
+    fn get_load_handler(&self) -> Option<LoadHandler> {
+        self.load_handler.clone()
+    }
+    fn get_render_handler(&self) -> Option<RenderHandler> {
+        self.render_handler.clone()
+    }
+    fn get_request_handler(&self) -> Option<RequestHandler> {
+        self.request_handler.clone()
+    }
+}
+
+/// Loads popups into the main frame of the browser that opened them.
+struct SameBrowserPopups;
+
+impl LifeSpanHandlerCallbacks for SameBrowserPopups {
+    fn on_before_popup(
+        &self,
+        browser: Browser,
+        frame: Frame,
+        target_url: Option<&str>,
+        target_frame_name: Option<&str>,
+        target_disposition: WindowOpenDisposition,
+        user_gesture: bool,
+        popup_features: PopupFeatures,
+        window_info: &mut WindowInfo,
+        client: &mut Client,
+        settings: &mut BrowserSettings,
+        extra_info: &mut DictionaryValue,
+        no_javascript_access: &mut bool,
+    ) -> bool {
+        if let Some(url) = target_url {
+            browser.get_main_frame().load_url(url);
+        }
+        true
+    }
+}
+
+/// Forwards console messages to the `log` crate.
+struct ConsoleLogger;
+
+impl DisplayHandlerCallbacks for ConsoleLogger {
+    fn on_console_message(
+        &mut self,
+        browser: Browser,
+        level: LogSeverity,
+        message: &str,
+        source: &str,
+        line: usize,
+    ) -> bool {
+        let level = match level {
+            LogSeverity::Fatal | LogSeverity::Error => log::Level::Error,
+            LogSeverity::Warning => log::Level::Warn,
+            LogSeverity::Verbose => log::Level::Debug,
+            _ => log::Level::Info,
+        };
+        log::log!(target: "cef::console", level, "{} ({}:{})", message, source, line);
+        false
+    }
+}
@@ -0,0 +1,188 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::{
+    client::render_handler::ScreenInfo,
+    values::{Point, Rect},
+};
+
+/// A monitor, in screen coordinates of device-independent pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Display {
+    /// Identifies the display among those of a [DisplayProvider].
+    pub id: u64,
+    pub name: String,
+    /// The bounds of the display.
+    pub rect: Rect,
+    /// The bounds minus task bars and docks.
+    pub work_area: Rect,
+    pub scale_factor: f32,
+    /// Bits per pixel, normally 24.
+    pub depth: u32,
+    pub primary: bool,
+}
+
+impl Display {
+    /// Returns the screen information to report for browsers on this display.
+    pub fn screen_info(&self) -> ScreenInfo {
+        ScreenInfo {
+            device_scale_factor: self.scale_factor,
+            depth: self.depth,
+            depth_per_component: self.depth / 3,
+            is_monochrome: false,
+            rect: self.rect,
+            available_rect: self.work_area,
+        }
+    }
+}
+
+/// Returns the area `a` and `b` have in common.
+fn overlap(a: Rect, b: Rect) -> i64 {
+    let width = (a.x + a.width).min(b.x + b.width) - a.x.max(b.x);
+    let height = (a.y + a.height).min(b.y + b.height) - a.y.max(b.y);
+    if width <= 0 || height <= 0 {
+        0
+    } else {
+        width as i64 * height as i64
+    }
+}
+
+/// Lists the displays of the system, so windowless browsers report the right
+/// `window.screen` values and place popups on the right monitor.
+pub trait DisplayProvider: 'static + Send + Sync {
+    fn displays(&self) -> Vec<Display>;
+    /// Returns the primary display, or the first one if none is marked.
+    fn primary(&self) -> Option<Display> {
+        let displays = self.displays();
+        displays.iter().find(|display| display.primary).cloned().or_else(|| displays.into_iter().next())
+    }
+    /// Returns the display `rect` overlaps most, falling back to the primary
+    /// display if it overlaps none.
+    fn display_for_rect(&self, rect: Rect) -> Option<Display> {
+        self.displays()
+            .into_iter()
+            .map(|display| (overlap(display.rect, rect), display))
+            .filter(|(area, _)| *area > 0)
+            .max_by_key(|(area, _)| *area)
+            .map(|(_, display)| display)
+            .or_else(|| self.primary())
+    }
+}
+
+/// A fixed list of displays, updated by the application when monitors change.
+#[derive(Clone, Default)]
+pub struct StaticDisplays(Arc<Mutex<Vec<Display>>>);
+
+impl StaticDisplays {
+    pub fn new(displays: Vec<Display>) -> StaticDisplays {
+        StaticDisplays(Arc::new(Mutex::new(displays)))
+    }
+    /// Replaces the displays, e.g. after a monitor was connected.
+    pub fn set(&self, displays: Vec<Display>) {
+        *self.0.lock() = displays;
+    }
+}
+
+impl DisplayProvider for StaticDisplays {
+    fn displays(&self) -> Vec<Display> {
+        self.0.lock().clone()
+    }
+}
+
+/// Answers the screen-related render handler queries of windowless browsers
+/// shown in one host window, from the window's position and a
+/// [DisplayProvider].
+///
+/// Return [ScreenGeometry::root_screen_rect], [ScreenGeometry::screen_point]
+/// and [ScreenGeometry::screen_info] from the
+/// [RenderHandlerCallbacks](crate::client::render_handler::RenderHandlerCallbacks)
+/// functions of the same names, and report window moves and resizes with
+/// [ScreenGeometry::set_window_rect]. When the window moves to another display,
+/// call [BrowserHost::notify_screen_info_changed](crate::browser_host::BrowserHost::notify_screen_info_changed),
+/// or use a [DisplayWatcher](crate::client::display_watcher::DisplayWatcher)
+/// fed with [ScreenGeometry::screen_info].
+#[derive(Clone)]
+pub struct ScreenGeometry<P: DisplayProvider> {
+    provider: Arc<P>,
+    /// The client area of the host window in screen coordinates.
+    window_rect: Arc<Mutex<Rect>>,
+}
+
+impl<P: DisplayProvider> ScreenGeometry<P> {
+    pub fn new(provider: P, window_rect: Rect) -> ScreenGeometry<P> {
+        ScreenGeometry {
+            provider: Arc::new(provider),
+            window_rect: Arc::new(Mutex::new(window_rect)),
+        }
+    }
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+    /// Records the client area of the host window in screen coordinates.
+    /// Returns true if the window is now on a different display.
+    pub fn set_window_rect(&self, rect: Rect) -> bool {
+        let old = std::mem::replace(&mut *self.window_rect.lock(), rect);
+        let id = |rect| self.provider.display_for_rect(rect).map(|display| display.id);
+        id(old) != id(rect)
+    }
+    pub fn window_rect(&self) -> Rect {
+        *self.window_rect.lock()
+    }
+    /// Returns the display the window is on.
+    pub fn current_display(&self) -> Option<Display> {
+        self.provider.display_for_rect(self.window_rect())
+    }
+    pub fn root_screen_rect(&self) -> Option<Rect> {
+        Some(self.window_rect())
+    }
+    /// Translates a point in view coordinates to screen coordinates.
+    pub fn screen_point(&self, view_point: Point) -> Option<Point> {
+        let window_rect = self.window_rect();
+        Some(Point {
+            x: window_rect.x + view_point.x,
+            y: window_rect.y + view_point.y,
+        })
+    }
+    pub fn screen_info(&self) -> Option<ScreenInfo> {
+        self.current_display().map(|display| display.screen_info())
+    }
+}
+
+#[cfg(feature = "winit-event-loop")]
+mod winit_displays {
+    use super::*;
+    use winit_event_loop::monitor::MonitorHandle;
+
+    impl StaticDisplays {
+        /// Creates the displays from winit's monitors, e.g.
+        /// `event_loop.available_monitors()`. winit doesn't report work
+        /// areas, so they equal the display bounds.
+        pub fn from_winit_monitors(monitors: impl IntoIterator<Item = MonitorHandle>, primary: &MonitorHandle) -> StaticDisplays {
+            let displays = monitors
+                .into_iter()
+                .enumerate()
+                .map(|(i, monitor)| {
+                    let scale_factor = monitor.scale_factor();
+                    let position = monitor.position().to_logical::<i32>(scale_factor);
+                    let size = monitor.size().to_logical::<i32>(scale_factor);
+                    let rect = Rect {
+                        x: position.x,
+                        y: position.y,
+                        width: size.width,
+                        height: size.height,
+                    };
+                    Display {
+                        id: i as u64,
+                        name: monitor.name().unwrap_or_default(),
+                        rect,
+                        work_area: rect,
+                        scale_factor: scale_factor as f32,
+                        depth: 24,
+                        primary: monitor == *primary,
+                    }
+                })
+                .collect();
+            StaticDisplays::new(displays)
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::{
+    browser_host::BrowserHost,
+    client::render_handler::ScreenInfo,
+    task::{TaskRunner, ThreadId},
+    values::{Rect, Size},
+};
+
+struct WatcherState {
+    screen_info: ScreenInfo,
+    view_rect: Rect,
+    hosts: Vec<BrowserHost>,
+}
+
+/// Keeps the screen information and view rectangle of windowless browsers in
+/// sync with the host window as it moves between monitors with different scale
+/// factors.
+///
+/// Return [DisplayWatcher::screen_info] and [DisplayWatcher::view_rect] from
+/// [RenderHandlerCallbacks::get_screen_info] and
+/// [RenderHandlerCallbacks::get_view_rect], and forward the host window's DPI
+/// and size changes to the watcher. On every change the watcher updates its
+/// stored values first and then calls
+/// [BrowserHost::notify_screen_info_changed] followed by
+/// [BrowserHost::was_resized] for each registered browser. That order matters:
+/// the browser must know the new scale factor before it lays out the new view
+/// size, otherwise it paints one frame at the wrong resolution.
+///
+/// [RenderHandlerCallbacks::get_screen_info]: crate::client::render_handler::RenderHandlerCallbacks::get_screen_info
+/// [RenderHandlerCallbacks::get_view_rect]: crate::client::render_handler::RenderHandlerCallbacks::get_view_rect
+#[derive(Clone)]
+pub struct DisplayWatcher(Arc<Mutex<WatcherState>>);
+
+impl DisplayWatcher {
+    /// Creates a watcher with the initial screen information and view
+    /// rectangle, in view (device-independent) coordinates.
+    pub fn new(screen_info: ScreenInfo, view_rect: Rect) -> DisplayWatcher {
+        DisplayWatcher(Arc::new(Mutex::new(WatcherState {
+            screen_info,
+            view_rect,
+            hosts: Vec::new(),
+        })))
+    }
+    /// Registers a browser to be notified of display changes.
+    pub fn add_browser(&self, host: &BrowserHost) {
+        self.0.lock().hosts.push(host.clone());
+    }
+    /// Unregisters a browser, e.g. from [LifeSpanHandlerCallbacks::on_before_close](crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_before_close).
+    pub fn remove_browser(&self, host: &BrowserHost) {
+        self.0.lock().hosts.retain(|h| h.as_ptr() != host.as_ptr());
+    }
+    pub fn screen_info(&self) -> ScreenInfo {
+        self.0.lock().screen_info
+    }
+    pub fn view_rect(&self) -> Rect {
+        self.0.lock().view_rect
+    }
+    /// Replaces the screen information, e.g. after the window moved to
+    /// another monitor.
+    pub fn set_screen_info(&self, screen_info: ScreenInfo) {
+        self.0.lock().screen_info = screen_info;
+        self.notify(true);
+    }
+    /// Sets a new view rectangle in view coordinates.
+    pub fn set_view_rect(&self, view_rect: Rect) {
+        self.0.lock().view_rect = view_rect;
+        self.notify(false);
+    }
+    /// Handles a change of the host window's scale factor, as reported by
+    /// winit's `WindowEvent::ScaleFactorChanged`. `physical_size` is the new
+    /// size of the window's client area in physical pixels, if known; the view
+    /// rectangle keeps its logical size otherwise.
+    pub fn on_scale_factor_changed(&self, scale_factor: f64, physical_size: Option<Size>) {
+        {
+            let mut state = self.0.lock();
+            state.screen_info.device_scale_factor = scale_factor as f32;
+            if let Some(size) = physical_size {
+                state.view_rect.width = (size.width as f64 / scale_factor).round() as i32;
+                state.view_rect.height = (size.height as f64 / scale_factor).round() as i32;
+            }
+        }
+        self.notify(true);
+    }
+    /// Handles a Win32 `WM_DPICHANGED` message of `hwnd`. Returns the window
+    /// rectangle suggested by the system, in physical screen coordinates,
+    /// which the application should apply with `SetWindowPos`. The view
+    /// rectangle gets the size of the client area within it.
+    #[cfg(target_os = "windows")]
+    pub fn on_wm_dpichanged(
+        &self,
+        hwnd: winapi::shared::windef::HWND,
+        wparam: winapi::shared::minwindef::WPARAM,
+        lparam: winapi::shared::minwindef::LPARAM,
+    ) -> Rect {
+        use winapi::{
+            shared::windef::RECT,
+            um::winuser::{AdjustWindowRectExForDpi, GetMenu, GetWindowLongW, GWL_EXSTYLE, GWL_STYLE},
+        };
+
+        // The X and Y DPI are always identical for this message.
+        let dpi = (wparam & 0xFFFF) as u32;
+        let suggested = unsafe { &*(lparam as *const RECT) };
+        // The suggested rectangle includes the frame; measure the frame at
+        // the new DPI by growing an empty client rectangle.
+        let mut frame = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+        let adjusted = unsafe {
+            AdjustWindowRectExForDpi(
+                &mut frame,
+                GetWindowLongW(hwnd, GWL_STYLE) as u32,
+                !GetMenu(hwnd).is_null() as i32,
+                GetWindowLongW(hwnd, GWL_EXSTYLE) as u32,
+                dpi,
+            ) != 0
+        };
+        let (frame_width, frame_height) = if adjusted { (frame.right - frame.left, frame.bottom - frame.top) } else { (0, 0) };
+        let window_size = Size {
+            width: suggested.right - suggested.left,
+            height: suggested.bottom - suggested.top,
+        };
+        let client_size = Size {
+            width: (window_size.width - frame_width).max(0),
+            height: (window_size.height - frame_height).max(0),
+        };
+        self.on_scale_factor_changed(f64::from(dpi) / 96.0, Some(client_size));
+        Rect {
+            x: suggested.left,
+            y: suggested.top,
+            width: window_size.width,
+            height: window_size.height,
+        }
+    }
+
+    fn notify(&self, screen_info_changed: bool) {
+        let hosts = self.0.lock().hosts.clone();
+        let notify = move || {
+            for host in &hosts {
+                if screen_info_changed {
+                    host.notify_screen_info_changed();
+                }
+                host.was_resized();
+            }
+        };
+        if TaskRunner::currently_on(ThreadId::UI) {
+            notify();
+        } else {
+            TaskRunner::post_task_on(ThreadId::UI, notify);
+        }
+    }
+}
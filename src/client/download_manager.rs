@@ -0,0 +1,657 @@
+//! Downloads that survive interruptions and restarts.
+//!
+//! A [DownloadManager] records every unfinished download in a state file:
+//! its URL, target path, received bytes and, once known, the validators of
+//! the response. Use [DownloadManager::handler] as the download handler and
+//! call [DownloadManager::resume_all] after start-up, or when the network is
+//! back:
+//!
+//! ```ignore
+//! let downloads = DownloadManager::new(data_dir.join("downloads.json"), DownloadPolicy::new().directory(&updates_dir));
+//! // in the client:
+//! download_handler: Some(downloads.handler()),
+//! // once CEF is initialized:
+//! downloads.resume_all(None, |record, result| log::info!("{}: {:?}", record.url, result.is_ok()));
+//! ```
+//!
+//! Interrupted downloads of the running session are resumed by CEF itself,
+//! which continues where it left off. Downloads of earlier sessions are
+//! unknown to CEF, so they're continued with a ranged [URLRequest] that
+//! appends to the partial file. The range is only requested with an
+//! `If-Range` validator, the `ETag` or `Last-Modified` date of an earlier
+//! response; CEF doesn't report those for its own downloads, so these start
+//! over. Servers that don't support ranges, or whose resource changed in the
+//! meantime, send the whole file again.
+//!
+//! The partial files and the state file are written on a thread of the
+//! manager, so the UI thread never waits for the disk.
+
+use crate::{
+    browser::Browser,
+    client::{
+        download_handler::{BeforeDownloadCallback, DownloadHandler, DownloadHandlerCallbacks, DownloadItem, DownloadItemCallback},
+        download_policy::{DownloadPolicy, PolicyDownloadHandler},
+    },
+    load_handler::ErrorCode,
+    request::{Request, URLRequestFlags},
+    request_context::RequestContext,
+    task::TaskRunner,
+    url_request::{URLRequest, URLRequestClient, URLRequestClientCallbacks, URLRequestStatus},
+    values::{DictionaryValue, ListValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread,
+};
+
+/// The state file is rewritten after this many bytes of progress of a
+/// download, besides on every state change.
+const SAVE_STEP: u64 = 1024 * 1024;
+
+/// The suffix Chromium gives files of unfinished downloads.
+const PARTIAL_SUFFIX: &str = ".crdownload";
+
+/// An unfinished download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadRecord {
+    pub url: String,
+    /// The path the finished download is saved to.
+    pub path: PathBuf,
+    pub received_bytes: u64,
+    pub total_bytes: Option<u64>,
+    /// The `ETag` of the response, if it was seen.
+    pub etag: Option<String>,
+    /// The `Last-Modified` date of the response, if it was seen.
+    pub last_modified: Option<String>,
+}
+
+impl DownloadRecord {
+    /// Returns the file holding the data received so far: the file Chromium
+    /// writes to while downloading, or the target file itself.
+    pub fn partial_path(&self) -> PathBuf {
+        let mut partial = self.path.clone().into_os_string();
+        partial.push(PARTIAL_SUFFIX);
+        let partial = PathBuf::from(partial);
+        if !partial.exists() && self.path.exists() {
+            self.path.clone()
+        } else {
+            partial
+        }
+    }
+
+    fn to_dictionary(&self) -> DictionaryValue {
+        let record = DictionaryValue::new();
+        record.insert_string("url", &self.url);
+        record.insert_string("path", &self.path.to_string_lossy());
+        // Doubles hold sizes exactly up to 2^53 bytes.
+        record.insert_double("received_bytes", self.received_bytes as f64);
+        if let Some(total_bytes) = self.total_bytes {
+            record.insert_double("total_bytes", total_bytes as f64);
+        }
+        if let Some(etag) = &self.etag {
+            record.insert_string("etag", etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            record.insert_string("last_modified", last_modified);
+        }
+        record
+    }
+    fn remove_partial(&self) {
+        let partial = self.partial_path();
+        if partial != self.path {
+            let _ = fs::remove_file(partial);
+        }
+    }
+
+    fn from_dictionary(record: &DictionaryValue) -> Option<DownloadRecord> {
+        let optional_string = |key| match record.get(key) {
+            StoredValue::String(value) => Some(value),
+            _ => None,
+        };
+        let url = record.get_string("url");
+        let path = record.get_string("path");
+        if url.is_empty() || path.is_empty() {
+            return None;
+        }
+        Some(DownloadRecord {
+            url,
+            path: PathBuf::from(path),
+            received_bytes: record.get_double("received_bytes") as u64,
+            total_bytes: match record.get("total_bytes") {
+                StoredValue::Double(total_bytes) => Some(total_bytes as u64),
+                StoredValue::Int(total_bytes) => Some(total_bytes as u64),
+                _ => None,
+            },
+            etag: optional_string("etag"),
+            last_modified: optional_string("last_modified"),
+        })
+    }
+}
+
+type FileJob = Box<dyn 'static + Send + FnOnce()>;
+
+/// Runs file operations one after another on a thread of its own.
+#[derive(Clone)]
+struct FileWorker(Arc<Mutex<mpsc::Sender<FileJob>>>);
+
+impl FileWorker {
+    fn new() -> FileWorker {
+        let (sender, receiver) = mpsc::channel::<FileJob>();
+        let spawned = thread::Builder::new().name("download-manager".into()).spawn(move || {
+            for job in receiver {
+                job();
+            }
+        });
+        if let Err(error) = spawned {
+            log::error!("could not start the download manager thread, writing on the calling thread: {}", error);
+        }
+        FileWorker(Arc::new(Mutex::new(sender)))
+    }
+    /// Queues `job`, or runs it right away if the thread isn't running.
+    fn run(&self, job: impl 'static + Send + FnOnce()) {
+        let sent = self.0.lock().send(Box::new(job));
+        if let Err(mpsc::SendError(job)) = sent {
+            job();
+        }
+    }
+}
+
+struct ManagerState {
+    /// Unfinished downloads, by target path.
+    records: HashMap<PathBuf, DownloadRecord>,
+    /// The downloads CEF is running in this session.
+    active: HashSet<PathBuf>,
+    /// The callbacks of interrupted downloads CEF can resume, by target path.
+    interrupted: HashMap<PathBuf, DownloadItemCallback>,
+    /// Downloads of earlier sessions currently being resumed, with the
+    /// runner of the thread their request was created on.
+    resuming: HashMap<PathBuf, (URLRequest, TaskRunner)>,
+    /// The received bytes of each download when the state was last saved.
+    saved_bytes: HashMap<PathBuf, u64>,
+}
+
+/// Persists unfinished downloads and resumes them. See the
+/// [module documentation](self).
+///
+/// Cloning the manager is cheap; clones share the downloads.
+#[derive(Clone)]
+pub struct DownloadManager {
+    state_file: Arc<PathBuf>,
+    policy: Arc<DownloadPolicy>,
+    state: Arc<Mutex<ManagerState>>,
+    worker: FileWorker,
+}
+
+impl DownloadManager {
+    /// Creates a manager saving downloads according to `policy` and keeping
+    /// unfinished ones in `state_file`, which is read right away. Reading the
+    /// state requires CEF to be initialized.
+    pub fn new<P: Into<PathBuf>>(state_file: P, policy: DownloadPolicy) -> DownloadManager {
+        let state_file = state_file.into();
+        let records = match fs::read_to_string(&state_file) {
+            Ok(json) => match StoredValue::from_json(&json) {
+                Some(StoredValue::List(list)) => (0..list.len())
+                    .filter_map(|i| list.get_dictionary(i))
+                    .filter_map(|record| DownloadRecord::from_dictionary(&record))
+                    .map(|record| (record.path.clone(), record))
+                    .collect(),
+                _ => {
+                    log::warn!("ignoring malformed download state {}", state_file.display());
+                    HashMap::new()
+                }
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => {
+                log::error!("could not read download state {}: {}", state_file.display(), error);
+                HashMap::new()
+            }
+        };
+        DownloadManager {
+            state_file: Arc::new(state_file),
+            policy: Arc::new(policy),
+            state: Arc::new(Mutex::new(ManagerState {
+                records,
+                active: HashSet::new(),
+                interrupted: HashMap::new(),
+                resuming: HashMap::new(),
+                saved_bytes: HashMap::new(),
+            })),
+            worker: FileWorker::new(),
+        }
+    }
+    /// Returns a download handler saving downloads according to the policy and
+    /// recording them.
+    pub fn handler(&self) -> DownloadHandler {
+        DownloadHandler::new(ManagerDownloadHandler {
+            manager: self.clone(),
+            policy: PolicyDownloadHandler::new((*self.policy).clone()),
+        })
+    }
+    /// Returns the unfinished downloads.
+    pub fn unfinished(&self) -> Vec<DownloadRecord> {
+        self.state.lock().records.values().cloned().collect()
+    }
+    /// Resumes all unfinished downloads that aren't running. Interrupted
+    /// downloads of this session are resumed by CEF; downloads of earlier
+    /// sessions are requested through `request_context`, or the global request
+    /// context if `None`, and `on_complete` is called for each of them. Returns
+    /// the number of downloads resumed. Must be called on the UI thread.
+    pub fn resume_all(
+        &self,
+        request_context: Option<&RequestContext>,
+        on_complete: impl 'static + Send + Sync + Fn(&DownloadRecord, Result<PathBuf, ErrorCode>),
+    ) -> usize {
+        let on_complete: Arc<dyn Fn(&DownloadRecord, Result<PathBuf, ErrorCode>) + Send + Sync> = Arc::new(on_complete);
+        let (interrupted, stale) = {
+            let mut state = self.state.lock();
+            let interrupted = state.interrupted.drain().map(|(_, callback)| callback).collect::<Vec<_>>();
+            let stale = state.records.values()
+                .filter(|record| !state.active.contains(&record.path) && !state.resuming.contains_key(&record.path))
+                .cloned()
+                .collect::<Vec<_>>();
+            (interrupted, stale)
+        };
+        let count = interrupted.len() + stale.len();
+        for callback in interrupted {
+            callback.resume();
+        }
+        for record in stale {
+            let task_runner = TaskRunner::get_for_current_thread().expect("resume_all must be called on a CEF thread");
+            let url_request = self.resume_from_file(record.clone(), request_context, task_runner.clone(), on_complete.clone());
+            self.state.lock().resuming.insert(record.path, (url_request, task_runner));
+        }
+        count
+    }
+    /// Forgets an unfinished download and deletes its partial file. A running
+    /// request for it is canceled on its thread, and the file is deleted once
+    /// the request let go of it.
+    pub fn discard(&self, path: &Path) {
+        let (record, resuming) = {
+            let mut state = self.state.lock();
+            let resuming = state.resuming.remove(path);
+            state.interrupted.remove(path);
+            state.active.remove(path);
+            state.saved_bytes.remove(path);
+            (state.records.remove(path), resuming)
+        };
+        // The request is canceled on its thread and outside the lock:
+        // canceling completes it synchronously, which takes the state lock.
+        let was_resuming = resuming.is_some();
+        if let Some((url_request, task_runner)) = resuming {
+            task_runner.post_task(move || {
+                if url_request.get_request_status() == URLRequestStatus::IOPending {
+                    url_request.cancel();
+                }
+            });
+        }
+        if let Some(record) = record {
+            if !was_resuming {
+                self.worker.run(move || record.remove_partial());
+            }
+            self.save();
+        }
+    }
+
+    fn resume_from_file(
+        &self,
+        record: DownloadRecord,
+        request_context: Option<&RequestContext>,
+        task_runner: TaskRunner,
+        on_complete: Arc<dyn Fn(&DownloadRecord, Result<PathBuf, ErrorCode>) + Send + Sync>,
+    ) -> URLRequest {
+        let partial = record.partial_path();
+        let mut request = Request::new();
+        request.set_url(&record.url);
+        request.set_method("GET");
+        // Without a validator the server can't tell whether the partial file
+        // is still current, so the download starts over.
+        let offset = match record.etag.as_ref().or(record.last_modified.as_ref()) {
+            Some(validator) => {
+                let offset = fs::metadata(&partial).map(|metadata| metadata.len()).unwrap_or(0);
+                if offset > 0 {
+                    request.set_header_by_name("Range", &format!("bytes={}-", offset), true);
+                    request.set_header_by_name("If-Range", validator, true);
+                }
+                offset
+            }
+            None => 0,
+        };
+        request.set_flags(&[URLRequestFlags::AllowStoredCredentials, URLRequestFlags::DisableCache]);
+        log::info!("resuming download of {} at {} bytes", record.url, offset);
+        let client = ResumeClient {
+            manager: self.clone(),
+            partial: Arc::new(PartialFile {
+                path: partial,
+                file: Mutex::new(None),
+                failed: Mutex::new(None),
+                task_runner: task_runner.clone(),
+            }),
+            offset,
+            opened: Mutex::new(false),
+            record: Mutex::new(record),
+            task_runner,
+            on_complete,
+        };
+        URLRequest::new(&mut request, URLRequestClient::new(client), request_context)
+    }
+
+    fn update(&self, mut record: DownloadRecord, force_save: bool) {
+        let save = {
+            let mut state = self.state.lock();
+            // CEF doesn't report the validators of its downloads, so those
+            // seen by an earlier resume are kept.
+            if let Some(previous) = state.records.get(&record.path).filter(|previous| previous.url == record.url) {
+                if record.etag.is_none() {
+                    record.etag = previous.etag.clone();
+                }
+                if record.last_modified.is_none() {
+                    record.last_modified = previous.last_modified.clone();
+                }
+            }
+            let saved = state.saved_bytes.get(&record.path).copied();
+            let save = force_save || saved.map_or(true, |saved| record.received_bytes >= saved + SAVE_STEP);
+            if save {
+                state.saved_bytes.insert(record.path.clone(), record.received_bytes);
+            }
+            state.records.insert(record.path.clone(), record);
+            save
+        };
+        if save {
+            self.save();
+        }
+    }
+    fn finish(&self, path: &Path) {
+        let removed = {
+            let mut state = self.state.lock();
+            state.active.remove(path);
+            state.interrupted.remove(path);
+            state.resuming.remove(path);
+            state.saved_bytes.remove(path);
+            state.records.remove(path).is_some()
+        };
+        if removed {
+            self.save();
+        }
+    }
+    /// Writes the records to the state file on the worker thread, through a
+    /// temporary file so a crash doesn't leave a truncated state behind.
+    fn save(&self) {
+        let list = ListValue::new();
+        for (i, record) in self.state.lock().records.values().enumerate() {
+            list.set_dictionary(i, record.to_dictionary());
+        }
+        let json = StoredValue::List(list).to_json().unwrap_or_else(|| "[]".to_owned());
+        let state_file = self.state_file.clone();
+        self.worker.run(move || {
+            let mut temporary = state_file.as_os_str().to_owned();
+            temporary.push(".tmp");
+            let result = fs::write(&temporary, json).and_then(|_| fs::rename(&temporary, &*state_file));
+            if let Err(error) = result {
+                log::error!("could not save download state {}: {}", state_file.display(), error);
+            }
+        });
+    }
+    /// Records the result of resuming a download. Called on the thread of its
+    /// request.
+    fn resumed(&self, record: &DownloadRecord, result: &Result<PathBuf, ErrorCode>) {
+        let discarded = self.state.lock().resuming.remove(&record.path).is_none();
+        match result {
+            Ok(path) => {
+                log::info!("download finished: {}", path.display());
+                self.finish(&record.path);
+            }
+            Err(_) if discarded => {
+                let record = record.clone();
+                self.worker.run(move || record.remove_partial());
+            }
+            Err(error) => {
+                log::warn!("could not resume download of {}: {:?}", record.url, error);
+                self.update(record.clone(), true);
+            }
+        }
+    }
+}
+
+struct ManagerDownloadHandler {
+    manager: DownloadManager,
+    policy: PolicyDownloadHandler,
+}
+
+impl DownloadHandlerCallbacks for ManagerDownloadHandler {
+    fn on_before_download(
+        &mut self,
+        browser: Browser,
+        download_item: DownloadItem,
+        suggested_name: &str,
+        callback: BeforeDownloadCallback,
+    ) {
+        self.policy.on_before_download(browser, download_item, suggested_name, callback);
+    }
+    fn on_download_updated(
+        &mut self,
+        browser: Browser,
+        download_item: DownloadItem,
+        callback: DownloadItemCallback,
+    ) {
+        let path = download_item.get_full_path();
+        if !path.is_empty() {
+            let path = PathBuf::from(path);
+            if download_item.is_complete() || download_item.is_canceled() {
+                self.manager.finish(&path);
+            } else {
+                let interrupted = !download_item.is_in_progress();
+                let record = DownloadRecord {
+                    url: download_item.get_url(),
+                    path: path.clone(),
+                    received_bytes: download_item.get_received_bytes(),
+                    total_bytes: download_item.get_percent_complete().map(|_| download_item.get_total_bytes()),
+                    etag: None,
+                    last_modified: None,
+                };
+                {
+                    let mut state = self.manager.state.lock();
+                    state.active.insert(path.clone());
+                    if interrupted {
+                        state.interrupted.insert(path, callback.clone());
+                    } else {
+                        state.interrupted.remove(&path);
+                    }
+                }
+                self.manager.update(record, interrupted);
+            }
+        }
+        self.policy.on_download_updated(browser, download_item, callback);
+    }
+}
+
+/// The partial file of a resumed download. It is only touched on the worker
+/// thread.
+struct PartialFile {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+    /// Set if the response can't be used or the file can't be written.
+    failed: Mutex<Option<ErrorCode>>,
+    /// The runner of the thread the request was created on.
+    task_runner: TaskRunner,
+}
+
+impl PartialFile {
+    /// Opens the file, appending to it or starting over.
+    fn open(&self, append: bool, request: URLRequest) {
+        if let Some(parent) = self.path.parent() {
+            if fs::create_dir_all(parent).is_err() {
+                return self.fail(ErrorCode::FileNotFound, request);
+            }
+        }
+        let file = if append {
+            OpenOptions::new().append(true).open(&self.path)
+        } else {
+            File::create(&self.path)
+        };
+        match file {
+            Ok(file) => *self.file.lock() = Some(file),
+            Err(error) => {
+                log::error!("could not open {}: {}", self.path.display(), error);
+                self.fail(ErrorCode::AccessDenied, request);
+            }
+        }
+    }
+    fn write(&self, data: &[u8], request: URLRequest) {
+        let result = match &mut *self.file.lock() {
+            Some(file) => file.write_all(data),
+            None => return,
+        };
+        if let Err(error) = result {
+            log::error!("could not write {}: {}", self.path.display(), error);
+            self.fail(ErrorCode::FileNoSpace, request);
+        }
+    }
+    /// Records `error` and cancels the request on its thread.
+    fn fail(&self, error: ErrorCode, request: URLRequest) {
+        self.file.lock().take();
+        self.failed.lock().get_or_insert(error);
+        self.task_runner.post_task(move || {
+            if request.get_request_status() == URLRequestStatus::IOPending {
+                request.cancel();
+            }
+        });
+    }
+}
+
+/// Continues a download of an earlier session into its partial file.
+struct ResumeClient {
+    manager: DownloadManager,
+    partial: Arc<PartialFile>,
+    /// The size of the partial file when the request was made.
+    offset: u64,
+    /// Whether the response was checked and the file opened.
+    opened: Mutex<bool>,
+    record: Mutex<DownloadRecord>,
+    task_runner: TaskRunner,
+    on_complete: Arc<dyn Fn(&DownloadRecord, Result<PathBuf, ErrorCode>) + Send + Sync>,
+}
+
+impl ResumeClient {
+    /// Checks the response and returns whether it continues the partial file
+    /// or replaces it.
+    fn check_response(&self, request: &URLRequest) -> Result<bool, ErrorCode> {
+        let response = request.get_response().ok_or(ErrorCode::Failed)?;
+        let status = response.get_status();
+        let content_range = response.get_header_by_name("Content-Range");
+        let append = match status {
+            206 => {
+                // "bytes <start>-<end>/<total>"
+                let start = content_range.trim_start_matches("bytes").trim().split('-').next().unwrap_or("");
+                if start.parse::<u64>().ok() != Some(self.offset) {
+                    return Err(ErrorCode::InvalidResponse);
+                }
+                true
+            }
+            200 => false,
+            _ => return Err(ErrorCode::Failed),
+        };
+        let mut record = self.record.lock();
+        let etag = response.get_header_by_name("ETag");
+        if !etag.is_empty() {
+            record.etag = Some(etag);
+        }
+        let last_modified = response.get_header_by_name("Last-Modified");
+        if !last_modified.is_empty() {
+            record.last_modified = Some(last_modified);
+        }
+        if let Some(total) = content_range.rsplit('/').next().and_then(|total| total.parse().ok()) {
+            record.total_bytes = Some(total);
+        }
+        record.received_bytes = if append { self.offset } else { 0 };
+        Ok(append)
+    }
+    fn file_was_written(&self) -> bool {
+        let record = self.record.lock();
+        record.received_bytes > self.offset || record.total_bytes == Some(record.received_bytes)
+    }
+}
+
+impl URLRequestClientCallbacks for ResumeClient {
+    fn on_download_data(&self, request: URLRequest, data: &[u8]) {
+        if self.partial.failed.lock().is_some() || is_range_not_satisfiable(&request) {
+            return;
+        }
+        let first = !std::mem::replace(&mut *self.opened.lock(), true);
+        if first {
+            match self.check_response(&request) {
+                Ok(append) => {
+                    let partial = self.partial.clone();
+                    let request = request.clone();
+                    self.manager.worker.run(move || partial.open(append, request));
+                }
+                Err(error) => {
+                    *self.partial.failed.lock() = Some(error);
+                    // No lock may be held: canceling completes the request
+                    // right away.
+                    request.cancel();
+                    return;
+                }
+            }
+        }
+        let record = {
+            let mut record = self.record.lock();
+            record.received_bytes += data.len() as u64;
+            record.clone()
+        };
+        let partial = self.partial.clone();
+        let data = data.to_vec();
+        self.manager.worker.run(move || partial.write(&data, request));
+        self.manager.update(record, false);
+    }
+    fn on_request_complete(&self, request: URLRequest) {
+        let record = self.record.lock().clone();
+        let result = match request.get_request_status() {
+            URLRequestStatus::Success if is_range_not_satisfiable(&request) => {
+                // The partial file holds everything if it has the full size.
+                if record.total_bytes == Some(self.offset) {
+                    Ok(())
+                } else {
+                    Err(ErrorCode::InvalidResponse)
+                }
+            }
+            URLRequestStatus::Success if self.file_was_written() => Ok(()),
+            URLRequestStatus::Success => Err(ErrorCode::Failed),
+            _ => Err(request.get_request_error()),
+        };
+        // The file is closed and moved after the queued writes, and the
+        // result is reported back on the thread of the request.
+        let partial = self.partial.clone();
+        let manager = self.manager.clone();
+        let task_runner = self.task_runner.clone();
+        let on_complete = self.on_complete.clone();
+        self.manager.worker.run(move || {
+            partial.file.lock().take();
+            let failed = *partial.failed.lock();
+            let result = match failed {
+                Some(error) => Err(error),
+                None => result,
+            };
+            let result = result.and_then(|_| {
+                if partial.path != record.path {
+                    fs::rename(&partial.path, &record.path).map_err(|_| ErrorCode::AccessDenied)?;
+                }
+                Ok(record.path.clone())
+            });
+            task_runner.post_task(move || {
+                manager.resumed(&record, &result);
+                on_complete(&record, result);
+            });
+        });
+    }
+}
+
+/// Returns true if the server answered that the partial file is at least as
+/// long as the resource.
+fn is_range_not_satisfiable(request: &URLRequest) -> bool {
+    request.get_response().map_or(false, |response| response.get_status() == 416)
+}
@@ -0,0 +1,276 @@
+//! Declarative download configuration.
+//!
+//! A [DownloadPolicy] describes where downloads go and which ones are
+//! accepted. [DownloadHandler::with_policy] turns it into a handler, so
+//! applications don't have to implement [DownloadHandlerCallbacks] themselves.
+
+use crate::{
+    browser::Browser,
+    client::download_handler::{BeforeDownloadCallback, DownloadHandler, DownloadHandlerCallbacks, DownloadItem, DownloadItemCallback},
+};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Progress of a download is logged every this many percent.
+const PROGRESS_LOG_STEP: u8 = 10;
+
+/// Sanitized file names are cut to this many bytes, leaving room for a suffix
+/// within common file system limits.
+const MAX_FILE_NAME_LEN: usize = 200;
+
+/// What to do if a file with the name of a download already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileConflict {
+    /// Replace the existing file.
+    Overwrite,
+    /// Add a number to the new file name, e.g. `report (1).pdf`.
+    UniqueSuffix,
+}
+
+/// Where downloads are saved and which downloads are accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadPolicy {
+    /// The directory downloads are saved to. Created if missing.
+    pub directory: PathBuf,
+    /// Whether to replace characters in suggested file names that aren't
+    /// allowed on some file systems, and to rename reserved Windows names.
+    /// The directory part of suggested names is always removed.
+    pub sanitize_file_names: bool,
+    pub on_conflict: FileConflict,
+    /// Downloads larger than this many bytes are canceled. Downloads of
+    /// unknown size are canceled once they exceed the limit.
+    pub max_size: Option<u64>,
+    /// The MIME types that may be downloaded, or `None` to allow all types.
+    /// Entries like `image/*` match all subtypes.
+    pub allowed_mime_types: Option<Vec<String>>,
+    /// Whether to show the "Save As" dialog, starting in `directory`.
+    pub show_dialog: bool,
+}
+
+impl DownloadPolicy {
+    /// Creates a policy saving every download to [default_download_dir]
+    /// without asking, adding a suffix to the names of existing files.
+    pub fn new() -> DownloadPolicy {
+        DownloadPolicy {
+            directory: default_download_dir(),
+            sanitize_file_names: true,
+            on_conflict: FileConflict::UniqueSuffix,
+            max_size: None,
+            allowed_mime_types: None,
+            show_dialog: false,
+        }
+    }
+    pub fn directory<P: Into<PathBuf>>(mut self, directory: P) -> Self {
+        self.directory = directory.into();
+        self
+    }
+    pub fn sanitize_file_names(mut self, sanitize_file_names: bool) -> Self {
+        self.sanitize_file_names = sanitize_file_names;
+        self
+    }
+    pub fn on_conflict(mut self, on_conflict: FileConflict) -> Self {
+        self.on_conflict = on_conflict;
+        self
+    }
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+    /// Adds `mime_type` to the allowed MIME types. Once a type was added, all
+    /// other types are rejected.
+    pub fn allow_mime_type<T: Into<String>>(mut self, mime_type: T) -> Self {
+        self.allowed_mime_types.get_or_insert_with(Vec::new).push(mime_type.into());
+        self
+    }
+    pub fn show_dialog(mut self, show_dialog: bool) -> Self {
+        self.show_dialog = show_dialog;
+        self
+    }
+
+    /// Returns true if downloads of `mime_type` are allowed.
+    pub fn allows_mime_type(&self, mime_type: &str) -> bool {
+        let allowed = match &self.allowed_mime_types {
+            Some(allowed) => allowed,
+            None => return true,
+        };
+        // Parameters like "; charset=utf-8" don't matter here.
+        let mime_type = mime_type.split(';').next().unwrap_or("").trim();
+        allowed.iter().any(|allowed| {
+            if allowed.ends_with("/*") {
+                let prefix = &allowed[..allowed.len() - 1];
+                mime_type.len() > prefix.len() && mime_type[..prefix.len()].eq_ignore_ascii_case(prefix)
+            } else {
+                allowed.eq_ignore_ascii_case(mime_type)
+            }
+        })
+    }
+    /// Returns the path a download suggested as `suggested_name` is saved to.
+    pub fn target_path(&self, suggested_name: &str) -> PathBuf {
+        // Suggested names come from the server, so drop any path components.
+        let name = suggested_name.rsplit(|c| c == '/' || c == '\\').next().unwrap_or("");
+        let name = if self.sanitize_file_names {
+            sanitize_file_name(name)
+        } else if name.is_empty() || name == "." || name == ".." {
+            "download".to_owned()
+        } else {
+            name.to_owned()
+        };
+        let path = self.directory.join(&name);
+        if self.on_conflict == FileConflict::Overwrite || !path.exists() {
+            return path;
+        }
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => (&name[..dot], &name[dot..]),
+            _ => (&name[..], ""),
+        };
+        (1..)
+            .map(|n| self.directory.join(format!("{} ({}){}", stem, n, extension)))
+            .find(|path| !path.exists())
+            .unwrap()
+    }
+}
+
+impl Default for DownloadPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the user's Downloads directory, or the temporary directory if the
+/// home directory is unknown.
+pub fn default_download_dir() -> PathBuf {
+    let home = if cfg!(target_os = "windows") {
+        env::var_os("USERPROFILE")
+    } else {
+        env::var_os("HOME")
+    };
+    match home {
+        Some(home) => Path::new(&home).join("Downloads"),
+        None => env::temp_dir(),
+    }
+}
+
+/// Makes `name` a valid file name on all platforms CEF supports.
+fn sanitize_file_name(name: &str) -> String {
+    const RESERVED: &[&str] = &[
+        "CON", "PRN", "AUX", "NUL",
+        "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+        "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+    ];
+    let mut name = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    // Leading dots hide files on Unix, trailing dots and spaces are dropped by
+    // Windows.
+    name = name.trim_start_matches('.').trim_end_matches(|c| c == '.' || c == ' ').to_owned();
+    if name.len() > MAX_FILE_NAME_LEN {
+        let mut end = MAX_FILE_NAME_LEN;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+    }
+    let stem = name.split('.').next().unwrap_or("");
+    if RESERVED.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem)) {
+        name.insert(0, '_');
+    }
+    if name.is_empty() {
+        name.push_str("download");
+    }
+    name
+}
+
+impl DownloadHandler {
+    /// Creates a handler saving downloads according to `policy` and logging
+    /// their progress.
+    pub fn with_policy(policy: DownloadPolicy) -> DownloadHandler {
+        DownloadHandler::new(PolicyDownloadHandler::new(policy))
+    }
+}
+
+pub(crate) struct PolicyDownloadHandler {
+    policy: DownloadPolicy,
+    /// The last logged progress of each download, by download identifier.
+    logged_progress: Vec<(u32, u8)>,
+}
+
+impl PolicyDownloadHandler {
+    pub(crate) fn new(policy: DownloadPolicy) -> PolicyDownloadHandler {
+        PolicyDownloadHandler {
+            policy,
+            logged_progress: Vec::new(),
+        }
+    }
+}
+
+impl DownloadHandlerCallbacks for PolicyDownloadHandler {
+    fn on_before_download(
+        &mut self,
+        browser: Browser,
+        download_item: DownloadItem,
+        suggested_name: &str,
+        callback: BeforeDownloadCallback,
+    ) {
+        // Not continuing the download cancels it.
+        let mime_type = download_item.get_mime_type();
+        if !self.policy.allows_mime_type(&mime_type) {
+            log::warn!("rejected download of {}: MIME type {} is not allowed", download_item.get_url(), mime_type);
+            return;
+        }
+        if let Some(max_size) = self.policy.max_size {
+            if download_item.get_percent_complete().is_some() && download_item.get_total_bytes() > max_size {
+                log::warn!("rejected download of {}: {} bytes exceed the limit", download_item.get_url(), download_item.get_total_bytes());
+                return;
+            }
+        }
+        if let Err(error) = fs::create_dir_all(&self.policy.directory) {
+            log::error!("could not create download directory {}: {}", self.policy.directory.display(), error);
+            return;
+        }
+        let path = self.policy.target_path(suggested_name);
+        log::info!("downloading {} to {}", download_item.get_url(), path.display());
+        callback.cont(&path.to_string_lossy(), self.policy.show_dialog);
+    }
+    fn on_download_updated(
+        &mut self,
+        browser: Browser,
+        download_item: DownloadItem,
+        callback: DownloadItemCallback,
+    ) {
+        let id = download_item.get_id();
+        if download_item.is_complete() || download_item.is_canceled() {
+            self.logged_progress.retain(|(download_id, _)| *download_id != id);
+            if download_item.is_complete() {
+                log::info!("download finished: {}", download_item.get_full_path());
+            } else {
+                log::info!("download canceled: {}", download_item.get_url());
+            }
+            return;
+        }
+        if let Some(max_size) = self.policy.max_size {
+            if download_item.get_received_bytes() > max_size {
+                log::warn!("canceled download of {}: size limit exceeded", download_item.get_url());
+                callback.cancel();
+                return;
+            }
+        }
+        if let Some(percent) = download_item.get_percent_complete() {
+            let step = percent / PROGRESS_LOG_STEP * PROGRESS_LOG_STEP;
+            match self.logged_progress.iter_mut().find(|(download_id, _)| *download_id == id) {
+                Some((_, logged)) if *logged >= step => (),
+                Some((_, logged)) => {
+                    *logged = step;
+                    log::debug!("download {}% complete: {}", percent, download_item.get_full_path());
+                }
+                None => self.logged_progress.push((id, step)),
+            }
+        }
+    }
+}
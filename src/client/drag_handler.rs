@@ -131,6 +131,7 @@ cef_callback_impl!{
             regions: *const cef_draggable_region_t: *const cef_draggable_region_t
         ) {
             let regions = unsafe{ std::slice::from_raw_parts(regions as *const DraggableRegion, regions_count) };
+            crate::hit_test::record_draggable_regions(browser.get_identifier(), regions);
             self.0.on_draggable_regions_changed(browser, frame, regions)
         }
     }
@@ -0,0 +1,198 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::{
+    browser::Browser,
+    browser_host::BrowserHost,
+    client::find_handler::FindHandlerCallbacks,
+    values::Rect,
+};
+
+/// The state of a find-in-page session, reported by [FindController].
+#[derive(Debug, Clone, PartialEq)]
+pub struct FindState {
+    pub search_text: String,
+    pub match_case: bool,
+    /// The 1-based position of the highlighted match, or 0 if there is none.
+    pub active_match: usize,
+    /// The number of matches found so far.
+    pub total: usize,
+    /// The location of the highlighted match, in view coordinates.
+    pub selection_rect: Rect,
+    /// Whether the search has finished and `total` is final.
+    pub final_update: bool,
+}
+
+type FindListener = Arc<dyn 'static + Send + Sync + Fn(Option<&FindState>)>;
+
+struct Session {
+    /// The identifier of the request that started the session. Results for
+    /// older identifiers belong to previous sessions and are dropped.
+    first_identifier: i32,
+    state: FindState,
+}
+
+struct ControllerState {
+    host: Option<BrowserHost>,
+    last_identifier: i32,
+    session: Option<Session>,
+    listeners: Vec<FindListener>,
+}
+
+/// Drives find-in-page for one browser and keeps track of the results.
+///
+/// [BrowserHost::find] requires identifiers that strictly increase across all
+/// requests, and its results arrive asynchronously through a [FindHandler],
+/// possibly still for an earlier search. The controller assigns identifiers,
+/// drops stale results and reports the current [FindState] to its listeners.
+///
+/// Return `FindHandler::new(controller.clone())` from
+/// [ClientCallbacks::get_find_handler] and call [FindController::attach] from
+/// [LifeSpanHandlerCallbacks::on_after_created].
+///
+/// [FindHandler]: crate::client::find_handler::FindHandler
+/// [ClientCallbacks::get_find_handler]: crate::client::ClientCallbacks::get_find_handler
+/// [LifeSpanHandlerCallbacks::on_after_created]: crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_after_created
+#[derive(Clone)]
+pub struct FindController(Arc<Mutex<ControllerState>>);
+
+impl FindController {
+    pub fn new() -> FindController {
+        FindController(Arc::new(Mutex::new(ControllerState {
+            host: None,
+            last_identifier: 0,
+            session: None,
+            listeners: Vec::new(),
+        })))
+    }
+    /// Sets the browser to search in. Requests made before are ignored.
+    pub fn attach(&self, browser: &Browser) {
+        self.0.lock().host = Some(browser.get_host());
+    }
+    /// Calls `listener` whenever the find state changes. The state is `None`
+    /// once the session was closed.
+    pub fn add_listener(&self, listener: impl 'static + Send + Sync + Fn(Option<&FindState>)) {
+        self.0.lock().listeners.push(Arc::new(listener));
+    }
+    /// Returns the state of the current session, if any.
+    pub fn state(&self) -> Option<FindState> {
+        self.0.lock().session.as_ref().map(|session| session.state.clone())
+    }
+    /// Starts searching for `search_text`, replacing any previous search. An
+    /// empty `search_text` closes the session.
+    pub fn find(&self, search_text: &str, match_case: bool) {
+        if search_text.is_empty() {
+            self.close(true);
+            return;
+        }
+        let (host, identifier) = {
+            let mut state = self.0.lock();
+            let host = match &state.host {
+                Some(host) => host.clone(),
+                None => return,
+            };
+            if let Some(session) = &state.session {
+                if session.state.search_text == search_text && session.state.match_case == match_case {
+                    drop(state);
+                    self.next();
+                    return;
+                }
+            }
+            state.last_identifier += 1;
+            let identifier = state.last_identifier;
+            state.session = Some(Session {
+                first_identifier: identifier,
+                state: FindState {
+                    search_text: search_text.to_owned(),
+                    match_case,
+                    active_match: 0,
+                    total: 0,
+                    selection_rect: Rect { x: 0, y: 0, width: 0, height: 0 },
+                    final_update: false,
+                },
+            });
+            (host, identifier)
+        };
+        host.find(identifier, search_text, true, match_case, false);
+    }
+    /// Highlights the next match of the current search.
+    pub fn next(&self) {
+        self.step(true);
+    }
+    /// Highlights the previous match of the current search.
+    pub fn prev(&self) {
+        self.step(false);
+    }
+    /// Ends the session. If `clear_selection` is false the last highlighted
+    /// match stays selected.
+    pub fn close(&self, clear_selection: bool) {
+        let (host, listeners) = {
+            let mut state = self.0.lock();
+            if state.session.take().is_none() {
+                return;
+            }
+            (state.host.clone(), state.listeners.clone())
+        };
+        if let Some(host) = host {
+            host.stop_finding(clear_selection);
+        }
+        for listener in listeners {
+            listener(None);
+        }
+    }
+
+    fn step(&self, forward: bool) {
+        let (host, identifier, search_text, match_case) = {
+            let mut state = self.0.lock();
+            let host = match &state.host {
+                Some(host) => host.clone(),
+                None => return,
+            };
+            let (search_text, match_case) = match &state.session {
+                Some(session) => (session.state.search_text.clone(), session.state.match_case),
+                None => return,
+            };
+            state.last_identifier += 1;
+            (host, state.last_identifier, search_text, match_case)
+        };
+        host.find(identifier, &search_text, forward, match_case, true);
+    }
+}
+
+impl Default for FindController {
+    fn default() -> Self {
+        FindController::new()
+    }
+}
+
+impl FindHandlerCallbacks for FindController {
+    fn on_find_result(
+        &mut self,
+        browser: Browser,
+        identifier: i32,
+        count: usize,
+        selection_rect: Rect,
+        active_match_ordinal: usize,
+        final_update: bool,
+    ) {
+        let (find_state, listeners) = {
+            let mut state = self.0.lock();
+            let state = &mut *state;
+            let session = match &mut state.session {
+                Some(session) if identifier >= session.first_identifier => session,
+                _ => return,
+            };
+            session.state.total = count;
+            // Keep the last highlighted match for updates that don't report one.
+            if active_match_ordinal > 0 {
+                session.state.active_match = active_match_ordinal;
+                session.state.selection_rect = selection_rect;
+            }
+            session.state.final_update = final_update;
+            (session.state.clone(), state.listeners.clone())
+        };
+        for listener in listeners {
+            listener(Some(&find_state));
+        }
+    }
+}
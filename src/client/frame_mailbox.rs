@@ -0,0 +1,135 @@
+use std::{
+    ptr::null_mut,
+    sync::{
+        atomic::{AtomicPtr, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::client::frame_pool::PaintFrame;
+
+/// A frame taken from a [FrameMailbox].
+#[derive(Debug, Clone)]
+pub struct MailboxFrame<T> {
+    /// Numbers the posted frames from 1. Gaps between the frames a consumer
+    /// acquires are frames that were replaced before it got to them.
+    pub sequence: u64,
+    pub frame: T,
+}
+
+struct MailboxInner<T> {
+    /// The latest posted frame that wasn't acquired yet, or null.
+    pending: AtomicPtr<MailboxFrame<T>>,
+    posted: AtomicU64,
+    acquired: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl<T> Drop for MailboxInner<T> {
+    fn drop(&mut self) {
+        let pending = self.pending.swap(null_mut(), Ordering::AcqRel);
+        if !pending.is_null() {
+            unsafe {
+                drop(Box::from_raw(pending));
+            }
+        }
+    }
+}
+
+/// Hands the latest windowless frame from the paint thread to a render
+/// thread.
+///
+/// The producer, usually
+/// [RenderHandlerCallbacks::on_paint](crate::client::render_handler::RenderHandlerCallbacks::on_paint),
+/// posts every frame; the consumer takes the newest one with
+/// [FrameMailbox::acquire_latest] whenever it renders. A frame the consumer
+/// didn't get to before the next one arrived is dropped and counted, so the
+/// consumer never renders stale frames and the producer never waits.
+/// Together with the frame being painted and the frame being rendered, the
+/// pending frame makes for triple buffering; frames from a
+/// [FramePool](crate::client::frame_pool::FramePool) recycle all three
+/// buffers:
+///
+/// ```ignore
+/// // On the paint thread:
+/// mailbox.post(pool.capture(type_, dirty_rects, buffer, width, height));
+/// // On the render thread, once per vsync:
+/// if let Some(latest) = mailbox.acquire_latest() {
+///     upload_texture(&latest.frame);
+/// }
+/// ```
+///
+/// Posting and acquiring are lock-free. Cloning the mailbox is cheap and
+/// shares it.
+pub struct FrameMailbox<T: Send = PaintFrame>(Arc<MailboxInner<T>>);
+
+impl<T: Send> FrameMailbox<T> {
+    pub fn new() -> FrameMailbox<T> {
+        FrameMailbox(Arc::new(MailboxInner {
+            pending: AtomicPtr::new(null_mut()),
+            posted: AtomicU64::new(0),
+            acquired: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }))
+    }
+    /// Makes `frame` the latest frame and returns its sequence number. The
+    /// previous frame is dropped if it wasn't acquired.
+    pub fn post(&self, frame: T) -> u64 {
+        let sequence = self.0.posted.fetch_add(1, Ordering::AcqRel) + 1;
+        let frame = Box::into_raw(Box::new(MailboxFrame { sequence, frame }));
+        let replaced = self.0.pending.swap(frame, Ordering::AcqRel);
+        if !replaced.is_null() {
+            self.0.dropped.fetch_add(1, Ordering::Relaxed);
+            unsafe {
+                drop(Box::from_raw(replaced));
+            }
+        }
+        sequence
+    }
+    /// Takes the latest frame, or returns `None` if no frame was posted since
+    /// the last call.
+    pub fn acquire_latest(&self) -> Option<MailboxFrame<T>> {
+        let pending = self.0.pending.swap(null_mut(), Ordering::AcqRel);
+        if pending.is_null() {
+            None
+        } else {
+            self.0.acquired.fetch_add(1, Ordering::Relaxed);
+            Some(*unsafe { Box::from_raw(pending) })
+        }
+    }
+    /// Returns true if a frame is waiting to be acquired.
+    pub fn has_pending(&self) -> bool {
+        !self.0.pending.load(Ordering::Acquire).is_null()
+    }
+    /// Returns the sequence number of the latest posted frame, 0 if none was
+    /// posted yet.
+    pub fn last_sequence(&self) -> u64 {
+        self.0.posted.load(Ordering::Acquire)
+    }
+    /// Returns how many frames were posted.
+    pub fn posted_frames(&self) -> u64 {
+        self.0.posted.load(Ordering::Relaxed)
+    }
+    /// Returns how many frames were acquired.
+    pub fn acquired_frames(&self) -> u64 {
+        self.0.acquired.load(Ordering::Relaxed)
+    }
+    /// Returns how many frames were replaced before being acquired. A steadily
+    /// growing count means the consumer renders slower than the browser
+    /// paints; lower the windowless frame rate to save work.
+    pub fn dropped_frames(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<T: Send> Clone for FrameMailbox<T> {
+    fn clone(&self) -> Self {
+        FrameMailbox(self.0.clone())
+    }
+}
+
+impl<T: Send> Default for FrameMailbox<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
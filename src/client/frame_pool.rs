@@ -0,0 +1,173 @@
+use std::{
+    ops::Deref,
+    ptr::null_mut,
+    sync::{
+        atomic::{AtomicPtr, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+};
+
+use crate::{browser_host::PaintElementType, values::Rect};
+
+/// The number of idle buffers a [FramePool] keeps by default: enough for a
+/// frame being painted, one being consumed and one queued.
+pub const DEFAULT_POOL_CAPACITY: usize = 3;
+
+struct PoolInner {
+    /// Idle buffers. A slot is either null or owns a boxed buffer; buffers are
+    /// moved in and out with atomic swaps, so the pool never blocks.
+    slots: Box<[AtomicPtr<Vec<u8>>]>,
+    allocations: AtomicUsize,
+}
+
+impl PoolInner {
+    fn take(&self, len: usize) -> Vec<u8> {
+        for slot in self.slots.iter() {
+            let buffer = slot.swap(null_mut(), Ordering::AcqRel);
+            if !buffer.is_null() {
+                let mut buffer = *unsafe { Box::from_raw(buffer) };
+                if buffer.capacity() >= len {
+                    buffer.clear();
+                    return buffer;
+                }
+                // Too small after a resize; let it go.
+            }
+        }
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        Vec::with_capacity(len)
+    }
+    fn give_back(&self, buffer: Vec<u8>) {
+        let buffer = Box::into_raw(Box::new(buffer));
+        for slot in self.slots.iter() {
+            if slot.compare_exchange(null_mut(), buffer, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                return;
+            }
+        }
+        // The pool is full.
+        unsafe {
+            drop(Box::from_raw(buffer));
+        }
+    }
+}
+
+impl Drop for PoolInner {
+    fn drop(&mut self) {
+        for slot in self.slots.iter() {
+            let buffer = slot.swap(null_mut(), Ordering::AcqRel);
+            if !buffer.is_null() {
+                unsafe {
+                    drop(Box::from_raw(buffer));
+                }
+            }
+        }
+    }
+}
+
+/// Recycles the pixel buffers of windowless paints.
+///
+/// [RenderHandlerCallbacks::on_paint](crate::client::render_handler::RenderHandlerCallbacks::on_paint)
+/// only lends its buffer for the duration of the call, so consumers on other
+/// threads need a copy. Copying into a new `Vec` every frame allocates
+/// several megabytes 60 times per second; [FramePool::capture] copies into a
+/// recycled buffer instead and returns a [PaintFrame] that can be shared with
+/// other threads and returns its buffer to the pool when the last clone is
+/// dropped:
+///
+/// ```ignore
+/// fn on_paint(&self, browser: Browser, type_: PaintElementType, dirty_rects: &[Rect], buffer: &[u8], width: i32, height: i32) {
+///     let frame = self.pool.capture(type_, dirty_rects, buffer, width, height);
+///     self.sender.send(frame).ok();
+/// }
+/// ```
+///
+/// Taking and returning buffers is lock-free. Cloning the pool is cheap.
+#[derive(Clone)]
+pub struct FramePool(Arc<PoolInner>);
+
+impl FramePool {
+    pub fn new() -> FramePool {
+        Self::with_capacity(DEFAULT_POOL_CAPACITY)
+    }
+    /// Creates a pool keeping up to `capacity` idle buffers.
+    pub fn with_capacity(capacity: usize) -> FramePool {
+        FramePool(Arc::new(PoolInner {
+            slots: (0..capacity.max(1)).map(|_| AtomicPtr::new(null_mut())).collect(),
+            allocations: AtomicUsize::new(0),
+        }))
+    }
+    /// Copies a painted BGRA buffer into a pooled frame.
+    pub fn capture(&self, type_: PaintElementType, dirty_rects: &[Rect], buffer: &[u8], width: i32, height: i32) -> PaintFrame {
+        let mut data = self.0.take(buffer.len());
+        data.extend_from_slice(buffer);
+        PaintFrame(Arc::new(FrameInner {
+            data,
+            type_,
+            dirty_rects: dirty_rects.to_vec(),
+            width,
+            height,
+            pool: Arc::downgrade(&self.0),
+        }))
+    }
+    /// Returns how many buffers the pool had to allocate so far. It stops
+    /// growing once the pool is warmed up, unless consumers hold on to more
+    /// frames than the pool's capacity.
+    pub fn allocations(&self) -> usize {
+        self.0.allocations.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for FramePool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct FrameInner {
+    data: Vec<u8>,
+    type_: PaintElementType,
+    dirty_rects: Vec<Rect>,
+    width: i32,
+    height: i32,
+    pool: Weak<PoolInner>,
+}
+
+impl Drop for FrameInner {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.upgrade() {
+            pool.give_back(std::mem::replace(&mut self.data, Vec::new()));
+        }
+    }
+}
+
+/// A painted frame captured by a [FramePool]. Dereferences to the BGRA
+/// pixels, `width * height * 4` bytes with the origin at the top left.
+/// Cloning is cheap and shares the pixels.
+#[derive(Clone)]
+pub struct PaintFrame(Arc<FrameInner>);
+
+impl PaintFrame {
+    pub fn element_type(&self) -> PaintElementType {
+        self.0.type_
+    }
+    /// The areas that changed since the previous frame of the same element.
+    pub fn dirty_rects(&self) -> &[Rect] {
+        &self.0.dirty_rects
+    }
+    pub fn width(&self) -> i32 {
+        self.0.width
+    }
+    pub fn height(&self) -> i32 {
+        self.0.height
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.0.data
+    }
+}
+
+impl Deref for PaintFrame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0.data
+    }
+}
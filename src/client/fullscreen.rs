@@ -0,0 +1,92 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::{
+    browser::Browser,
+    client::render_handler::ScreenInfo,
+    values::Rect,
+};
+
+struct FullscreenState {
+    fullscreen: bool,
+    display_rect: Option<Rect>,
+}
+
+/// Resizes a windowless browser to the bounds of its display while web content
+/// is in HTML5 fullscreen mode, and back when it leaves it.
+///
+/// Windowed browsers are resized by CEF itself, but for windowless browsers the
+/// view rectangle is whatever [RenderHandlerCallbacks::get_view_rect] returns,
+/// so fullscreen video would otherwise stay at its embedded size. Share one
+/// instance between the display and render handlers of a browser:
+///
+/// * Call [WindowlessFullscreen::on_fullscreen_mode_change] from
+///   [DisplayHandlerCallbacks::on_fullscreen_mode_change].
+/// * Return [WindowlessFullscreen::view_rect] from
+///   [RenderHandlerCallbacks::get_view_rect].
+/// * Keep the display bounds current with [WindowlessFullscreen::set_display_rect]
+///   or [WindowlessFullscreen::set_screen_info].
+///
+/// [RenderHandlerCallbacks::get_view_rect]: crate::client::render_handler::RenderHandlerCallbacks::get_view_rect
+/// [DisplayHandlerCallbacks::on_fullscreen_mode_change]: crate::client::display_handler::DisplayHandlerCallbacks::on_fullscreen_mode_change
+#[derive(Clone)]
+pub struct WindowlessFullscreen(Arc<Mutex<FullscreenState>>);
+
+impl WindowlessFullscreen {
+    pub fn new() -> WindowlessFullscreen {
+        WindowlessFullscreen(Arc::new(Mutex::new(FullscreenState {
+            fullscreen: false,
+            display_rect: None,
+        })))
+    }
+    /// Sets the bounds of the display the browser is shown on, in view
+    /// coordinates. Call [BrowserHost::was_resized](crate::browser_host::BrowserHost::was_resized)
+    /// afterwards if the browser is currently fullscreen.
+    pub fn set_display_rect(&self, rect: Rect) {
+        self.0.lock().display_rect = Some(rect);
+    }
+    /// Takes the display bounds from `screen_info.rect`.
+    pub fn set_screen_info(&self, screen_info: &ScreenInfo) {
+        self.set_display_rect(screen_info.rect);
+    }
+    /// Returns true if web content is currently fullscreen.
+    pub fn is_fullscreen(&self) -> bool {
+        self.0.lock().fullscreen
+    }
+    /// Records the new fullscreen state and tells the browser to query its
+    /// view rectangle again.
+    pub fn on_fullscreen_mode_change(&self, browser: &Browser, fullscreen: bool) {
+        self.0.lock().fullscreen = fullscreen;
+        browser.get_host().was_resized();
+    }
+    /// Returns the rectangle the browser should use as its view: the display
+    /// bounds while fullscreen, `normal` otherwise. Only the size of the
+    /// display bounds is used, since the view rectangle is relative to the
+    /// browser's own screen position.
+    pub fn view_rect(&self, normal: Rect) -> Rect {
+        let state = self.0.lock();
+        match (state.fullscreen, state.display_rect) {
+            (true, Some(display)) => Rect {
+                x: 0,
+                y: 0,
+                width: display.width,
+                height: display.height,
+            },
+            _ => normal,
+        }
+    }
+    /// Asks the page to leave fullscreen, e.g. when the user presses Escape.
+    /// [WindowlessFullscreen::on_fullscreen_mode_change] is called once it has.
+    pub fn exit(&self, browser: &Browser) {
+        if self.is_fullscreen() {
+            // The resize follows from on_fullscreen_mode_change.
+            browser.get_host().exit_fullscreen(false);
+        }
+    }
+}
+
+impl Default for WindowlessFullscreen {
+    fn default() -> Self {
+        WindowlessFullscreen::new()
+    }
+}
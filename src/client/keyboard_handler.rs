@@ -2,6 +2,7 @@ use crate::{
     browser::{Browser},
     events::KeyEvent,
     refcounted::{RefCountedPtr, Wrapper},
+    user_activity::{self, InputKind},
 };
 use cef_sys::{
     cef_browser_t,
@@ -76,6 +77,7 @@ cef_callback_impl!{
             _os_event: cef_event_handle_t: cef_event_handle_t,
             is_keyboard_shortcut: &mut c_int: *mut c_int
         ) -> c_int {
+            user_activity::record(browser.get_identifier(), InputKind::Key);
             let mut keyboard_shortcut = *is_keyboard_shortcut != 0;
             let ret = self.0.lock().on_pre_key_event(browser, event, &mut keyboard_shortcut) as c_int;
             *is_keyboard_shortcut = keyboard_shortcut as c_int;
@@ -293,6 +293,10 @@ cef_callback_impl!{
         }
         fn on_before_close(&self, browser: Browser: *mut cef_browser_t) {
             self.0.on_before_close(browser.clone());
+            crate::user_activity::forget_browser(browser.get_identifier());
+            crate::frame_selector::forget_browser(browser.get_identifier());
+            crate::pdf::forget_browser(browser.get_identifier());
+            crate::hit_test::forget_browser(browser.get_identifier());
             unsafe{ browser.poison(); }
         }
     }
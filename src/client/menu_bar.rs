@@ -0,0 +1,380 @@
+//! Application menus described once and shown by any toolkit.
+//!
+//! A [MenuBar] is a list of [Menu]s whose items carry commands of an
+//! application-defined type. [MenuBar::create_menu_buttons] turns it into
+//! views [MenuButton]s, whose menus are built from the current items each
+//! time they open, so items enabled, checked or replaced at runtime show up
+//! right away. Applications with a toolkit of their own draw the bar
+//! themselves and re-render it when [MenuBar::on_change] reports changes.
+//! Every way of choosing a command ends up in a single callback:
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+//! enum Command { NewTab, Quit, ShowBookmarks }
+//!
+//! let menu_bar = MenuBar::new(vec![
+//!     Menu::new("File")
+//!         .item(MenuItem::new(Command::NewTab, "New Tab").accelerator(Accelerator::new(WindowsKeyCode::T, EventFlags::CONTROL_DOWN)))
+//!         .separator()
+//!         .item(MenuItem::new(Command::Quit, "Quit")),
+//!     Menu::new("View").item(MenuItem::new(Command::ShowBookmarks, "Bookmarks Bar").checked(true)),
+//! ]);
+//! menu_bar.on_command(|command| match command { ... });
+//! menu_bar.bind_accelerators(&accelerators);
+//! for button in menu_bar.create_menu_buttons() {
+//!     toolbar.add_child_view(&button);
+//! }
+//! // or, when the user picked an item in the application's menu UI:
+//! menu_bar.dispatch(Command::NewTab);
+//! ```
+//!
+//! A menu can also be copied into a CEF [MenuModel] with
+//! [MenuBar::fill_menu_model], e.g. to offer it in the context menu; commands
+//! chosen there are resolved with [MenuBar::command_for_id].
+
+use crate::client::{
+    accelerators::{Accelerator, Accelerators},
+    context_menu_handler::{self as menu, CommandId, MenuModel, MenuModelDelegate, MenuModelDelegateCallbacks, Modifiers},
+};
+use crate::{
+    events::EventFlags,
+    values::Point,
+    views::{MenuAnchorPosition, MenuButton, MenuButtonDelegate, MenuButtonDelegateCallbacks},
+};
+use parking_lot::Mutex;
+use std::{hash::Hash, sync::Arc};
+
+/// The first command id used in menu models, CEF's `MENU_ID_USER_FIRST`.
+const FIRST_COMMAND_ID: i32 = 26500;
+
+/// A command of a [Menu].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuItem<C> {
+    pub command: C,
+    pub label: String,
+    pub enabled: bool,
+    /// Whether the item is checked, or `None` for items without check mark.
+    pub checked: Option<bool>,
+    pub accelerator: Option<Accelerator>,
+}
+
+impl<C> MenuItem<C> {
+    pub fn new(command: C, label: &str) -> MenuItem<C> {
+        MenuItem {
+            command,
+            label: label.to_owned(),
+            enabled: true,
+            checked: None,
+            accelerator: None,
+        }
+    }
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+    /// Makes the item a check item.
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.checked = Some(checked);
+        self
+    }
+    pub fn accelerator(mut self, accelerator: Accelerator) -> Self {
+        self.accelerator = Some(accelerator);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuEntry<C> {
+    Item(MenuItem<C>),
+    Separator,
+    Submenu(Menu<C>),
+}
+
+/// A menu of a [MenuBar], or a submenu.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Menu<C> {
+    pub label: String,
+    pub entries: Vec<MenuEntry<C>>,
+}
+
+impl<C> Menu<C> {
+    pub fn new(label: &str) -> Menu<C> {
+        Menu {
+            label: label.to_owned(),
+            entries: Vec::new(),
+        }
+    }
+    pub fn item(mut self, item: MenuItem<C>) -> Self {
+        self.entries.push(MenuEntry::Item(item));
+        self
+    }
+    pub fn separator(mut self) -> Self {
+        self.entries.push(MenuEntry::Separator);
+        self
+    }
+    pub fn submenu(mut self, submenu: Menu<C>) -> Self {
+        self.entries.push(MenuEntry::Submenu(submenu));
+        self
+    }
+
+    fn items_mut(&mut self) -> Vec<&mut MenuItem<C>> {
+        let mut items = Vec::new();
+        for entry in &mut self.entries {
+            match entry {
+                MenuEntry::Item(item) => items.push(item),
+                MenuEntry::Submenu(submenu) => items.extend(submenu.items_mut()),
+                MenuEntry::Separator => (),
+            }
+        }
+        items
+    }
+    fn items(&self) -> Vec<&MenuItem<C>> {
+        let mut items = Vec::new();
+        for entry in &self.entries {
+            match entry {
+                MenuEntry::Item(item) => items.push(item),
+                MenuEntry::Submenu(submenu) => items.extend(submenu.items()),
+                MenuEntry::Separator => (),
+            }
+        }
+        items
+    }
+}
+
+struct MenuBarState<C> {
+    menus: Mutex<Vec<Menu<C>>>,
+    /// Every command of the menus, in order; their index determines the id
+    /// used in menu models and accelerators.
+    commands: Mutex<Vec<C>>,
+    change_listener: Mutex<Option<Arc<dyn 'static + Send + Sync + Fn(&[Menu<C>])>>>,
+    command_listener: Mutex<Option<Arc<dyn 'static + Send + Sync + Fn(C)>>>,
+}
+
+/// The menus of a window. See the [module documentation](self).
+///
+/// Cloning is cheap; clones share the menus.
+pub struct MenuBar<C>(Arc<MenuBarState<C>>);
+
+impl<C> Clone for MenuBar<C> {
+    fn clone(&self) -> Self {
+        MenuBar(self.0.clone())
+    }
+}
+
+impl<C: 'static + Send + Clone + Eq + Hash> MenuBar<C> {
+    pub fn new(menus: Vec<Menu<C>>) -> MenuBar<C> {
+        let menu_bar = MenuBar(Arc::new(MenuBarState {
+            menus: Mutex::new(Vec::new()),
+            commands: Mutex::new(Vec::new()),
+            change_listener: Mutex::new(None),
+            command_listener: Mutex::new(None),
+        }));
+        menu_bar.set_menus(menus);
+        menu_bar
+    }
+    /// Calls `listener` with the menus whenever they changed.
+    pub fn on_change(&self, listener: impl 'static + Send + Sync + Fn(&[Menu<C>])) {
+        *self.0.change_listener.lock() = Some(Arc::new(listener));
+    }
+    /// Calls `listener` with every command chosen through
+    /// [MenuBar::dispatch], an accelerator or a menu model.
+    pub fn on_command(&self, listener: impl 'static + Send + Sync + Fn(C)) {
+        *self.0.command_listener.lock() = Some(Arc::new(listener));
+    }
+    pub fn menus(&self) -> Vec<Menu<C>> {
+        self.0.menus.lock().clone()
+    }
+    /// Replaces all menus, e.g. when a document with other commands got
+    /// focus.
+    pub fn set_menus(&self, menus: Vec<Menu<C>>) {
+        {
+            let mut commands = self.0.commands.lock();
+            commands.clear();
+            for item in menus.iter().flat_map(|menu| menu.items()) {
+                if !commands.contains(&item.command) {
+                    commands.push(item.command.clone());
+                }
+            }
+        }
+        *self.0.menus.lock() = menus;
+        self.changed();
+    }
+    /// Enables or disables the items of `command`.
+    pub fn set_enabled(&self, command: &C, enabled: bool) {
+        self.update(command, |item| item.enabled = enabled);
+    }
+    /// Checks or unchecks the items of `command`, making them check items.
+    pub fn set_checked(&self, command: &C, checked: bool) {
+        self.update(command, |item| item.checked = Some(checked));
+    }
+    pub fn set_label(&self, command: &C, label: &str) {
+        self.update(command, |item| item.label = label.to_owned());
+    }
+    /// Returns whether an item of `command` is enabled.
+    pub fn is_enabled(&self, command: &C) -> bool {
+        self.0.menus.lock().iter().flat_map(|menu| menu.items()).any(|item| item.command == *command && item.enabled)
+    }
+
+    /// Runs `command` if it is enabled. Returns true if it ran.
+    pub fn dispatch(&self, command: C) -> bool {
+        if !self.is_enabled(&command) {
+            return false;
+        }
+        let listener = self.0.command_listener.lock().clone();
+        match listener {
+            Some(listener) => {
+                listener(command);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Registers the accelerators of all items with `accelerators` and
+    /// dispatches their commands. This replaces the callback of
+    /// [Accelerators::on_accelerator].
+    pub fn bind_accelerators(&self, accelerators: &Accelerators) {
+        accelerators.remove_all_accelerators();
+        for item in self.0.menus.lock().iter().flat_map(|menu| menu.items()) {
+            if let (Some(accelerator), Some(id)) = (item.accelerator, self.command_id(&item.command)) {
+                accelerators.set_accelerator(id.get(), accelerator);
+            }
+        }
+        let menu_bar = self.clone();
+        accelerators.on_accelerator(move |_, id| match CommandId::new(id).and_then(|id| menu_bar.command_for_id(id)) {
+            Some(command) => menu_bar.dispatch(command),
+            None => false,
+        });
+    }
+
+    /// Copies the menu at `index` into `model`, appending to its items.
+    /// Returns false if there is no such menu.
+    pub fn fill_menu_model(&self, index: usize, model: &MenuModel) -> bool {
+        let menus = self.0.menus.lock();
+        match menus.get(index) {
+            Some(menu) => {
+                self.fill(menu, model);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Creates a views button for every menu, labeled with its label. The
+    /// menus are built from the items when they open, and the commands
+    /// chosen in them are dispatched. The labels aren't updated by later
+    /// changes. Must be called on the UI thread.
+    pub fn create_menu_buttons(&self) -> Vec<MenuButton> {
+        let labels: Vec<String> = self.0.menus.lock().iter().map(|menu| menu.label.clone()).collect();
+        labels
+            .iter()
+            .enumerate()
+            .map(|(index, label)| {
+                let delegate = MenuButtonDelegate::new(MenuBarButton { menu_bar: self.clone(), index });
+                MenuButton::new(delegate, label)
+            })
+            .collect()
+    }
+    /// Returns the command id of `command` in menu models.
+    pub fn command_id(&self, command: &C) -> Option<CommandId> {
+        let commands = self.0.commands.lock();
+        commands.iter().position(|c| c == command).and_then(|index| CommandId::new(FIRST_COMMAND_ID + index as i32))
+    }
+    /// Returns the command of a menu model item filled in by
+    /// [MenuBar::fill_menu_model].
+    pub fn command_for_id(&self, command_id: CommandId) -> Option<C> {
+        let index = command_id.get().checked_sub(FIRST_COMMAND_ID)?;
+        self.0.commands.lock().get(index as usize).cloned()
+    }
+
+    fn fill(&self, menu: &Menu<C>, model: &MenuModel) {
+        for entry in &menu.entries {
+            match entry {
+                MenuEntry::Item(item) => {
+                    let id = match self.command_id(&item.command) {
+                        Some(id) => id,
+                        None => continue,
+                    };
+                    match item.checked {
+                        Some(checked) => {
+                            model.add_check_item(id, &item.label);
+                            model.set_checked(id, checked);
+                        }
+                        None => {
+                            model.add_item(id, &item.label);
+                        }
+                    }
+                    model.set_enabled(id, item.enabled);
+                    if let Some(accelerator) = item.accelerator {
+                        model.set_accelerator(id, to_menu_accelerator(accelerator));
+                    }
+                }
+                MenuEntry::Separator => {
+                    model.add_separator();
+                }
+                MenuEntry::Submenu(submenu) => {
+                    let submenu_model = model.add_sub_menu(CommandId::unique(), &submenu.label);
+                    self.fill(submenu, &submenu_model);
+                }
+            }
+        }
+    }
+    fn update(&self, command: &C, f: impl Fn(&mut MenuItem<C>)) {
+        let mut changed = false;
+        for menu in self.0.menus.lock().iter_mut() {
+            for item in menu.items_mut() {
+                if item.command == *command {
+                    f(item);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.changed();
+        }
+    }
+    fn changed(&self) {
+        let menus = self.menus();
+        // The listener may call back into the menu bar.
+        let listener = self.0.change_listener.lock().clone();
+        if let Some(listener) = listener {
+            listener(&menus);
+        }
+    }
+}
+
+/// Shows one menu of a [MenuBar] when its button is pressed.
+struct MenuBarButton<C> {
+    menu_bar: MenuBar<C>,
+    index: usize,
+}
+
+impl<C: 'static + Send + Clone + Eq + Hash> MenuButtonDelegateCallbacks for MenuBarButton<C> {
+    fn on_menu_button_pressed(&self, menu_button: MenuButton, screen_point: Point) {
+        let model = MenuModel::new(MenuModelDelegate::new(MenuBarButton { menu_bar: self.menu_bar.clone(), index: self.index }));
+        if !self.menu_bar.fill_menu_model(self.index, &model) {
+            return;
+        }
+        // Open the menu below the button rather than at the pointer.
+        let bounds = menu_button.get_bounds_in_screen();
+        let point = Point { x: bounds.x, y: bounds.y + bounds.height };
+        menu_button.show_menu(&model, point, MenuAnchorPosition::TopLeft);
+    }
+}
+
+impl<C: 'static + Send + Clone + Eq + Hash> MenuModelDelegateCallbacks for MenuBarButton<C> {
+    fn execute_command(&self, menu_model: MenuModel, command_id: CommandId, event_flags: EventFlags) {
+        if let Some(command) = self.menu_bar.command_for_id(command_id) {
+            self.menu_bar.dispatch(command);
+        }
+    }
+}
+
+fn to_menu_accelerator(accelerator: Accelerator) -> menu::Accelerator {
+    let mut modifiers = Modifiers::empty();
+    modifiers.set(Modifiers::SHIFT, accelerator.modifiers.contains(EventFlags::SHIFT_DOWN));
+    modifiers.set(Modifiers::CTRL, accelerator.modifiers.intersects(EventFlags::CONTROL_DOWN | EventFlags::COMMAND_DOWN));
+    modifiers.set(Modifiers::ALT, accelerator.modifiers.contains(EventFlags::ALT_DOWN));
+    menu::Accelerator {
+        key: accelerator.key_code.get(),
+        modifiers,
+    }
+}
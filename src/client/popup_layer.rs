@@ -0,0 +1,117 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::{
+    browser_host::PaintElementType,
+    client::frame_pool::{FramePool, PaintFrame},
+    values::Rect,
+};
+
+/// The state of a [PopupLayer] at one point in time.
+#[derive(Clone)]
+pub struct PopupLayerState {
+    pub visible: bool,
+    /// The position and size of the popup in view coordinates.
+    pub rect: Option<Rect>,
+    /// The latest paint of the popup.
+    pub frame: Option<PaintFrame>,
+    /// Incremented on every change, so renderers can skip unchanged layers.
+    pub version: u64,
+}
+
+impl PopupLayerState {
+    /// Returns true if the layer should be drawn: it's visible, placed and
+    /// painted at its current size.
+    pub fn is_drawable(&self) -> bool {
+        match (&self.rect, &self.frame) {
+            (Some(rect), Some(frame)) => self.visible && frame.width() == rect.width && frame.height() == rect.height,
+            _ => false,
+        }
+    }
+}
+
+/// Keeps the popup widget of a windowless browser, e.g. an open `<select>`
+/// dropdown, as a layer of its own.
+///
+/// CEF paints the popup separately from the view. Compositing it into the
+/// view texture means re-uploading the whole view whenever the popup changes,
+/// e.g. on every hover highlight, and blurs the popup if the view is scaled.
+/// Engines that can draw layers natively should instead draw the view
+/// texture and, on top, the popup texture at [PopupLayerState::rect]. Feed the
+/// layer from the render handler:
+///
+/// * Call [PopupLayer::on_popup_show] and [PopupLayer::on_popup_size] from the
+///   [RenderHandlerCallbacks](crate::client::render_handler::RenderHandlerCallbacks)
+///   functions of the same name.
+/// * Call [PopupLayer::on_paint] from
+///   [RenderHandlerCallbacks::on_paint](crate::client::render_handler::RenderHandlerCallbacks::on_paint);
+///   it returns false for view paints, which are left to the caller.
+///
+/// Cloning the layer is cheap and shares it with the render thread.
+#[derive(Clone)]
+pub struct PopupLayer {
+    state: Arc<Mutex<PopupLayerState>>,
+    pool: FramePool,
+}
+
+impl PopupLayer {
+    pub fn new() -> PopupLayer {
+        PopupLayer {
+            state: Arc::new(Mutex::new(PopupLayerState {
+                visible: false,
+                rect: None,
+                frame: None,
+                version: 0,
+            })),
+            pool: FramePool::with_capacity(2),
+        }
+    }
+    pub fn on_popup_show(&self, show: bool) {
+        let mut state = self.state.lock();
+        state.visible = show;
+        if !show {
+            // The next popup starts with a fresh paint.
+            state.rect = None;
+            state.frame = None;
+        }
+        state.version += 1;
+    }
+    pub fn on_popup_size(&self, rect: Rect) {
+        let mut state = self.state.lock();
+        state.rect = Some(rect);
+        state.version += 1;
+    }
+    /// Stores popup paints and returns true, or returns false for view paints.
+    pub fn on_paint(&self, type_: PaintElementType, dirty_rects: &[Rect], buffer: &[u8], width: i32, height: i32) -> bool {
+        if type_ != PaintElementType::Popup {
+            return false;
+        }
+        let frame = self.pool.capture(type_, dirty_rects, buffer, width, height);
+        let mut state = self.state.lock();
+        state.frame = Some(frame);
+        state.version += 1;
+        true
+    }
+    /// Returns the current state of the layer.
+    pub fn state(&self) -> PopupLayerState {
+        self.state.lock().clone()
+    }
+    /// Returns the state if it changed since `version`.
+    pub fn state_if_changed(&self, version: u64) -> Option<PopupLayerState> {
+        let state = self.state.lock();
+        if state.version == version {
+            None
+        } else {
+            Some(state.clone())
+        }
+    }
+    pub fn is_visible(&self) -> bool {
+        self.state.lock().visible
+    }
+}
+
+impl Default for PopupLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -29,7 +29,7 @@ use std::os::raw::c_void;
 use std::ptr;
 
 #[repr(C)]
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum TextInputMode {
     Default = cef_text_input_mode_t::CEF_TEXT_INPUT_MODE_DEFAULT as isize,
     None = cef_text_input_mode_t::CEF_TEXT_INPUT_MODE_NONE as isize,
@@ -324,6 +324,10 @@ pub trait RenderHandlerCallbacks: 'static + Send + Sync {
     /// be `width`*`height`*4 bytes in size and represents a BGRA image with an
     /// upper-left origin. This function is only called when
     /// cef_window_tInfo::shared_texture_enabled is set to `false`.
+    ///
+    /// If the browser was created with [Transparency::Transparent](crate::color::Transparency::Transparent)
+    /// the alpha channel of `buffer` is meaningful and the color channels are
+    /// premultiplied by it. Otherwise every pixel is fully opaque.
     fn on_paint(
         &self,
         browser: Browser,
@@ -0,0 +1,283 @@
+use std::slice;
+
+use crate::{browser_host::PaintElementType, values::Rect};
+
+/// Memory a paint is copied into, usually a mapped GPU staging buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct StagingBuffer {
+    /// The first byte of the top-left pixel.
+    pub ptr: *mut u8,
+    /// Bytes between the starts of two rows, at least `width * 4`. GPU APIs
+    /// often require rows to be aligned, e.g. to 256 bytes for DX12.
+    pub stride: usize,
+    /// Set if the buffer still holds the previous frame, in which case only
+    /// the dirty areas are copied. Otherwise the whole frame is copied.
+    pub retains_contents: bool,
+}
+
+/// Receives windowless paints directly in memory the caller provides, to skip
+/// the intermediate copy of the paint into a `Vec`.
+///
+/// [StagingUploader::on_paint] copies the BGRA pixels CEF painted straight into
+/// the [StagingBuffer] returned by [StagingTarget::acquire] and then calls
+/// [StagingTarget::release] with the areas that changed, so the caller can
+/// record a buffer-to-image copy for just those areas. Both functions are
+/// called on the paint thread, the browser process UI thread.
+///
+/// # Safety
+///
+/// Implementations must return a buffer that is valid for writes of
+/// `stride * (height - 1) + width * 4` bytes until [StagingTarget::release]
+/// is called, and that isn't read by anyone else meanwhile, e.g. by a GPU copy
+/// still in flight. Synchronizing with the GPU is up to the implementation,
+/// typically with a fence waited on in [StagingTarget::acquire].
+pub unsafe trait StagingTarget: 'static + Send + Sync {
+    /// Returns the buffer to copy a `width` x `height` frame of `type_` into,
+    /// or `None` to skip the frame, e.g. while the buffer is resized.
+    fn acquire(&self, type_: PaintElementType, width: i32, height: i32) -> Option<StagingBuffer>;
+    /// Called after the frame was copied. `dirty_rects` are the areas that
+    /// were written, clipped to the frame.
+    fn release(&self, type_: PaintElementType, buffer: StagingBuffer, dirty_rects: &[Rect]);
+}
+
+/// Copies windowless paints into a [StagingTarget]. Call
+/// [StagingUploader::on_paint] from
+/// [RenderHandlerCallbacks::on_paint](crate::client::render_handler::RenderHandlerCallbacks::on_paint).
+pub struct StagingUploader<S: StagingTarget> {
+    target: S,
+}
+
+impl<S: StagingTarget> StagingUploader<S> {
+    pub fn new(target: S) -> StagingUploader<S> {
+        StagingUploader { target }
+    }
+    pub fn target(&self) -> &S {
+        &self.target
+    }
+    /// Copies a paint into the target. Returns false if the target skipped the
+    /// frame or the paint is malformed.
+    pub fn on_paint(&self, type_: PaintElementType, dirty_rects: &[Rect], buffer: &[u8], width: i32, height: i32) -> bool {
+        if width <= 0 || height <= 0 || buffer.len() < width as usize * height as usize * 4 {
+            return false;
+        }
+        let staging = match self.target.acquire(type_, width, height) {
+            Some(staging) if staging.stride >= width as usize * 4 && !staging.ptr.is_null() => staging,
+            Some(staging) => {
+                self.target.release(type_, staging, &[]);
+                return false;
+            }
+            None => return false,
+        };
+        let full = Rect { x: 0, y: 0, width, height };
+        let rects = if staging.retains_contents {
+            dirty_rects.iter().filter_map(|rect| clip(*rect, full)).collect::<Vec<_>>()
+        } else {
+            vec![full]
+        };
+        let source_stride = width as usize * 4;
+        for rect in &rects {
+            let row_len = rect.width as usize * 4;
+            for y in rect.y..rect.y + rect.height {
+                let source = y as usize * source_stride + rect.x as usize * 4;
+                let target = y as usize * staging.stride + rect.x as usize * 4;
+                // The rect is clipped to the frame and the target promises
+                // room for `height` rows of `stride` bytes.
+                unsafe {
+                    let target = slice::from_raw_parts_mut(staging.ptr.add(target), row_len);
+                    target.copy_from_slice(&buffer[source..source + row_len]);
+                }
+            }
+        }
+        self.target.release(type_, staging, &rects);
+        true
+    }
+}
+
+/// Returns the part of `rect` inside `bounds`.
+fn clip(rect: Rect, bounds: Rect) -> Option<Rect> {
+    let x = rect.x.max(bounds.x);
+    let y = rect.y.max(bounds.y);
+    let right = (rect.x + rect.width).min(bounds.x + bounds.width);
+    let bottom = (rect.y + rect.height).min(bounds.y + bounds.height);
+    if right <= x || bottom <= y {
+        None
+    } else {
+        Some(Rect { x, y, width: right - x, height: bottom - y })
+    }
+}
+
+#[cfg(feature = "vulkan-staging")]
+pub mod vulkan {
+    //! A reference [StagingTarget] for Vulkan.
+
+    use super::*;
+    use ash::{
+        version::{DeviceV1_0, InstanceV1_0},
+        vk, Device, Instance,
+    };
+    use parking_lot::Mutex;
+
+    /// Rows of Vulkan staging buffers are aligned to this many bytes, which
+    /// satisfies `optimalBufferCopyRowPitchAlignment` on common hardware.
+    pub const ROW_ALIGNMENT: usize = 256;
+
+    struct State {
+        /// Copies recorded since the last [VulkanStagingTarget::take_copy_regions].
+        regions: Vec<vk::BufferImageCopy>,
+        /// Set once the buffer holds a complete frame.
+        filled: bool,
+    }
+
+    /// A persistently mapped, host-coherent Vulkan buffer holding one
+    /// `width` x `height` view frame. Popup paints are skipped.
+    ///
+    /// After each paint, take the changed areas with
+    /// [VulkanStagingTarget::take_copy_regions] and record them with
+    /// `vkCmdCopyBufferToImage` into a `B8G8R8A8_UNORM` image. The application
+    /// must wait for that copy to finish before the next paint, e.g. by
+    /// waiting on its fence before calling
+    /// [BrowserHost::send_external_begin_frame](crate::browser_host::BrowserHost::send_external_begin_frame)
+    /// or between frames of its render loop; CEF paints on the UI thread.
+    pub struct VulkanStagingTarget {
+        device: Device,
+        buffer: vk::Buffer,
+        memory: vk::DeviceMemory,
+        mapped: *mut u8,
+        width: i32,
+        height: i32,
+        stride: usize,
+        state: Mutex<State>,
+    }
+
+    // The mapped pointer is only written on the paint thread, between
+    // acquire and release.
+    unsafe impl Send for VulkanStagingTarget {}
+    unsafe impl Sync for VulkanStagingTarget {}
+
+    impl VulkanStagingTarget {
+        /// Allocates and maps a staging buffer for `width` x `height` frames.
+        ///
+        /// # Safety
+        ///
+        /// `instance`, `physical_device` and `device` must be valid and
+        /// outlive the returned value.
+        pub unsafe fn new(
+            instance: &Instance,
+            physical_device: vk::PhysicalDevice,
+            device: &Device,
+            width: i32,
+            height: i32,
+        ) -> Result<VulkanStagingTarget, vk::Result> {
+            let stride = (width.max(1) as usize * 4 + ROW_ALIGNMENT - 1) / ROW_ALIGNMENT * ROW_ALIGNMENT;
+            let size = (stride * height.max(1) as usize) as vk::DeviceSize;
+            let buffer = device.create_buffer(
+                &vk::BufferCreateInfo::builder()
+                    .size(size)
+                    .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None,
+            )?;
+            let requirements = device.get_buffer_memory_requirements(buffer);
+            let properties = instance.get_physical_device_memory_properties(physical_device);
+            let wanted = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+            let memory_type = (0..properties.memory_type_count).find(|&i| {
+                requirements.memory_type_bits & (1 << i) != 0
+                    && properties.memory_types[i as usize].property_flags.contains(wanted)
+            });
+            let memory_type = match memory_type {
+                Some(memory_type) => memory_type,
+                None => {
+                    device.destroy_buffer(buffer, None);
+                    return Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY);
+                }
+            };
+            let memory = match device.allocate_memory(
+                &vk::MemoryAllocateInfo::builder()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(memory_type),
+                None,
+            ) {
+                Ok(memory) => memory,
+                Err(error) => {
+                    device.destroy_buffer(buffer, None);
+                    return Err(error);
+                }
+            };
+            let mapped = device.bind_buffer_memory(buffer, memory, 0)
+                .and_then(|_| device.map_memory(memory, 0, size, vk::MemoryMapFlags::empty()));
+            let mapped = match mapped {
+                Ok(mapped) => mapped as *mut u8,
+                Err(error) => {
+                    device.destroy_buffer(buffer, None);
+                    device.free_memory(memory, None);
+                    return Err(error);
+                }
+            };
+            Ok(VulkanStagingTarget {
+                device: device.clone(),
+                buffer,
+                memory,
+                mapped,
+                width,
+                height,
+                stride,
+                state: Mutex::new(State {
+                    regions: Vec::new(),
+                    filled: false,
+                }),
+            })
+        }
+        pub fn buffer(&self) -> vk::Buffer {
+            self.buffer
+        }
+        pub fn size(&self) -> (i32, i32) {
+            (self.width, self.height)
+        }
+        /// Returns the regions to copy into the image since the last call.
+        pub fn take_copy_regions(&self) -> Vec<vk::BufferImageCopy> {
+            std::mem::replace(&mut self.state.lock().regions, Vec::new())
+        }
+    }
+
+    unsafe impl StagingTarget for VulkanStagingTarget {
+        fn acquire(&self, type_: PaintElementType, width: i32, height: i32) -> Option<StagingBuffer> {
+            if type_ != PaintElementType::View || width != self.width || height != self.height {
+                return None;
+            }
+            Some(StagingBuffer {
+                ptr: self.mapped,
+                stride: self.stride,
+                retains_contents: self.state.lock().filled,
+            })
+        }
+        fn release(&self, type_: PaintElementType, buffer: StagingBuffer, dirty_rects: &[Rect]) {
+            let mut state = self.state.lock();
+            state.filled |= !dirty_rects.is_empty();
+            for rect in dirty_rects {
+                state.regions.push(vk::BufferImageCopy {
+                    buffer_offset: (rect.y as usize * self.stride + rect.x as usize * 4) as vk::DeviceSize,
+                    buffer_row_length: (self.stride / 4) as u32,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: 0,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D { x: rect.x, y: rect.y, z: 0 },
+                    image_extent: vk::Extent3D { width: rect.width as u32, height: rect.height as u32, depth: 1 },
+                });
+            }
+        }
+    }
+
+    impl Drop for VulkanStagingTarget {
+        fn drop(&mut self) {
+            unsafe {
+                self.device.unmap_memory(self.memory);
+                self.device.destroy_buffer(self.buffer, None);
+                self.device.free_memory(self.memory, None);
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::{
+    browser::Browser,
+    browser_host::BrowserHost,
+    client::render_handler::TextInputMode,
+    events::{EventFlags, KeyEvent, WindowsKeyCode},
+    values::{Range, Rect},
+};
+
+/// The on-screen keyboard layouts matching the `inputmode` of the focused
+/// element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyboardLayout {
+    Text,
+    Telephone,
+    Url,
+    Email,
+    Numeric,
+    Decimal,
+    Search,
+}
+
+impl KeyboardLayout {
+    /// Returns the layout for `input_mode`, or `None` if no keyboard should be
+    /// shown.
+    pub fn from_input_mode(input_mode: TextInputMode) -> Option<KeyboardLayout> {
+        match input_mode {
+            TextInputMode::None => None,
+            TextInputMode::Default | TextInputMode::Text => Some(KeyboardLayout::Text),
+            TextInputMode::Tel => Some(KeyboardLayout::Telephone),
+            TextInputMode::URL => Some(KeyboardLayout::Url),
+            TextInputMode::Email => Some(KeyboardLayout::Email),
+            TextInputMode::Numeric => Some(KeyboardLayout::Numeric),
+            TextInputMode::Decimal => Some(KeyboardLayout::Decimal),
+            TextInputMode::Search => Some(KeyboardLayout::Search),
+        }
+    }
+}
+
+/// The text input state of the focused element of a browser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextInputState {
+    pub input_mode: TextInputMode,
+    /// The layout to show, or `None` if the keyboard should be hidden.
+    pub layout: Option<KeyboardLayout>,
+    pub selected_text: Option<String>,
+    /// The selected character range, or the caret position if it is empty.
+    pub selected_range: Option<Range>,
+    /// The composition range and the bounds of its first character, in view
+    /// coordinates. Useful for placing candidate windows and magnifiers.
+    pub composition: Option<(Range, Rect)>,
+}
+
+type StateListener = Arc<dyn 'static + Send + Sync + Fn(&TextInputState)>;
+
+struct KeyboardState {
+    host: Option<BrowserHost>,
+    state: TextInputState,
+    listeners: Vec<StateListener>,
+}
+
+/// Tracks the text input state of a windowless browser so an application can
+/// show its own on-screen keyboard, and types into the browser on its behalf.
+///
+/// Forward [RenderHandlerCallbacks::on_virtual_keyboard_requested],
+/// [RenderHandlerCallbacks::on_text_selection_changed] and
+/// [RenderHandlerCallbacks::on_ime_composition_range_changed] to the methods
+/// of the same names and listen for changes with
+/// [VirtualKeyboard::add_listener]. Text is inserted with
+/// [BrowserHost::ime_commit_text], so it goes through the same path as text
+/// committed by a system IME.
+///
+/// [RenderHandlerCallbacks::on_virtual_keyboard_requested]: crate::client::render_handler::RenderHandlerCallbacks::on_virtual_keyboard_requested
+/// [RenderHandlerCallbacks::on_text_selection_changed]: crate::client::render_handler::RenderHandlerCallbacks::on_text_selection_changed
+/// [RenderHandlerCallbacks::on_ime_composition_range_changed]: crate::client::render_handler::RenderHandlerCallbacks::on_ime_composition_range_changed
+#[derive(Clone)]
+pub struct VirtualKeyboard(Arc<Mutex<KeyboardState>>);
+
+impl VirtualKeyboard {
+    pub fn new() -> VirtualKeyboard {
+        VirtualKeyboard(Arc::new(Mutex::new(KeyboardState {
+            host: None,
+            state: TextInputState {
+                input_mode: TextInputMode::None,
+                layout: None,
+                selected_text: None,
+                selected_range: None,
+                composition: None,
+            },
+            listeners: Vec::new(),
+        })))
+    }
+    /// Calls `listener` whenever the text input state changes.
+    pub fn add_listener(&self, listener: impl 'static + Send + Sync + Fn(&TextInputState)) {
+        self.0.lock().listeners.push(Arc::new(listener));
+    }
+    /// Returns the current text input state.
+    pub fn state(&self) -> TextInputState {
+        self.0.lock().state.clone()
+    }
+    /// Returns true if an on-screen keyboard should currently be shown.
+    pub fn is_visible(&self) -> bool {
+        self.0.lock().state.layout.is_some()
+    }
+
+    pub fn on_virtual_keyboard_requested(&self, browser: &Browser, input_mode: TextInputMode) {
+        self.update(browser, |state| {
+            state.input_mode = input_mode;
+            state.layout = KeyboardLayout::from_input_mode(input_mode);
+            if state.layout.is_none() {
+                state.composition = None;
+            }
+        });
+    }
+    pub fn on_text_selection_changed(&self, browser: &Browser, selected_text: Option<&str>, selected_range: Range) {
+        self.update(browser, |state| {
+            state.selected_text = selected_text.filter(|text| !text.is_empty()).map(str::to_owned);
+            state.selected_range = Some(selected_range);
+        });
+    }
+    pub fn on_ime_composition_range_changed(&self, browser: &Browser, selected_range: Range, character_bounds: Rect) {
+        self.update(browser, |state| {
+            state.composition = Some((selected_range, character_bounds));
+        });
+    }
+
+    /// Inserts `text` at the caret, replacing the selection.
+    pub fn insert_text(&self, text: &str) {
+        if let Some(host) = self.host() {
+            host.ime_commit_text(Some(text), None, 0);
+        }
+    }
+    /// Presses and releases `key`, e.g. [WindowsKeyCode::Back] or
+    /// [WindowsKeyCode::Return]. Keys that produce a character, like
+    /// [WindowsKeyCode::Return], also send it in between, as a hardware
+    /// keyboard does.
+    pub fn press_key(&self, key: WindowsKeyCode) {
+        if let Some(host) = self.host() {
+            let (down, char, up) = key_events(key);
+            host.send_key_event(down);
+            if let Some(char) = char {
+                host.send_key_event(char);
+            }
+            host.send_key_event(up);
+        }
+    }
+    /// Removes focus from the focused element, in the main frame or any
+    /// subframe, which hides the keyboard.
+    pub fn dismiss(&self, browser: &Browser) {
+        for identifier in browser.get_frame_identifiers() {
+            if let Some(frame) = browser.get_frame_byident(identifier) {
+                frame.execute_java_script(
+                    "if (document.activeElement) { document.activeElement.blur(); }",
+                    "",
+                    0,
+                );
+            }
+        }
+    }
+
+    fn host(&self) -> Option<BrowserHost> {
+        self.0.lock().host.clone()
+    }
+    fn update(&self, browser: &Browser, f: impl FnOnce(&mut TextInputState)) {
+        let (state, listeners) = {
+            let mut keyboard = self.0.lock();
+            if keyboard.host.is_none() {
+                keyboard.host = Some(browser.get_host());
+            }
+            let old = keyboard.state.clone();
+            f(&mut keyboard.state);
+            if keyboard.state == old {
+                return;
+            }
+            (keyboard.state.clone(), keyboard.listeners.clone())
+        };
+        for listener in listeners {
+            listener(&state);
+        }
+    }
+}
+
+impl Default for VirtualKeyboard {
+    fn default() -> Self {
+        VirtualKeyboard::new()
+    }
+}
+
+/// Returns the character `key` produces without modifiers, if any.
+fn key_char(key: WindowsKeyCode) -> Option<char> {
+    match key {
+        WindowsKeyCode::Back => Some('\u{8}'),
+        WindowsKeyCode::Tab => Some('\t'),
+        WindowsKeyCode::Return => Some('\r'),
+        WindowsKeyCode::Escape => Some('\u{1b}'),
+        WindowsKeyCode::Space => Some(' '),
+        _ => None,
+    }
+}
+
+fn key_events(key: WindowsKeyCode) -> (KeyEvent, Option<KeyEvent>, KeyEvent) {
+    let down = KeyEvent::KeyDown {
+        modifiers: EventFlags::empty(),
+        windows_key_code: key,
+        native_key_code: 0,
+        is_system_key: false,
+        focus_on_editable_field: true,
+    };
+    let up = KeyEvent::KeyUp {
+        modifiers: EventFlags::empty(),
+        windows_key_code: key,
+        native_key_code: 0,
+        is_system_key: false,
+        focus_on_editable_field: true,
+    };
+    let char = key_char(key).map(|char| KeyEvent::Char {
+        modifiers: EventFlags::empty(),
+        char,
+    });
+    (down, char, up)
+}
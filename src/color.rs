@@ -44,3 +44,52 @@ impl std::fmt::Debug for Color {
         write!(f, "#{:02X}{:02X}{:02X}{:02X}", self.r(), self.g(), self.b(), self.a())
     }
 }
+
+/// Background of a browser, as stored in the `background_color` fields of
+/// [Settings](crate::settings::Settings) and
+/// [BrowserSettings](crate::browser::BrowserSettings). CEF only accepts
+/// fully opaque or fully transparent background colors.
+///
+/// Transparency only takes effect for windowless (off-screen) browsers; a
+/// windowed browser with a transparent background is painted opaque white. When
+/// transparent, the buffers passed to
+/// [RenderHandlerCallbacks::on_paint](crate::client::render_handler::RenderHandlerCallbacks::on_paint)
+/// contain premultiplied alpha, ready to be composited over other content with
+/// a `(ONE, ONE_MINUS_SRC_ALPHA)` blend function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transparency {
+    /// Transparent painting for windowless browsers. Pages without a
+    /// background of their own are see-through.
+    Transparent,
+    /// Paint the given color behind pages without a background. The alpha
+    /// component of the color is ignored.
+    Opaque(Color),
+}
+
+impl Transparency {
+    /// Converts a background color into a [Transparency], returning `None` if
+    /// the alpha component is neither 0x00 nor 0xFF.
+    pub fn from_color(color: Color) -> Option<Transparency> {
+        match color.a() {
+            0x00 => Some(Transparency::Transparent),
+            0xFF => Some(Transparency::Opaque(color)),
+            _ => None,
+        }
+    }
+    /// Returns the background color CEF expects for this setting.
+    pub fn to_color(self) -> Color {
+        match self {
+            Transparency::Transparent => Color::wrap(0),
+            Transparency::Opaque(color) => Color::wrap(color.0 | 0xFF00_0000),
+        }
+    }
+    pub fn is_transparent(self) -> bool {
+        self == Transparency::Transparent
+    }
+}
+
+impl Default for Transparency {
+    fn default() -> Self {
+        Transparency::Opaque(Color::wrap(0xFFFF_FFFF))
+    }
+}
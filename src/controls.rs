@@ -0,0 +1,258 @@
+//! Simple controls for browser chrome: image views, progress bars and
+//! separators.
+//!
+//! The views framework of the CEF version this crate binds has no image
+//! view, progress bar or separator, unlike the controls in
+//! [views](crate::views). These are models the application draws, with its
+//! own toolkit or in a [Panel](crate::views::Panel) of its own. They hold
+//! what the control shows and report changes, so a download shelf or a
+//! loading indicator can be fed straight from the handlers:
+//!
+//! ```ignore
+//! let progress = ProgressBar::new();
+//! progress.on_change(|state| ui.set_progress(state.value, state.visible));
+//! // in DisplayHandlerCallbacks::on_loading_progress_change:
+//! progress.on_loading_progress_change(value);
+//! // in DownloadHandlerCallbacks::on_download_updated:
+//! shelf_item_progress.on_download_updated(&download_item);
+//!
+//! let icon = ImageView::new();
+//! icon.on_change(|icon| ui.set_icon(icon.get_bitmap(window_scale_factor)));
+//! icon.set_image(Some(favicon));
+//! ```
+
+use crate::{
+    client::download_handler::DownloadItem,
+    image::{AlphaType, BinaryImage, ColorType, Image},
+    values::Size,
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+/// A line between groups of controls. It has no state besides its
+/// orientation; it exists so layouts can describe chrome with the same types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Separator {
+    pub orientation: Orientation,
+}
+
+impl Separator {
+    pub fn new(orientation: Orientation) -> Separator {
+        Separator { orientation }
+    }
+    /// Returns the size of the separator when it spans `length` DIP.
+    pub fn preferred_size(&self, length: i32) -> Size {
+        match self.orientation {
+            Orientation::Horizontal => Size { width: length, height: 1 },
+            Orientation::Vertical => Size { width: 1, height: length },
+        }
+    }
+}
+
+struct ImageViewInner {
+    image: Mutex<Option<Image>>,
+    /// The size to show the image at, or `None` for its own size.
+    image_size: Mutex<Option<Size>>,
+    tooltip: Mutex<String>,
+    listener: Mutex<Option<Arc<dyn 'static + Send + Sync + Fn(&ImageView)>>>,
+}
+
+/// Shows an [Image], e.g. a favicon or the icon of a download. Must be used
+/// on the browser process UI thread, like [Image].
+///
+/// Cloning is cheap; clones share the state.
+#[derive(Clone)]
+pub struct ImageView(Arc<ImageViewInner>);
+
+impl ImageView {
+    pub fn new() -> ImageView {
+        ImageView(Arc::new(ImageViewInner {
+            image: Mutex::new(None),
+            image_size: Mutex::new(None),
+            tooltip: Mutex::new(String::new()),
+            listener: Mutex::new(None),
+        }))
+    }
+    /// Calls `listener` whenever the image, its size or the tooltip changes.
+    pub fn on_change(&self, listener: impl 'static + Send + Sync + Fn(&ImageView)) {
+        *self.0.listener.lock() = Some(Arc::new(listener));
+    }
+    pub fn image(&self) -> Option<Image> {
+        self.0.image.lock().clone()
+    }
+    /// Sets the image, or clears it with `None`.
+    pub fn set_image(&self, image: Option<Image>) {
+        {
+            let mut current = self.0.image.lock();
+            let same = match (&*current, &image) {
+                (Some(current), Some(image)) => current.is_same(image),
+                (None, None) => true,
+                _ => false,
+            };
+            if same {
+                return;
+            }
+            *current = image;
+        }
+        self.changed();
+    }
+    /// Shows the image at `size` DIP instead of its own size.
+    pub fn set_image_size(&self, size: Option<Size>) {
+        if std::mem::replace(&mut *self.0.image_size.lock(), size) != size {
+            self.changed();
+        }
+    }
+    pub fn tooltip(&self) -> String {
+        self.0.tooltip.lock().clone()
+    }
+    pub fn set_tooltip(&self, tooltip: &str) {
+        if std::mem::replace(&mut *self.0.tooltip.lock(), tooltip.to_owned()) != tooltip {
+            self.changed();
+        }
+    }
+    /// Returns the size in DIP the image is shown at: the size set with
+    /// [ImageView::set_image_size], or the size of the image. Empty without
+    /// an image.
+    pub fn preferred_size(&self) -> Size {
+        if let Some(size) = *self.0.image_size.lock() {
+            return size;
+        }
+        match &*self.0.image.lock() {
+            Some(image) if !image.is_empty() => Size {
+                width: image.get_width() as i32,
+                height: image.get_height() as i32,
+            },
+            _ => Size { width: 0, height: 0 },
+        }
+    }
+    /// Returns the representation of the image that best matches
+    /// `scale_factor` as premultiplied BGRA pixels, ready to be drawn.
+    pub fn get_bitmap(&self, scale_factor: f32) -> Option<BinaryImage> {
+        match &*self.0.image.lock() {
+            Some(image) if !image.is_empty() => image.get_as_bitmap(scale_factor, ColorType::Bgra8888, AlphaType::Premultiplied),
+            _ => None,
+        }
+    }
+
+    fn changed(&self) {
+        // The listener reads the image view, and may change it.
+        let listener = self.0.listener.lock().clone();
+        if let Some(listener) = listener {
+            listener(self);
+        }
+    }
+}
+
+impl Default for ImageView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a progress bar shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressBarState {
+    /// The progress from 0.0 to 1.0, or `None` while it is unknown and the bar
+    /// should be animated instead.
+    pub value: Option<f64>,
+    pub visible: bool,
+}
+
+struct ProgressBarInner {
+    state: Mutex<ProgressBarState>,
+    listener: Mutex<Option<Arc<dyn 'static + Send + Sync + Fn(&ProgressBarState)>>>,
+}
+
+/// A progress bar for page loads or downloads. It starts hidden; setting a
+/// value shows it.
+///
+/// Cloning is cheap; clones share the state.
+#[derive(Clone)]
+pub struct ProgressBar(Arc<ProgressBarInner>);
+
+impl ProgressBar {
+    pub fn new() -> ProgressBar {
+        ProgressBar(Arc::new(ProgressBarInner {
+            state: Mutex::new(ProgressBarState { value: Some(0.0), visible: false }),
+            listener: Mutex::new(None),
+        }))
+    }
+    /// Calls `listener` whenever the state changes.
+    pub fn on_change(&self, listener: impl 'static + Send + Sync + Fn(&ProgressBarState)) {
+        *self.0.listener.lock() = Some(Arc::new(listener));
+    }
+    pub fn state(&self) -> ProgressBarState {
+        *self.0.state.lock()
+    }
+    /// Shows `value`, clamped to 0.0 to 1.0.
+    pub fn set_value(&self, value: f64) {
+        let value = if value.is_nan() { 0.0 } else { value.max(0.0).min(1.0) };
+        self.update(Some(value), true);
+    }
+    /// Shows that work is going on without knowing how much is left.
+    pub fn set_indeterminate(&self) {
+        self.update(None, true);
+    }
+    pub fn hide(&self) {
+        let value = self.0.state.lock().value;
+        self.update(value, false);
+    }
+
+    /// Call this from
+    /// [DisplayHandlerCallbacks::on_loading_progress_change](crate::client::display_handler::DisplayHandlerCallbacks::on_loading_progress_change).
+    /// The bar is hidden once the page finished loading.
+    pub fn on_loading_progress_change(&self, progress: f64) {
+        if progress >= 1.0 {
+            self.update(Some(1.0), false);
+        } else {
+            self.set_value(progress);
+        }
+    }
+    /// Call this from
+    /// [DownloadHandlerCallbacks::on_download_updated](crate::client::download_handler::DownloadHandlerCallbacks::on_download_updated)
+    /// for the download the bar belongs to. The bar is indeterminate while the
+    /// size is unknown and hidden once the download ended.
+    pub fn on_download_updated(&self, download_item: &DownloadItem) {
+        if !download_item.is_valid() {
+            return;
+        }
+        if download_item.is_complete() {
+            self.update(Some(1.0), false);
+        } else if download_item.is_canceled() {
+            self.hide();
+        } else {
+            match download_item.get_percent_complete() {
+                Some(percent) => self.set_value(f64::from(percent) / 100.0),
+                None => self.set_indeterminate(),
+            }
+        }
+    }
+
+    fn update(&self, value: Option<f64>, visible: bool) {
+        let state = {
+            let mut state = self.0.state.lock();
+            let new_state = ProgressBarState { value, visible };
+            if *state == new_state {
+                return;
+            }
+            *state = new_state;
+            new_state
+        };
+        let listener = self.0.listener.lock().clone();
+        if let Some(listener) = listener {
+            listener(&state);
+        }
+    }
+}
+
+impl Default for ProgressBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
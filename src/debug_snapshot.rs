@@ -0,0 +1,187 @@
+//! Browser state snapshots for attaching to bug reports.
+//!
+//! CEF doesn't keep console output around, so console errors only show up in a
+//! [DebugSnapshot] if they are recorded with [record_console_message], usually
+//! from [DisplayHandlerCallbacks::on_console_message](crate::client::display_handler::DisplayHandlerCallbacks::on_console_message).
+
+use crate::{
+    browser::Browser,
+    browser_host::NavigationEntryVisitor,
+    settings::LogSeverity,
+    values::{DictionaryValue, ListValue, StoredValue},
+};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+/// The number of console errors kept per browser.
+pub const MAX_CONSOLE_ERRORS: usize = 20;
+
+lazy_static::lazy_static! {
+    static ref CONSOLE_ERRORS: Mutex<HashMap<i32, VecDeque<ConsoleError>>> = Mutex::new(HashMap::new());
+}
+
+/// A console message with a severity of [LogSeverity::Error] or higher.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleError {
+    pub time: DateTime<Utc>,
+    pub message: String,
+    pub source: String,
+    pub line: usize,
+}
+
+/// Records a console message for inclusion in future snapshots of `browser`.
+/// Messages below [LogSeverity::Error] are ignored. Only the last
+/// [MAX_CONSOLE_ERRORS] errors are kept.
+pub fn record_console_message(browser: &Browser, level: LogSeverity, message: &str, source: &str, line: usize) {
+    if level != LogSeverity::Error && level != LogSeverity::Fatal {
+        return;
+    }
+    let mut errors = CONSOLE_ERRORS.lock();
+    let errors = errors.entry(browser.get_identifier()).or_insert_with(VecDeque::new);
+    if errors.len() == MAX_CONSOLE_ERRORS {
+        errors.pop_front();
+    }
+    errors.push_back(ConsoleError {
+        time: Utc::now(),
+        message: message.to_owned(),
+        source: source.to_owned(),
+        line,
+    });
+}
+
+/// Drops the console errors recorded for `browser`. Call this from
+/// [LifeSpanHandlerCallbacks::on_before_close](crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_before_close).
+pub fn forget_browser(browser: &Browser) {
+    CONSOLE_ERRORS.lock().remove(&browser.get_identifier());
+}
+
+/// A navigation history entry as recorded in a [DebugSnapshot].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavigationEntrySnapshot {
+    pub url: String,
+    pub original_url: String,
+    pub title: String,
+    pub http_status_code: u16,
+    pub completion_time: DateTime<Utc>,
+    pub has_post_data: bool,
+    pub current: bool,
+}
+
+/// The state of a browser at a point in time. See [Browser::debug_snapshot].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugSnapshot {
+    pub time: DateTime<Utc>,
+    pub browser_id: i32,
+    pub url: String,
+    pub is_popup: bool,
+    pub is_loading: bool,
+    pub has_document: bool,
+    pub can_go_back: bool,
+    pub can_go_forward: bool,
+    pub frame_count: usize,
+    pub navigation_entries: Vec<NavigationEntrySnapshot>,
+    /// The ID of the browser process. CEF does not expose the IDs of renderer
+    /// processes.
+    pub browser_process_id: u32,
+    pub zoom_level: f64,
+    pub audio_muted: bool,
+    pub console_errors: Vec<ConsoleError>,
+}
+
+impl DebugSnapshot {
+    /// Collects the state of `browser`. Must be called on the browser process
+    /// UI thread, since navigation entries are only available there.
+    pub fn capture(browser: &Browser) -> DebugSnapshot {
+        let host = browser.get_host();
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let visited = entries.clone();
+        // The visitor is executed synchronously on the UI thread.
+        host.get_navigation_entries(NavigationEntryVisitor::new(move |visit| {
+            visited.lock().push(NavigationEntrySnapshot {
+                url: visit.entry.get_url(),
+                original_url: visit.entry.get_original_url(),
+                title: visit.entry.get_title(),
+                http_status_code: visit.entry.get_http_status_code(),
+                completion_time: visit.entry.get_completion_time(),
+                has_post_data: visit.entry.has_post_data(),
+                current: visit.current,
+            });
+            true
+        }), false);
+        let navigation_entries = std::mem::replace(&mut *entries.lock(), Vec::new());
+        let console_errors = CONSOLE_ERRORS.lock()
+            .get(&browser.get_identifier())
+            .map(|errors| errors.iter().cloned().collect())
+            .unwrap_or_default();
+
+        DebugSnapshot {
+            time: Utc::now(),
+            browser_id: browser.get_identifier(),
+            url: browser.get_main_frame().get_url(),
+            is_popup: browser.is_popup(),
+            is_loading: browser.is_loading(),
+            has_document: browser.has_document(),
+            can_go_back: browser.can_go_back(),
+            can_go_forward: browser.can_go_forward(),
+            frame_count: browser.get_frame_count(),
+            navigation_entries,
+            browser_process_id: std::process::id(),
+            zoom_level: host.get_zoom_level(),
+            audio_muted: host.is_audio_muted(),
+            console_errors,
+        }
+    }
+
+    /// Converts the snapshot into a [StoredValue], e.g. for sending it to
+    /// another process.
+    pub fn to_value(&self) -> StoredValue {
+        let snapshot = DictionaryValue::new();
+        snapshot.insert_string("time", &self.time.to_rfc3339());
+        snapshot.insert_int("browser_id", self.browser_id);
+        snapshot.insert_string("url", &self.url);
+        snapshot.insert_bool("is_popup", self.is_popup);
+        snapshot.insert_bool("is_loading", self.is_loading);
+        snapshot.insert_bool("has_document", self.has_document);
+        snapshot.insert_bool("can_go_back", self.can_go_back);
+        snapshot.insert_bool("can_go_forward", self.can_go_forward);
+        snapshot.insert_int("frame_count", self.frame_count as i32);
+        snapshot.insert_double("browser_process_id", self.browser_process_id as f64);
+        snapshot.insert_double("zoom_level", self.zoom_level);
+        snapshot.insert_bool("audio_muted", self.audio_muted);
+
+        let entries = ListValue::new();
+        for (i, entry) in self.navigation_entries.iter().enumerate() {
+            let value = DictionaryValue::new();
+            value.insert_string("url", &entry.url);
+            value.insert_string("original_url", &entry.original_url);
+            value.insert_string("title", &entry.title);
+            value.insert_int("http_status_code", entry.http_status_code as i32);
+            value.insert_string("completion_time", &entry.completion_time.to_rfc3339());
+            value.insert_bool("has_post_data", entry.has_post_data);
+            value.insert_bool("current", entry.current);
+            entries.set_dictionary(i, value);
+        }
+        snapshot.insert_list("navigation_entries", entries);
+
+        let errors = ListValue::new();
+        for (i, error) in self.console_errors.iter().enumerate() {
+            let value = DictionaryValue::new();
+            value.insert_string("time", &error.time.to_rfc3339());
+            value.insert_string("message", &error.message);
+            value.insert_string("source", &error.source);
+            value.insert_int("line", error.line as i32);
+            errors.set_dictionary(i, value);
+        }
+        snapshot.insert_list("console_errors", errors);
+
+        StoredValue::Dictionary(snapshot)
+    }
+    /// Serializes the snapshot as JSON.
+    pub fn to_json(&self) -> String {
+        self.to_value().to_json().unwrap_or_default()
+    }
+}
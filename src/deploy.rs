@@ -0,0 +1,358 @@
+//! Copying the CEF binary distribution next to an executable.
+//!
+//! An application only runs if `libcef`, its helper libraries, the V8
+//! snapshots and the resources of a CEF binary distribution sit next to it.
+//! [Distribution::deploy] copies them for the target platform and records
+//! them in a manifest, which [verify] later checks against.
+//!
+//! On macOS the target directory is the `.app` bundle instead, and the
+//! framework goes into its `Contents/Frameworks` directory.
+//!
+//! The module is part of the `cef` crate and so links `cef-sys`. Build
+//! scripts using it need `cef` as a build dependency as well:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     cef::deploy::deploy_from_build_script().unwrap();
+//! }
+//! ```
+//!
+//! Cargo doesn't create app bundles, so on macOS run the `cef-deploy` binary
+//! of the `deploy-cli` feature on the bundle after creating it.
+
+use std::{
+    env, fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// The environment variable pointing at the extracted CEF binary distribution.
+pub const CEF_ROOT_VAR: &str = "CEF_ROOT";
+
+/// The name of the manifest written into the target directory.
+pub const MANIFEST_NAME: &str = "cef-deploy.manifest";
+
+/// The platform a distribution is built for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Windows,
+    Linux,
+    MacOS,
+}
+
+impl Platform {
+    /// Returns the platform this code is compiled for.
+    pub fn current() -> Platform {
+        if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::MacOS
+        } else {
+            Platform::Linux
+        }
+    }
+    /// Returns the platform of a target triple such as the `TARGET` variable
+    /// of build scripts.
+    pub fn from_target(target: &str) -> Option<Platform> {
+        if target.contains("windows") {
+            Some(Platform::Windows)
+        } else if target.contains("apple-darwin") {
+            Some(Platform::MacOS)
+        } else if target.contains("linux") {
+            Some(Platform::Linux)
+        } else {
+            None
+        }
+    }
+    /// Where the binary files go, relative to the target directory.
+    fn binary_target_dir(self) -> &'static Path {
+        match self {
+            Platform::MacOS => Path::new("Contents/Frameworks"),
+            _ => Path::new(""),
+        }
+    }
+
+    /// Files from the `Release` or `Debug` directory. The last item is true if
+    /// the file is optional.
+    fn binary_files(self) -> &'static [(&'static str, bool)] {
+        match self {
+            Platform::Windows => &[
+                ("libcef.dll", false),
+                ("chrome_elf.dll", false),
+                ("libEGL.dll", false),
+                ("libGLESv2.dll", false),
+                ("d3dcompiler_47.dll", true),
+                ("snapshot_blob.bin", false),
+                ("v8_context_snapshot.bin", false),
+                ("swiftshader", true),
+            ],
+            Platform::Linux => &[
+                ("libcef.so", false),
+                ("libEGL.so", false),
+                ("libGLESv2.so", false),
+                ("chrome-sandbox", true),
+                ("snapshot_blob.bin", false),
+                ("v8_context_snapshot.bin", false),
+                ("swiftshader", true),
+            ],
+            // The framework contains the resources as well.
+            Platform::MacOS => &[("Chromium Embedded Framework.framework", false)],
+        }
+    }
+    /// Files from the `Resources` directory.
+    fn resource_files(self) -> &'static [(&'static str, bool)] {
+        match self {
+            Platform::MacOS => &[],
+            _ => &[
+                ("icudtl.dat", false),
+                ("resources.pak", false),
+                ("chrome_100_percent.pak", false),
+                ("chrome_200_percent.pak", false),
+                ("devtools_resources.pak", true),
+                ("locales", false),
+            ],
+        }
+    }
+}
+
+/// Why deploying or verifying failed.
+#[derive(Debug)]
+pub enum DeployError {
+    /// [CEF_ROOT_VAR] or a build script variable isn't set.
+    MissingVariable(&'static str),
+    /// A required file isn't part of the distribution.
+    MissingSource(PathBuf),
+    /// A deployed file is missing or differs from the manifest.
+    Mismatch(PathBuf),
+    /// The target directory of a macOS deployment isn't an `.app` bundle.
+    NotAnAppBundle(PathBuf),
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for DeployError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeployError::MissingVariable(name) => write!(f, "environment variable {} is not set", name),
+            DeployError::MissingSource(path) => write!(f, "{} is missing from the CEF distribution", path.display()),
+            DeployError::Mismatch(path) => write!(f, "{} is missing or differs from the manifest", path.display()),
+            DeployError::NotAnAppBundle(path) => write!(f, "{} is not an .app bundle", path.display()),
+            DeployError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for DeployError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DeployError::Io(_, error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A deployed file, as recorded in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// The path relative to the target directory, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    /// The FNV-1a hash of the contents.
+    pub hash: u64,
+}
+
+/// An extracted CEF binary distribution, as downloaded from
+/// <https://cef-builds.spotifycdn.com>.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Distribution {
+    pub root: PathBuf,
+    pub platform: Platform,
+    /// Use the `Debug` binaries instead of `Release`.
+    pub debug: bool,
+}
+
+impl Distribution {
+    pub fn new<T: Into<PathBuf>>(root: T, platform: Platform) -> Distribution {
+        Distribution {
+            root: root.into(),
+            platform,
+            debug: false,
+        }
+    }
+    /// Uses the distribution [CEF_ROOT_VAR] points at, for the current
+    /// platform.
+    pub fn from_env() -> Result<Distribution, DeployError> {
+        let root = env::var_os(CEF_ROOT_VAR).ok_or(DeployError::MissingVariable(CEF_ROOT_VAR))?;
+        Ok(Self::new(root, Platform::current()))
+    }
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// Returns the files to deploy as pairs of source path and path relative
+    /// to the target directory. Fails if a required file is missing.
+    pub fn files(&self) -> Result<Vec<(PathBuf, PathBuf)>, DeployError> {
+        let binary_dir = self.root.join(if self.debug { "Debug" } else { "Release" });
+        let resources_dir = self.root.join("Resources");
+        let mut files = Vec::new();
+        let groups = [
+            (binary_dir, self.platform.binary_target_dir(), self.platform.binary_files()),
+            (resources_dir, Path::new(""), self.platform.resource_files()),
+        ];
+        for (dir, target_dir, names) in &groups {
+            for (name, optional) in names.iter() {
+                let source = dir.join(name);
+                if source.exists() {
+                    collect_files(&source, &target_dir.join(name), &mut files)?;
+                } else if !optional {
+                    return Err(DeployError::MissingSource(source));
+                }
+            }
+        }
+        Ok(files)
+    }
+    /// Copies the distribution into `target_dir` and writes the manifest.
+    /// Files that are already up to date aren't copied again. Returns the
+    /// manifest entries.
+    ///
+    /// On macOS `target_dir` must be the `.app` bundle.
+    pub fn deploy(&self, target_dir: &Path) -> Result<Vec<ManifestEntry>, DeployError> {
+        if self.platform == Platform::MacOS && target_dir.extension().map_or(true, |extension| extension != "app") {
+            return Err(DeployError::NotAnAppBundle(target_dir.to_owned()));
+        }
+        let mut manifest = Vec::new();
+        for (source, relative) in self.files()? {
+            let target = target_dir.join(&relative);
+            let up_to_date = match (fs::metadata(&source), fs::metadata(&target)) {
+                (Ok(source), Ok(target)) => match (source.modified(), target.modified()) {
+                    (Ok(source_time), Ok(target_time)) => source.len() == target.len() && target_time >= source_time,
+                    _ => false,
+                },
+                _ => false,
+            };
+            if !up_to_date {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent).map_err(|error| DeployError::Io(parent.to_owned(), error))?;
+                }
+                fs::copy(&source, &target).map_err(|error| DeployError::Io(source.clone(), error))?;
+            }
+            let (size, hash) = hash_file(&target)?;
+            manifest.push(ManifestEntry {
+                path: relative_to_string(&relative),
+                size,
+                hash,
+            });
+        }
+        write_manifest(target_dir, &manifest)?;
+        Ok(manifest)
+    }
+}
+
+/// Checks the files in `target_dir` against the manifest written by
+/// [Distribution::deploy].
+pub fn verify(target_dir: &Path) -> Result<(), DeployError> {
+    for entry in read_manifest(target_dir)? {
+        let path = target_dir.join(&entry.path);
+        match hash_file(&path) {
+            Ok((size, hash)) if size == entry.size && hash == entry.hash => (),
+            _ => return Err(DeployError::Mismatch(path)),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the manifest written by [Distribution::deploy].
+pub fn read_manifest(target_dir: &Path) -> Result<Vec<ManifestEntry>, DeployError> {
+    let path = target_dir.join(MANIFEST_NAME);
+    let file = File::open(&path).map_err(|error| DeployError::Io(path.clone(), error))?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|error| DeployError::Io(path.clone(), error))?;
+        let mut fields = line.splitn(3, '\t');
+        let entry = (|| Some(ManifestEntry {
+            size: fields.next()?.parse().ok()?,
+            hash: u64::from_str_radix(fields.next()?, 16).ok()?,
+            path: fields.next()?.to_owned(),
+        }))();
+        match entry {
+            Some(entry) => entries.push(entry),
+            None => return Err(DeployError::Io(path, io::Error::new(io::ErrorKind::InvalidData, "malformed manifest line"))),
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the directory cargo puts the binaries of the package being built
+/// in, e.g. `target/debug`, based on the `OUT_DIR` of build scripts.
+pub fn target_dir_from_build_env() -> Result<PathBuf, DeployError> {
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").ok_or(DeployError::MissingVariable("OUT_DIR"))?);
+    // OUT_DIR is target/<profile>/build/<package>-<hash>/out.
+    out_dir.ancestors().nth(3).map(Path::to_owned).ok_or(DeployError::MissingVariable("OUT_DIR"))
+}
+
+/// Deploys the distribution [CEF_ROOT_VAR] points at next to the binaries of
+/// the package being built, for the build script's `TARGET`. Call from
+/// `build.rs`.
+///
+/// Fails with [DeployError::NotAnAppBundle] when targeting macOS, as the
+/// framework has to go into the app bundle cargo doesn't create.
+pub fn deploy_from_build_script() -> Result<Vec<ManifestEntry>, DeployError> {
+    println!("cargo:rerun-if-env-changed={}", CEF_ROOT_VAR);
+    let target = env::var("TARGET").map_err(|_| DeployError::MissingVariable("TARGET"))?;
+    let mut distribution = Distribution::from_env()?;
+    distribution.platform = Platform::from_target(&target).unwrap_or_else(Platform::current);
+    distribution.deploy(&target_dir_from_build_env()?)
+}
+
+fn collect_files(source: &Path, relative: &Path, files: &mut Vec<(PathBuf, PathBuf)>) -> Result<(), DeployError> {
+    if source.is_dir() {
+        let entries = fs::read_dir(source).map_err(|error| DeployError::Io(source.to_owned(), error))?;
+        for entry in entries {
+            let entry = entry.map_err(|error| DeployError::Io(source.to_owned(), error))?;
+            collect_files(&entry.path(), &relative.join(entry.file_name()), files)?;
+        }
+    } else {
+        files.push((source.to_owned(), relative.to_owned()));
+    }
+    Ok(())
+}
+
+fn relative_to_string(relative: &Path) -> String {
+    relative.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn write_manifest(target_dir: &Path, manifest: &[ManifestEntry]) -> Result<(), DeployError> {
+    let path = target_dir.join(MANIFEST_NAME);
+    let mut contents = String::new();
+    for entry in manifest {
+        contents.push_str(&format!("{}\t{:016x}\t{}\n", entry.size, entry.hash, entry.path));
+    }
+    File::create(&path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|error| DeployError::Io(path, error))
+}
+
+/// Returns the size and FNV-1a hash of the file at `path`.
+fn hash_file(path: &Path) -> Result<(u64, u64), DeployError> {
+    let mut file = File::open(path).map_err(|error| DeployError::Io(path.to_owned(), error))?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    loop {
+        let read = file.read(&mut buffer).map_err(|error| DeployError::Io(path.to_owned(), error))?;
+        if read == 0 {
+            break;
+        }
+        size += read as u64;
+        for byte in &buffer[..read] {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    Ok((size, hash))
+}
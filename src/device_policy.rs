@@ -0,0 +1,261 @@
+//! Keeping web content away from host peripherals.
+//!
+//! A [DevicePolicy] decides per device API whether pages may use it: not at
+//! all, everywhere, or only on listed origins. It is enforced in three layers,
+//! from coarse to fine:
+//!
+//! * [Settings::device_policy](crate::settings::Settings::device_policy)
+//!   removes WebUSB and WebHID from Blink entirely if they are denied
+//!   everywhere.
+//! * [DevicePolicy::apply_to_request_context] blocks the USB and HID content
+//!   settings of a request context, except for allowed origins.
+//! * [DevicePolicy::enforce] removes `navigator.getGamepads`, `navigator.hid`
+//!   and `navigator.usb` from the documents of a browser whose origin isn't
+//!   allowed. Gamepads have no content setting, so this is the only way to
+//!   restrict them.
+//!
+//! ```ignore
+//! let policy = DevicePolicy::new().gamepad(DeviceAccess::allow_origins(&["https://games.example"]));
+//! policy.apply_to_request_context(&context)?;
+//! let guard = policy.enforce(&browser.get_host());
+//! ```
+
+use crate::{
+    browser_host::BrowserHost,
+    request_context::RequestContext,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+};
+
+/// The switch disabling Blink runtime features.
+const DISABLE_BLINK_FEATURES_SWITCH: &str = "disable-blink-features";
+
+/// Content setting value blocking an API.
+const CONTENT_SETTING_BLOCK: i32 = 2;
+/// Content setting value asking the user, Chromium's default for devices.
+const CONTENT_SETTING_ASK: i32 = 3;
+
+/// A device API web content can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceApi {
+    Gamepad,
+    Hid,
+    Usb,
+}
+
+impl DeviceApi {
+    /// The Blink runtime feature, for APIs that can be disabled globally.
+    fn blink_feature(self) -> Option<&'static str> {
+        match self {
+            DeviceApi::Gamepad => None,
+            DeviceApi::Hid => Some("WebHID"),
+            DeviceApi::Usb => Some("WebUSB"),
+        }
+    }
+    /// The content settings type, for APIs with a content setting.
+    fn content_setting(self) -> Option<&'static str> {
+        match self {
+            DeviceApi::Gamepad => None,
+            DeviceApi::Hid => Some("hid_guard"),
+            DeviceApi::Usb => Some("usb_guard"),
+        }
+    }
+    /// The expression removing the API from a document.
+    fn removal_script(self) -> &'static str {
+        match self {
+            DeviceApi::Gamepad => "hide(Navigator.prototype, 'getGamepads'); hide(Navigator.prototype, 'webkitGetGamepads'); hide(window, 'ongamepadconnected'); hide(window, 'ongamepaddisconnected');",
+            DeviceApi::Hid => "hide(Navigator.prototype, 'hid');",
+            DeviceApi::Usb => "hide(Navigator.prototype, 'usb');",
+        }
+    }
+}
+
+/// Whether a device API is available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceAccess {
+    Deny,
+    Allow,
+    /// Only available to documents of these origins, e.g.
+    /// `https://app.example`.
+    AllowOrigins(Vec<String>),
+}
+
+impl DeviceAccess {
+    pub fn allow_origins(origins: &[&str]) -> DeviceAccess {
+        DeviceAccess::AllowOrigins(origins.iter().map(|origin| origin.trim_end_matches('/').to_owned()).collect())
+    }
+    /// Returns true if documents of `origin` may use the API.
+    pub fn allows(&self, origin: &str) -> bool {
+        match self {
+            DeviceAccess::Deny => false,
+            DeviceAccess::Allow => true,
+            DeviceAccess::AllowOrigins(origins) => origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin)),
+        }
+    }
+}
+
+/// Which device APIs pages may use. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevicePolicy {
+    pub gamepad: DeviceAccess,
+    pub hid: DeviceAccess,
+    pub usb: DeviceAccess,
+}
+
+impl DevicePolicy {
+    /// Creates a policy denying every device API.
+    pub fn new() -> DevicePolicy {
+        DevicePolicy {
+            gamepad: DeviceAccess::Deny,
+            hid: DeviceAccess::Deny,
+            usb: DeviceAccess::Deny,
+        }
+    }
+    /// Creates a policy allowing every device API, which is Chromium's
+    /// behavior.
+    pub fn allow_all() -> DevicePolicy {
+        DevicePolicy {
+            gamepad: DeviceAccess::Allow,
+            hid: DeviceAccess::Allow,
+            usb: DeviceAccess::Allow,
+        }
+    }
+    pub fn gamepad(mut self, gamepad: DeviceAccess) -> Self {
+        self.gamepad = gamepad;
+        self
+    }
+    pub fn hid(mut self, hid: DeviceAccess) -> Self {
+        self.hid = hid;
+        self
+    }
+    pub fn usb(mut self, usb: DeviceAccess) -> Self {
+        self.usb = usb;
+        self
+    }
+    pub fn access(&self, api: DeviceApi) -> &DeviceAccess {
+        match api {
+            DeviceApi::Gamepad => &self.gamepad,
+            DeviceApi::Hid => &self.hid,
+            DeviceApi::Usb => &self.usb,
+        }
+    }
+
+    /// Returns the Blink features to disable globally, i.e. those of APIs
+    /// that are denied everywhere.
+    pub(crate) fn disabled_blink_features(&self) -> Vec<&'static str> {
+        [DeviceApi::Gamepad, DeviceApi::Hid, DeviceApi::Usb].iter()
+            .filter(|api| *self.access(**api) == DeviceAccess::Deny)
+            .filter_map(|api| api.blink_feature())
+            .collect()
+    }
+    /// Sets the USB and HID content settings of `request_context`: blocked by
+    /// default, allowed for listed origins. Content settings the running CEF
+    /// build doesn't know are skipped. Must be called on the browser process
+    /// UI thread.
+    pub fn apply_to_request_context(&self, request_context: &RequestContext) -> Result<(), String> {
+        for api in &[DeviceApi::Hid, DeviceApi::Usb] {
+            let setting = match api.content_setting() {
+                Some(setting) => setting,
+                None => continue,
+            };
+            let default_name = format!("profile.default_content_setting_values.{}", setting);
+            if request_context.has_preference(&default_name) && request_context.can_set_preference(&default_name) {
+                let value = match self.access(*api) {
+                    DeviceAccess::Allow => CONTENT_SETTING_ASK,
+                    _ => CONTENT_SETTING_BLOCK,
+                };
+                request_context.set_preference(&default_name, Some(StoredValue::Int(value)))?;
+            }
+            let exceptions_name = format!("profile.content_settings.exceptions.{}", setting);
+            if let DeviceAccess::AllowOrigins(origins) = self.access(*api) {
+                if request_context.has_preference(&exceptions_name) && request_context.can_set_preference(&exceptions_name) {
+                    let exceptions = DictionaryValue::new();
+                    for origin in origins {
+                        let exception = DictionaryValue::new();
+                        exception.insert_int("setting", CONTENT_SETTING_ASK);
+                        exceptions.insert_dictionary(&format!("{},*", origin), exception);
+                    }
+                    request_context.set_preference(&exceptions_name, Some(StoredValue::Dictionary(exceptions)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Removes disallowed device APIs from the documents of the browser of
+    /// `host`, including documents that are already loaded. Enforcement stops
+    /// for new documents when the returned value is dropped.
+    pub fn enforce(&self, host: &BrowserHost) -> DeviceGuard {
+        let scripts = UserScripts::new(host);
+        let script = UserScript::new(&self.enforcement_source());
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        DeviceGuard { scripts, script_id }
+    }
+
+    fn enforcement_source(&self) -> String {
+        let mut source = String::from(
+            "function hide(target, name) {\n\
+             \x20 try { Object.defineProperty(target, name, { configurable: true, get: function() { return undefined; } }); } catch (e) {}\n\
+             \x20 try { delete target[name]; } catch (e) {}\n\
+             }\n\
+             var origin = location.origin.toLowerCase();\n",
+        );
+        for api in &[DeviceApi::Gamepad, DeviceApi::Hid, DeviceApi::Usb] {
+            let condition = match self.access(*api) {
+                DeviceAccess::Allow => continue,
+                DeviceAccess::Deny => "true".to_owned(),
+                DeviceAccess::AllowOrigins(origins) => {
+                    let origins = origins.iter()
+                        .map(|origin| StoredValue::String(origin.to_ascii_lowercase()).to_json().unwrap_or_default())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("[{}].indexOf(origin) < 0", origins)
+                }
+            };
+            source.push_str(&format!("if ({}) {{ {} }}\n", condition, api.removal_script()));
+        }
+        source
+    }
+}
+
+impl Default for DevicePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keeps a [DevicePolicy] enforced in a browser, returned by
+/// [DevicePolicy::enforce].
+pub struct DeviceGuard {
+    scripts: UserScripts,
+    script_id: UserScriptId,
+}
+
+impl Drop for DeviceGuard {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script_id);
+    }
+}
+
+/// Adds `features` to the value of the "disable-blink-features" switch in
+/// `switches`, keeping features disabled before.
+pub(crate) fn disable_blink_features(switches: &mut Vec<(String, Option<String>)>, features: &[&str]) {
+    if features.is_empty() {
+        return;
+    }
+    let existing = switches.iter_mut().find(|(name, _)| name == DISABLE_BLINK_FEATURES_SWITCH);
+    match existing {
+        Some((_, value)) => {
+            let mut all = value.as_ref()
+                .map(|value| value.split(',').filter(|feature| !feature.is_empty()).map(str::to_owned).collect::<Vec<_>>())
+                .unwrap_or_default();
+            for feature in features {
+                if !all.iter().any(|existing| existing == feature) {
+                    all.push((*feature).to_owned());
+                }
+            }
+            *value = Some(all.join(","));
+        }
+        None => switches.push((DISABLE_BLINK_FEATURES_SWITCH.to_owned(), Some(features.join(",")))),
+    }
+}
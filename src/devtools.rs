@@ -0,0 +1,329 @@
+//! Method call and event plumbing on top of
+//! [BrowserHost::execute_dev_tools_method] and [DevToolsMessageObserver].
+//!
+//! A [DevToolsSession] matches method results to the calls that produced them
+//! and dispatches protocol events to registered listeners, so callers don't
+//! need to keep track of message IDs themselves.
+//!
+//! For in-app debugging consoles, [DevToolsSession::eval_in_inspected_page]
+//! evaluates an expression the way the DevTools console does, with its
+//! command line API (`$0`, `$$`, `copy`, ...), and
+//! [DevToolsSession::eval_in_devtools] evaluates in the DevTools frontend
+//! itself, e.g. to script its panels.
+
+use crate::{
+    browser::Browser,
+    browser_host::BrowserHost,
+    devtools_message_observer::{DevToolsMessageObserver, DevToolsMessageObserverCallbacks},
+    registration::Registration,
+    task::{TaskRunner, ThreadId},
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{atomic::{AtomicI32, AtomicU64, Ordering}, Arc, Weak},
+    task::{Context, Poll, Waker},
+};
+
+// Message IDs are scoped to a browser, but several sessions may be attached to
+// the same browser, so hand them out from a single counter.
+static NEXT_MESSAGE_ID: AtomicI32 = AtomicI32::new(1_000_000);
+static NEXT_LISTENER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Error returned by a DevTools protocol method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevToolsError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl DevToolsError {
+    fn local(message: &str) -> DevToolsError {
+        DevToolsError {
+            code: -1,
+            message: message.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for DevToolsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DevTools error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for DevToolsError {}
+
+pub type DevToolsResult = Result<StoredValue, DevToolsError>;
+
+/// Identifies an event listener added with [DevToolsSession::add_event_listener].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventListenerId(u64);
+
+type ResultCallback = Box<dyn 'static + Send + FnOnce(DevToolsResult)>;
+type EventCallback = Arc<dyn 'static + Send + Sync + Fn(&Browser, &StoredValue)>;
+
+struct SessionState {
+    host: BrowserHost,
+    pending: Mutex<HashMap<i32, ResultCallback>>,
+    listeners: Mutex<Vec<(EventListenerId, String, EventCallback)>>,
+    registration: Mutex<Option<Registration>>,
+}
+
+/// A DevTools protocol client bound to a single browser. Cloning the session
+/// is cheap and all clones share the same observer registration, which is
+/// removed once the last clone is dropped.
+///
+/// Methods may be called from any thread; calls are forwarded to the browser
+/// process UI thread as required by CEF. Result and event callbacks are always
+/// executed on the UI thread.
+#[derive(Clone)]
+pub struct DevToolsSession(Arc<SessionState>);
+
+impl DevToolsSession {
+    /// Attaches a new session to `host`.
+    pub fn new(host: &BrowserHost) -> DevToolsSession {
+        let state = Arc::new(SessionState {
+            host: host.clone(),
+            pending: Mutex::new(HashMap::new()),
+            listeners: Mutex::new(Vec::new()),
+            registration: Mutex::new(None),
+        });
+        let weak = Arc::downgrade(&state);
+        Self::run_on_ui(move || {
+            if let Some(state) = weak.upgrade() {
+                let observer = DevToolsMessageObserver::new(SessionObserver(Arc::downgrade(&state)));
+                *state.registration.lock() = Some(state.host.add_dev_tools_message_observer(observer));
+            }
+        });
+        DevToolsSession(state)
+    }
+    /// Returns the host this session is attached to.
+    pub fn host(&self) -> &BrowserHost {
+        &self.0.host
+    }
+    /// Executes the DevTools protocol `method` with optional `params`. `callback`
+    /// receives the `result` dictionary of the method, or the protocol error if
+    /// the method failed or could not be submitted.
+    pub fn execute(
+        &self,
+        method: &str,
+        params: Option<DictionaryValue>,
+        callback: impl 'static + Send + FnOnce(DevToolsResult),
+    ) {
+        let message_id = NEXT_MESSAGE_ID.fetch_add(1, Ordering::SeqCst);
+        self.0.pending.lock().insert(message_id, Box::new(callback));
+        let state = self.0.clone();
+        let method = method.to_owned();
+        Self::run_on_ui(move || {
+            if !state.host.execute_dev_tools_method(message_id, &method, params) {
+                // The callback may execute another method, which takes the lock.
+                let callback = state.pending.lock().remove(&message_id);
+                if let Some(callback) = callback {
+                    callback(Err(DevToolsError::local("unable to submit DevTools method")));
+                }
+            }
+        });
+    }
+    /// Executes `method` and ignores its result.
+    pub fn send(&self, method: &str, params: Option<DictionaryValue>) {
+        self.execute(method, params, |_| ());
+    }
+    /// Calls `listener` with the `params` of every `method` event received by
+    /// this session. Note that most domains only emit events after their
+    /// `enable` method has been called.
+    pub fn add_event_listener(
+        &self,
+        method: &str,
+        listener: impl 'static + Send + Sync + Fn(&Browser, &StoredValue),
+    ) -> EventListenerId {
+        let id = EventListenerId(NEXT_LISTENER_ID.fetch_add(1, Ordering::SeqCst));
+        self.0.listeners.lock().push((id, method.to_owned(), Arc::new(listener)));
+        id
+    }
+    /// Removes a listener added with [DevToolsSession::add_event_listener].
+    pub fn remove_event_listener(&self, id: EventListenerId) {
+        self.0.listeners.lock().retain(|(listener_id, _, _)| *listener_id != id);
+    }
+
+    /// Evaluates `expression` in the main frame of the inspected page, like
+    /// the DevTools console does: with the console's command line API, and
+    /// awaiting the result if it is a promise. Resolves to the result as a
+    /// value; objects that can't be serialized resolve to `Null`. An
+    /// exception thrown by the expression resolves to an error with its
+    /// description.
+    pub fn eval_in_inspected_page(&self, expression: &str) -> Evaluation {
+        self.evaluate(expression, true)
+    }
+    /// Evaluates `expression` in the DevTools frontend showing the DevTools of
+    /// this session's browser, e.g. the browser of a
+    /// [DevToolsDock](crate::devtools_dock::DevToolsDock). CEF doesn't report
+    /// which browser shows the DevTools, so it is passed as `devtools`;
+    /// evaluation fails if it isn't showing a DevTools frontend.
+    pub fn eval_in_devtools(&self, devtools: &Browser, expression: &str) -> Evaluation {
+        let url = devtools.get_main_frame().get_url();
+        if !url.starts_with("devtools:") {
+            let state = Arc::new(Mutex::new(EvaluationState::default()));
+            finish_evaluation(&state, Err(DevToolsError::local("the browser isn't showing DevTools")));
+            return Evaluation(state);
+        }
+        DevToolsSession::new(&devtools.get_host()).evaluate(expression, false)
+    }
+
+    fn evaluate(&self, expression: &str, include_command_line_api: bool) -> Evaluation {
+        let state = Arc::new(Mutex::new(EvaluationState::default()));
+        let params = DictionaryValue::new();
+        params.insert_string("expression", expression);
+        params.insert_bool("includeCommandLineAPI", include_command_line_api);
+        params.insert_bool("awaitPromise", true);
+        params.insert_bool("returnByValue", true);
+        params.insert_string("objectGroup", "console");
+        let result_state = state.clone();
+        // The callback holds a clone of the session, which keeps the observer
+        // registered until the result arrived.
+        let session = self.clone();
+        self.execute("Runtime.evaluate", Some(params), move |result| {
+            let _session = session;
+            let result = result.and_then(|result| match result {
+                StoredValue::Dictionary(result) => {
+                    if let Some(details) = result.try_get_dictionary("exceptionDetails") {
+                        let message = details
+                            .try_get_dictionary("exception")
+                            .map(|exception| exception.get_string("description"))
+                            .filter(|description| !description.is_empty())
+                            .unwrap_or_else(|| details.get_string("text"));
+                        return Err(DevToolsError::local(&message));
+                    }
+                    Ok(match result.try_get_dictionary("result") {
+                        Some(remote_object) => match remote_object.get("value") {
+                            StoredValue::Invalid => StoredValue::Null,
+                            value => value,
+                        },
+                        None => StoredValue::Null,
+                    })
+                }
+                _ => Err(DevToolsError::local("malformed evaluation result")),
+            });
+            finish_evaluation(&result_state, result);
+        });
+        Evaluation(state)
+    }
+
+    fn run_on_ui(f: impl 'static + Send + FnOnce()) {
+        if TaskRunner::currently_on(ThreadId::UI) {
+            f();
+        } else {
+            TaskRunner::post_task_on(ThreadId::UI, f);
+        }
+    }
+}
+
+#[derive(Default)]
+struct EvaluationState {
+    result: Option<DevToolsResult>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the value of an expression evaluated with
+/// [DevToolsSession::eval_in_inspected_page] or
+/// [DevToolsSession::eval_in_devtools].
+pub struct Evaluation(Arc<Mutex<EvaluationState>>);
+
+impl Future for Evaluation {
+    type Output = DevToolsResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<DevToolsResult> {
+        let mut state = self.0.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn finish_evaluation(state: &Mutex<EvaluationState>, result: DevToolsResult) {
+    let waker = {
+        let mut state = state.lock();
+        state.result = Some(result);
+        state.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+struct SessionObserver(Weak<SessionState>);
+
+impl DevToolsMessageObserverCallbacks for SessionObserver {
+    fn on_dev_tools_method_result(
+        &mut self,
+        browser: Browser,
+        message_id: i32,
+        success: bool,
+        result: &[u8],
+    ) {
+        let state = match self.0.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let callback = state.pending.lock().remove(&message_id);
+        if let Some(callback) = callback {
+            let value = StoredValue::from_json_bytes(result).unwrap_or(StoredValue::Null);
+            if success {
+                callback(Ok(value));
+            } else {
+                let error = match value {
+                    StoredValue::Dictionary(error) => DevToolsError {
+                        code: error.get_int("code"),
+                        message: error.get_string("message"),
+                    },
+                    _ => DevToolsError::local("malformed DevTools error"),
+                };
+                callback(Err(error));
+            }
+        }
+    }
+    fn on_dev_tools_event(
+        &mut self,
+        browser: Browser,
+        method: &str,
+        params: &[u8],
+    ) {
+        let state = match self.0.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        // Clone the matching listeners so they may add or remove listeners
+        // themselves without deadlocking.
+        let listeners = state.listeners.lock().iter()
+            .filter(|(_, listener_method, _)| listener_method == method)
+            .map(|(_, _, listener)| listener.clone())
+            .collect::<Vec<_>>();
+        if listeners.is_empty() {
+            return;
+        }
+        let params = StoredValue::from_json_bytes(params).unwrap_or(StoredValue::Null);
+        for listener in listeners {
+            listener(&browser, &params);
+        }
+    }
+    fn on_dev_tools_agent_detached(
+        &mut self,
+        browser: Browser,
+    ) {
+        // Pending results will never be delivered once the agent detaches.
+        if let Some(state) = self.0.upgrade() {
+            let pending = std::mem::replace(&mut *state.pending.lock(), HashMap::new());
+            for (_, callback) in pending {
+                callback(Err(DevToolsError::local("DevTools agent detached")));
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! DevTools docked into the application window.
+//!
+//! [BrowserHost::show_dev_tools](crate::browser_host::BrowserHost::show_dev_tools) opens DevTools in a window of its own. A
+//! [DevToolsDock] opens them as a windowless browser instead, which the
+//! application paints next to the inspected page, like Chrome's docked
+//! DevTools. The dock splits the window between the two, resizes the
+//! DevTools browser with it and tracks which of the two has focus, so input
+//! goes to the right one:
+//!
+//! ```ignore
+//! let dock = DevToolsDock::new(&browser, DockSide::Bottom, 300);
+//! // The client paints into the application's surface and returns
+//! // `dock.view_rect()` from RenderHandlerCallbacks::get_view_rect.
+//! dock.set_client(Client::new(DevToolsClient { life_span_handler: dock.life_span_handler(), ... }));
+//! // on F12:
+//! dock.toggle();
+//! // when the window is resized:
+//! let (page_rect, devtools_rect) = dock.layout(window_rect);
+//! // when forwarding input:
+//! let target = dock.focused_browser();
+//! ```
+//!
+//! Must be used on the browser process UI thread.
+
+use crate::{
+    browser::Browser,
+    client::{
+        life_span_handler::{LifeSpanHandler, LifeSpanHandlerCallbacks},
+        Client,
+    },
+    values::{Point, Rect},
+    window::WindowInfo,
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The edge of the window DevTools are docked to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DockSide {
+    Bottom,
+    Right,
+    Left,
+}
+
+/// Changes of a [DevToolsDock].
+#[derive(Clone)]
+pub enum DockEvent {
+    /// The DevTools browser was created. Call [DevToolsDock::layout] to give
+    /// it its bounds.
+    Opened(Browser),
+    /// The DevTools browser was closed, by [DevToolsDock::close] or by
+    /// DevTools themselves.
+    Closed,
+    /// The DevTools bounds changed; the page has the rest of the window.
+    Resized(Rect),
+}
+
+/// The smallest size of the page and DevTools when the window is split.
+const MIN_SIZE: i32 = 50;
+
+struct DockState {
+    inspected: Browser,
+    client: Option<Client>,
+    devtools: Option<Browser>,
+    opening: bool,
+    side: DockSide,
+    size: i32,
+    bounds: Rect,
+    devtools_focused: bool,
+}
+
+struct DockInner {
+    state: Mutex<DockState>,
+    listener: Mutex<Option<Box<dyn 'static + Send + Fn(&DockEvent)>>>,
+}
+
+/// DevTools of one browser, docked into its window. See the
+/// [module documentation](self).
+///
+/// Cloning is cheap; clones share the state.
+#[derive(Clone)]
+pub struct DevToolsDock(Arc<DockInner>);
+
+impl DevToolsDock {
+    /// Creates a closed dock for `inspected` that takes `size` DIP at `side`
+    /// of the window when open.
+    pub fn new(inspected: &Browser, side: DockSide, size: i32) -> DevToolsDock {
+        DevToolsDock(Arc::new(DockInner {
+            state: Mutex::new(DockState {
+                inspected: inspected.clone(),
+                client: None,
+                devtools: None,
+                opening: false,
+                side,
+                size: size.max(MIN_SIZE),
+                bounds: Rect { x: 0, y: 0, width: 0, height: 0 },
+                devtools_focused: false,
+            }),
+            listener: Mutex::new(None),
+        }))
+    }
+    /// Sets the client of the DevTools browser. It must use the handler of
+    /// [DevToolsDock::life_span_handler] and paint the DevTools browser,
+    /// returning [DevToolsDock::view_rect] as its view rectangle.
+    pub fn set_client(&self, client: Client) {
+        self.0.state.lock().client = Some(client);
+    }
+    /// Returns the life span handler for the client of the DevTools browser.
+    pub fn life_span_handler(&self) -> LifeSpanHandler {
+        LifeSpanHandler::new(self.clone())
+    }
+    /// Calls `listener` whenever DevTools open, close or are resized.
+    pub fn on_event(&self, listener: impl 'static + Send + Fn(&DockEvent)) {
+        *self.0.listener.lock() = Some(Box::new(listener));
+    }
+
+    pub fn is_open(&self) -> bool {
+        let state = self.0.state.lock();
+        state.devtools.is_some() || state.opening
+    }
+    /// Returns the DevTools browser while it is open.
+    pub fn browser(&self) -> Option<Browser> {
+        self.0.state.lock().devtools.clone()
+    }
+    pub fn inspected_browser(&self) -> Browser {
+        self.0.state.lock().inspected.clone()
+    }
+    /// Opens DevTools. Returns false if no client was set.
+    pub fn open(&self) -> bool {
+        self.open_at(None)
+    }
+    /// Opens DevTools and inspects the element at `point` of the page, in
+    /// view coordinates. If DevTools are open already, only the element is
+    /// inspected. Returns false if no client was set.
+    pub fn open_at(&self, point: Option<Point>) -> bool {
+        let (inspected, client) = {
+            let mut state = self.0.state.lock();
+            let client = match &state.client {
+                Some(client) => client.clone(),
+                None => return false,
+            };
+            state.opening = state.devtools.is_none();
+            (state.inspected.clone(), client)
+        };
+        let host = inspected.get_host();
+        let window_info = WindowInfo::windowless(host.get_window_handle());
+        host.show_dev_tools(&window_info, Some(client), None, point.unwrap_or(Point { x: 0, y: 0 }));
+        true
+    }
+    /// Closes DevTools.
+    pub fn close(&self) {
+        let inspected = self.inspected_browser();
+        inspected.get_host().close_dev_tools();
+    }
+    /// Opens DevTools if they are closed and closes them otherwise. Returns
+    /// whether they are open now.
+    pub fn toggle(&self) -> bool {
+        if self.is_open() {
+            self.close();
+            false
+        } else {
+            self.open()
+        }
+    }
+
+    pub fn side(&self) -> DockSide {
+        self.0.state.lock().side
+    }
+    /// Moves DevTools to `side`. Call [DevToolsDock::layout] afterwards.
+    pub fn set_side(&self, side: DockSide) {
+        self.0.state.lock().side = side;
+    }
+    /// Sets the width or height DevTools take, e.g. while the user drags the
+    /// splitter. Call [DevToolsDock::layout] afterwards.
+    pub fn set_size(&self, size: i32) {
+        self.0.state.lock().size = size.max(MIN_SIZE);
+    }
+    /// Splits `window` between the page and DevTools and returns the bounds of
+    /// both, DevTools' only while they are open. The DevTools browser is told
+    /// about its new size.
+    pub fn layout(&self, window: Rect) -> (Rect, Option<Rect>) {
+        let (devtools, bounds, resized) = {
+            let mut state = self.0.state.lock();
+            let devtools = match &state.devtools {
+                Some(devtools) => devtools.clone(),
+                None => return (window, None),
+            };
+            let (page, bounds) = split(window, state.side, state.size);
+            let resized = state.bounds != bounds;
+            state.bounds = bounds;
+            (devtools, (page, bounds), resized)
+        };
+        if resized {
+            devtools.get_host().was_resized();
+            self.emit(&DockEvent::Resized(bounds.1));
+        }
+        (bounds.0, Some(bounds.1))
+    }
+    /// Returns the view rectangle of the DevTools browser, for
+    /// [RenderHandlerCallbacks::get_view_rect](crate::client::render_handler::RenderHandlerCallbacks::get_view_rect).
+    /// Its origin is always zero; the application draws it at the bounds
+    /// returned by [DevToolsDock::layout].
+    pub fn view_rect(&self) -> Rect {
+        let bounds = self.0.state.lock().bounds;
+        Rect {
+            x: 0,
+            y: 0,
+            width: bounds.width.max(1),
+            height: bounds.height.max(1),
+        }
+    }
+
+    /// Gives focus to DevTools, e.g. on a click into their bounds.
+    pub fn focus_devtools(&self) {
+        self.set_devtools_focused(true);
+    }
+    /// Gives focus back to the page.
+    pub fn focus_page(&self) {
+        self.set_devtools_focused(false);
+    }
+    /// Routes a click at `point`, in window coordinates, to the browser under
+    /// it and returns that browser with the point translated to its view
+    /// coordinates.
+    pub fn route_click(&self, point: Point) -> (Browser, Point) {
+        let (in_devtools, bounds) = {
+            let state = self.0.state.lock();
+            let bounds = state.bounds;
+            let in_devtools = state.devtools.is_some()
+                && point.x >= bounds.x
+                && point.y >= bounds.y
+                && point.x < bounds.x + bounds.width
+                && point.y < bounds.y + bounds.height;
+            (in_devtools, bounds)
+        };
+        self.set_devtools_focused(in_devtools);
+        let browser = self.focused_browser();
+        if in_devtools {
+            (browser, Point { x: point.x - bounds.x, y: point.y - bounds.y })
+        } else {
+            (browser, point)
+        }
+    }
+    /// Returns the browser keyboard input should go to.
+    pub fn focused_browser(&self) -> Browser {
+        let state = self.0.state.lock();
+        match (&state.devtools, state.devtools_focused) {
+            (Some(devtools), true) => devtools.clone(),
+            _ => state.inspected.clone(),
+        }
+    }
+
+    fn set_devtools_focused(&self, focused: bool) {
+        let (inspected, devtools) = {
+            let mut state = self.0.state.lock();
+            let devtools = match &state.devtools {
+                Some(devtools) => devtools.clone(),
+                None => return,
+            };
+            if state.devtools_focused == focused {
+                return;
+            }
+            state.devtools_focused = focused;
+            (state.inspected.clone(), devtools)
+        };
+        inspected.get_host().set_focus(!focused);
+        devtools.get_host().set_focus(focused);
+    }
+    fn emit(&self, event: &DockEvent) {
+        if let Some(listener) = &*self.0.listener.lock() {
+            listener(event);
+        }
+    }
+}
+
+/// Splits `window` into the page and DevTools bounds.
+fn split(window: Rect, side: DockSide, size: i32) -> (Rect, Rect) {
+    match side {
+        DockSide::Bottom => {
+            let size = size.min(window.height - MIN_SIZE).max(0);
+            (
+                Rect { height: window.height - size, ..window },
+                Rect { y: window.y + window.height - size, height: size, ..window },
+            )
+        }
+        DockSide::Right => {
+            let size = size.min(window.width - MIN_SIZE).max(0);
+            (
+                Rect { width: window.width - size, ..window },
+                Rect { x: window.x + window.width - size, width: size, ..window },
+            )
+        }
+        DockSide::Left => {
+            let size = size.min(window.width - MIN_SIZE).max(0);
+            (
+                Rect { x: window.x + size, width: window.width - size, ..window },
+                Rect { width: size, ..window },
+            )
+        }
+    }
+}
+
+impl LifeSpanHandlerCallbacks for DevToolsDock {
+    fn on_after_created(&self, browser: Browser) {
+        {
+            let mut state = self.0.state.lock();
+            state.opening = false;
+            state.devtools = Some(browser.clone());
+            state.devtools_focused = true;
+        }
+        self.emit(&DockEvent::Opened(browser));
+    }
+    fn on_before_close(&self, browser: Browser) {
+        let inspected = {
+            let mut state = self.0.state.lock();
+            match &state.devtools {
+                Some(devtools) if devtools.get_identifier() == browser.get_identifier() => (),
+                _ => return,
+            }
+            state.devtools = None;
+            state.opening = false;
+            state.devtools_focused = false;
+            state.bounds = Rect { x: 0, y: 0, width: 0, height: 0 };
+            state.inspected.clone()
+        };
+        inspected.get_host().set_focus(true);
+        self.emit(&DockEvent::Closed);
+    }
+}
@@ -95,7 +95,7 @@ pub trait DevToolsMessageObserverCallbacks: 'static + Send {
 struct DevToolsMessageObserverWrapper(SendProtectorMut<Box<dyn DevToolsMessageObserverCallbacks>>);
 
 impl DevToolsMessageObserver {
-    fn new(callbacks: impl DevToolsMessageObserverCallbacks) -> DevToolsMessageObserver {
+    pub fn new(callbacks: impl DevToolsMessageObserverCallbacks) -> DevToolsMessageObserver {
         unsafe{ DevToolsMessageObserver::from_ptr_unchecked(DevToolsMessageObserverWrapper(SendProtectorMut::new(Box::new(callbacks))).wrap().into_raw()) }
     }
 }
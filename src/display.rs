@@ -0,0 +1,149 @@
+//! The displays CEF knows about.
+//!
+//! [Display] wraps `cef_display_t`, which reports the monitors as Chromium
+//! sees them: bounds and work areas in screen coordinates of
+//! device-independent pixels, and the scale factor to convert to physical
+//! pixels. It is part of CEF's views API but also works with windowless
+//! browsers, where [SystemDisplays] feeds it to a
+//! [ScreenGeometry](crate::client::display_provider::ScreenGeometry):
+//!
+//! ```ignore
+//! let display = Display::get_matching_bounds(window_rect, false).unwrap();
+//! let work_area = display.get_work_area();
+//! window.set_position(work_area.x + (work_area.width - width) / 2, work_area.y + (work_area.height - height) / 2);
+//! ```
+//!
+//! All functions must be called on the browser process UI thread.
+
+use cef_sys::{
+    cef_display_get_alls, cef_display_get_count, cef_display_get_matching_bounds,
+    cef_display_get_nearest_point, cef_display_get_primary, cef_display_t, cef_point_t, cef_rect_t,
+};
+use std::ptr::null_mut;
+
+use crate::{
+    client::display_provider::{self, DisplayProvider},
+    values::{Point, Rect},
+};
+
+ref_counted_ptr! {
+    /// A display connected to the system. The functions of this structure must
+    /// be called on the browser process UI thread.
+    pub struct Display(*mut cef_display_t);
+}
+
+impl Display {
+    /// Returns the primary display.
+    pub fn get_primary() -> Option<Display> {
+        unsafe { Display::from_ptr(cef_display_get_primary()) }
+    }
+    /// Returns the display nearest the specified point. Set `input_pixel_coords`
+    /// to true if `point` is in pixel screen coordinates instead of
+    /// device-independent pixel screen coordinates.
+    pub fn get_nearest_point(point: Point, input_pixel_coords: bool) -> Option<Display> {
+        unsafe { Display::from_ptr(cef_display_get_nearest_point(&cef_point_t::from(point), input_pixel_coords as _)) }
+    }
+    /// Returns the display that most closely intersects `bounds`. Set
+    /// `input_pixel_coords` to true if `bounds` is in pixel screen coordinates
+    /// instead of device-independent pixel screen coordinates.
+    pub fn get_matching_bounds(bounds: Rect, input_pixel_coords: bool) -> Option<Display> {
+        unsafe { Display::from_ptr(cef_display_get_matching_bounds(&cef_rect_t::from(bounds), input_pixel_coords as _)) }
+    }
+    /// Returns the total number of displays.
+    pub fn get_count() -> usize {
+        unsafe { cef_display_get_count() as usize }
+    }
+    /// Returns all displays, the primary one first.
+    pub fn get_all() -> Vec<Display> {
+        let mut count = Display::get_count();
+        let mut displays = vec![null_mut(); count];
+        unsafe {
+            cef_display_get_alls(&mut count as *mut usize as *mut _, displays.as_mut_ptr());
+        }
+        displays.truncate(count);
+        displays.into_iter().filter_map(|display| unsafe { Display::from_ptr(display) }).collect()
+    }
+
+    /// Returns the unique identifier for this display.
+    pub fn get_id(&self) -> i64 {
+        self.0.get_id.map(|get_id| unsafe { get_id(self.as_ptr()) }).unwrap_or(0)
+    }
+    /// Returns this display's device pixel scale factor. This specifies how much
+    /// the UI should be scaled when the actual output has more pixels than
+    /// standard displays (which is around 100~120dpi). The potential return
+    /// values differ by platform.
+    pub fn get_device_scale_factor(&self) -> f32 {
+        self.0
+            .get_device_scale_factor
+            .map(|get_device_scale_factor| unsafe { get_device_scale_factor(self.as_ptr()) })
+            .unwrap_or(1.0)
+    }
+    /// Converts `point` from device-independent pixels to pixels on this
+    /// display.
+    pub fn convert_point_to_pixels(&self, point: Point) -> Point {
+        let mut point: cef_point_t = point.into();
+        if let Some(convert_point_to_pixels) = self.0.convert_point_to_pixels {
+            unsafe { convert_point_to_pixels(self.as_ptr(), &mut point) };
+        }
+        point.into()
+    }
+    /// Converts `point` from pixels on this display to device-independent
+    /// pixels.
+    pub fn convert_point_from_pixels(&self, point: Point) -> Point {
+        let mut point: cef_point_t = point.into();
+        if let Some(convert_point_from_pixels) = self.0.convert_point_from_pixels {
+            unsafe { convert_point_from_pixels(self.as_ptr(), &mut point) };
+        }
+        point.into()
+    }
+    /// Returns this display's bounds. This is the full size of the display.
+    pub fn get_bounds(&self) -> Rect {
+        self.0
+            .get_bounds
+            .map(|get_bounds| unsafe { get_bounds(self.as_ptr()) }.into())
+            .unwrap_or(Rect { x: 0, y: 0, width: 0, height: 0 })
+    }
+    /// Returns this display's work area. This excludes areas of the display
+    /// that are occupied for window manager toolbars, etc.
+    pub fn get_work_area(&self) -> Rect {
+        self.0
+            .get_work_area
+            .map(|get_work_area| unsafe { get_work_area(self.as_ptr()) }.into())
+            .unwrap_or(Rect { x: 0, y: 0, width: 0, height: 0 })
+    }
+    /// Returns this display's rotation in degrees.
+    pub fn get_rotation(&self) -> i32 {
+        self.0.get_rotation.map(|get_rotation| unsafe { get_rotation(self.as_ptr()) }).unwrap_or(0)
+    }
+
+    /// Converts the display to the description used by a
+    /// [DisplayProvider]. CEF doesn't report names or color depths, so the
+    /// name is empty and the depth 24 bits.
+    pub fn to_provider_display(&self, primary: bool) -> display_provider::Display {
+        display_provider::Display {
+            id: self.get_id() as u64,
+            name: String::new(),
+            rect: self.get_bounds(),
+            work_area: self.get_work_area(),
+            scale_factor: self.get_device_scale_factor(),
+            depth: 24,
+            primary,
+        }
+    }
+}
+
+/// A [DisplayProvider] that asks CEF for the displays every time, so monitor
+/// changes are picked up without further work. Must be used on the browser
+/// process UI thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemDisplays;
+
+impl DisplayProvider for SystemDisplays {
+    fn displays(&self) -> Vec<display_provider::Display> {
+        let primary_id = Display::get_primary().map(|primary| primary.get_id());
+        Display::get_all()
+            .iter()
+            .map(|display| display.to_provider_display(Some(display.get_id()) == primary_id))
+            .collect()
+    }
+}
@@ -0,0 +1,219 @@
+//! Choosing what `getDisplayMedia()` shares.
+//!
+//! CEF 85 has no desktop capture picker, so `navigator.mediaDevices.getDisplayMedia()`
+//! fails in CEF browsers. [DisplayMediaPicker] replaces it with a shim that
+//! asks the application which screen or window to share and opens the chosen
+//! source through the desktop capture constraints of `getUserMedia()`, which
+//! requires [Settings::enable_media_stream](crate::settings::Settings::enable_media_stream):
+//!
+//! ```ignore
+//! let picker = DisplayMediaPicker::attach(&browser, |_, request, callback| {
+//!     if request.origin == "https://meet.example" {
+//!         callback.select(DisplayMediaSource::Screen(0));
+//!     } else {
+//!         callback.cancel();
+//!     }
+//! });
+//! ```
+//!
+//! To share a source without any code in the application, use
+//! [Settings::auto_select_display_media](crate::settings::Settings::auto_select_display_media)
+//! instead.
+
+use crate::{
+    browser::Browser,
+    devtools::DevToolsSession,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+    window::RawWindow,
+};
+
+/// The name of the DevTools binding requests are reported through.
+const BINDING_NAME: &str = "__cefDisplayMediaRequest";
+
+const SHIM_SOURCE: &str = "\
+var notify = window.__cefDisplayMediaRequest;\n\
+var devices = navigator.mediaDevices;\n\
+if (typeof notify !== 'function' || !devices || window.__cefDisplayMedia) return;\n\
+try { delete window.__cefDisplayMediaRequest; } catch (e) {}\n\
+var pending = {}, nextId = 1;\n\
+devices.getDisplayMedia = function(constraints) {\n\
+  constraints = constraints || {};\n\
+  var id = nextId++;\n\
+  return new Promise(function(resolve, reject) {\n\
+    pending[id] = { constraints: constraints, resolve: resolve, reject: reject };\n\
+    notify(JSON.stringify({ id: id, origin: location.origin, video: constraints.video !== false, audio: !!constraints.audio }));\n\
+  });\n\
+};\n\
+window.__cefDisplayMedia = {\n\
+  select: function(id, sourceId) {\n\
+    var request = pending[id];\n\
+    if (!request) return;\n\
+    delete pending[id];\n\
+    var mandatory = { chromeMediaSource: 'desktop', chromeMediaSourceId: sourceId };\n\
+    navigator.mediaDevices.getUserMedia({\n\
+      video: { mandatory: mandatory },\n\
+      audio: request.constraints.audio ? { mandatory: mandatory } : false\n\
+    }).then(request.resolve, request.reject);\n\
+  },\n\
+  cancel: function(id) {\n\
+    var request = pending[id];\n\
+    if (!request) return;\n\
+    delete pending[id];\n\
+    request.reject(new DOMException('Permission denied', 'NotAllowedError'));\n\
+  }\n\
+};";
+
+/// A screen or window that can be shared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DisplayMediaSource {
+    /// The screen with this index, 0 being the primary screen.
+    Screen(u32),
+    /// The top-level window with this native identifier: a `HWND` on
+    /// Windows, an X11 window on Linux and a `CGWindowID` on macOS.
+    Window(u64),
+}
+
+impl DisplayMediaSource {
+    /// Returns the source of a native window, e.g. the application's own
+    /// top-level window. Not available on macOS, where the `CGWindowID` has to
+    /// be looked up by the application.
+    pub fn from_raw_window(window: &RawWindow) -> Option<DisplayMediaSource> {
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        {
+            Some(DisplayMediaSource::Window(window.to_cef_handle() as usize as u64))
+        }
+        #[cfg(not(any(target_os = "windows", target_os = "linux")))]
+        {
+            None
+        }
+    }
+    /// Returns Chromium's desktop media identifier, e.g. `screen:0:0`.
+    pub fn to_media_id(&self) -> String {
+        match self {
+            DisplayMediaSource::Screen(index) => format!("screen:{}:0", index),
+            DisplayMediaSource::Window(id) => format!("window:{}:0", id),
+        }
+    }
+}
+
+/// A `getDisplayMedia()` call of a page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayMediaRequest {
+    /// The origin of the main frame, e.g. `https://meet.example`.
+    pub origin: String,
+    pub video: bool,
+    /// Set if the page also asked for system audio.
+    pub audio: bool,
+}
+
+/// Answers a [DisplayMediaRequest]. Dropping it without answering cancels the
+/// request.
+pub struct DisplayMediaCallback {
+    browser: Browser,
+    id: i32,
+    answered: bool,
+}
+
+impl DisplayMediaCallback {
+    /// Shares `source` with the page.
+    pub fn select(mut self, source: DisplayMediaSource) {
+        self.answered = true;
+        let media_id = StoredValue::String(source.to_media_id()).to_json().unwrap_or_default();
+        self.call(&format!("select({}, {})", self.id, media_id));
+    }
+    /// Rejects the request with a `NotAllowedError`.
+    pub fn cancel(mut self) {
+        self.answered = true;
+        self.call(&format!("cancel({})", self.id));
+    }
+
+    fn call(&self, call: &str) {
+        let code = format!("window.__cefDisplayMedia && window.__cefDisplayMedia.{};", call);
+        self.browser.get_main_frame().execute_java_script(&code, "", 0);
+    }
+}
+
+impl Drop for DisplayMediaCallback {
+    fn drop(&mut self) {
+        if !self.answered {
+            self.call(&format!("cancel({})", self.id));
+        }
+    }
+}
+
+/// Routes the `getDisplayMedia()` calls of one browser's main frame to the
+/// application. See the [module documentation](self).
+pub struct DisplayMediaPicker {
+    session: DevToolsSession,
+    scripts: UserScripts,
+    script_id: UserScriptId,
+}
+
+impl DisplayMediaPicker {
+    /// Installs the shim into the documents of `browser` and calls
+    /// `on_request` on the UI thread for every `getDisplayMedia()` call.
+    pub fn attach(
+        browser: &Browser,
+        on_request: impl 'static + Send + Sync + Fn(&Browser, &DisplayMediaRequest, DisplayMediaCallback),
+    ) -> DisplayMediaPicker {
+        let session = DevToolsSession::new(&browser.get_host());
+        session.add_event_listener("Runtime.bindingCalled", move |browser, params| {
+            let params = match params {
+                StoredValue::Dictionary(params) if params.get_string("name") == BINDING_NAME => params,
+                _ => return,
+            };
+            let payload = match StoredValue::from_json(&params.get_string("payload")) {
+                Some(StoredValue::Dictionary(payload)) => payload,
+                _ => return,
+            };
+            let request = DisplayMediaRequest {
+                origin: payload.get_string("origin"),
+                video: payload.get_bool("video"),
+                audio: payload.get_bool("audio"),
+            };
+            let callback = DisplayMediaCallback {
+                browser: browser.clone(),
+                id: payload.get_int("id"),
+                answered: false,
+            };
+            on_request(browser, &request, callback);
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        let script = UserScript::new(SHIM_SOURCE);
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        DisplayMediaPicker {
+            session,
+            scripts,
+            script_id,
+        }
+    }
+    /// Installs a picker that always shares `source` without asking.
+    pub fn auto_select(browser: &Browser, source: DisplayMediaSource) -> DisplayMediaPicker {
+        Self::attach(browser, move |_, _, callback| callback.select(source))
+    }
+}
+
+impl Drop for DisplayMediaPicker {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script_id);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
+
+/// Returns the switches making Chromium share the source whose title is
+/// `title` without showing a picker, e.g. `Entire screen` or a window title.
+pub(crate) fn auto_select_switches(title: &str) -> Vec<(&'static str, Option<String>)> {
+    vec![
+        ("auto-select-desktop-capture-source", Some(title.to_owned())),
+        ("enable-usermedia-screen-capturing", None),
+    ]
+}
@@ -0,0 +1,46 @@
+//! Errors and the handling of functions missing from the loaded CEF binary.
+//!
+//! CEF hands out function tables in which any entry may be null, e.g. when the
+//! binary is a different build than the one the bindings were generated for.
+//! Most wrappers assume the functions they call exist. The methods of
+//! [BrowserHost](crate::browser_host::BrowserHost) instead skip calls to
+//! missing functions and call [unsupported], which logs a warning once per
+//! function, or panics in debug builds with the `strict` feature so
+//! mismatches are noticed during development. Those methods return nothing,
+//! so the [CefError] is only seen in the log.
+
+use parking_lot::Mutex;
+use std::{collections::HashSet, fmt};
+
+/// Error returned when CEF can't perform an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CefError {
+    /// The loaded CEF binary doesn't provide the named function.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for CefError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CefError::Unsupported(function) => write!(f, "{} is not supported by the loaded CEF binary", function),
+        }
+    }
+}
+
+impl std::error::Error for CefError {}
+
+lazy_static::lazy_static! {
+    static ref REPORTED: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+}
+
+/// Reports that `function` is missing and returns the matching error. Only
+/// used by [BrowserHost](crate::browser_host::BrowserHost) so far.
+pub(crate) fn unsupported(function: &'static str) -> CefError {
+    if cfg!(all(feature = "strict", debug_assertions)) {
+        panic!("{} is not supported by the loaded CEF binary", function);
+    }
+    if REPORTED.lock().insert(function) {
+        log::warn!("{} is not supported by the loaded CEF binary, ignoring the call", function);
+    }
+    CefError::Unsupported(function)
+}
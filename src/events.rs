@@ -466,3 +466,14 @@ impl WindowsKeyCode {
     pub const Kanji: Self = Self::Hanja;
     pub const OemFjJish: Self = Self::OemNecEqual;
 }
+
+impl WindowsKeyCode {
+    /// Wraps the virtual key code `key_code`.
+    pub const fn new(key_code: i32) -> WindowsKeyCode {
+        WindowsKeyCode(key_code)
+    }
+    /// Returns the virtual key code.
+    pub const fn get(self) -> i32 {
+        self.0
+    }
+}
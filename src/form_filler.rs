@@ -0,0 +1,358 @@
+//! Filling in and submitting forms from the application.
+//!
+//! A [FormFiller] lists the forms of the main frame with their fields, labels
+//! and `autocomplete` attributes, and fills and submits them the way a user
+//! would: values are set through the native setters and followed by `input`
+//! and `change` events, so frameworks like React pick them up. Forms often
+//! appear after the page has loaded, so requests that find nothing to work on
+//! are retried a few times before they give up:
+//!
+//! ```ignore
+//! let filler = FormFiller::new(&browser);
+//! let forms = block_on(filler.forms())?;
+//! let login = forms.iter().find(|form| form.fields.iter().any(|field| field.field_type == "password")).unwrap();
+//! block_on(filler.fill(login.index, &[
+//!     (FieldSelector::Autocomplete("username".to_owned()), "jane"),
+//!     (FieldSelector::Autocomplete("current-password".to_owned()), password),
+//! ]))?;
+//! block_on(filler.submit(login.index))?;
+//! ```
+
+use crate::{
+    browser::Browser,
+    devtools::{DevToolsError, DevToolsSession},
+    timer,
+    values::{DictionaryValue, ListValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+/// Performs one operation on the forms of the document. Returns
+/// `{ retry, value }`, where `retry` is set if the operation should be
+/// attempted again because the document isn't ready or something is missing.
+const FORMS_FUNCTION: &str = "(function(op, formIndex, values) {\n\
+  if (document.readyState === 'loading') return { retry: true, value: null };\n\
+  var forms = Array.prototype.slice.call(document.forms);\n\
+  function fieldsOf(form) {\n\
+    return Array.prototype.filter.call(form.elements, function(e) {\n\
+      return /^(INPUT|SELECT|TEXTAREA)$/.test(e.tagName) && ['submit', 'button', 'reset', 'image', 'hidden'].indexOf(e.type) < 0;\n\
+    });\n\
+  }\n\
+  function labelOf(e) {\n\
+    var label = e.labels && e.labels[0];\n\
+    return ((label && label.textContent) || e.getAttribute('aria-label') || e.placeholder || '').trim();\n\
+  }\n\
+  function describe(e) {\n\
+    var checkable = e.type === 'checkbox' || e.type === 'radio';\n\
+    return { name: e.name || '', id: e.id || '', label: labelOf(e), autocomplete: e.getAttribute('autocomplete') || '',\n\
+      type: e.type, value: checkable ? (e.checked ? e.value : '') : e.value, required: !!e.required,\n\
+      options: e.tagName === 'SELECT' ? Array.prototype.map.call(e.options, function(o) { return o.value; }) : [] };\n\
+  }\n\
+  function setValue(e, value) {\n\
+    if (e.type === 'radio' && e.value !== value) return;\n\
+    e.focus();\n\
+    if (e.type === 'checkbox') e.checked = ['', '0', 'false', 'off'].indexOf(value) < 0;\n\
+    else if (e.type === 'radio') e.checked = true;\n\
+    else if (e.tagName === 'SELECT') {\n\
+      var option = Array.prototype.find.call(e.options, function(o) { return o.value === value || o.text.trim() === value; });\n\
+      if (option) e.value = option.value;\n\
+    } else Object.getOwnPropertyDescriptor(Object.getPrototypeOf(e), 'value').set.call(e, value);\n\
+    e.dispatchEvent(new Event('input', { bubbles: true }));\n\
+    e.dispatchEvent(new Event('change', { bubbles: true }));\n\
+    e.blur();\n\
+  }\n\
+  if (op === 'query') return { retry: forms.length === 0, value: forms.map(function(form, i) {\n\
+    return { index: i, id: form.id || '', name: form.getAttribute('name') || '', action: form.action, method: form.method, fields: fieldsOf(form).map(describe) };\n\
+  }) };\n\
+  var form = forms[formIndex];\n\
+  if (!form) return { retry: true, value: null };\n\
+  if (op === 'submit') {\n\
+    if (form.requestSubmit) form.requestSubmit(); else form.submit();\n\
+    return { retry: false, value: true };\n\
+  }\n\
+  var fields = fieldsOf(form), filled = 0, missing = [];\n\
+  values.forEach(function(entry, i) {\n\
+    var matches = fields.filter(function(e) {\n\
+      switch (entry.by) {\n\
+        case 'name': return e.name === entry.key;\n\
+        case 'id': return e.id === entry.key;\n\
+        case 'label': return labelOf(e).toLowerCase() === entry.key.toLowerCase();\n\
+        default: return (e.getAttribute('autocomplete') || '').split(/\\s+/).indexOf(entry.key) >= 0;\n\
+      }\n\
+    });\n\
+    if (!matches.length) { missing.push(i); return; }\n\
+    matches.forEach(function(e) { setValue(e, entry.value); });\n\
+    filled++;\n\
+  });\n\
+  return { retry: missing.length > 0, value: { filled: filled, missing: missing } };\n\
+})";
+
+/// A field of a [FormInfo].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormField {
+    pub name: String,
+    pub id: String,
+    /// The text of the field's `<label>`, or its `aria-label` or placeholder.
+    pub label: String,
+    /// The `autocomplete` attribute, e.g. `email` or `current-password`.
+    pub autocomplete: String,
+    /// The `type` of the field, e.g. `text`, `password` or `select-one`.
+    pub field_type: String,
+    /// The current value. Empty for unchecked checkboxes and radio buttons.
+    pub value: String,
+    pub required: bool,
+    /// The option values of `<select>` elements.
+    pub options: Vec<String>,
+}
+
+/// A form of the main frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormInfo {
+    /// The position of the form in `document.forms`, which identifies it in
+    /// [FormFiller::fill] and [FormFiller::submit].
+    pub index: usize,
+    pub id: String,
+    pub name: String,
+    pub action: String,
+    pub method: String,
+    /// The fields users can fill in, without buttons and hidden inputs.
+    pub fields: Vec<FormField>,
+}
+
+/// Selects the fields of a form a value is filled into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldSelector {
+    Name(String),
+    Id(String),
+    /// Matches the label text, ignoring case.
+    Label(String),
+    /// Matches one of the tokens of the `autocomplete` attribute, e.g.
+    /// `email`. This is the most stable way of addressing fields, since it's
+    /// what browsers' own autofill relies on.
+    Autocomplete(String),
+}
+
+impl FieldSelector {
+    fn to_dictionary(&self, value: &str) -> DictionaryValue {
+        let (by, key) = match self {
+            FieldSelector::Name(key) => ("name", key),
+            FieldSelector::Id(key) => ("id", key),
+            FieldSelector::Label(key) => ("label", key),
+            FieldSelector::Autocomplete(key) => ("autocomplete", key),
+        };
+        let entry = DictionaryValue::new();
+        entry.insert_string("by", by);
+        entry.insert_string("key", key);
+        entry.insert_string("value", value);
+        entry
+    }
+}
+
+/// The outcome of [FormFiller::fill].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillReport {
+    /// The number of selectors that matched at least one field.
+    pub filled: usize,
+    /// The selectors that matched no field after all attempts.
+    pub missing: Vec<FieldSelector>,
+}
+
+pub type FormResult<T> = Result<T, DevToolsError>;
+
+struct RequestState<T> {
+    result: Option<FormResult<T>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the result of a [FormFiller] operation.
+pub struct FormRequest<T>(Arc<Mutex<RequestState<T>>>);
+
+impl<T> Future for FormRequest<T> {
+    type Output = FormResult<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<FormResult<T>> {
+        let mut state = self.0.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn unexpected() -> DevToolsError {
+    DevToolsError { code: -1, message: "unexpected result".to_owned() }
+}
+
+/// Fills in and submits the forms of the main frame of a browser. See the
+/// [module documentation](self).
+#[derive(Clone)]
+pub struct FormFiller {
+    session: DevToolsSession,
+    attempts: u32,
+    retry_delay: Duration,
+}
+
+impl FormFiller {
+    /// Creates a filler making 10 attempts, 250ms apart.
+    pub fn new(browser: &Browser) -> FormFiller {
+        FormFiller {
+            session: DevToolsSession::new(&browser.get_host()),
+            attempts: 10,
+            retry_delay: Duration::from_millis(250),
+        }
+    }
+    /// Sets how often operations are attempted before giving up.
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+    pub fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+    /// Lists the forms of the page, waiting for at least one to appear.
+    /// Resolves to an empty list if none did.
+    pub fn forms(&self) -> FormRequest<Vec<FormInfo>> {
+        self.run("query", 0, ListValue::new(), |value| {
+            let forms = match value {
+                StoredValue::List(forms) => forms,
+                _ => return Ok(Vec::new()),
+            };
+            Ok((0..forms.len()).filter_map(|i| forms.get_dictionary(i)).map(|form| {
+                let fields = form.try_get_list("fields").unwrap_or_else(ListValue::new);
+                FormInfo {
+                    index: form.get_int("index") as usize,
+                    id: form.get_string("id"),
+                    name: form.get_string("name"),
+                    action: form.get_string("action"),
+                    method: form.get_string("method"),
+                    fields: (0..fields.len()).filter_map(|i| fields.get_dictionary(i)).map(|field| {
+                        let options = field.try_get_list("options").unwrap_or_else(ListValue::new);
+                        FormField {
+                            name: field.get_string("name"),
+                            id: field.get_string("id"),
+                            label: field.get_string("label"),
+                            autocomplete: field.get_string("autocomplete"),
+                            field_type: field.get_string("type"),
+                            value: field.get_string("value"),
+                            required: field.get_bool("required"),
+                            options: (0..options.len()).filter_map(|i| options.get_string(i)).collect(),
+                        }
+                    }).collect(),
+                }
+            }).collect())
+        })
+    }
+    /// Fills `values` into the form at `form_index`. Selectors matching no
+    /// field are retried, in case the fields are added later, and reported in
+    /// [FillReport::missing] if they never appear. Fails if the form doesn't
+    /// exist.
+    pub fn fill(&self, form_index: usize, values: &[(FieldSelector, &str)]) -> FormRequest<FillReport> {
+        let entries = ListValue::new();
+        for (i, (selector, value)) in values.iter().enumerate() {
+            entries.set_dictionary(i, selector.to_dictionary(value));
+        }
+        let selectors = values.iter().map(|(selector, _)| selector.clone()).collect::<Vec<_>>();
+        self.run("fill", form_index, entries, move |value| {
+            let report = match value {
+                StoredValue::Dictionary(report) => report,
+                _ => return Err(DevToolsError { code: -1, message: "form not found".to_owned() }),
+            };
+            let missing = report.try_get_list("missing").unwrap_or_else(ListValue::new);
+            Ok(FillReport {
+                filled: report.get_int("filled") as usize,
+                missing: (0..missing.len())
+                    .filter_map(|i| missing.get_int(i))
+                    .filter_map(|i| selectors.get(i as usize).cloned())
+                    .collect(),
+            })
+        })
+    }
+    /// Submits the form at `form_index` as if its submit button was clicked,
+    /// so validation and submit handlers run.
+    pub fn submit(&self, form_index: usize) -> FormRequest<()> {
+        self.run("submit", form_index, ListValue::new(), |value| match value {
+            StoredValue::Bool(true) => Ok(()),
+            _ => Err(DevToolsError { code: -1, message: "form not found".to_owned() }),
+        })
+    }
+
+    fn run<T: 'static + Send>(
+        &self,
+        op: &str,
+        form_index: usize,
+        values: ListValue,
+        parse: impl 'static + Send + FnOnce(StoredValue) -> FormResult<T>,
+    ) -> FormRequest<T> {
+        let state = Arc::new(Mutex::new(RequestState { result: None, waker: None }));
+        let expression = format!(
+            "{}({}, {}, {})",
+            FORMS_FUNCTION,
+            StoredValue::String(op.to_owned()).to_json().unwrap_or_default(),
+            form_index,
+            StoredValue::List(values).to_json().unwrap_or_else(|| "[]".to_owned())
+        );
+        let result_state = state.clone();
+        attempt(self.session.clone(), expression, self.attempts, self.retry_delay, move |result| {
+            let result = result.and_then(parse);
+            let waker = {
+                let mut state = result_state.lock();
+                state.result = Some(result);
+                state.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+        FormRequest(state)
+    }
+}
+
+/// Evaluates `expression`, retrying while it asks for it or fails, e.g.
+/// because a navigation destroyed its context, and passes the last `value`
+/// to `done`.
+fn attempt(
+    session: DevToolsSession,
+    expression: String,
+    attempts_left: u32,
+    delay: Duration,
+    done: impl 'static + Send + FnOnce(FormResult<StoredValue>),
+) {
+    let params = DictionaryValue::new();
+    params.insert_string("expression", &expression);
+    params.insert_bool("awaitPromise", true);
+    params.insert_bool("returnByValue", true);
+    let next_session = session.clone();
+    session.execute("Runtime.evaluate", Some(params), move |result| {
+        let result = result.and_then(|result| {
+            let result = match result {
+                StoredValue::Dictionary(result) => result,
+                _ => return Err(unexpected()),
+            };
+            if let Some(exception) = result.try_get_dictionary("exceptionDetails") {
+                return Err(DevToolsError { code: -1, message: exception.get_string("text") });
+            }
+            result.try_get_dictionary("result")
+                .and_then(|result| result.try_get_dictionary("value"))
+                .ok_or_else(unexpected)
+        });
+        let retry = match &result {
+            Ok(outcome) => outcome.get_bool("retry"),
+            Err(_) => true,
+        };
+        if retry && attempts_left > 1 {
+            timer::once(delay, move || attempt(next_session, expression, attempts_left - 1, delay, done));
+        } else {
+            done(result.map(|outcome| outcome.get("value")));
+        }
+    });
+}
@@ -0,0 +1,240 @@
+//! Keeping form data out of the browser's storage.
+//!
+//! A [FormsPolicy] turns off autofill and the password manager through the
+//! preferences of a request context, and can mark every form field with
+//! `autocomplete="off"` so nothing typed into a page is offered again later.
+//! [FormsPolicy::watch] additionally reports focus, input and submit events
+//! of form fields, without their values, which lets kiosks react to users
+//! entering credentials:
+//!
+//! ```ignore
+//! let policy = FormsPolicy::new();
+//! policy.apply_to_request_context(&browser.get_host().get_request_context())?;
+//! let watcher = policy.watch(&browser.get_host(), |browser, interaction| {
+//!     if interaction.field_type == "password" { /* ... */ }
+//! });
+//! ```
+
+use crate::{
+    browser::Browser,
+    browser_host::BrowserHost,
+    devtools::DevToolsSession,
+    request_context::RequestContext,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+};
+
+/// The name of the DevTools binding form events are reported through.
+const BINDING_NAME: &str = "__cefFormEvent";
+
+/// Preferences disabling autofill of addresses and credit cards.
+const AUTOFILL_PREFERENCES: &[&str] = &["autofill.enabled", "autofill.profile_enabled", "autofill.credit_card_enabled"];
+
+/// Preferences disabling saving and filling in passwords.
+const PASSWORD_PREFERENCES: &[&str] = &["credentials_enable_service", "credentials_enable_autosignin", "profile.password_manager_enabled"];
+
+const WATCH_SOURCE: &str = "\
+var notify = window.__cefFormEvent;\n\
+if (typeof notify !== 'function') return;\n\
+try { delete window.__cefFormEvent; } catch (e) {}\n\
+function report(kind, target) {\n\
+  var form = target.form || (target.tagName === 'FORM' ? target : null);\n\
+  notify(JSON.stringify({\n\
+    kind: kind,\n\
+    url: location.href,\n\
+    name: target.tagName === 'FORM' ? '' : (target.name || target.id || ''),\n\
+    type: target.tagName === 'FORM' ? '' : (target.type || target.tagName.toLowerCase()),\n\
+    action: form ? String(form.action || '') : ''\n\
+  }));\n\
+}\n\
+function isField(target) {\n\
+  return target && (target.tagName === 'INPUT' || target.tagName === 'TEXTAREA' || target.tagName === 'SELECT');\n\
+}\n\
+document.addEventListener('focusin', function(e) { if (isField(e.target)) report('focus', e.target); }, true);\n\
+document.addEventListener('change', function(e) { if (isField(e.target)) report('input', e.target); }, true);\n\
+document.addEventListener('submit', function(e) { report('submit', e.target); }, true);";
+
+const AUTOCOMPLETE_OFF_SOURCE: &str = "\
+function disable(root) {\n\
+  var elements = root.querySelectorAll ? root.querySelectorAll('form, input, textarea, select') : [];\n\
+  for (var i = 0; i < elements.length; i++) {\n\
+    var element = elements[i];\n\
+    element.setAttribute('autocomplete', element.type === 'password' ? 'new-password' : 'off');\n\
+  }\n\
+}\n\
+disable(document);\n\
+new MutationObserver(function(mutations) {\n\
+  for (var i = 0; i < mutations.length; i++) {\n\
+    for (var j = 0; j < mutations[i].addedNodes.length; j++) {\n\
+      var node = mutations[i].addedNodes[j];\n\
+      if (node.nodeType === 1) { disable(node.parentNode || node); }\n\
+    }\n\
+  }\n\
+}).observe(document, { childList: true, subtree: true });";
+
+/// What happened to a form field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormInteractionKind {
+    /// A field received focus.
+    Focus,
+    /// The value of a field was changed and committed.
+    Input,
+    /// A form was submitted.
+    Submit,
+}
+
+/// A form event reported by [FormsPolicy::watch]. Field values are never
+/// reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormInteraction {
+    pub kind: FormInteractionKind,
+    /// The URL of the document containing the form.
+    pub url: String,
+    /// The name or ID of the field. Empty for [FormInteractionKind::Submit].
+    pub field_name: String,
+    /// The `type` of an input, e.g. `password`, or the tag name of other
+    /// fields. Empty for [FormInteractionKind::Submit].
+    pub field_type: String,
+    /// The URL the form submits to.
+    pub form_action: String,
+}
+
+impl FormInteraction {
+    fn from_payload(payload: &str) -> Option<FormInteraction> {
+        let event = match StoredValue::from_json(payload)? {
+            StoredValue::Dictionary(event) => event,
+            _ => return None,
+        };
+        let kind = match &event.get_string("kind")[..] {
+            "focus" => FormInteractionKind::Focus,
+            "input" => FormInteractionKind::Input,
+            "submit" => FormInteractionKind::Submit,
+            _ => return None,
+        };
+        Some(FormInteraction {
+            kind,
+            url: event.get_string("url"),
+            field_name: event.get_string("name"),
+            field_type: event.get_string("type"),
+            form_action: event.get_string("action"),
+        })
+    }
+}
+
+/// How forms are treated. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormsPolicy {
+    /// Turn off autofill of addresses and credit cards.
+    pub disable_autofill: bool,
+    /// Turn off saving and filling in passwords.
+    pub disable_password_manager: bool,
+    /// Set `autocomplete="off"` on all form fields of watched browsers.
+    pub autocomplete_off: bool,
+}
+
+impl FormsPolicy {
+    /// Creates the strictest policy, with everything disabled.
+    pub fn new() -> FormsPolicy {
+        FormsPolicy {
+            disable_autofill: true,
+            disable_password_manager: true,
+            autocomplete_off: true,
+        }
+    }
+    pub fn disable_autofill(mut self, disable_autofill: bool) -> Self {
+        self.disable_autofill = disable_autofill;
+        self
+    }
+    pub fn disable_password_manager(mut self, disable_password_manager: bool) -> Self {
+        self.disable_password_manager = disable_password_manager;
+        self
+    }
+    pub fn autocomplete_off(mut self, autocomplete_off: bool) -> Self {
+        self.autocomplete_off = autocomplete_off;
+        self
+    }
+
+    /// Sets the autofill and password manager preferences of
+    /// `request_context`. Preferences the running CEF build doesn't know are
+    /// skipped. Must be called on the browser process UI thread.
+    pub fn apply_to_request_context(&self, request_context: &RequestContext) -> Result<(), String> {
+        let mut preferences = Vec::new();
+        if self.disable_autofill {
+            preferences.extend_from_slice(AUTOFILL_PREFERENCES);
+        }
+        if self.disable_password_manager {
+            preferences.extend_from_slice(PASSWORD_PREFERENCES);
+        }
+        for name in preferences {
+            if request_context.has_preference(name) && request_context.can_set_preference(name) {
+                request_context.set_preference(name, Some(StoredValue::Bool(false)))?;
+            }
+        }
+        Ok(())
+    }
+    /// Applies `autocomplete_off` to the documents of the browser of `host`
+    /// and calls `listener` on the UI thread for every form interaction.
+    /// Watching stops when the returned value is dropped.
+    pub fn watch(
+        &self,
+        host: &BrowserHost,
+        listener: impl 'static + Send + Sync + Fn(&Browser, &FormInteraction),
+    ) -> FormWatcher {
+        let session = DevToolsSession::new(host);
+        session.add_event_listener("Runtime.bindingCalled", move |browser, params| {
+            if let StoredValue::Dictionary(params) = params {
+                if params.get_string("name") == BINDING_NAME {
+                    if let Some(interaction) = FormInteraction::from_payload(&params.get_string("payload")) {
+                        listener(browser, &interaction);
+                    }
+                }
+            }
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        let mut script_ids = Vec::new();
+        let mut sources = vec![WATCH_SOURCE];
+        if self.autocomplete_off {
+            sources.push(AUTOCOMPLETE_OFF_SOURCE);
+        }
+        for source in sources {
+            let script = UserScript::new(source);
+            script_ids.push(scripts.add(&script));
+            scripts.inject_into_loaded(&script);
+        }
+        FormWatcher {
+            session,
+            scripts,
+            script_ids,
+        }
+    }
+}
+
+impl Default for FormsPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reports form interactions of one browser, returned by [FormsPolicy::watch].
+/// Documents that are already loaded keep reporting until they navigate away.
+pub struct FormWatcher {
+    session: DevToolsSession,
+    scripts: UserScripts,
+    script_ids: Vec<UserScriptId>,
+}
+
+impl Drop for FormWatcher {
+    fn drop(&mut self) {
+        for id in self.script_ids.drain(..) {
+            self.scripts.remove(id);
+        }
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
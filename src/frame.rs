@@ -6,6 +6,7 @@ use crate::{
     url_request::{URLRequest, URLRequestClient},
     v8context::V8Context,
     process::{ProcessId, ProcessMessage},
+    task::{TaskRunner, ThreadId},
 };
 use cef_sys::{cef_frame_t, cef_string_userfree_utf16_free};
 
@@ -142,6 +143,19 @@ impl Frame {
             }
         }
     }
+    /// Types `text` into the focused editable element of this frame, replacing
+    /// its selection, as if the user entered it through an IME. Does nothing
+    /// if this frame isn't focused. Must be called in the browser process.
+    pub fn insert_text(&self, text: &str) {
+        crate::text_editing::insert_text(self, text)
+    }
+    /// Replaces the first occurrence of `old` in the focused input, text area
+    /// or content-editable element of this frame with `new`, searching from
+    /// the caret on. The replacement is typed, so it fires input events and
+    /// can be undone. Does nothing if no focused field contains `old`.
+    pub fn replace_text_in_focused_editable(&self, old: &str, new: &str) {
+        crate::text_editing::replace_text_in_focused_editable(self, old, new)
+    }
     /// Returns true if this is the main (top-level) frame.
     pub fn is_main(&self) -> bool {
         if let Some(is_main) = self.0.is_main {
@@ -150,6 +164,11 @@ impl Frame {
             false
         }
     }
+    /// Returns true if this frame shows Chromium's PDF viewer. See
+    /// [crate::pdf]. Must be called in the browser process.
+    pub fn is_pdf_viewer(&self) -> bool {
+        crate::pdf::is_pdf_viewer_frame(self)
+    }
     /// Returns true if this is the focused frame.
     pub fn is_focused(&self) -> bool {
         if let Some(is_focused) = self.0.is_focused {
@@ -274,10 +293,15 @@ impl Frame {
         message: ProcessMessage,
     ) {
         if let Some(send_process_message) = self.0.send_process_message {
-            let target_process = match crate::process_type() {
-                crate::ProcessType::Browser => ProcessId::Renderer,
-                _ => ProcessId::Browser,
+            // In single-process mode both sides live in the browser process,
+            // so tell them apart by thread.
+            let in_renderer = match crate::process_type() {
+                crate::ProcessType::Browser => {
+                    crate::is_single_process() && TaskRunner::currently_on(ThreadId::Renderer)
+                }
+                _ => true,
             };
+            let target_process = if in_renderer { ProcessId::Browser } else { ProcessId::Renderer };
             unsafe {
                 send_process_message(self.0.as_ptr(), target_process as _, message.into_raw());
             }
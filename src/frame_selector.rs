@@ -0,0 +1,117 @@
+//! Addressing frames by URL pattern instead of by handle.
+//!
+//! Frame handles go stale whenever an iframe navigates or is moved to another
+//! process, which happens constantly on iframe-heavy pages. A [FrameSelector]
+//! returned by [Browser::frames_matching](crate::browser::Browser::frames_matching)
+//! resolves the matching frames when it is used. If a frame is swapped out
+//! between resolving and executing, the script is run again in the next
+//! matching document that starts loading in the browser, provided the client
+//! has a [LoadHandler](crate::load_handler::LoadHandler).
+
+use crate::{browser::Browser, frame::Frame, url_pattern::glob_matches};
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a script waits for a replacement document after its frame went
+/// stale.
+const RETRY_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct PendingScript {
+    browser_id: i32,
+    pattern: String,
+    code: String,
+    deadline: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref PENDING: Mutex<Vec<PendingScript>> = Mutex::new(Vec::new());
+}
+
+/// The frames of a browser whose URL matches a glob, where `*` matches any
+/// sequence of characters.
+#[derive(Clone)]
+pub struct FrameSelector {
+    browser: Browser,
+    pattern: String,
+}
+
+impl FrameSelector {
+    pub(crate) fn new(browser: &Browser, pattern: &str) -> FrameSelector {
+        FrameSelector {
+            browser: browser.clone(),
+            pattern: pattern.to_owned(),
+        }
+    }
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+    /// Returns the frames matching the pattern right now.
+    pub fn frames(&self) -> Vec<Frame> {
+        self.resolve().into_iter().filter(|frame| frame.is_valid()).collect()
+    }
+    /// Executes `code` in every matching frame and returns the number of
+    /// frames it was sent to. If a frame went away before the script could be
+    /// sent, the script is run in the next matching document instead.
+    pub fn execute_js(&self, code: &str) -> usize {
+        let frames = self.resolve();
+        let mut executed = 0;
+        let mut stale = false;
+        for frame in frames {
+            if frame.is_valid() {
+                frame.execute_java_script(code, &frame.get_url(), 0);
+                executed += 1;
+            } else {
+                stale = true;
+            }
+        }
+        if stale {
+            PENDING.lock().push(PendingScript {
+                browser_id: self.browser.get_identifier(),
+                pattern: self.pattern.clone(),
+                code: code.to_owned(),
+                deadline: Instant::now() + RETRY_TIMEOUT,
+            });
+        }
+        executed
+    }
+
+    fn resolve(&self) -> Vec<Frame> {
+        self.browser.get_frame_identifiers().into_iter()
+            .filter_map(|id| self.browser.get_frame_byident(id))
+            .filter(|frame| glob_matches(&self.pattern, &frame.get_url()))
+            .collect()
+    }
+}
+
+/// Runs the scripts waiting for a document matching the URL of `frame`.
+/// Called when a frame starts loading.
+pub(crate) fn frame_load_started(browser: &Browser, frame: &Frame) {
+    let browser_id = browser.get_identifier();
+    let url = frame.get_url();
+    let now = Instant::now();
+    let ready = {
+        let mut pending = PENDING.lock();
+        if pending.is_empty() {
+            return;
+        }
+        pending.retain(|script| script.deadline > now);
+        let mut ready = Vec::new();
+        let mut index = 0;
+        while index < pending.len() {
+            if pending[index].browser_id == browser_id && glob_matches(&pending[index].pattern, &url) {
+                ready.push(pending.remove(index));
+            } else {
+                index += 1;
+            }
+        }
+        ready
+    };
+    for script in ready {
+        frame.execute_java_script(&script.code, &url, 0);
+    }
+}
+
+/// Drops the scripts waiting for documents of `browser_id`.
+pub(crate) fn forget_browser(browser_id: i32) {
+    PENDING.lock().retain(|script| script.browser_id != browser_id);
+}
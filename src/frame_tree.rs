@@ -0,0 +1,174 @@
+//! A snapshot of the frame hierarchy of a browser.
+//!
+//! Frame names aren't unique and identifiers change whenever a frame navigates
+//! to a different process, so neither is a reliable way to address an iframe.
+//! [FrameTree] records the parent of every frame and lets frames be addressed
+//! by their path of child indices from the main frame instead, which stays the
+//! same as long as the structure of the page does.
+
+use crate::{browser::Browser, frame::Frame};
+use std::collections::HashMap;
+
+/// A frame in a [FrameTree].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameNode {
+    pub identifier: i64,
+    /// The name of the frame, which may be empty and isn't necessarily unique.
+    pub name: Option<String>,
+    pub url: String,
+    pub is_main: bool,
+    pub parent: Option<i64>,
+    /// The identifiers of the child frames, in document order as far as CEF
+    /// reports it.
+    pub children: Vec<i64>,
+    /// The number of ancestors. The main frame has a depth of 0.
+    pub depth: usize,
+}
+
+/// The frames of a browser with their parent/child relations. Returned by
+/// [Browser::frame_tree]. The tree doesn't update by itself; call
+/// [FrameTree::refresh] after navigations, e.g. from
+/// [LoadHandlerCallbacks::on_load_end](crate::load_handler::LoadHandlerCallbacks::on_load_end).
+#[derive(Clone)]
+pub struct FrameTree {
+    browser: Browser,
+    nodes: HashMap<i64, FrameNode>,
+    main: Option<i64>,
+}
+
+impl FrameTree {
+    pub(crate) fn new(browser: &Browser) -> FrameTree {
+        let mut tree = FrameTree {
+            browser: browser.clone(),
+            nodes: HashMap::new(),
+            main: None,
+        };
+        tree.refresh();
+        tree
+    }
+    /// Reads the current frames of the browser again.
+    pub fn refresh(&mut self) {
+        self.nodes.clear();
+        self.main = None;
+        let frames = self.browser.get_frame_identifiers().into_iter()
+            .filter_map(|id| self.browser.get_frame_byident(id))
+            .collect::<Vec<_>>();
+        let mut order = Vec::with_capacity(frames.len());
+        for frame in &frames {
+            let identifier = match frame.get_identifier() {
+                Some(identifier) => identifier,
+                None => continue,
+            };
+            let is_main = frame.is_main();
+            if is_main {
+                self.main = Some(identifier);
+            }
+            order.push(identifier);
+            self.nodes.insert(identifier, FrameNode {
+                identifier,
+                name: frame.get_name().filter(|name| !name.is_empty()),
+                url: frame.get_url(),
+                is_main,
+                parent: frame.get_parent().and_then(|parent| parent.get_identifier()),
+                children: Vec::new(),
+                depth: 0,
+            });
+        }
+        for identifier in &order {
+            if let Some(parent) = self.nodes[identifier].parent {
+                if let Some(parent) = self.nodes.get_mut(&parent) {
+                    parent.children.push(*identifier);
+                }
+            }
+        }
+        if let Some(main) = self.main {
+            self.set_depths(main, 0);
+        }
+    }
+    /// Returns the browser the tree was built from.
+    pub fn browser(&self) -> &Browser {
+        &self.browser
+    }
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+    pub fn main_frame(&self) -> Option<&FrameNode> {
+        self.main.and_then(|main| self.nodes.get(&main))
+    }
+    pub fn get(&self, identifier: i64) -> Option<&FrameNode> {
+        self.nodes.get(&identifier)
+    }
+    pub fn parent(&self, identifier: i64) -> Option<&FrameNode> {
+        self.nodes.get(&identifier)?.parent.and_then(|parent| self.nodes.get(&parent))
+    }
+    pub fn children(&self, identifier: i64) -> Vec<&FrameNode> {
+        self.nodes.get(&identifier)
+            .map(|node| node.children.iter().filter_map(|child| self.nodes.get(child)).collect())
+            .unwrap_or_default()
+    }
+    /// Returns all frames in depth-first order, starting with the main frame.
+    pub fn iter(&self) -> impl Iterator<Item = &FrameNode> {
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        if let Some(main) = self.main {
+            self.collect_depth_first(main, &mut nodes);
+        }
+        nodes.into_iter()
+    }
+    /// Returns the frames whose name is `name`.
+    pub fn find_by_name(&self, name: &str) -> Vec<&FrameNode> {
+        self.iter().filter(|node| node.name.as_deref() == Some(name)).collect()
+    }
+    /// Returns the child indices leading from the main frame to the frame
+    /// `identifier`, e.g. `[1, 0]` for the first child of the second iframe of
+    /// the page. The main frame has an empty path.
+    pub fn path_of(&self, identifier: i64) -> Option<Vec<usize>> {
+        let mut path = Vec::new();
+        let mut node = self.nodes.get(&identifier)?;
+        while let Some(parent) = node.parent.and_then(|parent| self.nodes.get(&parent)) {
+            path.push(parent.children.iter().position(|child| *child == node.identifier)?);
+            node = parent;
+        }
+        if !node.is_main {
+            return None;
+        }
+        path.reverse();
+        Some(path)
+    }
+    /// Returns the frame at `path`, see [FrameTree::path_of].
+    pub fn find_by_path(&self, path: &[usize]) -> Option<&FrameNode> {
+        let mut node = self.main_frame()?;
+        for index in path {
+            node = self.nodes.get(node.children.get(*index)?)?;
+        }
+        Some(node)
+    }
+    /// Returns the frame object of the frame `identifier`, or `None` if it no
+    /// longer exists.
+    pub fn frame(&self, identifier: i64) -> Option<Frame> {
+        self.browser.get_frame_byident(identifier)
+    }
+
+    fn set_depths(&mut self, identifier: i64, depth: usize) {
+        let children = match self.nodes.get_mut(&identifier) {
+            Some(node) => {
+                node.depth = depth;
+                node.children.clone()
+            }
+            None => return,
+        };
+        for child in children {
+            self.set_depths(child, depth + 1);
+        }
+    }
+    fn collect_depth_first<'a>(&'a self, identifier: i64, nodes: &mut Vec<&'a FrameNode>) {
+        if let Some(node) = self.nodes.get(&identifier) {
+            nodes.push(node);
+            for child in &node.children {
+                self.collect_depth_first(*child, nodes);
+            }
+        }
+    }
+}
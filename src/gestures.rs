@@ -0,0 +1,237 @@
+//! Forwarding trackpad pinches and precise scrolling to windowless browsers.
+//!
+//! CEF 85 has no gesture events: windowless browsers only receive wheel
+//! events with integer deltas. This module fills the gap:
+//!
+//! * [PrecisionScroll] turns fractional line or pixel deltas, as trackpads
+//!   report them, into wheel events without losing the remainders, so slow
+//!   two-finger scrolling doesn't stall.
+//! * [PinchZoom] forwards pinch gestures either as Chromium's own pinch zoom,
+//!   synthesized through the DevTools protocol, or as the `ctrlKey` wheel
+//!   events pages like maps listen for, which is how other browsers report
+//!   trackpad pinches to pages.
+//!
+//! With the `winit-event-loop` feature, winit's `TouchPhase` and
+//! `MouseScrollDelta` convert into [GesturePhase] and [ScrollDelta]:
+//!
+//! ```ignore
+//! WindowEvent::MouseWheel { delta, phase, .. } => {
+//!     scroll.scroll(&host, &mouse_event, delta.into(), phase.into());
+//! }
+//! ```
+
+use crate::{
+    browser_host::BrowserHost,
+    devtools::DevToolsSession,
+    events::{EventFlags, MouseEvent},
+    values::DictionaryValue,
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The wheel delta of one line, which is what one notch of a mouse wheel
+/// scrolls. Chromium scrolls about 100 pixels per 120 units.
+pub const WHEEL_DELTA_PER_LINE: f64 = 120.0;
+/// The wheel delta of one pixel.
+pub const WHEEL_DELTA_PER_PIXEL: f64 = WHEEL_DELTA_PER_LINE / 100.0;
+
+/// The phase of a continuous gesture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GesturePhase {
+    Began,
+    Changed,
+    Ended,
+    Cancelled,
+}
+
+/// A scroll amount as reported by the platform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollDelta {
+    /// Lines, as reported by mouse wheels. Positive values scroll up and
+    /// left, like winit's.
+    Lines(f64, f64),
+    /// Pixels in view coordinates, as reported by trackpads. Positive values
+    /// scroll up and left.
+    Pixels(f64, f64),
+}
+
+impl ScrollDelta {
+    /// Returns the delta in wheel units.
+    pub fn to_wheel_delta(&self) -> (f64, f64) {
+        match *self {
+            ScrollDelta::Lines(x, y) => (x * WHEEL_DELTA_PER_LINE, y * WHEEL_DELTA_PER_LINE),
+            ScrollDelta::Pixels(x, y) => (x * WHEEL_DELTA_PER_PIXEL, y * WHEEL_DELTA_PER_PIXEL),
+        }
+    }
+}
+
+/// Sends fractional scroll deltas as wheel events, carrying the fractions
+/// over to the next event of the gesture.
+#[derive(Debug, Default)]
+pub struct PrecisionScroll {
+    remainder: (f64, f64),
+}
+
+impl PrecisionScroll {
+    pub fn new() -> PrecisionScroll {
+        Self::default()
+    }
+    /// Sends `delta` to `host` at the position of `event`. The remainder is
+    /// dropped when the gesture ends, so the next gesture starts clean.
+    pub fn scroll(&mut self, host: &BrowserHost, event: &MouseEvent, delta: ScrollDelta, phase: GesturePhase) {
+        if phase == GesturePhase::Began {
+            self.remainder = (0.0, 0.0);
+        }
+        let (x, y) = delta.to_wheel_delta();
+        let x = x + self.remainder.0;
+        let y = y + self.remainder.1;
+        let (whole_x, whole_y) = (x.trunc(), y.trunc());
+        self.remainder = (x - whole_x, y - whole_y);
+        if whole_x != 0.0 || whole_y != 0.0 {
+            host.send_mouse_wheel_event(event, whole_x as i32, whole_y as i32);
+        }
+        if phase == GesturePhase::Ended || phase == GesturePhase::Cancelled {
+            self.remainder = (0.0, 0.0);
+        }
+    }
+}
+
+/// How [PinchZoom] forwards pinches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PinchMode {
+    /// Chromium's pinch zoom, which magnifies the page without relayout, like
+    /// pinching on a touch screen.
+    PageScale,
+    /// Wheel events with `ctrlKey` set, which pages such as maps handle as
+    /// pinches. Pages that don't handle them leave them to the browser, which
+    /// ignores them in windowless mode.
+    CtrlWheel,
+}
+
+struct PinchState {
+    /// The scale factor accumulated while a synthesized pinch was in flight.
+    pending_scale: f64,
+    in_flight: bool,
+    anchor: (i32, i32),
+}
+
+/// Forwards trackpad pinch gestures to one browser. See the
+/// [module documentation](self).
+pub struct PinchZoom {
+    host: BrowserHost,
+    mode: PinchMode,
+    session: Option<DevToolsSession>,
+    state: Arc<Mutex<PinchState>>,
+    wheel: PrecisionScroll,
+}
+
+impl PinchZoom {
+    pub fn new(host: &BrowserHost, mode: PinchMode) -> PinchZoom {
+        PinchZoom {
+            host: host.clone(),
+            mode,
+            session: match mode {
+                PinchMode::PageScale => Some(DevToolsSession::new(host)),
+                PinchMode::CtrlWheel => None,
+            },
+            state: Arc::new(Mutex::new(PinchState {
+                pending_scale: 1.0,
+                in_flight: false,
+                anchor: (0, 0),
+            })),
+            wheel: PrecisionScroll::new(),
+        }
+    }
+    /// Forwards a pinch update. `delta` is the relative change of scale, as
+    /// reported by macOS and winit: 0.1 means 10% larger. `event` holds the
+    /// position of the pinch center in view coordinates.
+    pub fn pinch(&mut self, event: &MouseEvent, delta: f64, phase: GesturePhase) {
+        match self.mode {
+            PinchMode::CtrlWheel => {
+                let event = MouseEvent {
+                    x: event.x,
+                    y: event.y,
+                    modifiers: event.modifiers | EventFlags::CONTROL_DOWN,
+                };
+                // Pages derive the scale from the wheel delta, zooming in for
+                // negative values, like Chromium reports pinches on macOS.
+                let pixels = delta * 100.0;
+                self.wheel.scroll(&self.host, &event, ScrollDelta::Pixels(0.0, pixels), phase);
+            }
+            PinchMode::PageScale => {
+                if phase == GesturePhase::Cancelled {
+                    self.state.lock().pending_scale = 1.0;
+                    return;
+                }
+                {
+                    let mut state = self.state.lock();
+                    state.pending_scale *= 1.0 + delta;
+                    state.anchor = (event.x, event.y);
+                }
+                if let Some(session) = &self.session {
+                    flush_pinch(session.clone(), self.state.clone());
+                }
+            }
+        }
+    }
+    /// Resets Chromium's pinch zoom to 1.0, e.g. on double-tap.
+    pub fn reset(&self) {
+        self.state.lock().pending_scale = 1.0;
+        if let Some(session) = &self.session {
+            let params = DictionaryValue::new();
+            params.insert_double("pageScaleFactor", 1.0);
+            session.send("Emulation.setPageScaleFactor", Some(params));
+        }
+    }
+}
+
+/// Synthesizes the accumulated scale as one pinch. Pinches are synthesized one
+/// at a time; updates arriving meanwhile are merged into the next one.
+fn flush_pinch(session: DevToolsSession, state: Arc<Mutex<PinchState>>) {
+    let (scale, anchor) = {
+        let mut state = state.lock();
+        if state.in_flight || (state.pending_scale - 1.0).abs() < 0.001 {
+            return;
+        }
+        state.in_flight = true;
+        (std::mem::replace(&mut state.pending_scale, 1.0), state.anchor)
+    };
+    let params = DictionaryValue::new();
+    params.insert_double("x", anchor.0 as f64);
+    params.insert_double("y", anchor.1 as f64);
+    params.insert_double("scaleFactor", scale);
+    // Fast enough to keep up with the fingers.
+    params.insert_int("relativeSpeed", 2000);
+    params.insert_string("gestureSourceType", "touch");
+    let next_session = session.clone();
+    session.execute("Input.synthesizePinchGesture", Some(params), move |_| {
+        state.lock().in_flight = false;
+        flush_pinch(next_session, state);
+    });
+}
+
+#[cfg(feature = "winit-event-loop")]
+mod winit_conversions {
+    use super::*;
+    use winit_event_loop::event::{MouseScrollDelta, TouchPhase};
+
+    impl From<TouchPhase> for GesturePhase {
+        fn from(phase: TouchPhase) -> GesturePhase {
+            match phase {
+                TouchPhase::Started => GesturePhase::Began,
+                TouchPhase::Moved => GesturePhase::Changed,
+                TouchPhase::Ended => GesturePhase::Ended,
+                TouchPhase::Cancelled => GesturePhase::Cancelled,
+            }
+        }
+    }
+
+    impl From<MouseScrollDelta> for ScrollDelta {
+        fn from(delta: MouseScrollDelta) -> ScrollDelta {
+            match delta {
+                MouseScrollDelta::LineDelta(x, y) => ScrollDelta::Lines(x as f64, y as f64),
+                MouseScrollDelta::PixelDelta(position) => ScrollDelta::Pixels(position.x, position.y),
+            }
+        }
+    }
+}
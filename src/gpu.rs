@@ -0,0 +1,188 @@
+//! Presets for Chromium's GPU command-line switches.
+//!
+//! Apply a preset to the browser process with [Settings::gpu_options], or to
+//! child processes from
+//! [BrowserProcessHandlerCallbacks::on_before_child_process_launch] with
+//! [GpuOptions::apply]. [GpuMonitor] additionally detects GPU process crash
+//! loops and switches relaunched GPU processes to a fallback preset.
+//!
+//! Switches like `disable-gpu-compositing` also affect the browser process,
+//! which can't change them once it runs. [GpuMonitor::state_file] remembers
+//! the fallback across runs so the next start applies it to the browser
+//! process as well:
+//!
+//! ```ignore
+//! let monitor = GpuMonitor::new(GpuOptions::Auto, GpuOptions::DisableGpu, 3)
+//!     .state_file(profile_dir.join("gpu-fallback"));
+//! // in AppCallbacks::on_before_command_line_processing:
+//! monitor.on_before_command_line_processing(process_type, &command_line);
+//! // in BrowserProcessHandlerCallbacks::on_before_child_process_launch:
+//! monitor.on_before_child_process_launch(&command_line);
+//! // later, e.g. when a page reports rendering problems:
+//! if monitor.is_restart_required() {
+//!     offer_restart();
+//! }
+//! ```
+
+use crate::command_line::CommandLine;
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+/// A coherent set of GPU-related switches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuOptions {
+    /// Leave GPU selection to Chromium.
+    Auto,
+    /// Use the GPU even if the driver is on Chromium's blocklist.
+    ForceGpu,
+    /// Disable hardware acceleration and GPU compositing entirely.
+    DisableGpu,
+    /// Use ANGLE on top of Direct3D 11. Windows only.
+    AngleD3D11,
+    /// Render with the SwiftShader software GL implementation.
+    EglSwiftShader,
+}
+
+impl GpuOptions {
+    /// Returns the switches of this preset as `(name, value)` pairs.
+    pub fn switches(self) -> &'static [(&'static str, Option<&'static str>)] {
+        match self {
+            GpuOptions::Auto => &[],
+            GpuOptions::ForceGpu => &[
+                ("ignore-gpu-blocklist", None),
+                // Name used by older Chromium versions.
+                ("ignore-gpu-blacklist", None),
+                ("enable-gpu-rasterization", None),
+            ],
+            GpuOptions::DisableGpu => &[
+                ("disable-gpu", None),
+                ("disable-gpu-compositing", None),
+            ],
+            GpuOptions::AngleD3D11 => &[
+                ("use-gl", Some("angle")),
+                ("use-angle", Some("d3d11")),
+            ],
+            GpuOptions::EglSwiftShader => &[
+                ("use-gl", Some("swiftshader")),
+            ],
+        }
+    }
+    /// Appends the switches of this preset to `command_line`.
+    pub fn apply(self, command_line: &CommandLine) {
+        for (name, value) in self.switches() {
+            match value {
+                Some(value) => command_line.append_switch_with_value(name, value),
+                None => command_line.append_switch(name),
+            }
+        }
+    }
+}
+
+impl Default for GpuOptions {
+    fn default() -> Self {
+        GpuOptions::Auto
+    }
+}
+
+/// Tracks GPU process launches to detect crashes. Chromium relaunches the GPU
+/// process whenever it dies, so every launch after the first one is counted as
+/// a crash. Once `max_crashes` is reached, relaunched GPU processes get the
+/// `fallback` preset instead of `preferred`.
+///
+/// The browser process keeps the preset it was started with until it is
+/// restarted; see [GpuMonitor::state_file].
+#[derive(Debug)]
+pub struct GpuMonitor {
+    preferred: GpuOptions,
+    fallback: GpuOptions,
+    max_crashes: u32,
+    launches: AtomicU32,
+    state_file: Option<PathBuf>,
+    started_with_fallback: AtomicBool,
+}
+
+impl GpuMonitor {
+    pub fn new(preferred: GpuOptions, fallback: GpuOptions, max_crashes: u32) -> GpuMonitor {
+        GpuMonitor {
+            preferred,
+            fallback,
+            max_crashes,
+            launches: AtomicU32::new(0),
+            state_file: None,
+            started_with_fallback: AtomicBool::new(false),
+        }
+    }
+    /// Creates the file at `path` once the fallback is used, and starts the
+    /// browser process with the fallback while it exists. Remove it with
+    /// [GpuMonitor::reset], e.g. after a driver update.
+    pub fn state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.state_file = Some(path.into());
+        self
+    }
+    /// Call from [AppCallbacks::on_before_command_line_processing](crate::app::AppCallbacks::on_before_command_line_processing).
+    /// Applies the fallback to the browser process if the state file exists
+    /// and the preferred preset otherwise. Other processes are left untouched.
+    pub fn on_before_command_line_processing(&self, process_type: Option<&str>, command_line: &CommandLine) {
+        if process_type.is_some() {
+            return;
+        }
+        let fallback = self.state_file.as_ref().map_or(false, |path| path.exists());
+        self.started_with_fallback.store(fallback, Ordering::SeqCst);
+        if fallback {
+            log::warn!("GPU process crashed in a previous run, starting with {:?}", self.fallback);
+            self.fallback.apply(command_line);
+        } else {
+            self.preferred.apply(command_line);
+        }
+    }
+    /// Call from [BrowserProcessHandlerCallbacks::on_before_child_process_launch].
+    /// Non-GPU processes are left untouched.
+    pub fn on_before_child_process_launch(&self, command_line: &CommandLine) {
+        if command_line.get_switch_value("type").as_ref().map(|s| &**s) != Some("gpu-process") {
+            return;
+        }
+        let launches = self.launches.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.started_with_fallback.load(Ordering::SeqCst) {
+            self.fallback.apply(command_line);
+        } else if launches > self.max_crashes {
+            log::warn!("GPU process crashed {} times, falling back to {:?}", launches - 1, self.fallback);
+            if launches == self.max_crashes + 1 {
+                if let Some(path) = &self.state_file {
+                    if let Err(err) = std::fs::write(path, b"") {
+                        log::error!("failed to write GPU state file {}: {}", path.display(), err);
+                    }
+                }
+            }
+            self.fallback.apply(command_line);
+        } else {
+            self.preferred.apply(command_line);
+        }
+    }
+    /// Returns the number of times the GPU process has crashed.
+    pub fn crash_count(&self) -> u32 {
+        self.launches.load(Ordering::SeqCst).saturating_sub(1)
+    }
+    /// Returns true if GPU processes are being launched with the fallback preset.
+    pub fn is_using_fallback(&self) -> bool {
+        self.started_with_fallback.load(Ordering::SeqCst) || self.launches.load(Ordering::SeqCst) > self.max_crashes
+    }
+    /// Returns true if GPU processes fell back while the browser process still
+    /// runs with the preferred preset. Only a restart applies the fallback to
+    /// it, which requires a [GpuMonitor::state_file].
+    pub fn is_restart_required(&self) -> bool {
+        self.is_using_fallback() && !self.started_with_fallback.load(Ordering::SeqCst)
+    }
+    /// Removes the state file, so the next start uses the preferred preset
+    /// again.
+    pub fn reset(&self) {
+        if let Some(path) = &self.state_file {
+            if let Err(err) = std::fs::remove_file(path) {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    log::error!("failed to remove GPU state file {}: {}", path.display(), err);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Adding and replacing HTTP headers by URL.
+//!
+//! A [HeaderInjector] holds rules that match URLs with globs, where `*`
+//! matches any sequence of characters. Request headers, e.g. auth tokens for
+//! the app's own backend, are applied by returning the injector from
+//! [RequestHandlerCallbacks::get_resource_request_handler](crate::client::request_handler::RequestHandlerCallbacks::get_resource_request_handler):
+//!
+//! ```ignore
+//! let injector = HeaderInjector::new()
+//!     .set_request_header("https://api.example.com/*", "Authorization", &format!("Bearer {}", token))
+//!     .set_response_header("app://ui/*", "Content-Security-Policy", "default-src 'self'");
+//! // in get_resource_request_handler:
+//! Some(ResourceRequestHandler::new(injector.clone()))
+//! ```
+//!
+//! CEF doesn't allow modifying responses, so response headers are injected
+//! through the DevTools `Fetch` domain by [HeaderInjector::attach]. Matching
+//! responses are paused, their body is read and they are fulfilled again with
+//! the modified headers, which buffers each matching response completely.
+
+use crate::{
+    browser::Browser,
+    browser_host::BrowserHost,
+    devtools::DevToolsSession,
+    frame::Frame,
+    load_html::base64_encode,
+    request::Request,
+    resource_request_handler::ResourceRequestHandlerCallbacks,
+    url_pattern::glob_matches,
+    url_request::RequestCallback,
+    values::{DictionaryValue, ListValue, StoredValue},
+    ReturnValue,
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// How a rule treats headers of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderAction {
+    /// Replace all existing values.
+    Set,
+    /// Add a value, keeping existing ones.
+    Append,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderRule {
+    pub pattern: String,
+    pub name: String,
+    pub value: String,
+    pub action: HeaderAction,
+}
+
+impl HeaderRule {
+    fn matches(&self, url: &str) -> bool {
+        glob_matches(&self.pattern, url)
+    }
+}
+
+/// Header rules for requests and responses. See the
+/// [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct HeaderInjector {
+    pub request_rules: Vec<HeaderRule>,
+    pub response_rules: Vec<HeaderRule>,
+}
+
+impl HeaderInjector {
+    pub fn new() -> HeaderInjector {
+        HeaderInjector::default()
+    }
+    pub fn set_request_header(self, pattern: &str, name: &str, value: &str) -> Self {
+        self.request_rule(pattern, name, value, HeaderAction::Set)
+    }
+    pub fn append_request_header(self, pattern: &str, name: &str, value: &str) -> Self {
+        self.request_rule(pattern, name, value, HeaderAction::Append)
+    }
+    pub fn set_response_header(self, pattern: &str, name: &str, value: &str) -> Self {
+        self.response_rule(pattern, name, value, HeaderAction::Set)
+    }
+    pub fn append_response_header(self, pattern: &str, name: &str, value: &str) -> Self {
+        self.response_rule(pattern, name, value, HeaderAction::Append)
+    }
+
+    /// Applies the request rules matching the URL of `request`.
+    pub fn apply_to_request(&self, request: &Request) {
+        let url = request.get_url();
+        for rule in self.request_rules.iter().filter(|rule| rule.matches(&url)) {
+            request.set_header_by_name(&rule.name, &rule.value, rule.action == HeaderAction::Set);
+        }
+    }
+    /// Applies the response rules matching `url` to `headers`.
+    pub fn apply_to_response_headers(&self, url: &str, headers: &mut Vec<(String, String)>) {
+        for rule in self.response_rules.iter().filter(|rule| rule.matches(url)) {
+            if rule.action == HeaderAction::Set {
+                headers.retain(|(name, _)| !name.eq_ignore_ascii_case(&rule.name));
+            }
+            headers.push((rule.name.clone(), rule.value.clone()));
+        }
+    }
+    /// Starts injecting response headers into the responses received by the
+    /// browser of `host`. Injection stops when the returned value is dropped.
+    /// Later changes to this injector don't affect the attached copy.
+    pub fn attach(&self, host: &BrowserHost) -> ResponseHeaderInjection {
+        ResponseHeaderInjection::new(host, self.clone())
+    }
+
+    fn request_rule(mut self, pattern: &str, name: &str, value: &str, action: HeaderAction) -> Self {
+        self.request_rules.push(HeaderRule {
+            pattern: pattern.to_owned(),
+            name: name.to_owned(),
+            value: value.to_owned(),
+            action,
+        });
+        self
+    }
+    fn response_rule(mut self, pattern: &str, name: &str, value: &str, action: HeaderAction) -> Self {
+        self.response_rules.push(HeaderRule {
+            pattern: pattern.to_owned(),
+            name: name.to_owned(),
+            value: value.to_owned(),
+            action,
+        });
+        self
+    }
+}
+
+impl ResourceRequestHandlerCallbacks for HeaderInjector {
+    fn on_before_resource_load(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        callback: RequestCallback,
+    ) -> ReturnValue {
+        self.apply_to_request(&request);
+        ReturnValue::Continue
+    }
+}
+
+/// Response header injection for one browser, returned by
+/// [HeaderInjector::attach].
+pub struct ResponseHeaderInjection {
+    session: DevToolsSession,
+    /// The session used by the event listener. Cleared on drop, because the
+    /// listener is owned by the session itself.
+    listener_session: Arc<Mutex<Option<DevToolsSession>>>,
+}
+
+impl ResponseHeaderInjection {
+    fn new(host: &BrowserHost, injector: HeaderInjector) -> ResponseHeaderInjection {
+        let session = DevToolsSession::new(host);
+        let listener_session = Arc::new(Mutex::new(Some(session.clone())));
+        let patterns = ListValue::new();
+        for (index, rule) in injector.response_rules.iter().enumerate() {
+            let pattern = DictionaryValue::new();
+            pattern.insert_string("urlPattern", &rule.pattern);
+            pattern.insert_string("requestStage", "Response");
+            patterns.set_dictionary(index, pattern);
+        }
+        let injector = Arc::new(injector);
+        let slot = listener_session.clone();
+        session.add_event_listener("Fetch.requestPaused", move |_, params| {
+            let session = slot.lock().clone();
+            if let (Some(session), StoredValue::Dictionary(params)) = (session, params) {
+                Self::request_paused(&session, &injector, params);
+            }
+        });
+        if patterns.len() > 0 {
+            let params = DictionaryValue::new();
+            params.insert_list("patterns", patterns);
+            session.send("Fetch.enable", Some(params));
+        }
+        ResponseHeaderInjection {
+            session,
+            listener_session,
+        }
+    }
+
+    fn request_paused(session: &DevToolsSession, injector: &HeaderInjector, params: &DictionaryValue) {
+        let request_id = params.get_string("requestId");
+        let status = params.get_int("responseStatusCode");
+        let url = params.try_get_dictionary("request").map(|request| request.get_string("url")).unwrap_or_default();
+        // Failed requests and redirects have nothing worth rewriting.
+        if status == 0 || (300..400).contains(&status) || params.contains_key("responseErrorReason") {
+            return continue_request(session, &request_id);
+        }
+        let mut headers = Vec::new();
+        if let Some(list) = params.try_get_list("responseHeaders") {
+            for index in 0..list.len() {
+                if let Some(header) = list.get_dictionary(index) {
+                    headers.push((header.get_string("name"), header.get_string("value")));
+                }
+            }
+        }
+        injector.apply_to_response_headers(&url, &mut headers);
+
+        let body_params = DictionaryValue::new();
+        body_params.insert_string("requestId", &request_id);
+        let fulfill_session = session.clone();
+        session.execute("Fetch.getResponseBody", Some(body_params), move |result| {
+            let result = match result {
+                Ok(StoredValue::Dictionary(result)) => result,
+                _ => return continue_request(&fulfill_session, &request_id),
+            };
+            let body = if result.get_bool("base64Encoded") {
+                result.get_string("body")
+            } else {
+                let mut body = String::new();
+                base64_encode(result.get_string("body").as_bytes(), &mut body);
+                body
+            };
+            let header_list = ListValue::new();
+            for (index, (name, value)) in headers.iter().enumerate() {
+                let header = DictionaryValue::new();
+                header.insert_string("name", name);
+                header.insert_string("value", value);
+                header_list.set_dictionary(index, header);
+            }
+            let params = DictionaryValue::new();
+            params.insert_string("requestId", &request_id);
+            params.insert_int("responseCode", status);
+            params.insert_list("responseHeaders", header_list);
+            params.insert_string("body", &body);
+            fulfill_session.send("Fetch.fulfillRequest", Some(params));
+        });
+    }
+}
+
+impl Drop for ResponseHeaderInjection {
+    fn drop(&mut self) {
+        self.session.send("Fetch.disable", None);
+        self.listener_session.lock().take();
+    }
+}
+
+fn continue_request(session: &DevToolsSession, request_id: &str) {
+    let params = DictionaryValue::new();
+    params.insert_string("requestId", request_id);
+    session.send("Fetch.continueRequest", Some(params));
+}
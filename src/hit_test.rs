@@ -0,0 +1,270 @@
+//! Finding out what is under a point of a windowless browser, see
+//! [Browser::hit_test].
+//!
+//! Frameless windows that draw their title bar in HTML have to decide on
+//! every mouse press whether to start dragging the window or to forward the
+//! click to the page. Pages mark their title bars with
+//! `-webkit-app-region: drag`, which CEF reports to
+//! [DragHandlerCallbacks::on_draggable_regions_changed](crate::client::drag_handler::DragHandlerCallbacks::on_draggable_regions_changed).
+//! The regions are cached here, so [draggable_at] answers synchronously, while
+//! [Browser::hit_test] also asks the renderer through the DevTools protocol
+//! whether the point is over a link, an editable element or a scrollbar:
+//!
+//! ```ignore
+//! let result = block_on(browser.hit_test(Point { x, y }))?;
+//! if result.should_drag_window() {
+//!     window.drag_window();
+//! } else {
+//!     host.send_mouse_click_event(&event, MouseButtonType::Left, false, 1);
+//! }
+//! ```
+
+use crate::{
+    browser::Browser,
+    client::drag_handler::DraggableRegion,
+    devtools::{DevToolsError, DevToolsSession},
+    values::{DictionaryValue, ListValue, Point, Rect, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+const DESCRIBE_FUNCTION: &str = "function(x, y) {\n\
+  var element = this.nodeType === Node.ELEMENT_NODE ? this : this.parentElement;\n\
+  var result = { tagName: element ? element.tagName.toLowerCase() : '', link: null, editable: false, scrollbar: false, cursor: '' };\n\
+  if (!element) return result;\n\
+  var link = element.closest('a[href], area[href]');\n\
+  if (link) result.link = link.href;\n\
+  var input = element.closest('input, textarea, select, [contenteditable]');\n\
+  result.editable = !!input && (input.isContentEditable || ['INPUT', 'TEXTAREA', 'SELECT'].indexOf(input.tagName) >= 0) && !input.disabled;\n\
+  result.cursor = getComputedStyle(element).cursor;\n\
+  var root = document.scrollingElement || document.documentElement;\n\
+  if (x >= root.clientWidth || y >= root.clientHeight) { result.scrollbar = true; return result; }\n\
+  for (var e = element; e && e !== root; e = e.parentElement) {\n\
+    if (e.scrollHeight <= e.clientHeight && e.scrollWidth <= e.clientWidth) continue;\n\
+    var rect = e.getBoundingClientRect();\n\
+    var left = rect.left + e.clientLeft, top = rect.top + e.clientTop;\n\
+    if (x >= left + e.clientWidth || y >= top + e.clientHeight) { result.scrollbar = true; break; }\n\
+  }\n\
+  return result;\n\
+}";
+
+lazy_static::lazy_static! {
+    /// The draggable regions of browsers, by browser identifier, in the order
+    /// CEF reported them.
+    static ref REGIONS: Mutex<HashMap<i32, Vec<(Rect, bool)>>> = Mutex::new(HashMap::new());
+    /// Regions set with [set_draggable_regions], which replace the page's.
+    static ref OVERRIDES: Mutex<HashMap<i32, Vec<(Rect, bool)>>> = Mutex::new(HashMap::new());
+}
+
+pub(crate) fn record_draggable_regions(browser_id: i32, regions: &[DraggableRegion]) {
+    let regions = regions.iter().map(|region| (region.bounds(), region.draggable())).collect::<Vec<_>>();
+    let mut all = REGIONS.lock();
+    if regions.is_empty() {
+        all.remove(&browser_id);
+    } else {
+        all.insert(browser_id, regions);
+    }
+}
+
+pub(crate) fn forget_browser(browser_id: i32) {
+    REGIONS.lock().remove(&browser_id);
+    OVERRIDES.lock().remove(&browser_id);
+}
+
+/// Sets the draggable regions of `browser` at runtime, in view coordinates,
+/// as `(bounds, draggable)` pairs. They replace the regions the page marked
+/// with `-webkit-app-region` until cleared with `None`, e.g. for a title bar
+/// that the application draws over the browser, or while a page in the
+/// title bar area is still loading.
+pub fn set_draggable_regions(browser: &Browser, regions: Option<&[(Rect, bool)]>) {
+    let mut overrides = OVERRIDES.lock();
+    match regions {
+        Some(regions) => {
+            overrides.insert(browser.get_identifier(), regions.to_vec());
+        }
+        None => {
+            overrides.remove(&browser.get_identifier());
+        }
+    }
+}
+
+/// Returns whether `point`, in view coordinates, is in a draggable region of
+/// `browser`, or `None` if its page has no regions. Non-draggable regions,
+/// e.g. buttons in a title bar, take precedence over draggable ones.
+///
+/// Regions of the page are only known for browsers whose client has a
+/// [DragHandler](crate::client::drag_handler::DragHandler). Regions set with
+/// [set_draggable_regions] take their place.
+pub fn draggable_at(browser: &Browser, point: Point) -> Option<bool> {
+    let browser_id = browser.get_identifier();
+    let overrides = OVERRIDES.lock();
+    let page_regions = REGIONS.lock();
+    let regions = overrides.get(&browser_id).or_else(|| page_regions.get(&browser_id))?;
+    let contains = |rect: &Rect| {
+        point.x >= rect.x && point.y >= rect.y && point.x < rect.x + rect.width && point.y < rect.y + rect.height
+    };
+    let mut draggable = false;
+    for (rect, region_draggable) in regions {
+        if contains(rect) {
+            if !region_draggable {
+                return Some(false);
+            }
+            draggable = true;
+        }
+    }
+    Some(draggable)
+}
+
+/// What is under a point of a browser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HitTestResult {
+    pub point: Point,
+    /// The lowercase tag name of the element, empty if there is none.
+    pub tag_name: String,
+    /// The target of the link under the point.
+    pub link_url: Option<String>,
+    /// Set over form fields and content-editable elements.
+    pub editable: bool,
+    /// Set over the scrollbar of the page or of a scrollable element.
+    pub scrollbar: bool,
+    /// The computed CSS cursor, e.g. `pointer`.
+    pub cursor: String,
+    /// Whether the point is in a draggable region, see [draggable_at].
+    pub draggable_region: Option<bool>,
+}
+
+impl HitTestResult {
+    /// Returns true if a press at the point should drag the window: it's in a
+    /// draggable region and not over anything interactive.
+    pub fn should_drag_window(&self) -> bool {
+        self.draggable_region == Some(true) && self.link_url.is_none() && !self.editable && !self.scrollbar
+    }
+}
+
+pub type HitTestOutput = Result<HitTestResult, DevToolsError>;
+
+#[derive(Default)]
+struct HitTestState {
+    result: Option<HitTestOutput>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the [HitTestResult] of a point, see [Browser::hit_test].
+pub struct HitTest(Arc<Mutex<HitTestState>>);
+
+impl Future for HitTest {
+    type Output = HitTestOutput;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<HitTestOutput> {
+        let mut state = self.0.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn finish(state: &Mutex<HitTestState>, result: HitTestOutput) {
+    let waker = {
+        let mut state = state.lock();
+        state.result = Some(result);
+        state.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+fn unexpected() -> DevToolsError {
+    DevToolsError { code: -1, message: "unexpected result".to_owned() }
+}
+
+pub(crate) fn hit_test(browser: &Browser, point: Point) -> HitTest {
+    let state = Arc::new(Mutex::new(HitTestState::default()));
+    let base = HitTestResult {
+        point,
+        tag_name: String::new(),
+        link_url: None,
+        editable: false,
+        scrollbar: false,
+        cursor: String::new(),
+        draggable_region: draggable_at(browser, point),
+    };
+    let session = DevToolsSession::new(&browser.get_host());
+    let params = DictionaryValue::new();
+    params.insert_int("x", point.x);
+    params.insert_int("y", point.y);
+    params.insert_bool("includeUserAgentShadowDOM", false);
+    let result_state = state.clone();
+    // The callbacks hold the session, which keeps its observer registered
+    // until the result arrived.
+    let next_session = session.clone();
+    session.execute("DOM.getNodeForLocation", Some(params), move |result| {
+        let backend_node_id = match result {
+            Ok(StoredValue::Dictionary(result)) => result.get_int("backendNodeId"),
+            Ok(_) => return finish(&result_state, Err(unexpected())),
+            // Nothing at the point, e.g. outside of the page.
+            Err(_) => return finish(&result_state, Ok(base)),
+        };
+        let params = DictionaryValue::new();
+        params.insert_int("backendNodeId", backend_node_id);
+        let describe_session = next_session.clone();
+        next_session.execute("DOM.resolveNode", Some(params), move |result| {
+            let object_id = match result {
+                Ok(StoredValue::Dictionary(result)) => match result.try_get_dictionary("object") {
+                    Some(object) => object.get_string("objectId"),
+                    None => return finish(&result_state, Err(unexpected())),
+                },
+                Ok(_) => return finish(&result_state, Err(unexpected())),
+                Err(error) => return finish(&result_state, Err(error)),
+            };
+            let arguments = ListValue::new();
+            for (i, value) in [point.x, point.y].iter().enumerate() {
+                let argument = DictionaryValue::new();
+                argument.insert_int("value", *value);
+                arguments.set_dictionary(i, argument);
+            }
+            let params = DictionaryValue::new();
+            params.insert_string("objectId", &object_id);
+            params.insert_string("functionDeclaration", DESCRIBE_FUNCTION);
+            params.insert_list("arguments", arguments);
+            params.insert_bool("returnByValue", true);
+            let release_session = describe_session.clone();
+            describe_session.execute("Runtime.callFunctionOn", Some(params), move |result| {
+                let params = DictionaryValue::new();
+                params.insert_string("objectId", &object_id);
+                release_session.send("Runtime.releaseObject", Some(params));
+                let description = match result {
+                    Ok(StoredValue::Dictionary(result)) => result.try_get_dictionary("result").and_then(|result| result.try_get_dictionary("value")),
+                    Ok(_) => None,
+                    Err(error) => return finish(&result_state, Err(error)),
+                };
+                let description = match description {
+                    Some(description) => description,
+                    None => return finish(&result_state, Err(unexpected())),
+                };
+                finish(&result_state, Ok(HitTestResult {
+                    tag_name: description.get_string("tagName"),
+                    link_url: match description.get("link") {
+                        StoredValue::String(link) => Some(link),
+                        _ => None,
+                    },
+                    editable: description.get_bool("editable"),
+                    scrollbar: description.get_bool("scrollbar"),
+                    cursor: description.get_string("cursor"),
+                    ..base
+                }));
+            });
+        });
+    });
+    HitTest(state)
+}
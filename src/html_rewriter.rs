@@ -0,0 +1,267 @@
+//! Streaming HTML rewriting on top of [ResponseFilter], backed by `lol_html`.
+//!
+//! Register element and document handlers on an [HtmlRewriterFilter] once, then
+//! call [HtmlRewriterFilter::build] from
+//! [ResourceRequestHandlerCallbacks::get_resource_response_filter] to get a fresh
+//! filter for every HTML response. The document is parsed incrementally as CEF
+//! delivers it, so handlers never see a chunk boundary. Every filter runs its
+//! parser on a thread of its own, as `lol_html` rewriters can't move between
+//! threads.
+//!
+//! Note that rewriting usually changes the body length; the `Content-Length`
+//! header of the original response is not adjusted.
+
+use crate::{
+    response::Response,
+    url_request::{ResponseFilter, ResponseFilterCallbacks, ResponseFilterStatus},
+};
+use lol_html::{
+    html_content::{Comment, DocumentEnd, Element, TextChunk},
+    DocumentContentHandlers, ElementContentHandlers, HandlerResult, HtmlRewriter, Selector,
+    Settings,
+};
+use parking_lot::Mutex;
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+pub use lol_html::html_content;
+pub use lol_html::errors::SelectorError;
+
+type ElementFn = Arc<dyn Fn(&mut Element) -> HandlerResult + Send + Sync>;
+type TextFn = Arc<dyn Fn(&mut TextChunk) -> HandlerResult + Send + Sync>;
+type CommentFn = Arc<dyn Fn(&mut Comment) -> HandlerResult + Send + Sync>;
+type EndFn = Arc<dyn Fn(&mut DocumentEnd) -> HandlerResult + Send + Sync>;
+
+enum SelectorHandler {
+    Element(ElementFn),
+    Text(TextFn),
+    Comments(CommentFn),
+}
+
+/// A set of HTML rewriting rules that can be turned into [ResponseFilter]s.
+///
+/// The handlers are shared between every filter built from this object and may be
+/// called concurrently for different responses, so they must be `Send + Sync`.
+#[derive(Clone, Default)]
+pub struct HtmlRewriterFilter {
+    selector_handlers: Vec<(Selector, SelectorHandler)>,
+    end_handlers: Vec<EndFn>,
+}
+
+impl Clone for SelectorHandler {
+    fn clone(&self) -> Self {
+        match self {
+            SelectorHandler::Element(f) => SelectorHandler::Element(f.clone()),
+            SelectorHandler::Text(f) => SelectorHandler::Text(f.clone()),
+            SelectorHandler::Comments(f) => SelectorHandler::Comments(f.clone()),
+        }
+    }
+}
+
+impl HtmlRewriterFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Calls `handler` for every element matching the CSS `selector`.
+    pub fn element(
+        mut self,
+        selector: &str,
+        handler: impl 'static + Send + Sync + Fn(&mut Element) -> HandlerResult,
+    ) -> Result<Self, SelectorError> {
+        let selector = selector.parse::<Selector>()?;
+        self.selector_handlers.push((selector, SelectorHandler::Element(Arc::new(handler))));
+        Ok(self)
+    }
+    /// Calls `handler` for every text chunk inside elements matching the CSS `selector`.
+    pub fn text(
+        mut self,
+        selector: &str,
+        handler: impl 'static + Send + Sync + Fn(&mut TextChunk) -> HandlerResult,
+    ) -> Result<Self, SelectorError> {
+        let selector = selector.parse::<Selector>()?;
+        self.selector_handlers.push((selector, SelectorHandler::Text(Arc::new(handler))));
+        Ok(self)
+    }
+    /// Calls `handler` for every comment inside elements matching the CSS `selector`.
+    pub fn comments(
+        mut self,
+        selector: &str,
+        handler: impl 'static + Send + Sync + Fn(&mut Comment) -> HandlerResult,
+    ) -> Result<Self, SelectorError> {
+        let selector = selector.parse::<Selector>()?;
+        self.selector_handlers.push((selector, SelectorHandler::Comments(Arc::new(handler))));
+        Ok(self)
+    }
+    /// Calls `handler` once the whole document has been parsed. Useful for
+    /// appending content to the end of the response.
+    pub fn document_end(
+        mut self,
+        handler: impl 'static + Send + Sync + Fn(&mut DocumentEnd) -> HandlerResult,
+    ) -> Self {
+        self.end_handlers.push(Arc::new(handler));
+        self
+    }
+    /// Returns `true` if `response` looks like an HTML document that this filter
+    /// should be applied to.
+    pub fn handles(response: &Response) -> bool {
+        let mime_type = response.get_mime_type().to_ascii_lowercase();
+        mime_type == "text/html" || mime_type == "application/xhtml+xml"
+    }
+    /// Creates a new [ResponseFilter] applying the registered handlers. A filter
+    /// instance can only be used for a single response.
+    pub fn build(&self) -> ResponseFilter {
+        ResponseFilter::new(HtmlRewriterFilterCallbacks {
+            rules: self.clone(),
+            state: Mutex::new(None),
+        })
+    }
+
+    fn create_rewriter(&self, output: Arc<Mutex<VecDeque<u8>>>) -> HtmlRewriter<'static, OutputSink> {
+        let element_content_handlers = self.selector_handlers.iter().map(|(selector, handler)| {
+            let handlers = match handler.clone() {
+                SelectorHandler::Element(f) => ElementContentHandlers::default().element(move |el: &mut Element| f(el)),
+                SelectorHandler::Text(f) => ElementContentHandlers::default().text(move |t: &mut TextChunk| f(t)),
+                SelectorHandler::Comments(f) => ElementContentHandlers::default().comments(move |c: &mut Comment| f(c)),
+            };
+            (Cow::Borrowed(selector), handlers)
+        }).collect();
+        let document_content_handlers = self.end_handlers.iter().cloned().map(|f| {
+            DocumentContentHandlers::default().end(move |end: &mut DocumentEnd| f(end))
+        }).collect();
+        HtmlRewriter::new(
+            Settings {
+                element_content_handlers,
+                document_content_handlers,
+                ..Settings::default()
+            },
+            OutputSink(output),
+        )
+    }
+    /// Runs a rewriter on the calling thread, feeding it the chunks received
+    /// from `chunks` and acknowledging each one on `results`.
+    fn run_rewriter(&self, output: Arc<Mutex<VecDeque<u8>>>, chunks: Receiver<Chunk>, results: Sender<Result<(), String>>) {
+        let mut rewriter = self.create_rewriter(output);
+        let result = loop {
+            match chunks.recv() {
+                Ok(Chunk::Data(data)) => match rewriter.write(&data) {
+                    Ok(()) => { let _ = results.send(Ok(())); },
+                    Err(err) => break Err(err),
+                },
+                Ok(Chunk::End) => break rewriter.end(),
+                // The filter was dropped before the response completed.
+                Err(_) => return,
+            }
+        };
+        let _ = results.send(result.map_err(|err| err.to_string()));
+    }
+}
+
+struct OutputSink(Arc<Mutex<VecDeque<u8>>>);
+
+impl lol_html::OutputSink for OutputSink {
+    fn handle_chunk(&mut self, chunk: &[u8]) {
+        self.0.lock().extend(chunk);
+    }
+}
+
+enum Chunk {
+    Data(Vec<u8>),
+    End,
+}
+
+/// The filter's end of a rewriter running on its own thread. `lol_html`'s
+/// rewriter can't be moved between threads, so it stays on the thread that
+/// created it.
+struct RewriterState {
+    chunks: Sender<Chunk>,
+    results: Receiver<Result<(), String>>,
+    output: Arc<Mutex<VecDeque<u8>>>,
+    /// Set once the end of the input has been passed on to the rewriter.
+    ended: bool,
+}
+
+impl RewriterState {
+    fn send(&self, chunk: Chunk) -> Result<(), String> {
+        self.chunks.send(chunk).map_err(|_| "rewriter thread exited".to_owned())?;
+        self.results.recv().unwrap_or_else(|_| Err("rewriter thread exited".to_owned()))
+    }
+}
+
+struct HtmlRewriterFilterCallbacks {
+    rules: HtmlRewriterFilter,
+    state: Mutex<Option<RewriterState>>,
+}
+
+impl ResponseFilterCallbacks for HtmlRewriterFilterCallbacks {
+    fn init_filter(&self) -> bool {
+        let output = Arc::new(Mutex::new(VecDeque::new()));
+        let (chunks, chunk_receiver) = mpsc::channel();
+        let (result_sender, results) = mpsc::channel();
+        let rules = self.rules.clone();
+        let rewriter_output = output.clone();
+        let spawned = thread::Builder::new()
+            .name("html-rewriter".to_owned())
+            .spawn(move || rules.run_rewriter(rewriter_output, chunk_receiver, result_sender));
+        if let Err(err) = spawned {
+            log::error!("Failed to start the HTML rewriter thread: {}", err);
+            return false;
+        }
+        *self.state.lock() = Some(RewriterState {
+            chunks,
+            results,
+            output,
+            ended: false,
+        });
+        true
+    }
+    fn filter(
+        &self,
+        data_in: &[u8],
+        data_in_read: &mut usize,
+        data_out: &mut [u8],
+        data_out_written: &mut usize,
+    ) -> ResponseFilterStatus {
+        let mut state = self.state.lock();
+        let state = match state.as_mut() {
+            Some(state) => state,
+            None => return ResponseFilterStatus::Error,
+        };
+
+        let result = if !data_in.is_empty() {
+            state.send(Chunk::Data(data_in.to_vec()))
+        } else if !state.ended {
+            // CEF passes an empty chunk once the response is complete, and
+            // again on every call after that while output is still pending.
+            // Flush whatever the parser is holding on to the first time.
+            state.ended = true;
+            state.send(Chunk::End)
+        } else {
+            Ok(())
+        };
+        *data_in_read = data_in.len();
+        if let Err(err) = result {
+            log::error!("HTML rewriting failed: {}", err);
+            return ResponseFilterStatus::Error;
+        }
+
+        let mut output = state.output.lock();
+        let written = output.len().min(data_out.len());
+        for (dst, src) in data_out.iter_mut().zip(output.drain(..written)) {
+            *dst = src;
+        }
+        *data_out_written = written;
+
+        if output.is_empty() && state.ended {
+            ResponseFilterStatus::Done
+        } else {
+            ResponseFilterStatus::NeedMoreData
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Conversions between CEF requests and responses and the types of the
+//! [http] crate, so middleware written against `http` can inspect and build
+//! them. Enabled with the `http` feature.
+//!
+//! ```ignore
+//! // in ResourceRequestHandlerCallbacks::on_before_resource_load:
+//! let http_request = http::Request::<Vec<u8>>::try_from(&request)?;
+//! let http_request = middleware.apply(http_request);
+//! request.set_header_map(&from_header_map(http_request.headers()));
+//! ```
+//!
+//! Header maps are CEF's `HashMap<String, Vec<String>>` on one side, which
+//! Rust's coherence rules don't allow `From` implementations for, so they are
+//! converted with [to_header_map] and [from_header_map].
+
+use crate::{
+    request::{PostData, PostDataElement, PostDataElementType, Request},
+    response::Response,
+};
+use http::{
+    header::{HeaderName, HeaderValue, CONTENT_TYPE},
+    HeaderMap, Method, StatusCode,
+};
+use std::{collections::HashMap, convert::TryFrom, fmt};
+
+/// A value that CEF accepts but the [http] crate doesn't.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpConversionError {
+    InvalidHeaderName(String),
+    InvalidHeaderValue(String),
+    InvalidMethod(String),
+    InvalidUrl(String),
+    InvalidStatus(i32),
+    /// The request body refers to a file, which is only read by the network
+    /// stack.
+    FileBody(String),
+}
+
+impl fmt::Display for HttpConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HttpConversionError::InvalidHeaderName(name) => write!(f, "invalid header name {:?}", name),
+            HttpConversionError::InvalidHeaderValue(name) => write!(f, "invalid value for header {:?}", name),
+            HttpConversionError::InvalidMethod(method) => write!(f, "invalid method {:?}", method),
+            HttpConversionError::InvalidUrl(url) => write!(f, "invalid URL {:?}", url),
+            HttpConversionError::InvalidStatus(status) => write!(f, "invalid status code {}", status),
+            HttpConversionError::FileBody(path) => write!(f, "request body refers to the file {:?}", path),
+        }
+    }
+}
+
+impl std::error::Error for HttpConversionError {}
+
+/// Converts a CEF header map into an [HeaderMap].
+pub fn to_header_map(headers: &HashMap<String, Vec<String>>) -> Result<HeaderMap, HttpConversionError> {
+    let mut header_map = HeaderMap::new();
+    for (name, values) in headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| HttpConversionError::InvalidHeaderName(name.clone()))?;
+        for value in values {
+            let value = HeaderValue::from_str(value).map_err(|_| HttpConversionError::InvalidHeaderValue(name.clone()))?;
+            header_map.append(header_name.clone(), value);
+        }
+    }
+    Ok(header_map)
+}
+
+/// Converts an [HeaderMap] into a CEF header map. Values that aren't UTF-8
+/// are converted lossily.
+pub fn from_header_map(header_map: &HeaderMap) -> HashMap<String, Vec<String>> {
+    let mut headers: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, value) in header_map {
+        headers
+            .entry(name.as_str().to_owned())
+            .or_insert_with(Vec::new)
+            .push(String::from_utf8_lossy(value.as_bytes()).into_owned());
+    }
+    headers
+}
+
+/// Copies the URL, method, headers and body. Bodies that refer to files
+/// fail with [HttpConversionError::FileBody].
+impl TryFrom<&Request> for http::Request<Vec<u8>> {
+    type Error = HttpConversionError;
+
+    fn try_from(request: &Request) -> Result<Self, Self::Error> {
+        let url = request.get_url();
+        let method = request.get_method();
+        let mut body = Vec::new();
+        if let Some(post_data) = request.try_get_post_data() {
+            for element in post_data.get_elements() {
+                match element.get_type() {
+                    PostDataElementType::Bytes => body.extend_from_slice(&element.get_bytes()),
+                    PostDataElementType::File => return Err(HttpConversionError::FileBody(element.get_file())),
+                    PostDataElementType::Empty => (),
+                }
+            }
+        }
+        let mut http_request = http::Request::new(body);
+        *http_request.method_mut() = Method::from_bytes(method.as_bytes()).map_err(|_| HttpConversionError::InvalidMethod(method.clone()))?;
+        *http_request.uri_mut() = url.parse().map_err(|_| HttpConversionError::InvalidUrl(url.clone()))?;
+        *http_request.headers_mut() = to_header_map(&request.get_header_map())?;
+        Ok(http_request)
+    }
+}
+
+/// Creates a CEF request with the URL, method, headers and body.
+impl<B: AsRef<[u8]>> From<http::Request<B>> for Request {
+    fn from(http_request: http::Request<B>) -> Request {
+        let request = Request::new();
+        request.set_url(&http_request.uri().to_string());
+        request.set_method(http_request.method().as_str());
+        request.set_header_map(&from_header_map(http_request.headers()));
+        let body = http_request.body().as_ref();
+        if !body.is_empty() {
+            let element = PostDataElement::new();
+            element.set_to_bytes(body);
+            let post_data = PostData::new();
+            post_data.add_element(&element);
+            request.set_post_data(post_data);
+        }
+        request
+    }
+}
+
+/// Copies the status and headers. CEF reports the Content-Type of some
+/// responses only as MIME type and charset, so it is added from them if the
+/// headers lack it.
+impl TryFrom<&Response> for http::Response<()> {
+    type Error = HttpConversionError;
+
+    fn try_from(response: &Response) -> Result<Self, Self::Error> {
+        let status = response.get_status();
+        let mut http_response = http::Response::new(());
+        *http_response.status_mut() = u16::try_from(status)
+            .ok()
+            .and_then(|status| StatusCode::from_u16(status).ok())
+            .ok_or(HttpConversionError::InvalidStatus(status))?;
+        let mut headers = to_header_map(&response.get_header_map())?;
+        let mime_type = response.get_mime_type();
+        if !headers.contains_key(CONTENT_TYPE) && !mime_type.is_empty() {
+            let charset = response.get_charset();
+            let content_type = if charset.is_empty() { mime_type } else { format!("{}; charset={}", mime_type, charset) };
+            let value = HeaderValue::from_str(&content_type).map_err(|_| HttpConversionError::InvalidHeaderValue(CONTENT_TYPE.to_string()))?;
+            headers.insert(CONTENT_TYPE, value);
+        }
+        *http_response.headers_mut() = headers;
+        Ok(http_response)
+    }
+}
+
+/// Creates a CEF response with the status, headers, and the MIME type and
+/// charset of the Content-Type header. The body is left out, as CEF responses
+/// don't carry one.
+impl<B> From<&http::Response<B>> for Response {
+    fn from(http_response: &http::Response<B>) -> Response {
+        let response = Response::new();
+        let status = http_response.status();
+        response.set_status(i32::from(status.as_u16()));
+        response.set_status_text(status.canonical_reason().unwrap_or(""));
+        response.set_header_map(&from_header_map(http_response.headers()));
+        if let Some(content_type) = http_response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()) {
+            let mut parts = content_type.split(';');
+            response.set_mime_type(parts.next().unwrap_or("").trim());
+            for parameter in parts {
+                let mut parameter = parameter.splitn(2, '=');
+                if parameter.next().map(|name| name.trim().eq_ignore_ascii_case("charset")) == Some(true) {
+                    response.set_charset(parameter.next().unwrap_or("").trim().trim_matches('"'));
+                }
+            }
+        }
+        response
+    }
+}
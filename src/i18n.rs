@@ -0,0 +1,155 @@
+//! Locale selection and text direction.
+//!
+//! CEF defaults to "en-US" on Windows and macOS unless
+//! [Settings::locale](crate::settings::Settings::locale) is set. On Linux the
+//! locale always comes from the `LANGUAGE`, `LC_ALL`, `LC_MESSAGES` and `LANG`
+//! environment variables. [Settings::system_locale](crate::settings::Settings::system_locale)
+//! picks the locale of the operating system instead, restricted to the locale
+//! files that are actually shipped:
+//!
+//! ```ignore
+//! let settings = Settings::new().locales_dir_path(app_dir.join("locales")).system_locale();
+//! ```
+
+use parking_lot::Mutex;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// The locale Chromium uses when none is configured.
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Languages written right-to-left, by ISO 639 code.
+const RTL_LANGUAGES: &[&str] = &["ar", "ckb", "dv", "fa", "he", "iw", "ps", "sd", "ug", "ur", "yi"];
+
+lazy_static::lazy_static! {
+    static ref CONFIGURED_LOCALE: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// Returns true if the UI text direction of the running application is
+/// right-to-left. Must be called after CEF was initialized.
+pub fn is_rtl() -> bool {
+    unsafe { cef_sys::cef_is_rtl() != 0 }
+}
+
+/// Returns true if `locale` is written right-to-left.
+pub fn is_rtl_locale(locale: &str) -> bool {
+    let language = normalize_locale(locale);
+    let language = language.split('-').next().unwrap_or("");
+    RTL_LANGUAGES.contains(&language)
+}
+
+/// Returns the locale CEF was initialized with: the configured
+/// [Settings::locale](crate::settings::Settings::locale), the environment on
+/// Linux, or [DEFAULT_LOCALE].
+pub fn current_locale() -> String {
+    if cfg!(target_os = "linux") {
+        if let Some(locale) = system_locale() {
+            return normalize_locale(&locale);
+        }
+    }
+    CONFIGURED_LOCALE.lock().clone().unwrap_or_else(|| DEFAULT_LOCALE.to_owned())
+}
+
+pub(crate) fn set_configured_locale(locale: Option<String>) {
+    *CONFIGURED_LOCALE.lock() = locale.filter(|locale| !locale.is_empty());
+}
+
+/// Returns the user's locale as reported by the operating system, e.g.
+/// `de_DE.UTF-8` or `de-DE`. Use [normalize_locale] to get the Chromium name.
+pub fn system_locale() -> Option<String> {
+    os_locale().filter(|locale| !locale.is_empty() && locale != "C" && locale != "POSIX")
+}
+
+#[cfg(windows)]
+fn os_locale() -> Option<String> {
+    use winapi::um::winnls::GetUserDefaultLocaleName;
+    // LOCALE_NAME_MAX_LENGTH
+    let mut buffer = [0u16; 85];
+    let len = unsafe { GetUserDefaultLocaleName(buffer.as_mut_ptr(), buffer.len() as i32) };
+    if len <= 1 {
+        return None;
+    }
+    // The length includes the terminating null.
+    Some(String::from_utf16_lossy(&buffer[..len as usize - 1]))
+}
+
+#[cfg(not(windows))]
+fn os_locale() -> Option<String> {
+    // Same precedence as Chromium on Linux. GUI apps on macOS usually don't
+    // have these set, in which case the default locale is used.
+    if let Ok(language) = env::var("LANGUAGE") {
+        if let Some(first) = language.split(':').find(|language| !language.is_empty()) {
+            return Some(first.to_owned());
+        }
+    }
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// Converts a POSIX or BCP 47 locale such as `pt_BR.UTF-8@euro` or `de-AT` to
+/// the name of the matching Chromium locale file, e.g. `pt-BR` or `de`.
+pub fn normalize_locale(locale: &str) -> String {
+    let locale = locale.split(|c| c == '.' || c == '@').next().unwrap_or("");
+    let mut parts = locale.split(|c| c == '_' || c == '-').filter(|part| !part.is_empty());
+    let language = parts.next().unwrap_or("").to_ascii_lowercase();
+    let region = parts.next().map(|region| region.to_ascii_uppercase());
+    if language.is_empty() {
+        return DEFAULT_LOCALE.to_owned();
+    }
+    let language = match &language[..] {
+        // Chromium still uses the deprecated code for Hebrew.
+        "iw" => "he".to_owned(),
+        "nb" | "nn" | "no" => "nb".to_owned(),
+        _ => language,
+    };
+    match (&language[..], region.as_ref().map(|region| &region[..])) {
+        ("en", Some("GB")) | ("en", Some("AU")) | ("en", Some("NZ")) | ("en", Some("IE")) | ("en", Some("IN")) => "en-GB".to_owned(),
+        ("en", _) => "en-US".to_owned(),
+        ("es", Some("ES")) | ("es", None) => "es".to_owned(),
+        ("es", Some(_)) => "es-419".to_owned(),
+        ("pt", Some("PT")) => "pt-PT".to_owned(),
+        ("pt", _) => "pt-BR".to_owned(),
+        ("zh", Some("TW")) | ("zh", Some("HK")) | ("zh", Some("MO")) | ("zh", Some("HANT")) => "zh-TW".to_owned(),
+        ("zh", _) => "zh-CN".to_owned(),
+        (language, _) => language.to_owned(),
+    }
+}
+
+/// Returns the names of the locale files in `locales_dir`, e.g. `de` for
+/// `de.pak`.
+pub fn available_locales(locales_dir: &Path) -> Vec<String> {
+    let mut locales = fs::read_dir(locales_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().map_or(false, |extension| extension == "pak"))
+                .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_owned))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    locales.sort();
+    locales
+}
+
+/// Returns the locale of `available` that fits `preferred` best: the same
+/// locale, another region of the same language, or `None`.
+pub fn best_locale(preferred: &str, available: &[String]) -> Option<String> {
+    let preferred = normalize_locale(preferred);
+    if let Some(locale) = available.iter().find(|locale| locale.eq_ignore_ascii_case(&preferred)) {
+        return Some(locale.clone());
+    }
+    let language = preferred.split('-').next().unwrap_or("");
+    available.iter()
+        .find(|locale| locale.split('-').next().map_or(false, |other| other.eq_ignore_ascii_case(language)))
+        .cloned()
+}
+
+/// Returns the locales directory CEF uses if none is configured.
+pub(crate) fn default_locales_dir() -> Option<PathBuf> {
+    env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.join("locales")))
+}
+
@@ -0,0 +1,44 @@
+//! Helpers for generating JavaScript source from Rust values.
+
+use std::fmt::Write;
+
+/// Returns `s` as a double-quoted JavaScript string literal.
+pub(crate) fn string_literal(s: &str) -> String {
+    let mut literal = String::with_capacity(s.len() + 2);
+    literal.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            // Line and paragraph separators terminate string literals in older engines,
+            // and `</` would close an enclosing <script> element.
+            '\u{2028}' | '\u{2029}' | '<' => { write!(literal, "\\u{:04x}", c as u32).unwrap(); },
+            c if (c as u32) < 0x20 => { write!(literal, "\\u{:04x}", c as u32).unwrap(); },
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Converts a URL glob, where `*` matches any sequence of characters, into the
+/// source of an anchored JavaScript regular expression.
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::with_capacity(glob.len() + 2);
+    regex.push('^');
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '\\' | '^' | '$' | '.' | '|' | '?' | '+' | '(' | ')' | '[' | ']' | '{' | '}' | '/' => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            c => regex.push(c),
+        }
+    }
+    regex.push('$');
+    regex
+}
@@ -0,0 +1,196 @@
+//! The Keyboard Lock API for fullscreen content.
+//!
+//! Games and remote desktop clients call `navigator.keyboard.lock()` to
+//! receive keys the browser or system would otherwise handle, such as Escape,
+//! Alt+Tab or the Windows key. CEF 85 doesn't implement the permission path
+//! behind it, so the call has no effect and the application can't tell
+//! whether the page wants those keys. [KeyboardLock] replaces the API with a
+//! shim that asks the application, which then forwards the locked keys
+//! instead of acting on them:
+//!
+//! ```ignore
+//! let lock = KeyboardLock::attach(&browser, |_, request| match request {
+//!     KeyboardLockRequest::Lock(_) => fullscreen.is_fullscreen(),
+//!     KeyboardLockRequest::Unlock => true,
+//! });
+//! // In the key handler:
+//! if key == "Escape" && !lock.is_key_locked("Escape") {
+//!     host.exit_fullscreen(true);
+//! } else if lock.should_exit_on_escape_hold(held_for) {
+//!     host.exit_fullscreen(true);
+//! } else {
+//!     host.send_key_event(&event);
+//! }
+//! ```
+//!
+//! Like Chromium, the lock only applies while the page is fullscreen, and a
+//! locked Escape exits fullscreen only when held down; see
+//! [KeyboardLock::should_exit_on_escape_hold].
+
+use crate::{
+    browser::Browser,
+    devtools::DevToolsSession,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{sync::Arc, time::Duration};
+
+/// The name of the DevTools binding requests are reported through.
+const BINDING_NAME: &str = "__cefKeyboardLockRequest";
+
+/// How long a locked Escape has to be held to exit fullscreen, as in Chromium.
+pub const ESCAPE_HOLD_DURATION: Duration = Duration::from_secs(2);
+
+const SHIM_SOURCE: &str = "\
+var notify = window.__cefKeyboardLockRequest;\n\
+if (typeof notify !== 'function' || !navigator.keyboard || window.__cefKeyboardLock) return;\n\
+try { delete window.__cefKeyboardLockRequest; } catch (e) {}\n\
+var pending = {}, nextId = 1;\n\
+navigator.keyboard.lock = function(codes) {\n\
+  if (codes !== undefined && !Array.isArray(codes)) return Promise.reject(new TypeError('keyCodes must be an array'));\n\
+  var id = nextId++;\n\
+  return new Promise(function(resolve, reject) {\n\
+    pending[id] = { resolve: resolve, reject: reject };\n\
+    notify(JSON.stringify({ op: 'lock', id: id, codes: codes || null }));\n\
+  });\n\
+};\n\
+navigator.keyboard.unlock = function() { notify(JSON.stringify({ op: 'unlock' })); };\n\
+window.__cefKeyboardLock = {\n\
+  answer: function(id, granted) {\n\
+    var request = pending[id];\n\
+    if (!request) return;\n\
+    delete pending[id];\n\
+    if (granted) request.resolve();\n\
+    else request.reject(new DOMException('Keyboard lock was denied', 'NotAllowedError'));\n\
+  }\n\
+};";
+
+/// A request made by the page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardLockRequest {
+    /// The page called `navigator.keyboard.lock()`, with the `KeyboardEvent.code`
+    /// values to lock, or `None` for all keys.
+    Lock(Option<Vec<String>>),
+    /// The page called `navigator.keyboard.unlock()`.
+    Unlock,
+}
+
+/// The keys currently locked by the page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LockedKeys {
+    None,
+    All,
+    Some(Vec<String>),
+}
+
+/// Emulates the Keyboard Lock API for one browser. See the
+/// [module documentation](self).
+pub struct KeyboardLock {
+    locked: Arc<Mutex<LockedKeys>>,
+    session: DevToolsSession,
+    scripts: UserScripts,
+    script_id: UserScriptId,
+}
+
+impl KeyboardLock {
+    /// Installs the shim into the documents of `browser` and calls
+    /// `on_request` on the UI thread for every request of the page. Return
+    /// true to grant a lock; the result of unlock requests is ignored.
+    pub fn attach(
+        browser: &Browser,
+        on_request: impl 'static + Send + Sync + Fn(&Browser, &KeyboardLockRequest) -> bool,
+    ) -> KeyboardLock {
+        let locked = Arc::new(Mutex::new(LockedKeys::None));
+        let session = DevToolsSession::new(&browser.get_host());
+        let listener_locked = Arc::downgrade(&locked);
+        session.add_event_listener("Runtime.bindingCalled", move |browser, params| {
+            let params = match params {
+                StoredValue::Dictionary(params) if params.get_string("name") == BINDING_NAME => params,
+                _ => return,
+            };
+            let payload = match StoredValue::from_json(&params.get_string("payload")) {
+                Some(StoredValue::Dictionary(payload)) => payload,
+                _ => return,
+            };
+            let locked = match listener_locked.upgrade() {
+                Some(locked) => locked,
+                None => return,
+            };
+            match &payload.get_string("op")[..] {
+                "lock" => {
+                    let codes = payload.try_get_list("codes")
+                        .map(|codes| (0..codes.len()).filter_map(|i| codes.get_string(i)).collect::<Vec<_>>());
+                    let request = KeyboardLockRequest::Lock(codes.clone());
+                    let granted = on_request(browser, &request);
+                    if granted {
+                        *locked.lock() = match codes {
+                            Some(codes) if !codes.is_empty() => LockedKeys::Some(codes),
+                            _ => LockedKeys::All,
+                        };
+                    }
+                    let code = format!(
+                        "window.__cefKeyboardLock && window.__cefKeyboardLock.answer({}, {});",
+                        payload.get_int("id"),
+                        granted
+                    );
+                    browser.get_main_frame().execute_java_script(&code, "", 0);
+                }
+                "unlock" => {
+                    *locked.lock() = LockedKeys::None;
+                    on_request(browser, &KeyboardLockRequest::Unlock);
+                }
+                _ => (),
+            }
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        let script = UserScript::new(SHIM_SOURCE);
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        KeyboardLock {
+            locked,
+            session,
+            scripts,
+            script_id,
+        }
+    }
+    /// Returns true while the page holds a lock.
+    pub fn is_locked(&self) -> bool {
+        *self.locked.lock() != LockedKeys::None
+    }
+    /// Returns true if the key with the `KeyboardEvent.code` value `code`,
+    /// e.g. `Escape` or `MetaLeft`, should be forwarded to the page instead of
+    /// being handled by the application.
+    pub fn is_key_locked(&self, code: &str) -> bool {
+        match &*self.locked.lock() {
+            LockedKeys::None => false,
+            LockedKeys::All => true,
+            LockedKeys::Some(codes) => codes.iter().any(|locked| locked == code),
+        }
+    }
+    /// Returns true if Escape, held down for `held`, should exit fullscreen.
+    /// An unlocked Escape exits right away, a locked one after
+    /// [ESCAPE_HOLD_DURATION].
+    pub fn should_exit_on_escape_hold(&self, held: Duration) -> bool {
+        !self.is_key_locked("Escape") || held >= ESCAPE_HOLD_DURATION
+    }
+    /// Drops the lock from the application's side, e.g. when the page left
+    /// fullscreen. The page isn't notified, as in Chromium.
+    pub fn release(&self) {
+        *self.locked.lock() = LockedKeys::None;
+    }
+}
+
+impl Drop for KeyboardLock {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script_id);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
@@ -0,0 +1,385 @@
+//! Locking browsers down for unattended kiosk deployments.
+//!
+//! [KioskNavigationGuard] bundles the restrictions kiosks usually need: an
+//! allowlist of URLs, no popups or new windows, no downloads, no DevTools
+//! shortcuts, and a return to the home page after a period without input. It
+//! implements the handler traits involved, so hand clones of one guard to the
+//! client:
+//!
+//! ```ignore
+//! let guard = KioskNavigationGuard::new(KioskSettings::new("https://kiosk.example/")
+//!     .allow("https://kiosk.example/*")
+//!     .idle_timeout(Duration::from_secs(120)));
+//! // in ClientCallbacks
+//! fn get_request_handler(&self) -> Option<RequestHandler> { Some(self.guard.request_handler()) }
+//! fn get_life_span_handler(&self) -> Option<LifeSpanHandler> { Some(self.guard.life_span_handler()) }
+//! fn get_keyboard_handler(&self) -> Option<KeyboardHandler> { Some(self.guard.keyboard_handler()) }
+//! fn get_download_handler(&self) -> Option<DownloadHandler> { Some(self.guard.download_handler()) }
+//! ```
+//!
+//! Only keyboard input reaches the handlers, so the idle timer can't see mouse
+//! and touch input by itself. For windowless browsers, forward the events of
+//! [user_activity](crate::user_activity) to the guard:
+//!
+//! ```ignore
+//! let guard2 = guard.clone();
+//! user_activity::add_listener(move |_| guard2.record_activity());
+//! ```
+//!
+//! Otherwise call [KioskNavigationGuard::record_activity] wherever the
+//! application receives such input.
+
+use crate::{
+    browser::{Browser, BrowserSettings},
+    client::{
+        download_handler::{BeforeDownloadCallback, DownloadHandler, DownloadHandlerCallbacks, DownloadItem, DownloadItemCallback},
+        keyboard_handler::{KeyboardHandler, KeyboardHandlerCallbacks},
+        life_span_handler::{LifeSpanHandler, LifeSpanHandlerCallbacks, PopupFeatures},
+        request_handler::{RequestHandler, RequestHandlerCallbacks, WindowOpenDisposition},
+        Client,
+    },
+    events::{EventFlags, KeyEvent, WindowsKeyCode},
+    frame::Frame,
+    request::Request,
+    timer::{self, TimerHandle},
+    url_pattern::glob_matches,
+    values::DictionaryValue,
+    window::WindowInfo,
+};
+use parking_lot::Mutex;
+use std::{
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+/// How often the idle timeout is checked.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Configuration of a [KioskNavigationGuard].
+#[derive(Debug, Clone)]
+pub struct KioskSettings {
+    /// The page browsers return to when idle. Always allowed.
+    pub home_url: String,
+    /// URL globs, where `*` matches any sequence of characters, of the pages
+    /// that may be navigated to.
+    pub allowed_urls: Vec<String>,
+    /// Whether the allowlist also applies to subframes. Otherwise only
+    /// top-level navigations are checked.
+    pub restrict_subframes: bool,
+    /// Navigate back to `home_url` after this long without input. Browsers
+    /// already showing it aren't reloaded.
+    pub idle_timeout: Option<Duration>,
+    pub block_popups: bool,
+    pub block_downloads: bool,
+    /// Swallow the DevTools shortcuts F12 and Ctrl+Shift+I/J/C (Cmd+Alt+I/J/C
+    /// on macOS).
+    pub block_devtools: bool,
+}
+
+impl KioskSettings {
+    pub fn new(home_url: &str) -> KioskSettings {
+        KioskSettings {
+            home_url: home_url.to_owned(),
+            allowed_urls: Vec::new(),
+            restrict_subframes: false,
+            idle_timeout: None,
+            block_popups: true,
+            block_downloads: true,
+            block_devtools: true,
+        }
+    }
+    /// Allows navigating to URLs matching `pattern`. May be called multiple
+    /// times.
+    pub fn allow(mut self, pattern: &str) -> Self {
+        self.allowed_urls.push(pattern.to_owned());
+        self
+    }
+    pub fn restrict_subframes(mut self, restrict_subframes: bool) -> Self {
+        self.restrict_subframes = restrict_subframes;
+        self
+    }
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+    pub fn block_popups(mut self, block_popups: bool) -> Self {
+        self.block_popups = block_popups;
+        self
+    }
+    pub fn block_downloads(mut self, block_downloads: bool) -> Self {
+        self.block_downloads = block_downloads;
+        self
+    }
+    pub fn block_devtools(mut self, block_devtools: bool) -> Self {
+        self.block_devtools = block_devtools;
+        self
+    }
+
+    /// Returns true if `url` may be loaded.
+    pub fn is_allowed(&self, url: &str) -> bool {
+        normalize_url(url) == normalize_url(&self.home_url) || self.allowed_urls.iter().any(|pattern| glob_matches(pattern, url))
+    }
+}
+
+/// Returns `url` without its fragment, with the scheme and host lowercased,
+/// default ports removed and an empty path replaced by `/`, the way Chromium
+/// reports the URL of a loaded page.
+fn normalize_url(url: &str) -> String {
+    let url = url.split('#').next().unwrap_or("");
+    let scheme_end = match url.find("://") {
+        Some(scheme_end) => scheme_end,
+        None => return url.to_owned(),
+    };
+    let scheme = url[..scheme_end].to_ascii_lowercase();
+    let rest = &url[scheme_end + 3..];
+    let authority_end = rest.find(|c| c == '/' || c == '?').unwrap_or(rest.len());
+    let mut authority = rest[..authority_end].to_ascii_lowercase();
+    let default_port = match scheme.as_str() {
+        "http" | "ws" => Some(":80"),
+        "https" | "wss" => Some(":443"),
+        _ => None,
+    };
+    if let Some(default_port) = default_port {
+        if authority.ends_with(default_port) {
+            authority.truncate(authority.len() - default_port.len());
+        }
+    }
+    let path = &rest[authority_end..];
+    let separator = if path.starts_with('/') || scheme == "file" { "" } else { "/" };
+    format!("{}://{}{}{}", scheme, authority, separator, path)
+}
+
+struct KioskState {
+    settings: KioskSettings,
+    browsers: Mutex<Vec<Browser>>,
+    last_activity: Mutex<Instant>,
+    idle_timer: Mutex<Option<TimerHandle>>,
+    blocked_listener: Mutex<Option<Arc<dyn 'static + Send + Sync + Fn(&Browser, &str)>>>,
+}
+
+/// Applies [KioskSettings] to the browsers of a client. See the
+/// [module documentation](self).
+#[derive(Clone)]
+pub struct KioskNavigationGuard(Arc<KioskState>);
+
+impl KioskNavigationGuard {
+    pub fn new(settings: KioskSettings) -> KioskNavigationGuard {
+        KioskNavigationGuard(Arc::new(KioskState {
+            settings,
+            browsers: Mutex::new(Vec::new()),
+            last_activity: Mutex::new(Instant::now()),
+            idle_timer: Mutex::new(None),
+            blocked_listener: Mutex::new(None),
+        }))
+    }
+    pub fn settings(&self) -> &KioskSettings {
+        &self.0.settings
+    }
+    /// Calls `listener` with the URL of every navigation that was blocked.
+    pub fn on_blocked(&self, listener: impl 'static + Send + Sync + Fn(&Browser, &str)) {
+        *self.0.blocked_listener.lock() = Some(Arc::new(listener));
+    }
+    /// Resets the idle timer.
+    pub fn record_activity(&self) {
+        *self.0.last_activity.lock() = Instant::now();
+    }
+    /// Returns how long there has been no input.
+    pub fn idle_time(&self) -> Duration {
+        self.0.last_activity.lock().elapsed()
+    }
+    /// Navigates all browsers back to the home page.
+    pub fn go_home(&self) {
+        self.record_activity();
+        let browsers = self.0.browsers.lock().clone();
+        for browser in browsers {
+            browser.get_main_frame().load_url(&self.0.settings.home_url);
+        }
+    }
+
+    pub fn request_handler(&self) -> RequestHandler {
+        RequestHandler::new(self.clone())
+    }
+    pub fn life_span_handler(&self) -> LifeSpanHandler {
+        LifeSpanHandler::new(self.clone())
+    }
+    pub fn keyboard_handler(&self) -> KeyboardHandler {
+        KeyboardHandler::new(self.clone())
+    }
+    pub fn download_handler(&self) -> DownloadHandler {
+        DownloadHandler::new(self.clone())
+    }
+
+    fn blocked(&self, browser: &Browser, url: &str) {
+        log::info!("kiosk: blocked navigation to {}", url);
+        let listener = self.0.blocked_listener.lock().clone();
+        if let Some(listener) = listener {
+            listener(browser, url);
+        }
+    }
+    /// Navigates the browsers that left the home page back to it. Unlike
+    /// [KioskNavigationGuard::go_home], a page already showing the home page
+    /// isn't reloaded, so its state survives idle periods.
+    fn return_home(&self) {
+        self.record_activity();
+        let home_url = normalize_url(&self.0.settings.home_url);
+        let browsers = self.0.browsers.lock().clone();
+        for browser in browsers {
+            let frame = browser.get_main_frame();
+            if normalize_url(&frame.get_url()) != home_url {
+                frame.load_url(&self.0.settings.home_url);
+            }
+        }
+    }
+    fn start_idle_timer(&self) {
+        let idle_timeout = match self.0.settings.idle_timeout {
+            Some(idle_timeout) => idle_timeout,
+            None => return,
+        };
+        let mut idle_timer = self.0.idle_timer.lock();
+        if idle_timer.is_some() {
+            return;
+        }
+        let weak: Weak<KioskState> = Arc::downgrade(&self.0);
+        let handle = timer::interval(IDLE_CHECK_INTERVAL, move || {
+            if let Some(state) = weak.upgrade() {
+                let guard = KioskNavigationGuard(state);
+                if guard.idle_time() >= idle_timeout {
+                    guard.return_home();
+                }
+            }
+        });
+        *idle_timer = Some(handle);
+    }
+}
+
+impl Drop for KioskState {
+    fn drop(&mut self) {
+        if let Some(idle_timer) = self.idle_timer.get_mut().take() {
+            idle_timer.cancel();
+        }
+    }
+}
+
+impl RequestHandlerCallbacks for KioskNavigationGuard {
+    fn on_before_browse(
+        &self,
+        browser: Browser,
+        frame: Frame,
+        request: Request,
+        user_gesture: bool,
+        is_redirect: bool,
+    ) -> bool {
+        if user_gesture {
+            self.record_activity();
+        }
+        if !frame.is_main() && !self.0.settings.restrict_subframes {
+            return false;
+        }
+        let url = request.get_url();
+        if self.0.settings.is_allowed(&url) {
+            false
+        } else {
+            self.blocked(&browser, &url);
+            true
+        }
+    }
+    fn on_open_url_from_tab(
+        &self,
+        browser: Browser,
+        frame: Frame,
+        target_url: &str,
+        target_disposition: WindowOpenDisposition,
+        user_gesture: bool,
+    ) -> bool {
+        if self.0.settings.block_popups {
+            self.blocked(&browser, target_url);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl LifeSpanHandlerCallbacks for KioskNavigationGuard {
+    fn on_before_popup(
+        &self,
+        browser: Browser,
+        frame: Frame,
+        target_url: Option<&str>,
+        target_frame_name: Option<&str>,
+        target_disposition: WindowOpenDisposition,
+        user_gesture: bool,
+        popup_features: PopupFeatures,
+        window_info: &mut WindowInfo,
+        client: &mut Client,
+        settings: &mut BrowserSettings,
+        extra_info: &mut DictionaryValue,
+        no_javascript_access: &mut bool,
+    ) -> bool {
+        let url = target_url.unwrap_or("");
+        if self.0.settings.block_popups || !self.0.settings.is_allowed(url) {
+            self.blocked(&browser, url);
+            true
+        } else {
+            false
+        }
+    }
+    fn on_after_created(&self, browser: Browser) {
+        self.0.browsers.lock().push(browser);
+        self.start_idle_timer();
+    }
+    fn on_before_close(&self, browser: Browser) {
+        let browser_id = browser.get_identifier();
+        self.0.browsers.lock().retain(|b| b.get_identifier() != browser_id);
+    }
+}
+
+impl KeyboardHandlerCallbacks for KioskNavigationGuard {
+    fn on_pre_key_event(
+        &mut self,
+        browser: Browser,
+        event: KeyEvent,
+        is_keyboard_shortcut: &mut bool,
+    ) -> bool {
+        self.record_activity();
+        if !self.0.settings.block_devtools {
+            return false;
+        }
+        match event {
+            KeyEvent::KeyDown { modifiers, windows_key_code, .. } => {
+                let ctrl_shift = modifiers.contains(EventFlags::CONTROL_DOWN | EventFlags::SHIFT_DOWN)
+                    || modifiers.contains(EventFlags::COMMAND_DOWN | EventFlags::ALT_DOWN);
+                windows_key_code == WindowsKeyCode::F12
+                    || (ctrl_shift && [WindowsKeyCode::I, WindowsKeyCode::J, WindowsKeyCode::C].contains(&windows_key_code))
+            }
+            _ => false,
+        }
+    }
+}
+
+impl DownloadHandlerCallbacks for KioskNavigationGuard {
+    fn on_before_download(
+        &mut self,
+        browser: Browser,
+        download_item: DownloadItem,
+        suggested_name: &str,
+        callback: BeforeDownloadCallback,
+    ) {
+        // Not executing `callback` cancels the download.
+        if self.0.settings.block_downloads {
+            log::info!("kiosk: blocked download of {}", suggested_name);
+        } else {
+            callback.cont("", false);
+        }
+    }
+    fn on_download_updated(
+        &mut self,
+        browser: Browser,
+        download_item: DownloadItem,
+        callback: DownloadItemCallback,
+    ) {
+        if self.0.settings.block_downloads {
+            callback.cancel();
+        }
+    }
+}
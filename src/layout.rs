@@ -0,0 +1,304 @@
+//! Box layouts described like flexbox.
+//!
+//! A [BoxLayout] of views is applied to a panel with
+//! [Panel::set_to_box_layout](crate::views::Panel::set_to_box_layout), which
+//! adds the children and sets their flex:
+//!
+//! ```ignore
+//! panel.set_to_box_layout(
+//!     &row()
+//!         .gap(4)
+//!         .cross_axis_alignment(CrossAxisAlignment::Center)
+//!         .child_view(&back_button, Flex::NONE)
+//!         .child_view(&address_field, flex(1)),
+//! );
+//! ```
+//!
+//! For chrome drawn with the application's own toolkit, [BoxLayout] arranges
+//! children of a given preferred size itself and returns their bounds:
+//!
+//! ```ignore
+//! let bounds = row()
+//!     .gap(4)
+//!     .insets(Insets::uniform(6))
+//!     .cross_axis_alignment(CrossAxisAlignment::Center)
+//!     .child(Control::Back, Size { width: 24, height: 24 }, Flex::NONE)
+//!     .child(Control::Forward, Size { width: 24, height: 24 }, Flex::NONE)
+//!     .child(Control::AddressBar, Size { width: 200, height: 28 }, flex(1))
+//!     .layout(Rect { x: 0, y: 0, width: window_width, height: 40 });
+//! ```
+
+use cef_sys::{cef_box_layout_settings_t, cef_cross_axis_alignment_t, cef_main_axis_alignment_t};
+
+use crate::{
+    values::{Insets, Rect, Size},
+    views::View,
+};
+
+/// How children are positioned along the main axis when none of them flex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MainAxisAlignment {
+    Start,
+    Center,
+    End,
+}
+
+/// How children are positioned across the main axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CrossAxisAlignment {
+    /// Children fill the available space.
+    Stretch,
+    Start,
+    Center,
+    End,
+}
+
+/// The share of the free space along the main axis a child takes, relative
+/// to the other children. Children with [Flex::NONE] keep their preferred
+/// size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Flex(pub u32);
+
+impl Flex {
+    pub const NONE: Flex = Flex(0);
+}
+
+/// Shorthand for `Flex(weight)`.
+pub fn flex(weight: u32) -> Flex {
+    Flex(weight)
+}
+
+/// Starts a layout that places children left to right.
+pub fn row<K>() -> BoxLayout<K> {
+    BoxLayout::new(true)
+}
+
+/// Starts a layout that places children top to bottom.
+pub fn column<K>() -> BoxLayout<K> {
+    BoxLayout::new(false)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Child<K> {
+    key: K,
+    preferred_size: Size,
+    flex: Option<Flex>,
+}
+
+/// A row or column of children identified by keys of type `K`. See the
+/// [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoxLayout<K> {
+    horizontal: bool,
+    insets: Insets,
+    gap: i32,
+    main_axis_alignment: MainAxisAlignment,
+    cross_axis_alignment: CrossAxisAlignment,
+    minimum_cross_axis_size: i32,
+    default_flex: Flex,
+    children: Vec<Child<K>>,
+}
+
+impl<K> BoxLayout<K> {
+    fn new(horizontal: bool) -> BoxLayout<K> {
+        BoxLayout {
+            horizontal,
+            insets: Insets::default(),
+            gap: 0,
+            main_axis_alignment: MainAxisAlignment::Start,
+            cross_axis_alignment: CrossAxisAlignment::Stretch,
+            minimum_cross_axis_size: 0,
+            default_flex: Flex::NONE,
+            children: Vec::new(),
+        }
+    }
+    /// Sets the space between the border and the children.
+    pub fn insets(mut self, insets: Insets) -> Self {
+        self.insets = insets;
+        self
+    }
+    /// Sets the space between children.
+    pub fn gap(mut self, gap: i32) -> Self {
+        self.gap = gap.max(0);
+        self
+    }
+    pub fn main_axis_alignment(mut self, alignment: MainAxisAlignment) -> Self {
+        self.main_axis_alignment = alignment;
+        self
+    }
+    pub fn cross_axis_alignment(mut self, alignment: CrossAxisAlignment) -> Self {
+        self.cross_axis_alignment = alignment;
+        self
+    }
+    /// Sets the minimum size across the main axis of the layout's preferred
+    /// size.
+    pub fn minimum_cross_axis_size(mut self, size: i32) -> Self {
+        self.minimum_cross_axis_size = size.max(0);
+        self
+    }
+    /// Sets the flex of children added with [BoxLayout::child_default_flex].
+    pub fn default_flex(mut self, flex: Flex) -> Self {
+        self.default_flex = flex;
+        self
+    }
+    /// Adds a child of `preferred_size` that takes `flex` of the free space.
+    pub fn child(mut self, key: K, preferred_size: Size, flex: Flex) -> Self {
+        self.children.push(Child {
+            key,
+            preferred_size,
+            flex: Some(flex),
+        });
+        self
+    }
+    /// Adds a child of `preferred_size` with the layout's default flex.
+    pub fn child_default_flex(mut self, key: K, preferred_size: Size) -> Self {
+        self.children.push(Child {
+            key,
+            preferred_size,
+            flex: None,
+        });
+        self
+    }
+
+    /// Returns the settings of a views `BoxLayout` with the same
+    /// arrangement. Per-child flex is set with
+    /// `cef_box_layout_t::set_flex_for_view`; see [BoxLayout::flex_of].
+    pub fn to_cef_settings(&self) -> cef_box_layout_settings_t {
+        cef_box_layout_settings_t {
+            horizontal: self.horizontal as _,
+            inside_border_horizontal_spacing: 0,
+            inside_border_vertical_spacing: 0,
+            inside_border_insets: self.insets.into(),
+            between_child_spacing: self.gap,
+            main_axis_alignment: match self.main_axis_alignment {
+                MainAxisAlignment::Start => cef_main_axis_alignment_t::CEF_MAIN_AXIS_ALIGNMENT_START,
+                MainAxisAlignment::Center => cef_main_axis_alignment_t::CEF_MAIN_AXIS_ALIGNMENT_CENTER,
+                MainAxisAlignment::End => cef_main_axis_alignment_t::CEF_MAIN_AXIS_ALIGNMENT_END,
+            },
+            cross_axis_alignment: match self.cross_axis_alignment {
+                CrossAxisAlignment::Stretch => cef_cross_axis_alignment_t::CEF_CROSS_AXIS_ALIGNMENT_STRETCH,
+                CrossAxisAlignment::Start => cef_cross_axis_alignment_t::CEF_CROSS_AXIS_ALIGNMENT_START,
+                CrossAxisAlignment::Center => cef_cross_axis_alignment_t::CEF_CROSS_AXIS_ALIGNMENT_CENTER,
+                CrossAxisAlignment::End => cef_cross_axis_alignment_t::CEF_CROSS_AXIS_ALIGNMENT_END,
+            },
+            minimum_cross_axis_size: self.minimum_cross_axis_size,
+            default_flex: self.default_flex.0 as _,
+        }
+    }
+    /// Returns the keys of the children, in the order they were added.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.children.iter().map(|child| &child.key)
+    }
+    /// Returns the flex of the child at `index`.
+    pub fn flex_of(&self, index: usize) -> Option<Flex> {
+        self.children.get(index).map(|child| child.flex.unwrap_or(self.default_flex))
+    }
+
+    /// Returns the size the layout needs to show every child at its
+    /// preferred size.
+    pub fn preferred_size(&self) -> Size {
+        let main: i32 = self.children.iter().map(|child| self.main(child.preferred_size)).sum::<i32>() + self.gaps();
+        let cross = self
+            .children
+            .iter()
+            .map(|child| self.cross(child.preferred_size))
+            .max()
+            .unwrap_or(0)
+            .max(self.minimum_cross_axis_size);
+        let (width, height) = if self.horizontal { (main, cross) } else { (cross, main) };
+        Size {
+            width: width + self.insets.left + self.insets.right,
+            height: height + self.insets.top + self.insets.bottom,
+        }
+    }
+
+    /// Arranges the children within `bounds` and returns their bounds, in the
+    /// order they were added.
+    pub fn layout(&self, bounds: Rect) -> Vec<(K, Rect)>
+    where
+        K: Clone,
+    {
+        let content = Rect {
+            x: bounds.x + self.insets.left,
+            y: bounds.y + self.insets.top,
+            width: (bounds.width - self.insets.left - self.insets.right).max(0),
+            height: (bounds.height - self.insets.top - self.insets.bottom).max(0),
+        };
+        let (main_start, main_size, cross_start, cross_size) = if self.horizontal {
+            (content.x, content.width, content.y, content.height)
+        } else {
+            (content.y, content.height, content.x, content.width)
+        };
+
+        let mut sizes: Vec<i32> = self.children.iter().map(|child| self.main(child.preferred_size)).collect();
+        let free = main_size - sizes.iter().sum::<i32>() - self.gaps();
+        let flexes: Vec<u32> = (0..self.children.len()).map(|index| self.flex_of(index).unwrap_or(Flex::NONE).0).collect();
+        let total_flex: u32 = flexes.iter().sum();
+        let mut offset = 0;
+        if total_flex > 0 {
+            // Hand out the free space, or take away the missing space, in
+            // proportion to the flex. The last flexing child gets the rest
+            // lost to rounding.
+            let mut remaining = free;
+            let mut remaining_flex = total_flex;
+            for (size, &child_flex) in sizes.iter_mut().zip(&flexes) {
+                if child_flex == 0 {
+                    continue;
+                }
+                let share = if remaining_flex == child_flex {
+                    remaining
+                } else {
+                    (i64::from(free) * i64::from(child_flex) / i64::from(total_flex)) as i32
+                };
+                remaining -= share;
+                remaining_flex -= child_flex;
+                *size = (*size + share).max(0);
+            }
+        } else if free > 0 {
+            offset = match self.main_axis_alignment {
+                MainAxisAlignment::Start => 0,
+                MainAxisAlignment::Center => free / 2,
+                MainAxisAlignment::End => free,
+            };
+        }
+
+        let mut position = main_start + offset;
+        let mut result = Vec::with_capacity(self.children.len());
+        for (child, size) in self.children.iter().zip(sizes) {
+            let preferred_cross = self.cross(child.preferred_size).min(cross_size);
+            let (cross_position, cross_extent) = match self.cross_axis_alignment {
+                CrossAxisAlignment::Stretch => (cross_start, cross_size),
+                CrossAxisAlignment::Start => (cross_start, preferred_cross),
+                CrossAxisAlignment::Center => (cross_start + (cross_size - preferred_cross) / 2, preferred_cross),
+                CrossAxisAlignment::End => (cross_start + cross_size - preferred_cross, preferred_cross),
+            };
+            let rect = if self.horizontal {
+                Rect { x: position, y: cross_position, width: size, height: cross_extent }
+            } else {
+                Rect { x: cross_position, y: position, width: cross_extent, height: size }
+            };
+            result.push((child.key.clone(), rect));
+            position += size + self.gap;
+        }
+        result
+    }
+
+    fn main(&self, size: Size) -> i32 {
+        if self.horizontal { size.width } else { size.height }
+    }
+    fn cross(&self, size: Size) -> i32 {
+        if self.horizontal { size.height } else { size.width }
+    }
+    fn gaps(&self) -> i32 {
+        self.gap * (self.children.len() as i32 - 1).max(0)
+    }
+}
+
+impl BoxLayout<View> {
+    /// Adds `view` as a child that takes `flex` of the free space, at its
+    /// current preferred size.
+    pub fn child_view(self, view: &View, flex: Flex) -> Self {
+        let preferred_size = view.get_preferred_size();
+        self.child(view.clone(), preferred_size, flex)
+    }
+}
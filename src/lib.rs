@@ -8,31 +8,59 @@ pub mod helper_traits;
 mod ptr_hash;
 mod misc_fns;
 pub use misc_fns::*;
+pub mod version;
+pub mod error;
+pub use version::{supports, version, Capability, VersionInfo};
 
 pub mod accessibility_handler;
+pub mod accessibility_snapshot;
 pub mod string;
 pub mod multimap;
 pub mod values;
 pub mod scheme_registrar;
 pub mod resource_bundle;
 pub mod resource_bundle_handler;
+pub mod resource_locator;
 pub mod browser_process_handler;
 pub mod browser;
 pub mod browser_host;
+pub mod browser_startup;
+pub mod browser_store;
 pub mod frame;
+pub mod frame_selector;
+pub mod frame_tree;
+pub mod gestures;
+pub mod hit_test;
 pub mod load_handler;
 pub mod registration;
 pub mod render_process_handler;
+pub mod display_media;
 pub mod dom;
 pub mod v8context;
 pub mod process;
+pub mod process_monitor;
 pub mod request;
 pub mod response;
 pub mod url_request;
+pub mod net;
+#[cfg(feature = "http")] pub mod http_interop;
+pub mod replay_request;
+pub mod async_resource_handler;
+pub mod proxy_scheme_handler;
+pub mod server;
+pub mod local_bridge;
+pub mod local_content;
+pub mod load_html;
+pub mod header_injector;
+pub mod origin_auth;
 pub mod request_context;
+pub mod profile_manager;
 pub mod web_plugin;
 pub mod cookie;
 pub mod devtools_message_observer;
+pub mod devtools;
+pub mod devtools_dock;
+pub mod network_log;
 pub mod callback;
 pub mod resource_request_handler;
 pub mod client;
@@ -40,27 +68,65 @@ pub mod image;
 
 pub mod command_line;
 pub mod app;
+pub mod address_bar;
+pub mod app_badge;
 
 pub mod sandbox;
 mod main_args;
+pub mod media_devices;
 pub mod media_router;
+pub mod message_pump;
 pub mod scheme;
 pub mod settings;
+pub mod i18n;
+pub mod gpu;
 pub mod color;
 pub mod events;
 pub mod drag;
 pub mod file_dialog;
+pub mod pdf;
+pub mod pointer_lock;
 pub mod printing;
+pub mod privacy;
+pub mod print_interceptor;
+pub mod form_filler;
+pub mod forms_policy;
+pub mod screenshot;
+pub mod speech_synthesis;
+pub mod text_editing;
+#[cfg(target_os = "linux")] pub mod print_handler;
+pub mod controls;
+pub mod display;
+pub mod layout;
+pub mod views;
 pub mod window;
 pub mod x509_certificate;
 pub mod ime;
+pub mod keyboard_lock;
 pub mod navigation;
+pub mod notifications;
 pub mod extension;
 pub mod stream;
 pub mod ssl;
+pub mod storage_manager;
 pub mod task;
+pub mod shutdown;
+pub mod timer;
+pub mod kiosk;
+pub mod modal_dialog;
+pub mod tab_strip;
+pub mod throttling;
+pub mod traffic_meter;
+pub mod user_activity;
 pub mod logging;
+pub mod debug_snapshot;
+pub mod deploy;
+pub mod device_policy;
+#[cfg(feature = "lol_html")] pub mod html_rewriter;
 mod send_protector;
+mod js_util;
+mod url_pattern;
+pub mod user_scripts;
 #[cfg(target_os = "macos")] mod framework_loader_macos;
 #[cfg(target_os = "macos")] pub use framework_loader_macos::load_framework;
 
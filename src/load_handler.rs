@@ -7,6 +7,7 @@ use bitflags::bitflags;
 use crate::{
     browser::Browser,
     frame::Frame,
+    frame_selector,
     refcounted::{RefCountedPtr, Wrapper},
     string::CefString,
 };
@@ -73,6 +74,20 @@ pub enum TransitionType {
     Reload(TransitionTypeQualifiers),
 }
 
+impl TransitionType {
+    /// Returns the qualifiers of the transition.
+    pub fn qualifiers(&self) -> TransitionTypeQualifiers {
+        match *self {
+            TransitionType::Link(qualifiers)
+            | TransitionType::Explicit(qualifiers)
+            | TransitionType::AutoSubframe(qualifiers)
+            | TransitionType::ManualSubframe(qualifiers)
+            | TransitionType::FormSubmit(qualifiers)
+            | TransitionType::Reload(qualifiers) => qualifiers,
+        }
+    }
+}
+
 impl TryFrom<crate::CEnumType> for TransitionType {
     type Error = ();
     fn try_from(value: crate::CEnumType) -> Result<Self, Self::Error> {
@@ -1127,6 +1142,7 @@ cef_callback_impl! {
             frame: Frame: *mut cef_frame_t,
             transition_type: TransitionType: cef_transition_type_t,
         ) {
+            frame_selector::frame_load_started(&browser, &frame);
             self.delegate.on_load_start(
                 browser,
                 frame,
@@ -0,0 +1,144 @@
+//! Loading generated HTML without a scheme handler.
+//!
+//! Small documents are loaded from a `data:` URL. Documents that are too large
+//! for a URL, or that should appear under a specific URL, are served once by a
+//! scheme handler factory registered for the scheme and host of that URL in the
+//! browser's request context. The factory only answers the exact URL it was
+//! given, so other requests to the same host go to the network as usual.
+
+use crate::{
+    async_resource_handler::{AsyncResourceHandler, BytesBody, HttpResponseParts},
+    browser::Browser,
+    frame::Frame,
+    request::Request,
+    scheme::{SchemeHandlerFactory, SchemeHandlerFactoryCallbacks},
+    url_request::ResourceHandler,
+};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// The longest URL Chromium accepts.
+pub const MAX_URL_LENGTH: usize = 2 * 1024 * 1024;
+
+const DATA_URL_PREFIX: &str = "data:text/html;charset=utf-8;base64,";
+
+/// The host of the URLs generated for documents loaded without a fake URL.
+const GENERATED_HOST: &str = "load-html.invalid";
+
+lazy_static::lazy_static! {
+    /// Documents waiting to be served, by [pending_key] of their URL.
+    static ref PENDING: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+    /// The scheme and host pairs a factory was registered for, per request
+    /// context cache path.
+    static ref REGISTERED: Mutex<HashSet<(Option<String>, String, String)>> = Mutex::new(HashSet::new());
+}
+
+/// Returns `html` as a base64 `data:` URL, or `None` if the URL would exceed
+/// [MAX_URL_LENGTH].
+pub fn html_data_url(html: &str) -> Option<String> {
+    if DATA_URL_PREFIX.len() + (html.len() + 2) / 3 * 4 > MAX_URL_LENGTH {
+        return None;
+    }
+    let mut url = String::from(DATA_URL_PREFIX);
+    base64_encode(html.as_bytes(), &mut url);
+    Some(url)
+}
+
+pub(crate) fn load_html(browser: &Browser, html: &str, fake_url: Option<&str>) {
+    let frame = browser.get_main_frame();
+    match fake_url {
+        None => match html_data_url(html) {
+            Some(url) => frame.load_url(&url),
+            None => {
+                let url = format!("http://{}/{}.html", GENERATED_HOST, Uuid::new_v4().to_simple());
+                serve_once(browser, &frame, html, &url);
+            }
+        },
+        Some(url) => serve_once(browser, &frame, html, url),
+    }
+}
+
+fn serve_once(browser: &Browser, frame: &Frame, html: &str, url: &str) {
+    let ((scheme, host), key) = match split_origin(url).zip(pending_key(url)) {
+        Some(parts) => parts,
+        None => {
+            log::error!("load_html: unsupported URL {}", url);
+            return;
+        }
+    };
+    let context = browser.get_host().get_request_context();
+    let registration = (context.get_cache_path(), scheme.clone(), host.clone());
+    if REGISTERED.lock().insert(registration) {
+        let factory = SchemeHandlerFactory::new(OneShotFactory);
+        context.register_scheme_handler_factory(&scheme, Some(&host), factory);
+    }
+    PENDING.lock().insert(key, html.to_owned());
+    frame.load_url(url);
+}
+
+/// Returns the lowercase scheme and host of `url`.
+fn split_origin(url: &str) -> Option<(String, String)> {
+    let scheme_end = url.find("://")?;
+    let rest = &url[scheme_end + 3..];
+    let host_end = rest.find(|c| c == '/' || c == '?' || c == '#').unwrap_or(rest.len());
+    let authority = &rest[..host_end];
+    let host = authority.rsplit('@').next()?;
+    let host = match host.rfind(':') {
+        Some(colon) if !host.ends_with(']') => &host[..colon],
+        _ => host,
+    };
+    if host.is_empty() {
+        return None;
+    }
+    Some((url[..scheme_end].to_ascii_lowercase(), host.to_ascii_lowercase()))
+}
+
+/// Returns the key `url` is served under. Chromium canonicalizes the URL
+/// before requesting it, so it is reduced to the parts that survive that: the
+/// UUID token for generated URLs, and the lowercase scheme and host with the
+/// path otherwise.
+fn pending_key(url: &str) -> Option<String> {
+    let (scheme, host) = split_origin(url)?;
+    let rest = &url[url.find("://")? + 3..];
+    let path = &rest[rest.find(|c| c == '/' || c == '?' || c == '#').unwrap_or(rest.len())..];
+    let path = match &path[..path.find(|c| c == '?' || c == '#').unwrap_or(path.len())] {
+        "" => "/",
+        path => path,
+    };
+    if host == GENERATED_HOST {
+        let token = path.trim_start_matches('/').trim_end_matches(".html");
+        return Some(token.to_ascii_lowercase());
+    }
+    Some(format!("{}://{}{}", scheme, host, path))
+}
+
+struct OneShotFactory;
+
+impl SchemeHandlerFactoryCallbacks for OneShotFactory {
+    fn create(
+        &self,
+        browser: Browser,
+        frame: Frame,
+        scheme_name: &str,
+        request: Request,
+    ) -> Option<ResourceHandler> {
+        let key = pending_key(&request.get_url())?;
+        let html = PENDING.lock().remove(&key)?;
+        Some(AsyncResourceHandler::new(move |_request| async move {
+            Ok((HttpResponseParts::new(200, "text/html").charset("utf-8"), BytesBody::new(html)))
+        }))
+    }
+}
+
+pub(crate) fn base64_encode(bytes: &[u8], out: &mut String) {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        out.push(ALPHABET[(n >> 18) as usize & 63] as char);
+        out.push(ALPHABET[(n >> 12) as usize & 63] as char);
+        out.push(if chunk.len() > 1 { ALPHABET[(n >> 6) as usize & 63] as char } else { '=' });
+        out.push(if chunk.len() > 2 { ALPHABET[n as usize & 63] as char } else { '=' });
+    }
+}
@@ -0,0 +1,253 @@
+//! A WebSocket endpoint for page JavaScript that is handled in Rust.
+//!
+//! Process messages need a render process handler and a V8 binding on the
+//! page side. [LocalBridge] is an alternative that works with standard web
+//! tooling: the page opens a plain `WebSocket` and the messages land in
+//! [LocalBridgeCallbacks] in the browser process.
+//!
+//! CEF can't hand WebSocket upgrades to scheme handlers, so the bridge can't
+//! live on a custom scheme such as `wss://local-bridge`. Instead it runs a
+//! [Server] on the loopback interface and hands out a URL containing a random
+//! token. Only clients that know the token, and optionally only pages from
+//! the allowed origins, can connect:
+//!
+//! ```ignore
+//! let bridge = LocalBridge::start(LocalBridgeSettings::new().allow_origin("app://main"), MyBridge);
+//! // Once `on_started` was called, pass `bridge.url()` to the page, e.g. with a user script.
+//! ```
+//!
+//! Messages are UTF-8 text frames in both directions.
+
+use crate::{
+    callback::Callback,
+    request::Request,
+    server::{Server, ServerHandler, ServerHandlerCallbacks},
+};
+use parking_lot::Mutex;
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+use uuid::Uuid;
+
+/// Configuration of a [LocalBridge].
+#[derive(Debug, Clone)]
+pub struct LocalBridgeSettings {
+    /// The loopback address to listen on.
+    pub address: String,
+    /// The port to listen on. Must be between 1025 and 65535.
+    pub port: u16,
+    /// The origins allowed to connect, e.g. `app://main`. If empty, any page
+    /// that knows the bridge URL may connect.
+    pub allowed_origins: Vec<String>,
+    /// The maximum number of pending connections.
+    pub backlog: i32,
+}
+
+impl LocalBridgeSettings {
+    pub fn new() -> LocalBridgeSettings {
+        LocalBridgeSettings::default()
+    }
+    pub fn address(mut self, address: &str) -> Self {
+        self.address = address.to_owned();
+        self
+    }
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        self.allowed_origins.push(origin.to_owned());
+        self
+    }
+    pub fn backlog(mut self, backlog: i32) -> Self {
+        self.backlog = backlog;
+        self
+    }
+}
+
+impl Default for LocalBridgeSettings {
+    fn default() -> Self {
+        LocalBridgeSettings {
+            address: "127.0.0.1".to_owned(),
+            port: 47_100,
+            allowed_origins: Vec::new(),
+            backlog: 10,
+        }
+    }
+}
+
+/// Handles the connections of a [LocalBridge]. The functions of this trait are
+/// called on the dedicated server thread.
+pub trait LocalBridgeCallbacks: 'static + Send + Sync {
+    /// Called once the bridge is listening. `url` is the address pages connect
+    /// to, also available from [LocalBridge::url].
+    fn on_started(&self, bridge: &LocalBridge, url: &str) {}
+    /// Called if the server could not be started, e.g. because the port is in
+    /// use.
+    fn on_start_failed(&self, bridge: &LocalBridge) {}
+    /// Called when a page has connected. `origin` is the value of the
+    /// `Origin` header of the WebSocket request.
+    fn on_open(&self, bridge: &LocalBridge, connection_id: i32, origin: Option<&str>) {}
+    /// Called for every message sent by the page.
+    fn on_message(&self, bridge: &LocalBridge, connection_id: i32, message: &str);
+    /// Called when a connection has closed.
+    fn on_close(&self, bridge: &LocalBridge, connection_id: i32) {}
+}
+
+struct BridgeState {
+    token: String,
+    settings: LocalBridgeSettings,
+    server: Mutex<Option<Server>>,
+    url: Mutex<Option<String>>,
+    /// Accepted requests whose WebSocket isn't connected yet, with their origin.
+    pending: Mutex<HashMap<i32, Option<String>>>,
+    connections: Mutex<HashSet<i32>>,
+}
+
+/// A running WebSocket bridge. See the [module documentation](self). Cloning
+/// the bridge is cheap and all clones refer to the same server.
+#[derive(Clone)]
+pub struct LocalBridge(Arc<BridgeState>);
+
+impl LocalBridge {
+    /// Starts the bridge server. The server is created asynchronously;
+    /// [LocalBridgeCallbacks::on_started] is called once it is listening.
+    pub fn start<C: LocalBridgeCallbacks>(settings: LocalBridgeSettings, callbacks: C) -> LocalBridge {
+        let bridge = LocalBridge(Arc::new(BridgeState {
+            token: Uuid::new_v4().to_simple().to_string(),
+            settings,
+            server: Mutex::new(None),
+            url: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+            connections: Mutex::new(HashSet::new()),
+        }));
+        let settings = &bridge.0.settings;
+        Server::create(
+            &settings.address,
+            settings.port,
+            settings.backlog,
+            ServerHandler::new(BridgeHandler {
+                bridge: bridge.clone(),
+                callbacks: Box::new(callbacks),
+            }),
+        );
+        bridge
+    }
+    /// Returns the `ws://` URL pages connect to, or `None` if the server isn't
+    /// running.
+    pub fn url(&self) -> Option<String> {
+        self.0.url.lock().clone()
+    }
+    /// Returns the identifiers of the open connections.
+    pub fn connections(&self) -> Vec<i32> {
+        self.0.connections.lock().iter().cloned().collect()
+    }
+    /// Sends `message` to the connection `connection_id`.
+    pub fn send(&self, connection_id: i32, message: &str) {
+        if !self.0.connections.lock().contains(&connection_id) {
+            return;
+        }
+        if let Some(server) = &*self.0.server.lock() {
+            server.send_web_socket_message(connection_id, message.as_bytes());
+        }
+    }
+    /// Sends `message` to every open connection.
+    pub fn broadcast(&self, message: &str) {
+        for connection_id in self.connections() {
+            self.send(connection_id, message);
+        }
+    }
+    /// Closes the connection `connection_id`.
+    pub fn close(&self, connection_id: i32) {
+        if let Some(server) = &*self.0.server.lock() {
+            server.close_connection(connection_id);
+        }
+    }
+    /// Stops the server, closing all connections.
+    pub fn shutdown(&self) {
+        if let Some(server) = &*self.0.server.lock() {
+            server.shutdown();
+        }
+    }
+
+    fn accepts(&self, request: &Request) -> Option<Option<String>> {
+        let url = request.get_url();
+        let path = url.splitn(4, '/').nth(3).unwrap_or("");
+        if path.split(|c| c == '?' || c == '#').next() != Some(&*self.0.token) {
+            return None;
+        }
+        let origin = request.get_header_by_name("Origin").filter(|origin| !origin.is_empty());
+        let allowed = &self.0.settings.allowed_origins;
+        if !allowed.is_empty() && !origin.as_ref().map(|origin| allowed.contains(origin)).unwrap_or(false) {
+            return None;
+        }
+        Some(origin)
+    }
+}
+
+struct BridgeHandler {
+    bridge: LocalBridge,
+    callbacks: Box<dyn LocalBridgeCallbacks>,
+}
+
+impl ServerHandlerCallbacks for BridgeHandler {
+    fn on_server_created(&self, server: Server) {
+        if !server.is_running() {
+            log::error!("local bridge failed to listen on port {}", self.bridge.0.settings.port);
+            self.callbacks.on_start_failed(&self.bridge);
+            return;
+        }
+        let url = format!("ws://{}/{}", server.get_address(), self.bridge.0.token);
+        *self.bridge.0.server.lock() = Some(server);
+        *self.bridge.0.url.lock() = Some(url.clone());
+        self.callbacks.on_started(&self.bridge, &url);
+    }
+    fn on_server_destroyed(&self, server: Server) {
+        // Drop the server to break the reference cycle through this handler.
+        *self.bridge.0.server.lock() = None;
+        *self.bridge.0.url.lock() = None;
+        let connections = self.bridge.0.connections.lock().drain().collect::<Vec<_>>();
+        for connection_id in connections {
+            self.callbacks.on_close(&self.bridge, connection_id);
+        }
+    }
+    fn on_client_disconnected(&self, server: Server, connection_id: i32) {
+        self.bridge.0.pending.lock().remove(&connection_id);
+        if self.bridge.0.connections.lock().remove(&connection_id) {
+            self.callbacks.on_close(&self.bridge, connection_id);
+        }
+    }
+    fn on_http_request(&self, server: Server, connection_id: i32, client_address: &str, request: Request) {
+        server.send_http404_response(connection_id);
+    }
+    fn on_web_socket_request(
+        &self,
+        server: Server,
+        connection_id: i32,
+        client_address: &str,
+        request: Request,
+        callback: Callback,
+    ) {
+        match self.bridge.accepts(&request) {
+            Some(origin) => {
+                self.bridge.0.pending.lock().insert(connection_id, origin);
+                callback.cont();
+            }
+            None => {
+                log::warn!("local bridge rejected a WebSocket connection from {}", client_address);
+                callback.cancel();
+            }
+        }
+    }
+    fn on_web_socket_connected(&self, server: Server, connection_id: i32) {
+        let origin = match self.bridge.0.pending.lock().remove(&connection_id) {
+            Some(origin) => origin,
+            None => return,
+        };
+        self.bridge.0.connections.lock().insert(connection_id);
+        self.callbacks.on_open(&self.bridge, connection_id, origin.as_ref().map(|s| &**s));
+    }
+    fn on_web_socket_message(&self, server: Server, connection_id: i32, data: &[u8]) {
+        if self.bridge.0.connections.lock().contains(&connection_id) {
+            self.callbacks.on_message(&self.bridge, connection_id, &String::from_utf8_lossy(data));
+        }
+    }
+}
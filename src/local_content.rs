@@ -0,0 +1,180 @@
+//! Loading local HTML from `file://` URLs.
+//!
+//! By default Chromium treats every `file://` URL as a unique origin, so pages
+//! loaded from disk can't `fetch` or `XMLHttpRequest` their neighbouring files
+//! and can't access `http(s)` resources without CORS headers. Two browser
+//! settings relax this:
+//!
+//! * [BrowserSettings::file_access_from_file_urls] lets `file://` pages read
+//!   other `file://` URLs. The equivalent command line switch is
+//!   `--allow-file-access-from-files`.
+//! * [BrowserSettings::universal_access_from_file_urls] additionally lets them
+//!   read any URL regardless of origin. There is no separate switch for this;
+//!   `--disable-web-security` has the same effect but applies to all pages.
+//!
+//! Both settings grant access to the whole file system. [LocalContentPolicy]
+//! enables them together with a check that only lets `file://` URLs below a set
+//! of allowed directories load at all:
+//!
+//! ```ignore
+//! let policy = LocalContentPolicy::new().allow_directory(app_dir.join("ui")).file_access_from_files(true);
+//! policy.apply_to_browser_settings(&mut browser_settings);
+//! // and return `ResourceRequestHandler::new(policy.clone())` from
+//! // `RequestHandlerCallbacks::get_resource_request_handler`.
+//! ```
+//!
+//! [BrowserSettings::file_access_from_file_urls]: crate::browser::BrowserSettings::file_access_from_file_urls
+//! [BrowserSettings::universal_access_from_file_urls]: crate::browser::BrowserSettings::universal_access_from_file_urls
+
+use crate::{
+    browser::{Browser, BrowserSettings, State},
+    command_line::CommandLine,
+    frame::Frame,
+    request::Request,
+    resource_request_handler::ResourceRequestHandlerCallbacks,
+    url_request::RequestCallback,
+    ReturnValue,
+};
+use std::path::{Path, PathBuf};
+
+/// The command line switch equivalent to
+/// [BrowserSettings::file_access_from_file_urls](crate::browser::BrowserSettings::file_access_from_file_urls).
+pub const ALLOW_FILE_ACCESS_FROM_FILES_SWITCH: &str = "allow-file-access-from-files";
+
+/// Restricts which directories may be loaded through `file://` URLs and
+/// configures the related browser settings. See the
+/// [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct LocalContentPolicy {
+    /// The directories `file://` URLs may point into. If empty, no `file://`
+    /// URL is allowed.
+    pub allowed_directories: Vec<PathBuf>,
+    /// Let `file://` pages read other `file://` URLs.
+    pub file_access_from_files: bool,
+    /// Let `file://` pages read any URL. Implies `file_access_from_files`.
+    pub universal_access_from_files: bool,
+}
+
+impl LocalContentPolicy {
+    pub fn new() -> LocalContentPolicy {
+        LocalContentPolicy::default()
+    }
+    pub fn allow_directory<T: Into<PathBuf>>(mut self, directory: T) -> Self {
+        let directory = directory.into();
+        // Canonicalize once so symlinks and `..` in URLs can't escape the directory.
+        let directory = dunce::canonicalize(&directory).unwrap_or(directory);
+        self.allowed_directories.push(directory);
+        self
+    }
+    pub fn file_access_from_files(mut self, allow: bool) -> Self {
+        self.file_access_from_files = allow;
+        self
+    }
+    pub fn universal_access_from_files(mut self, allow: bool) -> Self {
+        self.universal_access_from_files = allow;
+        self
+    }
+
+    /// Sets the file access settings of `settings` according to this policy.
+    pub fn apply_to_browser_settings(&self, settings: &mut BrowserSettings) {
+        let state = |allow| if allow { State::Enabled } else { State::Disabled };
+        settings.file_access_from_file_urls = state(self.file_access_from_files || self.universal_access_from_files);
+        settings.universal_access_from_file_urls = state(self.universal_access_from_files);
+    }
+    /// Appends the switch for `file_access_from_files` to `command_line`, for
+    /// browsers whose settings can't be changed, e.g. DevTools windows. Call
+    /// this from [AppCallbacks::on_before_command_line_processing](crate::app::AppCallbacks::on_before_command_line_processing).
+    pub fn apply_to_command_line(&self, command_line: &CommandLine) {
+        if self.file_access_from_files || self.universal_access_from_files {
+            command_line.append_switch(ALLOW_FILE_ACCESS_FROM_FILES_SWITCH);
+        }
+    }
+    /// Returns true if `url` may be loaded. URLs with schemes other than
+    /// `file` are always allowed.
+    pub fn is_allowed_url(&self, url: &str) -> bool {
+        match file_url_to_path(url) {
+            Some(path) => self.is_allowed_path(&path),
+            None => !url.get(..5).map(|scheme| scheme.eq_ignore_ascii_case("file:")).unwrap_or(false),
+        }
+    }
+    /// Returns true if `path` is inside one of the allowed directories.
+    pub fn is_allowed_path(&self, path: &Path) -> bool {
+        // Files that don't exist can't be loaded anyway, but resolving them
+        // would fail, so check the normalized path instead.
+        let path = dunce::canonicalize(path).unwrap_or_else(|_| normalize(path));
+        self.allowed_directories.iter().any(|directory| path.starts_with(directory))
+    }
+}
+
+impl ResourceRequestHandlerCallbacks for LocalContentPolicy {
+    fn on_before_resource_load(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        callback: RequestCallback,
+    ) -> ReturnValue {
+        let url = request.get_url();
+        if self.is_allowed_url(&url) {
+            ReturnValue::Continue
+        } else {
+            log::warn!("blocked file URL outside of the allowed directories: {}", url);
+            ReturnValue::Cancel
+        }
+    }
+}
+
+/// Converts a `file://` URL to a path. Returns `None` for other URLs and for
+/// URLs that can't be decoded.
+fn file_url_to_path(url: &str) -> Option<PathBuf> {
+    if !url.get(..7)?.eq_ignore_ascii_case("file://") {
+        return None;
+    }
+    let rest = &url[7..];
+    let rest = &rest[..rest.find(|c| c == '?' || c == '#').unwrap_or(rest.len())];
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = String::from_utf8(percent_decode(path)?).ok()?;
+    if cfg!(windows) {
+        // file:///C:/dir and file://server/share/dir
+        match host {
+            "" | "localhost" => Some(PathBuf::from(path.trim_start_matches('/'))),
+            host => Some(PathBuf::from(format!(r"\\{}{}", host, path))),
+        }
+    } else {
+        match host {
+            "" | "localhost" => Some(PathBuf::from(path)),
+            _ => None,
+        }
+    }
+}
+
+fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(decoded)
+}
+
+/// Removes `.` and `..` components without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => { normalized.pop(); },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,339 @@
+//! Choosing the cameras and microphones pages get.
+//!
+//! CEF 85 has no permission handler: with
+//! [Settings::enable_media_stream](crate::settings::Settings::enable_media_stream)
+//! every `getUserMedia()` request is granted and Chromium picks the system's
+//! default devices. This module gives the application that choice back:
+//!
+//! * [enumerate_media_devices] lists the devices a browser sees.
+//! * [MediaDevicePolicy::enforce] routes every `getUserMedia()` call of a
+//!   browser through the policy, which can force a specific device or block
+//!   the request.
+//! * [FakeMediaDevices] replaces real devices with Chromium's test devices or
+//!   with media files, through
+//!   [Settings::fake_media_devices](crate::settings::Settings::fake_media_devices).
+//!
+//! ```ignore
+//! let devices = block_on(enumerate_media_devices(&browser))?;
+//! let policy = MediaDevicePolicy::new().camera(DeviceSelector::Label("Logitech".to_owned()));
+//! let guard = policy.enforce(&browser.get_host());
+//! ```
+//!
+//! Device labels are only reported once the page was granted media access,
+//! which with "enable-media-stream" is always the case.
+
+use crate::{
+    browser::Browser,
+    browser_host::BrowserHost,
+    devtools::{DevToolsError, DevToolsSession},
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+const ENUMERATE_EXPRESSION: &str = "navigator.mediaDevices.enumerateDevices().then(function(devices) {\n\
+  return devices.map(function(d) { return { deviceId: d.deviceId, groupId: d.groupId, kind: d.kind, label: d.label }; });\n\
+})";
+
+const POLICY_SOURCE: &str = "\
+var devices = navigator.mediaDevices;\n\
+if (!devices || devices.__cefMediaPolicy) return;\n\
+var policy = __POLICY__;\n\
+var getUserMedia = devices.getUserMedia.bind(devices);\n\
+var enumerate = devices.enumerateDevices.bind(devices);\n\
+function error(name, message) { return new DOMException(message, name); }\n\
+function select(selector, kind, list) {\n\
+  var found = list.filter(function(d) {\n\
+    if (d.kind !== kind) return false;\n\
+    if (selector.id !== undefined) return d.deviceId === selector.id;\n\
+    return d.label.toLowerCase().indexOf(selector.label.toLowerCase()) >= 0;\n\
+  })[0];\n\
+  return found ? found.deviceId : null;\n\
+}\n\
+function route(requested, selector, kind, list) {\n\
+  if (!requested || !selector) return requested;\n\
+  if (selector.block) throw error('NotAllowedError', 'Permission denied');\n\
+  var id = select(selector, kind, list);\n\
+  if (id === null) throw error('NotFoundError', 'Requested device not found');\n\
+  var constraints = typeof requested === 'object' ? Object.assign({}, requested) : {};\n\
+  constraints.deviceId = { exact: id };\n\
+  return constraints;\n\
+}\n\
+Object.defineProperty(devices, '__cefMediaPolicy', { value: true });\n\
+devices.getUserMedia = function(constraints) {\n\
+  constraints = constraints || {};\n\
+  return enumerate().then(function(list) {\n\
+    return getUserMedia(Object.assign({}, constraints, {\n\
+      video: route(constraints.video, policy.camera, 'videoinput', list),\n\
+      audio: route(constraints.audio, policy.microphone, 'audioinput', list)\n\
+    }));\n\
+  });\n\
+};\n\
+if (policy.hideOthers) {\n\
+  devices.enumerateDevices = function() {\n\
+    return enumerate().then(function(list) {\n\
+      return list.filter(function(d) {\n\
+        var selector = d.kind === 'videoinput' ? policy.camera : d.kind === 'audioinput' ? policy.microphone : null;\n\
+        return !selector || (!selector.block && select(selector, d.kind, [d]) !== null);\n\
+      });\n\
+    });\n\
+  };\n\
+}";
+
+/// The kind of a [MediaDeviceInfo].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaDeviceKind {
+    AudioInput,
+    AudioOutput,
+    VideoInput,
+}
+
+impl MediaDeviceKind {
+    fn from_js(kind: &str) -> Option<MediaDeviceKind> {
+        match kind {
+            "audioinput" => Some(MediaDeviceKind::AudioInput),
+            "audiooutput" => Some(MediaDeviceKind::AudioOutput),
+            "videoinput" => Some(MediaDeviceKind::VideoInput),
+            _ => None,
+        }
+    }
+}
+
+/// A media device as reported by `navigator.mediaDevices.enumerateDevices()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaDeviceInfo {
+    /// The identifier pages use to select the device. It is specific to the
+    /// origin of the page it was enumerated in.
+    pub device_id: String,
+    /// Devices of the same physical product, e.g. a webcam's camera and
+    /// microphone, share a group.
+    pub group_id: String,
+    pub kind: MediaDeviceKind,
+    /// The human-readable name, e.g. "FaceTime HD Camera".
+    pub label: String,
+}
+
+pub type MediaDeviceEnumerationResult = Result<Vec<MediaDeviceInfo>, DevToolsError>;
+
+#[derive(Default)]
+struct EnumerationState {
+    result: Option<MediaDeviceEnumerationResult>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the media devices of a browser, see [enumerate_media_devices].
+pub struct MediaDeviceEnumeration(Arc<Mutex<EnumerationState>>);
+
+impl Future for MediaDeviceEnumeration {
+    type Output = MediaDeviceEnumerationResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<MediaDeviceEnumerationResult> {
+        let mut state = self.0.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Lists the media devices visible to the main frame of `browser`. The
+/// device identifiers are only valid for the origin of the loaded page.
+pub fn enumerate_media_devices(browser: &Browser) -> MediaDeviceEnumeration {
+    let state = Arc::new(Mutex::new(EnumerationState::default()));
+    let session = DevToolsSession::new(&browser.get_host());
+    let params = DictionaryValue::new();
+    params.insert_string("expression", ENUMERATE_EXPRESSION);
+    params.insert_bool("awaitPromise", true);
+    params.insert_bool("returnByValue", true);
+    let result_state = state.clone();
+    // The callback holds the session, which keeps its observer registered
+    // until the result arrived.
+    let keep_alive = session.clone();
+    session.execute("Runtime.evaluate", Some(params), move |result| {
+        drop(keep_alive);
+        let result = result.and_then(|result| {
+            let result = match result {
+                StoredValue::Dictionary(result) => result,
+                _ => return Err(DevToolsError { code: -1, message: "unexpected result".to_owned() }),
+            };
+            if let Some(exception) = result.try_get_dictionary("exceptionDetails") {
+                return Err(DevToolsError { code: -1, message: exception.get_string("text") });
+            }
+            let devices = result.try_get_dictionary("result")
+                .and_then(|result| result.try_get_list("value"))
+                .ok_or_else(|| DevToolsError { code: -1, message: "unexpected result".to_owned() })?;
+            Ok((0..devices.len())
+                .filter_map(|i| devices.get_dictionary(i))
+                .filter_map(|device| {
+                    Some(MediaDeviceInfo {
+                        device_id: device.get_string("deviceId"),
+                        group_id: device.get_string("groupId"),
+                        kind: MediaDeviceKind::from_js(&device.get_string("kind"))?,
+                        label: device.get_string("label"),
+                    })
+                })
+                .collect())
+        });
+        let waker = {
+            let mut state = result_state.lock();
+            state.result = Some(result);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+    MediaDeviceEnumeration(state)
+}
+
+/// Which device a [MediaDevicePolicy] gives pages asking for a camera or
+/// microphone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceSelector {
+    /// Whatever the page asks for, Chromium's default.
+    Any,
+    /// The device with this identifier, see [MediaDeviceInfo::device_id].
+    Id(String),
+    /// The first device whose label contains this text, ignoring case.
+    /// Labels are stable across origins and sessions, unlike identifiers.
+    Label(String),
+    /// Rejects requests with a `NotAllowedError`.
+    Block,
+}
+
+impl DeviceSelector {
+    fn to_js(&self) -> String {
+        let quote = |text: &str| StoredValue::String(text.to_owned()).to_json().unwrap_or_default();
+        match self {
+            DeviceSelector::Any => "null".to_owned(),
+            DeviceSelector::Id(id) => format!("{{ id: {} }}", quote(id)),
+            DeviceSelector::Label(label) => format!("{{ label: {} }}", quote(label)),
+            DeviceSelector::Block => "{ block: true }".to_owned(),
+        }
+    }
+}
+
+/// Forces the cameras and microphones `getUserMedia()` opens. See the
+/// [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaDevicePolicy {
+    pub camera: DeviceSelector,
+    pub microphone: DeviceSelector,
+    /// Hides devices other than the selected ones from
+    /// `enumerateDevices()`, so device pickers in the page only offer those.
+    pub hide_others: bool,
+}
+
+impl MediaDevicePolicy {
+    /// Creates a policy that leaves the choice to the page.
+    pub fn new() -> MediaDevicePolicy {
+        MediaDevicePolicy {
+            camera: DeviceSelector::Any,
+            microphone: DeviceSelector::Any,
+            hide_others: false,
+        }
+    }
+    pub fn camera(mut self, camera: DeviceSelector) -> Self {
+        self.camera = camera;
+        self
+    }
+    pub fn microphone(mut self, microphone: DeviceSelector) -> Self {
+        self.microphone = microphone;
+        self
+    }
+    pub fn hide_others(mut self, hide_others: bool) -> Self {
+        self.hide_others = hide_others;
+        self
+    }
+    /// Routes the `getUserMedia()` calls of the browser of `host` through
+    /// this policy, including in documents that are already loaded. Documents
+    /// loaded after the returned value is dropped aren't affected.
+    pub fn enforce(&self, host: &BrowserHost) -> MediaDeviceGuard {
+        let policy = format!(
+            "{{ camera: {}, microphone: {}, hideOthers: {} }}",
+            self.camera.to_js(),
+            self.microphone.to_js(),
+            self.hide_others,
+        );
+        let scripts = UserScripts::new(host);
+        let script = UserScript::new(&POLICY_SOURCE.replace("__POLICY__", &policy));
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        MediaDeviceGuard { scripts, script_id }
+    }
+}
+
+impl Default for MediaDevicePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keeps a [MediaDevicePolicy] enforced in a browser, returned by
+/// [MediaDevicePolicy::enforce].
+pub struct MediaDeviceGuard {
+    scripts: UserScripts,
+    script_id: UserScriptId,
+}
+
+impl Drop for MediaDeviceGuard {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script_id);
+    }
+}
+
+/// Replaces the system's cameras and microphones with Chromium's fake
+/// devices, for tests and unattended kiosks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FakeMediaDevices {
+    /// Plays this Y4M or MJPEG file as the camera instead of a generated test
+    /// pattern.
+    pub video_file: Option<PathBuf>,
+    /// Plays this WAV file as the microphone instead of a generated beep.
+    pub audio_file: Option<PathBuf>,
+    /// Grants media requests without showing any UI, like
+    /// "enable-media-stream" does.
+    pub auto_accept: bool,
+}
+
+impl FakeMediaDevices {
+    pub fn new() -> FakeMediaDevices {
+        Self::default()
+    }
+    pub fn video_file(mut self, video_file: impl Into<PathBuf>) -> Self {
+        self.video_file = Some(video_file.into());
+        self
+    }
+    pub fn audio_file(mut self, audio_file: impl Into<PathBuf>) -> Self {
+        self.audio_file = Some(audio_file.into());
+        self
+    }
+    pub fn auto_accept(mut self, auto_accept: bool) -> Self {
+        self.auto_accept = auto_accept;
+        self
+    }
+    /// Returns the command line switches to append.
+    pub fn switches(&self) -> Vec<(&'static str, Option<String>)> {
+        let mut switches = vec![("use-fake-device-for-media-stream", None)];
+        if let Some(video_file) = &self.video_file {
+            switches.push(("use-file-for-fake-video-capture", Some(video_file.to_string_lossy().into_owned())));
+        }
+        if let Some(audio_file) = &self.audio_file {
+            switches.push(("use-file-for-fake-audio-capture", Some(audio_file.to_string_lossy().into_owned())));
+        }
+        if self.auto_accept {
+            switches.push(("use-fake-ui-for-media-stream", None));
+        }
+        switches
+    }
+}
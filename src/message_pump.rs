@@ -0,0 +1,272 @@
+//! Driving CEF from an existing event loop.
+//!
+//! With [Settings::external_message_pump](crate::settings::Settings::external_message_pump)
+//! CEF asks the application, through
+//! [BrowserProcessHandlerCallbacks::on_schedule_message_pump_work], to call
+//! [Context::do_message_loop_work](crate::Context::do_message_loop_work) at a
+//! given time. A [MessagePump] turns these requests into callbacks on a
+//! [HostEventLoop], which only has to run a callback on the main thread at a
+//! deadline. Adapters for winit and tao are available with the
+//! `winit-event-loop` and `tao-event-loop` features:
+//!
+//! ```ignore
+//! let host = message_pump::winit::WinitEventLoop::new(event_loop.create_proxy(), MyEvent::CefWork);
+//! let pump = MessagePump::new(host.clone());
+//! // Forward BrowserProcessHandlerCallbacks::on_schedule_message_pump_work
+//! // to pump.schedule_work, then in the event loop:
+//! event_loop.run(move |event, _, control_flow| {
+//!     host.run_due(control_flow);
+//! });
+//! ```
+
+use crate::{browser_process_handler::BrowserProcessHandlerCallbacks, misc_fns};
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// The longest the pump waits between iterations, even if CEF didn't ask for
+/// one. Some work, e.g. on macOS, isn't reported through scheduling requests.
+pub const MAX_PUMP_DELAY: Duration = Duration::from_millis(1000 / 30);
+
+/// A callback scheduled on a [HostEventLoop].
+pub type PumpCallback = Box<dyn 'static + Send + FnOnce()>;
+
+/// An event loop that can run callbacks on the main application thread.
+pub trait HostEventLoop: 'static + Send + Sync {
+    /// Runs `callback` on the main thread once `deadline` has passed. May be
+    /// called from any thread.
+    fn schedule(&self, callback: PumpCallback, deadline: Instant);
+    /// Wakes the event loop up if it is waiting, so it notices new callbacks.
+    fn wake(&self);
+}
+
+struct PumpState {
+    host: Box<dyn HostEventLoop>,
+    /// Incremented for every scheduling request; callbacks of older requests
+    /// do nothing, since CEF expects a new request to replace pending ones.
+    generation: AtomicU64,
+    /// Set while [Context::do_message_loop_work](crate::Context::do_message_loop_work)
+    /// runs. Work requested meanwhile is scheduled for afterwards.
+    active: AtomicBool,
+    reentrancy_detected: AtomicBool,
+}
+
+/// Schedules CEF message loop work on a [HostEventLoop]. See the
+/// [module documentation](self). Cloning the pump is cheap.
+#[derive(Clone)]
+pub struct MessagePump(Arc<PumpState>);
+
+impl MessagePump {
+    pub fn new(host: impl HostEventLoop) -> MessagePump {
+        MessagePump(Arc::new(PumpState {
+            host: Box::new(host),
+            generation: AtomicU64::new(0),
+            active: AtomicBool::new(false),
+            reentrancy_detected: AtomicBool::new(false),
+        }))
+    }
+    /// Handles a scheduling request. Call this from
+    /// [BrowserProcessHandlerCallbacks::on_schedule_message_pump_work], or
+    /// return the pump's callbacks from it directly.
+    pub fn schedule_work(&self, delay_ms: i64) {
+        if self.0.active.load(Ordering::SeqCst) {
+            // Work runs again right after the current iteration.
+            self.0.reentrancy_detected.store(true, Ordering::SeqCst);
+            return;
+        }
+        let delay = Duration::from_millis(delay_ms.max(0) as u64).min(MAX_PUMP_DELAY);
+        self.schedule_at(Instant::now() + delay);
+    }
+
+    fn schedule_at(&self, deadline: Instant) {
+        let generation = self.0.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let pump = self.clone();
+        self.0.host.schedule(Box::new(move || pump.do_work(generation)), deadline);
+        if deadline <= Instant::now() {
+            self.0.host.wake();
+        }
+    }
+    fn do_work(&self, generation: u64) {
+        if self.0.generation.load(Ordering::SeqCst) != generation || !misc_fns::context_initialized() {
+            return;
+        }
+        self.0.active.store(true, Ordering::SeqCst);
+        unsafe {
+            cef_sys::cef_do_message_loop_work();
+        }
+        self.0.active.store(false, Ordering::SeqCst);
+        if self.0.reentrancy_detected.swap(false, Ordering::SeqCst) {
+            self.schedule_at(Instant::now());
+        } else if self.0.generation.load(Ordering::SeqCst) == generation {
+            // Nothing new was requested, so keep a slow heartbeat going.
+            self.schedule_at(Instant::now() + MAX_PUMP_DELAY);
+        }
+    }
+}
+
+impl BrowserProcessHandlerCallbacks for MessagePump {
+    fn on_schedule_message_pump_work(&self, delay_ms: i64) {
+        self.schedule_work(delay_ms);
+    }
+}
+
+/// Callbacks waiting for their deadline, for implementing [HostEventLoop] on
+/// event loops that can only be woken up, like winit's.
+#[derive(Default)]
+pub struct PumpQueue {
+    /// Sorted by deadline.
+    callbacks: Vec<(Instant, PumpCallback)>,
+}
+
+impl PumpQueue {
+    pub fn new() -> PumpQueue {
+        Self::default()
+    }
+    pub fn push(&mut self, callback: PumpCallback, deadline: Instant) {
+        let index = self.callbacks.iter().position(|(other, _)| *other > deadline).unwrap_or(self.callbacks.len());
+        self.callbacks.insert(index, (deadline, callback));
+    }
+    /// Removes the callbacks whose deadline has passed.
+    pub fn take_due(&mut self) -> Vec<PumpCallback> {
+        let now = Instant::now();
+        let due = self.callbacks.iter().take_while(|(deadline, _)| *deadline <= now).count();
+        self.callbacks.drain(..due).map(|(_, callback)| callback).collect()
+    }
+    /// Returns the earliest deadline.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.callbacks.first().map(|(deadline, _)| *deadline)
+    }
+}
+
+/// A [PumpQueue] shared between the pump and the event loop, with a function
+/// waking the event loop up.
+struct SharedQueue<W> {
+    queue: Mutex<PumpQueue>,
+    wake: W,
+}
+
+impl<W: 'static + Send + Sync + Fn()> SharedQueue<W> {
+    fn run_due(&self) -> Option<Instant> {
+        // Callbacks may schedule new ones, so don't hold the lock.
+        let due = self.queue.lock().take_due();
+        for callback in due {
+            callback();
+        }
+        self.queue.lock().next_deadline()
+    }
+}
+
+impl<W: 'static + Send + Sync + Fn()> HostEventLoop for Arc<SharedQueue<W>> {
+    fn schedule(&self, callback: PumpCallback, deadline: Instant) {
+        self.queue.lock().push(callback, deadline);
+        (self.wake)();
+    }
+    fn wake(&self) {
+        (self.wake)();
+    }
+}
+
+#[cfg(feature = "winit-event-loop")]
+pub mod winit {
+    //! [HostEventLoop] for winit.
+
+    use super::*;
+    use winit_event_loop::event_loop::{ControlFlow, EventLoopProxy};
+
+    /// Wakes a winit event loop with a user event. Call
+    /// [WinitEventLoop::run_due] for every event.
+    pub struct WinitEventLoop<T: 'static + Send>(Arc<SharedQueue<Box<dyn 'static + Send + Sync + Fn()>>>, std::marker::PhantomData<fn() -> T>);
+
+    impl<T: 'static + Send> WinitEventLoop<T> {
+        /// Creates a host that sends `make_event()` through `proxy` to wake
+        /// the loop up.
+        pub fn new(proxy: EventLoopProxy<T>, make_event: impl 'static + Send + Sync + Fn() -> T) -> Self {
+            let proxy = Mutex::new(proxy);
+            let wake: Box<dyn 'static + Send + Sync + Fn()> = Box::new(move || {
+                // Fails only once the loop has exited.
+                proxy.lock().send_event(make_event()).ok();
+            });
+            WinitEventLoop(Arc::new(SharedQueue { queue: Mutex::new(PumpQueue::new()), wake }), std::marker::PhantomData)
+        }
+        /// Runs due callbacks and makes the loop wait for the next one.
+        pub fn run_due(&self, control_flow: &mut ControlFlow) {
+            if *control_flow == ControlFlow::Exit {
+                return;
+            }
+            *control_flow = match self.0.run_due() {
+                Some(deadline) => ControlFlow::WaitUntil(deadline),
+                None => ControlFlow::Wait,
+            };
+        }
+    }
+
+    impl<T: 'static + Send> Clone for WinitEventLoop<T> {
+        fn clone(&self) -> Self {
+            WinitEventLoop(self.0.clone(), std::marker::PhantomData)
+        }
+    }
+
+    impl<T: 'static + Send> HostEventLoop for WinitEventLoop<T> {
+        fn schedule(&self, callback: PumpCallback, deadline: Instant) {
+            self.0.schedule(callback, deadline);
+        }
+        fn wake(&self) {
+            self.0.wake();
+        }
+    }
+}
+
+#[cfg(feature = "tao-event-loop")]
+pub mod tao {
+    //! [HostEventLoop] for tao.
+
+    use super::*;
+    use ::tao::event_loop::{ControlFlow, EventLoopProxy};
+
+    /// Wakes a tao event loop with a user event. Call [TaoEventLoop::run_due]
+    /// for every event.
+    pub struct TaoEventLoop<T: 'static + Send>(Arc<SharedQueue<Box<dyn 'static + Send + Sync + Fn()>>>, std::marker::PhantomData<fn() -> T>);
+
+    impl<T: 'static + Send> TaoEventLoop<T> {
+        /// Creates a host that sends `make_event()` through `proxy` to wake
+        /// the loop up.
+        pub fn new(proxy: EventLoopProxy<T>, make_event: impl 'static + Send + Sync + Fn() -> T) -> Self {
+            let proxy = Mutex::new(proxy);
+            let wake: Box<dyn 'static + Send + Sync + Fn()> = Box::new(move || {
+                // Fails only once the loop has exited.
+                proxy.lock().send_event(make_event()).ok();
+            });
+            TaoEventLoop(Arc::new(SharedQueue { queue: Mutex::new(PumpQueue::new()), wake }), std::marker::PhantomData)
+        }
+        /// Runs due callbacks and makes the loop wait for the next one.
+        pub fn run_due(&self, control_flow: &mut ControlFlow) {
+            if *control_flow == ControlFlow::Exit {
+                return;
+            }
+            *control_flow = match self.0.run_due() {
+                Some(deadline) => ControlFlow::WaitUntil(deadline),
+                None => ControlFlow::Wait,
+            };
+        }
+    }
+
+    impl<T: 'static + Send> Clone for TaoEventLoop<T> {
+        fn clone(&self) -> Self {
+            TaoEventLoop(self.0.clone(), std::marker::PhantomData)
+        }
+    }
+
+    impl<T: 'static + Send> HostEventLoop for TaoEventLoop<T> {
+        fn schedule(&self, callback: PumpCallback, deadline: Instant) {
+            self.0.schedule(callback, deadline);
+        }
+        fn wake(&self) {
+            self.0.wake();
+        }
+    }
+}
@@ -7,7 +7,7 @@ use crate::{
     app::App,
     main_args::MainArgs,
     sandbox::SandboxInfo,
-    settings::Settings,
+    settings::{Settings, SINGLE_PROCESS_SWITCH},
 };
 use cef_sys::{
     cef_do_message_loop_work, cef_execute_process,
@@ -125,18 +125,42 @@ pub fn process_type() -> ProcessType {
             let flag = "--type=";
             let arg = std::env::args().find(|s| s.starts_with(flag));
             let arg = arg.as_ref().map(|s| &s[flag.len()..]);
-            match arg {
-                None => ProcessType::Browser,
-                Some("renderer") => ProcessType::Renderer,
-                Some("gpu-process") => ProcessType::Gpu,
-                Some("utility") => ProcessType::Utility,
-                _ => ProcessType::Other,
-            }
+            process_type_from_switch(arg)
         };
     }
     *PROCESS_TYPE
 }
 
+/// Maps the value of the `--type` switch to a [ProcessType].
+pub(crate) fn process_type_from_switch(value: Option<&str>) -> ProcessType {
+    match value {
+        None => ProcessType::Browser,
+        Some("renderer") => ProcessType::Renderer,
+        Some("gpu-process") => ProcessType::Gpu,
+        Some("utility") => ProcessType::Utility,
+        _ => ProcessType::Other,
+    }
+}
+
+static SINGLE_PROCESS: AtomicBool = AtomicBool::new(false);
+static WINDOWLESS_RENDERING_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Returns the value of [Settings::windowless_rendering_enabled] that CEF was
+/// initialized with.
+pub(crate) fn windowless_rendering_enabled() -> bool {
+    WINDOWLESS_RENDERING_ENABLED.load(Ordering::SeqCst)
+}
+
+/// Returns true if the renderer runs in the browser process, because of
+/// [Settings::single_process] or the "--single-process" command line switch.
+/// Render process handlers are then called in this process as well.
+pub fn is_single_process() -> bool {
+    lazy_static::lazy_static!{
+        static ref SWITCH_PASSED: bool = std::env::args().any(|arg| arg == "--single-process");
+    }
+    *SWITCH_PASSED || SINGLE_PROCESS.load(Ordering::SeqCst)
+}
+
 pub(crate) fn panic_if_cef_not_loaded() {
     #[cfg(target_os = "macos")]
     {
@@ -151,6 +175,10 @@ pub struct Context(std::marker::PhantomData<&'static std::cell::Cell<u8>>); // n
 
 static CONTEXT_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
+pub(crate) fn context_initialized() -> bool {
+    CONTEXT_INITIALIZED.load(Ordering::SeqCst)
+}
+
 impl Context {
     /// This function should be called on the main application thread to initialize
     /// the CEF browser process.
@@ -159,6 +187,9 @@ impl Context {
     /// value of true indicates that it succeeded and false indicates that it
     /// failed. The `windows_sandbox_info` parameter is only used on Windows and may
     /// be None (see [SandboxInfo] for details).
+    ///
+    /// Fails with a list of missing files if CEF's resources aren't where
+    /// `settings` says, see [ResourceLocator](crate::resource_locator::ResourceLocator).
     #[allow(unused_mut)]
     pub fn initialize(
         mut settings: Settings,
@@ -172,6 +203,19 @@ impl Context {
                 settings.framework_dir_path = Some(framework_path);
             }
         }
+        crate::resource_locator::ResourceLocator::from_settings(&settings).validate()?;
+        let application = match application {
+            None if !settings.command_line_switches.is_empty() => Some(App::new(crate::app::DefaultAppCallbacks)),
+            application => application,
+        };
+        crate::i18n::set_configured_locale(settings.locale.clone());
+        crate::browser_startup::record_settings(&settings);
+        crate::profile_manager::set_root_cache_path(settings.root_cache_path.clone().or_else(|| settings.cache_path.clone()));
+        WINDOWLESS_RENDERING_ENABLED.store(settings.windowless_rendering_enabled, Ordering::SeqCst);
+        if settings.command_line_switches.iter().any(|(name, _)| name == SINGLE_PROCESS_SWITCH) {
+            SINGLE_PROCESS.store(true, Ordering::SeqCst);
+        }
+        crate::app::set_browser_switches(settings.command_line_switches.clone());
         let args = MainArgs::new();
         enable_highdpi_support();
         CONTEXT_INITIALIZED.swap(true, Ordering::SeqCst);
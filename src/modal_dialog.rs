@@ -0,0 +1,348 @@
+//! Modal dialogs.
+//!
+//! A [ModalWindow] is a views window that is a child of a parent window,
+//! for settings or sign-in dialogs built from views. The parent doesn't take
+//! input while it is open, and the dialog is closed with a result:
+//!
+//! ```ignore
+//! let dialog = Window::create_as_dialog(&main_window, SettingsDialog { ... });
+//! // in the dialog's button handler:
+//! dialog.close(Some(StoredValue::String("saved".to_owned())));
+//! let result = dialog.result().await;
+//! ```
+//!
+//! Applications that paint browsers into windows of their own use a
+//! [ModalDialog] instead. It is a windowless browser the application shows
+//! on top of a parent browser. While it is open, the parent is marked as
+//! blocked so the application stops forwarding input to it, and the page
+//! closes the dialog with a result:
+//!
+//! ```ignore
+//! let dialog = ModalDialog::open(&browser, "app://settings", client, &settings)?;
+//! // when forwarding input to a browser:
+//! if !ModalDialog::is_blocked(&browser) { host.send_mouse_click_event(...) }
+//! // the page calls `cefDialog.close({ theme: 'dark' })`
+//! if let Some(StoredValue::Dictionary(result)) = dialog.result().await {
+//!     apply_theme(&result.get_string("theme"));
+//! }
+//! ```
+//!
+//! The result is `None` if the page called `cefDialog.close()` without an
+//! argument, or the dialog was closed with [ModalDialog::close].
+//! Likewise, the result of a [ModalWindow] is `None` if the user closed the
+//! window.
+
+use crate::{
+    browser::{Browser, BrowserSettings},
+    browser_host::BrowserHost,
+    browser_startup::CreateError,
+    client::Client,
+    devtools::DevToolsSession,
+    events::KeyEvent,
+    user_scripts::{UserScript, UserScripts},
+    values::{DictionaryValue, StoredValue},
+    views::{Window, WindowDelegate, WindowDelegateCallbacks},
+    window::WindowInfo,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+/// The name of the DevTools binding results are reported through.
+const BINDING_NAME: &str = "__cefDialogClose";
+
+const SHIM_SOURCE: &str = "\
+var notify = window.__cefDialogClose;\n\
+if (typeof notify !== 'function' || window.cefDialog) return;\n\
+try { delete window.__cefDialogClose; } catch (e) {}\n\
+Object.defineProperty(window, 'cefDialog', { value: Object.freeze({\n\
+  close: function(result) { notify(JSON.stringify({ result: result === undefined ? null : result })); }\n\
+}) });";
+
+lazy_static::lazy_static! {
+    /// The number of open dialogs by parent browser identifier.
+    static ref BLOCKED: Mutex<HashMap<i32, usize>> = Mutex::new(HashMap::new());
+}
+
+struct Outcome {
+    /// Set once the dialog was closed.
+    result: Option<Option<StoredValue>>,
+    waker: Option<Waker>,
+}
+
+impl Outcome {
+    fn new() -> Arc<Mutex<Outcome>> {
+        Arc::new(Mutex::new(Outcome { result: None, waker: None }))
+    }
+}
+
+/// Stores the result of a dialog. Returns the waker of the [DialogResult],
+/// or `None` if the dialog was closed already.
+fn resolve(outcome: &Mutex<Outcome>, result: Option<StoredValue>) -> Option<Option<Waker>> {
+    let mut outcome = outcome.lock();
+    if outcome.result.is_some() {
+        return None;
+    }
+    outcome.result = Some(result);
+    Some(outcome.waker.take())
+}
+
+struct DialogState {
+    outcome: Arc<Mutex<Outcome>>,
+    parent: Browser,
+    browser: Browser,
+}
+
+/// Resolves to the result of a [ModalDialog] or [ModalWindow].
+pub struct DialogResult(Arc<Mutex<Outcome>>);
+
+impl Future for DialogResult {
+    type Output = Option<StoredValue>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<StoredValue>> {
+        let mut state = self.0.lock();
+        match &state.result {
+            Some(result) => Poll::Ready(result.clone()),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// A page shown as a modal dialog over a parent browser. See the
+/// [module documentation](self).
+///
+/// Dropping the dialog closes it.
+pub struct ModalDialog {
+    state: Arc<DialogState>,
+    session: DevToolsSession,
+    scripts: UserScripts,
+}
+
+impl ModalDialog {
+    /// Opens `url` in a new windowless browser of `client` and blocks
+    /// `parent` until the dialog is closed. Must be called on the browser
+    /// process UI thread.
+    pub fn open(parent: &Browser, url: &str, client: Client, settings: &BrowserSettings) -> Result<ModalDialog, CreateError> {
+        let parent_host = parent.get_host();
+        let window_info = WindowInfo::windowless(parent_host.get_window_handle());
+        let browser = BrowserHost::create_browser_sync(&window_info, client, url, settings, None, Some(parent_host.get_request_context()))?;
+
+        *BLOCKED.lock().entry(parent.get_identifier()).or_insert(0) += 1;
+        parent_host.set_focus(false);
+        browser.get_host().set_focus(true);
+
+        let state = Arc::new(DialogState {
+            outcome: Outcome::new(),
+            parent: parent.clone(),
+            browser: browser.clone(),
+        });
+        let session = DevToolsSession::new(&browser.get_host());
+        let listener_state = Arc::downgrade(&state);
+        session.add_event_listener("Runtime.bindingCalled", move |_, params| {
+            let params = match params {
+                StoredValue::Dictionary(params) if params.get_string("name") == BINDING_NAME => params,
+                _ => return,
+            };
+            let result = match StoredValue::from_json(&params.get_string("payload")) {
+                Some(StoredValue::Dictionary(payload)) => match payload.get("result") {
+                    StoredValue::Null | StoredValue::Invalid => None,
+                    result => Some(result),
+                },
+                _ => return,
+            };
+            if let Some(state) = listener_state.upgrade() {
+                finish(&state, result);
+            }
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        scripts.add(&UserScript::new(SHIM_SOURCE));
+        Ok(ModalDialog { state, session, scripts })
+    }
+    /// Returns true while `browser` has an open dialog.
+    pub fn is_blocked(browser: &Browser) -> bool {
+        BLOCKED.lock().contains_key(&browser.get_identifier())
+    }
+
+    /// Returns the browser showing the dialog.
+    pub fn browser(&self) -> Browser {
+        self.state.browser.clone()
+    }
+    pub fn parent(&self) -> Browser {
+        self.state.parent.clone()
+    }
+    pub fn is_open(&self) -> bool {
+        self.state.outcome.lock().result.is_none()
+    }
+    /// Resolves once the dialog was closed.
+    pub fn result(&self) -> DialogResult {
+        DialogResult(self.state.outcome.clone())
+    }
+    /// Closes the dialog with `result`, e.g. when the user pressed Escape.
+    /// Does nothing if it was closed already.
+    pub fn close(&self, result: Option<StoredValue>) {
+        finish(&self.state, result);
+    }
+}
+
+/// Closes the dialog browser, unblocks the parent and resolves the result.
+fn finish(state: &DialogState, result: Option<StoredValue>) {
+    let waker = match resolve(&state.outcome, result) {
+        Some(waker) => waker,
+        None => return,
+    };
+    {
+        let mut blocked = BLOCKED.lock();
+        let parent_id = state.parent.get_identifier();
+        if let Some(count) = blocked.get_mut(&parent_id) {
+            *count -= 1;
+            if *count == 0 {
+                blocked.remove(&parent_id);
+            }
+        }
+    }
+    state.browser.get_host().close_browser(true);
+    state.parent.get_host().set_focus(true);
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+impl Drop for ModalDialog {
+    fn drop(&mut self) {
+        finish(&self.state, None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
+
+struct ModalWindowState {
+    outcome: Arc<Mutex<Outcome>>,
+    parent: Window,
+    /// Set once the window was created.
+    window: Mutex<Option<Window>>,
+}
+
+/// A views window shown as a modal dialog of a parent window. See the
+/// [module documentation](self).
+///
+/// Cloning is cheap; clones share the dialog. Unlike a [ModalDialog], the
+/// dialog stays open when it is dropped.
+#[derive(Clone)]
+pub struct ModalWindow(Arc<ModalWindowState>);
+
+impl ModalWindow {
+    /// Creates a window for `callbacks` as a child of `parent` and disables
+    /// `parent` until the dialog is closed. Show the window in
+    /// [WindowDelegateCallbacks::on_window_created]. Must be called on the
+    /// browser process UI thread.
+    pub fn open<C: WindowDelegateCallbacks>(parent: &Window, callbacks: C) -> ModalWindow {
+        let dialog = ModalWindow(Arc::new(ModalWindowState {
+            outcome: Outcome::new(),
+            parent: parent.clone(),
+            window: Mutex::new(None),
+        }));
+        parent.set_enabled(false);
+        Window::new(WindowDelegate::new(ModalWindowDelegate {
+            dialog: dialog.clone(),
+            callbacks,
+        }));
+        dialog
+    }
+    /// Returns the window of the dialog once it was created.
+    pub fn window(&self) -> Option<Window> {
+        self.0.window.lock().clone()
+    }
+    pub fn parent(&self) -> Window {
+        self.0.parent.clone()
+    }
+    pub fn is_open(&self) -> bool {
+        self.0.outcome.lock().result.is_none()
+    }
+    /// Resolves once the dialog was closed.
+    pub fn result(&self) -> DialogResult {
+        DialogResult(self.0.outcome.clone())
+    }
+    /// Closes the dialog with `result`. Does nothing if it was closed
+    /// already.
+    pub fn close(&self, result: Option<StoredValue>) {
+        let window = self.0.window.lock().clone();
+        if self.finish(result) {
+            if let Some(window) = window {
+                window.close();
+            }
+        }
+    }
+
+    /// Enables the parent again and resolves the result. Returns false if
+    /// the dialog was closed already.
+    fn finish(&self, result: Option<StoredValue>) -> bool {
+        let waker = match resolve(&self.0.outcome, result) {
+            Some(waker) => waker,
+            None => return false,
+        };
+        self.0.parent.set_enabled(true);
+        self.0.parent.activate();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        true
+    }
+}
+
+/// Makes the window of a [ModalWindow] a child of the parent and forwards
+/// everything else to the application's callbacks.
+struct ModalWindowDelegate<C> {
+    dialog: ModalWindow,
+    callbacks: C,
+}
+
+impl<C: WindowDelegateCallbacks> WindowDelegateCallbacks for ModalWindowDelegate<C> {
+    fn on_window_created(&self, window: Window) {
+        *self.dialog.0.window.lock() = Some(window.clone());
+        self.callbacks.on_window_created(window);
+    }
+    fn on_window_destroyed(&self, window: Window) {
+        self.dialog.finish(None);
+        self.dialog.0.window.lock().take();
+        self.callbacks.on_window_destroyed(window);
+    }
+    fn get_parent_window(&self, window: Window, is_menu: &mut bool, can_activate_menu: &mut bool) -> Option<Window> {
+        Some(self.dialog.0.parent.clone())
+    }
+    fn is_frameless(&self, window: Window) -> bool {
+        self.callbacks.is_frameless(window)
+    }
+    fn can_resize(&self, window: Window) -> bool {
+        self.callbacks.can_resize(window)
+    }
+    fn can_maximize(&self, window: Window) -> bool {
+        false
+    }
+    fn can_minimize(&self, window: Window) -> bool {
+        false
+    }
+    fn can_close(&self, window: Window) -> bool {
+        // Closing through `ModalWindow::close` was decided already.
+        !self.dialog.is_open() || self.callbacks.can_close(window)
+    }
+    fn on_accelerator(&self, window: Window, command_id: i32) -> bool {
+        self.callbacks.on_accelerator(window, command_id)
+    }
+    fn on_key_event(&self, window: Window, event: KeyEvent) -> bool {
+        self.callbacks.on_key_event(window, event)
+    }
+}
@@ -1,12 +1,48 @@
 use cef_sys::{cef_navigation_entry_t};
 use std::convert::TryInto;
 use crate::{
-    load_handler::TransitionType,
+    load_handler::{TransitionType, TransitionTypeQualifiers},
     ssl::SSLStatus,
     string::CefString,
 };
 use chrono::{DateTime, Utc};
 
+/// Why a navigation entry was visited, as shown by history UIs. Derived from
+/// the [TransitionType] of the entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NavigationReason {
+    /// A link was clicked or `window.open` was called.
+    Link,
+    /// The URL was entered or loaded explicitly, e.g. with
+    /// [Frame::load_url](crate::frame::Frame::load_url).
+    Typed,
+    FormSubmit,
+    Reload,
+    /// The back or forward buttons were used.
+    BackForward,
+    /// A subframe was navigated.
+    Subframe,
+    /// A server or client redirect.
+    Redirect,
+}
+
+impl From<&TransitionType> for NavigationReason {
+    fn from(transition_type: &TransitionType) -> NavigationReason {
+        let qualifiers = transition_type.qualifiers();
+        if qualifiers.contains(TransitionTypeQualifiers::FORWARD_BACK) {
+            return NavigationReason::BackForward;
+        }
+        match transition_type {
+            TransitionType::AutoSubframe(_) | TransitionType::ManualSubframe(_) => NavigationReason::Subframe,
+            _ if qualifiers.intersects(TransitionTypeQualifiers::CLIENT_REDIRECT | TransitionTypeQualifiers::SERVER_REDIRECT) => NavigationReason::Redirect,
+            TransitionType::Link(_) => NavigationReason::Link,
+            TransitionType::Explicit(_) => NavigationReason::Typed,
+            TransitionType::FormSubmit(_) => NavigationReason::FormSubmit,
+            TransitionType::Reload(_) => NavigationReason::Reload,
+        }
+    }
+}
+
 ref_counted_ptr! {
     /// Structure used to represent an entry in navigation history.
     pub struct NavigationEntry(*mut cef_navigation_entry_t);
@@ -0,0 +1,129 @@
+//! An HTTP client that uses the browser's network stack.
+//!
+//! [Client] wraps [URLRequest::fetch] for code that just wants to make
+//! requests the way the browser does, with its proxy settings, certificate
+//! handling, cache and cookies, without writing a
+//! [URLRequestClient](crate::url_request::URLRequestClient):
+//!
+//! ```ignore
+//! let client = Client::new()
+//!     .request_context(&profile_context)
+//!     .default_header("Accept", "application/json")
+//!     .on_auth(|challenge| Some((user.clone(), password.clone())));
+//! let response = client.get("https://example.com/api/items").await?;
+//! let created = client.post("https://example.com/api/items", "application/json", body).await?;
+//! ```
+//!
+//! Requests must be started on a CEF thread of the browser process, and the
+//! returned futures resolve there; see [URLRequest::new] for the rules.
+
+use crate::{
+    request::{RequestBuilder, URLRequestFlags},
+    request_context::RequestContext,
+    url_request::{AuthChallenge, AuthProvider, Fetch, URLRequest},
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// Whether requests send and store cookies and credentials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cookies {
+    /// Cookies of the request context are sent, and cookies of responses are
+    /// stored in it, like for navigations.
+    Include,
+    /// Neither cookies nor stored credentials are used.
+    Omit,
+}
+
+/// A configured HTTP client. See the [module documentation](self).
+///
+/// Cloning is cheap; clones share the authentication callback.
+#[derive(Clone)]
+pub struct Client {
+    request_context: Option<RequestContext>,
+    default_headers: HashMap<String, Vec<String>>,
+    follow_redirects: bool,
+    cookies: Cookies,
+    auth: Option<AuthProvider>,
+}
+
+impl Client {
+    /// Creates a client using the global request context, which follows
+    /// redirects and includes cookies.
+    pub fn new() -> Client {
+        Client {
+            request_context: None,
+            default_headers: HashMap::new(),
+            follow_redirects: true,
+            cookies: Cookies::Include,
+            auth: None,
+        }
+    }
+    /// Sends requests in `request_context`, using its cookies, cache and
+    /// proxy settings.
+    pub fn request_context(mut self, request_context: &RequestContext) -> Self {
+        self.request_context = Some(request_context.clone());
+        self
+    }
+    /// Adds a header sent with every request that doesn't set it itself.
+    pub fn default_header(mut self, name: &str, value: &str) -> Self {
+        self.default_headers.entry(name.to_owned()).or_insert_with(Vec::new).push(value.to_owned());
+        self
+    }
+    /// Sets whether redirects are followed. If not, requests resolve to the
+    /// redirect response.
+    pub fn follow_redirects(mut self, follow_redirects: bool) -> Self {
+        self.follow_redirects = follow_redirects;
+        self
+    }
+    pub fn cookies(mut self, cookies: Cookies) -> Self {
+        self.cookies = cookies;
+        self
+    }
+    /// Answers requests for credentials, by servers or proxies, with a user
+    /// name and password. Returning `None` fails the request. Called on the
+    /// IO thread.
+    pub fn on_auth(mut self, auth: impl 'static + Send + Sync + Fn(&AuthChallenge) -> Option<(String, String)>) -> Self {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    /// Sends a GET request.
+    pub fn get(&self, url: &str) -> Fetch {
+        self.send(RequestBuilder::new(url))
+    }
+    /// Sends a POST request with `body` of `content_type`.
+    pub fn post<T: Into<Vec<u8>>>(&self, url: &str, content_type: &str, body: T) -> Fetch {
+        self.send(RequestBuilder::new(url).method("POST").header("Content-Type", content_type).body(body))
+    }
+    /// Sends the request built by `request`, adding the client's default
+    /// headers and flags.
+    pub fn send(&self, request: RequestBuilder) -> Fetch {
+        let mut request = request.build();
+        if !self.default_headers.is_empty() {
+            let mut headers = request.get_header_map();
+            for (name, values) in &self.default_headers {
+                if !headers.keys().any(|header| header.eq_ignore_ascii_case(name)) {
+                    headers.insert(name.clone(), values.clone());
+                }
+            }
+            request.set_header_map(&headers);
+        }
+        let mut flags = request.get_flags();
+        let mut set_flag = |flag: URLRequestFlags, enabled: bool| {
+            flags.retain(|f| *f != flag);
+            if enabled {
+                flags.push(flag);
+            }
+        };
+        set_flag(URLRequestFlags::AllowStoredCredentials, self.cookies == Cookies::Include);
+        set_flag(URLRequestFlags::StopOnRedirect, !self.follow_redirects);
+        request.set_flags(&flags);
+        URLRequest::fetch_with_auth(&mut request, self.request_context.as_ref(), self.auth.clone())
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
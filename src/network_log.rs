@@ -0,0 +1,403 @@
+//! Recording network requests and exporting them as HAR.
+//!
+//! A [NetworkLog] listens to the DevTools `Network` domain of one browser and
+//! keeps the request and response metadata, sizes and timings of every
+//! request in memory. [NetworkLog::export] writes them as
+//! [HAR 1.2](http://www.softwareishard.com/blog/har-12-spec/), which can be
+//! opened by the network panel of most browsers. Response bodies are not
+//! recorded.
+
+use crate::{
+    browser_host::BrowserHost,
+    devtools::DevToolsSession,
+    values::{DictionaryValue, ListValue, StoredValue},
+};
+use chrono::{DateTime, TimeZone, Utc};
+use parking_lot::Mutex;
+use std::sync::{Arc, Weak};
+
+/// The number of entries kept by [NetworkLog::new]. Older entries are dropped.
+pub const DEFAULT_MAX_ENTRIES: usize = 5000;
+
+/// Connection timings of a request in milliseconds, as in HAR. Phases that
+/// didn't happen, e.g. DNS resolution for a reused connection, are `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RequestTimings {
+    pub blocked: Option<f64>,
+    pub dns: Option<f64>,
+    pub connect: Option<f64>,
+    /// Included in `connect`.
+    pub ssl: Option<f64>,
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+impl RequestTimings {
+    /// Returns the total time of the request.
+    pub fn total(&self) -> f64 {
+        self.blocked.unwrap_or(0.0) + self.dns.unwrap_or(0.0) + self.connect.unwrap_or(0.0)
+            + self.send + self.wait + self.receive
+    }
+}
+
+/// A request recorded by a [NetworkLog].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkEntry {
+    /// The DevTools request identifier. Redirects share the identifier of
+    /// the original request.
+    pub request_id: String,
+    pub started: DateTime<Utc>,
+    pub url: String,
+    pub method: String,
+    pub request_headers: Vec<(String, String)>,
+    /// The request body, if DevTools reported it. Large and binary bodies
+    /// are omitted by DevTools.
+    pub request_body: Option<String>,
+    /// `None` until the response headers arrived, or if the request failed
+    /// before.
+    pub status: Option<i32>,
+    pub status_text: String,
+    pub response_headers: Vec<(String, String)>,
+    pub mime_type: String,
+    /// The protocol, e.g. `http/1.1` or `h2`.
+    pub protocol: String,
+    pub remote_address: String,
+    pub redirect_url: String,
+    /// The number of bytes received over the network, including headers.
+    pub transfer_size: Option<i64>,
+    /// The size of the decoded response body.
+    pub body_size: i64,
+    pub timings: Option<RequestTimings>,
+    /// The network error, e.g. `net::ERR_NAME_NOT_RESOLVED`.
+    pub error: Option<String>,
+    pub finished: bool,
+    /// Monotonic DevTools timestamps in seconds.
+    start_timestamp: f64,
+    end_timestamp: Option<f64>,
+    /// The DevTools `ResourceTiming` of the response, relative to `requestTime`.
+    raw_timing: Option<DictionaryValue>,
+}
+
+impl NetworkEntry {
+    fn new(request_id: &str, params: &DictionaryValue, request: &DictionaryValue) -> NetworkEntry {
+        let wall_time = params.get_double("wallTime");
+        NetworkEntry {
+            request_id: request_id.to_owned(),
+            started: Utc.timestamp_millis((wall_time * 1000.0) as i64),
+            url: request.get_string("url"),
+            method: request.get_string("method"),
+            request_headers: headers(request.try_get_dictionary("headers")),
+            request_body: if request.contains_key("postData") {
+                Some(request.get_string("postData"))
+            } else {
+                None
+            },
+            status: None,
+            status_text: String::new(),
+            response_headers: Vec::new(),
+            mime_type: String::new(),
+            protocol: String::new(),
+            remote_address: String::new(),
+            redirect_url: String::new(),
+            transfer_size: None,
+            body_size: 0,
+            timings: None,
+            error: None,
+            finished: false,
+            start_timestamp: params.get_double("timestamp"),
+            end_timestamp: None,
+            raw_timing: None,
+        }
+    }
+    /// Returns the time from starting the request to its end in milliseconds.
+    pub fn time(&self) -> Option<f64> {
+        match (self.timings, self.end_timestamp) {
+            (Some(timings), _) => Some(timings.total()),
+            (None, Some(end)) => Some((end - self.start_timestamp) * 1000.0),
+            (None, None) => None,
+        }
+    }
+
+    fn set_response(&mut self, response: &DictionaryValue) {
+        self.status = Some(response.get_int("status"));
+        self.status_text = response.get_string("statusText");
+        self.response_headers = headers(response.try_get_dictionary("headers"));
+        self.mime_type = response.get_string("mimeType");
+        self.protocol = response.get_string("protocol");
+        self.remote_address = response.get_string("remoteIPAddress");
+        self.transfer_size = Some(response.get_double("encodedDataLength") as i64);
+        self.raw_timing = response.try_get_dictionary("timing");
+    }
+    fn finish(&mut self, timestamp: f64) {
+        self.finished = true;
+        self.end_timestamp = Some(timestamp);
+        self.timings = self.raw_timing.take().map(|timing| timings(&timing, timestamp));
+    }
+    fn to_har(&self) -> DictionaryValue {
+        let entry = DictionaryValue::new();
+        entry.insert_string("startedDateTime", &self.started.to_rfc3339());
+        entry.insert_double("time", self.time().unwrap_or(0.0));
+
+        let request = DictionaryValue::new();
+        request.insert_string("method", &self.method);
+        request.insert_string("url", &self.url);
+        request.insert_string("httpVersion", &self.protocol);
+        request.insert_list("cookies", ListValue::new());
+        request.insert_list("headers", har_headers(&self.request_headers));
+        request.insert_list("queryString", ListValue::new());
+        request.insert_int("headersSize", -1);
+        match &self.request_body {
+            Some(body) => {
+                let post_data = DictionaryValue::new();
+                let mime_type = self.request_headers.iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                    .map(|(_, value)| &value[..])
+                    .unwrap_or("");
+                post_data.insert_string("mimeType", mime_type);
+                post_data.insert_string("text", body);
+                request.insert_dictionary("postData", post_data);
+                request.insert_double("bodySize", body.len() as f64);
+            }
+            None => {
+                request.insert_int("bodySize", -1);
+            }
+        }
+        entry.insert_dictionary("request", request);
+
+        let response = DictionaryValue::new();
+        response.insert_int("status", self.status.unwrap_or(0));
+        response.insert_string("statusText", &self.status_text);
+        response.insert_string("httpVersion", &self.protocol);
+        response.insert_list("cookies", ListValue::new());
+        response.insert_list("headers", har_headers(&self.response_headers));
+        let content = DictionaryValue::new();
+        content.insert_double("size", self.body_size as f64);
+        content.insert_string("mimeType", &self.mime_type);
+        response.insert_dictionary("content", content);
+        response.insert_string("redirectURL", &self.redirect_url);
+        response.insert_int("headersSize", -1);
+        response.insert_int("bodySize", -1);
+        if let Some(transfer_size) = self.transfer_size {
+            response.insert_double("_transferSize", transfer_size as f64);
+        }
+        if let Some(error) = &self.error {
+            response.insert_string("_error", error);
+        }
+        entry.insert_dictionary("response", response);
+
+        entry.insert_dictionary("cache", DictionaryValue::new());
+        let timings = DictionaryValue::new();
+        let optional = |value: Option<f64>| value.unwrap_or(-1.0);
+        match self.timings {
+            Some(t) => {
+                timings.insert_double("blocked", optional(t.blocked));
+                timings.insert_double("dns", optional(t.dns));
+                timings.insert_double("connect", optional(t.connect));
+                timings.insert_double("ssl", optional(t.ssl));
+                timings.insert_double("send", t.send);
+                timings.insert_double("wait", t.wait);
+                timings.insert_double("receive", t.receive);
+            }
+            None => {
+                timings.insert_double("send", 0.0);
+                timings.insert_double("wait", self.time().unwrap_or(0.0));
+                timings.insert_double("receive", 0.0);
+            }
+        }
+        entry.insert_dictionary("timings", timings);
+        if !self.remote_address.is_empty() {
+            entry.insert_string("serverIPAddress", &self.remote_address);
+        }
+        entry
+    }
+}
+
+struct LogState {
+    session: DevToolsSession,
+    max_entries: usize,
+    entries: Mutex<Vec<NetworkEntry>>,
+}
+
+impl LogState {
+    fn update(&self, request_id: &str, f: impl FnOnce(&mut NetworkEntry)) {
+        let mut entries = self.entries.lock();
+        // Redirects add entries with the same identifier, update the last one.
+        if let Some(entry) = entries.iter_mut().rev().find(|entry| entry.request_id == request_id) {
+            f(entry);
+        }
+    }
+}
+
+impl Drop for LogState {
+    fn drop(&mut self) {
+        self.session.send("Network.disable", None);
+    }
+}
+
+/// An in-memory log of the requests of one browser. Cloning the log is cheap
+/// and all clones share the same entries. Recording stops once the last
+/// clone is dropped.
+#[derive(Clone)]
+pub struct NetworkLog(Arc<LogState>);
+
+impl NetworkLog {
+    /// Starts recording the requests of the browser of `host`, keeping the
+    /// last [DEFAULT_MAX_ENTRIES].
+    pub fn new(host: &BrowserHost) -> NetworkLog {
+        Self::with_max_entries(host, DEFAULT_MAX_ENTRIES)
+    }
+    pub fn with_max_entries(host: &BrowserHost, max_entries: usize) -> NetworkLog {
+        let session = DevToolsSession::new(host);
+        let state = Arc::new(LogState {
+            session: session.clone(),
+            max_entries,
+            entries: Mutex::new(Vec::new()),
+        });
+        // The listeners are owned by the session, which is owned by the state.
+        let listen = |method: &str, f: fn(&LogState, &DictionaryValue)| {
+            let weak: Weak<LogState> = Arc::downgrade(&state);
+            session.add_event_listener(method, move |_, params| {
+                if let (Some(state), StoredValue::Dictionary(params)) = (weak.upgrade(), params) {
+                    f(&state, params);
+                }
+            });
+        };
+        listen("Network.requestWillBeSent", Self::request_will_be_sent);
+        listen("Network.responseReceived", |state, params| {
+            if let Some(response) = params.try_get_dictionary("response") {
+                state.update(&params.get_string("requestId"), |entry| entry.set_response(&response));
+            }
+        });
+        listen("Network.dataReceived", |state, params| {
+            let length = params.get_int("dataLength") as i64;
+            state.update(&params.get_string("requestId"), |entry| entry.body_size += length);
+        });
+        listen("Network.loadingFinished", |state, params| {
+            let timestamp = params.get_double("timestamp");
+            let transfer_size = params.get_double("encodedDataLength") as i64;
+            state.update(&params.get_string("requestId"), |entry| {
+                entry.transfer_size = Some(transfer_size);
+                entry.finish(timestamp);
+            });
+        });
+        listen("Network.loadingFailed", |state, params| {
+            let timestamp = params.get_double("timestamp");
+            let error = params.get_string("errorText");
+            state.update(&params.get_string("requestId"), |entry| {
+                entry.error = Some(error);
+                entry.finish(timestamp);
+            });
+        });
+        session.send("Network.enable", None);
+        NetworkLog(state)
+    }
+    /// Returns the recorded requests, oldest first.
+    pub fn entries(&self) -> Vec<NetworkEntry> {
+        self.0.entries.lock().clone()
+    }
+    pub fn len(&self) -> usize {
+        self.0.entries.lock().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.entries.lock().is_empty()
+    }
+    pub fn clear(&self) {
+        self.0.entries.lock().clear();
+    }
+    /// Returns the log as a HAR `log` object wrapped in its root object.
+    pub fn export_value(&self) -> StoredValue {
+        let entries = ListValue::new();
+        for (index, entry) in self.0.entries.lock().iter().enumerate() {
+            entries.set_dictionary(index, entry.to_har());
+        }
+        let creator = DictionaryValue::new();
+        creator.insert_string("name", "cef-rs");
+        creator.insert_string("version", env!("CARGO_PKG_VERSION"));
+        let log = DictionaryValue::new();
+        log.insert_string("version", "1.2");
+        log.insert_dictionary("creator", creator);
+        log.insert_list("pages", ListValue::new());
+        log.insert_list("entries", entries);
+        let har = DictionaryValue::new();
+        har.insert_dictionary("log", log);
+        StoredValue::Dictionary(har)
+    }
+    /// Serializes the log as HAR JSON.
+    pub fn export(&self) -> String {
+        self.export_value().to_json().unwrap_or_default()
+    }
+
+    fn request_will_be_sent(state: &LogState, params: &DictionaryValue) {
+        let request_id = params.get_string("requestId");
+        let request = match params.try_get_dictionary("request") {
+            Some(request) => request,
+            None => return,
+        };
+        // A redirect finishes the previous request of the same identifier.
+        if let Some(response) = params.try_get_dictionary("redirectResponse") {
+            let timestamp = params.get_double("timestamp");
+            let url = request.get_string("url");
+            state.update(&request_id, |entry| {
+                entry.set_response(&response);
+                entry.redirect_url = url;
+                entry.finish(timestamp);
+            });
+        }
+        let mut entries = state.entries.lock();
+        if entries.len() >= state.max_entries {
+            let excess = entries.len() + 1 - state.max_entries;
+            entries.drain(..excess.min(entries.len()));
+        }
+        if state.max_entries > 0 {
+            entries.push(NetworkEntry::new(&request_id, params, &request));
+        }
+    }
+}
+
+fn headers(headers: Option<DictionaryValue>) -> Vec<(String, String)> {
+    let headers = match headers {
+        Some(headers) => headers,
+        None => return Vec::new(),
+    };
+    let mut result = Vec::new();
+    for (name, value) in &headers {
+        if let StoredValue::String(value) = value {
+            // DevTools joins repeated headers with newlines.
+            result.extend(value.split('\n').map(|value| (name.clone(), value.to_owned())));
+        }
+    }
+    result
+}
+
+fn har_headers(headers: &[(String, String)]) -> ListValue {
+    let list = ListValue::new();
+    for (index, (name, value)) in headers.iter().enumerate() {
+        let header = DictionaryValue::new();
+        header.insert_string("name", name);
+        header.insert_string("value", value);
+        list.set_dictionary(index, header);
+    }
+    list
+}
+
+/// Converts a DevTools `ResourceTiming` into HAR timings. The offsets in
+/// `timing` are milliseconds relative to its `requestTime` in seconds.
+fn timings(timing: &DictionaryValue, end_timestamp: f64) -> RequestTimings {
+    let get = |key: &str| timing.get_double(key);
+    let phase = |start: f64, end: f64| if start >= 0.0 && end >= start { Some(end - start) } else { None };
+    let first_start = [get("dnsStart"), get("connectStart"), get("sendStart")]
+        .iter()
+        .cloned()
+        .find(|start| *start >= 0.0);
+    let end = (end_timestamp - get("requestTime")) * 1000.0;
+    RequestTimings {
+        blocked: first_start,
+        dns: phase(get("dnsStart"), get("dnsEnd")),
+        connect: phase(get("connectStart"), get("connectEnd")),
+        ssl: phase(get("sslStart"), get("sslEnd")),
+        send: (get("sendEnd") - get("sendStart")).max(0.0),
+        wait: (get("receiveHeadersEnd") - get("sendEnd")).max(0.0),
+        receive: (end - get("receiveHeadersEnd")).max(0.0),
+    }
+}
@@ -0,0 +1,318 @@
+//! Showing Web Notifications through the application.
+//!
+//! Windowless browsers have no notification UI, and CEF 85 has no permission
+//! handler pages could ask, so `new Notification()` goes nowhere. A
+//! [NotificationBridge] replaces the page's `Notification` with a shim that
+//! asks a [NotificationPresenter] implemented by the application for
+//! permission and hands it the notifications to show, with their icon
+//! already downloaded:
+//!
+//! ```ignore
+//! struct Toasts;
+//!
+//! impl NotificationPresenter for Toasts {
+//!     fn permission(&self, origin: &str) -> NotificationPermission {
+//!         if origin == "https://app.example" { NotificationPermission::Granted } else { NotificationPermission::Denied }
+//!     }
+//!     fn show(&self, notification: Notification, icon: Option<Image>, events: NotificationEvents) {
+//!         let toast = native_toast(&notification.title, &notification.body, icon);
+//!         toast.on_click(move || events.click());
+//!         events.shown();
+//!     }
+//! }
+//!
+//! let bridge = NotificationBridge::attach(&browser, Toasts);
+//! ```
+//!
+//! `Notification.permission` reads `default` until the presenter's answer
+//! arrived, shortly after the document started loading.
+
+use crate::{
+    browser::Browser,
+    devtools::DevToolsSession,
+    image::Image,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+};
+use std::sync::Arc;
+
+/// The name of the DevTools binding requests are reported through.
+const BINDING_NAME: &str = "__cefNotificationRequest";
+
+/// The largest icon size requested, in device-independent pixels.
+const MAX_ICON_SIZE: u32 = 256;
+
+const SHIM_SOURCE: &str = "\
+var notify = window.__cefNotificationRequest;\n\
+if (typeof notify !== 'function' || window.__cefNotifications) return;\n\
+try { delete window.__cefNotificationRequest; } catch (e) {}\n\
+var permission = 'default', pending = {}, nextRequest = 1, live = {}, nextId = 1;\n\
+function absolute(url) { try { return url ? new URL(url, location.href).href : ''; } catch (e) { return ''; } }\n\
+class Notification extends EventTarget {\n\
+  constructor(title, options) {\n\
+    super();\n\
+    if (arguments.length < 1) throw new TypeError(\"Failed to construct 'Notification': 1 argument required, but only 0 present.\");\n\
+    options = options || {};\n\
+    var id = nextId++, self = this;\n\
+    Object.defineProperties(this, {\n\
+      _id: { value: id },\n\
+      title: { value: String(title), enumerable: true },\n\
+      body: { value: String(options.body || ''), enumerable: true },\n\
+      icon: { value: absolute(options.icon), enumerable: true },\n\
+      tag: { value: String(options.tag || ''), enumerable: true },\n\
+      data: { value: options.data === undefined ? null : options.data, enumerable: true },\n\
+      lang: { value: String(options.lang || ''), enumerable: true },\n\
+      dir: { value: options.dir || 'auto', enumerable: true },\n\
+      silent: { value: !!options.silent, enumerable: true },\n\
+      requireInteraction: { value: !!options.requireInteraction, enumerable: true }\n\
+    });\n\
+    this.onclick = this.onshow = this.onclose = this.onerror = null;\n\
+    if (permission !== 'granted') { setTimeout(function() { self._fire('error'); }); return; }\n\
+    live[id] = this;\n\
+    notify(JSON.stringify({ op: 'show', id: id, origin: location.origin, title: this.title, body: this.body, icon: this.icon,\n\
+      tag: this.tag, silent: this.silent, requireInteraction: this.requireInteraction }));\n\
+  }\n\
+  close() { if (live[this._id]) notify(JSON.stringify({ op: 'close', id: this._id })); }\n\
+  _fire(type) {\n\
+    var event = new Event(type, { cancelable: type === 'click' });\n\
+    this.dispatchEvent(event);\n\
+    var handler = this['on' + type];\n\
+    if (typeof handler === 'function') handler.call(this, event);\n\
+  }\n\
+  static get permission() { return permission; }\n\
+  static get maxActions() { return 0; }\n\
+  static requestPermission(callback) {\n\
+    var id = nextRequest++;\n\
+    return new Promise(function(resolve) {\n\
+      pending[id] = function(result) { if (typeof callback === 'function') callback(result); resolve(result); };\n\
+      notify(JSON.stringify({ op: 'request', id: id, origin: location.origin }));\n\
+    });\n\
+  }\n\
+}\n\
+Object.defineProperty(window, 'Notification', { configurable: true, writable: true, value: Notification });\n\
+window.__cefNotifications = {\n\
+  permission: function(result) { permission = result; },\n\
+  answer: function(id, result) { permission = result; var resolve = pending[id]; delete pending[id]; if (resolve) resolve(result); },\n\
+  event: function(id, type) {\n\
+    var notification = live[id];\n\
+    if (!notification) return;\n\
+    if (type === 'close' || type === 'error') delete live[id];\n\
+    notification._fire(type);\n\
+  }\n\
+};\n\
+notify(JSON.stringify({ op: 'query', origin: location.origin }));";
+
+/// Whether an origin may show notifications, as reported by
+/// `Notification.permission`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotificationPermission {
+    /// Not decided yet; pages may ask with `Notification.requestPermission()`.
+    Default,
+    Granted,
+    Denied,
+}
+
+impl NotificationPermission {
+    fn to_js(self) -> &'static str {
+        match self {
+            NotificationPermission::Default => "default",
+            NotificationPermission::Granted => "granted",
+            NotificationPermission::Denied => "denied",
+        }
+    }
+}
+
+/// A notification created by a page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    /// Identifies the notification within its document.
+    pub id: i32,
+    /// The origin of the page, e.g. `https://example.com`.
+    pub origin: String,
+    pub title: String,
+    pub body: String,
+    /// The absolute URL of the icon, if the page gave one.
+    pub icon_url: Option<String>,
+    /// Notifications with the same non-empty tag replace each other.
+    pub tag: String,
+    /// Set if the notification should make no sound or vibration.
+    pub silent: bool,
+    /// Set if the notification should stay until the user dismisses it.
+    pub require_interaction: bool,
+}
+
+impl Notification {
+    fn from_dictionary(request: &DictionaryValue) -> Notification {
+        let icon_url = request.get_string("icon");
+        Notification {
+            id: request.get_int("id"),
+            origin: request.get_string("origin"),
+            title: request.get_string("title"),
+            body: request.get_string("body"),
+            icon_url: if icon_url.is_empty() { None } else { Some(icon_url) },
+            tag: request.get_string("tag"),
+            silent: request.get_bool("silent"),
+            require_interaction: request.get_bool("requireInteraction"),
+        }
+    }
+}
+
+/// Shows the notifications of the pages of a [NotificationBridge]. Methods
+/// are called on the browser process UI thread and must not block.
+pub trait NotificationPresenter: 'static + Send + Sync {
+    /// Returns the permission of `origin`, reported to pages before they ask.
+    fn permission(&self, origin: &str) -> NotificationPermission {
+        NotificationPermission::Default
+    }
+    /// Called when a page asks for permission. The default grants what
+    /// [NotificationPresenter::permission] returns, denying undecided origins.
+    fn request_permission(&self, origin: &str) -> NotificationPermission {
+        match self.permission(origin) {
+            NotificationPermission::Default => NotificationPermission::Denied,
+            permission => permission,
+        }
+    }
+    /// Shows `notification`, with its icon if it has one that could be
+    /// downloaded. Only called for origins with permission.
+    fn show(&self, notification: Notification, icon: Option<Image>, events: NotificationEvents);
+    /// Hides the notification the page closed.
+    fn close(&self, id: i32) {}
+}
+
+/// Reports what happened to a [Notification] to the page, where it fires the
+/// events of the `Notification` object.
+#[derive(Clone)]
+pub struct NotificationEvents {
+    browser: Browser,
+    id: i32,
+}
+
+impl NotificationEvents {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+    /// Fires `show`, once the notification is visible.
+    pub fn shown(&self) {
+        self.fire("show");
+    }
+    /// Fires `click`. Pages usually focus their window in response, so the
+    /// application should bring it to the front.
+    pub fn click(&self) {
+        self.fire("click");
+    }
+    /// Fires `close`, after the user dismissed the notification or it timed
+    /// out. No further events are delivered.
+    pub fn closed(&self) {
+        self.fire("close");
+    }
+    /// Fires `error`, if the notification couldn't be shown.
+    pub fn error(&self) {
+        self.fire("error");
+    }
+
+    fn fire(&self, event: &str) {
+        let code = format!(
+            "window.__cefNotifications && window.__cefNotifications.event({}, '{}');",
+            self.id, event
+        );
+        self.browser.get_main_frame().execute_java_script(&code, "", 0);
+    }
+}
+
+/// Routes the Notifications API of one browser's main frame to a
+/// [NotificationPresenter]. See the [module documentation](self).
+pub struct NotificationBridge {
+    session: DevToolsSession,
+    scripts: UserScripts,
+    script_id: UserScriptId,
+}
+
+impl NotificationBridge {
+    /// Installs the shim into the documents of `browser`.
+    pub fn attach(browser: &Browser, presenter: impl NotificationPresenter) -> NotificationBridge {
+        let presenter = Arc::new(presenter);
+        let session = DevToolsSession::new(&browser.get_host());
+        session.add_event_listener("Runtime.bindingCalled", move |browser, params| {
+            let params = match params {
+                StoredValue::Dictionary(params) if params.get_string("name") == BINDING_NAME => params,
+                _ => return,
+            };
+            let request = match StoredValue::from_json(&params.get_string("payload")) {
+                Some(StoredValue::Dictionary(request)) => request,
+                _ => return,
+            };
+            let origin = request.get_string("origin");
+            let frame = browser.get_main_frame();
+            match &request.get_string("op")[..] {
+                "query" => {
+                    let code = format!(
+                        "window.__cefNotifications && window.__cefNotifications.permission('{}');",
+                        presenter.permission(&origin).to_js()
+                    );
+                    frame.execute_java_script(&code, "", 0);
+                }
+                "request" => {
+                    let code = format!(
+                        "window.__cefNotifications && window.__cefNotifications.answer({}, '{}');",
+                        request.get_int("id"),
+                        presenter.request_permission(&origin).to_js()
+                    );
+                    frame.execute_java_script(&code, "", 0);
+                }
+                "show" => {
+                    let notification = Notification::from_dictionary(&request);
+                    let events = NotificationEvents {
+                        browser: browser.clone(),
+                        id: notification.id,
+                    };
+                    // The page's permission may be stale, e.g. after the
+                    // user revoked it.
+                    if presenter.permission(&notification.origin) == NotificationPermission::Denied {
+                        return events.error();
+                    }
+                    match notification.icon_url.clone() {
+                        Some(icon_url) => {
+                            let presenter = presenter.clone();
+                            browser.get_host().download_image(&icon_url, false, MAX_ICON_SIZE, false, move |_, _, icon| {
+                                presenter.show(notification, icon, events);
+                            });
+                        }
+                        None => presenter.show(notification, None, events),
+                    }
+                }
+                "close" => {
+                    let id = request.get_int("id");
+                    presenter.close(id);
+                    NotificationEvents {
+                        browser: browser.clone(),
+                        id,
+                    }.closed();
+                }
+                _ => (),
+            }
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        let script = UserScript::new(SHIM_SOURCE);
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        NotificationBridge {
+            session,
+            scripts,
+            script_id,
+        }
+    }
+}
+
+impl Drop for NotificationBridge {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script_id);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
@@ -0,0 +1,258 @@
+//! Attaching the app's credentials to its own backend only.
+//!
+//! Adding an `Authorization` header with a
+//! [HeaderInjector](crate::header_injector::HeaderInjector) glob like
+//! `https://api.example.com*` is easy to get wrong: the glob also matches
+//! `https://api.example.com.evil.test/`. An [OriginAuthenticator] compares
+//! exact origins instead, removes its credential again when a request is
+//! redirected to another origin, and asks the application for a new token
+//! when the backend answers with 401, retrying the request once with it:
+//!
+//! ```ignore
+//! let auth = OriginAuthenticator::new(&["https://api.example.com"], Credential::Bearer(token))
+//!     .on_unauthorized(|url, stale| refresh_token_blocking(stale).map(Credential::Bearer));
+//! // in get_resource_request_handler:
+//! Some(ResourceRequestHandler::new(auth.clone()))
+//! ```
+
+use crate::{
+    browser::Browser,
+    frame::Frame,
+    request::Request,
+    resource_request_handler::ResourceRequestHandlerCallbacks,
+    response::Response,
+    url_request::{RequestCallback, URLRequestStatus},
+    ReturnValue,
+};
+use parking_lot::Mutex;
+use std::{collections::HashSet, sync::Arc};
+
+/// How the token is attached to requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// `Authorization: Bearer <token>`.
+    Bearer(String),
+    /// A header with the given name and value.
+    Header(String, String),
+    /// A cookie with the given name and value, added to the `Cookie` header
+    /// without storing it in the cookie jar, so pages can't read it.
+    Cookie(String, String),
+}
+
+impl Credential {
+    fn header_name(&self) -> &str {
+        match self {
+            Credential::Bearer(_) => "Authorization",
+            Credential::Header(name, _) => name,
+            Credential::Cookie(..) => "Cookie",
+        }
+    }
+    /// Returns true if `request` carries this credential.
+    fn is_attached_to(&self, request: &Request) -> bool {
+        let value = request.get_header_by_name(self.header_name()).unwrap_or_default();
+        match self {
+            Credential::Bearer(token) => value == format!("Bearer {}", token),
+            Credential::Header(_, expected) => value == *expected,
+            Credential::Cookie(name, expected) => value.split(';').any(|cookie| cookie.trim() == format!("{}={}", name, expected)),
+        }
+    }
+    fn attach_to(&self, request: &Request) {
+        match self {
+            Credential::Bearer(token) => request.set_header_by_name("Authorization", &format!("Bearer {}", token), true),
+            Credential::Header(name, value) => request.set_header_by_name(name, value, true),
+            Credential::Cookie(name, value) => {
+                let cookie = format!("{}={}", name, value);
+                let cookies = request.get_header_by_name("Cookie").unwrap_or_default();
+                let cookies = cookies
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|existing| !existing.is_empty() && !existing.starts_with(&format!("{}=", name)))
+                    .chain(std::iter::once(&cookie[..]))
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                request.set_header_by_name("Cookie", &cookies, true);
+            }
+        }
+    }
+    /// Removes the credential from `request`, leaving other cookies alone.
+    fn detach_from(&self, request: &Request) {
+        let mut headers = request.get_header_map();
+        let name = self.header_name().to_ascii_lowercase();
+        let key = match headers.keys().find(|key| key.to_ascii_lowercase() == name) {
+            Some(key) => key.clone(),
+            None => return,
+        };
+        if let Credential::Cookie(cookie_name, _) = self {
+            let prefix = format!("{}=", cookie_name);
+            let cookies = headers[&key]
+                .iter()
+                .flat_map(|value| value.split(';'))
+                .map(str::trim)
+                .filter(|cookie| !cookie.is_empty() && !cookie.starts_with(&prefix))
+                .collect::<Vec<_>>()
+                .join("; ");
+            if cookies.is_empty() {
+                headers.remove(&key);
+            } else {
+                headers.insert(key, vec![cookies]);
+            }
+        } else {
+            headers.remove(&key);
+        }
+        request.set_header_map(&headers);
+    }
+}
+
+/// Returns the origin of `url` as `scheme://host[:port]` in lowercase, without
+/// credentials and default ports.
+fn origin_of(url: &str) -> Option<String> {
+    let scheme_end = url.find("://")?;
+    let scheme = url[..scheme_end].to_ascii_lowercase();
+    let rest = &url[scheme_end + 3..];
+    let authority = &rest[..rest.find(|c| c == '/' || c == '?' || c == '#').unwrap_or(rest.len())];
+    let host_port = authority.rsplit('@').next()?.to_ascii_lowercase();
+    let host_port = match host_port.rfind(':') {
+        Some(colon) if !host_port.ends_with(']') => {
+            let default_port = match &scheme[..] {
+                "http" | "ws" => "80",
+                "https" | "wss" => "443",
+                _ => "",
+            };
+            if &host_port[colon + 1..] == default_port {
+                host_port[..colon].to_owned()
+            } else {
+                host_port
+            }
+        }
+        _ => host_port,
+    };
+    if host_port.is_empty() {
+        return None;
+    }
+    Some(format!("{}://{}", scheme, host_port))
+}
+
+type UnauthorizedCallback = dyn 'static + Send + Sync + Fn(&str, &Credential) -> Option<Credential>;
+
+struct AuthState {
+    credential: Credential,
+    /// The requests retried after a 401, which aren't retried again.
+    retried: HashSet<u64>,
+}
+
+/// Attaches a [Credential] to the requests of a set of origins. See the
+/// [module documentation](self).
+///
+/// Cloning the authenticator is cheap; clones share the credential.
+#[derive(Clone)]
+pub struct OriginAuthenticator {
+    origins: Arc<Vec<String>>,
+    state: Arc<Mutex<AuthState>>,
+    on_unauthorized: Option<Arc<UnauthorizedCallback>>,
+}
+
+impl OriginAuthenticator {
+    /// Creates an authenticator for `origins`, e.g. `https://api.example.com`.
+    /// Origins that can't be parsed are ignored.
+    pub fn new(origins: &[&str], credential: Credential) -> OriginAuthenticator {
+        OriginAuthenticator {
+            origins: Arc::new(origins.iter().filter_map(|origin| origin_of(origin)).collect()),
+            state: Arc::new(Mutex::new(AuthState {
+                credential,
+                retried: HashSet::new(),
+            })),
+            on_unauthorized: None,
+        }
+    }
+    /// Sets the function asked for a new credential when a request carrying
+    /// the current one is answered with 401. It receives the URL and the
+    /// rejected credential and is called on the IO thread, so it should answer
+    /// quickly; return None to let the 401 through.
+    pub fn on_unauthorized(mut self, f: impl 'static + Send + Sync + Fn(&str, &Credential) -> Option<Credential>) -> Self {
+        self.on_unauthorized = Some(Arc::new(f));
+        self
+    }
+    /// Replaces the credential, e.g. after the application refreshed it.
+    pub fn set_credential(&self, credential: Credential) {
+        self.state.lock().credential = credential;
+    }
+    pub fn credential(&self) -> Credential {
+        self.state.lock().credential.clone()
+    }
+    /// Returns true if requests to `url` get the credential.
+    pub fn is_authorized_url(&self, url: &str) -> bool {
+        match origin_of(url) {
+            Some(origin) => self.origins.iter().any(|allowed| *allowed == origin),
+            None => false,
+        }
+    }
+    /// Attaches the credential to `request` if it goes to one of the origins,
+    /// and removes it otherwise.
+    pub fn apply_to_request(&self, request: &Request) {
+        let credential = self.credential();
+        if self.is_authorized_url(&request.get_url()) {
+            credential.attach_to(request);
+        } else if credential.is_attached_to(request) {
+            // A redirect away from the app's origins.
+            credential.detach_from(request);
+        }
+    }
+}
+
+impl ResourceRequestHandlerCallbacks for OriginAuthenticator {
+    fn on_before_resource_load(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        callback: RequestCallback,
+    ) -> ReturnValue {
+        self.apply_to_request(&request);
+        ReturnValue::Continue
+    }
+    fn on_resource_response(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        response: Response,
+    ) -> bool {
+        if response.get_status() != 401 || !self.is_authorized_url(&request.get_url()) {
+            return false;
+        }
+        let on_unauthorized = match &self.on_unauthorized {
+            Some(on_unauthorized) => on_unauthorized,
+            None => return false,
+        };
+        let stale = {
+            let mut state = self.state.lock();
+            if !state.retried.insert(request.get_identifier()) {
+                return false;
+            }
+            state.credential.clone()
+        };
+        let fresh = if stale.is_attached_to(&request) {
+            match on_unauthorized(&request.get_url(), &stale) {
+                Some(fresh) => fresh,
+                None => return false,
+            }
+        } else {
+            // The credential changed while the request was in flight.
+            stale
+        };
+        self.set_credential(fresh.clone());
+        fresh.attach_to(&request);
+        true
+    }
+    fn on_resource_load_complete(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        response: Response,
+        status: URLRequestStatus,
+        received_content_length: i64,
+    ) {
+        self.state.lock().retried.remove(&request.get_identifier());
+    }
+}
@@ -0,0 +1,268 @@
+//! Controlling how PDF documents are handled.
+//!
+//! CEF shows PDFs inline with Chromium's PDF viewer extension, which requires
+//! [BrowserSettings::plugins](crate::browser::BrowserSettings::plugins) to be
+//! enabled and extensions not to be disabled with the "disable-extensions"
+//! switch. [Settings::pdf_viewer](crate::settings::Settings::pdf_viewer)
+//! turns the viewer off globally, in which case PDFs are downloaded.
+//!
+//! For per-document decisions, return a [PdfInterceptor] from
+//! [RequestHandlerCallbacks::get_resource_request_handler](crate::client::request_handler::RequestHandlerCallbacks::get_resource_request_handler):
+//!
+//! ```ignore
+//! let interceptor = PdfInterceptor::new(|load| if load.url.starts_with("https://reports.example/") {
+//!     PdfHandling::Inline
+//! } else {
+//!     PdfHandling::Download
+//! })
+//! .on_download(|download| save_pdf(&download.url, &download.data));
+//! ```
+//!
+//! Downloaded documents are taken from the response the frame received, so
+//! the request isn't sent twice; the frame itself is left with an empty
+//! document.
+
+use crate::{
+    browser::Browser,
+    frame::Frame,
+    request::{Request, ResourceType},
+    resource_request_handler::ResourceRequestHandlerCallbacks,
+    response::Response,
+    url_request::{RequestCallback, ResponseFilter, ResponseFilterCallbacks, ResponseFilterStatus, URLRequestStatus},
+    ReturnValue,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+/// The ID of Chromium's built-in PDF viewer extension.
+pub const PDF_VIEWER_EXTENSION_ID: &str = "mhjfbmdgcfjbbpaeojofohoefgiehjai";
+
+/// The switch disabling the PDF viewer extension.
+pub(crate) const DISABLE_PDF_EXTENSION_SWITCH: &str = "disable-pdf-extension";
+
+lazy_static::lazy_static! {
+    /// The frames whose current document is a PDF shown inline, by browser
+    /// and frame identifier.
+    static ref PDF_FRAMES: Mutex<HashSet<(i32, i64)>> = Mutex::new(HashSet::new());
+}
+
+/// Returns true if `url` belongs to the PDF viewer extension.
+pub fn is_pdf_viewer_url(url: &str) -> bool {
+    url.strip_prefix("chrome-extension://")
+        .map_or(false, |rest| rest.starts_with(PDF_VIEWER_EXTENSION_ID))
+}
+
+/// Returns true if `mime_type` is a PDF MIME type.
+pub fn is_pdf_mime_type(mime_type: &str) -> bool {
+    let mime_type = mime_type.split(';').next().unwrap_or("").trim();
+    mime_type.eq_ignore_ascii_case("application/pdf") || mime_type.eq_ignore_ascii_case("application/x-pdf")
+}
+
+/// Returns true if `frame` shows the PDF viewer, either because it is the
+/// viewer extension itself or because a [PdfInterceptor] let a PDF load into
+/// it inline.
+pub(crate) fn is_pdf_viewer_frame(frame: &Frame) -> bool {
+    if is_pdf_viewer_url(&frame.get_url()) {
+        return true;
+    }
+    let browser_id = frame.get_browser().get_identifier();
+    match frame.get_identifier() {
+        Some(frame_id) => PDF_FRAMES.lock().contains(&(browser_id, frame_id)),
+        None => false,
+    }
+}
+
+/// How a PDF document is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfHandling {
+    /// Show the PDF in the viewer, if it is enabled.
+    Inline,
+    /// Hand the PDF to the [PdfInterceptor::on_download] handler instead.
+    Download,
+}
+
+/// A PDF document about to be shown, see [PdfInterceptor].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdfLoad {
+    pub url: String,
+    pub is_main_frame: bool,
+    /// Set if the server asked for the document to be downloaded, in which
+    /// case it is downloaded regardless of the decision.
+    pub is_attachment: bool,
+}
+
+/// A PDF document downloaded by a [PdfInterceptor].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PdfDownload {
+    pub url: String,
+    pub mime_type: String,
+    /// The body of the response.
+    pub data: Vec<u8>,
+}
+
+struct PendingDownload {
+    url: String,
+    mime_type: String,
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+/// Decides between viewing and downloading PDF documents loaded into frames.
+/// See the [module documentation](self).
+pub struct PdfInterceptor {
+    decide: Box<dyn 'static + Send + Sync + Fn(&PdfLoad) -> PdfHandling>,
+    on_download: Option<Box<dyn 'static + Send + Sync + Fn(PdfDownload)>>,
+    /// The documents being downloaded, by request identifier.
+    downloads: Mutex<HashMap<u64, PendingDownload>>,
+}
+
+impl PdfInterceptor {
+    pub fn new(decide: impl 'static + Send + Sync + Fn(&PdfLoad) -> PdfHandling) -> PdfInterceptor {
+        PdfInterceptor {
+            decide: Box::new(decide),
+            on_download: None,
+            downloads: Mutex::new(HashMap::new()),
+        }
+    }
+    /// Creates an interceptor that always handles PDFs the same way.
+    pub fn always(handling: PdfHandling) -> PdfInterceptor {
+        Self::new(move |_| handling)
+    }
+    /// Calls `handler` with documents decided to be downloaded, once their
+    /// response is complete. Called on the IO thread. Without a handler,
+    /// [PdfHandling::Download] shows documents inline.
+    pub fn on_download(mut self, handler: impl 'static + Send + Sync + Fn(PdfDownload)) -> Self {
+        self.on_download = Some(Box::new(handler));
+        self
+    }
+}
+
+/// Collects the body of a downloaded document and passes nothing on to the
+/// frame.
+struct CaptureFilter(Arc<Mutex<Vec<u8>>>);
+
+impl ResponseFilterCallbacks for CaptureFilter {
+    fn init_filter(&self) -> bool {
+        true
+    }
+    fn filter(
+        &self,
+        data_in: &[u8],
+        data_in_read: &mut usize,
+        data_out: &mut [u8],
+        data_out_written: &mut usize,
+    ) -> ResponseFilterStatus {
+        self.0.lock().extend_from_slice(data_in);
+        *data_in_read = data_in.len();
+        *data_out_written = 0;
+        ResponseFilterStatus::Done
+    }
+}
+
+impl ResourceRequestHandlerCallbacks for PdfInterceptor {
+    fn on_before_resource_load(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        callback: RequestCallback,
+    ) -> ReturnValue {
+        ReturnValue::Continue
+    }
+    fn on_resource_response(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        response: Response,
+    ) -> bool {
+        let resource_type = request.get_resource_type();
+        if resource_type != ResourceType::MainFrame && resource_type != ResourceType::SubFrame {
+            return false;
+        }
+        let (browser, frame_id) = match (browser, frame.and_then(|frame| frame.get_identifier())) {
+            (Some(browser), Some(frame_id)) => (browser, frame_id),
+            _ => return false,
+        };
+        let key = (browser.get_identifier(), frame_id);
+        if !is_pdf_mime_type(&response.get_mime_type()) {
+            PDF_FRAMES.lock().remove(&key);
+            return false;
+        }
+        let load = PdfLoad {
+            url: request.get_url(),
+            is_main_frame: resource_type == ResourceType::MainFrame,
+            is_attachment: response.get_header_by_name("Content-Disposition").trim_start().to_ascii_lowercase().starts_with("attachment"),
+        };
+        if load.is_attachment {
+            return false;
+        }
+        match (self.decide)(&load) {
+            PdfHandling::Inline => {
+                PDF_FRAMES.lock().insert(key);
+            }
+            PdfHandling::Download if self.on_download.is_none() => {
+                log::warn!("no PDF download handler set, showing {} inline", load.url);
+                PDF_FRAMES.lock().insert(key);
+            }
+            PdfHandling::Download => {
+                PDF_FRAMES.lock().remove(&key);
+                // Responses can't be changed here, so the body is taken from
+                // the frame by a response filter.
+                self.downloads.lock().insert(
+                    request.get_identifier(),
+                    PendingDownload {
+                        url: load.url,
+                        mime_type: response.get_mime_type(),
+                        data: Arc::new(Mutex::new(Vec::new())),
+                    },
+                );
+            }
+        }
+        false
+    }
+    fn get_resource_response_filter(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        response: Response,
+    ) -> Option<ResponseFilter> {
+        let downloads = self.downloads.lock();
+        let download = downloads.get(&request.get_identifier())?;
+        Some(ResponseFilter::new(CaptureFilter(download.data.clone())))
+    }
+    fn on_resource_load_complete(
+        &self,
+        browser: Option<Browser>,
+        frame: Option<Frame>,
+        request: Request,
+        response: Response,
+        status: URLRequestStatus,
+        received_content_length: i64,
+    ) {
+        let download = match self.downloads.lock().remove(&request.get_identifier()) {
+            Some(download) => download,
+            None => return,
+        };
+        if status != URLRequestStatus::Success {
+            log::warn!("PDF download of {} failed", download.url);
+            return;
+        }
+        if let Some(on_download) = &self.on_download {
+            let data = std::mem::take(&mut *download.data.lock());
+            on_download(PdfDownload {
+                url: download.url,
+                mime_type: download.mime_type,
+                data,
+            });
+        }
+    }
+}
+
+/// Drops the PDF frames recorded for `browser_id`.
+pub(crate) fn forget_browser(browser_id: i32) {
+    PDF_FRAMES.lock().retain(|(id, _)| *id != browser_id);
+}
@@ -0,0 +1,209 @@
+//! Pointer lock for windowless browsers.
+//!
+//! Windowless browsers don't support the Pointer Lock API: the renderer has no
+//! window to confine the cursor to, so `requestPointerLock()` always fails.
+//! [PointerLock] replaces the API in the page with a shim that forwards
+//! requests to the application. Once the application grants a request it
+//! hides and confines the cursor itself and reports relative motion with
+//! [PointerLock::send_relative_motion], which the shim delivers as `mousemove`
+//! events carrying `movementX` and `movementY`, as FPS-style content expects:
+//!
+//! ```ignore
+//! let lock = PointerLock::attach(&browser, |_, request| match request {
+//!     PointerLockRequest::Lock => proxy.send_event(AppEvent::GrabCursor).unwrap(),
+//!     PointerLockRequest::Unlock => proxy.send_event(AppEvent::ReleaseCursor).unwrap(),
+//! });
+//! // After grabbing the cursor:
+//! lock.grant();
+//! // For every raw mouse motion while grabbed:
+//! lock.send_relative_motion(dx, dy, modifiers);
+//! // When the window loses focus or Escape is pressed:
+//! lock.release();
+//! ```
+//!
+//! Only the main frame is served. The synthetic events have `isTrusted` set
+//! to false.
+
+use crate::{
+    browser::Browser,
+    devtools::DevToolsSession,
+    events::EventFlags,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// The name of the DevTools binding requests are reported through.
+const BINDING_NAME: &str = "__cefPointerLockRequest";
+
+const SHIM_SOURCE: &str = "\
+var notify = window.__cefPointerLockRequest;\n\
+if (typeof notify !== 'function' || window.__cefPointerLock) return;\n\
+try { delete window.__cefPointerLockRequest; } catch (e) {}\n\
+var pending = null, locked = null;\n\
+function fire(name) { document.dispatchEvent(new Event(name)); }\n\
+Object.defineProperty(Document.prototype, 'pointerLockElement', { configurable: true, get: function() { return locked; } });\n\
+Element.prototype.requestPointerLock = function() { pending = this; notify('lock'); };\n\
+Document.prototype.exitPointerLock = function() {\n\
+  if (!locked) return;\n\
+  locked = null; fire('pointerlockchange'); notify('unlock');\n\
+};\n\
+function swallow(e) { if (locked && e.isTrusted) e.stopImmediatePropagation(); }\n\
+document.addEventListener('mousemove', swallow, true);\n\
+window.__cefPointerLock = {\n\
+  grant: function() {\n\
+    if (!pending || !pending.isConnected) { pending = null; fire('pointerlockerror'); return; }\n\
+    locked = pending; pending = null; fire('pointerlockchange');\n\
+  },\n\
+  deny: function() { pending = null; fire('pointerlockerror'); },\n\
+  release: function() { pending = null; if (locked) { locked = null; fire('pointerlockchange'); } },\n\
+  move: function(dx, dy, buttons, shift, ctrl, alt) {\n\
+    if (!locked) return;\n\
+    locked.dispatchEvent(new MouseEvent('mousemove', {\n\
+      bubbles: true, cancelable: true, composed: true, view: window,\n\
+      movementX: dx, movementY: dy, buttons: buttons,\n\
+      shiftKey: shift, ctrlKey: ctrl, altKey: alt\n\
+    }));\n\
+  }\n\
+};";
+
+/// A request made by the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerLockRequest {
+    /// The page called `requestPointerLock()`. Answer with
+    /// [PointerLock::grant] or [PointerLock::deny].
+    Lock,
+    /// The page called `document.exitPointerLock()`. The page already
+    /// considers the pointer unlocked; release the cursor.
+    Unlock,
+}
+
+struct LockState {
+    browser: Browser,
+    locked: Mutex<bool>,
+}
+
+/// Emulates the Pointer Lock API for one browser. See the
+/// [module documentation](self). The shim stays in documents that are already
+/// loaded when this is dropped, but stops receiving answers.
+pub struct PointerLock {
+    state: Arc<LockState>,
+    session: DevToolsSession,
+    scripts: UserScripts,
+    script_id: UserScriptId,
+}
+
+impl PointerLock {
+    /// Installs the shim into the documents of `browser` and calls
+    /// `on_request` on the UI thread for every request of the page.
+    pub fn attach(
+        browser: &Browser,
+        on_request: impl 'static + Send + Sync + Fn(&Browser, PointerLockRequest),
+    ) -> PointerLock {
+        let state = Arc::new(LockState {
+            browser: browser.clone(),
+            locked: Mutex::new(false),
+        });
+        let session = DevToolsSession::new(&browser.get_host());
+        let listener_state = Arc::downgrade(&state);
+        session.add_event_listener("Runtime.bindingCalled", move |browser, params| {
+            let params = match params {
+                StoredValue::Dictionary(params) if params.get_string("name") == BINDING_NAME => params,
+                _ => return,
+            };
+            let request = match &params.get_string("payload")[..] {
+                "lock" => PointerLockRequest::Lock,
+                "unlock" => PointerLockRequest::Unlock,
+                _ => return,
+            };
+            if request == PointerLockRequest::Unlock {
+                if let Some(state) = listener_state.upgrade() {
+                    *state.locked.lock() = false;
+                }
+            }
+            on_request(browser, request);
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        let script = UserScript::new(SHIM_SOURCE);
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        PointerLock {
+            state,
+            session,
+            scripts,
+            script_id,
+        }
+    }
+    /// Returns true between [PointerLock::grant] and the pointer being
+    /// unlocked by either side.
+    pub fn is_locked(&self) -> bool {
+        *self.state.locked.lock()
+    }
+    /// Locks the pointer to the element that requested it. Call this after
+    /// hiding and confining the cursor.
+    pub fn grant(&self) {
+        *self.state.locked.lock() = true;
+        self.call("grant()");
+    }
+    /// Rejects the pending request with a `pointerlockerror` event.
+    pub fn deny(&self) {
+        self.call("deny()");
+    }
+    /// Unlocks the pointer from the application's side, e.g. because Escape
+    /// was pressed or the window lost focus, and tells the browser that mouse
+    /// capture was lost so no button stays pressed.
+    pub fn release(&self) {
+        let was_locked = std::mem::replace(&mut *self.state.locked.lock(), false);
+        self.call("release()");
+        if was_locked {
+            self.state.browser.get_host().send_capture_lost_event();
+        }
+    }
+    /// Delivers relative mouse motion to the locked element. Ignored while the
+    /// pointer isn't locked.
+    pub fn send_relative_motion(&self, dx: i32, dy: i32, modifiers: EventFlags) {
+        if !self.is_locked() || (dx == 0 && dy == 0) {
+            return;
+        }
+        let mut buttons = 0;
+        if modifiers.contains(EventFlags::LEFT_MOUSE_BUTTON) {
+            buttons |= 1;
+        }
+        if modifiers.contains(EventFlags::RIGHT_MOUSE_BUTTON) {
+            buttons |= 2;
+        }
+        if modifiers.contains(EventFlags::MIDDLE_MOUSE_BUTTON) {
+            buttons |= 4;
+        }
+        self.call(&format!(
+            "move({}, {}, {}, {}, {}, {})",
+            dx,
+            dy,
+            buttons,
+            modifiers.contains(EventFlags::SHIFT_DOWN),
+            modifiers.contains(EventFlags::CONTROL_DOWN),
+            modifiers.contains(EventFlags::ALT_DOWN),
+        ));
+    }
+
+    fn call(&self, call: &str) {
+        let code = format!("window.__cefPointerLock && window.__cefPointerLock.{};", call);
+        self.state.browser.get_main_frame().execute_java_script(&code, "", 0);
+    }
+}
+
+impl Drop for PointerLock {
+    fn drop(&mut self) {
+        self.release();
+        self.scripts.remove(self.script_id);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
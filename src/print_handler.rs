@@ -0,0 +1,255 @@
+use cef_sys::{
+    cef_browser_t, cef_print_dialog_callback_t, cef_print_handler_t, cef_print_job_callback_t,
+    cef_print_settings_t, cef_size_t, cef_string_t,
+};
+use std::{os::raw::c_int, process::Command, thread};
+
+use crate::{
+    browser::Browser,
+    printing::PrintSettings,
+    refcounted::{RefCountedPtr, Wrapper},
+    string::CefString,
+    task::{TaskRunner, ThreadId},
+    values::Size,
+};
+
+ref_counted_ptr!{
+    /// Handler for printing on Linux. The functions of this structure will be
+    /// called on the browser process UI thread.
+    pub struct PrintHandler(*mut cef_print_handler_t);
+}
+
+ref_counted_ptr!{
+    /// Callback structure for asynchronous continuation of print dialog requests.
+    pub struct PrintDialogCallback(*mut cef_print_dialog_callback_t);
+}
+
+ref_counted_ptr!{
+    /// Callback structure for asynchronous continuation of print job requests.
+    pub struct PrintJobCallback(*mut cef_print_job_callback_t);
+}
+
+impl PrintHandler {
+    pub fn new<C: PrintHandlerCallbacks>(callbacks: C) -> PrintHandler {
+        unsafe{ PrintHandler::from_ptr_unchecked(PrintHandlerWrapper(Box::new(callbacks)).wrap().into_raw()) }
+    }
+}
+
+impl PrintDialogCallback {
+    /// Continue printing with the specified `settings`.
+    pub fn cont(&self, settings: PrintSettings) {
+        unsafe{ self.0.cont.unwrap()(self.as_ptr(), settings.into_raw()) }
+    }
+    /// Cancel the printing.
+    pub fn cancel(&self) {
+        unsafe{ self.0.cancel.unwrap()(self.as_ptr()) }
+    }
+}
+
+impl PrintJobCallback {
+    /// Indicate completion of the print job.
+    pub fn cont(&self) {
+        unsafe{ self.0.cont.unwrap()(self.as_ptr()) }
+    }
+}
+
+/// Implement this trait to handle printing on Linux. Each browser will have
+/// only one print job in progress at a time.
+pub trait PrintHandlerCallbacks: 'static + Send + Sync {
+    /// Called when printing has started for the specified `browser`. This
+    /// function will be called before the other print functions and
+    /// irrespective of how printing was initiated (e.g. [BrowserHost::print],
+    /// JavaScript `window.print()` or PDF extension print button).
+    fn on_print_start(&self, browser: Browser) {}
+    /// Synchronize `settings` with client state. If `get_defaults` is true
+    /// then populate `settings` with the default print settings. Do not keep a
+    /// reference to `settings` outside of this callback.
+    fn on_print_settings(&self, browser: Browser, settings: PrintSettings, get_defaults: bool);
+    /// Show the print dialog. Execute `callback` once the dialog is dismissed.
+    /// Return true if the dialog will be displayed or false to cancel the
+    /// printing immediately.
+    fn on_print_dialog(&self, browser: Browser, has_selection: bool, callback: PrintDialogCallback) -> bool;
+    /// Send the print job to the printer. Execute `callback` once the job is
+    /// completed. Return true if the job will proceed or false to cancel the
+    /// job immediately.
+    fn on_print_job(
+        &self,
+        browser: Browser,
+        document_name: &str,
+        pdf_file_path: &str,
+        callback: PrintJobCallback,
+    ) -> bool;
+    /// Reset client state related to printing.
+    fn on_print_reset(&self, browser: Browser) {}
+    /// Return the PDF paper size in device units. Used in combination with
+    /// [BrowserHost::print_to_pdf].
+    fn get_pdf_paper_size(&self, device_units_per_inch: i32) -> Size {
+        a4_paper_size(device_units_per_inch)
+    }
+}
+
+/// Returns the size of an A4 sheet in device units.
+fn a4_paper_size(device_units_per_inch: i32) -> Size {
+    Size {
+        width: (8.27 * device_units_per_inch as f64) as i32,
+        height: (11.69 * device_units_per_inch as f64) as i32,
+    }
+}
+
+/// A [PrintHandlerCallbacks] implementation that never shows a dialog and
+/// sends every job to a printer using the CUPS `lp` command. Together with
+/// [Settings::disable_print_preview](crate::settings::Settings::disable_print_preview)
+/// this makes [BrowserHost::print] and `window.print()` print silently.
+#[derive(Debug, Clone, Default)]
+pub struct SilentPrintHandler {
+    /// The printer to print to, or `None` to use the system default printer.
+    pub printer: Option<String>,
+    /// The number of copies to print. Values below 1 print a single copy.
+    pub copies: i32,
+    /// Whether to print in landscape orientation.
+    pub landscape: bool,
+}
+
+impl SilentPrintHandler {
+    pub fn new() -> SilentPrintHandler {
+        SilentPrintHandler::default()
+    }
+    pub fn printer<T: Into<String>>(mut self, printer: T) -> Self {
+        self.printer = Some(printer.into());
+        self
+    }
+    pub fn copies(mut self, copies: i32) -> Self {
+        self.copies = copies;
+        self
+    }
+    pub fn landscape(mut self, landscape: bool) -> Self {
+        self.landscape = landscape;
+        self
+    }
+}
+
+impl PrintHandlerCallbacks for SilentPrintHandler {
+    fn on_print_settings(&self, browser: Browser, settings: PrintSettings, get_defaults: bool) {
+        if settings.is_read_only() {
+            return;
+        }
+        settings.set_device_name(self.printer.as_ref().map(|s| &**s));
+        settings.set_orientation(self.landscape);
+        settings.set_copies(self.copies.max(1));
+    }
+    fn on_print_dialog(&self, browser: Browser, has_selection: bool, callback: PrintDialogCallback) -> bool {
+        let settings = PrintSettings::new();
+        settings.set_device_name(self.printer.as_ref().map(|s| &**s));
+        settings.set_orientation(self.landscape);
+        settings.set_copies(self.copies.max(1));
+        callback.cont(settings);
+        true
+    }
+    fn on_print_job(
+        &self,
+        browser: Browser,
+        document_name: &str,
+        pdf_file_path: &str,
+        callback: PrintJobCallback,
+    ) -> bool {
+        let mut command = Command::new("lp");
+        if let Some(printer) = &self.printer {
+            command.arg("-d").arg(printer);
+        }
+        command
+            .arg("-n").arg(self.copies.max(1).to_string())
+            .arg("-t").arg(document_name)
+            .arg(pdf_file_path);
+        // `lp` waits for the print server, so it runs off the UI thread. The
+        // job is only continued once it was queued; CEF keeps the PDF until
+        // then.
+        let spawned = thread::Builder::new().name("silent-print".into()).spawn(move || {
+            match command.status() {
+                Ok(status) if status.success() => {
+                    TaskRunner::post_task_on(ThreadId::UI, move || callback.cont());
+                }
+                Ok(status) => log::error!("lp failed to queue the print job: {}", status),
+                Err(err) => log::error!("failed to run lp: {}", err),
+            }
+        });
+        spawned.is_ok()
+    }
+}
+
+struct PrintHandlerWrapper(Box<dyn PrintHandlerCallbacks>);
+
+impl Wrapper for PrintHandlerWrapper {
+    type Cef = cef_print_handler_t;
+    fn wrap(self) -> RefCountedPtr<Self::Cef> {
+        RefCountedPtr::wrap(
+            cef_print_handler_t {
+                base: unsafe { std::mem::zeroed() },
+                on_print_start: Some(Self::on_print_start),
+                on_print_settings: Some(Self::on_print_settings),
+                on_print_dialog: Some(Self::on_print_dialog),
+                on_print_job: Some(Self::on_print_job),
+                on_print_reset: Some(Self::on_print_reset),
+                get_pdf_paper_size: Some(Self::get_pdf_paper_size),
+            },
+            self,
+        )
+    }
+}
+
+cef_callback_impl!{
+    impl for PrintHandlerWrapper: cef_print_handler_t {
+        fn on_print_start(
+            &self,
+            browser: Browser: *mut cef_browser_t,
+        ) {
+            crate::printing::print_started(browser.get_identifier());
+            self.0.on_print_start(browser);
+        }
+        fn on_print_settings(
+            &self,
+            browser: Browser: *mut cef_browser_t,
+            settings: PrintSettings: *mut cef_print_settings_t,
+            get_defaults: bool: c_int,
+        ) {
+            self.0.on_print_settings(browser, settings, get_defaults);
+        }
+        fn on_print_dialog(
+            &self,
+            browser: Browser: *mut cef_browser_t,
+            has_selection: bool: c_int,
+            callback: PrintDialogCallback: *mut cef_print_dialog_callback_t,
+        ) -> c_int {
+            self.0.on_print_dialog(browser, has_selection, callback) as c_int
+        }
+        fn on_print_job(
+            &self,
+            browser: Browser: *mut cef_browser_t,
+            document_name: &CefString: *const cef_string_t,
+            pdf_file_path: &CefString: *const cef_string_t,
+            callback: PrintJobCallback: *mut cef_print_job_callback_t,
+        ) -> c_int {
+            self.0.on_print_job(
+                browser,
+                &String::from(document_name),
+                &String::from(pdf_file_path),
+                callback,
+            ) as c_int
+        }
+        fn on_print_reset(
+            &self,
+            browser: Browser: *mut cef_browser_t,
+        ) {
+            self.0.on_print_reset(browser);
+        }
+        fn get_pdf_paper_size(
+            &self,
+            device_units_per_inch: c_int: c_int,
+        ) -> cef_size_t {
+            let size = self.0.get_pdf_paper_size(device_units_per_inch);
+            cef_size_t {
+                width: size.width,
+                height: size.height,
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Redirecting JavaScript `window.print()` calls.
+//!
+//! [PrintInterceptor] replaces `window.print` in every document of a browser
+//! with a function that reports the call to the browser process, where the
+//! application decides what happens: print as usual, print to a PDF file with
+//! [BrowserHost::print_to_pdf], show its own dialog, or do nothing. This works
+//! on all platforms, unlike [PrintHandler](crate::print_handler::PrintHandler),
+//! whose `on_print_start` callback only exists on Linux and can't cancel the
+//! print.
+//!
+//! The `beforeprint` and `afterprint` events are fired by Chromium when a
+//! document is actually printed, so pages only see them for
+//! [PrintAction::Print] and [PrintAction::PrintToPdf].
+
+use crate::{
+    browser::Browser,
+    browser_host::BrowserHost,
+    devtools::DevToolsSession,
+    printing::PDFPrintSettings,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{path::PathBuf, sync::Arc};
+
+/// The name of the DevTools binding `window.print` is redirected to.
+const BINDING_NAME: &str = "__cefPrintRequested";
+
+const OVERRIDE_SOURCE: &str = "\
+var notify = window.__cefPrintRequested;\n\
+if (typeof notify !== 'function') return;\n\
+try { delete window.__cefPrintRequested; } catch (e) {}\n\
+window.print = function print() { notify(''); };";
+
+/// What to do with a `window.print()` call.
+#[derive(Debug, Clone)]
+pub enum PrintAction {
+    /// Continue with the regular print flow, as [BrowserHost::print] would.
+    Print,
+    /// Print to the PDF file at `path` instead.
+    PrintToPdf {
+        path: PathBuf,
+        settings: PDFPrintSettings,
+    },
+    /// Ignore the call, e.g. because the application shows its own dialog.
+    Ignore,
+}
+
+type PrintRequestHandler = Box<dyn 'static + Send + Sync + Fn(&Browser) -> PrintAction>;
+type PdfListener = Arc<dyn 'static + Send + Sync + Fn(&Browser, &str, bool)>;
+
+struct InterceptorState {
+    handler: PrintRequestHandler,
+    pdf_listener: Mutex<Option<PdfListener>>,
+}
+
+/// Intercepts `window.print()` in the documents of a single browser. See the
+/// [module documentation](self).
+///
+/// Interception stops when the interceptor is dropped, although documents that
+/// are already loaded keep reporting their calls until they navigate away.
+pub struct PrintInterceptor {
+    state: Arc<InterceptorState>,
+    session: DevToolsSession,
+    scripts: UserScripts,
+    script: UserScriptId,
+}
+
+impl PrintInterceptor {
+    /// Starts intercepting `window.print()` in the browser of `host`.
+    /// `handler` is called on the UI thread with the browser whose page
+    /// called `window.print()` and decides what happens.
+    pub fn new(
+        host: &BrowserHost,
+        handler: impl 'static + Send + Sync + Fn(&Browser) -> PrintAction,
+    ) -> PrintInterceptor {
+        let state = Arc::new(InterceptorState {
+            handler: Box::new(handler),
+            pdf_listener: Mutex::new(None),
+        });
+        let session = DevToolsSession::new(host);
+        let listener_state = state.clone();
+        session.add_event_listener("Runtime.bindingCalled", move |browser, params| {
+            if let StoredValue::Dictionary(params) = params {
+                if params.get_string("name") == BINDING_NAME {
+                    Self::handle(&listener_state, browser);
+                }
+            }
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        let script = scripts.add(&UserScript::new(OVERRIDE_SOURCE));
+        // The script only applies to new documents, so patch the current ones
+        // as well. The binding is installed in existing contexts right away.
+        let browser = host.get_browser();
+        let source = format!("(function() {{\n{}\n}})();", OVERRIDE_SOURCE);
+        for id in browser.get_frame_identifiers() {
+            if let Some(frame) = browser.get_frame_byident(id) {
+                frame.execute_java_script(&source, "", 0);
+            }
+        }
+        PrintInterceptor {
+            state,
+            session,
+            scripts,
+            script,
+        }
+    }
+    /// Calls `listener` with the path and success of every PDF file written for
+    /// [PrintAction::PrintToPdf].
+    pub fn on_pdf_finished(&self, listener: impl 'static + Send + Sync + Fn(&Browser, &str, bool)) {
+        *self.state.pdf_listener.lock() = Some(Arc::new(listener));
+    }
+
+    fn handle(state: &Arc<InterceptorState>, browser: &Browser) {
+        match (state.handler)(browser) {
+            PrintAction::Print => browser.get_host().print(),
+            PrintAction::PrintToPdf { path, settings } => {
+                let state = state.clone();
+                let pdf_browser = browser.clone();
+                browser.get_host().print_to_pdf(&path.to_string_lossy(), &settings, move |path, ok| {
+                    let listener = state.pdf_listener.lock().clone();
+                    if let Some(listener) = listener {
+                        listener(&pdf_browser, path, ok);
+                    }
+                });
+            }
+            PrintAction::Ignore => (),
+        }
+    }
+}
+
+impl Drop for PrintInterceptor {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
@@ -1,6 +1,18 @@
-use cef_sys::{cef_pdf_print_margin_type_t, cef_pdf_print_settings_t};
+use cef_sys::{cef_pdf_print_margin_type_t, cef_pdf_print_settings_t, cef_print_settings_t, cef_print_settings_create};
+use parking_lot::Mutex;
+use std::{
+    convert::TryInto,
+    fmt, fs,
+    os::raw::c_int,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::Duration,
+};
 
-use crate::string::CefString;
+use crate::{
+    string::CefString,
+    task::{TaskRunner, ThreadId},
+};
 
 #[repr(C)]
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
@@ -90,3 +102,232 @@ impl Default for PDFPrintMargin {
         Self::Default
     }
 }
+
+ref_counted_ptr! {
+    /// Structure representing print settings.
+    pub struct PrintSettings(*mut cef_print_settings_t);
+}
+
+impl PrintSettings {
+    pub fn new() -> PrintSettings {
+        unsafe { PrintSettings::from_ptr_unchecked(cef_print_settings_create()) }
+    }
+    /// Returns true if this object is valid. Do not call any other functions
+    /// if this function returns false.
+    pub fn is_valid(&self) -> bool {
+        unsafe { self.0.is_valid.unwrap()(self.as_ptr()) != 0 }
+    }
+    /// Returns true if the values of this object are read-only. Some APIs may
+    /// expose read-only objects.
+    pub fn is_read_only(&self) -> bool {
+        unsafe { self.0.is_read_only.unwrap()(self.as_ptr()) != 0 }
+    }
+    /// Set the page orientation.
+    pub fn set_orientation(&self, landscape: bool) {
+        unsafe { self.0.set_orientation.unwrap()(self.as_ptr(), landscape as c_int) }
+    }
+    /// Returns true if the orientation is landscape.
+    pub fn is_landscape(&self) -> bool {
+        unsafe { self.0.is_landscape.unwrap()(self.as_ptr()) != 0 }
+    }
+    /// Set the device name. Passing `None` selects the default device.
+    pub fn set_device_name(&self, name: Option<&str>) {
+        let name = name.map(CefString::new);
+        unsafe {
+            self.0.set_device_name.unwrap()(
+                self.as_ptr(),
+                name.as_ref().map(CefString::as_ptr).unwrap_or_else(std::ptr::null),
+            )
+        }
+    }
+    /// Get the device name.
+    pub fn get_device_name(&self) -> Option<String> {
+        unsafe { CefString::from_userfree(self.0.get_device_name.unwrap()(self.as_ptr())).map(String::from) }
+    }
+    /// Set the DPI (dots per inch).
+    pub fn set_dpi(&self, dpi: i32) {
+        unsafe { self.0.set_dpi.unwrap()(self.as_ptr(), dpi) }
+    }
+    /// Get the DPI (dots per inch).
+    pub fn get_dpi(&self) -> i32 {
+        unsafe { self.0.get_dpi.unwrap()(self.as_ptr()) }
+    }
+    /// Set whether only the selection will be printed.
+    pub fn set_selection_only(&self, selection_only: bool) {
+        unsafe { self.0.set_selection_only.unwrap()(self.as_ptr(), selection_only as c_int) }
+    }
+    /// Returns true if only the selection will be printed.
+    pub fn is_selection_only(&self) -> bool {
+        unsafe { self.0.is_selection_only.unwrap()(self.as_ptr()) != 0 }
+    }
+    /// Set whether pages will be collated.
+    pub fn set_collate(&self, collate: bool) {
+        unsafe { self.0.set_collate.unwrap()(self.as_ptr(), collate as c_int) }
+    }
+    /// Returns true if pages will be collated.
+    pub fn will_collate(&self) -> bool {
+        unsafe { self.0.will_collate.unwrap()(self.as_ptr()) != 0 }
+    }
+    /// Set the number of copies.
+    pub fn set_copies(&self, copies: i32) {
+        unsafe { self.0.set_copies.unwrap()(self.as_ptr(), copies) }
+    }
+    /// Get the number of copies.
+    pub fn get_copies(&self) -> i32 {
+        unsafe { self.0.get_copies.unwrap()(self.as_ptr()) }
+    }
+}
+
+impl Default for PrintSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Progress of a [PdfJob].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfProgress {
+    /// The renderer started laying out the document. Only reported on Linux,
+    /// where CEF notifies the [PrintHandler](crate::print_handler::PrintHandler).
+    Started,
+    /// The PDF file was written.
+    Finished,
+}
+
+/// Error returned by a [PdfJob].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdfError {
+    /// CEF reported that printing failed.
+    Failed,
+    /// [PdfJob::cancel] was called.
+    Canceled,
+    /// The renderer did not finish the document in time.
+    TimedOut,
+    /// The loaded CEF binary can't print to PDF.
+    Unsupported,
+}
+
+impl fmt::Display for PdfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PdfError::Failed => write!(f, "printing to PDF failed"),
+            PdfError::Canceled => write!(f, "printing to PDF was canceled"),
+            PdfError::TimedOut => write!(f, "printing to PDF timed out"),
+            PdfError::Unsupported => write!(f, "printing to PDF is not supported"),
+        }
+    }
+}
+
+impl std::error::Error for PdfError {}
+
+pub type PdfResult = Result<PathBuf, PdfError>;
+
+type PdfCallback = Box<dyn 'static + Send + FnOnce(PdfResult)>;
+type ProgressListener = Arc<dyn 'static + Send + Sync + Fn(PdfProgress)>;
+
+struct PdfJobState {
+    browser_id: i32,
+    path: PathBuf,
+    /// Taken when the job finishes, fails, times out or is canceled.
+    callback: Mutex<Option<PdfCallback>>,
+    listeners: Mutex<Vec<ProgressListener>>,
+}
+
+lazy_static::lazy_static! {
+    static ref ACTIVE_JOBS: Mutex<Vec<Weak<PdfJobState>>> = Mutex::new(Vec::new());
+}
+
+/// A running [BrowserHost::print_to_pdf_job](crate::browser_host::BrowserHost::print_to_pdf_job).
+///
+/// CEF can't abort a PDF job once it was submitted, so canceling or timing out
+/// completes the job right away and the output file is deleted whenever the
+/// renderer gets around to writing it.
+#[derive(Clone)]
+pub struct PdfJob(Arc<PdfJobState>);
+
+impl PdfJob {
+    pub(crate) fn start(
+        browser_id: i32,
+        path: PathBuf,
+        timeout: Duration,
+        callback: impl 'static + Send + FnOnce(PdfResult),
+    ) -> PdfJob {
+        let state = Arc::new(PdfJobState {
+            browser_id,
+            path,
+            callback: Mutex::new(Some(Box::new(callback))),
+            listeners: Mutex::new(Vec::new()),
+        });
+        {
+            let mut active = ACTIVE_JOBS.lock();
+            active.retain(|job| job.strong_count() > 0);
+            active.push(Arc::downgrade(&state));
+        }
+        let job = PdfJob(state);
+        let timed_out = job.clone();
+        TaskRunner::post_delayed_task_on(
+            ThreadId::UI,
+            move || timed_out.complete(Err(PdfError::TimedOut)),
+            timeout.as_millis().try_into().unwrap_or(i64::max_value()),
+        );
+        job
+    }
+    /// Returns the path the PDF is written to.
+    pub fn path(&self) -> &Path {
+        &self.0.path
+    }
+    /// Returns true once the job completed, failed, timed out or was canceled.
+    pub fn is_finished(&self) -> bool {
+        self.0.callback.lock().is_none()
+    }
+    /// Calls `listener` on the UI thread whenever the job makes progress.
+    pub fn on_progress(&self, listener: impl 'static + Send + Sync + Fn(PdfProgress)) {
+        self.0.listeners.lock().push(Arc::new(listener));
+    }
+    /// Completes the job with [PdfError::Canceled] unless it already finished.
+    pub fn cancel(&self) {
+        self.complete(Err(PdfError::Canceled));
+    }
+
+    /// Called by CEF when the PDF was written.
+    pub(crate) fn finished(&self, ok: bool) {
+        if self.is_finished() {
+            // The job was canceled or timed out, so nobody wants the file.
+            if ok {
+                let _ = fs::remove_file(&self.0.path);
+            }
+            return;
+        }
+        if ok {
+            self.notify(PdfProgress::Finished);
+            self.complete(Ok(self.0.path.clone()));
+        } else {
+            self.complete(Err(PdfError::Failed));
+        }
+    }
+    pub(crate) fn complete(&self, result: PdfResult) {
+        let callback = self.0.callback.lock().take();
+        if let Some(callback) = callback {
+            ACTIVE_JOBS.lock().retain(|job| job.upgrade().map_or(false, |job| !Arc::ptr_eq(&job, &self.0)));
+            callback(result);
+        }
+    }
+    fn notify(&self, progress: PdfProgress) {
+        let listeners = self.0.listeners.lock().clone();
+        for listener in listeners {
+            listener(progress);
+        }
+    }
+}
+
+/// Reports [PdfProgress::Started] to the running PDF jobs of `browser_id`.
+/// Called when CEF starts printing.
+pub(crate) fn print_started(browser_id: i32) {
+    let jobs = ACTIVE_JOBS.lock().iter()
+        .filter_map(Weak::upgrade)
+        .filter(|job| job.browser_id == browser_id)
+        .collect::<Vec<_>>();
+    for job in jobs {
+        PdfJob(job).notify(PdfProgress::Started);
+    }
+}
@@ -0,0 +1,213 @@
+//! Reducing what pages can learn about the device.
+//!
+//! The Battery Status, Network Information and Idle Detection APIs expose
+//! details that help fingerprint users. A [PrivacyProfile] decides for each of
+//! them whether pages see the real values, plausible constant values, or no
+//! API at all, and bundles related preferences such as Do Not Track and the
+//! [WebRtcPolicy]:
+//!
+//! ```ignore
+//! let profile = PrivacyProfile::strict();
+//! let settings = Settings::new().privacy_profile(&profile);
+//! // After creating the request context and browser:
+//! context.set_privacy_profile(&profile)?;
+//! let guard = profile.enforce(&browser.get_host());
+//! ```
+//!
+//! Disabled APIs are removed from Blink with a switch where Chromium has one,
+//! which applies to all browsers. Spoofed APIs, and disabled ones without a
+//! switch, are replaced by [PrivacyProfile::enforce] per browser.
+
+use crate::{
+    browser_host::BrowserHost,
+    media_devices::MediaDeviceGuard,
+    request_context::{RequestContext, WebRtcPolicy},
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::StoredValue,
+};
+
+const HELPERS_SOURCE: &str = "\
+function hide(target, name) {\n\
+  try { Object.defineProperty(target, name, { configurable: true, get: function() { return undefined; } }); } catch (e) {}\n\
+  try { delete target[name]; } catch (e) {}\n\
+}\n\
+function constant(target, name, value) {\n\
+  try { Object.defineProperty(target, name, { configurable: true, get: function() { return value; } }); } catch (e) {}\n\
+}\n\
+function inert(values) {\n\
+  var target = new EventTarget();\n\
+  Object.keys(values).forEach(function(key) { constant(target, key, values[key]); });\n\
+  return target;\n\
+}\n";
+
+const BATTERY_SPOOF_SOURCE: &str = "\
+var battery = inert({ charging: true, chargingTime: 0, dischargingTime: Infinity, level: 1,\n\
+  onchargingchange: null, onchargingtimechange: null, ondischargingtimechange: null, onlevelchange: null });\n\
+Navigator.prototype.getBattery = function() { return Promise.resolve(battery); };\n";
+
+const NETWORK_SPOOF_SOURCE: &str = "\
+var connection = inert({ effectiveType: '4g', downlink: 10, downlinkMax: Infinity, rtt: 50, saveData: false,\n\
+  type: 'unknown', onchange: null, ontypechange: null });\n\
+constant(Navigator.prototype, 'connection', connection);\n";
+
+const IDLE_SPOOF_SOURCE: &str = "\
+if (window.IdleDetector) {\n\
+  var IdleDetector = function() { var target = inert({ userState: null, screenState: null, onchange: null });\n\
+    target.start = function() {\n\
+      constant(target, 'userState', 'active'); constant(target, 'screenState', 'unlocked');\n\
+      return Promise.resolve();\n\
+    };\n\
+    return target;\n\
+  };\n\
+  IdleDetector.requestPermission = function() { return Promise.resolve('granted'); };\n\
+  window.IdleDetector = IdleDetector;\n\
+}\n";
+
+/// How a fingerprintable API is exposed to pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiExposure {
+    /// The real API, Chromium's default.
+    Real,
+    /// The API reports constant, common values: a full battery that is
+    /// charging, a fast 4G connection, a user that is always active.
+    Spoof,
+    /// The API doesn't exist, as in browsers that don't support it.
+    Disable,
+}
+
+/// Privacy settings for the pages of a request context. See the
+/// [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivacyProfile {
+    /// `navigator.getBattery()`.
+    pub battery: ApiExposure,
+    /// `navigator.connection`.
+    pub network_information: ApiExposure,
+    /// `IdleDetector`.
+    pub idle_detection: ApiExposure,
+    /// The "enable_do_not_track" preference, sending `DNT: 1` with requests.
+    pub do_not_track: bool,
+    /// Applied with [RequestContext::set_webrtc_policy] if set.
+    pub webrtc: Option<WebRtcPolicy>,
+}
+
+impl PrivacyProfile {
+    /// Creates a profile that changes nothing.
+    pub fn new() -> PrivacyProfile {
+        PrivacyProfile {
+            battery: ApiExposure::Real,
+            network_information: ApiExposure::Real,
+            idle_detection: ApiExposure::Real,
+            do_not_track: false,
+            webrtc: None,
+        }
+    }
+    /// Creates a profile that spoofs the battery and network, disables idle
+    /// detection, sends Do Not Track and uses [WebRtcPolicy::private]. Spoofing
+    /// keeps pages working that assume the APIs exist.
+    pub fn strict() -> PrivacyProfile {
+        PrivacyProfile {
+            battery: ApiExposure::Spoof,
+            network_information: ApiExposure::Spoof,
+            idle_detection: ApiExposure::Disable,
+            do_not_track: true,
+            webrtc: Some(WebRtcPolicy::private()),
+        }
+    }
+    pub fn battery(mut self, battery: ApiExposure) -> Self {
+        self.battery = battery;
+        self
+    }
+    pub fn network_information(mut self, network_information: ApiExposure) -> Self {
+        self.network_information = network_information;
+        self
+    }
+    pub fn idle_detection(mut self, idle_detection: ApiExposure) -> Self {
+        self.idle_detection = idle_detection;
+        self
+    }
+    pub fn do_not_track(mut self, do_not_track: bool) -> Self {
+        self.do_not_track = do_not_track;
+        self
+    }
+    pub fn webrtc(mut self, webrtc: WebRtcPolicy) -> Self {
+        self.webrtc = Some(webrtc);
+        self
+    }
+
+    /// Returns the Blink features to disable globally.
+    pub(crate) fn disabled_blink_features(&self) -> Vec<&'static str> {
+        // Battery Status and Network Information have no runtime feature.
+        if self.idle_detection == ApiExposure::Disable {
+            vec!["IdleDetection"]
+        } else {
+            Vec::new()
+        }
+    }
+    /// Replaces spoofed and disabled APIs in the documents of the browser of
+    /// `host`, including documents that are already loaded. Documents loaded
+    /// after the returned value is dropped see the real APIs again, unless
+    /// they were disabled by a switch. Also enforces the media devices of the
+    /// [WebRtcPolicy], see [WebRtcPolicy::enforce].
+    pub fn enforce(&self, host: &BrowserHost) -> PrivacyGuard {
+        let scripts = UserScripts::new(host);
+        let script = UserScript::new(&self.enforcement_source());
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        let media_devices = self.webrtc.as_ref().and_then(|webrtc| webrtc.enforce(host));
+        PrivacyGuard { scripts, script_id, media_devices }
+    }
+
+    fn enforcement_source(&self) -> String {
+        let mut source = String::from(HELPERS_SOURCE);
+        match self.battery {
+            ApiExposure::Real => (),
+            ApiExposure::Spoof => source.push_str(BATTERY_SPOOF_SOURCE),
+            ApiExposure::Disable => source.push_str("hide(Navigator.prototype, 'getBattery');\n"),
+        }
+        match self.network_information {
+            ApiExposure::Real => (),
+            ApiExposure::Spoof => source.push_str(NETWORK_SPOOF_SOURCE),
+            ApiExposure::Disable => source.push_str("hide(Navigator.prototype, 'connection'); hide(window, 'NetworkInformation');\n"),
+        }
+        match self.idle_detection {
+            ApiExposure::Real => (),
+            ApiExposure::Spoof => source.push_str(IDLE_SPOOF_SOURCE),
+            ApiExposure::Disable => source.push_str("hide(window, 'IdleDetector');\n"),
+        }
+        source
+    }
+}
+
+impl Default for PrivacyProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keeps a [PrivacyProfile] enforced in a browser, returned by
+/// [PrivacyProfile::enforce].
+pub struct PrivacyGuard {
+    scripts: UserScripts,
+    script_id: UserScriptId,
+    media_devices: Option<MediaDeviceGuard>,
+}
+
+impl Drop for PrivacyGuard {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script_id);
+    }
+}
+
+impl RequestContext {
+    /// Applies the preferences of `profile` to this context. Returns the first
+    /// error reported by [RequestContext::set_preference], if any. This
+    /// function must be called on the browser process UI thread.
+    pub fn set_privacy_profile(&self, profile: &PrivacyProfile) -> Result<(), String> {
+        self.set_preference("enable_do_not_track", Some(StoredValue::Bool(profile.do_not_track)))?;
+        if let Some(webrtc) = &profile.webrtc {
+            self.set_webrtc_policy(webrtc)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+//! Tracking the child processes CEF launches.
+//!
+//! CEF doesn't report the IDs of its child processes, so a [ProcessMonitor]
+//! polls the operating system for descendants of the browser process. On
+//! Linux and macOS the type of each process is read from its `--type`
+//! switch. Windows doesn't expose the command lines of other processes
+//! cheaply, so there new processes are matched, in order, to the launches
+//! seen by
+//! [BrowserProcessHandlerCallbacks::on_before_child_process_launch](crate::browser_process_handler::BrowserProcessHandlerCallbacks::on_before_child_process_launch),
+//! which requires an [App](crate::app::App) with a browser process handler.
+//!
+//! With the process IDs, supervisors can attribute CPU and memory usage to
+//! renderers, the GPU process and utility services such as the network
+//! service.
+
+use crate::{
+    command_line::CommandLine,
+    misc_fns::ProcessType,
+    task::{TaskRunner, ThreadId},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    sync::{atomic::{AtomicU64, Ordering}, Arc, Weak},
+    time::{Duration, Instant},
+};
+
+static NEXT_LISTENER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Launches that weren't matched to a process yet are dropped after this long.
+const LAUNCH_MATCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Launch {
+    process_type: ProcessType,
+    sub_type: Option<String>,
+    time: Instant,
+}
+
+lazy_static::lazy_static! {
+    /// Recent child process launches, oldest first.
+    static ref LAUNCHES: Mutex<VecDeque<Launch>> = Mutex::new(VecDeque::new());
+}
+
+/// Records a child process launch. Called from the browser process handler.
+pub(crate) fn child_process_launching(command_line: &CommandLine) {
+    let process_type = crate::misc_fns::process_type_from_switch(command_line.get_switch_value("type").as_deref());
+    crate::browser_startup::child_process_launched(process_type);
+    let mut launches = LAUNCHES.lock();
+    launches.retain(|launch| launch.time.elapsed() < LAUNCH_MATCH_TIMEOUT);
+    launches.push_back(Launch {
+        process_type,
+        sub_type: command_line.get_switch_value("utility-sub-type"),
+        time: Instant::now(),
+    });
+}
+
+/// A child process of the browser process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildProcess {
+    pub pid: u32,
+    pub process_type: ProcessType,
+    /// The service a utility process runs, e.g. `network.mojom.NetworkService`.
+    pub sub_type: Option<String>,
+    /// When the monitor first saw the process.
+    pub first_seen: Instant,
+}
+
+/// A change reported to [ProcessMonitor] listeners.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessEvent {
+    Started(ChildProcess),
+    Exited(ChildProcess),
+}
+
+/// Identifies a listener added with [ProcessMonitor::add_listener].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcessListenerId(u64);
+
+type ProcessListener = Arc<dyn 'static + Send + Sync + Fn(&ProcessEvent)>;
+
+struct MonitorState {
+    interval: Duration,
+    processes: Mutex<HashMap<u32, ChildProcess>>,
+    listeners: Mutex<Vec<(ProcessListenerId, ProcessListener)>>,
+}
+
+/// Polls the child processes of the browser process. Cloning the monitor is
+/// cheap; polling stops once the last clone is dropped.
+#[derive(Clone)]
+pub struct ProcessMonitor(Arc<MonitorState>);
+
+impl ProcessMonitor {
+    /// Starts polling every `interval` on a background thread. Must be called
+    /// in the browser process after CEF was initialized.
+    pub fn start(interval: Duration) -> ProcessMonitor {
+        let state = Arc::new(MonitorState {
+            interval,
+            processes: Mutex::new(HashMap::new()),
+            listeners: Mutex::new(Vec::new()),
+        });
+        Self::schedule(Arc::downgrade(&state), 0);
+        ProcessMonitor(state)
+    }
+    /// Returns the child processes seen by the last poll.
+    pub fn processes(&self) -> Vec<ChildProcess> {
+        let mut processes = self.0.processes.lock().values().cloned().collect::<Vec<_>>();
+        processes.sort_by_key(|process| process.first_seen);
+        processes
+    }
+    /// Returns the child processes of `process_type`.
+    pub fn processes_of_type(&self, process_type: ProcessType) -> Vec<ChildProcess> {
+        self.processes().into_iter().filter(|process| process.process_type == process_type).collect()
+    }
+    /// Calls `listener` on a background thread whenever a child process
+    /// starts or exits.
+    pub fn add_listener(&self, listener: impl 'static + Send + Sync + Fn(&ProcessEvent)) -> ProcessListenerId {
+        let id = ProcessListenerId(NEXT_LISTENER_ID.fetch_add(1, Ordering::SeqCst));
+        self.0.listeners.lock().push((id, Arc::new(listener)));
+        id
+    }
+    pub fn remove_listener(&self, id: ProcessListenerId) {
+        self.0.listeners.lock().retain(|(listener_id, _)| *listener_id != id);
+    }
+    /// Polls right away instead of waiting for the next interval. Blocks on
+    /// file system access on Linux, so avoid calling this on the UI thread.
+    pub fn refresh(&self) {
+        Self::poll(&self.0);
+    }
+
+    fn schedule(state: Weak<MonitorState>, delay_ms: i64) {
+        TaskRunner::post_delayed_task_on(ThreadId::FileBackground, move || {
+            if let Some(state) = state.upgrade() {
+                Self::poll(&state);
+                let delay_ms = state.interval.as_millis().try_into().unwrap_or(i64::max_value());
+                Self::schedule(Arc::downgrade(&state), delay_ms);
+            }
+        }, delay_ms);
+    }
+    fn poll(state: &MonitorState) {
+        let current = match os::descendants(std::process::id()) {
+            Some(current) => current,
+            None => return,
+        };
+        let mut events = Vec::new();
+        {
+            let mut processes = state.processes.lock();
+            let exited = processes.keys()
+                .filter(|pid| !current.iter().any(|(current_pid, _)| current_pid == *pid))
+                .cloned()
+                .collect::<Vec<_>>();
+            for pid in exited {
+                if let Some(process) = processes.remove(&pid) {
+                    events.push(ProcessEvent::Exited(process));
+                }
+            }
+            for (pid, args) in current {
+                if processes.contains_key(&pid) {
+                    continue;
+                }
+                let (process_type, sub_type) = match args {
+                    Some(args) => (
+                        crate::misc_fns::process_type_from_switch(switch_value(&args, "type")),
+                        switch_value(&args, "utility-sub-type").map(str::to_owned),
+                    ),
+                    None => match LAUNCHES.lock().pop_front() {
+                        Some(launch) => (launch.process_type, launch.sub_type),
+                        None => (ProcessType::Other, None),
+                    },
+                };
+                let process = ChildProcess {
+                    pid,
+                    process_type,
+                    sub_type,
+                    first_seen: Instant::now(),
+                };
+                processes.insert(pid, process.clone());
+                events.push(ProcessEvent::Started(process));
+            }
+        }
+        if events.is_empty() {
+            return;
+        }
+        let listeners = state.listeners.lock().iter().map(|(_, listener)| listener.clone()).collect::<Vec<_>>();
+        for event in &events {
+            for listener in &listeners {
+                listener(event);
+            }
+        }
+    }
+}
+
+/// Returns the value of `--name=value` in `args`.
+fn switch_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().find_map(|arg| {
+        let arg = arg.strip_prefix("--")?;
+        let value = arg.strip_prefix(name)?;
+        value.strip_prefix('=')
+    })
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use std::fs;
+
+    /// Returns the descendants of `root` with their arguments.
+    pub(super) fn descendants(root: u32) -> Option<Vec<(u32, Option<Vec<String>>)>> {
+        // Renderers are forked from the zygote, so look beyond direct children.
+        let mut parents = Vec::new();
+        for entry in fs::read_dir("/proc").ok()?.filter_map(|entry| entry.ok()) {
+            let pid = match entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+            if let Some(ppid) = parent_pid(pid) {
+                parents.push((pid, ppid));
+            }
+        }
+        Some(super::descendants_of(root, &parents).into_iter()
+            .map(|pid| (pid, arguments(pid)))
+            .collect())
+    }
+
+    fn parent_pid(pid: u32) -> Option<u32> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // The command name may contain spaces and parentheses.
+        let rest = &stat[stat.rfind(')')? + 1..];
+        rest.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    fn arguments(pid: u32) -> Option<Vec<String>> {
+        let cmdline = fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+        Some(cmdline.split(|b| *b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect())
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod os {
+    use std::process::Command;
+
+    /// Returns the descendants of `root` with their arguments.
+    pub(super) fn descendants(root: u32) -> Option<Vec<(u32, Option<Vec<String>>)>> {
+        let output = Command::new("ps").args(&["-axo", "pid=,ppid=,args="]).output().ok()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        let mut parents = Vec::new();
+        let mut args = Vec::new();
+        for line in output.lines() {
+            let mut fields = line.split_whitespace();
+            let pid = match fields.next().and_then(|pid| pid.parse::<u32>().ok()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+            let ppid = match fields.next().and_then(|ppid| ppid.parse::<u32>().ok()) {
+                Some(ppid) => ppid,
+                None => continue,
+            };
+            parents.push((pid, ppid));
+            // Paths with spaces are split as well, which doesn't matter for
+            // finding switches.
+            args.push((pid, fields.map(str::to_owned).collect::<Vec<_>>()));
+        }
+        let descendants = super::descendants_of(root, &parents);
+        Some(args.into_iter()
+            .filter(|(pid, _)| descendants.contains(pid))
+            .map(|(pid, args)| (pid, Some(args)))
+            .collect())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod os {
+    use std::mem;
+    use winapi::um::{
+        handleapi::{CloseHandle, INVALID_HANDLE_VALUE},
+        tlhelp32::{CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS},
+    };
+
+    /// Returns the descendants of `root`. Arguments aren't available.
+    pub(super) fn descendants(root: u32) -> Option<Vec<(u32, Option<Vec<String>>)>> {
+        let mut parents = Vec::new();
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            let mut entry: PROCESSENTRY32W = mem::zeroed();
+            entry.dwSize = mem::size_of::<PROCESSENTRY32W>() as u32;
+            let mut ok = Process32FirstW(snapshot, &mut entry);
+            while ok != 0 {
+                parents.push((entry.th32ProcessID, entry.th32ParentProcessID));
+                ok = Process32NextW(snapshot, &mut entry);
+            }
+            CloseHandle(snapshot);
+        }
+        // The snapshot lists processes roughly in creation order, which is
+        // what matching them to launches relies on.
+        Some(super::descendants_of(root, &parents).into_iter().map(|pid| (pid, None)).collect())
+    }
+}
+
+/// Returns the transitive children of `root` given `(pid, parent pid)`
+/// pairs, in the order of `parents`.
+fn descendants_of(root: u32, parents: &[(u32, u32)]) -> Vec<u32> {
+    let mut found = vec![root];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (pid, ppid) in parents {
+            if *pid != root && found.contains(ppid) && !found.contains(pid) {
+                found.push(*pid);
+                changed = true;
+            }
+        }
+    }
+    let found = &found[1..];
+    parents.iter().map(|(pid, _)| *pid).filter(|pid| found.contains(pid)).collect()
+}
@@ -0,0 +1,372 @@
+//! Named browser profiles with isolated storage.
+//!
+//! A profile is a [RequestContext] whose cache directory is a subdirectory of
+//! a common root, named after the profile. Cookies, local storage, caches and
+//! preferences of different profiles are kept apart, which is what
+//! multi-account applications need.
+//!
+//! The root must be equal to or a child of
+//! [Settings::root_cache_path](crate::settings::Settings::root_cache_path),
+//! otherwise CEF refuses to create the contexts. Use a directory that holds
+//! nothing but profiles, since every subdirectory with a valid profile name is
+//! listed as a profile.
+//!
+//! [RequestContext::for_partition] uses a shared manager below
+//! `<root cache path>/partitions` for per-site storage.
+
+use crate::{
+    browser::Browser,
+    request_context::{RequestContext, RequestContextBuilder},
+    task::{TaskRunner, ThreadId},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use uuid::Uuid;
+
+/// The subdirectory of the root cache path holding the partitions of
+/// [RequestContext::for_partition].
+const PARTITIONS_DIR: &str = "partitions";
+
+/// Directories of deleted profiles are renamed to end with this suffix before
+/// being removed, so an interrupted deletion never looks like a profile.
+const DELETING_SUFFIX: &str = ".deleting";
+
+/// How often removing the directory of a deleted profile is retried. Files
+/// may stay locked for a moment after the context was released.
+const DELETE_ATTEMPTS: u32 = 5;
+const DELETE_RETRY_DELAY_MS: i64 = 1000;
+
+/// Error returned by [ProfileManager] operations.
+#[derive(Debug)]
+pub enum ProfileError {
+    /// Profile names may only contain ASCII letters, digits, `-` and `_`.
+    InvalidName(String),
+    /// The profile still has open browsers.
+    InUse(String),
+    /// No profile with that name exists.
+    NotFound(String),
+    /// Partitions need [Settings::root_cache_path](crate::settings::Settings::root_cache_path)
+    /// or [Settings::cache_path](crate::settings::Settings::cache_path) to be set.
+    NoRootCachePath,
+    Io(io::Error),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::InvalidName(name) => write!(f, "invalid profile name {:?}", name),
+            ProfileError::InUse(name) => write!(f, "profile {:?} has open browsers", name),
+            ProfileError::NotFound(name) => write!(f, "profile {:?} does not exist", name),
+            ProfileError::NoRootCachePath => write!(f, "no root cache path is configured"),
+            ProfileError::Io(error) => write!(f, "profile I/O error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<io::Error> for ProfileError {
+    fn from(error: io::Error) -> Self {
+        ProfileError::Io(error)
+    }
+}
+
+struct Profile {
+    context: RequestContext,
+    browsers: Vec<Browser>,
+}
+
+type ContextConfig = Arc<dyn 'static + Send + Sync + Fn(&str, RequestContextBuilder) -> RequestContextBuilder>;
+
+struct ManagerState {
+    root: PathBuf,
+    profiles: Mutex<HashMap<String, Profile>>,
+    configure: Mutex<Option<ContextConfig>>,
+}
+
+/// Creates, lists and deletes profiles below a root directory. See the
+/// [module documentation](self). Cloning the manager is cheap and all clones
+/// share the same state.
+#[derive(Clone)]
+pub struct ProfileManager(Arc<ManagerState>);
+
+impl ProfileManager {
+    /// Creates a manager for the profiles below `root`, creating the directory
+    /// if necessary.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Result<ProfileManager, ProfileError> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        let root = dunce::canonicalize(&root)?;
+        Ok(ProfileManager(Arc::new(ManagerState {
+            root,
+            profiles: Mutex::new(HashMap::new()),
+            configure: Mutex::new(None),
+        })))
+    }
+    /// Sets a function that adjusts the settings of each context before it is
+    /// created, e.g. to add a handler or persist session cookies. The cache
+    /// path is already set when it is called.
+    pub fn configure_contexts(
+        &self,
+        configure: impl 'static + Send + Sync + Fn(&str, RequestContextBuilder) -> RequestContextBuilder,
+    ) {
+        *self.0.configure.lock() = Some(Arc::new(configure));
+    }
+    pub fn root(&self) -> &Path {
+        &self.0.root
+    }
+    /// Returns the cache directory of the profile `name`.
+    pub fn profile_path(&self, name: &str) -> Result<PathBuf, ProfileError> {
+        validate_name(name)?;
+        Ok(self.0.root.join(name))
+    }
+    /// Returns the names of all profiles on disk, sorted.
+    pub fn profile_names(&self) -> Result<Vec<String>, ProfileError> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.0.root)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str() {
+                if validate_name(name).is_ok() {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+    /// Returns true if the profile `name` exists on disk.
+    pub fn exists(&self, name: &str) -> bool {
+        self.profile_path(name).map(|path| path.is_dir()).unwrap_or(false)
+    }
+    /// Returns the request context of the profile `name`, creating the profile
+    /// if it doesn't exist. Contexts are created once per profile and reused
+    /// afterwards.
+    pub fn context(&self, name: &str) -> Result<RequestContext, ProfileError> {
+        let path = self.profile_path(name)?;
+        if let Some(profile) = self.0.profiles.lock().get(name) {
+            return Ok(profile.context.clone());
+        }
+        fs::create_dir_all(&path)?;
+        // The callback runs without the locks held, so it may use the manager.
+        let mut builder = RequestContextBuilder::new().with_cache_path(&path);
+        let configure = self.0.configure.lock().clone();
+        if let Some(configure) = configure {
+            builder = configure(name, builder);
+        }
+        let context = builder.build();
+        // Another call may have created the profile in the meantime; keep the
+        // first context.
+        let mut profiles = self.0.profiles.lock();
+        let profile = profiles.entry(name.to_owned()).or_insert_with(|| Profile {
+            context,
+            browsers: Vec::new(),
+        });
+        Ok(profile.context.clone())
+    }
+    /// Records that `browser` was created with the context of the profile
+    /// `name`. Call this from
+    /// [LifeSpanHandlerCallbacks::on_after_created](crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_after_created).
+    pub fn add_browser(&self, name: &str, browser: &Browser) {
+        if let Some(profile) = self.0.profiles.lock().get_mut(name) {
+            profile.browsers.push(browser.clone());
+        }
+    }
+    /// Forgets `browser`. Call this from
+    /// [LifeSpanHandlerCallbacks::on_before_close](crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_before_close).
+    pub fn browser_closed(&self, browser: &Browser) {
+        let browser_id = browser.get_identifier();
+        for profile in self.0.profiles.lock().values_mut() {
+            profile.browsers.retain(|b| b.get_identifier() != browser_id);
+        }
+    }
+    /// Returns the open browsers of the profile `name`.
+    pub fn browsers(&self, name: &str) -> Vec<Browser> {
+        self.0.profiles.lock().get(name).map(|profile| profile.browsers.clone()).unwrap_or_default()
+    }
+    /// Asks all browsers of the profile `name` to close. Wait for
+    /// [ProfileManager::browsers] to become empty before deleting the profile.
+    pub fn close_browsers(&self, name: &str, force_close: bool) {
+        for browser in self.browsers(name) {
+            browser.get_host().close_browser(force_close);
+        }
+    }
+    /// Deletes the profile `name` and all its data. Fails with
+    /// [ProfileError::InUse] if the profile has open browsers. The context's
+    /// connections are closed first and the directory is then removed in the
+    /// background, retrying for a few seconds if files are still locked.
+    /// `callback` receives the result, right away if the profile can't be
+    /// deleted and on the UI thread otherwise.
+    pub fn delete(&self, name: &str, callback: impl 'static + Send + FnOnce(Result<(), ProfileError>)) {
+        let path = match self.profile_path(name) {
+            Ok(path) => path,
+            Err(error) => return callback(Err(error)),
+        };
+        if !path.is_dir() {
+            return callback(Err(ProfileError::NotFound(name.to_owned())));
+        }
+        let (in_use, context) = {
+            let profiles = self.0.profiles.lock();
+            let profile = profiles.get(name);
+            (
+                profile.map(|profile| !profile.browsers.is_empty()).unwrap_or(false),
+                profile.map(|profile| profile.context.clone()),
+            )
+        };
+        if in_use {
+            return callback(Err(ProfileError::InUse(name.to_owned())));
+        }
+        // The profile is only forgotten once its directory was moved away, so
+        // a failed deletion leaves it usable.
+        let manager = self.clone();
+        let name = name.to_owned();
+        let remove = move || {
+            // Rename first so the profile disappears at once and a new profile
+            // with the same name starts out empty.
+            let doomed = path.with_file_name(format!("{}.{}{}", name_of(&path), Uuid::new_v4().to_simple(), DELETING_SUFFIX));
+            if let Err(error) = fs::rename(&path, &doomed) {
+                return callback(Err(error.into()));
+            }
+            manager.0.profiles.lock().remove(&name);
+            callback(Ok(()));
+            remove_dir(doomed, DELETE_ATTEMPTS);
+        };
+        match context {
+            Some(context) => context.close_all_connections(move || {
+                drop(context);
+                TaskRunner::post_task_on(ThreadId::UI, remove);
+            }),
+            None => { TaskRunner::post_task_on(ThreadId::UI, remove); },
+        }
+    }
+    /// Removes directories left over by deletions that were interrupted, e.g.
+    /// because the application exited. Call this once at startup.
+    pub fn remove_leftovers(&self) -> Result<(), ProfileError> {
+        for entry in fs::read_dir(&self.0.root)? {
+            let entry = entry?;
+            let is_leftover = entry.file_name().to_str().map(|name| name.ends_with(DELETING_SUFFIX)).unwrap_or(false);
+            if is_leftover && entry.file_type()?.is_dir() {
+                remove_dir(entry.path(), DELETE_ATTEMPTS);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_name(name: &str) -> Result<(), ProfileError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(ProfileError::InvalidName(name.to_owned()))
+    }
+}
+
+fn name_of(path: &Path) -> String {
+    path.file_name().and_then(|name| name.to_str()).unwrap_or("profile").to_owned()
+}
+
+fn remove_dir(path: PathBuf, attempts: u32) {
+    TaskRunner::post_task_on(ThreadId::FileBackground, move || {
+        match fs::remove_dir_all(&path) {
+            Ok(()) => (),
+            Err(error) if attempts > 1 => {
+                log::debug!("retrying removal of {}: {}", path.display(), error);
+                TaskRunner::post_delayed_task_on(ThreadId::FileBackground, move || remove_dir(path, attempts - 1), DELETE_RETRY_DELAY_MS);
+            }
+            Err(error) => log::error!("could not remove {}: {}", path.display(), error),
+        }
+    });
+}
+
+lazy_static::lazy_static! {
+    static ref ROOT_CACHE_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+    static ref PARTITIONS: Mutex<Option<ProfileManager>> = Mutex::new(None);
+}
+
+/// Remembers the root cache path CEF was initialized with.
+pub(crate) fn set_root_cache_path(path: Option<PathBuf>) {
+    *ROOT_CACHE_PATH.lock() = path;
+    *PARTITIONS.lock() = None;
+}
+
+/// Returns the manager of the partitions below the root cache path.
+fn partitions() -> Result<ProfileManager, ProfileError> {
+    let mut partitions = PARTITIONS.lock();
+    if let Some(manager) = &*partitions {
+        return Ok(manager.clone());
+    }
+    let root = ROOT_CACHE_PATH.lock().clone().ok_or(ProfileError::NoRootCachePath)?;
+    let manager = ProfileManager::new(root.join(PARTITIONS_DIR))?;
+    *partitions = Some(manager.clone());
+    Ok(manager)
+}
+
+impl RequestContext {
+    /// Returns the context of the storage partition `name`, creating it on
+    /// first use. Each partition keeps cookies, caches and local storage in
+    /// its own directory below the root cache path, so browsers of different
+    /// tenants can't see each other's data. Partition names follow the rules
+    /// of profile names. Must be called on the browser process UI thread.
+    pub fn for_partition(name: &str) -> Result<RequestContext, ProfileError> {
+        partitions()?.context(name)
+    }
+    /// Returns the context of the partition for the origin of `url`, named
+    /// after its scheme, host and port and a hash of them, e.g.
+    /// `https_site-a_example_443_79b047de9016ac24`.
+    pub fn for_origin(url: &str) -> Result<RequestContext, ProfileError> {
+        Self::for_partition(&partition_name_for_origin(url)?)
+    }
+    /// Returns the partition manager, for listing or deleting partitions and
+    /// tracking their browsers.
+    pub fn partitions() -> Result<ProfileManager, ProfileError> {
+        partitions()
+    }
+}
+
+/// Derives a valid partition name from the origin of `url`: a readable
+/// prefix, which loses characters and may be cut off, and a hash of the whole
+/// origin that keeps distinct origins apart.
+fn partition_name_for_origin(url: &str) -> Result<String, ProfileError> {
+    let invalid = || ProfileError::InvalidName(url.to_owned());
+    let (scheme, rest) = url.split_at(url.find("://").ok_or_else(invalid)?);
+    let scheme = scheme.to_ascii_lowercase();
+    let authority = rest[3..].split(|c| c == '/' || c == '?' || c == '#').next().unwrap_or("");
+    let host_port = authority.rsplit('@').next().unwrap_or("");
+    if host_port.is_empty() {
+        return Err(invalid());
+    }
+    let (host, port) = match host_port.rfind(':') {
+        Some(index) if !host_port.ends_with(']') => (&host_port[..index], &host_port[index + 1..]),
+        _ => (host_port, match &scheme[..] {
+            "http" | "ws" => "80",
+            "https" | "wss" => "443",
+            _ => "0",
+        }),
+    };
+    let host = host.to_ascii_lowercase();
+    let origin = format!("{}://{}:{}", scheme, host, port);
+    let prefix = format!("{}_{}_{}", scheme, host, port)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .take(47)
+        .collect::<String>();
+    let name = format!("{}_{:016x}", prefix, fnv1a(origin.as_bytes()));
+    validate_name(&name)?;
+    Ok(name)
+}
+
+/// The 64-bit FNV-1a hash, which unlike the standard library's hashers is
+/// stable across builds, so partitions keep their directories.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
@@ -0,0 +1,447 @@
+//! Forwarding custom scheme requests to a local HTTP server.
+//!
+//! A common architecture for desktop apps is to serve the UI and its API from
+//! a local HTTP backend and point the browser at it through a custom scheme
+//! such as `app://`, which keeps the backend off the network and gives the UI
+//! a stable origin. [ProxySchemeHandler] implements the forwarding side:
+//!
+//! ```ignore
+//! SchemeHandlerFactory::new(ProxySchemeHandler::new(ProxyTarget::unix("/run/my-app/ui.sock")))
+//!     .register(SchemeName::Custom { name: "app" });
+//! ```
+//!
+//! Requests are sent as HTTP/1.0 with `Connection: close`, so responses are
+//! delimited by `Content-Length` or the end of the connection and never use
+//! chunked transfer encoding. Each request is handled on its own thread with
+//! blocking socket I/O, which also reads the files uploaded with it.
+
+use crate::{
+    async_resource_handler::{AsyncRead, AsyncResourceHandler, HttpResponseParts},
+    browser::Browser,
+    frame::Frame,
+    load_handler::ErrorCode,
+    request::{PostDataElementType, Request},
+    scheme::SchemeHandlerFactoryCallbacks,
+    url_request::ResourceHandler,
+};
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+#[cfg(unix)]
+use std::{os::unix::net::UnixStream, path::PathBuf};
+
+/// Bytes of response body buffered ahead of CEF before the worker thread stops
+/// reading from the backend.
+const MAX_BUFFERED: usize = 1 << 20;
+
+/// Headers that only apply to a single connection and must not be forwarded.
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection", "keep-alive", "proxy-connection", "transfer-encoding", "upgrade", "te", "trailer",
+];
+
+/// The local server requests are forwarded to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyTarget {
+    /// A TCP address such as `127.0.0.1:8080`.
+    Tcp(String),
+    /// A Unix domain socket.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl ProxyTarget {
+    pub fn tcp(address: &str) -> ProxyTarget {
+        ProxyTarget::Tcp(address.to_owned())
+    }
+    #[cfg(unix)]
+    pub fn unix<T: Into<PathBuf>>(path: T) -> ProxyTarget {
+        ProxyTarget::Unix(path.into())
+    }
+
+    fn connect(&self, timeout: Duration) -> io::Result<Box<dyn Connection>> {
+        match self {
+            ProxyTarget::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(unix)]
+            ProxyTarget::Unix(path) => {
+                let stream = UnixStream::connect(path)?;
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+}
+
+trait Connection: Read + Write + Send {}
+impl<T: Read + Write + Send> Connection for T {}
+
+/// A [SchemeHandlerFactoryCallbacks] implementation forwarding requests to a
+/// local HTTP server. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct ProxySchemeHandler {
+    pub target: ProxyTarget,
+    /// The `Host` header sent to the server. Defaults to the host of the
+    /// request URL.
+    pub host: Option<String>,
+    /// Timeout for connecting and for each read or write on the connection.
+    pub timeout: Duration,
+}
+
+impl ProxySchemeHandler {
+    pub fn new(target: ProxyTarget) -> ProxySchemeHandler {
+        ProxySchemeHandler {
+            target,
+            host: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = Some(host.to_owned());
+        self
+    }
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+    /// Returns a resource handler forwarding the request it is opened for.
+    /// Useful for proxying from a [ResourceRequestHandlerCallbacks::get_resource_handler](crate::resource_request_handler::ResourceRequestHandlerCallbacks::get_resource_handler)
+    /// as well.
+    pub fn handler(&self) -> ResourceHandler {
+        let proxy = self.clone();
+        AsyncResourceHandler::new(move |request: Request| {
+            let head = Arc::new(Mutex::new(HeadState { result: None, waker: None }));
+            let body = Arc::new(BodyShared::default());
+            let captured = CapturedRequest::from_request(&request, proxy.host.as_ref().map(|s| &**s));
+            let (thread_head, thread_body) = (head.clone(), body.clone());
+            std::thread::Builder::new()
+                .name("cef proxy".to_owned())
+                .spawn(move || proxy.forward(captured, thread_head, thread_body))
+                .expect("failed to spawn proxy thread");
+            HeadFuture { head, body: Some(body) }
+        })
+    }
+
+    fn forward(self, request: Result<CapturedRequest, ErrorCode>, head: Arc<Mutex<HeadState>>, body: Arc<BodyShared>) {
+        let set_head = |result| {
+            let mut head = head.lock();
+            head.result = Some(result);
+            if let Some(waker) = head.waker.take() {
+                waker.wake();
+            }
+        };
+        let mut reader = match request.and_then(CapturedRequest::into_outgoing).and_then(|request| self.send(&request)) {
+            Ok(connection) => BufReader::new(connection),
+            Err(error) => return set_head(Err(error)),
+        };
+        match read_head(&mut reader) {
+            Ok(parts) => set_head(Ok(parts)),
+            Err(error) => {
+                log::error!("invalid response from proxy target {:?}: {}", self.target, error);
+                return set_head(Err(ErrorCode::InvalidResponse));
+            }
+        }
+        let mut buffer = [0u8; 16 * 1024];
+        loop {
+            let result = reader.read(&mut buffer);
+            let mut state = body.state.lock();
+            match result {
+                Ok(0) => state.done = true,
+                Ok(read) => state.chunks.push_back(buffer[..read].to_vec()),
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    state.error = Some(error);
+                    state.done = true;
+                }
+            }
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            if state.done {
+                return;
+            }
+            while !state.cancelled && state.buffered() > MAX_BUFFERED {
+                body.consumed.wait(&mut state);
+            }
+            if state.cancelled {
+                return;
+            }
+        }
+    }
+    fn send(&self, request: &OutgoingRequest) -> Result<Box<dyn Connection>, ErrorCode> {
+        let mut connection = self.target.connect(self.timeout).map_err(|error| {
+            log::error!("unable to connect to proxy target {:?}: {}", self.target, error);
+            ErrorCode::ConnectionRefused
+        })?;
+        connection.write_all(&request.head)
+            .and_then(|_| connection.write_all(&request.body))
+            .and_then(|_| connection.flush())
+            .map_err(|error| {
+                log::error!("unable to send request to proxy target {:?}: {}", self.target, error);
+                ErrorCode::ConnectionReset
+            })?;
+        Ok(connection)
+    }
+}
+
+impl SchemeHandlerFactoryCallbacks for ProxySchemeHandler {
+    fn create(
+        &self,
+        browser: Browser,
+        frame: Frame,
+        scheme_name: &str,
+        request: Request,
+    ) -> Option<ResourceHandler> {
+        Some(self.handler())
+    }
+}
+
+/// A part of a request body, copied from the request on the IO thread.
+enum BodyElement {
+    Bytes(Vec<u8>),
+    /// A file that is read by the worker thread.
+    File(String),
+}
+
+/// A request as copied on the IO thread, before its body is read.
+struct CapturedRequest {
+    /// The request line and headers, without `Content-Length`.
+    head: String,
+    body: Vec<BodyElement>,
+}
+
+impl CapturedRequest {
+    fn from_request(request: &Request, host: Option<&str>) -> Result<CapturedRequest, ErrorCode> {
+        let url = request.get_url();
+        let (authority, path) = split_url(&url).ok_or(ErrorCode::InvalidUrl)?;
+
+        let mut body = Vec::new();
+        if let Some(post_data) = request.try_get_post_data() {
+            for element in post_data.get_elements() {
+                match element.get_type() {
+                    PostDataElementType::Bytes => body.push(BodyElement::Bytes(element.get_bytes())),
+                    PostDataElementType::File => body.push(BodyElement::File(element.get_file())),
+                    PostDataElementType::Empty => (),
+                }
+            }
+        }
+
+        let mut head = format!("{} {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n", request.get_method(), path, host.unwrap_or(authority));
+        let referrer = request.get_referrer_url();
+        if !referrer.is_empty() {
+            head.push_str(&format!("Referer: {}\r\n", referrer));
+        }
+        for (name, values) in request.get_header_map() {
+            let lower = name.to_ascii_lowercase();
+            if lower == "host" || lower == "content-length" || HOP_BY_HOP_HEADERS.contains(&&*lower) {
+                continue;
+            }
+            for value in values {
+                head.push_str(&format!("{}: {}\r\n", name, value));
+            }
+        }
+        Ok(CapturedRequest { head, body })
+    }
+    /// Reads the uploaded files and finishes the head. Blocks, so it must
+    /// run on the worker thread.
+    fn into_outgoing(self) -> Result<OutgoingRequest, ErrorCode> {
+        let mut body = Vec::new();
+        for element in self.body {
+            match element {
+                BodyElement::Bytes(bytes) => body.extend(bytes),
+                BodyElement::File(path) => {
+                    let bytes = std::fs::read(&path).map_err(|error| {
+                        log::error!("unable to read upload file {}: {}", path, error);
+                        ErrorCode::UploadFileChanged
+                    })?;
+                    body.extend(bytes);
+                }
+            }
+        }
+        let mut head = self.head;
+        if !body.is_empty() {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        head.push_str("\r\n");
+        Ok(OutgoingRequest { head: head.into_bytes(), body })
+    }
+}
+
+struct OutgoingRequest {
+    head: Vec<u8>,
+    body: Vec<u8>,
+}
+
+/// Splits a URL such as `app://ui/index.html?x=1` into its authority and
+/// origin-form path.
+fn split_url(url: &str) -> Option<(&str, String)> {
+    let rest = &url[url.find("://")? + 3..];
+    let rest = rest.split('#').next().unwrap_or(rest);
+    let end = rest.find(|c| c == '/' || c == '?').unwrap_or(rest.len());
+    if end == 0 {
+        return None;
+    }
+    let (authority, path) = rest.split_at(end);
+    let path = if path.starts_with('/') { path.to_owned() } else { format!("/{}", path) };
+    Some((authority, path))
+}
+
+fn read_head(reader: &mut impl BufRead) -> io::Result<HttpResponseParts> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut status_line = line.trim_end().splitn(3, ' ');
+    let version = status_line.next().unwrap_or("");
+    if !version.starts_with("HTTP/") {
+        return Err(invalid("missing status line"));
+    }
+    let status = status_line.next()
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| invalid("invalid status code"))?;
+    let mut parts = HttpResponseParts::new(status, "");
+    if let Some(status_text) = status_line.next() {
+        parts = parts.status_text(status_text);
+    }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("unexpected end of headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let colon = header.find(':').ok_or_else(|| invalid("malformed header"))?;
+        let (name, value) = (header[..colon].trim(), header[colon + 1..].trim());
+        let lower = name.to_ascii_lowercase();
+        if HOP_BY_HOP_HEADERS.contains(&&*lower) {
+            continue;
+        }
+        match &*lower {
+            "content-type" => {
+                let mut params = value.split(';');
+                parts.mime_type = params.next().unwrap_or("").trim().to_owned();
+                for param in params {
+                    let param = param.trim();
+                    if param.len() > 8 && param[..8].eq_ignore_ascii_case("charset=") {
+                        parts.charset = Some(param[8..].trim_matches('"').to_owned());
+                    }
+                }
+            }
+            "content-length" => parts.content_length = value.parse().ok(),
+            _ => (),
+        }
+        parts.headers.push((name.to_owned(), value.to_owned()));
+    }
+    Ok(parts)
+}
+
+struct HeadState {
+    result: Option<Result<HttpResponseParts, ErrorCode>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves once the worker thread has read the response head.
+struct HeadFuture {
+    head: Arc<Mutex<HeadState>>,
+    body: Option<Arc<BodyShared>>,
+}
+
+impl Future for HeadFuture {
+    type Output = Result<(HttpResponseParts, ProxyBody), ErrorCode>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let result = {
+            let mut head = self.head.lock();
+            match head.result.take() {
+                Some(result) => result,
+                None => {
+                    head.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        };
+        let body = self.body.take().expect("HeadFuture polled after completion");
+        Poll::Ready(result.map(|parts| (parts, ProxyBody(body))))
+    }
+}
+
+impl Drop for HeadFuture {
+    fn drop(&mut self) {
+        if let Some(body) = &self.body {
+            body.cancel();
+        }
+    }
+}
+
+#[derive(Default)]
+struct BodyState {
+    chunks: VecDeque<Vec<u8>>,
+    done: bool,
+    cancelled: bool,
+    error: Option<io::Error>,
+    waker: Option<Waker>,
+}
+
+impl BodyState {
+    fn buffered(&self) -> usize {
+        self.chunks.iter().map(Vec::len).sum()
+    }
+}
+
+#[derive(Default)]
+struct BodyShared {
+    state: Mutex<BodyState>,
+    consumed: Condvar,
+}
+
+impl BodyShared {
+    fn cancel(&self) {
+        self.state.lock().cancelled = true;
+        self.consumed.notify_all();
+    }
+}
+
+/// The response body, filled by the worker thread.
+struct ProxyBody(Arc<BodyShared>);
+
+impl AsyncRead for ProxyBody {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut state = self.0.state.lock();
+        if let Some(chunk) = state.chunks.front_mut() {
+            let len = chunk.len().min(buf.len());
+            buf[..len].copy_from_slice(&chunk[..len]);
+            chunk.drain(..len);
+            if chunk.is_empty() {
+                state.chunks.pop_front();
+            }
+            self.0.consumed.notify_all();
+            return Poll::Ready(Ok(len));
+        }
+        if let Some(error) = state.error.take() {
+            return Poll::Ready(Err(error));
+        }
+        if state.done {
+            return Poll::Ready(Ok(0));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for ProxyBody {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
@@ -26,6 +26,19 @@ pub(crate) trait Wrapper: Sized + Send + Sync {
 }
 
 macro_rules! ref_counter {
+    // Structures that extend another one, like the views types, reach the
+    // `cef_base_ref_counted_t` through their chain of `base` fields.
+    ($cef:ty, base: $($base:ident).+) => {
+        unsafe impl crate::refcounted::RefCounter for $cef {
+            const POISONABLE: bool = false;
+            fn base(&self) -> &cef_sys::cef_base_ref_counted_t {
+                &self.$($base).+
+            }
+            fn base_mut(&mut self) -> &mut cef_sys::cef_base_ref_counted_t {
+                &mut self.$($base).+
+            }
+        }
+    };
     ($cef:ty) => {
         ref_counter!($cef, false);
     };
@@ -105,9 +118,9 @@ lazy_static!{
 }
 
 macro_rules! ref_counted_ptr {
-    (
+    (@struct
         $(#[$meta:meta])*
-        $vis:vis struct $Struct:ident$(<$($generic:ident $(: $bound:path)?),+>)?(*mut $cef:ty $(, $poisonable:expr)?);
+        $vis:vis struct $Struct:ident$(<$($generic:ident $(: $bound:path)?),+>)?(*mut $cef:ty);
     ) => {
         $(#[$meta])*
         #[repr(transparent)]
@@ -117,8 +130,6 @@ macro_rules! ref_counted_ptr {
         unsafe impl$(<$($generic $(: $bound)?),+>)? Send for $Struct$(<$($generic),+>)? {}
         unsafe impl$(<$($generic $(: $bound)?),+>)? Sync for $Struct$(<$($generic),+>)? {}
 
-        ref_counter!($cef $(, $poisonable)?);
-
         impl$(<$($generic $(: $bound)?),+>)? $Struct$(<$($generic),+>)? {
             pub(crate) unsafe fn from_ptr_add_ref(ptr: *mut $cef) -> Option<Self> {
                 crate::refcounted::RefCountedPtr::from_ptr_add_ref(ptr).map(Self)
@@ -151,6 +162,20 @@ macro_rules! ref_counted_ptr {
 
         owned_casts!(impl for $Struct = *mut $cef);
     };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $Struct:ident(*mut $cef:ty, base: $($base:ident).+);
+    ) => {
+        ref_counter!($cef, base: $($base).+);
+        ref_counted_ptr!(@struct $(#[$meta])* $vis struct $Struct(*mut $cef););
+    };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $Struct:ident$(<$($generic:ident $(: $bound:path)?),+>)?(*mut $cef:ty $(, $poisonable:expr)?);
+    ) => {
+        ref_counter!($cef $(, $poisonable)?);
+        ref_counted_ptr!(@struct $(#[$meta])* $vis struct $Struct$(<$($generic $(: $bound)?),+>)?(*mut $cef););
+    };
 }
 
 impl<C: RefCounter> Deref for RefCountedPtr<C> {
@@ -14,7 +14,8 @@ use cef_sys::{
     cef_load_handler_t, cef_process_id_t, cef_process_message_t, cef_render_process_handler_t,
     cef_v8context_t, cef_v8exception_t, cef_v8stack_trace_t,
 };
-use std::ptr::null_mut;
+use parking_lot::Mutex;
+use std::{collections::HashMap, ptr::null_mut, sync::Arc};
 
 ref_counted_ptr!{
     pub struct RenderProcessHandler(*mut cef_render_process_handler_t);
@@ -75,7 +76,9 @@ pub trait RenderProcessHandlerCallbacks: 'static + Send {
     /// executed.
     fn on_focused_node_changed(&self, browser: Browser, frame: Frame, node: Option<DOMNode>) {}
     /// Called when a new message is received from a different process. Return true
-    /// if the message was handled or false otherwise.
+    /// if the message was handled or false otherwise. Messages handled by the
+    /// [MessageRouter] returned from [RenderProcessHandlerCallbacks::get_message_router]
+    /// don't reach this function.
     fn on_process_message_received(
         &self,
         browser: Browser,
@@ -84,6 +87,84 @@ pub trait RenderProcessHandlerCallbacks: 'static + Send {
     ) -> bool {
         false
     }
+    /// Return a router to dispatch process messages to handlers registered
+    /// per frame. Its handlers are removed automatically when the context of
+    /// their frame is released or their browser is destroyed.
+    fn get_message_router(&self) -> Option<&MessageRouter> {
+        None
+    }
+}
+
+type MessageHandler = Arc<dyn 'static + Send + Sync + Fn(&Browser, &Frame, &ProcessMessage) -> bool>;
+
+/// Routes process messages received in the render process to handlers
+/// registered for a frame and message name. Return it from
+/// [RenderProcessHandlerCallbacks::get_message_router] so handlers don't
+/// outlive their frames.
+#[derive(Default)]
+pub struct MessageRouter {
+    routes: Mutex<HashMap<(i32, i64), HashMap<String, MessageHandler>>>,
+}
+
+impl MessageRouter {
+    pub fn new() -> MessageRouter {
+        MessageRouter::default()
+    }
+    /// Calls `handler` for messages named `name` sent to `frame`, replacing a
+    /// handler registered before. Return false from `handler` to pass the
+    /// message on to [RenderProcessHandlerCallbacks::on_process_message_received].
+    pub fn register(
+        &self,
+        frame: &Frame,
+        name: &str,
+        handler: impl 'static + Send + Sync + Fn(&Browser, &Frame, &ProcessMessage) -> bool,
+    ) {
+        let key = match Self::key(&frame.get_browser(), frame) {
+            Some(key) => key,
+            None => return,
+        };
+        self.routes.lock().entry(key).or_insert_with(HashMap::new).insert(name.to_owned(), Arc::new(handler));
+    }
+    pub fn unregister(&self, frame: &Frame, name: &str) {
+        if let Some(key) = Self::key(&frame.get_browser(), frame) {
+            let mut routes = self.routes.lock();
+            if let Some(handlers) = routes.get_mut(&key) {
+                handlers.remove(name);
+                if handlers.is_empty() {
+                    routes.remove(&key);
+                }
+            }
+        }
+    }
+    /// Returns the number of frames with registered handlers.
+    pub fn frame_count(&self) -> usize {
+        self.routes.lock().len()
+    }
+    /// Calls the handler registered for `message`, if any, and returns whether
+    /// it handled the message.
+    pub fn route(&self, browser: &Browser, frame: &Frame, message: &ProcessMessage) -> bool {
+        let (key, name) = match (Self::key(browser, frame), message.get_name()) {
+            (Some(key), Some(name)) => (key, name),
+            _ => return false,
+        };
+        let handler = self.routes.lock().get(&key).and_then(|handlers| handlers.get(&name)).cloned();
+        handler.map(|handler| handler(browser, frame, message)).unwrap_or(false)
+    }
+    /// Removes the handlers of `frame`.
+    pub fn frame_released(&self, browser: &Browser, frame: &Frame) {
+        if let Some(key) = Self::key(browser, frame) {
+            self.routes.lock().remove(&key);
+        }
+    }
+    /// Removes the handlers of all frames of `browser`.
+    pub fn browser_destroyed(&self, browser: &Browser) {
+        let browser_id = browser.get_identifier();
+        self.routes.lock().retain(|(id, _), _| *id != browser_id);
+    }
+
+    fn key(browser: &Browser, frame: &Frame) -> Option<(i32, i64)> {
+        Some((browser.get_identifier(), frame.get_identifier()?))
+    }
 }
 
 #[repr(transparent)]
@@ -145,7 +226,11 @@ cef_callback_impl! {
             &self,
             browser: Browser: *mut cef_browser_t,
         ) {
-            unsafe{ self.0.get() }.on_browser_destroyed(browser);
+            let handler = unsafe{ self.0.get() };
+            if let Some(router) = handler.get_message_router() {
+                router.browser_destroyed(&browser);
+            }
+            handler.on_browser_destroyed(browser);
         }
 
         fn get_load_handler(
@@ -173,7 +258,11 @@ cef_callback_impl! {
             frame: Frame: *mut cef_frame_t,
             context: V8Context: *mut cef_v8context_t,
         ) {
-            unsafe{ self.0.get() }.on_context_created(
+            let handler = unsafe{ self.0.get() };
+            if let Some(router) = handler.get_message_router() {
+                router.frame_released(&browser, &frame);
+            }
+            handler.on_context_released(
                 browser,
                 frame,
                 context,
@@ -218,7 +307,13 @@ cef_callback_impl! {
             message: ProcessMessage: *mut cef_process_message_t,
         ) -> std::os::raw::c_int {
             assert_eq!(ProcessId::Browser, source_process);
-            unsafe{ self.0.get() }.on_process_message_received(
+            let handler = unsafe{ self.0.get() };
+            if let Some(router) = handler.get_message_router() {
+                if router.route(&browser, &frame, &message) {
+                    return 1;
+                }
+            }
+            handler.on_process_message_received(
                 browser,
                 frame,
                 message
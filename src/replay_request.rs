@@ -0,0 +1,177 @@
+//! Re-issuing captured requests for debugging.
+//!
+//! A [ReplayRequest] is a plain copy of a request recorded by a
+//! [NetworkLog](crate::network_log::NetworkLog) or seen by a resource
+//! handler. [ReplayRequest::send] issues it again through [URLRequest], which
+//! is handy for hammering a flaky endpoint with exactly the request the page
+//! sent:
+//!
+//! ```ignore
+//! let entry = log.entries().into_iter().find(|entry| entry.status == Some(502)).unwrap();
+//! ReplayRequest::from_captured(&entry).send(None, |response| println!("{:?}", response.status));
+//! ```
+//!
+//! Cookies are sent from the cookie store of the request context, so they
+//! reflect the current session rather than the captured one.
+
+use crate::{
+    load_handler::ErrorCode,
+    network_log::NetworkEntry,
+    request::{PostData, PostDataElement, PostDataElementType, Request, URLRequestFlags},
+    request_context::RequestContext,
+    url_request::{URLRequest, URLRequestClient, URLRequestClientCallbacks, URLRequestStatus},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Headers that are derived from the connection or the body and must not be
+/// copied into a new request.
+const SKIPPED_HEADERS: &[&str] = &["host", "content-length", "connection", "cookie"];
+
+/// A request that can be sent again. See the [module documentation](self).
+#[derive(Clone, PartialEq, Eq)]
+pub struct ReplayRequest {
+    pub url: String,
+    pub method: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+    pub flags: Vec<URLRequestFlags>,
+}
+
+impl ReplayRequest {
+    /// Copies a captured request, e.g. a [NetworkEntry] or a [Request].
+    pub fn from_captured<T: Into<ReplayRequest>>(captured: T) -> ReplayRequest {
+        captured.into()
+    }
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+    pub fn body<T: Into<Vec<u8>>>(mut self, body: T) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Creates a new [Request] with the captured URL, method, headers and
+    /// body.
+    pub fn to_request(&self) -> Request {
+        let request = Request::new();
+        let mut headers: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, value) in &self.headers {
+            if !SKIPPED_HEADERS.iter().any(|skipped| skipped.eq_ignore_ascii_case(name)) {
+                headers.entry(name.clone()).or_default().push(value.clone());
+            }
+        }
+        let post_data = PostData::new();
+        if let Some(body) = &self.body {
+            let element = PostDataElement::new();
+            element.set_to_bytes(body);
+            post_data.add_element(&element);
+        }
+        request.set(&self.url, &self.method, post_data, headers);
+        request.set_flags(&self.flags);
+        request
+    }
+    /// Sends the request through `request_context`, or the global request
+    /// context if `None`, and calls `callback` with the complete response.
+    /// Must be called on the browser process UI or IO thread.
+    pub fn send(
+        &self,
+        request_context: Option<&RequestContext>,
+        callback: impl 'static + Send + FnOnce(ReplayResponse),
+    ) -> URLRequest {
+        let client = ReplayClient {
+            started: Instant::now(),
+            body: Mutex::new(Vec::new()),
+            callback: Mutex::new(Some(Box::new(callback))),
+        };
+        URLRequest::new(&mut self.to_request(), URLRequestClient::new(client), request_context)
+    }
+}
+
+impl From<&NetworkEntry> for ReplayRequest {
+    fn from(entry: &NetworkEntry) -> Self {
+        ReplayRequest {
+            url: entry.url.clone(),
+            method: entry.method.clone(),
+            headers: entry.request_headers.clone(),
+            body: entry.request_body.as_ref().map(|body| body.clone().into_bytes()),
+            flags: vec![URLRequestFlags::AllowStoredCredentials],
+        }
+    }
+}
+
+impl From<&Request> for ReplayRequest {
+    fn from(request: &Request) -> Self {
+        let headers = request.get_header_map().into_iter()
+            .flat_map(|(name, values)| values.into_iter().map(move |value| (name.clone(), value)))
+            .collect();
+        // Only in-memory elements can be copied; files may be gone by now.
+        let body = request.try_get_post_data().map(|post_data| {
+            post_data.get_elements().iter()
+                .filter(|element| element.get_type() == PostDataElementType::Bytes)
+                .flat_map(|element| element.get_bytes())
+                .collect()
+        });
+        let mut flags = request.get_flags();
+        if !flags.contains(&URLRequestFlags::AllowStoredCredentials) {
+            flags.push(URLRequestFlags::AllowStoredCredentials);
+        }
+        ReplayRequest {
+            url: request.get_url(),
+            method: request.get_method(),
+            headers,
+            body,
+            flags,
+        }
+    }
+}
+
+/// The outcome of [ReplayRequest::send].
+#[derive(Clone)]
+pub struct ReplayResponse {
+    pub status: URLRequestStatus,
+    /// The network error if `status` is [URLRequestStatus::Failed].
+    pub error: ErrorCode,
+    /// The HTTP status code, or 0 if no response was received.
+    pub http_status: i32,
+    pub status_text: String,
+    pub headers: HashMap<String, Vec<String>>,
+    pub body: Vec<u8>,
+    /// The time from sending the request to receiving the complete response.
+    pub elapsed: Duration,
+    pub was_cached: bool,
+}
+
+struct ReplayClient {
+    started: Instant,
+    body: Mutex<Vec<u8>>,
+    callback: Mutex<Option<Box<dyn 'static + Send + FnOnce(ReplayResponse)>>>,
+}
+
+impl URLRequestClientCallbacks for ReplayClient {
+    fn on_request_complete(&self, request: URLRequest) {
+        let callback = match self.callback.lock().take() {
+            Some(callback) => callback,
+            None => return,
+        };
+        let response = request.get_response();
+        callback(ReplayResponse {
+            status: request.get_request_status(),
+            error: request.get_request_error(),
+            http_status: response.as_ref().map(|response| response.get_status()).unwrap_or(0),
+            status_text: response.as_ref().map(|response| response.get_status_text()).unwrap_or_default(),
+            headers: response.as_ref().map(|response| response.get_header_map()).unwrap_or_default(),
+            body: std::mem::replace(&mut *self.body.lock(), Vec::new()),
+            elapsed: self.started.elapsed(),
+            was_cached: request.response_was_cached(),
+        });
+    }
+    fn on_download_data(&self, request: URLRequest, data: &[u8]) {
+        self.body.lock().extend_from_slice(data);
+    }
+}
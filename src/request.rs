@@ -3,7 +3,8 @@ use cef_sys::{
     cef_postdataelement_type_t, cef_referrer_policy_t, cef_request_create, cef_request_t,
     cef_resource_type_t, cef_string_userfree_utf16_free,
 };
-use std::{collections::HashMap, convert::TryFrom, ptr::null_mut};
+use std::{collections::HashMap, convert::TryFrom, fs, io, path::Path, ptr::null_mut};
+use uuid::Uuid;
 
 use crate::{load_handler::TransitionType, multimap::MultiMap, string::CefString};
 
@@ -271,6 +272,11 @@ impl Request {
         let get_post_data = self.0.get_post_data.unwrap();
         unsafe { PostData::from_ptr_unchecked(get_post_data(self.0.as_ptr())) }
     }
+    /// Get the post data, or `None` if the request has no body.
+    pub fn try_get_post_data(&self) -> Option<PostData> {
+        let get_post_data = self.0.get_post_data.unwrap();
+        unsafe { PostData::from_ptr(get_post_data(self.0.as_ptr())) }
+    }
     /// Set the post data.
     pub fn set_post_data(&self, post_data: PostData) {
         if let Some(set_post_data) = self.0.set_post_data {
@@ -289,6 +295,14 @@ impl Request {
             HashMap::new()
         }
     }
+    /// Set the header values. If a Referer value exists in the header map it
+    /// will be removed and ignored.
+    pub fn set_header_map(&self, header_map: &HashMap<String, Vec<String>>) {
+        let map: MultiMap = header_map.into();
+        if let Some(set_header_map) = self.0.set_header_map {
+            unsafe { set_header_map(self.0.as_ptr(), map.as_ptr()) };
+        }
+    }
     /// Returns the first header value for `name` or None if not found.
     /// Will not return the Referer value if any. Use [Request::get_header_map] instead if
     /// `name` might have multiple values.
@@ -422,6 +436,224 @@ impl Default for Request {
     }
 }
 
+/// A part of the body of a [RequestBuilder].
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum BodyPart {
+    Bytes(Vec<u8>),
+    File(String),
+}
+
+/// Builds a [Request], e.g. for [URLRequest::new](crate::url_request::URLRequest::new)
+/// or [Frame::load_request](crate::frame::Frame::load_request):
+///
+/// ```ignore
+/// let request = RequestBuilder::new("https://example.com/api")
+///     .method("POST")
+///     .header("Content-Type", "application/json")
+///     .body(r#"{"query":"cef"}"#)
+///     .flags(&[URLRequestFlags::DisableCache])
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct RequestBuilder {
+    url: String,
+    method: String,
+    referrer: Option<(String, ReferrerPolicy)>,
+    headers: HashMap<String, Vec<String>>,
+    flags: Vec<URLRequestFlags>,
+    first_party_for_cookies: Option<String>,
+    body: Vec<BodyPart>,
+}
+
+impl RequestBuilder {
+    /// Starts a GET request of `url`.
+    pub fn new(url: &str) -> RequestBuilder {
+        RequestBuilder {
+            url: url.to_owned(),
+            method: "GET".to_owned(),
+            referrer: None,
+            headers: HashMap::new(),
+            flags: Vec::new(),
+            first_party_for_cookies: None,
+            body: Vec::new(),
+        }
+    }
+    /// Sets the method. A request with a body is sent as POST unless another
+    /// method is set.
+    pub fn method(mut self, method: &str) -> Self {
+        self.method = method.to_owned();
+        self
+    }
+    pub fn referrer(mut self, referrer_url: &str, policy: ReferrerPolicy) -> Self {
+        self.referrer = Some((referrer_url.to_owned(), policy));
+        self
+    }
+    /// Adds a value of the header `name`. Values of the same header are sent
+    /// in the order they were added.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.entry(name.to_owned()).or_insert_with(Vec::new).push(value.to_owned());
+        self
+    }
+    /// Replaces all headers. A Referer header is ignored; use
+    /// [RequestBuilder::referrer] instead.
+    pub fn headers(mut self, headers: HashMap<String, Vec<String>>) -> Self {
+        self.headers = headers;
+        self
+    }
+    pub fn flags(mut self, flags: &[URLRequestFlags]) -> Self {
+        self.flags = flags.to_vec();
+        self
+    }
+    /// Sets the URL to the first party for cookies used in combination with
+    /// [URLRequest](crate::url_request::URLRequest).
+    pub fn first_party_for_cookies(mut self, url: &str) -> Self {
+        self.first_party_for_cookies = Some(url.to_owned());
+        self
+    }
+    /// Appends `bytes` to the body.
+    pub fn body<T: Into<Vec<u8>>>(mut self, bytes: T) -> Self {
+        self.body.push(BodyPart::Bytes(bytes.into()));
+        self
+    }
+    /// Appends the contents of the file at `path` to the body. Requests made
+    /// with a [URLRequest](crate::url_request::URLRequest) may only have a
+    /// single element.
+    pub fn file(mut self, path: &str) -> Self {
+        self.body.push(BodyPart::File(path.to_owned()));
+        self
+    }
+    /// Sends `form` as the body, replacing the parts added before, with its
+    /// Content-Type header.
+    pub fn multipart(mut self, form: MultipartFormBuilder) -> Self {
+        self.body = vec![BodyPart::Bytes(form.body())];
+        self.headers.retain(|name, _| !name.eq_ignore_ascii_case("content-type"));
+        self.header("Content-Type", &form.content_type())
+    }
+
+    /// Creates the request. Adjacent byte parts of the body are sent as one
+    /// element.
+    pub fn build(&self) -> Request {
+        let request = Request::new();
+        let mut parts: Vec<BodyPart> = Vec::new();
+        for part in &self.body {
+            match (parts.last_mut(), part) {
+                (_, BodyPart::Bytes(bytes)) if bytes.is_empty() => (),
+                (Some(BodyPart::Bytes(last)), BodyPart::Bytes(bytes)) => last.extend_from_slice(bytes),
+                (_, part) => parts.push(part.clone()),
+            }
+        }
+        let method = if !parts.is_empty() && self.method == "GET" { "POST" } else { &self.method[..] };
+        request.set_url(&self.url);
+        request.set_method(method);
+        request.set_header_map(&self.headers);
+        if !parts.is_empty() {
+            let post_data = PostData::new();
+            for part in &parts {
+                let element = PostDataElement::new();
+                match part {
+                    BodyPart::Bytes(bytes) => element.set_to_bytes(bytes),
+                    BodyPart::File(path) => element.set_to_file(path),
+                }
+                post_data.add_element(&element);
+            }
+            request.set_post_data(post_data);
+        }
+        if let Some((referrer_url, policy)) = &self.referrer {
+            request.set_referrer(Some(referrer_url), *policy);
+        }
+        if !self.flags.is_empty() {
+            request.set_flags(&self.flags);
+        }
+        if let Some(url) = &self.first_party_for_cookies {
+            request.set_first_party_for_cookies(url);
+        }
+        request
+    }
+}
+
+/// Builds a `multipart/form-data` body, as browsers send for forms with file
+/// inputs:
+///
+/// ```ignore
+/// let form = MultipartFormBuilder::new()
+///     .field("title", "Holiday")
+///     .file_from_path("photo", Path::new("beach.jpg"), "image/jpeg")?;
+/// let request = RequestBuilder::new("https://example.com/upload").multipart(form).build();
+/// ```
+///
+/// Files are read into memory, as requests made with a
+/// [URLRequest](crate::url_request::URLRequest) can only have a single body
+/// element.
+#[derive(Clone)]
+pub struct MultipartFormBuilder {
+    boundary: String,
+    body: Vec<u8>,
+}
+
+impl MultipartFormBuilder {
+    pub fn new() -> MultipartFormBuilder {
+        MultipartFormBuilder {
+            boundary: format!("----CefFormBoundary{}", Uuid::new_v4().to_simple()),
+            body: Vec::new(),
+        }
+    }
+    /// Adds a text field.
+    pub fn field(mut self, name: &str, value: &str) -> Self {
+        self.part_header(&format!("form-data; name=\"{}\"", escape_form_name(name)), None);
+        self.body.extend_from_slice(value.as_bytes());
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+    /// Adds a file named `file_name` with the given contents.
+    pub fn file(mut self, name: &str, file_name: &str, content_type: &str, contents: &[u8]) -> Self {
+        self.part_header(
+            &format!("form-data; name=\"{}\"; filename=\"{}\"", escape_form_name(name), escape_form_name(file_name)),
+            Some(content_type),
+        );
+        self.body.extend_from_slice(contents);
+        self.body.extend_from_slice(b"\r\n");
+        self
+    }
+    /// Adds the file at `path`, named after its last component.
+    pub fn file_from_path(self, name: &str, path: &Path, content_type: &str) -> io::Result<Self> {
+        let contents = fs::read(path)?;
+        let file_name = path.file_name().map(|file_name| file_name.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(self.file(name, &file_name, content_type, &contents))
+    }
+    /// Returns the value of the Content-Type header to send with the body.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+    /// Returns the complete body.
+    pub fn body(&self) -> Vec<u8> {
+        let mut body = self.body.clone();
+        body.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        body
+    }
+    pub fn build(&self) -> PostData {
+        PostData::from_bytes(&self.body())
+    }
+
+    fn part_header(&mut self, disposition: &str, content_type: Option<&str>) {
+        self.body.extend_from_slice(format!("--{}\r\nContent-Disposition: {}\r\n", self.boundary, disposition).as_bytes());
+        if let Some(content_type) = content_type {
+            self.body.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+        }
+        self.body.extend_from_slice(b"\r\n");
+    }
+}
+
+impl Default for MultipartFormBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Escapes a field or file name like browsers do in form submissions.
+fn escape_form_name(name: &str) -> String {
+    name.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
 ref_counted_ptr! {
     /// Structure used to represent post data for a web request. The functions of
     /// this structure may be called on any thread.
@@ -432,6 +664,23 @@ impl PostData {
     pub fn new() -> Self {
         unsafe { Self::from_ptr_unchecked(cef_post_data_create()) }
     }
+    /// Creates post data with a single element containing `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let post_data = Self::new();
+        let element = PostDataElement::new();
+        element.set_to_bytes(bytes);
+        post_data.add_element(&element);
+        post_data
+    }
+    /// Creates post data with a single element containing the file at
+    /// `file_name`.
+    pub fn from_file(file_name: &str) -> Self {
+        let post_data = Self::new();
+        let element = PostDataElement::new();
+        element.set_to_file(file_name);
+        post_data.add_element(&element);
+        post_data
+    }
 
     /// Returns true if this object is read-only.
     pub fn is_read_only(&self) -> bool {
@@ -466,9 +715,11 @@ impl PostData {
                 unsafe {
                     get_elements(self.as_ptr(), &mut count, elements.as_mut_ptr());
                 }
+                // CEF may fill in fewer elements than it counted before.
+                elements.truncate(count);
                 elements
                     .into_iter()
-                    .map(|p| unsafe { PostDataElement::from_ptr_unchecked(p) })
+                    .filter_map(|p| unsafe { PostDataElement::from_ptr(p) })
                     .collect()
             } else {
                 Vec::new()
@@ -28,8 +28,10 @@ use std::{
 
 use crate::{
     browser::Browser,
+    browser_host::BrowserHost,
     frame::Frame,
     refcounted::{RefCountedPtr, Wrapper},
+    media_devices::{MediaDeviceGuard, MediaDevicePolicy},
     media_router::MediaRouter,
     request::Request,
     resource_request_handler::ResourceRequestHandler,
@@ -722,3 +724,136 @@ impl Default for RequestContextBuilder {
         Self::new()
     }
 }
+
+/// Controls which network interfaces WebRTC may use to gather ICE candidates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebRtcIpHandlingPolicy {
+    /// Use all available interfaces.
+    Default,
+    /// Use the default route and associated private addresses only.
+    DefaultPublicAndPrivateInterfaces,
+    /// Use the default public route only, hiding local addresses.
+    DefaultPublicInterfaceOnly,
+    /// Only use UDP if it goes through a proxy. Effectively forces TURN over TCP.
+    DisableNonProxiedUdp,
+}
+
+impl WebRtcIpHandlingPolicy {
+    fn as_pref(self) -> &'static str {
+        match self {
+            WebRtcIpHandlingPolicy::Default => "default",
+            WebRtcIpHandlingPolicy::DefaultPublicAndPrivateInterfaces => "default_public_and_private_interfaces",
+            WebRtcIpHandlingPolicy::DefaultPublicInterfaceOnly => "default_public_interface_only",
+            WebRtcIpHandlingPolicy::DisableNonProxiedUdp => "disable_non_proxied_udp",
+        }
+    }
+}
+
+/// WebRTC privacy settings. The network settings are applied to a
+/// [RequestContext] through its preferences with
+/// [RequestContext::set_webrtc_policy]. CEF 85 has no permission handler, so
+/// the cameras and microphones pages can open and enumerate are gated per
+/// browser with [WebRtcPolicy::enforce], on top of
+/// [Settings::enable_media_stream](crate::settings::Settings::enable_media_stream).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebRtcPolicy {
+    /// The "webrtc.ip_handling_policy" preference.
+    pub ip_handling_policy: WebRtcIpHandlingPolicy,
+    /// The "webrtc.multiple_routes_enabled" preference. When false WebRTC only
+    /// uses the default route.
+    pub multiple_routes_enabled: bool,
+    /// The "webrtc.nonproxied_udp_enabled" preference.
+    pub nonproxied_udp_enabled: bool,
+    /// The "webrtc.udp_port_range" preference, as an inclusive range of local
+    /// ports. `None` lets the OS pick any port.
+    pub udp_port_range: Option<(u16, u16)>,
+    /// The devices `getUserMedia()` and `enumerateDevices()` give pages.
+    /// `None` leaves them to Chromium.
+    pub media_devices: Option<MediaDevicePolicy>,
+}
+
+impl WebRtcPolicy {
+    /// Chromium's defaults.
+    pub fn new() -> WebRtcPolicy {
+        WebRtcPolicy {
+            ip_handling_policy: WebRtcIpHandlingPolicy::Default,
+            multiple_routes_enabled: true,
+            nonproxied_udp_enabled: true,
+            udp_port_range: None,
+            media_devices: None,
+        }
+    }
+    /// A policy that doesn't leak local IP addresses: only the default public
+    /// interface is used and multiple routes are disabled.
+    pub fn private() -> WebRtcPolicy {
+        WebRtcPolicy {
+            ip_handling_policy: WebRtcIpHandlingPolicy::DefaultPublicInterfaceOnly,
+            multiple_routes_enabled: false,
+            nonproxied_udp_enabled: true,
+            udp_port_range: None,
+            media_devices: None,
+        }
+    }
+    /// A policy that never sends UDP traffic outside of a proxy.
+    pub fn proxied_only() -> WebRtcPolicy {
+        WebRtcPolicy {
+            ip_handling_policy: WebRtcIpHandlingPolicy::DisableNonProxiedUdp,
+            multiple_routes_enabled: false,
+            nonproxied_udp_enabled: false,
+            udp_port_range: None,
+            media_devices: None,
+        }
+    }
+    pub fn ip_handling_policy(mut self, ip_handling_policy: WebRtcIpHandlingPolicy) -> Self {
+        self.ip_handling_policy = ip_handling_policy;
+        self
+    }
+    pub fn multiple_routes_enabled(mut self, multiple_routes_enabled: bool) -> Self {
+        self.multiple_routes_enabled = multiple_routes_enabled;
+        self
+    }
+    pub fn nonproxied_udp_enabled(mut self, nonproxied_udp_enabled: bool) -> Self {
+        self.nonproxied_udp_enabled = nonproxied_udp_enabled;
+        self
+    }
+    /// Sets the range of local UDP ports. [RequestContext::set_webrtc_policy]
+    /// rejects ranges where `min` is greater than `max`.
+    pub fn udp_port_range(mut self, min: u16, max: u16) -> Self {
+        self.udp_port_range = Some((min, max));
+        self
+    }
+    pub fn media_devices(mut self, media_devices: MediaDevicePolicy) -> Self {
+        self.media_devices = Some(media_devices);
+        self
+    }
+    /// Routes the `getUserMedia()` and `enumerateDevices()` calls of the
+    /// browser of `host` through [WebRtcPolicy::media_devices], if set. See
+    /// [MediaDevicePolicy::enforce].
+    pub fn enforce(&self, host: &BrowserHost) -> Option<MediaDeviceGuard> {
+        self.media_devices.as_ref().map(|media_devices| media_devices.enforce(host))
+    }
+}
+
+impl Default for WebRtcPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestContext {
+    /// Applies `policy` to this context's preferences. Returns the first error
+    /// reported by [RequestContext::set_preference], if any. This function must
+    /// be called on the browser process UI thread.
+    pub fn set_webrtc_policy(&self, policy: &WebRtcPolicy) -> Result<(), String> {
+        if let Some((min, max)) = policy.udp_port_range {
+            if min > max {
+                return Err(format!("invalid WebRTC UDP port range {}-{}", min, max));
+            }
+        }
+        self.set_preference("webrtc.ip_handling_policy", Some(StoredValue::String(policy.ip_handling_policy.as_pref().to_owned())))?;
+        self.set_preference("webrtc.multiple_routes_enabled", Some(StoredValue::Bool(policy.multiple_routes_enabled)))?;
+        self.set_preference("webrtc.nonproxied_udp_enabled", Some(StoredValue::Bool(policy.nonproxied_udp_enabled)))?;
+        let port_range = policy.udp_port_range.map(|(min, max)| format!("{}-{}", min, max)).unwrap_or_default();
+        self.set_preference("webrtc.udp_port_range", Some(StoredValue::String(port_range)))
+    }
+}
@@ -0,0 +1,299 @@
+//! Finding the files CEF needs at runtime.
+//!
+//! Besides the library itself, CEF needs ICU data and V8 snapshots next to
+//! the library, and resource packs and locale files in the resources and
+//! locales directories, by default next to the library as well (all inside
+//! the framework bundle on macOS). The library is found where it was loaded
+//! from, so setups loading it from outside the executable's directory work
+//! unchanged. If one of the files is missing the process usually aborts in
+//! native code without a useful message. [Context::initialize](crate::Context::initialize)
+//! checks for these files with a [ResourceLocator] built from the [Settings]
+//! and fails with a list of everything missing. Build one yourself to override
+//! the directories:
+//!
+//! ```ignore
+//! let settings = ResourceLocator::new()
+//!     .resources_dir(app_dir.join("cef"))
+//!     .subprocess_name("app-helper")
+//!     .apply(Settings::new());
+//! ```
+
+use crate::{
+    i18n::{self, DEFAULT_LOCALE},
+    settings::Settings,
+};
+use std::{
+    env, fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Files that must be next to the CEF library. Chromium loads them from the
+/// module directory, regardless of the resources directory.
+const REQUIRED_DATA_FILES: &[&str] = &["icudtl.dat", "snapshot_blob.bin", "v8_context_snapshot.bin"];
+
+/// Resource packs that must be in the resources directory unless pack loading
+/// is disabled.
+const REQUIRED_PACK_FILES: &[&str] = &["resources.pak", "chrome_100_percent.pak", "chrome_200_percent.pak"];
+
+/// What a missing file is needed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// ICU data or a V8 snapshot.
+    Data,
+    /// A resource pack.
+    Pack,
+    /// The locale file for the configured locale.
+    Locale,
+    /// The executable launched for child processes.
+    Subprocess,
+}
+
+/// A file a [ResourceLocator] expected but didn't find.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingResource {
+    pub kind: ResourceKind,
+    pub path: PathBuf,
+}
+
+/// Returned by [ResourceLocator::validate], listing every missing file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingResources(pub Vec<MissingResource>);
+
+impl fmt::Display for MissingResources {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CEF resources are missing:")?;
+        for resource in &self.0 {
+            write!(f, "\n  {} ({:?})", resource.path.display(), resource.kind)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingResources {}
+
+impl From<MissingResources> for io::Error {
+    fn from(missing: MissingResources) -> Self {
+        io::Error::new(io::ErrorKind::NotFound, missing)
+    }
+}
+
+/// Locates CEF's runtime files. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceLocator {
+    /// The directory the running executable is in.
+    pub exe_dir: PathBuf,
+    /// The directory the CEF library is in, if not next to the executable.
+    /// Resource packs and locales default to it as well.
+    pub module_dir: Option<PathBuf>,
+    pub resources_dir: Option<PathBuf>,
+    pub locales_dir: Option<PathBuf>,
+    pub subprocess_path: Option<PathBuf>,
+    /// The locale whose file must exist, or the default of
+    /// [i18n::current_locale] if `None`.
+    pub locale: Option<String>,
+    /// Skip resource packs and locales, for when
+    /// [Settings::pack_loading_disabled] is set.
+    pub pack_loading_disabled: bool,
+}
+
+impl ResourceLocator {
+    /// Looks for everything relative to the running executable.
+    pub fn new() -> ResourceLocator {
+        let exe_dir = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_owned))
+            .unwrap_or_default();
+        ResourceLocator {
+            exe_dir,
+            module_dir: None,
+            resources_dir: None,
+            locales_dir: None,
+            subprocess_path: None,
+            locale: None,
+            pack_loading_disabled: false,
+        }
+    }
+    /// Takes the overrides configured in `settings`, and the directory the
+    /// CEF library was loaded from.
+    pub fn from_settings(settings: &Settings) -> ResourceLocator {
+        ResourceLocator {
+            module_dir: macos_resources_dir(settings).or_else(loaded_module_dir),
+            resources_dir: settings.resources_dir_path.clone().or_else(|| macos_resources_dir(settings)),
+            locales_dir: settings.locales_dir_path.clone(),
+            subprocess_path: settings.browser_subprocess_path.clone(),
+            locale: settings.locale.clone().filter(|locale| !locale.is_empty()),
+            pack_loading_disabled: settings.pack_loading_disabled,
+            ..Self::new()
+        }
+    }
+    /// Sets the directory the CEF library, and with it ICU data and V8
+    /// snapshots, is in. Relative paths are relative to the executable.
+    pub fn module_dir<T: Into<PathBuf>>(mut self, module_dir: T) -> Self {
+        self.module_dir = Some(self.exe_dir.join(module_dir.into()));
+        self
+    }
+    /// Sets the directory with resource packs. Relative paths are relative to
+    /// the executable.
+    pub fn resources_dir<T: Into<PathBuf>>(mut self, resources_dir: T) -> Self {
+        self.resources_dir = Some(self.exe_dir.join(resources_dir.into()));
+        self
+    }
+    /// Sets the directory with locale files. Relative paths are relative to
+    /// the executable.
+    pub fn locales_dir<T: Into<PathBuf>>(mut self, locales_dir: T) -> Self {
+        self.locales_dir = Some(self.exe_dir.join(locales_dir.into()));
+        self
+    }
+    /// Sets the executable launched for child processes. Relative paths are
+    /// relative to the executable.
+    pub fn subprocess_path<T: Into<PathBuf>>(mut self, subprocess_path: T) -> Self {
+        self.subprocess_path = Some(self.exe_dir.join(subprocess_path.into()));
+        self
+    }
+    /// Uses the executable `name` next to the running one for child
+    /// processes, adding the platform's extension, e.g. `.exe`.
+    pub fn subprocess_name(self, name: &str) -> Self {
+        self.subprocess_path(format!("{}{}", name, env::consts::EXE_SUFFIX))
+    }
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = Some(locale.to_owned());
+        self
+    }
+
+    /// Returns the directory with the CEF library, ICU data and V8 snapshots.
+    pub fn get_module_dir(&self) -> PathBuf {
+        self.module_dir.clone().unwrap_or_else(|| self.exe_dir.clone())
+    }
+    /// Returns the directory with resource packs.
+    pub fn get_resources_dir(&self) -> PathBuf {
+        self.resources_dir.clone().unwrap_or_else(|| self.get_module_dir())
+    }
+    /// Returns the directory with locale files.
+    pub fn get_locales_dir(&self) -> PathBuf {
+        self.locales_dir.clone().unwrap_or_else(|| self.get_module_dir().join("locales"))
+    }
+    /// Returns the path of `icudtl.dat`.
+    pub fn icu_data_path(&self) -> PathBuf {
+        self.get_module_dir().join("icudtl.dat")
+    }
+    /// Returns the paths of the resource packs CEF loads.
+    pub fn pack_paths(&self) -> Vec<PathBuf> {
+        let resources_dir = self.get_resources_dir();
+        REQUIRED_PACK_FILES.iter().map(|file| resources_dir.join(file)).collect()
+    }
+    /// Returns the locale file that will be loaded, falling back to the one of
+    /// [DEFAULT_LOCALE] like Chromium does, or `None` if neither exists.
+    pub fn locale_path(&self) -> Option<PathBuf> {
+        let locales_dir = self.get_locales_dir();
+        let locale = self.locale.as_ref().map(|locale| i18n::normalize_locale(locale)).unwrap_or_else(i18n::current_locale);
+        [&locale[..], DEFAULT_LOCALE].iter()
+            .map(|locale| locales_dir.join(format!("{}.pak", locale)))
+            .find(|path| path.exists())
+    }
+
+    /// Checks that every file CEF needs exists. Locale files aren't checked on
+    /// macOS, where they are part of the framework bundle.
+    pub fn validate(&self) -> Result<(), MissingResources> {
+        let mut missing = Vec::new();
+        let module_dir = self.get_module_dir();
+        for file in REQUIRED_DATA_FILES {
+            let path = module_dir.join(file);
+            if !path.exists() {
+                missing.push(MissingResource { kind: ResourceKind::Data, path });
+            }
+        }
+        if !self.pack_loading_disabled {
+            for path in self.pack_paths() {
+                if !path.exists() {
+                    missing.push(MissingResource { kind: ResourceKind::Pack, path });
+                }
+            }
+            if !cfg!(target_os = "macos") && self.locale_path().is_none() {
+                missing.push(MissingResource {
+                    kind: ResourceKind::Locale,
+                    path: self.get_locales_dir().join(format!("{}.pak", DEFAULT_LOCALE)),
+                });
+            }
+        }
+        if let Some(path) = &self.subprocess_path {
+            if !path.exists() {
+                missing.push(MissingResource { kind: ResourceKind::Subprocess, path: path.clone() });
+            }
+        }
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(MissingResources(missing)),
+        }
+    }
+    /// Writes the overridden directories and subprocess path into `settings`.
+    pub fn apply(&self, mut settings: Settings) -> Settings {
+        if let Some(resources_dir) = &self.resources_dir {
+            settings.resources_dir_path = Some(resources_dir.clone());
+        }
+        if let Some(locales_dir) = &self.locales_dir {
+            settings.locales_dir_path = Some(locales_dir.clone());
+        }
+        if let Some(subprocess_path) = &self.subprocess_path {
+            settings.browser_subprocess_path = Some(subprocess_path.clone());
+        }
+        if let Some(locale) = &self.locale {
+            settings.locale = Some(locale.clone());
+        }
+        settings
+    }
+}
+
+impl Default for ResourceLocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// On macOS resources are in the framework bundle instead of next to the
+/// executable.
+fn macos_resources_dir(settings: &Settings) -> Option<PathBuf> {
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+    settings.framework_dir_path.as_ref().map(|framework_dir| framework_dir.join("Resources"))
+}
+
+/// Returns the directory of the loaded CEF library. On macOS the framework
+/// directory from the settings is used instead.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn loaded_module_dir() -> Option<PathBuf> {
+    use std::{ffi::CStr, os::unix::ffi::OsStrExt};
+    let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+    let address = cef_sys::cef_initialize as *const libc::c_void;
+    if unsafe { libc::dladdr(address, &mut info) } == 0 || info.dli_fname.is_null() {
+        return None;
+    }
+    let path = Path::new(std::ffi::OsStr::from_bytes(unsafe { CStr::from_ptr(info.dli_fname) }.to_bytes()));
+    path.parent().map(Path::to_owned)
+}
+
+#[cfg(windows)]
+fn loaded_module_dir() -> Option<PathBuf> {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+    use winapi::um::libloaderapi::{
+        GetModuleFileNameW, GetModuleHandleExW, GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+        GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+    };
+    let mut module = std::ptr::null_mut();
+    let flags = GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT;
+    if unsafe { GetModuleHandleExW(flags, cef_sys::cef_initialize as *const u16, &mut module) } == 0 {
+        return None;
+    }
+    let mut buffer = vec![0u16; 32768];
+    let len = unsafe { GetModuleFileNameW(module, buffer.as_mut_ptr(), buffer.len() as u32) } as usize;
+    if len == 0 || len >= buffer.len() {
+        return None;
+    }
+    let path = PathBuf::from(OsString::from_wide(&buffer[..len]));
+    path.parent().map(Path::to_owned)
+}
+
+#[cfg(target_os = "macos")]
+fn loaded_module_dir() -> Option<PathBuf> {
+    None
+}
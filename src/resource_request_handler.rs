@@ -95,14 +95,19 @@ pub trait ResourceRequestHandlerCallbacks: 'static + Sync + Send {
     /// Called on the IO thread when a resource response is received. The `browser`
     /// and `frame` values represent the source of the request, and may be None for
     /// requests originating from service workers or [URLRequest]. The `response`
-    /// object cannot be modified in this callback.
+    /// object cannot be modified in this callback. To allow the resource load to
+    /// proceed without modification return false. To redirect or retry the
+    /// resource load optionally modify `request` and return true. Modification
+    /// of the request URL will be treated as a redirect. Requests handled using
+    /// the default network loader cannot be redirected in this callback.
     fn on_resource_response(
         &self,
         browser: Option<Browser>,
         frame: Option<Frame>,
         request: Request,
         response: Response,
-    ) {
+    ) -> bool {
+        false
     }
     /// Called on the IO thread to optionally filter resource response content. The
     /// `browser` and `frame` values represent the source of the request, and may
@@ -257,8 +262,7 @@ cef_callback_impl! {
                 frame,
                 request,
                 response,
-            );
-            0
+            ) as std::os::raw::c_int
         }
 
         fn get_resource_response_filter(
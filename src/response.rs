@@ -14,18 +14,28 @@ ref_counted_ptr! {
 }
 
 impl Response {
+    /// Create a new, writable response object.
+    pub fn new() -> Self {
+        unsafe { Self::from_ptr_unchecked(cef_response_create()) }
+    }
+
+    /// Returns true if this object is read-only.
     pub fn is_read_only(&self) -> bool {
         self.0
             .is_read_only
             .map(|is_read_only| unsafe { is_read_only(self.0.as_ptr()) != 0 })
             .unwrap_or(true)
-        }
+    }
+    /// Get the response error code. Returns [ErrorCode::None] if there was no
+    /// error.
     pub fn get_error(&self) -> ErrorCode {
         self.0
             .get_error
             .map(|get_error| unsafe { ErrorCode::from_unchecked(get_error(self.0.as_ptr())) })
             .unwrap_or(ErrorCode::Failed)
     }
+    /// Set the response error code. This can be used by custom scheme handlers
+    /// to return errors during initial request processing.
     pub fn set_error(&self, error: ErrorCode) {
         if let Some(set_error) = self.0.set_error {
             unsafe {
@@ -33,12 +43,14 @@ impl Response {
             }
         }
     }
+    /// Get the response status code.
     pub fn get_status(&self) -> i32 {
         self.0
             .get_status
             .map(|get_status| unsafe { get_status(self.0.as_ptr()) } as _)
             .unwrap_or(0)
     }
+    /// Set the response status code.
     pub fn set_status(&self, status: i32) {
         if let Some(set_status) = self.0.set_status {
             unsafe {
@@ -46,6 +58,7 @@ impl Response {
             }
         }
     }
+    /// Get the response status text.
     pub fn get_status_text(&self) -> String {
         self.0
             .get_status_text
@@ -57,6 +70,7 @@ impl Response {
             })
             .unwrap_or_default()
     }
+    /// Set the response status text.
     pub fn set_status_text(&self, status_text: &str) {
         if let Some(set_status_text) = self.0.set_status_text {
             unsafe {
@@ -64,6 +78,7 @@ impl Response {
             }
         }
     }
+    /// Get the response mime type.
     pub fn get_mime_type(&self) -> String {
         self.0
             .get_mime_type
@@ -75,6 +90,7 @@ impl Response {
             })
             .unwrap_or_default()
     }
+    /// Set the response mime type.
     pub fn set_mime_type(&self, mime_type: &str) {
         if let Some(set_mime_type) = self.0.set_mime_type {
             unsafe {
@@ -82,6 +98,7 @@ impl Response {
             }
         }
     }
+    /// Get the response charset.
     pub fn get_charset(&self) -> String {
         self.0
             .get_charset
@@ -93,6 +110,7 @@ impl Response {
             })
             .unwrap_or_default()
     }
+    /// Set the response charset.
     pub fn set_charset(&self, charset: &str) {
         if let Some(set_charset) = self.0.set_charset {
             unsafe {
@@ -100,6 +118,8 @@ impl Response {
             }
         }
     }
+    /// Get the value for the specified response header field. Lookup is
+    /// case-insensitive; an empty string is returned if the header is missing.
     pub fn get_header_by_name(&self, name: &str) -> String {
         self.0
             .get_header_by_name
@@ -111,6 +131,9 @@ impl Response {
             })
             .unwrap_or_default()
     }
+    /// Set the header `name` to `value`. If `overwrite` is true any existing
+    /// values will be replaced with the new value. If `overwrite` is false any
+    /// existing values will not be overwritten.
     pub fn set_header_by_name(&self, name: &str, value: &str, overwrite: bool) {
         if let Some(set_header_by_name) = self.0.set_header_by_name {
             unsafe {
@@ -118,6 +141,7 @@ impl Response {
             }
         }
     }
+    /// Get all response header fields.
     pub fn get_header_map(&self) -> HashMap<String, Vec<String>> {
         self.0
             .get_header_map
@@ -128,12 +152,14 @@ impl Response {
             })
             .unwrap_or_else(HashMap::new)
     }
+    /// Set all response header fields.
     pub fn set_header_map(&self, header_map: &HashMap<String, Vec<String>>) {
         let map: MultiMap = header_map.into();
         self.0
             .set_header_map
             .map(|set_header_map| unsafe { set_header_map(self.as_ptr(), map.as_ptr()); });
     }
+    /// Get the resolved URL after redirects or changed as a result of HSTS.
     pub fn get_url(&self) -> String {
         self.0
             .get_url
@@ -145,6 +171,7 @@ impl Response {
             })
             .unwrap_or_default()
     }
+    /// Set the resolved URL after redirects or changed as a result of HSTS.
     pub fn set_url(&self, url: &str) {
         if let Some(set_url) = self.0.set_url {
             unsafe {
@@ -156,8 +183,6 @@ impl Response {
 
 impl Default for Response {
     fn default() -> Self {
-        unsafe {
-            Self::from_ptr_unchecked(cef_response_create())
-        }
+        Self::new()
     }
 }
@@ -0,0 +1,218 @@
+//! Screenshots of single elements, see [Browser::screenshot_element].
+//!
+//! Capturing an element takes several DevTools round trips: the element is
+//! looked up with `DOM.querySelector`, scrolled into view, measured with
+//! `DOM.getBoxModel` and captured with `Page.captureScreenshot` clipped to its
+//! border box, offset by the scroll position from `Page.getLayoutMetrics`.
+
+use crate::{
+    browser::Browser,
+    devtools::{DevToolsError, DevToolsSession},
+    string::CefString,
+    values::{BinaryValue, DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+/// Error returned by [Browser::screenshot_element].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScreenshotError {
+    /// No element matches the selector.
+    ElementNotFound,
+    /// The element has no layout box, e.g. because it is hidden.
+    NotRendered,
+    /// A DevTools method failed.
+    DevTools(DevToolsError),
+    /// The screenshot data could not be decoded.
+    InvalidImage,
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScreenshotError::ElementNotFound => write!(f, "no element matches the selector"),
+            ScreenshotError::NotRendered => write!(f, "the element is not rendered"),
+            ScreenshotError::DevTools(error) => error.fmt(f),
+            ScreenshotError::InvalidImage => write!(f, "the screenshot could not be decoded"),
+        }
+    }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+impl From<DevToolsError> for ScreenshotError {
+    fn from(error: DevToolsError) -> Self {
+        ScreenshotError::DevTools(error)
+    }
+}
+
+pub type ScreenshotResult = Result<Vec<u8>, ScreenshotError>;
+
+#[derive(Default)]
+struct ScreenshotState {
+    result: Option<ScreenshotResult>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the PNG encoded screenshot of an element. The screenshot is
+/// taken whether or not the future is polled.
+pub struct Screenshot(Arc<Mutex<ScreenshotState>>);
+
+impl Future for Screenshot {
+    type Output = ScreenshotResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<ScreenshotResult> {
+        let mut state = self.0.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Completes a [Screenshot]. Every step of the capture holds a clone of the
+/// session, which keeps its observer registered until the last result arrived.
+#[derive(Clone)]
+struct Capture {
+    session: DevToolsSession,
+    state: Arc<Mutex<ScreenshotState>>,
+}
+
+impl Capture {
+    fn finish(&self, result: ScreenshotResult) {
+        let waker = {
+            let mut state = self.state.lock();
+            state.result = Some(result);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+    /// Executes `method` and passes its result dictionary to `next`, or
+    /// finishes the capture with the error.
+    fn step(
+        &self,
+        method: &str,
+        params: Option<DictionaryValue>,
+        next: impl 'static + Send + FnOnce(&Capture, DictionaryValue),
+    ) {
+        let capture = self.clone();
+        self.session.execute(method, params, move |result| match result {
+            Ok(StoredValue::Dictionary(result)) => next(&capture, result),
+            Ok(_) => capture.finish(Err(DevToolsError { code: -1, message: "unexpected result".to_owned() }.into())),
+            Err(error) => capture.finish(Err(error.into())),
+        });
+    }
+}
+
+pub(crate) fn screenshot_element(browser: &Browser, selector: &str) -> Screenshot {
+    let state = Arc::new(Mutex::new(ScreenshotState::default()));
+    let capture = Capture {
+        session: DevToolsSession::new(&browser.get_host()),
+        state: state.clone(),
+    };
+    let selector = selector.to_owned();
+    let params = DictionaryValue::new();
+    params.insert_int("depth", 0);
+    capture.step("DOM.getDocument", Some(params), move |capture, result| {
+        let root = match result.try_get_dictionary("root") {
+            Some(root) => root.get_int("nodeId"),
+            None => return capture.finish(Err(ScreenshotError::ElementNotFound)),
+        };
+        let params = DictionaryValue::new();
+        params.insert_int("nodeId", root);
+        params.insert_string("selector", &selector);
+        capture.step("DOM.querySelector", Some(params), |capture, result| {
+            let node_id = result.get_int("nodeId");
+            if node_id == 0 {
+                return capture.finish(Err(ScreenshotError::ElementNotFound));
+            }
+            let params = DictionaryValue::new();
+            params.insert_int("nodeId", node_id);
+            // Elements outside of the viewport are not painted, so scroll the
+            // element into view first. Failures are caught by the box model.
+            capture.session.send("DOM.scrollIntoViewIfNeeded", Some(params));
+            let params = DictionaryValue::new();
+            params.insert_int("nodeId", node_id);
+            capture.step("DOM.getBoxModel", Some(params), |capture, result| {
+                let (x, y, width, height) = match result.try_get_dictionary("model").and_then(|model| border_box(&model)) {
+                    Some(bounds) => bounds,
+                    None => return capture.finish(Err(ScreenshotError::NotRendered)),
+                };
+                capture.step("Page.getLayoutMetrics", None, move |capture, result| {
+                    // The box model is relative to the viewport while the clip
+                    // is relative to the document.
+                    let (page_x, page_y) = match result.try_get_dictionary("layoutViewport") {
+                        Some(viewport) => (
+                            number(&viewport.get("pageX")).unwrap_or(0.0),
+                            number(&viewport.get("pageY")).unwrap_or(0.0),
+                        ),
+                        None => (0.0, 0.0),
+                    };
+                    let clip = DictionaryValue::new();
+                    clip.insert_double("x", x + page_x);
+                    clip.insert_double("y", y + page_y);
+                    clip.insert_double("width", width);
+                    clip.insert_double("height", height);
+                    clip.insert_double("scale", 1.0);
+                    let params = DictionaryValue::new();
+                    params.insert_string("format", "png");
+                    params.insert_dictionary("clip", clip);
+                    capture.step("Page.captureScreenshot", Some(params), |capture, result| {
+                        capture.finish(decode_base64(&result.get_string("data")).ok_or(ScreenshotError::InvalidImage));
+                    });
+                });
+            });
+        });
+    });
+    Screenshot(state)
+}
+
+/// Returns the bounding rectangle of the `border` quad of a box model as
+/// `(x, y, width, height)`.
+fn border_box(model: &DictionaryValue) -> Option<(f64, f64, f64, f64)> {
+    let quad = model.try_get_list("border")?;
+    let points = (0..quad.len())
+        .map(|index| quad.get(index).as_ref().and_then(number))
+        .collect::<Option<Vec<f64>>>()?;
+    if points.len() != 8 {
+        return None;
+    }
+    let xs = points.iter().step_by(2);
+    let ys = points.iter().skip(1).step_by(2);
+    let min_x = xs.clone().cloned().fold(f64::INFINITY, f64::min);
+    let max_x = xs.cloned().fold(f64::NEG_INFINITY, f64::max);
+    let min_y = ys.clone().cloned().fold(f64::INFINITY, f64::min);
+    let max_y = ys.cloned().fold(f64::NEG_INFINITY, f64::max);
+    if max_x <= min_x || max_y <= min_y {
+        return None;
+    }
+    Some((min_x, min_y, max_x - min_x, max_y - min_y))
+}
+
+/// DevTools numbers without a fraction arrive as integers.
+fn number(value: &StoredValue) -> Option<f64> {
+    match value {
+        StoredValue::Int(value) => Some(*value as f64),
+        StoredValue::Double(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    if data.is_empty() {
+        return None;
+    }
+    unsafe { BinaryValue::from_ptr(cef_sys::cef_base64decode(CefString::new(data).as_ptr())) }
+        .map(|binary| binary.to_vec())
+}
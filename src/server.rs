@@ -0,0 +1,347 @@
+use cef_sys::{
+    cef_callback_t, cef_request_t, cef_server_create, cef_server_handler_t, cef_server_t,
+    cef_string_t, cef_string_userfree_utf16_free,
+};
+use std::{collections::HashMap, ffi::c_void, os::raw::c_int, slice};
+
+use crate::{
+    callback::Callback,
+    multimap::MultiMap,
+    refcounted::{RefCountedPtr, Wrapper},
+    request::Request,
+    string::CefString,
+    task::TaskRunner,
+};
+
+ref_counted_ptr! {
+    /// Structure representing a server that supports HTTP and WebSocket requests.
+    /// Server capacity is limited and is intended to handle only a small number of
+    /// simultaneous connections (e.g. for communicating between applications on
+    /// localhost). The functions of this structure are safe to call from any
+    /// thread in the browser process unless otherwise indicated.
+    pub struct Server(*mut cef_server_t);
+}
+
+impl Server {
+    /// Create a new server that binds to `address` and `port`. `address` must be
+    /// a valid IPv4 or IPv6 address (e.g. 127.0.0.1 or ::1) and `port` must be a
+    /// port number outside of the reserved range (e.g. between 1025 and 65535 on
+    /// most platforms). `backlog` is the maximum number of pending connections. A
+    /// new thread will be created for each created server. It is therefore
+    /// recommended to use a different [ServerHandler] instance for each created
+    /// server. [ServerHandlerCallbacks::on_server_created] will be called with
+    /// the new server, successful or not, and [ServerHandlerCallbacks::on_server_destroyed]
+    /// will be called when the server has stopped.
+    pub fn create(address: &str, port: u16, backlog: i32, handler: ServerHandler) {
+        unsafe {
+            cef_server_create(CefString::new(address).as_ptr(), port, backlog, handler.into_raw());
+        }
+    }
+    /// Returns the task runner for the dedicated server thread.
+    pub fn get_task_runner(&self) -> Option<TaskRunner> {
+        unsafe { TaskRunner::from_ptr(self.0.get_task_runner.unwrap()(self.as_ptr())) }
+    }
+    /// Stop the server and shut down the dedicated server thread. See
+    /// [ServerHandlerCallbacks::on_server_created] documentation for a
+    /// description of server lifespan.
+    pub fn shutdown(&self) {
+        unsafe { self.0.shutdown.unwrap()(self.as_ptr()) }
+    }
+    /// Returns true if the server is currently running and accepting incoming
+    /// connections. See [ServerHandlerCallbacks::on_server_created] documentation
+    /// for a description of server lifespan. This function must be called on the
+    /// dedicated server thread.
+    pub fn is_running(&self) -> bool {
+        unsafe { self.0.is_running.unwrap()(self.as_ptr()) != 0 }
+    }
+    /// Returns the server address including the port number.
+    pub fn get_address(&self) -> String {
+        let address = unsafe { self.0.get_address.unwrap()(self.as_ptr()) };
+        let result = unsafe { CefString::from_ptr(address) }.map(String::from);
+        if result.is_some() {
+            unsafe {
+                cef_string_userfree_utf16_free(address);
+            }
+        }
+        result.unwrap_or_default()
+    }
+    /// Returns true if the server currently has a connection. This function must
+    /// be called on the dedicated server thread.
+    pub fn has_connection(&self) -> bool {
+        unsafe { self.0.has_connection.unwrap()(self.as_ptr()) != 0 }
+    }
+    /// Returns true if `connection_id` represents a valid connection. This
+    /// function must be called on the dedicated server thread.
+    pub fn is_valid_connection(&self, connection_id: i32) -> bool {
+        unsafe { self.0.is_valid_connection.unwrap()(self.as_ptr(), connection_id) != 0 }
+    }
+    /// Send an HTTP 200 "OK" response to the connection identified by
+    /// `connection_id`. `content_type` is the response content type (e.g.
+    /// "text/html") and `data` is the response content. The connection will be
+    /// closed automatically after the response is sent.
+    pub fn send_http200_response(&self, connection_id: i32, content_type: &str, data: &[u8]) {
+        unsafe {
+            self.0.send_http200response.unwrap()(
+                self.as_ptr(),
+                connection_id,
+                CefString::new(content_type).as_ptr(),
+                data.as_ptr() as *const c_void,
+                data.len(),
+            );
+        }
+    }
+    /// Send an HTTP 404 "Not Found" response to the connection identified by
+    /// `connection_id`. The connection will be closed automatically after the
+    /// response is sent.
+    pub fn send_http404_response(&self, connection_id: i32) {
+        unsafe { self.0.send_http404response.unwrap()(self.as_ptr(), connection_id) }
+    }
+    /// Send an HTTP 500 "Internal Server Error" response to the connection
+    /// identified by `connection_id`. `error_message` is the associated error
+    /// message. The connection will be closed automatically after the response is
+    /// sent.
+    pub fn send_http500_response(&self, connection_id: i32, error_message: &str) {
+        unsafe {
+            self.0.send_http500response.unwrap()(
+                self.as_ptr(),
+                connection_id,
+                CefString::new(error_message).as_ptr(),
+            );
+        }
+    }
+    /// Send a custom HTTP response to the connection identified by
+    /// `connection_id`. `response_code` is the HTTP response code sent in the
+    /// status line (e.g. 200), `content_type` is the response content type sent
+    /// as the "Content-Type" header (e.g. "text/html"), `content_length` is the
+    /// expected content length, and `extra_headers` is the map of extra response
+    /// headers. If `content_length` is `None` then no "Content-Length" header will
+    /// be sent. If `content_length` is `Some` then the "Content-Length" header
+    /// will be sent and the content must be sent separately with
+    /// [Server::send_raw_data]; the connection must then be closed with
+    /// [Server::close_connection] once the content has been sent.
+    pub fn send_http_response(
+        &self,
+        connection_id: i32,
+        response_code: i32,
+        content_type: &str,
+        content_length: Option<u64>,
+        extra_headers: &HashMap<String, Vec<String>>,
+    ) {
+        let extra_headers = MultiMap::from(extra_headers);
+        unsafe {
+            self.0.send_http_response.unwrap()(
+                self.as_ptr(),
+                connection_id,
+                response_code,
+                CefString::new(content_type).as_ptr(),
+                content_length.map(|length| length as i64).unwrap_or(-1),
+                extra_headers.as_ptr(),
+            );
+        }
+    }
+    /// Send raw data directly to the connection identified by `connection_id`.
+    /// This function should only be used after sending the response headers with
+    /// [Server::send_http_response].
+    pub fn send_raw_data(&self, connection_id: i32, data: &[u8]) {
+        unsafe {
+            self.0.send_raw_data.unwrap()(
+                self.as_ptr(),
+                connection_id,
+                data.as_ptr() as *const c_void,
+                data.len(),
+            );
+        }
+    }
+    /// Close the connection identified by `connection_id`. See
+    /// [Server::send_http_response] documentation for intended usage.
+    pub fn close_connection(&self, connection_id: i32) {
+        unsafe { self.0.close_connection.unwrap()(self.as_ptr(), connection_id) }
+    }
+    /// Send a WebSocket message to the connection identified by `connection_id`.
+    /// Messages are sent as text frames. See
+    /// [ServerHandlerCallbacks::on_web_socket_request] documentation for intended
+    /// usage.
+    pub fn send_web_socket_message(&self, connection_id: i32, data: &[u8]) {
+        unsafe {
+            self.0.send_web_socket_message.unwrap()(
+                self.as_ptr(),
+                connection_id,
+                data.as_ptr() as *const c_void,
+                data.len(),
+            );
+        }
+    }
+}
+
+ref_counted_ptr! {
+    /// Implement this structure to handle HTTP server requests. A new thread will
+    /// be created for each [Server::create] call, and the functions of this
+    /// structure will be called on that thread.
+    pub struct ServerHandler(*mut cef_server_handler_t);
+}
+
+impl ServerHandler {
+    pub fn new<C: ServerHandlerCallbacks>(callbacks: C) -> ServerHandler {
+        unsafe { ServerHandler::from_ptr_unchecked(ServerHandlerWrapper(Box::new(callbacks)).wrap().into_raw()) }
+    }
+}
+
+/// Implement this trait to handle HTTP server requests. The functions of this
+/// trait will be called on the dedicated server thread.
+///
+/// Requests are handled with the functions of [Server]. Unless noted
+/// otherwise, a connection must be responded to, or closed with
+/// [Server::close_connection], to free its resources.
+pub trait ServerHandlerCallbacks: 'static + Send + Sync {
+    /// Called when `server` is created. If the server was started successfully
+    /// then [Server::is_running] will return true. The server will continue
+    /// running until [Server::shutdown] is called, after which time
+    /// [ServerHandlerCallbacks::on_server_destroyed] will be called. If the
+    /// server failed to start then `on_server_destroyed` will be called
+    /// immediately after this function returns.
+    fn on_server_created(&self, server: Server) {}
+    /// Called when `server` is destroyed. The server thread will be stopped
+    /// after this function returns. The client should release any references to
+    /// `server` when this function is called.
+    fn on_server_destroyed(&self, server: Server) {}
+    /// Called when a client connects to `server`. `connection_id` uniquely
+    /// identifies the connection. Each call to this function will have a
+    /// matching call to [ServerHandlerCallbacks::on_client_disconnected].
+    fn on_client_connected(&self, server: Server, connection_id: i32) {}
+    /// Called when a client disconnects from `server`. `connection_id` uniquely
+    /// identifies the connection. The client should release any data associated
+    /// with `connection_id` when this function is called and `connection_id`
+    /// should no longer be passed to [Server] functions. Disconnects can
+    /// originate from either the client or the server. For example, the server
+    /// will disconnect automatically after a [Server::send_http200_response]
+    /// call.
+    fn on_client_disconnected(&self, server: Server, connection_id: i32) {}
+    /// Called when `server` receives an HTTP request. `connection_id` uniquely
+    /// identifies the connection, `client_address` is the requesting IPv4 or IPv6
+    /// client address including port number, and `request` contains the request
+    /// contents (URL, function, headers and optional POST data). Call [Server]
+    /// functions either synchronously or asynchronously to send a response.
+    fn on_http_request(&self, server: Server, connection_id: i32, client_address: &str, request: Request) {
+        server.send_http404_response(connection_id);
+    }
+    /// Called when `server` receives a WebSocket request. `connection_id` uniquely
+    /// identifies the connection, `client_address` is the requesting IPv4 or IPv6
+    /// client address including port number, and `request` contains the request
+    /// contents (URL, function, headers and optional POST data). Execute
+    /// `callback` either synchronously or asynchronously to accept or decline the
+    /// WebSocket connection. If the request is accepted then
+    /// [ServerHandlerCallbacks::on_web_socket_connected] will be called after the
+    /// WebSocket has connected and incoming messages will be delivered to
+    /// [ServerHandlerCallbacks::on_web_socket_message]. If the request is declined
+    /// then the client will be disconnected and
+    /// [ServerHandlerCallbacks::on_client_disconnected] will be called. Call
+    /// [Server::send_web_socket_message] to send messages to the client.
+    fn on_web_socket_request(
+        &self,
+        server: Server,
+        connection_id: i32,
+        client_address: &str,
+        request: Request,
+        callback: Callback,
+    ) {
+        callback.cancel();
+    }
+    /// Called after the client has accepted the WebSocket connection for
+    /// `server` and `connection_id` via the callback passed to
+    /// [ServerHandlerCallbacks::on_web_socket_request].
+    fn on_web_socket_connected(&self, server: Server, connection_id: i32) {}
+    /// Called when `server` receives a WebSocket message. `connection_id`
+    /// uniquely identifies the connection and `data` is the message content.
+    /// `data` is only valid for the scope of this callback and should be copied
+    /// if necessary.
+    fn on_web_socket_message(&self, server: Server, connection_id: i32, data: &[u8]) {}
+}
+
+struct ServerHandlerWrapper(Box<dyn ServerHandlerCallbacks>);
+
+impl Wrapper for ServerHandlerWrapper {
+    type Cef = cef_server_handler_t;
+    fn wrap(self) -> RefCountedPtr<Self::Cef> {
+        RefCountedPtr::wrap(
+            cef_server_handler_t {
+                base: unsafe { std::mem::zeroed() },
+                on_server_created: Some(Self::on_server_created),
+                on_server_destroyed: Some(Self::on_server_destroyed),
+                on_client_connected: Some(Self::on_client_connected),
+                on_client_disconnected: Some(Self::on_client_disconnected),
+                on_http_request: Some(Self::on_http_request),
+                on_web_socket_request: Some(Self::on_web_socket_request),
+                on_web_socket_connected: Some(Self::on_web_socket_connected),
+                on_web_socket_message: Some(Self::on_web_socket_message),
+            },
+            self,
+        )
+    }
+}
+
+cef_callback_impl! {
+    impl for ServerHandlerWrapper: cef_server_handler_t {
+        fn on_server_created(
+            &self,
+            server: Server: *mut cef_server_t,
+        ) {
+            self.0.on_server_created(server);
+        }
+        fn on_server_destroyed(
+            &self,
+            server: Server: *mut cef_server_t,
+        ) {
+            self.0.on_server_destroyed(server);
+        }
+        fn on_client_connected(
+            &self,
+            server: Server: *mut cef_server_t,
+            connection_id: i32: c_int,
+        ) {
+            self.0.on_client_connected(server, connection_id);
+        }
+        fn on_client_disconnected(
+            &self,
+            server: Server: *mut cef_server_t,
+            connection_id: i32: c_int,
+        ) {
+            self.0.on_client_disconnected(server, connection_id);
+        }
+        fn on_http_request(
+            &self,
+            server: Server: *mut cef_server_t,
+            connection_id: i32: c_int,
+            client_address: &CefString: *const cef_string_t,
+            request: Request: *mut cef_request_t,
+        ) {
+            self.0.on_http_request(server, connection_id, &String::from(client_address), request);
+        }
+        fn on_web_socket_request(
+            &self,
+            server: Server: *mut cef_server_t,
+            connection_id: i32: c_int,
+            client_address: &CefString: *const cef_string_t,
+            request: Request: *mut cef_request_t,
+            callback: Callback: *mut cef_callback_t,
+        ) {
+            self.0.on_web_socket_request(server, connection_id, &String::from(client_address), request, callback);
+        }
+        fn on_web_socket_connected(
+            &self,
+            server: Server: *mut cef_server_t,
+            connection_id: i32: c_int,
+        ) {
+            self.0.on_web_socket_connected(server, connection_id);
+        }
+        fn on_web_socket_message(
+            &self,
+            server: Server: *mut cef_server_t,
+            connection_id: i32: c_int,
+            data: *const c_void: *const c_void,
+            data_size: usize: usize,
+        ) {
+            let data = unsafe { slice::from_raw_parts(data as *const u8, data_size) };
+            self.0.on_web_socket_message(server, connection_id, data);
+        }
+    }
+}
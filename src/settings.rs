@@ -1,6 +1,8 @@
 use std::os::raw::c_int;
 use crate::{
-    color::Color,
+    color::{Color, Transparency},
+    gpu::GpuOptions,
+    i18n,
     string::CefString,
 };
 use cef_sys::{cef_log_severity_t, cef_settings_t};
@@ -34,6 +36,8 @@ impl LogSeverity {
     }
 }
 
+pub(crate) const SINGLE_PROCESS_SWITCH: &str = "single-process";
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Settings {
     /// Set the path to a separate executable that will be launched for sub-processes.
@@ -210,6 +214,11 @@ pub struct Settings {
     /// will be an empty string and the file will be treated as an untrusted
     /// file when the GUID is empty.
     pub application_client_id_for_file_scanning: Option<Uuid>,
+    /// Additional switches appended to the browser process command line, as
+    /// `(name, value)` pairs without the leading dashes. They are applied before
+    /// [AppCallbacks::on_before_command_line_processing] is called, so the
+    /// callback can still inspect or override them.
+    pub command_line_switches: Vec<(String, Option<String>)>,
 }
 
 impl Settings {
@@ -242,6 +251,7 @@ impl Settings {
             background_color: Color::wrap(0),
             accept_language_list: None,
             application_client_id_for_file_scanning: None,
+            command_line_switches: Vec::new(),
         }
     }
     pub(crate) fn to_cef(&self, use_sandbox: bool) -> Result<cef_settings_t, std::io::Error> {
@@ -339,6 +349,26 @@ impl Settings {
         self.locale = Some(locale.into());
         self
     }
+    /// Sets `locale` to the locale of the operating system, falling back to
+    /// another region of the same language or to the default locale if the
+    /// locales directory has no file for it. Call after
+    /// [Settings::locales_dir_path]. Has no effect on Linux, where CEF always
+    /// reads the locale from the environment. See [crate::i18n].
+    pub fn system_locale(mut self) -> Self {
+        let preferred = match i18n::system_locale() {
+            Some(locale) => locale,
+            None => return self,
+        };
+        let locales_dir = self.locales_dir_path.clone().or_else(i18n::default_locales_dir);
+        let available = locales_dir.map(|dir| i18n::available_locales(&dir)).unwrap_or_default();
+        self.locale = if available.is_empty() {
+            // Without a locales directory to check against, trust the name.
+            Some(i18n::normalize_locale(&preferred))
+        } else {
+            i18n::best_locale(&preferred, &available)
+        };
+        self
+    }
     pub fn log_file<T: Into<PathBuf>>(mut self, log_file: T) -> Self {
         self.log_file = Some(log_file.into());
         self
@@ -379,6 +409,14 @@ impl Settings {
         self.background_color = background_color;
         self
     }
+    /// Sets `background_color` from a [Transparency]. A transparent background
+    /// also turns on `windowless_rendering_enabled`, which transparent painting
+    /// depends on.
+    pub fn transparency(mut self, transparency: Transparency) -> Self {
+        self.background_color = transparency.to_color();
+        self.windowless_rendering_enabled |= transparency.is_transparent();
+        self
+    }
     pub fn accept_language_list<T: Into<String>>(mut self, accept_language_list: T) -> Self {
         self.accept_language_list = Some(accept_language_list.into());
         self
@@ -387,6 +425,102 @@ impl Settings {
         self.application_client_id_for_file_scanning = Some(application_client_id_for_file_scanning.into());
         self
     }
+    pub fn command_line_switch<T: Into<String>>(mut self, name: T) -> Self {
+        self.command_line_switches.push((name.into(), None));
+        self
+    }
+    pub fn command_line_switch_with_value<T: Into<String>, V: Into<String>>(mut self, name: T, value: V) -> Self {
+        self.command_line_switches.push((name.into(), Some(value.into())));
+        self
+    }
+    /// Disables Chromium's print preview, so printing goes straight to the
+    /// platform print dialog or, on Linux, to the [PrintHandler]. Sets the
+    /// "disable-print-preview" switch.
+    pub fn disable_print_preview(self) -> Self {
+        self.command_line_switch("disable-print-preview")
+    }
+    /// Prints to the default printer without showing a print dialog. Sets the
+    /// "kiosk-printing" switch and disables print preview. On Linux the job is
+    /// still handed to the [PrintHandler]; use [SilentPrintHandler] to skip the
+    /// dialog there as well.
+    pub fn kiosk_printing(self) -> Self {
+        self.disable_print_preview().command_line_switch("kiosk-printing")
+    }
+    /// Allows web content to request camera and microphone access and to
+    /// enumerate media devices. Without this switch CEF denies all
+    /// `getUserMedia()` requests. Sets the "enable-media-stream" switch.
+    pub fn enable_media_stream(self) -> Self {
+        self.command_line_switch("enable-media-stream")
+    }
+    /// Disables the APIs that `privacy_profile` disables and Chromium has a
+    /// switch for. The rest of the profile is applied per request context and
+    /// browser, see [PrivacyProfile](crate::privacy::PrivacyProfile).
+    pub fn privacy_profile(mut self, privacy_profile: &crate::privacy::PrivacyProfile) -> Self {
+        crate::device_policy::disable_blink_features(&mut self.command_line_switches, &privacy_profile.disabled_blink_features());
+        self
+    }
+    /// Shares the screen or window titled `title`, e.g. `Entire screen`,
+    /// whenever a page calls `getDisplayMedia()`, without showing a picker.
+    /// Sets the "auto-select-desktop-capture-source" switch. Use
+    /// [DisplayMediaPicker](crate::display_media::DisplayMediaPicker) to decide
+    /// per request instead.
+    pub fn auto_select_display_media(mut self, title: &str) -> Self {
+        for (name, value) in crate::display_media::auto_select_switches(title) {
+            self.command_line_switches.push((name.to_string(), value));
+        }
+        self
+    }
+    /// Appends the switches of a [FakeMediaDevices](crate::media_devices::FakeMediaDevices)
+    /// configuration, replacing real cameras and microphones.
+    pub fn fake_media_devices(mut self, fake_media_devices: &crate::media_devices::FakeMediaDevices) -> Self {
+        for (name, value) in fake_media_devices.switches() {
+            self.command_line_switches.push((name.to_string(), value));
+        }
+        self
+    }
+    /// Runs the renderer in the browser process instead of in separate
+    /// processes, so a single debugger session covers the whole pipeline. Sets
+    /// the "single-process" switch.
+    ///
+    /// **For debugging only, never ship this.** Chromium doesn't support
+    /// single-process mode and it is known to crash and misbehave:
+    ///
+    /// - A crashing or hung page takes down the whole application.
+    /// - Site isolation is off, so pages can read each other's memory.
+    /// - Browser and render process handlers run in the same process, on
+    ///   different threads. Globals are shared between them and
+    ///   [process_type](crate::process_type) always returns
+    ///   [ProcessType::Browser](crate::ProcessType::Browser); use
+    ///   [is_single_process](crate::is_single_process) to tell.
+    /// - Sandboxing must be disabled, i.e. pass no [SandboxInfo](crate::sandbox::SandboxInfo).
+    /// - Not supported at all on macOS.
+    pub fn single_process(self) -> Self {
+        self.command_line_switch(SINGLE_PROCESS_SWITCH)
+    }
+    /// Enables or disables Chromium's built-in PDF viewer. It is enabled by
+    /// default; when disabled, PDF documents are downloaded instead of shown.
+    /// Sets the "disable-pdf-extension" switch. See [crate::pdf].
+    pub fn pdf_viewer(self, enabled: bool) -> Self {
+        if enabled {
+            self
+        } else {
+            self.command_line_switch(crate::pdf::DISABLE_PDF_EXTENSION_SWITCH)
+        }
+    }
+    /// Appends the switches of a [GpuOptions] preset.
+    pub fn gpu_options(mut self, gpu_options: GpuOptions) -> Self {
+        for (name, value) in gpu_options.switches() {
+            self.command_line_switches.push((name.to_string(), value.map(String::from)));
+        }
+        self
+    }
+    /// Disables the WebUSB and WebHID APIs entirely if `device_policy` denies
+    /// them everywhere. Origin allowlists are applied per request context, see
+    /// [DevicePolicy](crate::device_policy::DevicePolicy).
+    pub fn device_policy(mut self, device_policy: &crate::device_policy::DevicePolicy) -> Self {
+        crate::device_policy::disable_blink_features(&mut self.command_line_switches, &device_policy.disabled_blink_features());
+        self
+    }
 }
 
 pub(crate) unsafe fn drop_settings(settings: cef_settings_t) {
@@ -0,0 +1,178 @@
+//! Closing many browsers before [Context] is dropped.
+//!
+//! `cef_shutdown` must not be called while browsers are still open, but
+//! closing a browser is asynchronous, may run `onbeforeunload` handlers and can
+//! stall on a hung renderer. [Shutdown] closes a set of browsers, waits for
+//! each of them to reach [LifeSpanHandlerCallbacks::on_before_close], force
+//! closes the ones that don't make it in time and finally gives up on the rest.
+//!
+//! The crate can't observe `on_before_close` by itself, so forward it with
+//! [Shutdown::browser_closed]:
+//!
+//! ```ignore
+//! impl LifeSpanHandlerCallbacks for MyLifeSpanHandler {
+//!     fn on_before_close(&self, browser: Browser) {
+//!         Shutdown::browser_closed(&browser);
+//!     }
+//! }
+//! ```
+//!
+//! [LifeSpanHandlerCallbacks::on_before_close]: crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_before_close
+
+use crate::{
+    browser::Browser,
+    task::{TaskRunner, ThreadId},
+    Context,
+};
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::HashMap,
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Weak},
+    time::Duration,
+};
+
+lazy_static::lazy_static! {
+    static ref ACTIVE: Mutex<Vec<Weak<ShutdownState>>> = Mutex::new(Vec::new());
+}
+
+struct PendingBrowser {
+    browser: Browser,
+    force_closed: bool,
+}
+
+struct ShutdownState {
+    pending: Mutex<HashMap<i32, PendingBrowser>>,
+    abandoned: AtomicBool,
+    done: Condvar,
+}
+
+impl ShutdownState {
+    fn is_complete(&self) -> bool {
+        self.abandoned.load(Ordering::SeqCst) || self.pending.lock().is_empty()
+    }
+    fn remove(&self, browser_id: i32) {
+        let mut pending = self.pending.lock();
+        pending.remove(&browser_id);
+        if pending.is_empty() {
+            self.done.notify_all();
+        }
+    }
+}
+
+/// A running shutdown sequence. See the [module documentation](self).
+#[derive(Clone)]
+pub struct Shutdown(Arc<ShutdownState>);
+
+impl Shutdown {
+    /// Starts closing `browsers`. Each browser is asked to close with
+    /// [BrowserHost::try_close_browser](crate::browser_host::BrowserHost::try_close_browser),
+    /// which runs `onbeforeunload` handlers. Browsers that haven't closed after
+    /// `timeout` are closed with `close_browser(true)`, and browsers that
+    /// still haven't closed after another `timeout` are given up on.
+    pub fn begin(browsers: Vec<Browser>, timeout: Duration) -> Shutdown {
+        let pending = browsers.into_iter()
+            .map(|browser| (browser.get_identifier(), PendingBrowser { browser, force_closed: false }))
+            .collect::<HashMap<_, _>>();
+        let state = Arc::new(ShutdownState {
+            pending: Mutex::new(pending),
+            abandoned: AtomicBool::new(false),
+            done: Condvar::new(),
+        });
+        ACTIVE.lock().push(Arc::downgrade(&state));
+
+        let timeout_ms = timeout.as_millis() as i64;
+        let weak = Arc::downgrade(&state);
+        let start = move || {
+            let state = match weak.upgrade() {
+                Some(state) => state,
+                None => return,
+            };
+            let browsers = state.pending.lock().values()
+                .map(|pending| pending.browser.clone())
+                .collect::<Vec<_>>();
+            for browser in browsers {
+                browser.get_host().try_close_browser();
+            }
+            let weak = weak.clone();
+            TaskRunner::post_delayed_task_on(ThreadId::UI, move || Self::force_close(weak, timeout_ms), timeout_ms);
+        };
+        if TaskRunner::currently_on(ThreadId::UI) {
+            start();
+        } else {
+            TaskRunner::post_task_on(ThreadId::UI, start);
+        }
+        Shutdown(state)
+    }
+    /// Notifies all running shutdown sequences that `browser` has closed. Call
+    /// this from [LifeSpanHandlerCallbacks::on_before_close](crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_before_close).
+    pub fn browser_closed(browser: &Browser) {
+        let browser_id = browser.get_identifier();
+        let mut active = ACTIVE.lock();
+        active.retain(|state| match state.upgrade() {
+            Some(state) => {
+                state.remove(browser_id);
+                !state.is_complete()
+            }
+            None => false,
+        });
+    }
+    /// Returns true once every browser has closed or the sequence gave up on
+    /// the remaining ones.
+    pub fn is_complete(&self) -> bool {
+        self.0.is_complete()
+    }
+    /// Returns the browsers that haven't closed yet.
+    pub fn remaining(&self) -> Vec<Browser> {
+        self.0.pending.lock().values().map(|pending| pending.browser.clone()).collect()
+    }
+    /// Pumps the CEF message loop until the sequence is complete. Use this when
+    /// the application drives the message loop itself, i.e. without
+    /// [Settings::multi_threaded_message_loop](crate::settings::Settings::multi_threaded_message_loop).
+    /// Must be called on the main application thread.
+    pub fn run_until_complete(&self, context: &Context) {
+        while !self.is_complete() {
+            context.do_message_loop_work();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+    /// Blocks until the sequence is complete. Only use this with
+    /// [Settings::multi_threaded_message_loop](crate::settings::Settings::multi_threaded_message_loop),
+    /// from a thread other than the CEF UI thread.
+    pub fn wait(&self) {
+        let mut pending = self.0.pending.lock();
+        while !pending.is_empty() && !self.0.abandoned.load(Ordering::SeqCst) {
+            self.0.done.wait(&mut pending);
+        }
+    }
+
+    fn force_close(weak: Weak<ShutdownState>, timeout_ms: i64) {
+        let state = match weak.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let browsers = state.pending.lock().values_mut()
+            .filter(|pending| !pending.force_closed)
+            .map(|pending| {
+                pending.force_closed = true;
+                pending.browser.clone()
+            })
+            .collect::<Vec<_>>();
+        if browsers.is_empty() {
+            return;
+        }
+        for browser in browsers {
+            log::warn!("browser {} did not close in time, forcing it to close", browser.get_identifier());
+            browser.get_host().close_browser(true);
+        }
+        TaskRunner::post_delayed_task_on(ThreadId::UI, move || {
+            if let Some(state) = weak.upgrade() {
+                let pending = state.pending.lock();
+                if !pending.is_empty() {
+                    log::error!("giving up on {} browsers that did not close", pending.len());
+                    state.abandoned.store(true, Ordering::SeqCst);
+                    state.done.notify_all();
+                }
+            }
+        }, timeout_ms);
+    }
+}
@@ -0,0 +1,297 @@
+//! Routing `speechSynthesis` to a native text-to-speech engine.
+//!
+//! CEF doesn't pass Chromium's TTS plumbing through, so depending on the
+//! platform and build `speechSynthesis.speak()` either does nothing or uses a
+//! voice the application can't control. A [SpeechBridge] replaces the page's
+//! `speechSynthesis` with a shim that hands utterances to a
+//! [SpeechSynthesizer] implemented by the application, which reports progress
+//! back through [UtteranceEvents]:
+//!
+//! ```ignore
+//! struct Engine(native_tts::Tts);
+//!
+//! impl SpeechSynthesizer for Engine {
+//!     fn voices(&self) -> Vec<Voice> {
+//!         vec![Voice::new("Kiosk", "en-US")]
+//!     }
+//!     fn speak(&self, utterance: Utterance, events: UtteranceEvents) {
+//!         events.start();
+//!         self.0.speak(&utterance.text, move || events.end());
+//!     }
+//!     fn cancel(&self) {
+//!         self.0.stop();
+//!     }
+//! }
+//!
+//! let bridge = SpeechBridge::attach(&browser, Engine(tts));
+//! ```
+//!
+//! The shim queues utterances like Chromium does and only hands the next one
+//! to the synthesizer after the previous one ended.
+
+use crate::{
+    browser::Browser,
+    devtools::DevToolsSession,
+    user_scripts::{UserScript, UserScriptId, UserScripts},
+    values::{DictionaryValue, ListValue, StoredValue},
+};
+use std::sync::Arc;
+
+/// The name of the DevTools binding calls are reported through.
+const BINDING_NAME: &str = "__cefSpeechRequest";
+
+const SHIM_SOURCE: &str = "\
+var notify = window.__cefSpeechRequest;\n\
+if (typeof notify !== 'function' || window.__cefSpeech) return;\n\
+try { delete window.__cefSpeechRequest; } catch (e) {}\n\
+var voices = __VOICES__.map(function(v) {\n\
+  return Object.freeze({ name: v.name, lang: v.lang, voiceURI: v.name, localService: v.localService, default: v.default });\n\
+});\n\
+var queue = [], current = null, paused = false, nextId = 1;\n\
+var synthesis = new EventTarget();\n\
+function fire(utterance, type, charIndex, error) {\n\
+  var init = { utterance: utterance, charIndex: charIndex || 0, elapsedTime: 0, name: type === 'boundary' ? 'word' : '' };\n\
+  var event;\n\
+  if (type === 'error') { init.error = error || 'synthesis-failed'; event = new SpeechSynthesisErrorEvent('error', init); }\n\
+  else event = new SpeechSynthesisEvent(type, init);\n\
+  utterance.dispatchEvent(event);\n\
+}\n\
+function next() {\n\
+  if (current || paused || !queue.length) return;\n\
+  current = queue.shift();\n\
+  var u = current.utterance;\n\
+  notify(JSON.stringify({ op: 'speak', id: current.id, text: u.text, lang: u.lang || document.documentElement.lang || '',\n\
+    voice: u.voice ? u.voice.name : null, rate: u.rate, pitch: u.pitch, volume: u.volume }));\n\
+}\n\
+Object.defineProperties(synthesis, {\n\
+  speaking: { get: function() { return !!current; } },\n\
+  pending: { get: function() { return queue.length > 0; } },\n\
+  paused: { get: function() { return paused; } }\n\
+});\n\
+synthesis.onvoiceschanged = null;\n\
+synthesis.getVoices = function() { return voices.slice(); };\n\
+synthesis.speak = function(utterance) { queue.push({ id: nextId++, utterance: utterance }); next(); };\n\
+synthesis.cancel = function() {\n\
+  var dropped = queue; queue = [];\n\
+  dropped.forEach(function(entry) { fire(entry.utterance, 'error', 0, 'canceled'); });\n\
+  if (current) notify(JSON.stringify({ op: 'cancel', id: current.id }));\n\
+};\n\
+synthesis.pause = function() {\n\
+  if (paused) return; paused = true;\n\
+  if (current) { notify(JSON.stringify({ op: 'pause', id: current.id })); fire(current.utterance, 'pause'); }\n\
+};\n\
+synthesis.resume = function() {\n\
+  if (!paused) return; paused = false;\n\
+  if (current) { notify(JSON.stringify({ op: 'resume', id: current.id })); fire(current.utterance, 'resume'); }\n\
+  next();\n\
+};\n\
+Object.defineProperty(window, 'speechSynthesis', { configurable: true, get: function() { return synthesis; } });\n\
+window.__cefSpeech = {\n\
+  event: function(id, type, charIndex, error) {\n\
+    if (!current || current.id !== id) return;\n\
+    var u = current.utterance;\n\
+    if (type === 'end' || type === 'error') { current = null; fire(u, type, charIndex, error); next(); }\n\
+    else fire(u, type, charIndex);\n\
+  }\n\
+};";
+
+/// A voice offered to pages through `speechSynthesis.getVoices()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Voice {
+    /// The name pages select the voice by, unique among the voices.
+    pub name: String,
+    /// A BCP 47 language tag, e.g. `en-US`.
+    pub lang: String,
+    /// Set if the engine runs without network access.
+    pub local_service: bool,
+    /// Set for the voice used when the page doesn't pick one.
+    pub default: bool,
+}
+
+impl Voice {
+    pub fn new(name: &str, lang: &str) -> Voice {
+        Voice {
+            name: name.to_owned(),
+            lang: lang.to_owned(),
+            local_service: true,
+            default: false,
+        }
+    }
+    pub fn default_voice(mut self, default: bool) -> Self {
+        self.default = default;
+        self
+    }
+}
+
+/// A `SpeechSynthesisUtterance` passed to `speechSynthesis.speak()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Utterance {
+    /// Identifies the utterance within its document.
+    pub id: i32,
+    pub text: String,
+    /// The language of the utterance or the document, possibly empty.
+    pub lang: String,
+    /// The name of the [Voice] the page picked, if any.
+    pub voice: Option<String>,
+    /// The speaking rate, 1.0 being normal speed.
+    pub rate: f64,
+    /// The pitch from 0.0 to 2.0, 1.0 being normal.
+    pub pitch: f64,
+    /// The volume from 0.0 to 1.0.
+    pub volume: f64,
+}
+
+impl Utterance {
+    fn from_dictionary(request: &DictionaryValue) -> Utterance {
+        let number = |key: &str, default: f64| match request.get(key) {
+            StoredValue::Double(value) => value,
+            StoredValue::Int(value) => value as f64,
+            _ => default,
+        };
+        Utterance {
+            id: request.get_int("id"),
+            text: request.get_string("text"),
+            lang: request.get_string("lang"),
+            voice: match request.get("voice") {
+                StoredValue::String(voice) => Some(voice),
+                _ => None,
+            },
+            rate: number("rate", 1.0),
+            pitch: number("pitch", 1.0),
+            volume: number("volume", 1.0),
+        }
+    }
+}
+
+/// A text-to-speech engine serving the pages of a [SpeechBridge]. Methods are
+/// called on the browser process UI thread and must not block.
+pub trait SpeechSynthesizer: 'static + Send + Sync {
+    /// Returns the voices to offer. Called once when the bridge is attached.
+    fn voices(&self) -> Vec<Voice>;
+    /// Starts speaking `utterance`. Call [UtteranceEvents::end] or
+    /// [UtteranceEvents::error] when done, from any thread.
+    fn speak(&self, utterance: Utterance, events: UtteranceEvents);
+    /// Stops the utterance being spoken. The engine should still report its
+    /// end, e.g. with [UtteranceEvents::error] and `canceled`.
+    fn cancel(&self);
+    fn pause(&self) {}
+    fn resume(&self) {}
+}
+
+/// Reports the progress of an [Utterance] to the page, where it fires the
+/// events of the `SpeechSynthesisUtterance`.
+#[derive(Clone)]
+pub struct UtteranceEvents {
+    browser: Browser,
+    id: i32,
+}
+
+impl UtteranceEvents {
+    /// Fires `start`.
+    pub fn start(&self) {
+        self.fire("start", 0, None);
+    }
+    /// Fires `boundary` for the word starting at `char_index`, in UTF-16 code
+    /// units like the page counts.
+    pub fn boundary(&self, char_index: usize) {
+        self.fire("boundary", char_index, None);
+    }
+    /// Fires `end` and hands the next queued utterance to the synthesizer.
+    pub fn end(&self) {
+        self.fire("end", 0, None);
+    }
+    /// Fires `error` with a `SpeechSynthesisErrorCode` such as `canceled`,
+    /// `interrupted` or `synthesis-failed`, and hands the next queued
+    /// utterance to the synthesizer.
+    pub fn error(&self, code: &str) {
+        self.fire("error", 0, Some(code));
+    }
+
+    fn fire(&self, event: &str, char_index: usize, error: Option<&str>) {
+        let error = error
+            .and_then(|error| StoredValue::String(error.to_owned()).to_json())
+            .unwrap_or_else(|| "null".to_owned());
+        let code = format!(
+            "window.__cefSpeech && window.__cefSpeech.event({}, '{}', {}, {});",
+            self.id, event, char_index, error
+        );
+        self.browser.get_main_frame().execute_java_script(&code, "", 0);
+    }
+}
+
+/// Routes the `speechSynthesis` API of one browser's main frame to a
+/// [SpeechSynthesizer]. See the [module documentation](self).
+pub struct SpeechBridge {
+    session: DevToolsSession,
+    scripts: UserScripts,
+    script_id: UserScriptId,
+}
+
+impl SpeechBridge {
+    /// Installs the shim into the documents of `browser`.
+    pub fn attach(browser: &Browser, synthesizer: impl SpeechSynthesizer) -> SpeechBridge {
+        let synthesizer = Arc::new(synthesizer);
+        let voices = voices_json(&synthesizer.voices());
+        let session = DevToolsSession::new(&browser.get_host());
+        session.add_event_listener("Runtime.bindingCalled", move |browser, params| {
+            let params = match params {
+                StoredValue::Dictionary(params) if params.get_string("name") == BINDING_NAME => params,
+                _ => return,
+            };
+            let request = match StoredValue::from_json(&params.get_string("payload")) {
+                Some(StoredValue::Dictionary(request)) => request,
+                _ => return,
+            };
+            match &request.get_string("op")[..] {
+                "speak" => {
+                    let utterance = Utterance::from_dictionary(&request);
+                    let events = UtteranceEvents {
+                        browser: browser.clone(),
+                        id: utterance.id,
+                    };
+                    synthesizer.speak(utterance, events);
+                }
+                "cancel" => synthesizer.cancel(),
+                "pause" => synthesizer.pause(),
+                "resume" => synthesizer.resume(),
+                _ => (),
+            }
+        });
+        session.send("Runtime.enable", None);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        session.send("Runtime.addBinding", Some(params));
+
+        let scripts = UserScripts::with_session(session.clone());
+        let script = UserScript::new(&SHIM_SOURCE.replace("__VOICES__", &voices));
+        let script_id = scripts.add(&script);
+        scripts.inject_into_loaded(&script);
+        SpeechBridge {
+            session,
+            scripts,
+            script_id,
+        }
+    }
+}
+
+impl Drop for SpeechBridge {
+    fn drop(&mut self) {
+        self.scripts.remove(self.script_id);
+        let params = DictionaryValue::new();
+        params.insert_string("name", BINDING_NAME);
+        self.session.send("Runtime.removeBinding", Some(params));
+    }
+}
+
+fn voices_json(voices: &[Voice]) -> String {
+    let list = ListValue::new();
+    for (i, voice) in voices.iter().enumerate() {
+        let entry = DictionaryValue::new();
+        entry.insert_string("name", &voice.name);
+        entry.insert_string("lang", &voice.lang);
+        entry.insert_bool("localService", voice.local_service);
+        entry.insert_bool("default", voice.default);
+        list.set_dictionary(i, entry);
+    }
+    StoredValue::List(list).to_json().unwrap_or_else(|| "[]".to_owned())
+}
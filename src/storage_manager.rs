@@ -0,0 +1,253 @@
+//! Inspecting and evicting the data sites store.
+//!
+//! A [StorageManager] reports how much disk space the cache of a request
+//! context takes, how much each origin stores in IndexedDB, local storage,
+//! Cache Storage and the like, and clears the data of single origins. Usage
+//! and clearing go through the DevTools `Storage` domain, so the manager is
+//! created from a browser of the request context in question:
+//!
+//! ```ignore
+//! let storage = StorageManager::new(&browser);
+//! if storage.disk_usage()?.total > 200 * 1024 * 1024 {
+//!     for origin in least_recently_used_origins() {
+//!         block_on(storage.clear_origin(&origin))?;
+//!     }
+//!     block_on(storage.clear_cache())?;
+//! }
+//! ```
+
+use crate::{
+    browser::Browser,
+    devtools::{DevToolsError, DevToolsSession},
+    request_context::RequestContext,
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Waker},
+};
+
+/// A kind of data stored for an origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageType {
+    AppCache,
+    Cookies,
+    FileSystems,
+    IndexedDb,
+    LocalStorage,
+    ShaderCache,
+    WebSql,
+    ServiceWorkers,
+    CacheStorage,
+}
+
+impl StorageType {
+    pub const ALL: &'static [StorageType] = &[
+        StorageType::AppCache,
+        StorageType::Cookies,
+        StorageType::FileSystems,
+        StorageType::IndexedDb,
+        StorageType::LocalStorage,
+        StorageType::ShaderCache,
+        StorageType::WebSql,
+        StorageType::ServiceWorkers,
+        StorageType::CacheStorage,
+    ];
+
+    /// Returns the name of the type in the DevTools protocol.
+    pub fn to_devtools(self) -> &'static str {
+        match self {
+            StorageType::AppCache => "appcache",
+            StorageType::Cookies => "cookies",
+            StorageType::FileSystems => "file_systems",
+            StorageType::IndexedDb => "indexeddb",
+            StorageType::LocalStorage => "local_storage",
+            StorageType::ShaderCache => "shader_cache",
+            StorageType::WebSql => "websql",
+            StorageType::ServiceWorkers => "service_workers",
+            StorageType::CacheStorage => "cache_storage",
+        }
+    }
+    pub fn from_devtools(name: &str) -> Option<StorageType> {
+        StorageType::ALL.iter().copied().find(|storage_type| storage_type.to_devtools() == name)
+    }
+}
+
+/// The space an origin uses, as reported by the quota system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OriginUsage {
+    pub origin: String,
+    /// The bytes used by all storage types.
+    pub usage: u64,
+    /// The bytes the origin may use.
+    pub quota: u64,
+    /// The bytes used per storage type. Types the quota system doesn't track,
+    /// like local storage and cookies, aren't listed.
+    pub breakdown: Vec<(StorageType, u64)>,
+}
+
+/// The disk space taken by the caches of a request context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// The HTTP cache.
+    pub cache: u64,
+    /// Compiled JavaScript and WebAssembly.
+    pub code_cache: u64,
+    /// The GPU shader cache.
+    pub gpu_cache: u64,
+    /// Everything in the cache directory, including the above and site data.
+    pub total: u64,
+}
+
+pub type StorageResult<T> = Result<T, DevToolsError>;
+
+struct RequestState<T> {
+    result: Option<StorageResult<T>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the result of a [StorageManager] operation.
+pub struct StorageRequest<T>(Arc<Mutex<RequestState<T>>>);
+
+impl<T> Future for StorageRequest<T> {
+    type Output = StorageResult<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<StorageResult<T>> {
+        let mut state = self.0.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Returns the size of the files below `path`, or 0 if it doesn't exist.
+fn directory_size(path: &Path) -> io::Result<u64> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(error) => return Err(error),
+    };
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += directory_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Inspects and clears the stored data of one request context. See the
+/// [module documentation](self).
+#[derive(Clone)]
+pub struct StorageManager {
+    context: RequestContext,
+    session: DevToolsSession,
+}
+
+impl StorageManager {
+    /// Creates a manager for the request context of `browser`.
+    pub fn new(browser: &Browser) -> StorageManager {
+        let host = browser.get_host();
+        StorageManager {
+            context: host.get_request_context(),
+            session: DevToolsSession::new(&host),
+        }
+    }
+    /// Returns the cache directory of the request context, or `None` for
+    /// in-memory contexts.
+    pub fn cache_path(&self) -> Option<PathBuf> {
+        self.context.get_cache_path().filter(|path| !path.is_empty()).map(PathBuf::from)
+    }
+    /// Measures the cache directory. In-memory contexts use no disk space.
+    /// This walks the directory, so call it off the UI thread for large
+    /// caches.
+    pub fn disk_usage(&self) -> io::Result<DiskUsage> {
+        let root = match self.cache_path() {
+            Some(root) => root,
+            None => return Ok(DiskUsage { cache: 0, code_cache: 0, gpu_cache: 0, total: 0 }),
+        };
+        Ok(DiskUsage {
+            cache: directory_size(&root.join("Cache"))?,
+            code_cache: directory_size(&root.join("Code Cache"))?,
+            gpu_cache: directory_size(&root.join("GPUCache"))?,
+            total: directory_size(&root)?,
+        })
+    }
+    /// Queries the usage and quota of `origin`, e.g. `https://example.com`.
+    pub fn origin_usage(&self, origin: &str) -> StorageRequest<OriginUsage> {
+        let params = DictionaryValue::new();
+        params.insert_string("origin", origin);
+        let origin = origin.to_owned();
+        self.execute("Storage.getUsageAndQuota", params, move |result| {
+            let breakdown = result.try_get_list("usageBreakdown");
+            let breakdown = breakdown.iter()
+                .flat_map(|breakdown| (0..breakdown.len()).filter_map(move |i| breakdown.get_dictionary(i)))
+                .filter_map(|entry| Some((StorageType::from_devtools(&entry.get_string("storageType"))?, entry.get_double("usage") as u64)))
+                .filter(|(_, usage)| *usage > 0)
+                .collect();
+            Ok(OriginUsage {
+                origin,
+                usage: result.get_double("usage") as u64,
+                quota: result.get_double("quota") as u64,
+                breakdown,
+            })
+        })
+    }
+    /// Clears all data `origin` stored: cookies, storage, caches and service
+    /// workers.
+    pub fn clear_origin(&self, origin: &str) -> StorageRequest<()> {
+        self.clear_origin_types(origin, StorageType::ALL)
+    }
+    /// Clears the data of `storage_types` stored by `origin`.
+    pub fn clear_origin_types(&self, origin: &str, storage_types: &[StorageType]) -> StorageRequest<()> {
+        let params = DictionaryValue::new();
+        params.insert_string("origin", origin);
+        let types = storage_types.iter().map(|storage_type| storage_type.to_devtools()).collect::<Vec<_>>();
+        params.insert_string("storageTypes", &types.join(","));
+        self.execute("Storage.clearDataForOrigin", params, |_| Ok(()))
+    }
+    /// Clears the HTTP cache of the request context.
+    pub fn clear_cache(&self) -> StorageRequest<()> {
+        self.execute("Network.clearBrowserCache", DictionaryValue::new(), |_| Ok(()))
+    }
+
+    fn execute<T: 'static + Send>(
+        &self,
+        method: &str,
+        params: DictionaryValue,
+        parse: impl 'static + Send + FnOnce(DictionaryValue) -> StorageResult<T>,
+    ) -> StorageRequest<T> {
+        let state = Arc::new(Mutex::new(RequestState { result: None, waker: None }));
+        let result_state = state.clone();
+        // The callback holds the session, which keeps its observer registered
+        // until the result arrived.
+        let keep_alive = self.session.clone();
+        self.session.execute(method, Some(params), move |result| {
+            drop(keep_alive);
+            let result = result.and_then(|result| match result {
+                StoredValue::Dictionary(result) => parse(result),
+                _ => Err(DevToolsError { code: -1, message: "unexpected result".to_owned() }),
+            });
+            let waker = {
+                let mut state = result_state.lock();
+                state.result = Some(result);
+                state.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+        StorageRequest(state)
+    }
+}
@@ -0,0 +1,530 @@
+//! Managing the browsers of one window as tabs.
+//!
+//! A [TabStrip] tracks the order of the tabs and which one is active, hides
+//! and focuses browsers as tabs are switched, and turns the popups pages open
+//! with `window.open()` or `target="_blank"` links into new tabs next to the
+//! tab that opened them. The application draws the strip itself.
+//!
+//! A strip created with [TabStrip::in_panel] shows its tabs as
+//! [BrowserView]s stacked in a [Panel], of which only the active one is
+//! visible. Tabs are opened with [TabStrip::create_tab]:
+//!
+//! ```ignore
+//! let tabs = TabStrip::in_panel(&content_panel);
+//! tabs.create_tab(Client::new(MyClient { life_span_handler: tabs.life_span_handler(), ... }), url, &settings, None);
+//! ```
+//!
+//! A strip created with [TabStrip::new] manages windowless browsers instead,
+//! and the application paints the active tab into its window:
+//!
+//! ```ignore
+//! let tabs = TabStrip::new();
+//! tabs.on_event(|event| match event {
+//!     TabEvent::Added { tab, index, .. } => ui.insert_tab(*index, *tab),
+//!     TabEvent::Activated { tab, .. } => ui.select_tab(*tab),
+//!     TabEvent::Moved { from, to, .. } => ui.move_tab(*from, *to),
+//!     TabEvent::Closed { index, .. } | TabEvent::Detached { index, .. } => ui.remove_tab(*index),
+//!     TabEvent::Attached { tab, index } => ui.insert_tab(*index, *tab),
+//! });
+//! // in ClientCallbacks
+//! fn get_life_span_handler(&self) -> Option<LifeSpanHandler> { Some(self.tabs.life_span_handler()) }
+//! ```
+//!
+//! Every browser of the client becomes a tab once it was created. Popups are
+//! matched to the tab that opened them in the order they were requested. In
+//! a panel, their views are added to the panel of the opener's strip.
+//!
+//! Tabs can be torn off and moved to the strip of another window with
+//! [TabStrip::detach] and [TabStrip::attach], which keeps the browser and its
+//! page alive. The strip whose handler the browser was created with keeps
+//! receiving its callbacks and forwards them to the strip the tab moved to.
+//! The view of a tab moves to the panel of the new strip; windowless browsers
+//! have to be painted into the new window by the application:
+//!
+//! ```ignore
+//! // the user dropped a tab outside of the window
+//! if let Some(detached) = tabs.detach(tab) {
+//!     let window = open_window();
+//!     window.tabs.attach(detached, None);
+//! }
+//! ```
+
+use crate::{
+    browser::{Browser, BrowserSettings},
+    client::{
+        life_span_handler::{LifeSpanHandler, LifeSpanHandlerCallbacks, PopupFeatures},
+        request_handler::WindowOpenDisposition,
+        Client,
+    },
+    frame::Frame,
+    request_context::RequestContext,
+    values::DictionaryValue,
+    views::{BrowserView, BrowserViewDelegate, BrowserViewDelegateCallbacks, Panel},
+    window::WindowInfo,
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Weak},
+};
+
+/// Identifies a tab of a [TabStrip].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TabId(u64);
+
+/// A change of a [TabStrip].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabEvent {
+    /// A browser was created and inserted at `index`. `opener` is set for
+    /// popups.
+    Added { tab: TabId, index: usize, opener: Option<TabId> },
+    Activated { tab: TabId, previous: Option<TabId> },
+    Moved { tab: TabId, from: usize, to: usize },
+    /// The browser of the tab was destroyed; it was at `index`.
+    Closed { tab: TabId, index: usize },
+    /// The tab at `index` was torn off with [TabStrip::detach].
+    Detached { tab: TabId, index: usize },
+    /// A torn off tab was inserted at `index` with [TabStrip::attach].
+    Attached { tab: TabId, index: usize },
+}
+
+struct Tab {
+    id: TabId,
+    browser: Browser,
+    opener: Option<TabId>,
+    /// The strip whose life span handler receives the callbacks of the
+    /// browser.
+    origin: Weak<TabStripState>,
+}
+
+/// A popup allowed by `on_before_popup` that hasn't been created yet.
+struct PendingPopup {
+    opener: Option<TabId>,
+    foreground: bool,
+    /// The strip of the opener, which the popup goes to.
+    strip: Weak<TabStripState>,
+}
+
+struct Tabs {
+    tabs: Vec<Tab>,
+    active: Option<TabId>,
+    next_id: u64,
+    pending_popups: VecDeque<PendingPopup>,
+}
+
+impl Tabs {
+    fn index_of(&self, tab: TabId) -> Option<usize> {
+        self.tabs.iter().position(|t| t.id == tab)
+    }
+    fn find_browser(&self, browser: &Browser) -> Option<usize> {
+        let browser_id = browser.get_identifier();
+        self.tabs.iter().position(|t| t.browser.get_identifier() == browser_id)
+    }
+}
+
+struct TabStripState {
+    tabs: Mutex<Tabs>,
+    listener: Mutex<Option<Arc<dyn 'static + Send + Sync + Fn(&TabEvent)>>>,
+    /// The strips that browsers created with this strip's handler moved to,
+    /// by browser identifier.
+    moved: Mutex<HashMap<i32, Weak<TabStripState>>>,
+    /// The panel showing the views of the tabs, unless the browsers are
+    /// windowless.
+    panel: Option<Panel>,
+}
+
+/// A tab torn off with [TabStrip::detach]. Dropping it without attaching it
+/// to a strip closes the browser.
+pub struct DetachedTab {
+    browser: Option<Browser>,
+    origin: Weak<TabStripState>,
+}
+
+impl DetachedTab {
+    pub fn browser(&self) -> &Browser {
+        self.browser.as_ref().unwrap()
+    }
+}
+
+impl Drop for DetachedTab {
+    fn drop(&mut self) {
+        if let Some(browser) = self.browser.take() {
+            browser.get_host().close_browser(true);
+        }
+    }
+}
+
+/// The tabs of one window. See the [module documentation](self).
+///
+/// Cloning the strip is cheap; clones share the tabs.
+#[derive(Clone)]
+pub struct TabStrip(Arc<TabStripState>);
+
+impl TabStrip {
+    /// Creates a strip of windowless browsers.
+    pub fn new() -> TabStrip {
+        TabStrip::with_panel(None)
+    }
+    /// Creates a strip that shows its tabs in `panel`, which is given a fill
+    /// layout. Must be called on the UI thread.
+    pub fn in_panel(panel: &Panel) -> TabStrip {
+        panel.set_to_fill_layout();
+        TabStrip::with_panel(Some(panel.clone()))
+    }
+    fn with_panel(panel: Option<Panel>) -> TabStrip {
+        TabStrip(Arc::new(TabStripState {
+            tabs: Mutex::new(Tabs {
+                tabs: Vec::new(),
+                active: None,
+                next_id: 0,
+                pending_popups: VecDeque::new(),
+            }),
+            listener: Mutex::new(None),
+            moved: Mutex::new(HashMap::new()),
+            panel,
+        }))
+    }
+    /// Calls `listener` on the UI thread with every change of the strip.
+    pub fn on_event(&self, listener: impl 'static + Send + Sync + Fn(&TabEvent)) {
+        *self.0.listener.lock() = Some(Arc::new(listener));
+    }
+    pub fn life_span_handler(&self) -> LifeSpanHandler {
+        LifeSpanHandler::new(self.clone())
+    }
+    /// Returns the delegate of the views of the tabs, which adds the views of
+    /// popups to the strip's panel.
+    pub fn browser_view_delegate(&self) -> BrowserViewDelegate {
+        BrowserViewDelegate::new(self.clone())
+    }
+    /// Returns the panel the tabs are shown in.
+    pub fn panel(&self) -> Option<&Panel> {
+        self.0.panel.as_ref()
+    }
+    /// Opens a tab with a browser of `client` that loads `url`. Its view is
+    /// added to the panel hidden; the tab is added once the browser was
+    /// created, like any other browser of the client. `client` must use the
+    /// strip's [life span handler](TabStrip::life_span_handler). Returns
+    /// `None` if the strip has no panel or the view couldn't be created.
+    pub fn create_tab(&self, client: Client, url: &str, settings: &BrowserSettings, request_context: Option<RequestContext>) -> Option<BrowserView> {
+        let panel = self.0.panel.as_ref()?;
+        let view = BrowserView::new(client, url, settings, None, request_context, Some(self.browser_view_delegate()))?;
+        view.set_visible(false);
+        panel.add_child_view(&view);
+        Some(view)
+    }
+
+    /// Returns the tabs from left to right.
+    pub fn tabs(&self) -> Vec<TabId> {
+        self.0.tabs.lock().tabs.iter().map(|tab| tab.id).collect()
+    }
+    pub fn len(&self) -> usize {
+        self.0.tabs.lock().tabs.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn active(&self) -> Option<TabId> {
+        self.0.tabs.lock().active
+    }
+    pub fn active_browser(&self) -> Option<Browser> {
+        self.active().and_then(|tab| self.browser(tab))
+    }
+    pub fn browser(&self, tab: TabId) -> Option<Browser> {
+        let tabs = self.0.tabs.lock();
+        tabs.index_of(tab).map(|index| tabs.tabs[index].browser.clone())
+    }
+    /// Returns the tab showing `browser`.
+    pub fn tab_of(&self, browser: &Browser) -> Option<TabId> {
+        let tabs = self.0.tabs.lock();
+        tabs.find_browser(browser).map(|index| tabs.tabs[index].id)
+    }
+    pub fn index_of(&self, tab: TabId) -> Option<usize> {
+        self.0.tabs.lock().index_of(tab)
+    }
+    /// Returns the tab that opened `tab` as a popup.
+    pub fn opener(&self, tab: TabId) -> Option<TabId> {
+        let tabs = self.0.tabs.lock();
+        tabs.index_of(tab).and_then(|index| tabs.tabs[index].opener)
+    }
+
+    /// Shows and focuses the browser of `tab`, and hides the previously
+    /// active one. Returns false if there is no such tab.
+    pub fn activate(&self, tab: TabId) -> bool {
+        let (browser, previous, previous_browser) = {
+            let mut tabs = self.0.tabs.lock();
+            let index = match tabs.index_of(tab) {
+                Some(index) => index,
+                None => return false,
+            };
+            if tabs.active == Some(tab) {
+                return true;
+            }
+            let previous = tabs.active.replace(tab);
+            let previous_browser = previous.and_then(|previous| tabs.index_of(previous)).map(|i| tabs.tabs[i].browser.clone());
+            (tabs.tabs[index].browser.clone(), previous, previous_browser)
+        };
+        if let Some(previous_browser) = previous_browser {
+            show(&previous_browser, false);
+        }
+        show(&browser, true);
+        self.emit(TabEvent::Activated { tab, previous });
+        true
+    }
+    /// Moves `tab` to `index`, counted after its removal. Returns false if
+    /// there is no such tab.
+    pub fn move_tab(&self, tab: TabId, index: usize) -> bool {
+        let (from, to) = {
+            let mut tabs = self.0.tabs.lock();
+            let from = match tabs.index_of(tab) {
+                Some(from) => from,
+                None => return false,
+            };
+            let moved = tabs.tabs.remove(from);
+            let to = index.min(tabs.tabs.len());
+            tabs.tabs.insert(to, moved);
+            (from, to)
+        };
+        if from != to {
+            self.emit(TabEvent::Moved { tab, from, to });
+        }
+        true
+    }
+    /// Asks the browser of `tab` to close, which runs the page's
+    /// `onbeforeunload` handler unless `force_close` is set. The tab is
+    /// removed once the browser was destroyed. Returns false if there is no
+    /// such tab.
+    pub fn close(&self, tab: TabId, force_close: bool) -> bool {
+        match self.browser(tab) {
+            Some(browser) => {
+                browser.get_host().close_browser(force_close);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Closes all tabs.
+    pub fn close_all(&self, force_close: bool) {
+        for tab in self.tabs() {
+            self.close(tab, force_close);
+        }
+    }
+
+    /// Removes `tab` without closing its browser, which is hidden until it is
+    /// attached to a strip again. Returns `None` if there is no such tab.
+    pub fn detach(&self, tab: TabId) -> Option<DetachedTab> {
+        let browser = self.browser(tab)?;
+        let (removed, index, next) = self.remove(&browser)?;
+        show(&browser, false);
+        if let (Some(panel), Some(view)) = (&self.0.panel, BrowserView::get_for_browser(&browser)) {
+            panel.remove_child_view(&view);
+        }
+        self.emit(TabEvent::Detached { tab, index });
+        if let Some(next) = next {
+            self.activate(next);
+        }
+        Some(DetachedTab {
+            browser: Some(browser),
+            origin: removed.origin,
+        })
+    }
+    /// Inserts a torn off tab at `index`, or at the end, and activates it.
+    pub fn attach(&self, mut detached: DetachedTab, index: Option<usize>) -> TabId {
+        let browser = detached.browser.take().unwrap();
+        if let Some(origin) = detached.origin.upgrade() {
+            let mut moved = origin.moved.lock();
+            if Arc::ptr_eq(&origin, &self.0) {
+                moved.remove(&browser.get_identifier());
+            } else {
+                moved.insert(browser.get_identifier(), Arc::downgrade(&self.0));
+            }
+        }
+        if let (Some(panel), Some(view)) = (&self.0.panel, BrowserView::get_for_browser(&browser)) {
+            panel.add_child_view(&view);
+        }
+        let (tab, index) = self.insert(&browser, None, index, detached.origin.clone());
+        self.emit(TabEvent::Attached { tab, index });
+        self.activate(tab);
+        tab
+    }
+
+    /// Inserts `browser` at `index`, or right of `opener` and the popups it
+    /// opened before, or at the end.
+    fn insert(&self, browser: &Browser, opener: Option<TabId>, index: Option<usize>, origin: Weak<TabStripState>) -> (TabId, usize) {
+        let mut tabs = self.0.tabs.lock();
+        let tab = TabId(tabs.next_id);
+        tabs.next_id += 1;
+        let opener = opener.filter(|opener| tabs.index_of(*opener).is_some());
+        let index = match (index, opener.and_then(|opener| tabs.index_of(opener))) {
+            (Some(index), _) => index.min(tabs.tabs.len()),
+            (None, Some(opener_index)) => {
+                let siblings = tabs.tabs[opener_index + 1..].iter().take_while(|t| t.opener == opener).count();
+                opener_index + 1 + siblings
+            }
+            (None, None) => tabs.tabs.len(),
+        };
+        tabs.tabs.insert(index, Tab { id: tab, browser: browser.clone(), opener, origin });
+        (tab, index)
+    }
+    /// Removes the tab of `browser` and returns it, its index, and the tab to
+    /// activate in its place if it was active.
+    fn remove(&self, browser: &Browser) -> Option<(Tab, usize, Option<TabId>)> {
+        let mut tabs = self.0.tabs.lock();
+        let index = tabs.find_browser(browser)?;
+        let removed = tabs.tabs.remove(index);
+        // Fall back to the opener, then to the tab that took the place.
+        let next = if tabs.active == Some(removed.id) {
+            tabs.active = None;
+            removed.opener
+                .filter(|opener| tabs.index_of(*opener).is_some())
+                .or_else(|| tabs.tabs.get(index).or_else(|| tabs.tabs.last()).map(|t| t.id))
+        } else {
+            None
+        };
+        Some((removed, index, next))
+    }
+    /// Returns the strip `browser` is a tab of, following tabs that moved to
+    /// other strips.
+    fn owner_of(&self, browser: &Browser) -> Option<TabStrip> {
+        if self.0.tabs.lock().find_browser(browser).is_some() {
+            return Some(self.clone());
+        }
+        self.0.moved.lock().get(&browser.get_identifier()).and_then(Weak::upgrade).map(TabStrip)
+    }
+
+    fn emit(&self, event: TabEvent) {
+        // The listener may call back into the strip, e.g. to replace itself.
+        let listener = self.0.listener.lock().clone();
+        if let Some(listener) = listener {
+            listener(&event);
+        }
+    }
+}
+
+/// Shows and focuses or hides `browser`: its view if it has one, or else the
+/// windowless browser.
+fn show(browser: &Browser, shown: bool) {
+    match BrowserView::get_for_browser(browser) {
+        Some(view) => {
+            view.set_visible(shown);
+            if shown {
+                view.request_focus();
+            }
+        }
+        None => {
+            let host = browser.get_host();
+            if !shown {
+                host.set_focus(false);
+            }
+            host.was_hidden(!shown);
+            if shown {
+                host.set_focus(true);
+            }
+        }
+    }
+}
+
+impl Default for TabStrip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LifeSpanHandlerCallbacks for TabStrip {
+    fn on_before_popup(
+        &self,
+        browser: Browser,
+        frame: Frame,
+        target_url: Option<&str>,
+        target_frame_name: Option<&str>,
+        target_disposition: WindowOpenDisposition,
+        user_gesture: bool,
+        popup_features: PopupFeatures,
+        window_info: &mut WindowInfo,
+        client: &mut Client,
+        settings: &mut BrowserSettings,
+        extra_info: &mut DictionaryValue,
+        no_javascript_access: &mut bool,
+    ) -> bool {
+        let foreground = match target_disposition {
+            WindowOpenDisposition::CurrentTab => {
+                if let Some(url) = target_url {
+                    frame.load_url(url);
+                }
+                return true;
+            }
+            WindowOpenDisposition::NewBackgroundTab => false,
+            WindowOpenDisposition::NewForegroundTab
+            | WindowOpenDisposition::SingletonTab
+            | WindowOpenDisposition::NewPopup
+            | WindowOpenDisposition::NewWindow
+            | WindowOpenDisposition::Unknown => true,
+            // Opening these would create a browser the strip doesn't manage.
+            _ => return true,
+        };
+        // Popups of views are shown in views of their own, which
+        // `on_popup_browser_view_created` adds to the panel.
+        if BrowserView::get_for_browser(&browser).is_none() {
+            window_info.windowless_rendering_enabled = true;
+        }
+        let owner = self.owner_of(&browser).unwrap_or_else(|| self.clone());
+        let opener = owner.tab_of(&browser);
+        self.0.tabs.lock().pending_popups.push_back(PendingPopup { opener, foreground, strip: Arc::downgrade(&owner.0) });
+        false
+    }
+    fn on_after_created(&self, browser: Browser) {
+        let popup = if browser.is_popup() { self.0.tabs.lock().pending_popups.pop_front() } else { None };
+        let (strip, opener, foreground) = match popup {
+            Some(popup) => (popup.strip.upgrade().map(TabStrip).unwrap_or_else(|| self.clone()), popup.opener, popup.foreground),
+            None => (self.clone(), None, false),
+        };
+        if !Arc::ptr_eq(&strip.0, &self.0) {
+            self.0.moved.lock().insert(browser.get_identifier(), Arc::downgrade(&strip.0));
+        }
+        let no_active = strip.active().is_none();
+        let (tab, index) = strip.insert(&browser, opener, None, Arc::downgrade(&self.0));
+        strip.emit(TabEvent::Added { tab, index, opener: strip.opener(tab) });
+        if foreground || no_active {
+            strip.activate(tab);
+        } else {
+            show(&browser, false);
+        }
+    }
+    fn on_before_close(&self, browser: Browser) {
+        let strip = match self.owner_of(&browser) {
+            Some(strip) => strip,
+            None => return,
+        };
+        self.0.moved.lock().remove(&browser.get_identifier());
+        if let Some((removed, index, next)) = strip.remove(&browser) {
+            strip.emit(TabEvent::Closed { tab: removed.id, index });
+            if let Some(next) = next {
+                strip.activate(next);
+            }
+        }
+    }
+}
+
+impl BrowserViewDelegateCallbacks for TabStrip {
+    fn get_delegate_for_popup_browser_view(&self, browser_view: BrowserView, is_devtools: bool) -> Option<BrowserViewDelegate> {
+        if is_devtools {
+            None
+        } else {
+            Some(self.browser_view_delegate())
+        }
+    }
+    fn on_popup_browser_view_created(&self, browser_view: BrowserView, popup_browser_view: BrowserView, is_devtools: bool) -> bool {
+        if is_devtools {
+            return false;
+        }
+        let owner = browser_view.get_browser().and_then(|browser| self.owner_of(&browser)).unwrap_or_else(|| self.clone());
+        match &owner.0.panel {
+            Some(panel) => {
+                // Shown by `on_after_created` once the browser exists.
+                popup_browser_view.set_visible(false);
+                panel.add_child_view(&popup_browser_view);
+                true
+            }
+            None => false,
+        }
+    }
+}
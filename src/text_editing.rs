@@ -0,0 +1,66 @@
+//! Editing the focused field of a page from the application, see
+//! [Frame::insert_text] and [Frame::replace_text_in_focused_editable].
+//!
+//! [BrowserHost::replace_misspelling](crate::browser_host::BrowserHost::replace_misspelling)
+//! only replaces the word under a spelling suggestion. Applications that fill
+//! in fields themselves, e.g. with snippets or auto-fill values, need to type
+//! text as if the user did, so pages see the usual `beforeinput` and `input`
+//! events and the change can be undone with Ctrl+Z.
+
+use crate::{
+    devtools::DevToolsSession,
+    frame::Frame,
+    values::{DictionaryValue, StoredValue},
+};
+
+/// Selects the first occurrence of `old` at or after the caret of the focused
+/// editable element, or before it if there is none, and replaces it by
+/// typing `replacement`.
+const REPLACE_SOURCE: &str = "(function(old, replacement) {\n\
+  var element = document.activeElement;\n\
+  if (!element || !old) return false;\n\
+  if ((element.tagName === 'INPUT' || element.tagName === 'TEXTAREA') && !element.readOnly && !element.disabled) {\n\
+    var value = element.value, start = value.indexOf(old, element.selectionStart || 0);\n\
+    if (start < 0) start = value.indexOf(old);\n\
+    if (start < 0) return false;\n\
+    element.setSelectionRange(start, start + old.length);\n\
+    if (!document.execCommand('insertText', false, replacement)) element.setRangeText(replacement, start, start + old.length, 'end');\n\
+    return true;\n\
+  }\n\
+  if (!element.isContentEditable) return false;\n\
+  var walker = document.createTreeWalker(element, NodeFilter.SHOW_TEXT), node;\n\
+  while ((node = walker.nextNode())) {\n\
+    var index = node.data.indexOf(old);\n\
+    if (index < 0) continue;\n\
+    var range = document.createRange();\n\
+    range.setStart(node, index);\n\
+    range.setEnd(node, index + old.length);\n\
+    var selection = window.getSelection();\n\
+    selection.removeAllRanges();\n\
+    selection.addRange(range);\n\
+    return document.execCommand('insertText', false, replacement);\n\
+  }\n\
+  return false;\n\
+})";
+
+pub(crate) fn insert_text(frame: &Frame, text: &str) {
+    if !frame.is_focused() {
+        return;
+    }
+    let session = DevToolsSession::new(&frame.get_browser().get_host());
+    let params = DictionaryValue::new();
+    params.insert_string("text", text);
+    // Keep the session alive until the command is done.
+    let keep_alive = session.clone();
+    session.execute("Input.insertText", Some(params), move |_| drop(keep_alive));
+}
+
+pub(crate) fn replace_text_in_focused_editable(frame: &Frame, old: &str, new: &str) {
+    let code = format!(
+        "{}({}, {});",
+        REPLACE_SOURCE,
+        StoredValue::String(old.to_owned()).to_json().unwrap_or_default(),
+        StoredValue::String(new.to_owned()).to_json().unwrap_or_default()
+    );
+    frame.execute_java_script(&code, "", 0);
+}
@@ -0,0 +1,316 @@
+//! Reducing the resource usage of browsers that aren't visible.
+//!
+//! Chromium throttles timers and rendering of hidden tabs by itself, but an
+//! embedder has to tell it when a browser is hidden. [Browser::set_hidden_and_throttled]
+//! does that, lowers the windowless frame rate and freezes the page through the
+//! DevTools protocol, so no JavaScript runs until the browser is shown again.
+//! [ThrottlingPolicy] applies it to all browsers of a window when the window is
+//! minimized. [AdaptiveFrameRate] lowers the frame rate of visible windowless
+//! browsers while their content doesn't change.
+//!
+//! [Browser::set_hidden_and_throttled]: crate::browser::Browser::set_hidden_and_throttled
+
+use crate::{
+    browser::Browser,
+    client::render_handler::PaintElementType,
+    devtools::DevToolsSession,
+    task::{TaskRunner, ThreadId},
+    values::{DictionaryValue, Rect},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+/// The windowless frame rate of throttled browsers.
+pub const THROTTLED_FRAME_RATE: i32 = 1;
+
+lazy_static::lazy_static! {
+    /// The frame rates of throttled browsers before they were throttled, by
+    /// browser identifier.
+    static ref THROTTLED: Mutex<HashMap<i32, i32>> = Mutex::new(HashMap::new());
+}
+
+pub(crate) fn set_hidden_and_throttled(browser: &Browser, throttled: bool) {
+    let browser = browser.clone();
+    let apply = move || {
+        let host = browser.get_host();
+        let browser_id = browser.get_identifier();
+        {
+            let mut throttled_browsers = THROTTLED.lock();
+            if throttled == throttled_browsers.contains_key(&browser_id) {
+                return;
+            }
+            if throttled {
+                throttled_browsers.insert(browser_id, host.get_windowless_frame_rate());
+            } else if let Some(frame_rate) = throttled_browsers.remove(&browser_id) {
+                host.set_windowless_frame_rate(frame_rate);
+            }
+        }
+        let params = DictionaryValue::new();
+        params.insert_string("state", if throttled { "frozen" } else { "active" });
+        if throttled {
+            // Pages can only be frozen while hidden.
+            host.was_hidden(true);
+            host.set_windowless_frame_rate(THROTTLED_FRAME_RATE);
+            DevToolsSession::new(&host).send("Page.setWebLifecycleState", Some(params));
+        } else {
+            DevToolsSession::new(&host).send("Page.setWebLifecycleState", Some(params));
+            host.was_hidden(false);
+            host.invalidate(PaintElementType::View);
+        }
+    };
+    if TaskRunner::currently_on(ThreadId::UI) {
+        apply();
+    } else {
+        TaskRunner::post_task_on(ThreadId::UI, apply);
+    }
+}
+
+pub(crate) fn is_throttled(browser: &Browser) -> bool {
+    THROTTLED.lock().contains_key(&browser.get_identifier())
+}
+
+/// Forgets `browser`. Call this from
+/// [LifeSpanHandlerCallbacks::on_before_close](crate::client::life_span_handler::LifeSpanHandlerCallbacks::on_before_close)
+/// for browsers that may have been throttled.
+pub fn forget_browser(browser: &Browser) {
+    THROTTLED.lock().remove(&browser.get_identifier());
+}
+
+/// Throttles the browsers of a window while the window is minimized. Report
+/// the window state with [ThrottlingPolicy::set_minimized]; browsers that were
+/// throttled explicitly stay throttled when the window is restored.
+#[derive(Clone, Default)]
+pub struct ThrottlingPolicy(Arc<Mutex<PolicyState>>);
+
+#[derive(Default)]
+struct PolicyState {
+    minimized: bool,
+    browsers: Vec<Browser>,
+    /// Browsers throttled by the policy, as opposed to by the application.
+    throttled_by_policy: Vec<i32>,
+}
+
+impl ThrottlingPolicy {
+    pub fn new() -> ThrottlingPolicy {
+        ThrottlingPolicy::default()
+    }
+    /// Adds a browser shown in the window. It is throttled right away if the
+    /// window is minimized.
+    pub fn add_browser(&self, browser: &Browser) {
+        let mut state = self.0.lock();
+        state.browsers.push(browser.clone());
+        if state.minimized && !is_throttled(browser) {
+            state.throttled_by_policy.push(browser.get_identifier());
+            set_hidden_and_throttled(browser, true);
+        }
+    }
+    pub fn remove_browser(&self, browser: &Browser) {
+        let browser_id = browser.get_identifier();
+        let mut state = self.0.lock();
+        state.browsers.retain(|b| b.get_identifier() != browser_id);
+        state.throttled_by_policy.retain(|id| *id != browser_id);
+    }
+    /// Call when the host window is minimized or restored.
+    pub fn set_minimized(&self, minimized: bool) {
+        let mut state = self.0.lock();
+        if state.minimized == minimized {
+            return;
+        }
+        state.minimized = minimized;
+        if minimized {
+            let browsers = state.browsers.iter()
+                .filter(|browser| !is_throttled(browser))
+                .cloned()
+                .collect::<Vec<_>>();
+            for browser in browsers {
+                state.throttled_by_policy.push(browser.get_identifier());
+                set_hidden_and_throttled(&browser, true);
+            }
+        } else {
+            let throttled = std::mem::replace(&mut state.throttled_by_policy, Vec::new());
+            for browser in state.browsers.iter().filter(|browser| throttled.contains(&browser.get_identifier())) {
+                set_hidden_and_throttled(browser, false);
+            }
+        }
+    }
+    pub fn is_minimized(&self) -> bool {
+        self.0.lock().minimized
+    }
+}
+
+/// How often [AdaptiveFrameRate] checks whether the browser became idle.
+const ADAPTIVE_TICK_MS: i64 = 250;
+
+struct AdaptiveState {
+    browser: Browser,
+    min_frame_rate: i32,
+    max_frame_rate: i32,
+    idle_delay: Duration,
+    min_dirty_pixels: i64,
+    frame_rate: i32,
+    last_activity: Instant,
+    ticking: bool,
+    enabled: bool,
+}
+
+/// Dials the windowless frame rate of a browser between a minimum and a
+/// maximum depending on activity.
+///
+/// Input and paints that change a noticeable area count as activity and raise
+/// the frame rate to the maximum right away. Once nothing happened for the
+/// idle delay, the frame rate is halved every 250 ms down to the minimum. Since
+/// animations keep producing paints even at the minimum rate, they bring the
+/// frame rate back up on their own. Report activity from the handlers of the
+/// browser:
+///
+/// * Call [AdaptiveFrameRate::on_paint] from
+///   [RenderHandlerCallbacks::on_paint](crate::client::render_handler::RenderHandlerCallbacks::on_paint).
+/// * Call [AdaptiveFrameRate::on_input] whenever input is sent to the browser.
+///
+/// Browsers throttled with [Browser::set_hidden_and_throttled](crate::browser::Browser::set_hidden_and_throttled)
+/// are left alone until they are shown again.
+#[derive(Clone)]
+pub struct AdaptiveFrameRate(Arc<Mutex<AdaptiveState>>);
+
+impl AdaptiveFrameRate {
+    /// Creates a controller for `browser`, which starts at `max_frame_rate`.
+    /// Rates are clamped to CEF's range of 1 to 60.
+    pub fn new(browser: &Browser, min_frame_rate: i32, max_frame_rate: i32) -> AdaptiveFrameRate {
+        let max_frame_rate = max_frame_rate.max(1).min(60);
+        let controller = AdaptiveFrameRate(Arc::new(Mutex::new(AdaptiveState {
+            browser: browser.clone(),
+            min_frame_rate: min_frame_rate.max(1).min(max_frame_rate),
+            max_frame_rate,
+            idle_delay: Duration::from_secs(1),
+            min_dirty_pixels: 32 * 32,
+            frame_rate: max_frame_rate,
+            last_activity: Instant::now(),
+            ticking: false,
+            enabled: true,
+        })));
+        controller.apply(max_frame_rate);
+        controller.start_ticking();
+        controller
+    }
+    /// Sets how long the browser has to be idle before the frame rate drops.
+    /// Defaults to one second.
+    pub fn idle_delay(self, idle_delay: Duration) -> Self {
+        self.0.lock().idle_delay = idle_delay;
+        self
+    }
+    /// Sets the dirty area, in pixels, below which paints don't count as
+    /// activity, so a blinking caret or spinner doesn't keep the frame rate
+    /// up. Defaults to 32 x 32.
+    pub fn min_dirty_pixels(self, min_dirty_pixels: i64) -> Self {
+        self.0.lock().min_dirty_pixels = min_dirty_pixels;
+        self
+    }
+    /// Returns the frame rate last set by the controller.
+    pub fn frame_rate(&self) -> i32 {
+        self.0.lock().frame_rate
+    }
+    /// Turns the controller off and restores the maximum frame rate, or turns
+    /// it back on.
+    pub fn set_enabled(&self, enabled: bool) {
+        let max_frame_rate = {
+            let mut state = self.0.lock();
+            if state.enabled == enabled {
+                return;
+            }
+            state.enabled = enabled;
+            state.last_activity = Instant::now();
+            state.max_frame_rate
+        };
+        self.apply(max_frame_rate);
+        if enabled {
+            self.start_ticking();
+        }
+    }
+    /// Reports input sent to the browser.
+    pub fn on_input(&self) {
+        self.on_activity();
+    }
+    /// Reports a paint of the browser. Paints of popups always count as
+    /// activity; paints of the view only if their dirty area is large enough.
+    pub fn on_paint(&self, type_: PaintElementType, dirty_rects: &[Rect]) {
+        let min_dirty_pixels = self.0.lock().min_dirty_pixels;
+        let dirty_pixels = dirty_rects.iter().map(|rect| rect.width as i64 * rect.height as i64).sum::<i64>();
+        if type_ == PaintElementType::Popup || dirty_pixels >= min_dirty_pixels {
+            self.on_activity();
+        }
+    }
+
+    fn on_activity(&self) {
+        let raise = {
+            let mut state = self.0.lock();
+            state.last_activity = Instant::now();
+            if !state.enabled || state.frame_rate == state.max_frame_rate {
+                None
+            } else {
+                Some(state.max_frame_rate)
+            }
+        };
+        if let Some(max_frame_rate) = raise {
+            self.apply(max_frame_rate);
+            self.start_ticking();
+        }
+    }
+    fn apply(&self, frame_rate: i32) {
+        let browser = {
+            let mut state = self.0.lock();
+            state.frame_rate = frame_rate;
+            state.browser.clone()
+        };
+        let apply = move || {
+            // The original rate is restored when the browser is shown again.
+            if !is_throttled(&browser) {
+                browser.get_host().set_windowless_frame_rate(frame_rate);
+            }
+        };
+        if TaskRunner::currently_on(ThreadId::UI) {
+            apply();
+        } else {
+            TaskRunner::post_task_on(ThreadId::UI, apply);
+        }
+    }
+    fn start_ticking(&self) {
+        {
+            let mut state = self.0.lock();
+            if state.ticking {
+                return;
+            }
+            state.ticking = true;
+        }
+        schedule_tick(Arc::downgrade(&self.0));
+    }
+}
+
+fn schedule_tick(state: Weak<Mutex<AdaptiveState>>) {
+    TaskRunner::post_delayed_task_on(ThreadId::UI, move || {
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let controller = AdaptiveFrameRate(state);
+        let lower = {
+            let mut state = controller.0.lock();
+            if !state.enabled || state.frame_rate <= state.min_frame_rate {
+                state.ticking = false;
+                return;
+            }
+            if state.last_activity.elapsed() < state.idle_delay {
+                None
+            } else {
+                Some((state.frame_rate / 2).max(state.min_frame_rate))
+            }
+        };
+        if let Some(frame_rate) = lower {
+            controller.apply(frame_rate);
+        }
+        schedule_tick(Arc::downgrade(&controller.0));
+    }, ADAPTIVE_TICK_MS);
+}
@@ -0,0 +1,61 @@
+//! Timers running on the browser process UI thread.
+//!
+//! Timers are built on [TaskRunner::post_delayed_task_on], so they need no
+//! runtime besides CEF's own message loop. Their callbacks are always executed
+//! on the UI thread.
+
+use crate::task::{TaskRunner, ThreadId};
+use std::{
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    time::Duration,
+};
+
+/// Handle to a timer created with [once] or [interval]. Dropping the handle
+/// does not cancel the timer; call [TimerHandle::cancel] for that.
+#[derive(Debug, Clone)]
+pub struct TimerHandle(Arc<AtomicBool>);
+
+impl TimerHandle {
+    /// Stops the timer. The callback will not be executed again, even if it is
+    /// already queued on the UI thread.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Executes `f` on the UI thread once `delay` has elapsed.
+pub fn once(delay: Duration, f: impl 'static + Send + FnOnce()) -> TimerHandle {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let handle = TimerHandle(cancelled.clone());
+    TaskRunner::post_delayed_task_on(ThreadId::UI, move || {
+        if !cancelled.load(Ordering::SeqCst) {
+            f();
+        }
+    }, delay.as_millis() as i64);
+    handle
+}
+
+/// Executes `f` on the UI thread every `period`, starting one `period` from
+/// now, until the timer is cancelled. The next execution is scheduled after
+/// `f` returns, so slow callbacks delay the following ones rather than piling
+/// up.
+pub fn interval(period: Duration, f: impl 'static + Send + FnMut()) -> TimerHandle {
+    let handle = TimerHandle(Arc::new(AtomicBool::new(false)));
+    schedule(handle.clone(), period.as_millis() as i64, Box::new(f));
+    handle
+}
+
+fn schedule(handle: TimerHandle, period_ms: i64, mut f: Box<dyn 'static + Send + FnMut()>) {
+    TaskRunner::post_delayed_task_on(ThreadId::UI, move || {
+        if handle.is_cancelled() {
+            return;
+        }
+        f();
+        if !handle.is_cancelled() {
+            schedule(handle, period_ms, f);
+        }
+    }, period_ms);
+}
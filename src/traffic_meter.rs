@@ -0,0 +1,226 @@
+//! Counting the bytes browsers receive, for usage reporting on metered
+//! connections.
+//!
+//! A [TrafficMeter] sums up the bytes received over the network, including
+//! headers, by browser, by host and by resource type. Browsers are metered
+//! through the DevTools `Network` domain once they're attached, and requests
+//! the application makes itself can be added with [TrafficMeter::record]:
+//!
+//! ```ignore
+//! let meter = TrafficMeter::new();
+//! // in on_after_created:
+//! attachments.insert(browser.get_identifier(), meter.attach(&browser));
+//! // every minute:
+//! let _timer = meter.report_every(Duration::from_secs(60), true, |snapshot| upload_usage(snapshot));
+//! ```
+//!
+//! Responses served from the cache count as 0 bytes, as they don't use the
+//! connection.
+
+use crate::{
+    browser::Browser,
+    devtools::DevToolsSession,
+    timer::{self, TimerHandle},
+    values::{DictionaryValue, StoredValue},
+};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+/// The totals of a [TrafficMeter] at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrafficSnapshot {
+    pub taken_at: DateTime<Utc>,
+    /// When counting started or the meter was last reset.
+    pub since: DateTime<Utc>,
+    pub total: u64,
+    /// Bytes by browser identifier. Requests without a browser aren't listed.
+    pub by_browser: HashMap<i32, u64>,
+    /// Bytes by lowercase host name.
+    pub by_host: HashMap<String, u64>,
+    /// Bytes by DevTools resource type, e.g. `Document`, `Image` or `Media`.
+    pub by_resource_type: HashMap<String, u64>,
+}
+
+struct Totals {
+    since: DateTime<Utc>,
+    total: u64,
+    by_browser: HashMap<i32, u64>,
+    by_host: HashMap<String, u64>,
+    by_resource_type: HashMap<String, u64>,
+}
+
+impl Totals {
+    fn new() -> Totals {
+        Totals {
+            since: Utc::now(),
+            total: 0,
+            by_browser: HashMap::new(),
+            by_host: HashMap::new(),
+            by_resource_type: HashMap::new(),
+        }
+    }
+}
+
+/// Returns the lowercase host of `url`, or an empty string for URLs without
+/// one, like `data:` URLs.
+fn host_of(url: &str) -> String {
+    let rest = match url.find("://") {
+        Some(scheme_end) => &url[scheme_end + 3..],
+        None => return String::new(),
+    };
+    let authority = rest.split(|c| c == '/' || c == '?' || c == '#').next().unwrap_or("");
+    let host_port = authority.rsplit('@').next().unwrap_or("");
+    let host = match host_port.rfind(':') {
+        Some(colon) if !host_port.ends_with(']') => &host_port[..colon],
+        _ => host_port,
+    };
+    host.to_ascii_lowercase()
+}
+
+/// Sums up received bytes. See the [module documentation](self).
+///
+/// Cloning the meter is cheap; clones share the totals.
+#[derive(Clone)]
+pub struct TrafficMeter(Arc<Mutex<Totals>>);
+
+impl TrafficMeter {
+    pub fn new() -> TrafficMeter {
+        TrafficMeter(Arc::new(Mutex::new(Totals::new())))
+    }
+    /// Starts metering the requests of `browser`. Metering stops when the
+    /// returned value is dropped.
+    pub fn attach(&self, browser: &Browser) -> MeteredBrowser {
+        MeteredBrowser::new(browser, Arc::downgrade(&self.0))
+    }
+    /// Adds `bytes` received for `url`, e.g. by a
+    /// [URLRequest](crate::url_request::URLRequest) of the application as
+    /// reported by its `on_download_progress`.
+    pub fn record(&self, browser_id: Option<i32>, url: &str, resource_type: &str, bytes: u64) {
+        record(&self.0, browser_id, url, resource_type, bytes);
+    }
+    pub fn snapshot(&self) -> TrafficSnapshot {
+        let totals = self.0.lock();
+        TrafficSnapshot {
+            taken_at: Utc::now(),
+            since: totals.since,
+            total: totals.total,
+            by_browser: totals.by_browser.clone(),
+            by_host: totals.by_host.clone(),
+            by_resource_type: totals.by_resource_type.clone(),
+        }
+    }
+    /// Returns the current totals and starts counting from zero.
+    pub fn take_snapshot(&self) -> TrafficSnapshot {
+        let snapshot = self.snapshot();
+        *self.0.lock() = Totals {
+            since: snapshot.taken_at,
+            ..Totals::new()
+        };
+        snapshot
+    }
+    /// Calls `f` on the UI thread with a snapshot every `period`. With
+    /// `reset`, every snapshot covers the traffic since the previous one.
+    pub fn report_every(&self, period: Duration, reset: bool, mut f: impl 'static + Send + FnMut(TrafficSnapshot)) -> TimerHandle {
+        let meter = self.clone();
+        timer::interval(period, move || {
+            f(if reset { meter.take_snapshot() } else { meter.snapshot() });
+        })
+    }
+    pub fn reset(&self) {
+        *self.0.lock() = Totals::new();
+    }
+}
+
+impl Default for TrafficMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn record(totals: &Mutex<Totals>, browser_id: Option<i32>, url: &str, resource_type: &str, bytes: u64) {
+    if bytes == 0 {
+        return;
+    }
+    let mut totals = totals.lock();
+    totals.total += bytes;
+    if let Some(browser_id) = browser_id {
+        *totals.by_browser.entry(browser_id).or_insert(0) += bytes;
+    }
+    *totals.by_host.entry(host_of(url)).or_insert(0) += bytes;
+    *totals.by_resource_type.entry(resource_type.to_owned()).or_insert(0) += bytes;
+}
+
+/// A request seen by a [MeteredBrowser] that hasn't finished yet.
+struct PendingRequest {
+    url: String,
+    resource_type: String,
+    /// The bytes received so far, counted if the request fails.
+    received: u64,
+}
+
+/// Metering of one browser, returned by [TrafficMeter::attach].
+pub struct MeteredBrowser {
+    session: DevToolsSession,
+}
+
+impl MeteredBrowser {
+    fn new(browser: &Browser, totals: Weak<Mutex<Totals>>) -> MeteredBrowser {
+        let browser_id = browser.get_identifier();
+        let session = DevToolsSession::new(&browser.get_host());
+        let pending = Arc::new(Mutex::new(HashMap::<String, PendingRequest>::new()));
+        // The listeners are owned by the session, so they only hold the
+        // totals weakly.
+        let listen = |method: &str, f: fn(&Mutex<Totals>, &Mutex<HashMap<String, PendingRequest>>, i32, &DictionaryValue)| {
+            let totals = totals.clone();
+            let pending = pending.clone();
+            session.add_event_listener(method, move |_, params| {
+                if let (Some(totals), StoredValue::Dictionary(params)) = (totals.upgrade(), params) {
+                    f(&totals, &pending, browser_id, params);
+                }
+            });
+        };
+        listen("Network.requestWillBeSent", |totals, pending, browser_id, params| {
+            let url = params.try_get_dictionary("request").map(|request| request.get_string("url")).unwrap_or_default();
+            let request_id = params.get_string("requestId");
+            let resource_type = params.get_string("type");
+            let mut pending = pending.lock();
+            // A redirect: the previous response is complete.
+            if let Some(redirected) = pending.remove(&request_id) {
+                if let Some(response) = params.try_get_dictionary("redirectResponse") {
+                    let bytes = response.get_double("encodedDataLength") as u64;
+                    record(totals, Some(browser_id), &redirected.url, &redirected.resource_type, bytes);
+                }
+            }
+            pending.insert(request_id, PendingRequest { url, resource_type, received: 0 });
+        });
+        listen("Network.dataReceived", |_, pending, _, params| {
+            if let Some(request) = pending.lock().get_mut(&params.get_string("requestId")) {
+                request.received += params.get_int("encodedDataLength").max(0) as u64;
+            }
+        });
+        listen("Network.loadingFinished", |totals, pending, browser_id, params| {
+            if let Some(request) = pending.lock().remove(&params.get_string("requestId")) {
+                let bytes = params.get_double("encodedDataLength") as u64;
+                record(totals, Some(browser_id), &request.url, &request.resource_type, bytes);
+            }
+        });
+        listen("Network.loadingFailed", |totals, pending, browser_id, params| {
+            if let Some(request) = pending.lock().remove(&params.get_string("requestId")) {
+                record(totals, Some(browser_id), &request.url, &request.resource_type, request.received);
+            }
+        });
+        session.send("Network.enable", None);
+        MeteredBrowser { session }
+    }
+}
+
+impl Drop for MeteredBrowser {
+    fn drop(&mut self) {
+        self.session.send("Network.disable", None);
+    }
+}
@@ -0,0 +1,25 @@
+//! URL globs in Rust, matching the semantics of [js_util::glob_to_regex](crate::js_util::glob_to_regex).
+
+/// Returns true if `url` matches `glob` as a whole, where `*` matches any
+/// sequence of characters and every other character matches itself.
+pub(crate) fn glob_matches(glob: &str, url: &str) -> bool {
+    let mut parts = glob.split('*');
+    // `split` always yields at least one part.
+    let first = parts.next().unwrap();
+    if !url.starts_with(first) {
+        return false;
+    }
+    let mut rest = &url[first.len()..];
+    let parts = parts.collect::<Vec<_>>();
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        None => return rest.is_empty(),
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
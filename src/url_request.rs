@@ -7,10 +7,19 @@ use cef_sys::{
     cef_urlrequest_create, cef_urlrequest_status_t, cef_urlrequest_t,
 };
 use std::{
+    collections::HashMap,
     convert::TryInto,
+    env,
+    fs::{self, File},
+    future::Future,
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
     ptr::null_mut,
     os::raw::{c_int, c_void},
     cell::RefCell,
+    sync::Arc,
+    task::{Context, Poll, Waker},
 };
 
 use crate::{
@@ -20,11 +29,13 @@ use crate::{
     frame::Frame,
     load_handler::ErrorCode,
     refcounted::{RefCountedPtr, Wrapper},
-    request::Request,
+    request::{PostData, PostDataElement, Request, URLRequestFlags},
     response::Response,
     request_context::RequestContext,
     string::CefString,
+    task::TaskRunner,
 };
+use uuid::Uuid;
 
 /// Flags that represent [URLRequest] status.
 #[repr(C)]
@@ -75,7 +86,8 @@ impl URLRequest {
     ///   - It cannot be intercepted by the client so only http(s) and blob schemes
     ///     are supported.
     ///   - POST data may only contain a single element of type [PostDataElementType::Bytes].
-    ///   - The `request_context` parameter must be None.
+    ///   - The `request_context` parameter must be None. It is ignored with a
+    ///     warning otherwise.
     ///
     /// The `request` object will be marked as read-only after calling this function.
     pub fn new(
@@ -83,6 +95,14 @@ impl URLRequest {
         client: URLRequestClient,
         request_context: Option<&RequestContext>,
     ) -> Self {
+        // CEF rejects request contexts outside of the browser process.
+        let request_context = match request_context {
+            Some(_) if crate::process_type() != crate::ProcessType::Browser => {
+                log::warn!("ignoring the request context of a URL request made in a render process");
+                None
+            }
+            request_context => request_context,
+        };
         unsafe {
             Self::from_ptr_unchecked(cef_urlrequest_create(
                 request.as_ptr(),
@@ -159,6 +179,21 @@ impl AuthCallback {
     }
 }
 
+/// A request for credentials, as passed to
+/// [URLRequestClientCallbacks::get_auth_credentials].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthChallenge {
+    pub is_proxy: bool,
+    pub host: String,
+    pub port: u16,
+    pub realm: String,
+    pub scheme: String,
+}
+
+/// Answers an [AuthChallenge] with a user name and password, or `None` to
+/// cancel the request.
+pub(crate) type AuthProvider = Arc<dyn 'static + Send + Sync + Fn(&AuthChallenge) -> Option<(String, String)>>;
+
 ref_counted_ptr!{
     pub struct URLRequestClient(*mut cef_urlrequest_client_t);
 }
@@ -303,8 +338,567 @@ cef_callback_impl! {
     }
 }
 
+/// A complete response received by [URLRequest::fetch].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchedResponse {
+    /// The URL after redirects.
+    pub url: String,
+    pub status: i32,
+    pub status_text: String,
+    pub mime_type: String,
+    pub charset: String,
+    pub headers: HashMap<String, Vec<String>>,
+    pub body: Vec<u8>,
+    pub was_cached: bool,
+}
+
+struct FetchState {
+    result: Option<Result<FetchedResponse, ErrorCode>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves to the response of [URLRequest::fetch]. Dropping it before it
+/// resolved cancels the request.
+pub struct Fetch {
+    state: Arc<Mutex<FetchState>>,
+    request: URLRequest,
+    task_runner: Option<TaskRunner>,
+}
+
+impl Fetch {
+    /// Returns the underlying request, e.g. to check its status.
+    pub fn url_request(&self) -> &URLRequest {
+        &self.request
+    }
+}
+
+impl Future for Fetch {
+    type Output = Result<FetchedResponse, ErrorCode>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Fetch {
+    fn drop(&mut self) {
+        cancel_on_thread(self.request.clone(), self.task_runner.as_ref());
+    }
+}
+
+/// Cancels `request` if it's still pending, on the thread of `task_runner`
+/// that created it, so futures can be dropped on any thread.
+fn cancel_on_thread(request: URLRequest, task_runner: Option<&TaskRunner>) {
+    let cancel = move || {
+        if request.get_request_status() == URLRequestStatus::IOPending {
+            request.cancel();
+        }
+    };
+    match task_runner {
+        Some(task_runner) => {
+            task_runner.post_task(cancel);
+        }
+        None => cancel(),
+    }
+}
+
+struct FetchClient {
+    complete: Box<dyn 'static + Send + Sync + Fn(Result<FetchedResponse, ErrorCode>)>,
+    upload_progress: Option<Box<dyn 'static + Send + Sync + Fn(u64, u64)>>,
+    auth: Option<AuthProvider>,
+    body: Mutex<Vec<u8>>,
+}
+
+/// Returns true if `request` was stopped at a redirect because of
+/// [URLRequestFlags::StopOnRedirect], which CEF reports as canceled.
+fn stopped_on_redirect(request: &URLRequest) -> bool {
+    request.get_request().get_flags().contains(&URLRequestFlags::StopOnRedirect)
+        && request.get_response().map(|response| (300..400).contains(&response.get_status())).unwrap_or(false)
+}
+
+impl URLRequestClientCallbacks for FetchClient {
+    fn on_request_complete(&self, request: URLRequest) {
+        let status = request.get_request_status();
+        let completed = status == URLRequestStatus::Success || (status == URLRequestStatus::Canceled && stopped_on_redirect(&request));
+        let result = match status {
+            _ if completed => {
+                let response = request.get_response();
+                Ok(FetchedResponse {
+                    url: response.as_ref().map(|response| response.get_url()).unwrap_or_default(),
+                    status: response.as_ref().map(|response| response.get_status()).unwrap_or(0),
+                    status_text: response.as_ref().map(|response| response.get_status_text()).unwrap_or_default(),
+                    mime_type: response.as_ref().map(|response| response.get_mime_type()).unwrap_or_default(),
+                    charset: response.as_ref().map(|response| response.get_charset()).unwrap_or_default(),
+                    headers: response.as_ref().map(|response| response.get_header_map()).unwrap_or_default(),
+                    body: std::mem::replace(&mut *self.body.lock(), Vec::new()),
+                    was_cached: request.response_was_cached(),
+                })
+            }
+            URLRequestStatus::Canceled => Err(ErrorCode::Aborted),
+            _ => Err(match request.get_request_error() {
+                ErrorCode::None => ErrorCode::Failed,
+                error => error,
+            }),
+        };
+        (self.complete)(result);
+    }
+    fn on_upload_progress(&self, request: URLRequest, current: i64, total: i64) {
+        if let Some(upload_progress) = &self.upload_progress {
+            upload_progress(current.max(0) as u64, total.max(0) as u64);
+        }
+    }
+    fn on_download_data(&self, request: URLRequest, data: &[u8]) {
+        self.body.lock().extend_from_slice(data);
+    }
+    fn get_auth_credentials(&self, is_proxy: bool, host: &str, port: u16, realm: &str, scheme: &str, callback: AuthCallback) -> bool {
+        let auth = match &self.auth {
+            Some(auth) => auth,
+            None => return false,
+        };
+        let challenge = AuthChallenge {
+            is_proxy,
+            host: host.to_owned(),
+            port,
+            realm: realm.to_owned(),
+            scheme: scheme.to_owned(),
+        };
+        match auth(&challenge) {
+            Some((username, password)) => {
+                callback.cont(&username, &password);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl URLRequest {
+    /// Sends `request` like [URLRequest::new] and resolves to the complete
+    /// response, with the body buffered in memory. HTTP error statuses
+    /// resolve to a response; only network errors and cancellation resolve to
+    /// an error.
+    pub fn fetch(request: &mut Request, request_context: Option<&RequestContext>) -> Fetch {
+        URLRequest::fetch_with_auth(request, request_context, None)
+    }
+    /// Like [URLRequest::fetch], answering requests for credentials with
+    /// `auth`.
+    pub(crate) fn fetch_with_auth(request: &mut Request, request_context: Option<&RequestContext>, auth: Option<AuthProvider>) -> Fetch {
+        let state = Arc::new(Mutex::new(FetchState { result: None, waker: None }));
+        let complete_state = state.clone();
+        let client = FetchClient {
+            complete: Box::new(move |result| {
+                let waker = {
+                    let mut state = complete_state.lock();
+                    state.result = Some(result);
+                    state.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }),
+            upload_progress: None,
+            auth,
+            body: Mutex::new(Vec::new()),
+        };
+        let request = URLRequest::new(request, URLRequestClient::new(client), request_context);
+        Fetch {
+            state,
+            request,
+            task_runner: TaskRunner::get_for_current_thread(),
+        }
+    }
+    /// Sends `request` with a body read from `body`, and resolves to the
+    /// complete response like [URLRequest::fetch]. Must be called on a CEF
+    /// thread of the browser process.
+    ///
+    /// CEF can't take a body in pieces, so `body` is copied to a temporary
+    /// file on a background thread, and the network stack then reads that
+    /// file as it uploads. Memory use doesn't depend on the size of the body.
+    /// `on_progress` is called on the calling thread with the number of bytes
+    /// sent and the total size. The file is deleted once the request
+    /// completes.
+    pub fn upload(
+        request: Request,
+        mut body: impl 'static + Read + Send,
+        request_context: Option<RequestContext>,
+        on_progress: impl 'static + Send + Sync + Fn(u64, u64),
+    ) -> Upload {
+        let state = Arc::new(Mutex::new(UploadState {
+            result: None,
+            waker: None,
+            request: None,
+            canceled: false,
+        }));
+        let task_runner = TaskRunner::get_for_current_thread();
+        let upload = Upload {
+            state: state.clone(),
+            task_runner: task_runner.clone(),
+        };
+        let task_runner = match task_runner {
+            Some(task_runner) => task_runner,
+            None => {
+                let error = io::Error::new(io::ErrorKind::Other, "URLRequest::upload must be called on a CEF thread");
+                finish_upload(&state, Err(UploadError::Read(error)));
+                return upload;
+            }
+        };
+        std::thread::spawn(move || {
+            let path = env::temp_dir().join(format!("cef-upload-{}", Uuid::new_v4().to_simple()));
+            let spooled = File::create(&path).and_then(|mut file| io::copy(&mut body, &mut file).and_then(|_| file.sync_all()));
+            if let Err(error) = spooled {
+                let _ = fs::remove_file(&path);
+                finish_upload(&state, Err(UploadError::Read(error)));
+                return;
+            }
+            task_runner.post_task(move || {
+                if state.lock().canceled {
+                    let _ = fs::remove_file(&path);
+                    return;
+                }
+                let element = PostDataElement::new();
+                element.set_to_file(&path.to_string_lossy());
+                let post_data = PostData::new();
+                post_data.add_element(&element);
+                let mut request = request;
+                request.set_post_data(post_data);
+                let mut flags = request.get_flags();
+                if !flags.contains(&URLRequestFlags::ReportUploadProgress) {
+                    flags.push(URLRequestFlags::ReportUploadProgress);
+                    request.set_flags(&flags);
+                }
+                let complete_state = state.clone();
+                let client = FetchClient {
+                    complete: Box::new(move |result| {
+                        let _ = fs::remove_file(&path);
+                        finish_upload(&complete_state, result.map_err(UploadError::Request));
+                    }),
+                    upload_progress: Some(Box::new(on_progress)),
+                    auth: None,
+                    body: Mutex::new(Vec::new()),
+                };
+                let url_request = URLRequest::new(&mut request, URLRequestClient::new(client), request_context.as_ref());
+                state.lock().request = Some(url_request);
+            });
+        });
+        upload
+    }
+}
+
+/// The reason an [Upload] failed.
+#[derive(Debug)]
+pub enum UploadError {
+    /// Reading the body failed.
+    Read(io::Error),
+    /// The request failed or was canceled.
+    Request(ErrorCode),
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UploadError::Read(error) => write!(f, "failed to read the upload body: {}", error),
+            UploadError::Request(error) => write!(f, "upload failed: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UploadError::Read(error) => Some(error),
+            UploadError::Request(_) => None,
+        }
+    }
+}
+
+struct UploadState {
+    result: Option<Result<FetchedResponse, UploadError>>,
+    waker: Option<Waker>,
+    /// Set once the body was spooled and the request started.
+    request: Option<URLRequest>,
+    canceled: bool,
+}
+
+fn finish_upload(state: &Mutex<UploadState>, result: Result<FetchedResponse, UploadError>) {
+    let waker = {
+        let mut state = state.lock();
+        state.request = None;
+        state.result = Some(result);
+        state.waker.take()
+    };
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+}
+
+/// Resolves to the response of [URLRequest::upload]. Dropping it before it
+/// resolved cancels the upload.
+pub struct Upload {
+    state: Arc<Mutex<UploadState>>,
+    task_runner: Option<TaskRunner>,
+}
+
+impl Future for Upload {
+    type Output = Result<FetchedResponse, UploadError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        let request = {
+            let mut state = self.state.lock();
+            state.canceled = true;
+            state.request.take()
+        };
+        // The request must be canceled on the thread that created it.
+        if let (Some(request), Some(task_runner)) = (request, &self.task_runner) {
+            task_runner.post_task(move || {
+                if request.get_request_status() == URLRequestStatus::IOPending {
+                    request.cancel();
+                }
+            });
+        }
+    }
+}
+
+/// A response whose body [URLRequest::download_to_file] wrote to a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadedFile {
+    /// The URL after redirects.
+    pub url: String,
+    pub status: i32,
+    pub status_text: String,
+    pub mime_type: String,
+    pub headers: HashMap<String, Vec<String>>,
+    pub path: PathBuf,
+    /// The number of bytes written to `path`.
+    pub size: u64,
+}
+
+/// The reason a [DownloadToFile] failed. The partially written file is
+/// removed in either case.
+#[derive(Debug)]
+pub enum DownloadError {
+    /// Creating or writing the file failed.
+    Write(io::Error),
+    /// The request failed or was canceled.
+    Request(ErrorCode),
+}
+
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DownloadError::Write(error) => write!(f, "failed to write the download: {}", error),
+            DownloadError::Request(error) => write!(f, "download failed: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DownloadError::Write(error) => Some(error),
+            DownloadError::Request(_) => None,
+        }
+    }
+}
+
+struct DownloadState {
+    result: Option<Result<DownloadedFile, DownloadError>>,
+    waker: Option<Waker>,
+}
+
+/// Resolves once [URLRequest::download_to_file] finished. Dropping it before
+/// it resolved cancels the download.
+pub struct DownloadToFile {
+    state: Arc<Mutex<DownloadState>>,
+    request: Option<URLRequest>,
+    task_runner: Option<TaskRunner>,
+}
+
+impl DownloadToFile {
+    /// Returns the underlying request, or `None` if the file couldn't be
+    /// created.
+    pub fn url_request(&self) -> Option<&URLRequest> {
+        self.request.as_ref()
+    }
+    /// Cancels the download. Must be called on the thread that started it.
+    pub fn cancel(&self) {
+        if let Some(request) = &self.request {
+            if request.get_request_status() == URLRequestStatus::IOPending {
+                request.cancel();
+            }
+        }
+    }
+}
+
+impl Future for DownloadToFile {
+    type Output = Result<DownloadedFile, DownloadError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for DownloadToFile {
+    fn drop(&mut self) {
+        if let Some(request) = self.request.take() {
+            cancel_on_thread(request, self.task_runner.as_ref());
+        }
+    }
+}
+
+struct DownloadClient {
+    state: Arc<Mutex<DownloadState>>,
+    path: PathBuf,
+    file: Mutex<Option<BufWriter<File>>>,
+    written: Mutex<u64>,
+    write_error: Mutex<Option<io::Error>>,
+    progress: Box<dyn 'static + Send + Sync + Fn(u64, Option<u64>)>,
+}
+
+impl DownloadClient {
+    fn finish(&self, request: &URLRequest) -> Result<DownloadedFile, DownloadError> {
+        let file = self.file.lock().take();
+        if let Some(error) = self.write_error.lock().take() {
+            return Err(DownloadError::Write(error));
+        }
+        match request.get_request_status() {
+            URLRequestStatus::Success => (),
+            URLRequestStatus::Canceled => return Err(DownloadError::Request(ErrorCode::Aborted)),
+            _ => {
+                return Err(DownloadError::Request(match request.get_request_error() {
+                    ErrorCode::None => ErrorCode::Failed,
+                    error => error,
+                }))
+            }
+        }
+        if let Some(mut file) = file {
+            file.flush().map_err(DownloadError::Write)?;
+        }
+        let response = request.get_response();
+        Ok(DownloadedFile {
+            url: response.as_ref().map(|response| response.get_url()).unwrap_or_default(),
+            status: response.as_ref().map(|response| response.get_status()).unwrap_or(0),
+            status_text: response.as_ref().map(|response| response.get_status_text()).unwrap_or_default(),
+            mime_type: response.as_ref().map(|response| response.get_mime_type()).unwrap_or_default(),
+            headers: response.as_ref().map(|response| response.get_header_map()).unwrap_or_default(),
+            path: self.path.clone(),
+            size: *self.written.lock(),
+        })
+    }
+}
+
+impl URLRequestClientCallbacks for DownloadClient {
+    fn on_request_complete(&self, request: URLRequest) {
+        let result = self.finish(&request);
+        if result.is_err() {
+            let _ = fs::remove_file(&self.path);
+        }
+        let waker = {
+            let mut state = self.state.lock();
+            state.result = Some(result);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+    fn on_download_progress(&self, request: URLRequest, current: i64, total: i64) {
+        (self.progress)(current.max(0) as u64, if total < 0 { None } else { Some(total as u64) });
+    }
+    fn on_download_data(&self, request: URLRequest, data: &[u8]) {
+        let mut file = self.file.lock();
+        let result = match &mut *file {
+            Some(file) => file.write_all(data),
+            None => return,
+        };
+        match result {
+            Ok(()) => *self.written.lock() += data.len() as u64,
+            Err(error) => {
+                *file = None;
+                // Canceling completes the request right away, which takes
+                // the file lock again.
+                drop(file);
+                *self.write_error.lock() = Some(error);
+                request.cancel();
+            }
+        }
+    }
+}
+
+impl URLRequest {
+    /// Sends `request` like [URLRequest::new] and writes the response body to
+    /// `path` as it arrives, so large files aren't held in memory. An existing
+    /// file is replaced. `on_progress` is called on the calling thread with
+    /// the number of bytes received and the expected size, if known. As with
+    /// [URLRequest::fetch], HTTP error statuses resolve to a response.
+    pub fn download_to_file(
+        request: &mut Request,
+        path: impl AsRef<Path>,
+        request_context: Option<&RequestContext>,
+        on_progress: impl 'static + Send + Sync + Fn(u64, Option<u64>),
+    ) -> DownloadToFile {
+        let path = path.as_ref().to_owned();
+        let state = Arc::new(Mutex::new(DownloadState { result: None, waker: None }));
+        let file = match File::create(&path) {
+            Ok(file) => file,
+            Err(error) => {
+                state.lock().result = Some(Err(DownloadError::Write(error)));
+                return DownloadToFile { state, request: None, task_runner: None };
+            }
+        };
+        let client = DownloadClient {
+            state: state.clone(),
+            path,
+            file: Mutex::new(Some(BufWriter::new(file))),
+            written: Mutex::new(0),
+            write_error: Mutex::new(None),
+            progress: Box::new(on_progress),
+        };
+        let request = URLRequest::new(request, URLRequestClient::new(client), request_context);
+        DownloadToFile {
+            state,
+            request: Some(request),
+            task_runner: TaskRunner::get_for_current_thread(),
+        }
+    }
+}
+
 ref_counted_ptr! {
     /// Callback structure used for asynchronous continuation of url requests.
+    ///
+    /// Handlers that return [ReturnValue::ContinueAsync](crate::ReturnValue::ContinueAsync),
+    /// e.g. from
+    /// [ResourceRequestHandlerCallbacks::on_before_resource_load](crate::resource_request_handler::ResourceRequestHandlerCallbacks::on_before_resource_load),
+    /// can keep the callback, which holds its own reference, and decide later
+    /// on any thread. The request stays pending until [RequestCallback::cont]
+    /// or [RequestCallback::cancel] is called, so every path must call one of
+    /// them exactly once.
     pub struct RequestCallback(*mut cef_request_callback_t);
 }
 
@@ -312,14 +906,18 @@ impl RequestCallback {
     /// Continue the url request. If `allow` is true the request will be
     /// continued. Otherwise, the request will be canceled.
     pub fn cont(&self, allow: bool) {
-        unsafe {
-            self.0.cont.unwrap()(self.0.as_ptr(), allow as i32);
+        if let Some(cont) = self.0.cont {
+            unsafe {
+                cont(self.as_ptr(), allow as i32);
+            }
         }
     }
     /// Cancel the url request.
     pub fn cancel(&self) {
-        unsafe {
-            self.0.cancel.unwrap()(self.0.as_ptr());
+        if let Some(cancel) = self.0.cancel {
+            unsafe {
+                cancel(self.as_ptr());
+            }
         }
     }
 }
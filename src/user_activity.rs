@@ -0,0 +1,111 @@
+//! Tracking user input per browser, e.g. for screensavers or returning a
+//! kiosk to its home page.
+//!
+//! Input is recorded when it is sent to a browser with the `send_*_event`
+//! functions of [BrowserHost](crate::browser_host::BrowserHost), which is how
+//! windowless browsers receive it, and when a key event reaches a
+//! [KeyboardHandler](crate::client::keyboard_handler::KeyboardHandler). Mouse
+//! input to windowed browsers isn't visible to the crate.
+
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+    time::{Duration, Instant},
+};
+
+/// Listeners are notified at most this often per browser, since mouse moves
+/// arrive at a high rate.
+const EVENT_INTERVAL: Duration = Duration::from_secs(1);
+
+static NEXT_LISTENER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The kind of input that was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputKind {
+    Key,
+    MouseClick,
+    MouseMove,
+    MouseWheel,
+    Touch,
+}
+
+/// Reported to listeners added with [add_listener].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserActivity {
+    pub browser_id: i32,
+    pub kind: InputKind,
+    pub time: Instant,
+    /// How long the browser was idle before this input, or `None` if this is
+    /// its first input.
+    pub idle_before: Option<Duration>,
+}
+
+/// Identifies a listener added with [add_listener].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ActivityListenerId(u64);
+
+struct BrowserActivity {
+    last_input: Instant,
+    last_event: Instant,
+}
+
+type ActivityListener = Arc<dyn 'static + Send + Sync + Fn(&UserActivity)>;
+
+lazy_static::lazy_static! {
+    static ref ACTIVITY: Mutex<HashMap<i32, BrowserActivity>> = Mutex::new(HashMap::new());
+    static ref LISTENERS: Mutex<Vec<(ActivityListenerId, ActivityListener)>> = Mutex::new(Vec::new());
+}
+
+/// Calls `listener` when a browser receives input. Calls are throttled to one
+/// per second and browser, so not every input is reported.
+pub fn add_listener(listener: impl 'static + Send + Sync + Fn(&UserActivity)) -> ActivityListenerId {
+    let id = ActivityListenerId(NEXT_LISTENER_ID.fetch_add(1, Ordering::SeqCst));
+    LISTENERS.lock().push((id, Arc::new(listener)));
+    id
+}
+
+pub fn remove_listener(id: ActivityListenerId) {
+    LISTENERS.lock().retain(|(listener_id, _)| *listener_id != id);
+}
+
+/// Returns the time of the last input to the browser with the identifier
+/// `browser_id`.
+pub fn last_input(browser_id: i32) -> Option<Instant> {
+    ACTIVITY.lock().get(&browser_id).map(|activity| activity.last_input)
+}
+
+/// Returns the time of the last input to any browser.
+pub fn last_input_any() -> Option<Instant> {
+    ACTIVITY.lock().values().map(|activity| activity.last_input).max()
+}
+
+pub(crate) fn record(browser_id: i32, kind: InputKind) {
+    let now = Instant::now();
+    let activity = {
+        let mut browsers = ACTIVITY.lock();
+        match browsers.get_mut(&browser_id) {
+            Some(activity) => {
+                let idle_before = now - activity.last_input;
+                activity.last_input = now;
+                if now - activity.last_event < EVENT_INTERVAL {
+                    return;
+                }
+                activity.last_event = now;
+                UserActivity { browser_id, kind, time: now, idle_before: Some(idle_before) }
+            }
+            None => {
+                browsers.insert(browser_id, BrowserActivity { last_input: now, last_event: now });
+                UserActivity { browser_id, kind, time: now, idle_before: None }
+            }
+        }
+    };
+    let listeners = LISTENERS.lock().iter().map(|(_, listener)| listener.clone()).collect::<Vec<_>>();
+    for listener in listeners {
+        listener(&activity);
+    }
+}
+
+pub(crate) fn forget_browser(browser_id: i32) {
+    ACTIVITY.lock().remove(&browser_id);
+}
@@ -0,0 +1,328 @@
+//! Greasemonkey-style user script injection.
+//!
+//! Scripts are registered with `Page.addScriptToEvaluateOnNewDocument` over the
+//! DevTools protocol, so they run before any page script of every document
+//! created after [UserScripts::add] and survive navigations.
+
+use crate::{
+    browser::Browser,
+    browser_host::BrowserHost,
+    frame::Frame,
+    devtools::DevToolsSession,
+    js_util,
+    values::{DictionaryValue, StoredValue},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::{atomic::{AtomicU64, Ordering}, Arc},
+};
+
+/// When a user script is run relative to the loading of its document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunAt {
+    /// Run as soon as the document is created, before any page script.
+    DocumentStart,
+    /// Run once the DOM has been parsed (`DOMContentLoaded`).
+    DocumentEnd,
+    /// Run after the document and all of its subresources have loaded.
+    DocumentIdle,
+}
+
+/// Which frames a user script is injected into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFilter {
+    AllFrames,
+    MainFrameOnly,
+}
+
+/// The JavaScript world a user script is executed in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptWorld {
+    /// The page's own world. The script can see and modify page globals.
+    Main,
+    /// A named isolated world sharing the DOM but not the JavaScript globals
+    /// of the page. Scripts using the same name share a world. Page scripts
+    /// can neither see nor overwrite what the script defines, so use this for
+    /// anything that must not be tampered with.
+    Isolated(String),
+}
+
+/// A script to be injected into matching documents.
+#[derive(Debug, Clone)]
+pub struct UserScript {
+    pub source: String,
+    pub run_at: RunAt,
+    pub frames: FrameFilter,
+    pub world: ScriptWorld,
+    /// URL globs the document URL must match, where `*` matches any sequence
+    /// of characters. An empty list matches every URL.
+    pub matches: Vec<String>,
+}
+
+impl UserScript {
+    pub fn new(source: &str) -> UserScript {
+        UserScript {
+            source: source.to_owned(),
+            run_at: RunAt::DocumentStart,
+            frames: FrameFilter::AllFrames,
+            world: ScriptWorld::Main,
+            matches: Vec::new(),
+        }
+    }
+    pub fn run_at(mut self, run_at: RunAt) -> Self {
+        self.run_at = run_at;
+        self
+    }
+    pub fn frames(mut self, frames: FrameFilter) -> Self {
+        self.frames = frames;
+        self
+    }
+    pub fn world(mut self, world: ScriptWorld) -> Self {
+        self.world = world;
+        self
+    }
+    /// Restricts the script to documents whose URL matches `pattern`. May be
+    /// called multiple times.
+    pub fn matches(mut self, pattern: &str) -> Self {
+        self.matches.push(pattern.to_owned());
+        self
+    }
+
+    /// Wraps the script source so that the frame, URL and run-at filters are
+    /// applied inside the document.
+    pub(crate) fn to_injected_source(&self) -> String {
+        let patterns = self.matches.iter()
+            .map(|glob| format!("new RegExp({})", js_util::string_literal(&js_util::glob_to_regex(glob))))
+            .collect::<Vec<_>>()
+            .join(",");
+        let run = match self.run_at {
+            RunAt::DocumentStart => "run();",
+            RunAt::DocumentEnd => "if (document.readyState === 'loading') { document.addEventListener('DOMContentLoaded', run, { once: true }); } else { run(); }",
+            RunAt::DocumentIdle => "if (document.readyState !== 'complete') { window.addEventListener('load', run, { once: true }); } else { run(); }",
+        };
+        format!(
+            "(function() {{\n\
+             if ({main_frame_only} && window.top !== window) return;\n\
+             var patterns = [{patterns}];\n\
+             if (patterns.length && !patterns.some(function(p) {{ return p.test(location.href); }})) return;\n\
+             var run = function() {{\n{source}\n}};\n\
+             {run}\n\
+             }})();",
+            main_frame_only = self.frames == FrameFilter::MainFrameOnly,
+            patterns = patterns,
+            source = self.source,
+            run = run,
+        )
+    }
+}
+
+/// Identifies a script added with [UserScripts::add].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UserScriptId(u64);
+
+static NEXT_SCRIPT_ID: AtomicU64 = AtomicU64::new(1);
+
+enum ScriptState {
+    /// Waiting for the DevTools identifier of the script.
+    Pending,
+    /// Installed with the given DevTools identifier.
+    Installed(String),
+}
+
+/// Manages the user scripts of a single browser.
+#[derive(Clone)]
+pub struct UserScripts {
+    session: DevToolsSession,
+    scripts: Arc<Mutex<HashMap<UserScriptId, ScriptState>>>,
+}
+
+impl UserScripts {
+    pub fn new(host: &BrowserHost) -> UserScripts {
+        Self::with_session(DevToolsSession::new(host))
+    }
+    /// Creates a manager that shares an existing DevTools session.
+    pub fn with_session(session: DevToolsSession) -> UserScripts {
+        UserScripts {
+            session,
+            scripts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    /// Registers `script` for all documents created from now on. Documents that
+    /// are already loaded are not affected.
+    pub fn add(&self, script: &UserScript) -> UserScriptId {
+        let id = UserScriptId(NEXT_SCRIPT_ID.fetch_add(1, Ordering::SeqCst));
+        self.scripts.lock().insert(id, ScriptState::Pending);
+
+        let params = DictionaryValue::new();
+        params.insert_string("source", &script.to_injected_source());
+        if let ScriptWorld::Isolated(name) = &script.world {
+            params.insert_string("worldName", name);
+        }
+        let scripts = self.scripts.clone();
+        let session = self.session.clone();
+        self.session.execute("Page.addScriptToEvaluateOnNewDocument", Some(params), move |result| {
+            let identifier = match result {
+                Ok(StoredValue::Dictionary(result)) => result.get_string("identifier"),
+                _ => {
+                    scripts.lock().remove(&id);
+                    return;
+                }
+            };
+            let mut scripts = scripts.lock();
+            if let Some(state) = scripts.get_mut(&id) {
+                *state = ScriptState::Installed(identifier);
+            } else {
+                // Removed while the call was in flight.
+                drop(scripts);
+                Self::remove_identifier(&session, &identifier);
+            }
+        });
+        id
+    }
+    /// Runs `script` in the documents that are already loaded, which [UserScripts::add]
+    /// leaves alone. Scripts for an isolated world get a world created in
+    /// every frame through `Page.createIsolatedWorld`, so page JavaScript can't
+    /// reach the globals they define.
+    pub fn inject_into_loaded(&self, script: &UserScript) {
+        let source = script.to_injected_source();
+        let name = match &script.world {
+            ScriptWorld::Main => {
+                let browser = self.session.host().get_browser();
+                for id in browser.get_frame_identifiers() {
+                    if let Some(frame) = browser.get_frame_byident(id) {
+                        frame.execute_java_script(&source, "", 0);
+                    }
+                }
+                return;
+            }
+            ScriptWorld::Isolated(name) => name.clone(),
+        };
+        let session = self.session.clone();
+        self.session.execute("Page.getFrameTree", None, move |result| {
+            let tree = match result {
+                Ok(StoredValue::Dictionary(result)) => result.get("frameTree"),
+                _ => return,
+            };
+            let mut frame_ids = Vec::new();
+            collect_frame_ids(&tree, &mut frame_ids);
+            for frame_id in frame_ids {
+                let params = DictionaryValue::new();
+                params.insert_string("frameId", &frame_id);
+                params.insert_string("worldName", &name);
+                let evaluate_session = session.clone();
+                let source = source.clone();
+                session.execute("Page.createIsolatedWorld", Some(params), move |result| {
+                    let context_id = match result {
+                        Ok(StoredValue::Dictionary(result)) => result.get_int("executionContextId"),
+                        _ => return,
+                    };
+                    let params = DictionaryValue::new();
+                    params.insert_string("expression", &source);
+                    params.insert_int("contextId", context_id);
+                    evaluate_session.send("Runtime.evaluate", Some(params));
+                });
+            }
+        });
+    }
+    /// Unregisters a script. Documents that already ran the script are not
+    /// affected.
+    pub fn remove(&self, id: UserScriptId) {
+        if let Some(ScriptState::Installed(identifier)) = self.scripts.lock().remove(&id) {
+            Self::remove_identifier(&self.session, &identifier);
+        }
+    }
+    /// Unregisters all scripts added through this manager.
+    pub fn clear(&self) {
+        let scripts = std::mem::replace(&mut *self.scripts.lock(), HashMap::new());
+        for (_, state) in scripts {
+            if let ScriptState::Installed(identifier) = state {
+                Self::remove_identifier(&self.session, &identifier);
+            }
+        }
+    }
+
+    fn remove_identifier(session: &DevToolsSession, identifier: &str) {
+        let params = DictionaryValue::new();
+        params.insert_string("identifier", identifier);
+        session.send("Page.removeScriptToEvaluateOnNewDocument", Some(params));
+    }
+}
+
+/// Appends the frame identifiers of a `Page.FrameTree` to `frame_ids`.
+fn collect_frame_ids(tree: &StoredValue, frame_ids: &mut Vec<String>) {
+    let tree = match tree {
+        StoredValue::Dictionary(tree) => tree,
+        _ => return,
+    };
+    if let StoredValue::Dictionary(frame) = tree.get("frame") {
+        frame_ids.push(frame.get_string("id"));
+    }
+    if let StoredValue::List(children) = tree.get("childFrames") {
+        for index in 0..children.len() {
+            if let Some(child) = children.get(index) {
+                collect_frame_ids(&child, frame_ids);
+            }
+        }
+    }
+}
+
+static NEXT_STYLE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A user stylesheet added with [Browser::inject_css]. Dropping the handle
+/// leaves the stylesheet in place; call [CssHandle::remove] to take it out.
+pub struct CssHandle {
+    browser: Browser,
+    scripts: UserScripts,
+    script: UserScriptId,
+    element_id: String,
+}
+
+impl CssHandle {
+    pub(crate) fn inject(browser: &Browser, css: &str) -> CssHandle {
+        let element_id = format!("__cef_user_css_{}", NEXT_STYLE_ID.fetch_add(1, Ordering::SeqCst));
+        let source = format!(
+            "(function() {{\n\
+             var insert = function() {{\n\
+             if (document.getElementById({id})) return;\n\
+             var style = document.createElement('style');\n\
+             style.id = {id};\n\
+             style.textContent = {css};\n\
+             (document.head || document.documentElement).appendChild(style);\n\
+             }};\n\
+             if (document.documentElement) {{ insert(); }} else {{ document.addEventListener('readystatechange', insert, {{ once: true }}); }}\n\
+             }})();",
+            id = js_util::string_literal(&element_id),
+            css = js_util::string_literal(css),
+        );
+        let scripts = UserScripts::new(&browser.get_host());
+        let script = scripts.add(&UserScript::new(&source));
+        // The script only applies to new documents, so style the current ones
+        // right away.
+        for frame in Self::frames(browser) {
+            frame.execute_java_script(&source, "", 0);
+        }
+        CssHandle {
+            browser: browser.clone(),
+            scripts,
+            script,
+            element_id,
+        }
+    }
+    /// Removes the stylesheet from all current documents and stops injecting
+    /// it into new ones.
+    pub fn remove(self) {
+        self.scripts.remove(self.script);
+        let source = format!(
+            "(function() {{ var style = document.getElementById({}); if (style) style.remove(); }})();",
+            js_util::string_literal(&self.element_id),
+        );
+        for frame in Self::frames(&self.browser) {
+            frame.execute_java_script(&source, "", 0);
+        }
+    }
+
+    fn frames(browser: &Browser) -> impl Iterator<Item = Frame> + '_ {
+        browser.get_frame_identifiers().into_iter().filter_map(move |id| browser.get_frame_byident(id))
+    }
+}
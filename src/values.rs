@@ -1,10 +1,12 @@
+pub mod compare;
 pub mod iter;
 use self::iter::DictionaryValueKeysIter;
 use cef_sys::{
     cef_binary_value_create, cef_binary_value_t, cef_dictionary_value_create,
-    cef_dictionary_value_t, cef_list_value_create, cef_list_value_t, cef_point_t, cef_range_t,
+    cef_dictionary_value_t, cef_insets_t, cef_list_value_create, cef_list_value_t, cef_point_t, cef_range_t,
     cef_size_t, cef_string_userfree_utf16_free, cef_value_create, cef_value_t, cef_value_type_t,
-    cef_rect_t,
+    cef_rect_t, cef_parse_json, cef_write_json, cef_json_parser_options_t,
+    cef_json_writer_options_t,
 };
 use std::{
     collections::HashMap,
@@ -307,6 +309,35 @@ impl TryFrom<StoredValue> for Value {
     }
 }
 
+impl StoredValue {
+    /// Parses the specified JSON string and returns the resulting value, or
+    /// `None` if `json` could not be parsed.
+    pub fn from_json(json: &str) -> Option<StoredValue> {
+        unsafe {
+            Value::from_ptr(cef_parse_json(
+                CefString::new(json).as_ptr(),
+                cef_json_parser_options_t::JSON_PARSER_RFC,
+            )).map(StoredValue::from)
+        }
+    }
+    /// Parses UTF8-encoded JSON bytes, such as the messages delivered to
+    /// [DevToolsMessageObserverCallbacks](crate::devtools_message_observer::DevToolsMessageObserverCallbacks).
+    pub fn from_json_bytes(json: &[u8]) -> Option<StoredValue> {
+        std::str::from_utf8(json).ok().and_then(StoredValue::from_json)
+    }
+    /// Generates a JSON string from this value. Returns `None` if the value
+    /// cannot be represented as JSON, e.g. if it contains binary data.
+    pub fn to_json(&self) -> Option<String> {
+        let value = Value::try_from(self.clone()).ok()?;
+        unsafe {
+            CefString::from_userfree(cef_write_json(
+                value.into_raw(),
+                cef_json_writer_options_t::JSON_WRITER_DEFAULT,
+            )).map(String::from)
+        }
+    }
+}
+
 ref_counted_ptr! {
     // #[derive(Eq)]
     pub struct BinaryValue(*mut cef_binary_value_t);
@@ -1316,12 +1347,38 @@ impl From<cef_range_t> for Range {
 }
 
 /// Structure representing insets.
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Insets {
-    top: i32,
-    left: i32,
-    bottom: i32,
-    right: i32,
+    pub top: i32,
+    pub left: i32,
+    pub bottom: i32,
+    pub right: i32,
+}
+
+impl Insets {
+    pub fn new(top: i32, left: i32, bottom: i32, right: i32) -> Insets {
+        Insets { top, left, bottom, right }
+    }
+    /// Returns insets of `inset` on every side.
+    pub fn uniform(inset: i32) -> Insets {
+        Insets::new(inset, inset, inset, inset)
+    }
+}
+
+impl From<cef_insets_t> for Insets {
+    fn from(insets: cef_insets_t) -> Insets {
+        Insets::new(insets.top, insets.left, insets.bottom, insets.right)
+    }
+}
+impl From<Insets> for cef_insets_t {
+    fn from(insets: Insets) -> cef_insets_t {
+        cef_insets_t {
+            top: insets.top,
+            left: insets.left,
+            bottom: insets.bottom,
+            right: insets.right,
+        }
+    }
 }
 
 use chrono::{DateTime, NaiveDateTime, NaiveDate, NaiveTime, Utc, Datelike, Timelike};
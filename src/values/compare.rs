@@ -0,0 +1,183 @@
+//! Structural comparison of [StoredValue]s.
+//!
+//! The `PartialEq` implementations of the CEF value types ask CEF, which
+//! doesn't look through [StoredValue] and treats `Invalid` and `Null` as
+//! different types. The functions here recurse into dictionaries and lists,
+//! compare binary data by content and report where two values differ.
+
+use super::*;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+/// A step into a nested value.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, ".{}", key),
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+        }
+    }
+}
+
+/// A difference found by [StoredValue::diff].
+#[derive(Debug, Clone)]
+pub enum PathChange {
+    /// The other value has an entry at `path` that this one lacks.
+    Added { path: Vec<PathSegment>, value: StoredValue },
+    /// This value has an entry at `path` that the other one lacks.
+    Removed { path: Vec<PathSegment>, value: StoredValue },
+    /// Both values have an entry at `path`, but they differ.
+    Changed { path: Vec<PathSegment>, old: StoredValue, new: StoredValue },
+}
+
+impl PathChange {
+    pub fn path(&self) -> &[PathSegment] {
+        match self {
+            PathChange::Added { path, .. } | PathChange::Removed { path, .. } | PathChange::Changed { path, .. } => path,
+        }
+    }
+    /// Returns the path in the form `.key[2].other`, or an empty string for
+    /// the root.
+    pub fn path_string(&self) -> String {
+        self.path().iter().map(PathSegment::to_string).collect()
+    }
+}
+
+impl StoredValue {
+    /// Returns true if both values have the same structure and contents.
+    /// Dictionary order doesn't matter, and NaN equals NaN so a value always
+    /// equals its copy.
+    pub fn deep_eq(&self, other: &StoredValue) -> bool {
+        match (self, other) {
+            (StoredValue::Invalid, StoredValue::Invalid) | (StoredValue::Null, StoredValue::Null) => true,
+            (StoredValue::Bool(a), StoredValue::Bool(b)) => a == b,
+            (StoredValue::Int(a), StoredValue::Int(b)) => a == b,
+            (StoredValue::Double(a), StoredValue::Double(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (StoredValue::String(a), StoredValue::String(b)) => a == b,
+            (StoredValue::Binary(a), StoredValue::Binary(b)) => a.len() == b.len() && a.to_vec() == b.to_vec(),
+            (StoredValue::Dictionary(a), StoredValue::Dictionary(b)) => {
+                a.len() == b.len() && a.into_iter().all(|(key, value)| b.contains_key(&key) && value.deep_eq(&b.get(&key)))
+            }
+            (StoredValue::List(a), StoredValue::List(b)) => {
+                a.len() == b.len() && (0..a.len()).all(|i| match (a.get(i), b.get(i)) {
+                    (Some(a), Some(b)) => a.deep_eq(&b),
+                    (a, b) => a.is_none() && b.is_none(),
+                })
+            }
+            _ => false,
+        }
+    }
+    /// Lists the changes that turn this value into `other`, descending into
+    /// dictionaries and lists. Dictionary keys are visited in sorted order.
+    pub fn diff(&self, other: &StoredValue) -> Vec<PathChange> {
+        let mut changes = Vec::new();
+        diff_into(self, other, &mut Vec::new(), &mut changes);
+        changes
+    }
+    /// Returns a hash of the contents that is equal for values that are
+    /// [deep_eq](StoredValue::deep_eq). It is only stable within one build of
+    /// the application.
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash_structure(&mut hasher);
+        hasher.finish()
+    }
+    /// Feeds the contents into `hasher`, see [StoredValue::structural_hash].
+    pub fn hash_structure<H: Hasher>(&self, hasher: &mut H) {
+        match self {
+            StoredValue::Invalid => 0u8.hash(hasher),
+            StoredValue::Null => 1u8.hash(hasher),
+            StoredValue::Bool(value) => {
+                2u8.hash(hasher);
+                value.hash(hasher);
+            }
+            StoredValue::Int(value) => {
+                3u8.hash(hasher);
+                value.hash(hasher);
+            }
+            StoredValue::Double(value) => {
+                4u8.hash(hasher);
+                // -0.0 equals 0.0 and all NaNs are treated as equal.
+                let bits = if *value == 0.0 {
+                    0
+                } else if value.is_nan() {
+                    std::f64::NAN.to_bits()
+                } else {
+                    value.to_bits()
+                };
+                bits.hash(hasher);
+            }
+            StoredValue::String(value) => {
+                5u8.hash(hasher);
+                value.hash(hasher);
+            }
+            StoredValue::Binary(value) => {
+                6u8.hash(hasher);
+                value.to_vec().hash(hasher);
+            }
+            StoredValue::Dictionary(value) => {
+                7u8.hash(hasher);
+                let mut entries = value.into_iter().collect::<Vec<_>>();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                entries.len().hash(hasher);
+                for (key, value) in entries {
+                    key.hash(hasher);
+                    value.hash_structure(hasher);
+                }
+            }
+            StoredValue::List(value) => {
+                8u8.hash(hasher);
+                value.len().hash(hasher);
+                for i in 0..value.len() {
+                    value.get(i).unwrap_or(StoredValue::Invalid).hash_structure(hasher);
+                }
+            }
+        }
+    }
+}
+
+fn diff_into(old: &StoredValue, new: &StoredValue, path: &mut Vec<PathSegment>, changes: &mut Vec<PathChange>) {
+    match (old, new) {
+        (StoredValue::Dictionary(old), StoredValue::Dictionary(new)) => {
+            let mut keys = old.keys().chain(new.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                path.push(PathSegment::Key(key.clone()));
+                match (old.contains_key(&key), new.contains_key(&key)) {
+                    (true, true) => diff_into(&old.get(&key), &new.get(&key), path, changes),
+                    (true, false) => changes.push(PathChange::Removed { path: path.clone(), value: old.get(&key) }),
+                    (false, true) => changes.push(PathChange::Added { path: path.clone(), value: new.get(&key) }),
+                    (false, false) => (),
+                }
+                path.pop();
+            }
+        }
+        (StoredValue::List(old), StoredValue::List(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                path.push(PathSegment::Index(i));
+                match (old.get(i), new.get(i)) {
+                    (Some(old), Some(new)) => diff_into(&old, &new, path, changes),
+                    (Some(old), None) => changes.push(PathChange::Removed { path: path.clone(), value: old }),
+                    (None, Some(new)) => changes.push(PathChange::Added { path: path.clone(), value: new }),
+                    (None, None) => (),
+                }
+                path.pop();
+            }
+        }
+        (old, new) if !old.deep_eq(new) => changes.push(PathChange::Changed {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => (),
+    }
+}
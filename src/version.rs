@@ -0,0 +1,104 @@
+//! Version information of the loaded CEF binary and runtime capability checks.
+//!
+//! The bindings are generated for one CEF release, but the binary loaded at
+//! runtime may be a different build. [supports] lets applications check for
+//! optional functionality and degrade gracefully instead of running into
+//! missing function pointers.
+
+use std::{ffi::CStr, fmt, os::raw::c_int};
+
+/// Version components of the loaded CEF binary, as returned by [version].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub cef_major: i32,
+    pub cef_minor: i32,
+    pub cef_patch: i32,
+    /// The number of commits in the CEF branch.
+    pub cef_commit_number: i32,
+    /// The git hash of the CEF commit the binary was built from.
+    pub cef_commit_hash: String,
+    pub chrome_major: i32,
+    pub chrome_minor: i32,
+    pub chrome_build: i32,
+    pub chrome_patch: i32,
+    /// Hash of the platform-specific C API.
+    pub api_hash_platform: String,
+    /// Hash of the C API shared by all platforms.
+    pub api_hash_universal: String,
+}
+
+impl fmt::Display for VersionInfo {
+    /// Formats the version the way CEF does, e.g.
+    /// `85.3.12+g3e94ebf+chromium-85.0.4183.121`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}+g{}+chromium-{}.{}.{}.{}",
+            self.cef_major,
+            self.cef_minor,
+            self.cef_patch,
+            self.cef_commit_hash.get(..7).unwrap_or(&self.cef_commit_hash),
+            self.chrome_major,
+            self.chrome_minor,
+            self.chrome_build,
+            self.chrome_patch,
+        )
+    }
+}
+
+/// Returns the version of the loaded CEF binary. On macOS the framework must
+/// be loaded first.
+pub fn version() -> VersionInfo {
+    crate::misc_fns::panic_if_cef_not_loaded();
+    let info = |entry: c_int| unsafe { cef_sys::cef_version_info(entry) };
+    let hash = |entry: c_int| unsafe {
+        let hash = cef_sys::cef_api_hash(entry);
+        if hash.is_null() {
+            String::new()
+        } else {
+            CStr::from_ptr(hash).to_string_lossy().into_owned()
+        }
+    };
+    VersionInfo {
+        cef_major: info(0),
+        cef_minor: info(1),
+        cef_patch: info(2),
+        cef_commit_number: info(3),
+        chrome_major: info(4),
+        chrome_minor: info(5),
+        chrome_build: info(6),
+        chrome_patch: info(7),
+        api_hash_platform: hash(0),
+        api_hash_universal: hash(1),
+        cef_commit_hash: hash(2),
+    }
+}
+
+/// Optional functionality that depends on the platform and the CEF build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Windowless rendering into shared GPU textures, see
+    /// [RenderHandlerCallbacks::on_accelerated_paint](crate::client::render_handler::RenderHandlerCallbacks::on_accelerated_paint).
+    /// Windows and macOS only.
+    SharedTexture,
+    /// Driving windowless rendering with
+    /// [BrowserHost::send_external_begin_frame](crate::browser_host::BrowserHost::send_external_begin_frame).
+    ExternalBeginFrame,
+    /// The Chrome runtime enabled with the "enable-chrome-runtime" switch.
+    /// Experimental in the CEF releases these bindings support.
+    ChromeRuntime,
+    /// Casting through [MediaRouter](crate::media_router::MediaRouter).
+    MediaRouter,
+}
+
+/// Returns true if the loaded CEF binary supports `capability` on this
+/// platform.
+pub fn supports(capability: Capability) -> bool {
+    let version = version();
+    match capability {
+        Capability::SharedTexture => cfg!(any(target_os = "windows", target_os = "macos")) && version.chrome_major >= 75,
+        Capability::ExternalBeginFrame => version.chrome_major >= 75,
+        Capability::ChromeRuntime => version.chrome_major >= 85,
+        Capability::MediaRouter => version.chrome_major >= 85,
+    }
+}
@@ -0,0 +1,909 @@
+//! CEF's views framework: windows, panels and browser views drawn by CEF
+//! itself, with the same look on every platform.
+//!
+//! A [Window] is a [Panel] with a native window around it, and a [Panel] is a
+//! [View] that holds child views arranged by a layout. A [BrowserView] shows a
+//! browser inside a panel. The types that extend another one dereference to
+//! it, so every [Window] method is available on a [Panel] and every [Panel]
+//! method on a [View]:
+//!
+//! ```ignore
+//! struct MainWindow { tabs: Mutex<Option<TabStrip>> }
+//! impl WindowDelegateCallbacks for MainWindow {
+//!     fn on_window_created(&self, window: Window) {
+//!         let tabs = TabStrip::in_panel(&window);
+//!         tabs.create_tab(make_client(&tabs), "https://example.com", &BrowserSettings::new(), None);
+//!         *self.tabs.lock() = Some(tabs);
+//!         window.center_window(Size { width: 1024, height: 768 });
+//!         window.show();
+//!     }
+//! }
+//! let window = Window::new(WindowDelegate::new(MainWindow { tabs: Mutex::new(None) }));
+//! ```
+//!
+//! Views must be created and used on the browser process UI thread, after
+//! CEF was initialized.
+
+use crate::{
+    browser::{Browser, BrowserSettings},
+    client::{
+        accelerators::{Accelerator, AcceleratorRange},
+        context_menu_handler::MenuModel,
+        Client,
+    },
+    color::Color,
+    events::{EventFlags, KeyEvent},
+    layout::BoxLayout,
+    modal_dialog::ModalWindow,
+    refcounted::{RefCountedPtr, Wrapper},
+    request_context::RequestContext,
+    string::CefString,
+    values::{DictionaryValue, Point, Rect, Size, StoredValue},
+};
+use cef_sys::{
+    cef_browser_settings_t, cef_browser_t, cef_browser_view_create, cef_browser_view_delegate_t,
+    cef_browser_view_get_for_browser, cef_browser_view_t, cef_client_t, cef_key_event_t,
+    cef_menu_anchor_position_t, cef_menu_button_create, cef_menu_button_delegate_t,
+    cef_menu_button_pressed_lock_t, cef_menu_button_t, cef_panel_create, cef_point_t,
+    cef_panel_t, cef_string_userfree_utf16_free, cef_string_userfree_t, cef_textfield_create,
+    cef_textfield_delegate_t, cef_textfield_t, cef_view_t, cef_window_create_top_level,
+    cef_window_delegate_t, cef_window_t,
+};
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    os::raw::c_int,
+    ptr::null_mut,
+};
+
+ref_counted_ptr!{
+    /// The base of all views. A view is positioned by the layout of its
+    /// parent [Panel].
+    pub struct View(*mut cef_view_t, base: base);
+}
+
+ref_counted_ptr!{
+    /// A view that holds other views.
+    pub struct Panel(*mut cef_panel_t, base: base.base);
+}
+
+ref_counted_ptr!{
+    /// A top-level native window holding views.
+    pub struct Window(*mut cef_window_t, base: base.base.base);
+}
+
+ref_counted_ptr!{
+    /// A view showing a browser.
+    pub struct BrowserView(*mut cef_browser_view_t, base: base.base);
+}
+
+ref_counted_ptr!{
+    /// A single-line text field.
+    pub struct Textfield(*mut cef_textfield_t, base: base.base);
+}
+
+ref_counted_ptr!{
+    /// A button that shows a menu when pressed.
+    pub struct MenuButton(*mut cef_menu_button_t, base: base.base.base.base);
+}
+
+ref_counted_ptr!{
+    /// Receives the events of a [Window].
+    pub struct WindowDelegate(*mut cef_window_delegate_t, base: base.base.base);
+}
+
+ref_counted_ptr!{
+    /// Receives the events of a [BrowserView].
+    pub struct BrowserViewDelegate(*mut cef_browser_view_delegate_t, base: base.base);
+}
+
+ref_counted_ptr!{
+    /// Receives the events of a [MenuButton].
+    pub struct MenuButtonDelegate(*mut cef_menu_button_delegate_t, base: base.base.base);
+}
+
+ref_counted_ptr!{
+    /// Receives the events of a [Textfield].
+    pub struct TextfieldDelegate(*mut cef_textfield_delegate_t, base: base.base);
+}
+
+// Extending structures start with the structure they extend, and the types
+// are `#[repr(transparent)]` wrappers of pointers to them, so a reference to
+// one is a valid reference to the type it extends.
+macro_rules! extends {
+    ($Struct:ident: $Base:ident) => {
+        impl Deref for $Struct {
+            type Target = $Base;
+            fn deref(&self) -> &$Base {
+                unsafe { &*(self as *const $Struct as *const $Base) }
+            }
+        }
+        impl From<$Struct> for $Base {
+            fn from(view: $Struct) -> $Base {
+                unsafe { $Base::from_ptr_unchecked(view.into_raw() as *mut _) }
+            }
+        }
+    };
+}
+
+extends!(Panel: View);
+extends!(Window: Panel);
+extends!(BrowserView: View);
+extends!(Textfield: View);
+extends!(MenuButton: View);
+
+impl View {
+    /// Returns true if this view is valid.
+    pub fn is_valid(&self) -> bool {
+        self.0
+            .is_valid
+            .map(|is_valid| unsafe { is_valid(self.as_ptr()) != 0 })
+            .unwrap_or(false)
+    }
+    /// Returns true if this view is currently attached to another view. A view
+    /// can only be attached to one parent at a time.
+    pub fn is_attached(&self) -> bool {
+        self.0
+            .is_attached
+            .map(|is_attached| unsafe { is_attached(self.as_ptr()) != 0 })
+            .unwrap_or(false)
+    }
+    /// Returns true if this view is the same object as `that`.
+    pub fn is_same(&self, that: &View) -> bool {
+        self.0
+            .is_same
+            .map(|is_same| unsafe { is_same(self.as_ptr(), that.clone().into_raw()) != 0 })
+            .unwrap_or(false)
+    }
+    /// Returns this view as a [Panel], if it is one.
+    pub fn as_panel(&self) -> Option<Panel> {
+        self.0
+            .as_panel
+            .and_then(|as_panel| unsafe { Panel::from_ptr(as_panel(self.as_ptr())) })
+    }
+    /// Returns this view as a [BrowserView], if it is one.
+    pub fn as_browser_view(&self) -> Option<BrowserView> {
+        self.0
+            .as_browser_view
+            .and_then(|as_browser_view| unsafe { BrowserView::from_ptr(as_browser_view(self.as_ptr())) })
+    }
+    /// Returns this view as a [Textfield], if it is one.
+    pub fn as_textfield(&self) -> Option<Textfield> {
+        self.0
+            .as_textfield
+            .and_then(|as_textfield| unsafe { Textfield::from_ptr(as_textfield(self.as_ptr())) })
+    }
+    /// Returns the window this view belongs to, if it is attached to one.
+    pub fn get_window(&self) -> Option<Window> {
+        self.0
+            .get_window
+            .and_then(|get_window| unsafe { Window::from_ptr(get_window(self.as_ptr())) })
+    }
+    /// Returns the parent of this view, if it is attached to one.
+    pub fn get_parent_view(&self) -> Option<View> {
+        self.0
+            .get_parent_view
+            .and_then(|get_parent_view| unsafe { View::from_ptr(get_parent_view(self.as_ptr())) })
+    }
+    /// Returns the ID of this view, or 0 if none was set.
+    pub fn get_id(&self) -> i32 {
+        self.0
+            .get_id
+            .map(|get_id| unsafe { get_id(self.as_ptr()) })
+            .unwrap_or(0)
+    }
+    /// Sets the ID of this view, which identifies it among the views of its
+    /// window. `id` must be unique within the window and not 0.
+    pub fn set_id(&self, id: i32) {
+        if let Some(set_id) = self.0.set_id {
+            unsafe { set_id(self.as_ptr(), id) }
+        }
+    }
+    /// Returns the bounds of this view in its parent's coordinates.
+    pub fn get_bounds(&self) -> Rect {
+        self.0
+            .get_bounds
+            .map(|get_bounds| unsafe { Rect::from(get_bounds(self.as_ptr())) })
+            .unwrap_or(Rect { x: 0, y: 0, width: 0, height: 0 })
+    }
+    /// Sets the bounds of this view in its parent's coordinates. Layouts
+    /// overwrite them.
+    pub fn set_bounds(&self, bounds: Rect) {
+        let bounds = bounds.into();
+        if let Some(set_bounds) = self.0.set_bounds {
+            unsafe { set_bounds(self.as_ptr(), &bounds) }
+        }
+    }
+    /// Returns the bounds of this view in screen coordinates.
+    pub fn get_bounds_in_screen(&self) -> Rect {
+        self.0
+            .get_bounds_in_screen
+            .map(|get_bounds_in_screen| unsafe { Rect::from(get_bounds_in_screen(self.as_ptr())) })
+            .unwrap_or(Rect { x: 0, y: 0, width: 0, height: 0 })
+    }
+    /// Returns the size this view would like to be given by the layout.
+    pub fn get_preferred_size(&self) -> Size {
+        self.0
+            .get_preferred_size
+            .map(|get_preferred_size| unsafe { Size::from(get_preferred_size(self.as_ptr())) })
+            .unwrap_or(Size { width: 0, height: 0 })
+    }
+    /// Shows or hides this view. Hidden views take no space in layouts.
+    pub fn set_visible(&self, visible: bool) {
+        if let Some(set_visible) = self.0.set_visible {
+            unsafe { set_visible(self.as_ptr(), visible as c_int) }
+        }
+    }
+    /// Returns whether this view is visible. The view may still not be drawn
+    /// if a parent is hidden.
+    pub fn is_visible(&self) -> bool {
+        self.0
+            .is_visible
+            .map(|is_visible| unsafe { is_visible(self.as_ptr()) != 0 })
+            .unwrap_or(false)
+    }
+    /// Enables or disables this view. Disabled views don't receive input.
+    pub fn set_enabled(&self, enabled: bool) {
+        if let Some(set_enabled) = self.0.set_enabled {
+            unsafe { set_enabled(self.as_ptr(), enabled as c_int) }
+        }
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.0
+            .is_enabled
+            .map(|is_enabled| unsafe { is_enabled(self.as_ptr()) != 0 })
+            .unwrap_or(false)
+    }
+    /// Gives focus to this view, if it can take it.
+    pub fn request_focus(&self) {
+        if let Some(request_focus) = self.0.request_focus {
+            unsafe { request_focus(self.as_ptr()) }
+        }
+    }
+    /// Fills the background of this view with `color`.
+    pub fn set_background_color(&self, color: Color) {
+        if let Some(set_background_color) = self.0.set_background_color {
+            unsafe { set_background_color(self.as_ptr(), color.get()) }
+        }
+    }
+}
+
+impl Panel {
+    /// Creates an empty panel.
+    pub fn new() -> Panel {
+        unsafe { Panel::from_ptr_unchecked(cef_panel_create(null_mut())) }
+    }
+    /// Returns this panel as a [Window], if it is one.
+    pub fn as_window(&self) -> Option<Window> {
+        self.0
+            .as_window
+            .and_then(|as_window| unsafe { Window::from_ptr(as_window(self.as_ptr())) })
+    }
+    /// Lays the children out on top of each other, each filling the panel.
+    pub fn set_to_fill_layout(&self) {
+        if let Some(set_to_fill_layout) = self.0.set_to_fill_layout {
+            unsafe {
+                let layout = set_to_fill_layout(self.as_ptr());
+                if let Some(release) = layout.as_ref().and_then(|layout| layout.base.base.release) {
+                    release(&mut (*layout).base.base);
+                }
+            }
+        }
+    }
+    /// Replaces the children with those of `layout` and arranges them in a
+    /// row or column as it describes.
+    pub fn set_to_box_layout(&self, layout: &BoxLayout<View>) {
+        let set_to_box_layout = match self.0.set_to_box_layout {
+            Some(set_to_box_layout) => set_to_box_layout,
+            None => return,
+        };
+        self.remove_all_child_views();
+        let settings = layout.to_cef_settings();
+        unsafe {
+            let box_layout = set_to_box_layout(self.as_ptr(), &settings);
+            for (index, view) in layout.keys().enumerate() {
+                self.add_child_view(view);
+                if let (Some(box_layout), Some(flex)) = (box_layout.as_mut(), layout.flex_of(index)) {
+                    if let Some(set_flex_for_view) = box_layout.set_flex_for_view {
+                        set_flex_for_view(box_layout, view.clone().into_raw(), flex.0 as c_int);
+                    }
+                }
+            }
+            if let Some(release) = box_layout.as_ref().and_then(|box_layout| box_layout.base.base.release) {
+                release(&mut (*box_layout).base.base);
+            }
+        }
+        self.layout();
+    }
+    /// Recomputes the bounds of the children. Happens automatically when the
+    /// panel is resized or children are added or removed.
+    pub fn layout(&self) {
+        if let Some(layout) = self.0.layout {
+            unsafe { layout(self.as_ptr()) }
+        }
+    }
+    /// Adds `view` as the last child.
+    pub fn add_child_view(&self, view: &View) {
+        if let Some(add_child_view) = self.0.add_child_view {
+            unsafe { add_child_view(self.as_ptr(), view.clone().into_raw()) }
+        }
+    }
+    /// Adds `view` as the child at `index`.
+    pub fn add_child_view_at(&self, view: &View, index: usize) {
+        if let Some(add_child_view_at) = self.0.add_child_view_at {
+            unsafe { add_child_view_at(self.as_ptr(), view.clone().into_raw(), index as c_int) }
+        }
+    }
+    /// Removes `view` from the children. It stays alive as long as it is
+    /// referenced, so it can be added to another panel.
+    pub fn remove_child_view(&self, view: &View) {
+        if let Some(remove_child_view) = self.0.remove_child_view {
+            unsafe { remove_child_view(self.as_ptr(), view.clone().into_raw()) }
+        }
+    }
+    pub fn remove_all_child_views(&self) {
+        if let Some(remove_all_child_views) = self.0.remove_all_child_views {
+            unsafe { remove_all_child_views(self.as_ptr()) }
+        }
+    }
+    pub fn get_child_view_count(&self) -> usize {
+        self.0
+            .get_child_view_count
+            .map(|get_child_view_count| unsafe { get_child_view_count(self.as_ptr()) as usize })
+            .unwrap_or(0)
+    }
+    pub fn get_child_view_at(&self, index: usize) -> Option<View> {
+        self.0
+            .get_child_view_at
+            .and_then(|get_child_view_at| unsafe { View::from_ptr(get_child_view_at(self.as_ptr(), index as c_int)) })
+    }
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Window {
+    /// Creates a top-level window. It is hidden until [Window::show] is
+    /// called, usually from [WindowDelegateCallbacks::on_window_created].
+    pub fn new(delegate: WindowDelegate) -> Window {
+        unsafe { Window::from_ptr_unchecked(cef_window_create_top_level(delegate.into_raw())) }
+    }
+    pub fn show(&self) {
+        if let Some(show) = self.0.show {
+            unsafe { show(self.as_ptr()) }
+        }
+    }
+    pub fn hide(&self) {
+        if let Some(hide) = self.0.hide {
+            unsafe { hide(self.as_ptr()) }
+        }
+    }
+    /// Creates a window for `callbacks` as a modal dialog of `parent`. See
+    /// [ModalWindow].
+    pub fn create_as_dialog<C: WindowDelegateCallbacks>(parent: &Window, callbacks: C) -> ModalWindow {
+        ModalWindow::open(parent, callbacks)
+    }
+    /// Sizes the window to `size` and centers it on the current display.
+    pub fn center_window(&self, size: Size) {
+        let size = size.into();
+        if let Some(center_window) = self.0.center_window {
+            unsafe { center_window(self.as_ptr(), &size) }
+        }
+    }
+    /// Closes the window, after asking [WindowDelegateCallbacks::can_close].
+    pub fn close(&self) {
+        if let Some(close) = self.0.close {
+            unsafe { close(self.as_ptr()) }
+        }
+    }
+    pub fn is_closed(&self) -> bool {
+        self.0
+            .is_closed
+            .map(|is_closed| unsafe { is_closed(self.as_ptr()) != 0 })
+            .unwrap_or(false)
+    }
+    /// Brings the window to the front and gives it keyboard focus.
+    pub fn activate(&self) {
+        if let Some(activate) = self.0.activate {
+            unsafe { activate(self.as_ptr()) }
+        }
+    }
+    pub fn is_active(&self) -> bool {
+        self.0
+            .is_active
+            .map(|is_active| unsafe { is_active(self.as_ptr()) != 0 })
+            .unwrap_or(false)
+    }
+    pub fn maximize(&self) {
+        if let Some(maximize) = self.0.maximize {
+            unsafe { maximize(self.as_ptr()) }
+        }
+    }
+    pub fn minimize(&self) {
+        if let Some(minimize) = self.0.minimize {
+            unsafe { minimize(self.as_ptr()) }
+        }
+    }
+    /// Restores the window from the maximized or minimized state.
+    pub fn restore(&self) {
+        if let Some(restore) = self.0.restore {
+            unsafe { restore(self.as_ptr()) }
+        }
+    }
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if let Some(set_fullscreen) = self.0.set_fullscreen {
+            unsafe { set_fullscreen(self.as_ptr(), fullscreen as c_int) }
+        }
+    }
+    pub fn is_fullscreen(&self) -> bool {
+        self.0
+            .is_fullscreen
+            .map(|is_fullscreen| unsafe { is_fullscreen(self.as_ptr()) != 0 })
+            .unwrap_or(false)
+    }
+    pub fn set_title(&self, title: &str) {
+        if let Some(set_title) = self.0.set_title {
+            unsafe { set_title(self.as_ptr(), CefString::new(title).as_ptr()) }
+        }
+    }
+    pub fn get_title(&self) -> String {
+        self.0
+            .get_title
+            .map(|get_title| unsafe { take_string(get_title(self.as_ptr())) })
+            .unwrap_or_default()
+    }
+    /// Runs `command_id` when `accelerator` is pressed in the window, by
+    /// calling [WindowDelegateCallbacks::on_accelerator]. The command key
+    /// isn't supported and [EventFlags::COMMAND_DOWN] is ignored.
+    pub fn set_accelerator(&self, command_id: i32, accelerator: Accelerator) {
+        let modifiers = accelerator.modifiers;
+        if let Some(set_accelerator) = self.0.set_accelerator {
+            unsafe {
+                set_accelerator(
+                    self.as_ptr(),
+                    command_id,
+                    accelerator.key_code.get(),
+                    modifiers.contains(EventFlags::SHIFT_DOWN) as c_int,
+                    modifiers.contains(EventFlags::CONTROL_DOWN) as c_int,
+                    modifiers.contains(EventFlags::ALT_DOWN) as c_int,
+                )
+            }
+        }
+    }
+    /// Sets the accelerators of all commands of `range`.
+    pub fn set_accelerator_range(&self, range: AcceleratorRange) {
+        for (command_id, accelerator) in range.iter() {
+            self.set_accelerator(command_id, accelerator);
+        }
+    }
+    /// Removes the accelerator of `command_id`.
+    pub fn remove_accelerator(&self, command_id: i32) {
+        if let Some(remove_accelerator) = self.0.remove_accelerator {
+            unsafe { remove_accelerator(self.as_ptr(), command_id) }
+        }
+    }
+    pub fn remove_all_accelerators(&self) {
+        if let Some(remove_all_accelerators) = self.0.remove_all_accelerators {
+            unsafe { remove_all_accelerators(self.as_ptr()) }
+        }
+    }
+}
+
+impl BrowserView {
+    /// Creates a view with a new browser of `client` that loads `url`. The
+    /// browser is created once the view was added to a window, and
+    /// [BrowserViewDelegateCallbacks::on_browser_created] is called then.
+    pub fn new(
+        client: Client,
+        url: &str,
+        settings: &BrowserSettings,
+        extra_info: Option<&HashMap<String, StoredValue>>,
+        request_context: Option<RequestContext>,
+        delegate: Option<BrowserViewDelegate>,
+    ) -> Option<BrowserView> {
+        let extra_info = extra_info.map(DictionaryValue::from);
+        unsafe {
+            BrowserView::from_ptr(cef_browser_view_create(
+                client.into_raw(),
+                CefString::new(url).as_ptr(),
+                &settings.into_raw(),
+                extra_info.map(|ei| ei.into_raw()).unwrap_or_else(null_mut),
+                request_context.map(|rc| rc.into_raw()).unwrap_or_else(null_mut),
+                delegate.map(|delegate| delegate.into_raw()).unwrap_or_else(null_mut),
+            ))
+        }
+    }
+    /// Returns the view showing `browser`, or `None` if it wasn't created in a
+    /// view.
+    pub fn get_for_browser(browser: &Browser) -> Option<BrowserView> {
+        unsafe { BrowserView::from_ptr(cef_browser_view_get_for_browser(browser.clone().into_raw())) }
+    }
+    /// Returns the browser, or `None` until it was created.
+    pub fn get_browser(&self) -> Option<Browser> {
+        self.0
+            .get_browser
+            .and_then(|get_browser| unsafe { Browser::from_ptr(get_browser(self.as_ptr())) })
+    }
+    /// Lets the window's accelerators take precedence over the keyboard
+    /// handling of the page.
+    pub fn set_prefer_accelerators(&self, prefer_accelerators: bool) {
+        if let Some(set_prefer_accelerators) = self.0.set_prefer_accelerators {
+            unsafe { set_prefer_accelerators(self.as_ptr(), prefer_accelerators as c_int) }
+        }
+    }
+}
+
+impl Textfield {
+    /// Creates an empty text field.
+    pub fn new(delegate: Option<TextfieldDelegate>) -> Textfield {
+        unsafe { Textfield::from_ptr_unchecked(cef_textfield_create(delegate.map(|delegate| delegate.into_raw()).unwrap_or_else(null_mut))) }
+    }
+    pub fn get_text(&self) -> String {
+        self.0
+            .get_text
+            .map(|get_text| unsafe { take_string(get_text(self.as_ptr())) })
+            .unwrap_or_default()
+    }
+    /// Replaces the text. Doesn't call
+    /// [TextfieldDelegateCallbacks::on_after_user_action].
+    pub fn set_text(&self, text: &str) {
+        if let Some(set_text) = self.0.set_text {
+            unsafe { set_text(self.as_ptr(), CefString::new(text).as_ptr()) }
+        }
+    }
+    pub fn select_all(&self, reversed: bool) {
+        if let Some(select_all) = self.0.select_all {
+            unsafe { select_all(self.as_ptr(), reversed as c_int) }
+        }
+    }
+    pub fn set_read_only(&self, read_only: bool) {
+        if let Some(set_read_only) = self.0.set_read_only {
+            unsafe { set_read_only(self.as_ptr(), read_only as c_int) }
+        }
+    }
+    /// Sets the text shown while the field is empty.
+    pub fn set_placeholder_text(&self, text: &str) {
+        if let Some(set_placeholder_text) = self.0.set_placeholder_text {
+            unsafe { set_placeholder_text(self.as_ptr(), CefString::new(text).as_ptr()) }
+        }
+    }
+}
+
+/// The corner of a menu placed at the point it is shown at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MenuAnchorPosition {
+    TopLeft,
+    TopRight,
+    BottomCenter,
+}
+
+impl MenuButton {
+    /// Creates a button labeled `text`.
+    pub fn new(delegate: MenuButtonDelegate, text: &str) -> MenuButton {
+        unsafe { MenuButton::from_ptr_unchecked(cef_menu_button_create(delegate.into_raw(), CefString::new(text).as_ptr())) }
+    }
+    pub fn set_text(&self, text: &str) {
+        if let Some(set_text) = self.0.base.set_text {
+            unsafe { set_text(&mut (*self.as_ptr()).base, CefString::new(text).as_ptr()) }
+        }
+    }
+    pub fn get_text(&self) -> String {
+        self.0
+            .base
+            .get_text
+            .map(|get_text| unsafe { take_string(get_text(&mut (*self.as_ptr()).base)) })
+            .unwrap_or_default()
+    }
+    /// Shows `menu_model` with its `anchor_position` corner at
+    /// `screen_point`. Call this from
+    /// [MenuButtonDelegateCallbacks::on_menu_button_pressed].
+    pub fn show_menu(&self, menu_model: &MenuModel, screen_point: Point, anchor_position: MenuAnchorPosition) {
+        let screen_point: cef_point_t = screen_point.into();
+        let anchor_position = match anchor_position {
+            MenuAnchorPosition::TopLeft => cef_menu_anchor_position_t::CEF_MENU_ANCHOR_TOPLEFT,
+            MenuAnchorPosition::TopRight => cef_menu_anchor_position_t::CEF_MENU_ANCHOR_TOPRIGHT,
+            MenuAnchorPosition::BottomCenter => cef_menu_anchor_position_t::CEF_MENU_ANCHOR_BOTTOMCENTER,
+        };
+        if let Some(show_menu) = self.0.show_menu {
+            unsafe { show_menu(self.as_ptr(), menu_model.clone().into_raw(), &screen_point, anchor_position) }
+        }
+    }
+    /// Shows the menu as if the button was pressed.
+    pub fn trigger_menu(&self) {
+        if let Some(trigger_menu) = self.0.trigger_menu {
+            unsafe { trigger_menu(self.as_ptr()) }
+        }
+    }
+}
+
+/// Converts a string returned by CEF and frees it.
+unsafe fn take_string(string: cef_string_userfree_t) -> String {
+    let result = CefString::from_ptr(string).map(String::from).unwrap_or_default();
+    if !string.is_null() {
+        cef_string_userfree_utf16_free(string);
+    }
+    result
+}
+
+impl WindowDelegate {
+    pub fn new<C: WindowDelegateCallbacks>(callbacks: C) -> WindowDelegate {
+        unsafe { WindowDelegate::from_ptr_unchecked(WindowDelegateWrapper(Box::new(callbacks)).wrap().into_raw()) }
+    }
+}
+
+/// Implement this trait to handle the events of a [Window].
+pub trait WindowDelegateCallbacks: 'static + Send + Sync {
+    /// Called when `window` is created. Add the views and call
+    /// [Window::show] here.
+    fn on_window_created(&self, window: Window) {}
+    /// Called when `window` is destroyed. Release all references to the
+    /// window and its views.
+    fn on_window_destroyed(&self, window: Window) {}
+    /// Return the parent of `window` to make it a child window, like a
+    /// dialog, that stays on top of the parent. Set `is_menu` to make it a
+    /// menu, and `can_activate_menu` if the menu may take keyboard focus.
+    fn get_parent_window(&self, window: Window, is_menu: &mut bool, can_activate_menu: &mut bool) -> Option<Window> {
+        None
+    }
+    /// Return true to create a window without a frame and title bar.
+    fn is_frameless(&self, window: Window) -> bool {
+        false
+    }
+    fn can_resize(&self, window: Window) -> bool {
+        true
+    }
+    fn can_maximize(&self, window: Window) -> bool {
+        true
+    }
+    fn can_minimize(&self, window: Window) -> bool {
+        true
+    }
+    /// Return false to keep `window` open, e.g. until the browsers in it ran
+    /// their unload handlers.
+    fn can_close(&self, window: Window) -> bool {
+        true
+    }
+    /// Called when the accelerator of `command_id` set with
+    /// [Window::set_accelerator] is pressed. Return true if it was handled.
+    fn on_accelerator(&self, window: Window, command_id: i32) -> bool {
+        false
+    }
+    /// Called for key events no view of `window` handled. Return true if the
+    /// event was handled.
+    fn on_key_event(&self, window: Window, event: KeyEvent) -> bool {
+        false
+    }
+}
+
+struct WindowDelegateWrapper(Box<dyn WindowDelegateCallbacks>);
+
+impl Wrapper for WindowDelegateWrapper {
+    type Cef = cef_window_delegate_t;
+    fn wrap(self) -> RefCountedPtr<Self::Cef> {
+        RefCountedPtr::wrap(
+            cef_window_delegate_t {
+                on_window_created: Some(Self::on_window_created),
+                on_window_destroyed: Some(Self::on_window_destroyed),
+                get_parent_window: Some(Self::get_parent_window),
+                is_frameless: Some(Self::is_frameless),
+                can_resize: Some(Self::can_resize),
+                can_maximize: Some(Self::can_maximize),
+                can_minimize: Some(Self::can_minimize),
+                can_close: Some(Self::can_close),
+                on_accelerator: Some(Self::on_accelerator),
+                on_key_event: Some(Self::on_key_event),
+                ..unsafe { std::mem::zeroed() }
+            },
+            self,
+        )
+    }
+}
+
+cef_callback_impl!{
+    impl for WindowDelegateWrapper: cef_window_delegate_t {
+        fn on_window_created(&self, window: Window: *mut cef_window_t) {
+            self.0.on_window_created(window);
+        }
+        fn on_window_destroyed(&self, window: Window: *mut cef_window_t) {
+            self.0.on_window_destroyed(window);
+        }
+        fn get_parent_window(
+            &self,
+            window: Window: *mut cef_window_t,
+            is_menu: &mut c_int: *mut c_int,
+            can_activate_menu: &mut c_int: *mut c_int,
+        ) -> *mut cef_window_t {
+            let mut menu = *is_menu != 0;
+            let mut can_activate = *can_activate_menu != 0;
+            let parent = self.0.get_parent_window(window, &mut menu, &mut can_activate);
+            *is_menu = menu as c_int;
+            *can_activate_menu = can_activate as c_int;
+            parent.map(|parent| parent.into_raw()).unwrap_or_else(null_mut)
+        }
+        fn is_frameless(&self, window: Window: *mut cef_window_t) -> c_int {
+            self.0.is_frameless(window) as c_int
+        }
+        fn can_resize(&self, window: Window: *mut cef_window_t) -> c_int {
+            self.0.can_resize(window) as c_int
+        }
+        fn can_maximize(&self, window: Window: *mut cef_window_t) -> c_int {
+            self.0.can_maximize(window) as c_int
+        }
+        fn can_minimize(&self, window: Window: *mut cef_window_t) -> c_int {
+            self.0.can_minimize(window) as c_int
+        }
+        fn can_close(&self, window: Window: *mut cef_window_t) -> c_int {
+            self.0.can_close(window) as c_int
+        }
+        fn on_accelerator(&self, window: Window: *mut cef_window_t, command_id: i32: c_int) -> c_int {
+            self.0.on_accelerator(window, command_id) as c_int
+        }
+        fn on_key_event(&self, window: Window: *mut cef_window_t, event: KeyEvent: *const cef_key_event_t) -> c_int {
+            self.0.on_key_event(window, event) as c_int
+        }
+    }
+}
+
+impl BrowserViewDelegate {
+    pub fn new<C: BrowserViewDelegateCallbacks>(callbacks: C) -> BrowserViewDelegate {
+        unsafe { BrowserViewDelegate::from_ptr_unchecked(BrowserViewDelegateWrapper(Box::new(callbacks)).wrap().into_raw()) }
+    }
+}
+
+/// Implement this trait to handle the events of a [BrowserView].
+pub trait BrowserViewDelegateCallbacks: 'static + Send + Sync {
+    /// Called when `browser` was created for `browser_view`.
+    fn on_browser_created(&self, browser_view: BrowserView, browser: Browser) {}
+    /// Called when `browser` of `browser_view` is destroyed. Release all
+    /// references to the browser.
+    fn on_browser_destroyed(&self, browser_view: BrowserView, browser: Browser) {}
+    /// Returns the delegate of the view of a popup opened by the browser of
+    /// `browser_view`.
+    fn get_delegate_for_popup_browser_view(&self, browser_view: BrowserView, is_devtools: bool) -> Option<BrowserViewDelegate> {
+        None
+    }
+    /// Called when the browser of `browser_view` opened a popup shown in
+    /// `popup_browser_view`. Return true after adding the view to a window
+    /// or panel, or false to show it in a window of its own.
+    fn on_popup_browser_view_created(&self, browser_view: BrowserView, popup_browser_view: BrowserView, is_devtools: bool) -> bool {
+        false
+    }
+}
+
+struct BrowserViewDelegateWrapper(Box<dyn BrowserViewDelegateCallbacks>);
+
+impl Wrapper for BrowserViewDelegateWrapper {
+    type Cef = cef_browser_view_delegate_t;
+    fn wrap(self) -> RefCountedPtr<Self::Cef> {
+        RefCountedPtr::wrap(
+            cef_browser_view_delegate_t {
+                on_browser_created: Some(Self::on_browser_created),
+                on_browser_destroyed: Some(Self::on_browser_destroyed),
+                get_delegate_for_popup_browser_view: Some(Self::get_delegate_for_popup_browser_view),
+                on_popup_browser_view_created: Some(Self::on_popup_browser_view_created),
+                ..unsafe { std::mem::zeroed() }
+            },
+            self,
+        )
+    }
+}
+
+cef_callback_impl!{
+    impl for BrowserViewDelegateWrapper: cef_browser_view_delegate_t {
+        fn on_browser_created(&self, browser_view: BrowserView: *mut cef_browser_view_t, browser: Browser: *mut cef_browser_t) {
+            self.0.on_browser_created(browser_view, browser);
+        }
+        fn on_browser_destroyed(&self, browser_view: BrowserView: *mut cef_browser_view_t, browser: Browser: *mut cef_browser_t) {
+            self.0.on_browser_destroyed(browser_view, browser);
+        }
+        fn get_delegate_for_popup_browser_view(
+            &self,
+            browser_view: BrowserView: *mut cef_browser_view_t,
+            _settings: *const cef_browser_settings_t: *const cef_browser_settings_t,
+            _client: *mut cef_client_t: *mut cef_client_t,
+            is_devtools: bool: c_int,
+        ) -> *mut cef_browser_view_delegate_t {
+            self.0
+                .get_delegate_for_popup_browser_view(browser_view, is_devtools)
+                .map(|delegate| delegate.into_raw())
+                .unwrap_or_else(null_mut)
+        }
+        fn on_popup_browser_view_created(
+            &self,
+            browser_view: BrowserView: *mut cef_browser_view_t,
+            popup_browser_view: BrowserView: *mut cef_browser_view_t,
+            is_devtools: bool: c_int,
+        ) -> c_int {
+            self.0.on_popup_browser_view_created(browser_view, popup_browser_view, is_devtools) as c_int
+        }
+    }
+}
+
+impl TextfieldDelegate {
+    pub fn new<C: TextfieldDelegateCallbacks>(callbacks: C) -> TextfieldDelegate {
+        unsafe { TextfieldDelegate::from_ptr_unchecked(TextfieldDelegateWrapper(Box::new(callbacks)).wrap().into_raw()) }
+    }
+}
+
+/// Implement this trait to handle the events of a [Textfield].
+pub trait TextfieldDelegateCallbacks: 'static + Send + Sync {
+    /// Called when `textfield` receives a key event. Return true if the
+    /// event was handled, so the field ignores it.
+    fn on_key_event(&self, textfield: Textfield, event: KeyEvent) -> bool {
+        false
+    }
+    /// Called after the user changed the text or the selection of
+    /// `textfield`.
+    fn on_after_user_action(&self, textfield: Textfield) {}
+}
+
+struct TextfieldDelegateWrapper(Box<dyn TextfieldDelegateCallbacks>);
+
+impl Wrapper for TextfieldDelegateWrapper {
+    type Cef = cef_textfield_delegate_t;
+    fn wrap(self) -> RefCountedPtr<Self::Cef> {
+        RefCountedPtr::wrap(
+            cef_textfield_delegate_t {
+                on_key_event: Some(Self::on_key_event),
+                on_after_user_action: Some(Self::on_after_user_action),
+                ..unsafe { std::mem::zeroed() }
+            },
+            self,
+        )
+    }
+}
+
+cef_callback_impl!{
+    impl for TextfieldDelegateWrapper: cef_textfield_delegate_t {
+        fn on_key_event(&self, textfield: Textfield: *mut cef_textfield_t, event: KeyEvent: *const cef_key_event_t) -> c_int {
+            self.0.on_key_event(textfield, event) as c_int
+        }
+        fn on_after_user_action(&self, textfield: Textfield: *mut cef_textfield_t) {
+            self.0.on_after_user_action(textfield);
+        }
+    }
+}
+
+impl MenuButtonDelegate {
+    pub fn new<C: MenuButtonDelegateCallbacks>(callbacks: C) -> MenuButtonDelegate {
+        unsafe { MenuButtonDelegate::from_ptr_unchecked(MenuButtonDelegateWrapper(Box::new(callbacks)).wrap().into_raw()) }
+    }
+}
+
+/// Implement this trait to handle the events of a [MenuButton].
+pub trait MenuButtonDelegateCallbacks: 'static + Send + Sync {
+    /// Called when `menu_button` was pressed at `screen_point`. Show the menu
+    /// with [MenuButton::show_menu] here.
+    fn on_menu_button_pressed(&self, menu_button: MenuButton, screen_point: Point);
+}
+
+struct MenuButtonDelegateWrapper(Box<dyn MenuButtonDelegateCallbacks>);
+
+impl Wrapper for MenuButtonDelegateWrapper {
+    type Cef = cef_menu_button_delegate_t;
+    fn wrap(self) -> RefCountedPtr<Self::Cef> {
+        RefCountedPtr::wrap(
+            cef_menu_button_delegate_t {
+                on_menu_button_pressed: Some(Self::on_menu_button_pressed),
+                ..unsafe { std::mem::zeroed() }
+            },
+            self,
+        )
+    }
+}
+
+cef_callback_impl!{
+    impl for MenuButtonDelegateWrapper: cef_menu_button_delegate_t {
+        fn on_menu_button_pressed(
+            &self,
+            menu_button: MenuButton: *mut cef_menu_button_t,
+            screen_point: &Point: *const cef_point_t,
+            _button_pressed_lock: *mut cef_menu_button_pressed_lock_t: *mut cef_menu_button_pressed_lock_t,
+        ) {
+            self.0.on_menu_button_pressed(menu_button, *screen_point);
+        }
+    }
+}
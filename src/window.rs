@@ -1,6 +1,15 @@
-use crate::string::CefString;
+use crate::{
+    browser::BrowserSettings,
+    color::Transparency,
+    string::CefString,
+};
 use cef_sys::{cef_window_info_t, cef_window_handle_t};
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use std::fmt;
+
+/// The lowest and highest value CEF accepts for
+/// [BrowserSettings::windowless_frame_rate].
+pub const WINDOWLESS_FRAME_RATE_RANGE: std::ops::RangeInclusive<i32> = 1..=60;
 
 pub struct RawWindow(RawWindowHandle);
 
@@ -86,17 +95,60 @@ pub struct WindowInfo {
     pub windowless_rendering_enabled: bool,
     pub shared_texture_enabled: bool,
     pub external_begin_frame_enabled: bool,
+    /// How a windowed browser's window is first shown. See [ShowState] for
+    /// what each platform supports.
+    pub show_state: ShowState,
+    /// Creates a windowed browser's window without title bar and border, for
+    /// shells that draw their own and mark it with `-webkit-app-region: drag`
+    /// (see [hit_test](crate::hit_test)). Only supported on Windows; on other
+    /// platforms, create a frameless parent window with the windowing library
+    /// and embed the browser as a child. Windowless browsers have no window of
+    /// their own, so this is ignored for them.
+    pub frameless: bool,
     pub platform_specific: PlatformSpecificWindowInfo,
 }
 
+/// The initial state of a browser window.
+///
+/// CEF doesn't carry a show state in its window information, so this maps to
+/// what each platform's window information can express: window styles on
+/// Windows and the `hidden` flag on macOS. [WindowInfo::validate] reports
+/// states a platform can't apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShowState {
+    Normal,
+    /// Created hidden. Supported on Windows and macOS.
+    Hidden,
+    /// Supported on Windows.
+    Minimized,
+    /// Supported on Windows.
+    Maximized,
+    /// A maximized window without frame, covering the taskbar. Supported on
+    /// Windows.
+    Fullscreen,
+}
+
+impl Default for ShowState {
+    fn default() -> Self {
+        ShowState::Normal
+    }
+}
+
 #[cfg(target_os = "windows")]
 use windows::PlatformSpecificWindowInfo;
 #[cfg(target_os = "windows")]
 pub mod windows {
+    use super::ShowState;
     use std::ptr;
-    use winapi::shared::{
-        windef::HMENU,
-        minwindef::DWORD,
+    use winapi::{
+        shared::{
+            windef::HMENU,
+            minwindef::DWORD,
+        },
+        um::winuser::{
+            WS_CAPTION, WS_CHILD, WS_CLIPCHILDREN, WS_CLIPSIBLINGS, WS_MAXIMIZE, WS_MAXIMIZEBOX, WS_MINIMIZE,
+            WS_MINIMIZEBOX, WS_OVERLAPPEDWINDOW, WS_POPUP, WS_SYSMENU, WS_THICKFRAME, WS_VISIBLE,
+        },
     };
     pub struct PlatformSpecificWindowInfo {
         pub style: DWORD,
@@ -113,6 +165,46 @@ pub mod windows {
             }
         }
     }
+
+    /// The style of a top-level browser window when `style` is left at zero.
+    const DEFAULT_STYLE: DWORD = WS_OVERLAPPEDWINDOW | WS_CLIPCHILDREN | WS_CLIPSIBLINGS | WS_VISIBLE;
+    /// The parts of a style that draw a frame.
+    const FRAME_STYLE: DWORD = WS_CAPTION | WS_THICKFRAME | WS_SYSMENU | WS_MINIMIZEBOX | WS_MAXIMIZEBOX;
+
+    /// Applies `show_state` and `frameless` to `style`. Child windows are left
+    /// alone, they are shown by their parent.
+    pub(crate) fn apply_style(style: DWORD, show_state: ShowState, frameless: bool) -> DWORD {
+        if (show_state == ShowState::Normal && !frameless) || style & WS_CHILD != 0 {
+            return style;
+        }
+        let mut style = if style == 0 { DEFAULT_STYLE } else { style };
+        if frameless || show_state == ShowState::Fullscreen {
+            style = (style & !FRAME_STYLE) | WS_POPUP;
+        }
+        match show_state {
+            ShowState::Normal => style,
+            ShowState::Hidden => style & !WS_VISIBLE,
+            ShowState::Minimized => style | WS_MINIMIZE,
+            ShowState::Maximized | ShowState::Fullscreen => style | WS_MAXIMIZE,
+        }
+    }
+
+    /// Recovers the show state and frame from a window style.
+    pub(crate) fn parse_style(style: DWORD) -> (ShowState, bool) {
+        let frameless = style & WS_POPUP != 0 && style & WS_CAPTION == 0;
+        let show_state = if style & WS_CHILD != 0 || style == 0 {
+            ShowState::Normal
+        } else if style & WS_VISIBLE == 0 {
+            ShowState::Hidden
+        } else if style & WS_MINIMIZE != 0 {
+            ShowState::Minimized
+        } else if style & WS_MAXIMIZE != 0 {
+            if frameless { ShowState::Fullscreen } else { ShowState::Maximized }
+        } else {
+            ShowState::Normal
+        };
+        (show_state, frameless && show_state != ShowState::Fullscreen)
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -145,10 +237,113 @@ pub mod macos {
     }
 }
 
+/// A combination of [WindowInfo] and [BrowserSettings] that CEF can't create
+/// a working browser from. Returned by [WindowInfo::validate].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrowserConfigError {
+    /// The browser is windowless, but CEF was initialized without
+    /// [Settings::windowless_rendering_enabled](crate::settings::Settings::windowless_rendering_enabled).
+    WindowlessRenderingDisabled,
+    /// `shared_texture_enabled` or `external_begin_frame_enabled` is set for a
+    /// windowed browser. Both only apply to windowless browsers.
+    WindowlessOptionWithoutWindowless(&'static str),
+    /// `windowless_frame_rate` is outside of [WINDOWLESS_FRAME_RATE_RANGE].
+    FrameRateOutOfRange(i32),
+    /// The alpha of `background_color` is neither fully transparent nor fully
+    /// opaque, which CEF rejects.
+    PartialBackgroundAlpha(u8),
+    /// A window option this platform can't apply, see [ShowState] and
+    /// [WindowInfo::frameless].
+    UnsupportedWindowOption(&'static str),
+}
+
+impl fmt::Display for BrowserConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BrowserConfigError::WindowlessRenderingDisabled => {
+                write!(f, "windowless browser requires Settings::windowless_rendering_enabled")
+            }
+            BrowserConfigError::WindowlessOptionWithoutWindowless(option) => {
+                write!(f, "{} requires a windowless browser", option)
+            }
+            BrowserConfigError::FrameRateOutOfRange(frame_rate) => write!(
+                f,
+                "windowless frame rate {} is outside of {}..={}",
+                frame_rate,
+                WINDOWLESS_FRAME_RATE_RANGE.start(),
+                WINDOWLESS_FRAME_RATE_RANGE.end(),
+            ),
+            BrowserConfigError::PartialBackgroundAlpha(alpha) => {
+                write!(f, "background alpha {:#04x} must be 0x00 or 0xff", alpha)
+            }
+            BrowserConfigError::UnsupportedWindowOption(option) => {
+                write!(f, "{} isn't supported for browser windows on this platform", option)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BrowserConfigError {}
+
 impl WindowInfo {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Checks that a browser can be created from this window information and
+    /// `settings`. Misconfigurations otherwise show up as blank windows or
+    /// crashes inside CEF. Must be called after CEF has been initialized.
+    pub fn validate(&self, settings: &BrowserSettings) -> Result<(), BrowserConfigError> {
+        let transparency = Transparency::from_color(settings.background_color)
+            .ok_or_else(|| BrowserConfigError::PartialBackgroundAlpha(settings.background_color.a()))?;
+        if self.windowless_rendering_enabled {
+            if !crate::misc_fns::windowless_rendering_enabled() {
+                return Err(BrowserConfigError::WindowlessRenderingDisabled);
+            }
+            // Zero selects the default frame rate.
+            let frame_rate = settings.windowless_frame_rate;
+            if !self.external_begin_frame_enabled && frame_rate != 0 && !WINDOWLESS_FRAME_RATE_RANGE.contains(&frame_rate) {
+                return Err(BrowserConfigError::FrameRateOutOfRange(settings.windowless_frame_rate));
+            }
+        } else {
+            if self.shared_texture_enabled {
+                return Err(BrowserConfigError::WindowlessOptionWithoutWindowless("shared_texture_enabled"));
+            }
+            if self.external_begin_frame_enabled {
+                return Err(BrowserConfigError::WindowlessOptionWithoutWindowless("external_begin_frame_enabled"));
+            }
+            // Windowed browsers are painted opaque, using the background
+            // color of the settings instead. The all-zero default color means
+            // unset.
+            if transparency.is_transparent() && settings.background_color.get() != 0 {
+                log::warn!("transparent background of a windowed browser is ignored; transparency requires a windowless browser");
+            }
+            if !cfg!(target_os = "windows") {
+                if self.frameless {
+                    return Err(BrowserConfigError::UnsupportedWindowOption("frameless"));
+                }
+                match self.show_state {
+                    ShowState::Normal => (),
+                    ShowState::Hidden if cfg!(target_os = "macos") => (),
+                    ShowState::Hidden => return Err(BrowserConfigError::UnsupportedWindowOption("ShowState::Hidden")),
+                    ShowState::Minimized => return Err(BrowserConfigError::UnsupportedWindowOption("ShowState::Minimized")),
+                    ShowState::Maximized => return Err(BrowserConfigError::UnsupportedWindowOption("ShowState::Maximized")),
+                    ShowState::Fullscreen => return Err(BrowserConfigError::UnsupportedWindowOption("ShowState::Fullscreen")),
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Returns window information for a windowless (off-screen) browser. This
+    /// is the only kind of browser that supports alpha; set its background with
+    /// [BrowserSettings::set_transparency](crate::browser::BrowserSettings::set_transparency).
+    /// `parent_window` is used for placing context menus and dialogs.
+    pub fn windowless(parent_window: Option<RawWindow>) -> Self {
+        WindowInfo {
+            parent_window,
+            windowless_rendering_enabled: true,
+            ..Self::default()
+        }
+    }
     pub fn into_raw(&self) -> cef_window_info_t {
         self.into()
     }
@@ -156,6 +351,7 @@ impl WindowInfo {
     pub unsafe fn from_raw(info: &cef_window_info_t) -> WindowInfo {
         #[cfg(target_os = "windows")]
         {
+            let (show_state, frameless) = windows::parse_style(info.style);
             WindowInfo {
                 window_name: CefString::from_ptr_unchecked(&info.window_name).into(),
                 x: info.x,
@@ -167,6 +363,8 @@ impl WindowInfo {
                 windowless_rendering_enabled: info.windowless_rendering_enabled != 0,
                 shared_texture_enabled: info.shared_texture_enabled != 0,
                 external_begin_frame_enabled: info.external_begin_frame_enabled != 0,
+                show_state,
+                frameless,
                 platform_specific: PlatformSpecificWindowInfo {
                     menu: info.menu as _,
                     style: info.style,
@@ -188,6 +386,8 @@ impl WindowInfo {
                 windowless_rendering_enabled: info.windowless_rendering_enabled != 0,
                 shared_texture_enabled: info.shared_texture_enabled != 0,
                 external_begin_frame_enabled: info.external_begin_frame_enabled != 0,
+                show_state: ShowState::Normal,
+                frameless: false,
                 platform_specific: PlatformSpecificWindowInfo
             }
         }
@@ -205,6 +405,8 @@ impl WindowInfo {
                 windowless_rendering_enabled: info.windowless_rendering_enabled != 0,
                 shared_texture_enabled: info.shared_texture_enabled != 0,
                 external_begin_frame_enabled: info.external_begin_frame_enabled != 0,
+                show_state: if info.hidden != 0 { ShowState::Hidden } else { ShowState::Normal },
+                frameless: false,
                 platform_specific: PlatformSpecificWindowInfo {
                     hidden: info.hidden,
                 }
@@ -219,7 +421,11 @@ impl<'a> From<&'a WindowInfo> for cef_window_info_t {
         cef_window_info_t {
             ex_style: info.platform_specific.ex_style,
             window_name: CefString::new(&info.window_name).into_raw(),
-            style: info.platform_specific.style,
+            style: if info.windowless_rendering_enabled {
+                info.platform_specific.style
+            } else {
+                windows::apply_style(info.platform_specific.style, info.show_state, info.frameless)
+            },
             x: info.x as _,
             y: info.y as _,
             width: info.width as _,
@@ -261,7 +467,7 @@ impl<'a> From<&'a WindowInfo> for cef_window_info_t {
             y: info.y as _,
             width: info.width as _,
             height: info.height as _,
-            hidden: info.platform_specific.hidden,
+            hidden: (info.platform_specific.hidden != 0 || info.show_state == ShowState::Hidden) as _,
             parent_view: info.parent_window.as_ref().map(|h| h.to_cef_handle()).unwrap_or(std::ptr::null_mut()),
             view: info.window.as_ref().map(|h| h.to_cef_handle()).unwrap_or(std::ptr::null_mut()),
             windowless_rendering_enabled: info.windowless_rendering_enabled as _,
@@ -284,6 +490,8 @@ impl Default for WindowInfo {
             windowless_rendering_enabled: false,
             shared_texture_enabled: false,
             external_begin_frame_enabled: false,
+            show_state: ShowState::Normal,
+            frameless: false,
             platform_specific: PlatformSpecificWindowInfo::default(),
         }
     }